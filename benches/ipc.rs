@@ -0,0 +1,50 @@
+//! JSON vs binary IPC encoding of a 2000-level order book
+//!
+//! Run with `cargo bench --features ipc --bench ipc`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lighter_rs::ws_client::{OrderBook, PriceLevel};
+
+fn book(levels_per_side: usize) -> OrderBook {
+    let level = |price: String, i: usize| PriceLevel {
+        price,
+        size: format!("{}.{:04}", i % 50, i % 10_000),
+    };
+    OrderBook {
+        asks: (0..levels_per_side)
+            .map(|i| level(format!("{}.{:02}", 3000 + i, i % 100), i))
+            .collect(),
+        bids: (0..levels_per_side)
+            .map(|i| level(format!("{}.{:02}", 2999 - i, i % 100), i))
+            .collect(),
+    }
+}
+
+fn encode_decode(c: &mut Criterion) {
+    let book = book(1000);
+    let json = serde_json::to_vec(&book).unwrap();
+    let binary = book.to_ipc_bytes().unwrap();
+    println!(
+        "2000-level book: {} bytes as JSON, {} bytes binary",
+        json.len(),
+        binary.len()
+    );
+
+    let mut group = c.benchmark_group("order_book_2000_levels");
+    group.bench_function("json", |b| {
+        b.iter(|| {
+            let bytes = serde_json::to_vec(black_box(&book)).unwrap();
+            serde_json::from_slice::<OrderBook>(&bytes).unwrap()
+        })
+    });
+    group.bench_function("ipc", |b| {
+        b.iter(|| {
+            let bytes = black_box(&book).to_ipc_bytes().unwrap();
+            OrderBook::from_ipc_bytes(&bytes).unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, encode_decode);
+criterion_main!(benches);
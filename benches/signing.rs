@@ -0,0 +1,117 @@
+//! Signing hot path: request to signed order
+//!
+//! Run with `cargo bench --bench signing`. Before the Criterion groups a
+//! single-threaded baseline of `create_order` calls per second is printed;
+//! allocation budgets are enforced by `tests/signing_allocations.rs`.
+//!
+//! `hash` measures the hasher built into the crate; there is no native
+//! hasher to compare it with yet.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::time::Instant;
+
+use lighter_rs::client::{TxClient, TxRequest};
+use lighter_rs::signer::{PoseidonKeyManager, Signer};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, ChainId, CreateOrderTxReq, OrderInfo, TransactOpts, TxInfo,
+};
+
+const KEY_HEX: &str =
+    "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718";
+const CHAIN_ID: u32 = 304;
+
+fn client() -> TxClient {
+    // No URL: nothing is ever sent
+    TxClient::from_ids(
+        "",
+        KEY_HEX,
+        AccountIndex::new(12345).unwrap(),
+        ApiKeyIndex::new(0).unwrap(),
+        ChainId::new(CHAIN_ID).unwrap(),
+    )
+    .unwrap()
+}
+
+fn request() -> CreateOrderTxReq {
+    CreateOrderTxReq {
+        market_index: 1,
+        client_order_index: 42,
+        base_amount: 15_000,
+        price: 300_000,
+        ..Default::default()
+    }
+}
+
+fn opts() -> TransactOpts {
+    TransactOpts {
+        from_account_index: Some(12345),
+        api_key_index: Some(0),
+        nonce: Some(1),
+        expired_at: 4_000_000_000_000,
+        ..Default::default()
+    }
+}
+
+/// Print how many orders one thread signs per second, as a baseline
+fn print_baseline(runtime: &tokio::runtime::Runtime, client: &TxClient) {
+    const ORDERS: u32 = 20_000;
+    let (req, opts) = (request(), opts());
+    let start = Instant::now();
+    for _ in 0..ORDERS {
+        black_box(
+            runtime
+                .block_on(client.create_order(&req, Some(opts.clone())))
+                .unwrap(),
+        );
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "baseline: {} create_order calls in {:?}, {:.0} orders/s, {:?} per order",
+        ORDERS,
+        elapsed,
+        f64::from(ORDERS) / elapsed.as_secs_f64(),
+        elapsed / ORDERS
+    );
+}
+
+fn signing(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let client = client();
+    print_baseline(&runtime, &client);
+
+    let req = request();
+    let opts = opts();
+    let tx = req.build_tx(&opts).unwrap();
+    let hash = tx.hash(CHAIN_ID).unwrap();
+    let key_manager = PoseidonKeyManager::from_hex(KEY_HEX).unwrap();
+
+    let mut group = c.benchmark_group("signing");
+    group.bench_function("order_info", |b| {
+        b.iter(|| {
+            let info = OrderInfo::from(black_box(&req));
+            let tx = black_box(&req).build_tx(&opts).unwrap();
+            tx.validate().unwrap();
+            (info, tx)
+        })
+    });
+    group.bench_function("hash", |b| {
+        b.iter(|| black_box(&tx).hash(CHAIN_ID).unwrap())
+    });
+    group.bench_function("sign", |b| {
+        b.iter(|| key_manager.sign(black_box(&hash)).unwrap())
+    });
+    group.bench_function("create_order", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(client.create_order(black_box(&req), Some(opts.clone())))
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, signing);
+criterion_main!(benches);
@@ -7,21 +7,22 @@
 use lighter_rs::client::TxClient;
 use lighter_rs::constants::*;
 use lighter_rs::types::{
-    CancelAllOrdersTxReq, CancelOrderTxReq, CreateGroupedOrdersTxReq, CreateOrderTxReq,
-    ModifyOrderTxReq, TransactOpts,
+    AccountIndex, ApiKeyIndex, CancelAllOrdersTxReq, CancelOrderTxReq, ChainId,
+    CreateGroupedOrdersTxReq, CreateOrderTxReq, ExpiryMs, ModifyOrderTxReq, TransactOpts,
 };
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== Lighter RS: Advanced Orders Example ===\n");
 
     // Initialize the transaction client
-    let tx_client = TxClient::new(
+    let tx_client = TxClient::from_ids(
         "", // Offline mode
         "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890",
-        12345,
-        0,
-        1,
+        AccountIndex::new(12345)?,
+        ApiKeyIndex::new(0)?,
+        ChainId::new(1)?,
     )?;
 
     println!("✓ Transaction client initialized\n");
@@ -30,15 +31,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== Canceling Order ===");
     let cancel_req = CancelOrderTxReq {
         market_index: 0,
-        index: 123456,
+        index: 281_474_976_710_656, // exchange-assigned order index
     };
 
     let opts = TransactOpts {
         from_account_index: Some(tx_client.account_index()),
         api_key_index: Some(tx_client.api_key_index()),
-        expired_at: 1000000000,
+        expired_at: ExpiryMs::after(Duration::from_secs(600))?.as_millis(),
         nonce: Some(1),
         dry_run: false,
+        ..Default::default()
     };
 
     let _cancel_tx = tx_client
@@ -53,7 +55,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== Modifying Order ===");
     let modify_req = ModifyOrderTxReq {
         market_index: 0,
-        index: 123456,
+        index: 281_474_976_710_656, // exchange-assigned order index
         base_amount: 2000000,
         price: 105000000,
         trigger_price: 0,
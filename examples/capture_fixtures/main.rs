@@ -0,0 +1,126 @@
+//! Capture fixtures for `tests/wire_compat` from mainnet
+//!
+//! ```bash
+//! cargo run --example capture_fixtures --features wire-capture -- <account_index> [output_dir]
+//! ```
+//!
+//! Records the first order book snapshot and update of market 0, the first
+//! `account_all` snapshot and update of the account, a `nextNonce` body and
+//! the answer to an empty `sendTx`, scrubs them and writes them to
+//! `output_dir` (`tests/wire_compat/fixtures/v1` by default). Frames that
+//! don't arrive within a minute are left out; the account update needs
+//! activity on the account. Review the diff before committing.
+
+mod scrub;
+
+use lighter_rs::client::HTTPClient;
+use lighter_rs::errors::LighterError;
+use lighter_rs::ws_client::{RawWsMessage, WsClient};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use scrub::Scrubber;
+
+const HOST: &str = "mainnet.zklighter.elliot.ai";
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Fixture file of each frame type captured
+const FRAMES: &[(&str, &str)] = &[
+    ("subscribed/order_book", "ws/order_book_subscribed.json"),
+    ("update/order_book", "ws/order_book_update.json"),
+    ("subscribed/account_all", "ws/account_all_subscribed.json"),
+    ("update/account_all", "ws/account_all_update.json"),
+];
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let account_index: i64 = args
+        .next()
+        .ok_or("usage: capture_fixtures <account_index> [output_dir]")?
+        .parse()?;
+    let output = args.next().map_or_else(
+        || PathBuf::from("tests/wire_compat/fixtures/v1"),
+        PathBuf::from,
+    );
+    let mut scrubber = Scrubber::default();
+
+    let http = HTTPClient::new(&format!("https://{}", HOST))?;
+    let nonce = reqwest::get(format!(
+        "{}?account_index={}&api_key_index=0",
+        http.api_url("nextNonce"),
+        account_index
+    ))
+    .await?
+    .text()
+    .await?;
+    write(&output, "rest/next_nonce.json", &nonce, &mut scrubber)?;
+
+    // An empty transaction is refused, which is the response we want
+    let rejected = match http.send_tx(14, "{}").await {
+        Ok(response) => response.raw.unwrap_or_default(),
+        Err(LighterError::TxSubmissionFailed { body, .. }) => body,
+        Err(e) => return Err(e.into()),
+    };
+    write(&output, "rest/send_tx_error.json", &rejected, &mut scrubber)?;
+
+    let (tx, mut rx) = mpsc::channel::<RawWsMessage>(1_000);
+    let client = WsClient::builder()
+        .host(HOST)
+        .order_books(vec![0])
+        .accounts(vec![account_index])
+        .raw_message_tap(tx)
+        .build()?;
+    let capture = async {
+        let mut captured = HashMap::new();
+        while captured.len() < FRAMES.len() {
+            let Some(message) = rx.recv().await else {
+                break;
+            };
+            let parsed: Value = serde_json::from_str(&message.text)?;
+            let Some(kind) = parsed["type"].as_str() else {
+                continue;
+            };
+            if let Some((_, file)) = FRAMES.iter().find(|(frame, _)| *frame == kind) {
+                captured.entry(*file).or_insert(message.text);
+            }
+        }
+        Ok::<_, Box<dyn std::error::Error>>(captured)
+    };
+    let captured = tokio::select! {
+        result = tokio::time::timeout(CAPTURE_TIMEOUT, capture) => result.unwrap_or_else(|_| Ok(HashMap::new()))?,
+        result = client.run(|_, _| {}, |_, _| {}) => {
+            result?;
+            HashMap::new()
+        }
+    };
+
+    for (_, file) in FRAMES {
+        match captured.get(file) {
+            Some(text) => write(&output, file, text, &mut scrubber)?,
+            None => println!("not captured: {}", file),
+        }
+    }
+    Ok(())
+}
+
+/// Scrub a JSON payload and write it pretty-printed
+fn write(
+    dir: &Path,
+    file: &str,
+    text: &str,
+    scrubber: &mut Scrubber,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut value: Value = serde_json::from_str(text)?;
+    scrubber.scrub(&mut value);
+    let path = dir.join(file);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&value)? + "\n")?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
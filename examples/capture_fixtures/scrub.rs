@@ -0,0 +1,138 @@
+//! Removing account numbers, addresses, hashes and balances from captures
+//!
+//! Shared by the capture tool and `tests/wire_compat`, which checks that
+//! every checked-in fixture is already scrubbed. Scrubbing is idempotent.
+
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// First placeholder account number, one above the largest real index
+pub const PLACEHOLDER_ACCOUNT: i64 = 1 << 48;
+
+/// Fields holding account numbers
+const ACCOUNT_FIELDS: &[&str] = &[
+    "account",
+    "account_id",
+    "account_index",
+    "ask_account_id",
+    "bid_account_id",
+    "from_account_index",
+    "owner_account_index",
+    "to_account_index",
+];
+
+/// Fields holding amounts of money the account owns
+const BALANCE_FIELDS: &[&str] = &[
+    "allocated_margin",
+    "available_balance",
+    "collateral",
+    "cross_asset_value",
+    "portfolio_value",
+    "position_value",
+    "realized_pnl",
+    "total_asset_value",
+    "unrealized_pnl",
+];
+
+/// Hex fields that identify the account or its transactions
+const HEX_FIELDS: &[&str] = &["l1_address", "tx_hash"];
+
+/// Scrubs payloads, mapping each account to the same placeholder throughout
+#[derive(Debug, Default)]
+pub struct Scrubber {
+    accounts: HashMap<i64, i64>,
+}
+
+impl Scrubber {
+    /// Scrub `value` in place
+    pub fn scrub(&mut self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    let key = key.as_str();
+                    if ACCOUNT_FIELDS.contains(&key) {
+                        self.scrub_account(field);
+                    } else if BALANCE_FIELDS.contains(&key) {
+                        scrub_balance(field);
+                    } else if HEX_FIELDS.contains(&key) {
+                        scrub_hex(field);
+                    } else if key == "channel" {
+                        self.scrub_channel(field);
+                    } else {
+                        self.scrub(field);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.scrub(item)),
+            _ => {}
+        }
+    }
+
+    fn placeholder(&mut self, account: i64) -> i64 {
+        if !(0..PLACEHOLDER_ACCOUNT).contains(&account) {
+            return account;
+        }
+        let next = PLACEHOLDER_ACCOUNT + self.accounts.len() as i64;
+        *self.accounts.entry(account).or_insert(next)
+    }
+
+    fn scrub_account(&mut self, field: &mut Value) {
+        if let Some(account) = field.as_i64() {
+            *field = Value::from(self.placeholder(account));
+        }
+    }
+
+    /// `account_all:7` and `account_all/7`
+    fn scrub_channel(&mut self, field: &mut Value) {
+        let Some(channel) = field.as_str() else {
+            return;
+        };
+        let Some((kind, id)) = channel.split_once([':', '/']) else {
+            return;
+        };
+        if !kind.starts_with("account") {
+            return;
+        }
+        if let Ok(account) = id.parse::<i64>() {
+            let separator = &channel[kind.len()..kind.len() + 1];
+            *field = Value::from(format!(
+                "{}{}{}",
+                kind,
+                separator,
+                self.placeholder(account)
+            ));
+        }
+    }
+}
+
+/// 1000 with the decimals of the original, so the format is kept
+fn scrub_balance(field: &mut Value) {
+    match field {
+        Value::String(text) => {
+            let decimals = text
+                .split_once('.')
+                .map_or(0, |(_, fraction)| fraction.len());
+            *text = if decimals == 0 {
+                "1000".to_string()
+            } else {
+                format!("1000.{}", "0".repeat(decimals))
+            };
+        }
+        Value::Number(number) => {
+            *field = if number.is_f64() {
+                Value::from(1000.0)
+            } else {
+                Value::from(1000)
+            };
+        }
+        _ => {}
+    }
+}
+
+/// Zero every hex digit, keeping the length and any `0x` prefix
+fn scrub_hex(field: &mut Value) {
+    if let Value::String(text) = field {
+        let (prefix, digits) = text.split_at(if text.starts_with("0x") { 2 } else { 0 });
+        *text = format!("{}{}", prefix, "0".repeat(digits.len()));
+    }
+}
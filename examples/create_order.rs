@@ -1,6 +1,9 @@
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
-use lighter_rs::types::TxInfo;
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, ChainId, MarketIndex, OrderKind, OrderParams, Price,
+    Side,
+};
 use std::env;
 
 #[tokio::main]
@@ -22,28 +25,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let api_url = env::var("LIGHTER_API_URL").expect("LIGHTER_API_URL must be set in .env file");
 
     // Create transaction client
-    let tx_client = TxClient::new(
+    let tx_client = TxClient::from_ids(
         &api_url,
         &private_key,
-        account_index,
-        api_key_index,
-        304, // 304 = Mainnet, 300 = Testnet
+        AccountIndex::new(account_index)?,
+        ApiKeyIndex::new(api_key_index)?,
+        ChainId::new(304)?, // 304 = Mainnet, 300 = Testnet
     )?;
 
-    let market_index = 0u8; // Market 0 = ETH
-    let mid_price = 3000_00; // Price protection for market order
+    let market_index = MarketIndex::new(0)?; // Market 0 = ETH
+    let mid_price = Price::new(300_000)?; // Price protection for market order
 
     println!("Creating market order...");
 
     // Create and submit market order
     match tx_client
-        .create_market_order(
-            market_index,
-            chrono::Utc::now().timestamp_millis(),
-            100_000, // Small size for demo
-            mid_price,
-            0,     // BUY (0 = buy, 1 = sell)
-            false, // not reduce-only
+        .create_order_with(
+            // Small size for demo
+            OrderParams::new(
+                market_index,
+                Side::Buy,
+                BaseAmount::new(100_000)?,
+                mid_price,
+            )
+            .client_order_index(chrono::Utc::now().timestamp_millis()),
+            OrderKind::Market,
             None,
         )
         .await
@@ -0,0 +1,91 @@
+use dotenv::dotenv;
+use lighter_rs::lighter_client::{Credentials, LighterClient, LighterEvent};
+use lighter_rs::network::Network;
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, MarketIndex, OrderParams, Price, Side,
+};
+use std::env;
+use std::time::Duration;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    // Load configuration from environment
+    let private_key =
+        env::var("LIGHTER_API_KEY").expect("LIGHTER_API_KEY must be set in .env file");
+    let account_index: i64 = env::var("LIGHTER_ACCOUNT_INDEX")
+        .expect("LIGHTER_ACCOUNT_INDEX must be set in .env file")
+        .parse()
+        .expect("LIGHTER_ACCOUNT_INDEX must be a valid number");
+    let api_key_index: u8 = env::var("LIGHTER_API_KEY_INDEX")
+        .unwrap_or_else(|_| "0".to_string())
+        .parse()
+        .expect("LIGHTER_API_KEY_INDEX must be a valid number");
+
+    let market_index = MarketIndex::new(0)?; // Market 0 = ETH
+
+    let client = LighterClient::builder(
+        Network::Testnet,
+        Credentials::new(
+            private_key,
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+        ),
+    )
+    .markets(vec![u32::from(market_index.get())])
+    .build()?;
+
+    // Subscribing starts the WebSocket stream
+    let mut events = client.events();
+
+    // Place a resting bid far below the market
+    let placed = client
+        .place_limit(OrderParams::new(
+            market_index,
+            Side::Buy,
+            BaseAmount::new(1_000)?,
+            Price::new(100_000)?,
+        ))
+        .await?;
+    println!(
+        "Order submitted: client_order_index={} tx_hash={:?}",
+        placed.client_order_index, placed.response.tx_hash
+    );
+
+    // Watch the stream until the order shows up, then cancel everything
+    let deadline = tokio::time::sleep(Duration::from_secs(30));
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event? {
+                LighterEvent::OrderBook { market_id, order_book } => {
+                    if let (Some(bid), Some(ask)) = (order_book.bids.first(), order_book.asks.first()) {
+                        println!("Market {}: {} / {}", market_id, bid.price, ask.price);
+                    }
+                }
+                LighterEvent::Account { event, .. } => println!("Account event: {:?}", event),
+                LighterEvent::Disconnected { error } => println!("Disconnected: {:?}", error),
+                LighterEvent::DisconnectCancel { outcome } => println!("Cancel on disconnect: {:?}", outcome),
+                LighterEvent::MarketStatus { change } => println!("Market status: {:?}", change),
+            },
+            _ = &mut deadline => break,
+        }
+
+        if let Some(order) = client.order_tracker().get(placed.client_order_index) {
+            if order.order_index.is_some() {
+                println!("Order is live: {:?}", order);
+                break;
+            }
+        }
+    }
+
+    let response = client.cancel_all().await?;
+    println!(
+        "Cancel all: code={} tx_hash={:?}",
+        response.code, response.tx_hash
+    );
+
+    client.shutdown().await;
+    Ok(())
+}
@@ -5,19 +5,23 @@
 //! Run with: cargo run --example pool_operations
 
 use lighter_rs::client::TxClient;
-use lighter_rs::types::{BurnSharesTxReq, CreatePublicPoolTxReq, MintSharesTxReq, TransactOpts};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BurnSharesTxReq, ChainId, CreatePublicPoolTxReq, ExpiryMs,
+    MintSharesTxReq, TransactOpts,
+};
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== Lighter RS: Pool Operations Example ===\n");
 
     // Initialize the transaction client
-    let tx_client = TxClient::new(
+    let tx_client = TxClient::from_ids(
         "", // Offline mode
         "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890",
-        12345, // Account index
-        0,     // API key index
-        1,     // Chain ID
+        AccountIndex::new(12345)?, // Account index
+        ApiKeyIndex::new(0)?,      // API key index
+        ChainId::new(1)?,          // Chain ID
     )?;
 
     println!("✓ Transaction client initialized\n");
@@ -33,9 +37,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opts = TransactOpts {
         from_account_index: Some(tx_client.account_index()),
         api_key_index: Some(tx_client.api_key_index()),
-        expired_at: 1000000000,
+        expired_at: ExpiryMs::after(Duration::from_secs(600))?.as_millis(),
         nonce: Some(1),
         dry_run: false,
+        ..Default::default()
     };
 
     let _create_pool_tx = tx_client
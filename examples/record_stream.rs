@@ -0,0 +1,49 @@
+//! Record raw WebSocket frames to a newline-delimited JSON file
+//!
+//! The recording can be fed back through `lighter_rs::ws_client::replay_messages`
+//! to reproduce order book state offline.
+
+use lighter_rs::ws_client::{RawWsMessage, WsClient};
+use std::env;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let output = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "stream.ndjson".to_string());
+
+    let (tx, mut rx) = mpsc::channel::<RawWsMessage>(10_000);
+
+    let writer = tokio::spawn(async move {
+        let mut file = File::create(&output).await?;
+        while let Some(message) = rx.recv().await {
+            let mut line = serde_json::to_vec(&message)?;
+            line.push(b'\n');
+            file.write_all(&line).await?;
+        }
+        file.flush().await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+    });
+
+    let client = WsClient::builder()
+        .host("mainnet.zklighter.elliot.ai")
+        .order_books(vec![0])
+        .raw_message_tap(tx)
+        .build()?;
+
+    println!("Recording order book 0... (Ctrl+C to stop)");
+
+    tokio::select! {
+        result = client.run(|_, _| {}, |_, _| {}) => result?,
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    println!("Dropped {} frames", client.raw_tap_dropped());
+    drop(client);
+    writer.await?.map_err(|e| e.to_string())?;
+
+    Ok(())
+}
@@ -17,7 +17,10 @@
 
 use lighter_rs::client::{TxClient, TxResponse};
 use lighter_rs::constants::*;
-use lighter_rs::types::{CancelOrderTxReq, CreateOrderTxReq, TxInfo};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, CancelOrderTxReq, ChainId, CreateOrderTxReq,
+    MarketIndex, OrderKind, OrderParams, Price, Side, TxInfo,
+};
 use std::env;
 
 #[tokio::main]
@@ -51,12 +54,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  API Key Index: {}\n", api_key_index);
 
     // Initialize the transaction client
-    let tx_client = TxClient::new(
+    let tx_client = TxClient::from_ids(
         testnet_url,
         &api_key,
-        account_index,
-        api_key_index,
-        chain_id,
+        AccountIndex::new(account_index)?,
+        ApiKeyIndex::new(api_key_index)?,
+        ChainId::new(chain_id)?,
     )?;
 
     println!("✓ Connected to Lighter Testnet\n");
@@ -116,14 +119,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("═══ Example 2: Creating Market Order ═══");
 
     let market_order = tx_client
-        .create_market_order(
-            0,                                     // market_index
-            chrono::Utc::now().timestamp_millis(), // client_order_index
-            500_000,                               // base_amount (0.5 units)
-            105_000_000,                           // price (max acceptable price for buy)
-            0,                                     // is_ask (BUY)
-            false,                                 // reduce_only
-            None,                                  // opts
+        .create_order_with(
+            // 0.5 units on market 0, max acceptable price for the buy
+            OrderParams::new(
+                MarketIndex::new(0)?,
+                Side::Buy,
+                BaseAmount::new(500_000)?,
+                Price::new(105_000_000)?,
+            )
+            .client_order_index(chrono::Utc::now().timestamp_millis()),
+            OrderKind::Market,
+            None,
         )
         .await?;
 
@@ -143,15 +149,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("═══ Example 3: Creating Stop Loss Order ═══");
 
     let sl_order = tx_client
-        .create_sl_order(
-            0,                                     // market_index
-            chrono::Utc::now().timestamp_millis(), // client_order_index
-            1_000_000,                             // base_amount
-            95_000_000,                            // trigger_price
-            94_000_000,                            // price
-            1,                                     // is_ask (SELL)
-            false,                                 // reduce_only
-            None,                                  // opts
+        .create_order_with(
+            OrderParams::new(
+                MarketIndex::new(0)?,
+                Side::Sell,
+                BaseAmount::new(1_000_000)?,
+                Price::new(94_000_000)?,
+            )
+            .trigger(Price::new(95_000_000)?)
+            .client_order_index(chrono::Utc::now().timestamp_millis()),
+            OrderKind::StopLoss,
+            None,
         )
         .await?;
 
@@ -172,10 +180,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let leverage_tx = tx_client
         .update_leverage_with_multiplier(
-            0,                 // market_index
-            5,                 // 5x leverage
-            MARGIN_MODE_CROSS, // cross margin mode
-            None,              // opts
+            MarketIndex::new(0)?, // market_index
+            5,                    // 5x leverage
+            MARGIN_MODE_CROSS,    // cross margin mode
+            None,                 // opts
         )
         .await?;
 
@@ -194,16 +202,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // ========== Example 5: Cancel Order ==========
     println!("═══ Example 5: Canceling Order ═══");
 
-    // Note: You would typically get the order index from a previous order creation
-    // or from querying active orders. For this example, we'll use the first order's index.
-    let cancel_req = CancelOrderTxReq {
-        market_index: 0,
-        index: order_client_index, // Using client order index from first example
-    };
+    // Cancel the first order by the client order index it was created with.
+    // Use CancelOrderTxReq::by_order_index with an exchange-assigned index
+    // from the active orders endpoint instead if you have one.
+    let cancel_req = CancelOrderTxReq::by_client_order_index(0, order_client_index)?;
 
     println!("Cancel Parameters:");
     println!("  Market Index: {}", cancel_req.market_index);
-    println!("  Order Index: {}", cancel_req.index);
+    println!("  Client Order Index: {}", cancel_req.index);
 
     println!("\nSigning cancel transaction...");
     let cancel_tx = tx_client.cancel_order(&cancel_req, None).await?;
@@ -16,6 +16,10 @@
 //! Run with: cargo run --example trading_bot_simple
 
 use lighter_rs::client::TxClient;
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, ChainId, MarketIndex, OrderKind, OrderParams, Price,
+    Side,
+};
 use lighter_rs::ws_client::{OrderBook, WsClient};
 use serde_json::Value;
 use std::env;
@@ -46,7 +50,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .parse()
         .expect("LIGHTER_ACCOUNT_INDEX must be a valid number");
 
-    let market_index = 0u8; // Trading on market 0 -> ETH
+    let market_index = MarketIndex::new(0)?; // Trading on market 0 -> ETH
+    let order_size = BaseAmount::new(100_000)?; // Small size for demo
     let url = "https://mainnet.zklighter.elliot.ai";
     let url_ws = "mainnet.zklighter.elliot.ai";
 
@@ -56,12 +61,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Mode: Demo (Educational)\n");
 
     // Create trading client
-    let tx_client = Arc::new(TxClient::new(
+    let tx_client = Arc::new(TxClient::from_ids(
         url,
         &api_key,
-        account_index,
-        api_key_index, // api_key_index
-        304,           // 300 Testnet; 304 Mainnet
+        AccountIndex::new(account_index)?,
+        ApiKeyIndex::new(api_key_index)?, // api_key_index
+        ChainId::new(304)?,               // 300 Testnet; 304 Mainnet
     )?);
 
     // Flag to track if we've placed an order
@@ -72,7 +77,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Create WebSocket client
     let ws_client = WsClient::builder()
         .host(url_ws)
-        .order_books(vec![market_index as u32])
+        .order_books(vec![u32::from(market_index.get())])
         .accounts(vec![account_index])
         .build()?;
 
@@ -112,16 +117,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     // Spawn task to place order (non-blocking)
                     tokio::spawn(async move {
                         // Place a small buy order at mid price
-                        let mid_price = ((ask_price + bid_price) / 2.0) as u32;
+                        let mid_price = match Price::new(((ask_price + bid_price) / 2.0) as u32) {
+                            Ok(price) => price,
+                            Err(e) => {
+                                eprintln!("  ✗ Invalid mid price: {}", e);
+                                return;
+                            }
+                        };
 
                         match tx_client
-                            .create_market_order(
-                                market_index,
-                                chrono::Utc::now().timestamp_millis(),
-                                100_000, // Small size for demo
-                                mid_price,
-                                0,     // BUY
-                                false, // not reduce-only
+                            .create_order_with(
+                                OrderParams::new(market_index, Side::Buy, order_size, mid_price)
+                                    .client_order_index(chrono::Utc::now().timestamp_millis()),
+                                OrderKind::Market,
                                 None,
                             )
                             .await
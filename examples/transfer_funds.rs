@@ -5,47 +5,43 @@
 //! Run with: cargo run --example transfer_funds
 
 use lighter_rs::client::TxClient;
-use lighter_rs::types::{TransactOpts, TransferTxReq, TxInfo};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, ChainId, ExpiryMs, TransactOpts, TransferTxReq, TxInfo,
+};
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== Lighter RS: Transfer Funds Example ===\n");
 
     // Initialize the transaction client
-    let tx_client = TxClient::new(
+    let tx_client = TxClient::from_ids(
         "", // Offline mode
         "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890",
-        12345, // From account index
-        0,     // API key index
-        1,     // Chain ID
+        AccountIndex::new(12345)?, // From account index
+        ApiKeyIndex::new(0)?,      // API key index
+        ChainId::new(1)?,          // Chain ID
     )?;
 
     println!("✓ Transaction client initialized");
     println!("  From Account: {}", tx_client.account_index());
 
     // Create a transfer request
-    let transfer_req = TransferTxReq {
-        to_account_index: 54321,
-        usdc_amount: 1000000, // 1 USDC (6 decimals)
-        fee: 1000,            // 0.001 USDC
-        memo: [0u8; 32],
-    };
+    let transfer_req = TransferTxReq::usdc(54321, "1".parse()?, "0.001".parse()?, [0u8; 32])?;
 
     println!("\nTransfer Details:");
-    println!("  To Account: {}", transfer_req.to_account_index);
-    println!(
-        "  Amount: {} USDC",
-        transfer_req.usdc_amount as f64 / 1_000_000.0
-    );
-    println!("  Fee: {} USDC", transfer_req.fee as f64 / 1_000_000.0);
+    println!("  To Account: {}", transfer_req.to_account_index());
+    println!("  Amount: {} USDC", transfer_req.amount());
+    println!("  Fee: {} USDC", transfer_req.fee());
 
     // Create transaction options
     let opts = TransactOpts {
         from_account_index: Some(tx_client.account_index()),
         api_key_index: Some(tx_client.api_key_index()),
-        expired_at: 1000000000,
+        expired_at: ExpiryMs::after(Duration::from_secs(600))?.as_millis(),
         nonce: Some(1),
         dry_run: false,
+        ..Default::default()
     };
 
     // Sign the transaction
@@ -7,11 +7,15 @@
 //! 4. Automatic order placement based on market conditions
 //! 5. Safety mechanisms and error handling
 //!
-//! Circuit Breaker States:
+//! Circuit Breaker States (`lighter_rs::risk::CircuitBreaker`):
 //! - CLOSED: Normal operation, orders can be placed
 //! - OPEN: Too many failures, stop trading temporarily
 //! - HALF_OPEN: Testing if system recovered
 //!
+//! The breaker is attached to the `TxClient`, so every submission is checked
+//! against it and its outcome recorded automatically. Signing failures are
+//! fed in by hand with `record_failure`.
+//!
 //! Setup:
 //! 1. Copy .env.example to .env
 //! 2. Fill in your credentials in .env
@@ -19,84 +23,29 @@
 
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
+use lighter_rs::risk::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, ChainId, MarketIndex, OrderKind, OrderParams, Price,
+    Side,
+};
 use lighter_rs::ws_client::{OrderBook, WsClient};
+use lighter_rs::LighterError;
 use serde_json::Value;
 use std::env;
-use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-
-// Circuit breaker states
-const CIRCUIT_CLOSED: u8 = 0; // Normal operation
-const CIRCUIT_OPEN: u8 = 1; // Too many failures, stop trading
-const CIRCUIT_HALF_OPEN: u8 = 2; // Testing recovery
+use std::time::Duration;
 
 // Circuit breaker configuration
 const MAX_FAILURES: u32 = 3; // Open circuit after 3 failures
 const CIRCUIT_TIMEOUT: Duration = Duration::from_secs(60); // Wait 60s before half-open
 const MIN_SPREAD_BPS: f64 = 5.0; // Minimum spread to trade (5 basis points)
 
-#[derive(Clone)]
-struct CircuitBreaker {
-    state: Arc<AtomicU8>,
-    failure_count: Arc<AtomicU32>,
-    last_failure_time: Arc<tokio::sync::RwLock<Option<Instant>>>,
-}
-
-impl CircuitBreaker {
-    fn new() -> Self {
-        Self {
-            state: Arc::new(AtomicU8::new(CIRCUIT_CLOSED)),
-            failure_count: Arc::new(AtomicU32::new(0)),
-            last_failure_time: Arc::new(tokio::sync::RwLock::new(None)),
-        }
-    }
-
-    fn is_closed(&self) -> bool {
-        self.state.load(Ordering::Relaxed) == CIRCUIT_CLOSED
-    }
-
-    fn is_half_open(&self) -> bool {
-        self.state.load(Ordering::Relaxed) == CIRCUIT_HALF_OPEN
-    }
-
-    async fn record_success(&self) {
-        self.failure_count.store(0, Ordering::Relaxed);
-        self.state.store(CIRCUIT_CLOSED, Ordering::Relaxed);
-        println!("  ✓ Circuit Breaker: SUCCESS - Reset to CLOSED state");
-    }
-
-    async fn record_failure(&self) {
-        let failures = self.failure_count.fetch_add(1, Ordering::Relaxed) + 1;
-        *self.last_failure_time.write().await = Some(Instant::now());
-
-        println!("  ✗ Circuit Breaker: FAILURE {}/{}", failures, MAX_FAILURES);
-
-        if failures >= MAX_FAILURES {
-            self.state.store(CIRCUIT_OPEN, Ordering::Relaxed);
-            println!("  🔴 Circuit Breaker: OPENED (too many failures)");
-            println!("     Will retry in {:?}", CIRCUIT_TIMEOUT);
-        }
-    }
-
-    async fn check_and_update(&self) {
-        if self.state.load(Ordering::Relaxed) == CIRCUIT_OPEN {
-            if let Some(last_failure) = *self.last_failure_time.read().await {
-                if last_failure.elapsed() > CIRCUIT_TIMEOUT {
-                    self.state.store(CIRCUIT_HALF_OPEN, Ordering::Relaxed);
-                    println!("  🟡 Circuit Breaker: HALF_OPEN (testing recovery)");
-                }
-            }
-        }
-    }
-
-    fn state_name(&self) -> &str {
-        match self.state.load(Ordering::Relaxed) {
-            CIRCUIT_CLOSED => "CLOSED",
-            CIRCUIT_OPEN => "OPEN",
-            CIRCUIT_HALF_OPEN => "HALF_OPEN",
-            _ => "UNKNOWN",
-        }
+fn state_name(state: CircuitState) -> &'static str {
+    match state {
+        CircuitState::Closed => "CLOSED",
+        CircuitState::Open => "OPEN",
+        CircuitState::HalfOpen => "HALF_OPEN",
     }
 }
 
@@ -142,20 +91,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Chain ID: {}", chain_id);
     println!();
 
-    // Create trading client
-    let tx_client = Arc::new(TxClient::new(
+    // Create circuit breaker
+    let circuit_breaker = Arc::new(CircuitBreaker::new(CircuitBreakerConfig {
+        failure_threshold: MAX_FAILURES,
+        cool_down: CIRCUIT_TIMEOUT,
+        half_open_probes: 1,
+    })?);
+
+    // Create trading client; every submission goes through the breaker
+    let mut tx_client = TxClient::from_ids(
         &api_url,
         &api_key,
-        account_index,
-        api_key_index,
-        chain_id,
-    )?);
+        AccountIndex::new(account_index)?,
+        ApiKeyIndex::new(api_key_index)?,
+        ChainId::new(chain_id)?,
+    )?;
+    tx_client.set_circuit_breaker(circuit_breaker.clone());
+    let tx_client = Arc::new(tx_client);
 
     println!("✓ Trading client initialized");
 
-    // Create circuit breaker
-    let circuit_breaker = Arc::new(CircuitBreaker::new());
-
     println!("✓ Circuit breaker initialized");
     println!("  Max failures: {}", MAX_FAILURES);
     println!("  Timeout: {:?}", CIRCUIT_TIMEOUT);
@@ -190,11 +145,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let order_count = order_count_clone.clone();
 
         tokio::spawn(async move {
-            // Update circuit breaker state
-            cb.check_and_update().await;
-
-            let state = cb.state_name();
-            println!("📊 Market {} | Circuit: {}", market_id, state);
+            let state = cb.state();
+            println!("📊 Market {} | Circuit: {}", market_id, state_name(state));
 
             if let (Some(best_ask), Some(best_bid)) =
                 (order_book.asks.first(), order_book.bids.first())
@@ -213,7 +165,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("  Spread: {:.4} ({:.2} bps)", spread, spread_bps);
 
                     // Trading logic: Only trade if circuit is closed or half-open
-                    if (cb.is_closed() || cb.is_half_open()) && spread_bps >= MIN_SPREAD_BPS {
+                    if state != CircuitState::Open && spread_bps >= MIN_SPREAD_BPS {
                         let count = order_count.load(Ordering::Relaxed);
 
                         // Limit total orders for demo
@@ -224,24 +176,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             );
                             println!("     Placing order #{}", count + 1);
 
-                            // Place a small market buy order
-                            let result = tx_client
-                                .create_market_order(
-                                    market_id_num,
-                                    chrono::Utc::now().timestamp_millis(),
-                                    100_000,                   // Small size for demo
-                                    (mid_price * 1.01) as u32, // 1% slippage tolerance
-                                    0,                         // BUY
-                                    false,
-                                    None,
+                            // Place a small market buy order, 1% slippage tolerance
+                            let params = Price::new((mid_price * 1.01) as u32).and_then(|price| {
+                                Ok(OrderParams::new(
+                                    MarketIndex::new(market_id_num)?,
+                                    Side::Buy,
+                                    BaseAmount::new(100_000)?, // Small size for demo
+                                    price,
                                 )
-                                .await;
+                                .client_order_index(chrono::Utc::now().timestamp_millis()))
+                            });
+                            let result = match params {
+                                Ok(params) => {
+                                    tx_client
+                                        .create_order_with(params, OrderKind::Market, None)
+                                        .await
+                                }
+                                Err(e) => Err(e),
+                            };
 
                             match result {
                                 Ok(order) => {
                                     println!("     ✓ Order signed (nonce: {})", order.nonce);
 
-                                    // Submit to API
+                                    // Submit to API; the breaker records the outcome
                                     match tx_client.send_transaction(&order).await {
                                         Ok(response) => {
                                             if response.code == 200 {
@@ -249,31 +207,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                                 if let Some(hash) = response.tx_hash {
                                                     println!("       Tx: {}", hash);
                                                 }
-                                                cb.record_success().await;
                                                 order_count.fetch_add(1, Ordering::Relaxed);
                                             } else {
                                                 println!(
                                                     "     ✗ Order rejected: {:?}",
                                                     response.message
                                                 );
-                                                cb.record_failure().await;
                                             }
                                         }
-                                        Err(e) => {
-                                            println!("     ✗ Submit failed: {}", e);
-                                            cb.record_failure().await;
+                                        Err(LighterError::CircuitOpen { retry_after }) => {
+                                            println!(
+                                                "     ⛔ Circuit breaker refused the order, retry after {:?}",
+                                                retry_after
+                                            );
                                         }
+                                        Err(e) => println!("     ✗ Submit failed: {}", e),
                                     }
                                 }
                                 Err(e) => {
                                     println!("     ✗ Order creation failed: {}", e);
-                                    cb.record_failure().await;
+                                    // Not a submission, so fed in by hand
+                                    cb.record_failure();
                                 }
                             }
                         } else {
                             println!("  ⚠ Demo limit reached (3 orders max)");
                         }
-                    } else if !cb.is_closed() && !cb.is_half_open() {
+                    } else if state == CircuitState::Open {
                         println!("  ⛔ Circuit breaker is OPEN - not trading");
                     }
                 }
@@ -361,7 +321,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("║   Trading Bot Stopped                             ║");
     println!("╚═══════════════════════════════════════════════════╝");
     println!("\nOrders placed: {}", order_count.load(Ordering::Relaxed));
-    println!("Circuit state: {}", circuit_breaker.state_name());
+    println!("Circuit state: {}", state_name(circuit_breaker.state()));
 
     Ok(())
 }
@@ -13,6 +13,10 @@
 
 use dotenv::dotenv;
 use lighter_rs::client::TxClient;
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, ChainId, MarketIndex, OrderKind, OrderParams, Price,
+    Side, Usdc,
+};
 use lighter_rs::ws_client::{OrderBook, WsClient};
 use serde_json::Value;
 use std::env;
@@ -53,12 +57,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Chain ID: {}\n", chain_id);
 
     // Create trading client
-    let tx_client = Arc::new(TxClient::new(
+    let tx_client = Arc::new(TxClient::from_ids(
         &api_url,
         &api_key,
-        account_index,
-        0,
-        chain_id,
+        AccountIndex::new(account_index)?,
+        ApiKeyIndex::new(0)?,
+        ChainId::new(chain_id)?,
     )?);
 
     println!("✓ Trading client initialized\n");
@@ -154,27 +158,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 price_change_pct
                             );
 
-                            let is_ask = if price_change_pct > 0.0 { 1 } else { 0 }; // Sell if price up, buy if down
+                            // Sell if price up, buy if down
+                            let side = if price_change_pct > 0.0 {
+                                Side::Sell
+                            } else {
+                                Side::Buy
+                            };
 
                             println!(
                                 "     Action: {} at ${:.4}",
-                                if is_ask == 1 { "SELL" } else { "BUY" },
+                                if side == Side::Sell { "SELL" } else { "BUY" },
                                 mid_price
                             );
 
                             // Create small market order
-                            match tx_client
-                                .create_market_order(
-                                    0,
-                                    chrono::Utc::now().timestamp_millis(),
-                                    50_000, // Very small size
-                                    mid_price as u32,
-                                    is_ask,
-                                    false,
-                                    None,
+                            let params = Price::new(mid_price as u32).and_then(|price| {
+                                Ok(OrderParams::new(
+                                    MarketIndex::new(0)?,
+                                    side,
+                                    BaseAmount::new(50_000)?, // Very small size
+                                    price,
                                 )
-                                .await
-                            {
+                                .client_order_index(chrono::Utc::now().timestamp_millis()))
+                            });
+                            let order = match params {
+                                Ok(params) => {
+                                    tx_client
+                                        .create_order_with(params, OrderKind::Market, None)
+                                        .await
+                                }
+                                Err(e) => Err(e),
+                            };
+                            match order {
                                 Ok(order) => {
                                     println!("     ✓ Order signed (nonce: {})", order.nonce);
 
@@ -212,8 +227,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         if let Some(obj) = account_data.as_object() {
             if let Some(balance) = obj.get("usdc_balance").and_then(|b| b.as_str()) {
-                if let Ok(balance_num) = balance.parse::<f64>() {
-                    println!("  💵 Balance: ${:.2} USDC", balance_num / 1_000_000.0);
+                if let Ok(units) = balance.parse::<i64>() {
+                    println!("  💵 Balance: ${} USDC", Usdc::from_units(units));
                 }
             }
 
@@ -238,10 +253,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
 
             if let Some(pnl) = obj.get("unrealized_pnl").and_then(|p| p.as_str()) {
-                if let Ok(pnl_num) = pnl.parse::<f64>() {
-                    let pnl_usdc = pnl_num / 1_000_000.0;
-                    let emoji = if pnl_usdc >= 0.0 { "💹" } else { "📉" };
-                    println!("  {} Unrealized PnL: ${:.2}", emoji, pnl_usdc);
+                if let Ok(units) = pnl.parse::<i64>() {
+                    let pnl_usdc = Usdc::from_units(units);
+                    let emoji = if pnl_usdc.is_negative() {
+                        "📉"
+                    } else {
+                        "💹"
+                    };
+                    println!("  {} Unrealized PnL: ${}", emoji, pnl_usdc);
                 }
             }
         }
@@ -0,0 +1,481 @@
+//! Threshold alerts on account state
+//!
+//! [`Alerts`] evaluates a list of [`AlertRule`]s on every account snapshot
+//! and, for rules that depend on mark prices, on order book updates. A rule
+//! fires once when its condition starts to hold and is re-armed when the
+//! condition clears; a re-armed rule fires again at most once per cooldown.
+//!
+//! ```no_run
+//! # fn example(ws: &lighter_rs::ws_client::WsClient) {
+//! use std::time::Duration;
+//! use lighter_rs::alerts::{AlertEvent, AlertRule, Alerts};
+//! use lighter_rs::types::Usdc;
+//!
+//! let alerts = Alerts::new(
+//!     vec![AlertRule::BalanceBelow(Usdc::from_units(500_000_000))],
+//!     Duration::from_secs(300),
+//! )
+//! .on_alert(|event| {
+//!     if let AlertEvent::Fired(alert) = event {
+//!         eprintln!("ALERT {:?}: {}", alert.rule, alert.value);
+//!     }
+//! });
+//! let _alerts = alerts.subscribe(ws, 12345);
+//! # }
+//! ```
+
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::book_stats::BookSample;
+use crate::types::Usdc;
+use crate::ws_client::{AccountSnapshot, AccountUpdate, OrderBook, WsClient};
+
+/// Condition on account state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertRule {
+    /// Collateral below the amount
+    BalanceBelow(Usdc),
+    /// Notional of all positions over collateral above the ratio
+    ///
+    /// Holds whenever there are positions and collateral isn't positive.
+    MarginRatioAbove(Decimal),
+    /// Absolute notional of the position in a market above the amount
+    PositionNotionalAbove(u32, Usdc),
+    /// More open orders than this across all markets
+    OpenOrdersAbove(usize),
+}
+
+impl AlertRule {
+    /// Whether the rule needs mark prices
+    pub fn uses_marks(&self) -> bool {
+        matches!(
+            self,
+            AlertRule::MarginRatioAbove(_) | AlertRule::PositionNotionalAbove(..)
+        )
+    }
+
+    /// Whether the condition holds, with the value compared; `None` if the
+    /// snapshot lacks what the rule needs
+    ///
+    /// Notional uses the mark of a market when there is one and the
+    /// position's average entry price otherwise.
+    pub fn evaluate(
+        &self,
+        account: &AccountSnapshot,
+        marks: &HashMap<u32, Decimal>,
+    ) -> Option<(bool, Decimal)> {
+        match *self {
+            AlertRule::BalanceBelow(threshold) => {
+                let collateral = account.collateral?;
+                Some((collateral < threshold.to_decimal(), collateral))
+            }
+            AlertRule::MarginRatioAbove(threshold) => {
+                let collateral = account.collateral?;
+                let notional = total_notional(account, marks)?;
+                if collateral <= Decimal::ZERO {
+                    return Some((notional > Decimal::ZERO, Decimal::MAX));
+                }
+                let ratio = notional / collateral;
+                Some((ratio > threshold, ratio))
+            }
+            AlertRule::PositionNotionalAbove(market_id, threshold) => {
+                let notional = match account.positions.get(&market_id.to_string()) {
+                    Some(position) => position.abs_size() * mark_of(market_id, position, marks)?,
+                    None => Decimal::ZERO,
+                };
+                Some((notional > threshold.to_decimal(), notional))
+            }
+            AlertRule::OpenOrdersAbove(max) => {
+                let open = account
+                    .orders
+                    .values()
+                    .flatten()
+                    .filter(|order| !order.is_terminal())
+                    .count();
+                Some((open > max, Decimal::from(open)))
+            }
+        }
+    }
+}
+
+fn mark_of(
+    market_id: u32,
+    position: &crate::ws_client::AccountPosition,
+    marks: &HashMap<u32, Decimal>,
+) -> Option<Decimal> {
+    marks.get(&market_id).copied().or(position.avg_entry_price)
+}
+
+fn total_notional(account: &AccountSnapshot, marks: &HashMap<u32, Decimal>) -> Option<Decimal> {
+    account
+        .positions
+        .values()
+        .filter(|position| !position.size.is_zero())
+        .map(|position| Some(position.abs_size() * mark_of(position.market_id, position, marks)?))
+        .sum()
+}
+
+/// A rule whose condition started to hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Position of the rule in the list given to [`Alerts::new`]
+    pub rule_index: usize,
+    pub rule: AlertRule,
+    /// Value compared with the threshold, e.g. the collateral
+    pub value: Decimal,
+}
+
+/// Change of a rule's alert state
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertEvent {
+    Fired(Alert),
+    /// The condition of a fired rule cleared
+    Rearmed {
+        rule_index: usize,
+        rule: AlertRule,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RuleState {
+    armed: bool,
+    last_fired: Option<Instant>,
+}
+
+type AlertCallback = dyn Fn(AlertEvent) + Send + Sync;
+
+/// Rules evaluated on account snapshots and book updates
+pub struct Alerts {
+    rules: Vec<AlertRule>,
+    states: Vec<RuleState>,
+    cooldown: Duration,
+    marks: HashMap<u32, Decimal>,
+    last_snapshot: Option<Arc<AccountSnapshot>>,
+    callbacks: Vec<Box<AlertCallback>>,
+    senders: Vec<mpsc::UnboundedSender<AlertEvent>>,
+}
+
+impl std::fmt::Debug for Alerts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Alerts")
+            .field("rules", &self.rules)
+            .field("cooldown", &self.cooldown)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Alerts {
+    /// Armed rules firing at most once per `cooldown` each
+    pub fn new(rules: Vec<AlertRule>, cooldown: Duration) -> Self {
+        let states = vec![
+            RuleState {
+                armed: true,
+                last_fired: None,
+            };
+            rules.len()
+        ];
+        Self {
+            rules,
+            states,
+            cooldown,
+            marks: HashMap::new(),
+            last_snapshot: None,
+            callbacks: Vec::new(),
+            senders: Vec::new(),
+        }
+    }
+
+    /// Call `callback` with every alert event
+    ///
+    /// It runs inline with evaluation, so it should return quickly.
+    pub fn on_alert<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(AlertEvent) + Send + Sync + 'static,
+    {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Receive alert events on a channel
+    pub fn channel(&mut self) -> mpsc::UnboundedReceiver<AlertEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.senders.push(sender);
+        receiver
+    }
+
+    /// Evaluate the rules on updates of `account_index` from `ws`
+    ///
+    /// Book updates of every market are used as marks if a rule needs them.
+    pub fn subscribe(self, ws: &WsClient, account_index: i64) -> Arc<Mutex<Alerts>> {
+        let uses_marks = self.rules.iter().any(AlertRule::uses_marks);
+        let alerts = Arc::new(Mutex::new(self));
+        let on_account = alerts.clone();
+        ws.on_account(account_index, move |_, update| {
+            on_account.lock().unwrap().on_update(&update);
+        });
+        if uses_marks {
+            let on_book = alerts.clone();
+            ws.on_any_order_book(move |market_id, book| {
+                if let Ok(market_id) = market_id.parse() {
+                    on_book.lock().unwrap().on_book(market_id, &book);
+                }
+            });
+        }
+        alerts
+    }
+
+    /// Evaluate a typed account update; events are ignored
+    pub fn on_update(&mut self, update: &AccountUpdate) {
+        if let AccountUpdate::Snapshot(snapshot) = update {
+            self.on_snapshot(snapshot.clone());
+        }
+    }
+
+    /// Evaluate every rule on the account's latest state
+    pub fn on_snapshot(&mut self, snapshot: Arc<AccountSnapshot>) {
+        self.on_snapshot_at(snapshot, Instant::now());
+    }
+
+    /// [`on_snapshot`](Self::on_snapshot) at an explicit time
+    pub fn on_snapshot_at(&mut self, snapshot: Arc<AccountSnapshot>, now: Instant) {
+        self.last_snapshot = Some(snapshot);
+        self.evaluate(now, false);
+    }
+
+    /// Take the mid of `book` as the mark of `market_id` and evaluate the
+    /// rules that use marks
+    pub fn on_book(&mut self, market_id: u32, book: &OrderBook) {
+        self.on_book_at(market_id, book, Instant::now());
+    }
+
+    /// [`on_book`](Self::on_book) at an explicit time
+    pub fn on_book_at(&mut self, market_id: u32, book: &OrderBook, now: Instant) {
+        let Some(mark) = BookSample::from_book(book, chrono::Utc::now())
+            .and_then(|sample| Decimal::from_f64(sample.mid))
+        else {
+            return;
+        };
+        if self.marks.insert(market_id, mark) != Some(mark) {
+            self.evaluate(now, true);
+        }
+    }
+
+    /// Latest mark of a market
+    pub fn mark(&self, market_id: u32) -> Option<Decimal> {
+        self.marks.get(&market_id).copied()
+    }
+
+    /// Whether a rule is armed, i.e. not fired or re-armed since
+    pub fn is_armed(&self, rule_index: usize) -> Option<bool> {
+        self.states.get(rule_index).map(|state| state.armed)
+    }
+
+    fn evaluate(&mut self, now: Instant, marks_only: bool) {
+        let Some(snapshot) = self.last_snapshot.clone() else {
+            return;
+        };
+        for (rule_index, rule) in self.rules.iter().enumerate() {
+            if marks_only && !rule.uses_marks() {
+                continue;
+            }
+            let Some((holds, value)) = rule.evaluate(&snapshot, &self.marks) else {
+                continue;
+            };
+            let state = &mut self.states[rule_index];
+            let event = match (holds, state.armed) {
+                (true, true) => {
+                    let cooled = state
+                        .last_fired
+                        .is_none_or(|at| now.duration_since(at) >= self.cooldown);
+                    if !cooled {
+                        continue;
+                    }
+                    state.armed = false;
+                    state.last_fired = Some(now);
+                    AlertEvent::Fired(Alert {
+                        rule_index,
+                        rule: *rule,
+                        value,
+                    })
+                }
+                (false, false) => {
+                    state.armed = true;
+                    AlertEvent::Rearmed {
+                        rule_index,
+                        rule: *rule,
+                    }
+                }
+                _ => continue,
+            };
+            for callback in &self.callbacks {
+                callback(event.clone());
+            }
+            self.senders
+                .retain(|sender| sender.send(event.clone()).is_ok());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_client::{AccountOrder, AccountPosition, PriceLevel};
+
+    fn snapshot(
+        collateral: &str,
+        position: Option<(&str, &str)>,
+        open_orders: usize,
+    ) -> Arc<AccountSnapshot> {
+        let mut account = AccountSnapshot {
+            collateral: Some(collateral.parse().unwrap()),
+            ..Default::default()
+        };
+        if let Some((size, entry)) = position {
+            account.positions.insert(
+                "1".to_string(),
+                AccountPosition {
+                    market_id: 1,
+                    size: size.parse().unwrap(),
+                    avg_entry_price: Some(entry.parse().unwrap()),
+                    extras: Default::default(),
+                },
+            );
+        }
+        let orders = (0..open_orders)
+            .map(|i| AccountOrder {
+                order_index: i as i64 + 1,
+                client_order_index: 0,
+                market_index: 1,
+                is_ask: false,
+                price: Decimal::ONE,
+                remaining_base_amount: Decimal::ONE,
+                status: Some("open".to_string()),
+                extras: Default::default(),
+            })
+            .collect();
+        account.orders.insert("1".to_string(), orders);
+        Arc::new(account)
+    }
+
+    fn book(bid: &str, ask: &str) -> OrderBook {
+        let level = |price: &str| PriceLevel {
+            price: price.to_string(),
+            size: "1".to_string(),
+        };
+        OrderBook {
+            bids: vec![level(bid)],
+            asks: vec![level(ask)],
+        }
+    }
+
+    fn usdc(value: &str) -> Usdc {
+        value.parse().unwrap()
+    }
+
+    /// Compact form of events: `+i` fired, `-i` re-armed
+    fn drain(receiver: &mut mpsc::UnboundedReceiver<AlertEvent>) -> Vec<String> {
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(match event {
+                AlertEvent::Fired(alert) => format!("+{}", alert.rule_index),
+                AlertEvent::Rearmed { rule_index, .. } => format!("-{}", rule_index),
+            });
+        }
+        events
+    }
+
+    #[test]
+    fn test_fire_and_rearm_sequence() {
+        let mut alerts = Alerts::new(
+            vec![
+                AlertRule::BalanceBelow(usdc("100")),
+                AlertRule::OpenOrdersAbove(2),
+            ],
+            Duration::from_secs(60),
+        );
+        let mut events = alerts.channel();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        alerts.on_snapshot_at(snapshot("150", None, 1), at(0));
+        assert!(drain(&mut events).is_empty());
+
+        // Both cross, then stay crossed without firing again
+        alerts.on_snapshot_at(snapshot("90", None, 3), at(1));
+        assert_eq!(drain(&mut events), vec!["+0", "+1"]);
+        alerts.on_snapshot_at(snapshot("80", None, 4), at(2));
+        assert!(drain(&mut events).is_empty());
+        assert_eq!(alerts.is_armed(0), Some(false));
+
+        // Clearing re-arms; crossing again within the cooldown doesn't fire
+        alerts.on_snapshot_at(snapshot("120", None, 4), at(3));
+        assert_eq!(drain(&mut events), vec!["-0"]);
+        alerts.on_snapshot_at(snapshot("95", None, 2), at(4));
+        assert_eq!(drain(&mut events), vec!["-1"]);
+        assert_eq!(alerts.is_armed(0), Some(true));
+
+        // Still below once the cooldown has passed
+        alerts.on_snapshot_at(snapshot("95", None, 2), at(61));
+        assert_eq!(drain(&mut events), vec!["+0"]);
+        alerts.on_snapshot_at(snapshot("95", None, 3), at(62));
+        assert_eq!(drain(&mut events), vec!["+1"]);
+    }
+
+    #[test]
+    fn test_mark_dependent_rules_follow_books() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let log = fired.clone();
+        let mut alerts = Alerts::new(
+            vec![
+                AlertRule::PositionNotionalAbove(1, usdc("1000")),
+                AlertRule::MarginRatioAbove(Decimal::from(5)),
+            ],
+            Duration::ZERO,
+        )
+        .on_alert(move |event| log.lock().unwrap().push(event));
+        let start = Instant::now();
+
+        // 10 at an entry of 90: notional 900, ratio 4.5
+        alerts.on_snapshot_at(snapshot("200", Some(("10", "90")), 0), start);
+        assert!(fired.lock().unwrap().is_empty());
+
+        // Mark 110: notional 1100, ratio 5.5
+        alerts.on_book_at(1, &book("109", "111"), start);
+        assert_eq!(alerts.mark(1), Some(Decimal::from(110)));
+        let events = fired.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[0],
+            AlertEvent::Fired(Alert { rule_index: 0, value, .. }) if *value == Decimal::from(1100)
+        ));
+
+        // Back to 95 clears both
+        alerts.on_book_at(1, &book("94", "96"), start);
+        let events = fired.lock().unwrap().clone();
+        assert_eq!(
+            events[2..],
+            [
+                AlertEvent::Rearmed {
+                    rule_index: 0,
+                    rule: AlertRule::PositionNotionalAbove(1, usdc("1000")),
+                },
+                AlertEvent::Rearmed {
+                    rule_index: 1,
+                    rule: AlertRule::MarginRatioAbove(Decimal::from(5)),
+                },
+            ]
+        );
+
+        // Non-positive collateral with a position trips the ratio
+        let account = snapshot("0", Some(("1", "95")), 0);
+        assert_eq!(
+            AlertRule::MarginRatioAbove(Decimal::from(5)).evaluate(&account, &HashMap::new()),
+            Some((true, Decimal::MAX))
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Audit trail of order modifications
+//!
+//! With an [`AmendmentLog`] set on a [`TxClient`], every signed modify is
+//! recorded as an [`Amendment`]: the new values, the reason given, and what
+//! changed from the previous values. Previous values come from the caller
+//! through [`ModifyAudit::previous`] or, failing that, from the last
+//! amendment the log holds for the order. Amendments are kept per order and
+//! can be forwarded to an audit sink as they are recorded.
+//!
+//! [`TxClient`]: crate::client::TxClient
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tokio::sync::mpsc;
+
+use crate::types::ModifyOrderTxReq;
+
+/// Amendments kept per order unless configured otherwise
+pub const DEFAULT_AMENDMENTS_PER_ORDER: usize = 32;
+
+/// Price, size and trigger of an order, in the units the exchange uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderValues {
+    pub base_amount: i64,
+    pub price: u32,
+    pub trigger_price: u32,
+}
+
+impl From<&ModifyOrderTxReq> for OrderValues {
+    fn from(req: &ModifyOrderTxReq) -> Self {
+        Self {
+            base_amount: req.base_amount,
+            price: req.price,
+            trigger_price: req.trigger_price,
+        }
+    }
+}
+
+/// Old and new value of a changed field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange<T> {
+    pub old: T,
+    pub new: T,
+}
+
+/// Fields a modify changed; unchanged fields are `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AmendmentDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_amount: Option<FieldChange<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<FieldChange<u32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<FieldChange<u32>>,
+}
+
+impl AmendmentDiff {
+    pub fn between(old: &OrderValues, new: &OrderValues) -> Self {
+        fn change<T: PartialEq + Copy>(old: T, new: T) -> Option<FieldChange<T>> {
+            (old != new).then_some(FieldChange { old, new })
+        }
+        Self {
+            base_amount: change(old.base_amount, new.base_amount),
+            price: change(old.price, new.price),
+            trigger_price: change(old.trigger_price, new.trigger_price),
+        }
+    }
+
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Audit details of one modify
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModifyAudit {
+    /// Values before the modify, if the caller knows them
+    pub previous: Option<OrderValues>,
+    pub reason: Option<String>,
+}
+
+impl ModifyAudit {
+    /// Audit a modify made for `reason`
+    pub fn reason(reason: impl Into<String>) -> Self {
+        Self {
+            previous: None,
+            reason: Some(reason.into()),
+        }
+    }
+
+    /// Set the values the order had before the modify
+    pub fn previous(mut self, previous: OrderValues) -> Self {
+        self.previous = Some(previous);
+        self
+    }
+}
+
+/// A recorded modify
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Amendment {
+    pub market_index: u8,
+    /// Index the modify addressed, an order or client order index
+    pub order_index: i64,
+    pub at: DateTime<Utc>,
+    pub values: OrderValues,
+    /// `None` if the previous values weren't known
+    pub diff: Option<AmendmentDiff>,
+    pub reason: Option<String>,
+    pub tx_hash: Option<String>,
+}
+
+#[derive(Default)]
+struct LogState {
+    orders: HashMap<i64, VecDeque<Amendment>>,
+}
+
+/// The last amendments of each order, oldest first
+pub struct AmendmentLog {
+    per_order: usize,
+    state: Mutex<LogState>,
+    sink: Option<mpsc::UnboundedSender<Amendment>>,
+}
+
+impl Default for AmendmentLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AMENDMENTS_PER_ORDER)
+    }
+}
+
+impl AmendmentLog {
+    /// Keep up to `per_order` amendments of each order
+    pub fn new(per_order: usize) -> Self {
+        Self {
+            per_order: per_order.max(1),
+            state: Mutex::default(),
+            sink: None,
+        }
+    }
+
+    /// Also send every amendment to `sink` as it is recorded
+    pub fn with_sink(mut self, sink: mpsc::UnboundedSender<Amendment>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Record a signed modify and return the amendment
+    pub fn record(
+        &self,
+        req: &ModifyOrderTxReq,
+        audit: ModifyAudit,
+        tx_hash: Option<String>,
+    ) -> Amendment {
+        let values = OrderValues::from(req);
+        let mut state = self.state.lock().unwrap();
+        let history = state.orders.entry(req.index).or_default();
+        let previous = audit
+            .previous
+            .or_else(|| history.back().map(|last| last.values));
+        let amendment = Amendment {
+            market_index: req.market_index,
+            order_index: req.index,
+            at: Utc::now(),
+            values,
+            diff: previous.map(|previous| AmendmentDiff::between(&previous, &values)),
+            reason: audit.reason,
+            tx_hash,
+        };
+        if history.len() == self.per_order {
+            history.pop_front();
+        }
+        history.push_back(amendment.clone());
+        drop(state);
+
+        if let Some(sink) = &self.sink {
+            let _ = sink.send(amendment.clone());
+        }
+        amendment
+    }
+
+    /// Amendments of an order, oldest first
+    pub fn get(&self, order_index: i64) -> Vec<Amendment> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .get(&order_index)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Latest amendment of an order
+    pub fn latest(&self, order_index: i64) -> Option<Amendment> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .get(&order_index)
+            .and_then(|history| history.back().cloned())
+    }
+
+    /// Forget an order, e.g. once it is filled or cancelled
+    pub fn remove(&self, order_index: i64) {
+        self.state.lock().unwrap().orders.remove(&order_index);
+    }
+
+    /// Number of orders with amendments
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(base_amount: i64, price: u32) -> ModifyOrderTxReq {
+        ModifyOrderTxReq {
+            market_index: 2,
+            index: 77,
+            base_amount,
+            price,
+            trigger_price: 0,
+        }
+    }
+
+    #[test]
+    fn test_log_keeps_the_last_amendments() {
+        let log = AmendmentLog::new(2);
+        let first = log.record(&req(10, 100), ModifyAudit::default(), None);
+        assert_eq!(first.diff, None);
+
+        for price in [101, 102] {
+            log.record(&req(10, price), ModifyAudit::default(), None);
+        }
+        let kept = log.get(77);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].values.price, 101);
+        assert_eq!(log.latest(77).unwrap().values.price, 102);
+        assert!(log.get(78).is_empty());
+
+        log.remove(77);
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_diff_lists_changed_fields() {
+        let old = OrderValues::from(&req(10, 100));
+        let diff = AmendmentDiff::between(&old, &OrderValues::from(&req(10, 105)));
+        assert_eq!(
+            serde_json::to_value(diff).unwrap(),
+            serde_json::json!({"price": {"old": 100, "new": 105}})
+        );
+        assert!(AmendmentDiff::between(&old, &old).is_empty());
+    }
+}
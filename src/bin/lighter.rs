@@ -0,0 +1,504 @@
+//! `lighter` command line tool
+//!
+//! Signs and submits common transactions and inspects market data.
+//! Credentials come from the environment (or a `.env` file):
+//! `LIGHTER_API_KEY`, `LIGHTER_ACCOUNT_INDEX`, `LIGHTER_API_KEY_INDEX`,
+//! `LIGHTER_API_URL` and `LIGHTER_CHAIN_ID`. Every command prints JSON.
+//!
+//! Without an API URL the tool works offline: transactions are signed and
+//! printed but not sent, and `--nonce` must be given.
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use lighter_rs::client::{HTTPClient, TxClient, TxResponse};
+use lighter_rs::constants::CANCEL_ALL_IMMEDIATE;
+use lighter_rs::errors::{LighterError, Result};
+use lighter_rs::types::*;
+use lighter_rs::utils::checked_transfer_amount;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "lighter",
+    version,
+    about = "Command line tool for the Lighter API"
+)]
+struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Args)]
+struct GlobalArgs {
+    /// Hex-encoded API private key
+    #[arg(long, env = "LIGHTER_API_KEY", hide_env_values = true, global = true)]
+    api_key: Option<String>,
+    #[arg(long, env = "LIGHTER_ACCOUNT_INDEX", global = true)]
+    account_index: Option<i64>,
+    #[arg(
+        long,
+        env = "LIGHTER_API_KEY_INDEX",
+        default_value_t = 0,
+        global = true
+    )]
+    api_key_index: u8,
+    /// REST base URL; leave unset to work offline
+    #[arg(long, env = "LIGHTER_API_URL", global = true)]
+    api_url: Option<String>,
+    /// 304 for mainnet, 300 for testnet
+    #[arg(long, env = "LIGHTER_CHAIN_ID", default_value_t = 304, global = true)]
+    chain_id: u32,
+    /// Nonce to sign with instead of asking the API
+    #[arg(long, global = true)]
+    nonce: Option<i64>,
+    /// Sign and print transactions without sending them
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Cancel every open order of the account
+    CancelAll,
+    /// Order commands
+    #[command(subcommand)]
+    Order(OrderCommand),
+    /// Transfer USDC to another account
+    Transfer(TransferArgs),
+    /// Show the resting orders of a market
+    Book {
+        #[arg(long)]
+        market: u32,
+        /// Price levels per side
+        #[arg(long, default_value_t = 10)]
+        depth: u32,
+    },
+    /// Show the next nonce of the API key
+    Nonce,
+    /// Sign a transaction request read from a JSON file
+    Sign {
+        #[arg(long)]
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum OrderCommand {
+    /// Create an order from human-readable size and price
+    Create(CreateOrderArgs),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CliSide {
+    Buy,
+    Sell,
+}
+
+impl From<CliSide> for Side {
+    fn from(side: CliSide) -> Self {
+        match side {
+            CliSide::Buy => Side::Buy,
+            CliSide::Sell => Side::Sell,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CliOrderKind {
+    Limit,
+    Market,
+}
+
+impl From<CliOrderKind> for OrderKind {
+    fn from(kind: CliOrderKind) -> Self {
+        match kind {
+            CliOrderKind::Limit => OrderKind::Limit,
+            CliOrderKind::Market => OrderKind::Market,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct CreateOrderArgs {
+    #[arg(long)]
+    market: u8,
+    #[arg(long, value_enum)]
+    side: CliSide,
+    /// Size in base asset units, e.g. 0.1
+    #[arg(long)]
+    size: Decimal,
+    /// Price in quote units, e.g. 3000
+    #[arg(long)]
+    price: Decimal,
+    /// Size precision of the market
+    #[arg(long)]
+    size_decimals: u32,
+    /// Price precision of the market
+    #[arg(long)]
+    price_decimals: u32,
+    #[arg(long, value_enum, default_value_t = CliOrderKind::Limit)]
+    kind: CliOrderKind,
+    #[arg(long)]
+    client_order_index: Option<i64>,
+    #[arg(long)]
+    reduce_only: bool,
+}
+
+#[derive(Debug, Args)]
+struct TransferArgs {
+    /// Receiving account index
+    #[arg(long)]
+    to: i64,
+    /// Amount in USDC, e.g. 25.5
+    #[arg(long)]
+    amount: Decimal,
+    /// Fee in USDC protocol units; quoted from the API when omitted
+    #[arg(long, conflicts_with = "max_fee")]
+    fee: Option<i64>,
+    /// Highest quoted fee in USDC to accept
+    #[arg(long, default_value = "1")]
+    max_fee: Decimal,
+}
+
+/// Transaction request accepted by `lighter sign`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", content = "request", rename_all = "snake_case")]
+enum SignRequest {
+    CreateOrder(CreateOrderTxReq),
+    CancelOrder(CancelOrderTxReq),
+    CancelAll(CancelAllOrdersTxReq),
+    Transfer(TransferTxReq),
+    Withdraw(WithdrawTxReq),
+}
+
+impl GlobalArgs {
+    fn tx_client(&self) -> Result<TxClient> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or_else(|| LighterError::MissingField("LIGHTER_API_KEY".to_string()))?;
+        let account_index = self
+            .account_index
+            .ok_or_else(|| LighterError::MissingField("LIGHTER_ACCOUNT_INDEX".to_string()))?;
+        TxClient::from_ids(
+            self.api_url.as_deref().unwrap_or(""),
+            api_key,
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(self.api_key_index)?,
+            ChainId::new(self.chain_id)?,
+        )
+    }
+
+    fn http_client(&self) -> Result<HTTPClient> {
+        let api_url = self
+            .api_url
+            .as_deref()
+            .ok_or_else(|| LighterError::MissingField("LIGHTER_API_URL".to_string()))?;
+        HTTPClient::new(api_url)
+    }
+
+    fn opts(&self) -> Option<TransactOpts> {
+        self.nonce.map(|nonce| TransactOpts {
+            nonce: Some(nonce),
+            ..Default::default()
+        })
+    }
+
+    fn submits(&self) -> bool {
+        !self.dry_run && self.api_url.is_some()
+    }
+}
+
+/// JSON output for a signed transaction, with the API response if it was sent
+async fn finish<T: TxInfo>(client: &TxClient, global: &GlobalArgs, tx: T) -> Result<Value> {
+    let mut output = json!({
+        "tx_type": tx.get_tx_type(),
+        "tx_info": serde_json::from_str::<Value>(&tx.get_tx_info()?)?,
+        "tx_hash": tx.get_tx_hash(),
+    });
+    if global.submits() {
+        output["response"] = response_json(&client.send_transaction(&tx).await?);
+    }
+    Ok(output)
+}
+
+fn response_json(response: &TxResponse) -> Value {
+    json!({
+        "code": response.code,
+        "tx_hash": response.tx_hash,
+        "message": response.message,
+    })
+}
+
+async fn run(cli: Cli) -> Result<Value> {
+    let global = &cli.global;
+    match cli.command {
+        Command::CancelAll => {
+            let client = global.tx_client()?;
+            let req = CancelAllOrdersTxReq {
+                time_in_force: CANCEL_ALL_IMMEDIATE,
+                time: 0,
+            };
+            let tx = client.cancel_all_orders(&req, global.opts()).await?;
+            finish(&client, global, tx).await
+        }
+        Command::Order(OrderCommand::Create(args)) => {
+            let client = global.tx_client()?;
+            let mut params = OrderParams::from_decimals(
+                MarketIndex::new(args.market)?,
+                args.side.into(),
+                args.size,
+                args.size_decimals,
+                args.price,
+                args.price_decimals,
+            )?;
+            params.client_order_index = args.client_order_index;
+            params.reduce_only = args.reduce_only;
+            let req = params.to_request(args.kind.into());
+            let tx = client.create_order(&req, global.opts()).await?;
+            finish(&client, global, tx).await
+        }
+        Command::Transfer(args) => {
+            let client = global.tx_client()?;
+            let tx = match args.fee {
+                Some(fee) => {
+                    let req = TransferTxReq::from_raw_units(
+                        args.to,
+                        checked_transfer_amount(args.amount)?,
+                        fee,
+                        [0u8; 32],
+                    );
+                    client.transfer(&req, global.opts()).await?
+                }
+                None => {
+                    client
+                        .transfer_auto_fee(
+                            AccountIndex::new(args.to)?,
+                            args.amount,
+                            [0u8; 32],
+                            args.max_fee,
+                            global.opts(),
+                        )
+                        .await?
+                }
+            };
+            finish(&client, global, tx).await
+        }
+        Command::Book { market, depth } => {
+            let book = global.http_client()?.get_order_book(market, depth).await?;
+            let depth = depth as usize;
+            Ok(json!({
+                "market": market,
+                "offset": book.offset,
+                "asks": book.order_book.asks.iter().take(depth).collect::<Vec<_>>(),
+                "bids": book.order_book.bids.iter().take(depth).collect::<Vec<_>>(),
+            }))
+        }
+        Command::Nonce => {
+            let account_index = global
+                .account_index
+                .ok_or_else(|| LighterError::MissingField("LIGHTER_ACCOUNT_INDEX".to_string()))?;
+            let nonce = global
+                .http_client()?
+                .get_next_nonce(
+                    AccountIndex::new(account_index)?,
+                    ApiKeyIndex::new(global.api_key_index)?,
+                )
+                .await?;
+            Ok(json!({
+                "account_index": account_index,
+                "api_key_index": global.api_key_index,
+                "nonce": nonce,
+            }))
+        }
+        Command::Sign { file } => {
+            let contents = std::fs::read_to_string(&file).map_err(|e| {
+                LighterError::InvalidConfiguration(format!("cannot read {}: {}", file.display(), e))
+            })?;
+            let request: SignRequest = serde_json::from_str(&contents)?;
+            let client = global.tx_client()?;
+            let opts = global.opts();
+            match request {
+                SignRequest::CreateOrder(req) => {
+                    let tx = client.create_order(&req, opts).await?;
+                    finish(&client, global, tx).await
+                }
+                SignRequest::CancelOrder(req) => {
+                    let tx = client.cancel_order(&req, opts).await?;
+                    finish(&client, global, tx).await
+                }
+                SignRequest::CancelAll(req) => {
+                    let tx = client.cancel_all_orders(&req, opts).await?;
+                    finish(&client, global, tx).await
+                }
+                SignRequest::Transfer(req) => {
+                    let tx = client.transfer(&req, opts).await?;
+                    finish(&client, global, tx).await
+                }
+                SignRequest::Withdraw(req) => {
+                    let tx = client.withdraw(&req, opts).await?;
+                    finish(&client, global, tx).await
+                }
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    match run(cli).await {
+        Ok(output) => {
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", json!({ "error": e.to_string() }));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_KEY: &str =
+        "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+
+    fn parse(args: &[&str]) -> Cli {
+        let credentials = ["lighter", "--api-key", TEST_KEY, "--account-index", "12345"];
+        Cli::try_parse_from(credentials.iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_order_create() {
+        let cli = parse(&[
+            "order",
+            "create",
+            "--market",
+            "0",
+            "--side",
+            "buy",
+            "--size",
+            "0.1",
+            "--price",
+            "3000",
+            "--size-decimals",
+            "4",
+            "--price-decimals",
+            "2",
+        ]);
+        match cli.command {
+            Command::Order(OrderCommand::Create(args)) => {
+                assert_eq!(args.market, 0);
+                assert_eq!(args.side, CliSide::Buy);
+                assert_eq!(args.size, Decimal::new(1, 1));
+                assert_eq!(args.price, Decimal::from(3000));
+                assert_eq!(args.kind, CliOrderKind::Limit);
+                assert!(!args.reduce_only);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+        assert_eq!(cli.global.chain_id, 304);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_arguments() {
+        let base = ["lighter", "order", "create", "--market", "0", "--size", "1"];
+        // Missing --side, --price and the precisions
+        assert!(Cli::try_parse_from(base).is_err());
+        assert!(Cli::try_parse_from(["lighter", "book", "--market", "x"]).is_err());
+        assert!(Cli::try_parse_from([
+            "lighter",
+            "transfer",
+            "--to",
+            "1",
+            "--amount",
+            "5",
+            "--fee",
+            "1",
+            "--max-fee",
+            "2"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_global_flags_after_subcommand() {
+        let cli = parse(&["book", "--market", "3", "--api-url", "http://localhost:1"]);
+        assert!(matches!(
+            cli.command,
+            Command::Book {
+                market: 3,
+                depth: 10
+            }
+        ));
+        assert_eq!(cli.global.api_url.as_deref(), Some("http://localhost:1"));
+    }
+
+    #[tokio::test]
+    async fn test_offline_order_create() {
+        let cli = parse(&[
+            "--nonce",
+            "7",
+            "order",
+            "create",
+            "--market",
+            "1",
+            "--side",
+            "sell",
+            "--size",
+            "0.5",
+            "--price",
+            "3000.25",
+            "--size-decimals",
+            "4",
+            "--price-decimals",
+            "2",
+            "--reduce-only",
+        ]);
+        let output = run(cli).await.unwrap();
+        assert_eq!(output["tx_type"], json!(14));
+        let info = &output["tx_info"];
+        assert_eq!(info["nonce"], json!(7));
+        assert_eq!(info["order_info"]["base_amount"], json!(5_000));
+        assert_eq!(info["order_info"]["price"], json!(300_025));
+        assert_eq!(info["order_info"]["is_ask"], json!(1));
+        assert_eq!(info["order_info"]["reduce_only"], json!(1));
+        // Offline runs never submit
+        assert!(output.get("response").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_offline_sign_file() {
+        let path = std::env::temp_dir().join(format!("lighter-cli-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"type": "cancel_order", "request": {"market_index": 2, "index": 99}}"#,
+        )
+        .unwrap();
+        let cli = parse(&["--nonce", "3", "sign", "--file", path.to_str().unwrap()]);
+        let output = run(cli).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let output = output.unwrap();
+        assert_eq!(output["tx_type"], json!(15));
+        assert_eq!(output["tx_info"]["nonce"], json!(3));
+    }
+
+    #[tokio::test]
+    async fn test_offline_requires_nonce() {
+        let cli = parse(&["cancel-all"]);
+        assert!(run(cli).await.is_err());
+        let cli = parse(&["nonce"]);
+        assert!(matches!(run(cli).await, Err(LighterError::MissingField(_))));
+    }
+}
@@ -0,0 +1,497 @@
+//! Synchronous wrappers around the transaction clients
+//!
+//! Enabled with the `blocking` feature. Each wrapper owns a current-thread
+//! tokio runtime and blocks on the async implementation, so constructors,
+//! errors and return types match [`crate::client`]. The WebSocket client has
+//! no blocking counterpart.
+//!
+//! Don't call these from inside an async runtime; blocking on a runtime from
+//! within another one panics.
+//!
+//! ```rust,no_run
+//! use lighter_rs::blocking::TxClient;
+//! use lighter_rs::types::{AccountIndex, ApiKeyIndex, ChainId, CreateOrderTxReq};
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let tx_client = TxClient::from_ids(
+//!     "https://api.lighter.xyz",
+//!     "your_api_key_hex",
+//!     AccountIndex::new(12345)?,
+//!     ApiKeyIndex::new(0)?,
+//!     ChainId::new(304)?,
+//! )?;
+//! // let tx = tx_client.create_order(&order, None)?;
+//! // let response = tx_client.send_transaction(&tx)?;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+use crate::bulk::{BulkPlan, BulkPolicy, BulkReport};
+use crate::client::{self, CancelOutcome, PairedResult, TxResponse};
+use crate::constants::TxKind;
+use crate::errors::Result;
+use crate::key_rotation::RotationReport;
+use crate::order_tracker::OrderIndexResolver;
+use crate::peg::{MarketRules, PeggedOrder, PeggedOrderReq};
+use crate::risk::CircuitBreaker;
+use crate::runtime_config::{ClientRuntimeConfig, RuntimeConfig};
+use crate::signer::l1::L1Signer;
+use crate::signer::{KeyManager, NonceGapPolicy, NonceStore};
+use crate::throttle::OrderThrottle;
+use crate::types::interop::TxEnvelope;
+use crate::types::*;
+use crate::withdraw::WithdrawOutcome;
+use crate::ws_client::{AccountSnapshot, OrderBook};
+
+fn new_runtime() -> Result<Arc<Runtime>> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| {
+            crate::errors::LighterError::Other(format!("Failed to start runtime: {}", e))
+        })?;
+    Ok(Arc::new(runtime))
+}
+
+/// Forward `&self` methods to the async client, blocking on the runtime
+macro_rules! blocking_methods {
+    ($( $(#[$meta:meta])* fn $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty; )*) => {
+        $(
+            $(#[$meta])*
+            pub fn $name(&self $(, $arg: $ty)*) -> Result<$ret> {
+                self.runtime.block_on(self.inner.$name($($arg),*))
+            }
+        )*
+    };
+}
+
+/// Blocking [`client::HTTPClient`]
+#[derive(Clone)]
+pub struct HTTPClient {
+    inner: client::HTTPClient,
+    runtime: Arc<Runtime>,
+}
+
+impl HTTPClient {
+    /// Create a new HTTP client
+    pub fn new(base_url: &str) -> Result<Self> {
+        Ok(Self {
+            inner: client::HTTPClient::new(base_url)?,
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// Enable or disable fat finger protection
+    pub fn set_fat_finger_protection(&mut self, enabled: bool) {
+        self.inner.set_fat_finger_protection(enabled);
+    }
+
+    /// Fail typed responses carrying keys this version doesn't know
+    pub fn set_strict_parsing(&mut self, enabled: bool) {
+        self.inner.set_strict_parsing(enabled);
+    }
+
+    /// Largest `sendTx` body to send, before compression
+    pub fn set_max_body_size(&mut self, bytes: usize) {
+        self.inner.set_max_body_size(bytes);
+    }
+
+    /// Send `sendTx` bodies gzip-compressed, falling back on 415
+    pub fn set_gzip_requests(&mut self, enabled: bool) {
+        self.inner.set_gzip_requests(enabled);
+    }
+
+    /// Path prefix added by a gateway in front of the API
+    pub fn set_path_prefix(&mut self, prefix: &str) {
+        self.inner.set_path_prefix(prefix);
+    }
+
+    /// API version used in endpoint paths
+    pub fn set_api_version(&mut self, version: client::ApiVersion) {
+        self.inner.set_api_version(version);
+    }
+
+    /// Time limit of each request
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.inner.set_request_timeout(timeout)
+    }
+
+    /// Full URL of an endpoint
+    pub fn api_url(&self, name: &str) -> String {
+        self.inner.api_url(name)
+    }
+
+    /// Current auth token, refreshed when close to expiry
+    pub fn auth_token(&self) -> Result<String> {
+        self.inner.auth_token()
+    }
+
+    /// GET a private endpoint with the auth token attached
+    pub fn get_private<T: DeserializeOwned>(&self, path_and_query: &str) -> Result<T> {
+        self.runtime
+            .block_on(self.inner.get_private(path_and_query))
+    }
+
+    blocking_methods! {
+        /// Get the next nonce for an account and API key
+        fn get_next_nonce(&self, account_index: AccountIndex, api_key_index: ApiKeyIndex) -> i64;
+        /// Current time according to the API server
+        fn get_server_time(&self) -> DateTime<Utc>;
+        /// Get the state of a public pool
+        fn get_public_pool(&self, public_pool_index: AccountIndex) -> PublicPoolInfo;
+        /// Get the shares an account holds in a public pool
+        fn get_pool_position(&self, account_index: AccountIndex, public_pool_index: AccountIndex) -> PoolPosition;
+        /// Get one page of an account's inactive orders
+        fn get_order_history(&self, account_index: AccountIndex, market: Option<MarketIndex>, cursor: Option<&str>, limit: u32) -> Page<HistoricalOrder>;
+        /// Get one page of an account's trades
+        fn get_trade_history(&self, account_index: AccountIndex, market: Option<MarketIndex>, cursor: Option<&str>, limit: u32) -> Page<Fill>;
+        /// Get the fee for a transfer between two accounts
+        fn get_transfer_fee_info(&self, account_index: AccountIndex, to_account_index: AccountIndex) -> FeeInfo;
+        /// Status of a transaction by its hash
+        fn get_tx_status(&self, tx_hash: &str) -> Option<client::TxStatus>;
+        /// Send a transaction
+        fn send_tx(&self, tx_type: u8, tx_info: &str) -> TxResponse;
+    }
+}
+
+/// Blocking [`client::TxClient`]
+pub struct TxClient {
+    inner: client::TxClient,
+    runtime: Arc<Runtime>,
+}
+
+impl std::fmt::Debug for TxClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl TxClient {
+    /// Create a new transaction client
+    ///
+    /// Arguments are the same as for [`client::TxClient::new`].
+    pub fn new(
+        api_client_url: &str,
+        api_key_private_key: &str,
+        account_index: i64,
+        api_key_index: u8,
+        chain_id: u32,
+    ) -> Result<Self> {
+        Self::from_ids(
+            api_client_url,
+            api_key_private_key,
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+            ChainId::new(chain_id)?,
+        )
+    }
+
+    /// Create a new transaction client from validated indices and chain id
+    ///
+    /// Arguments are the same as for [`client::TxClient::from_ids`].
+    pub fn from_ids(
+        api_client_url: &str,
+        api_key_private_key: &str,
+        account_index: AccountIndex,
+        api_key_index: ApiKeyIndex,
+        chain_id: ChainId,
+    ) -> Result<Self> {
+        Ok(Self {
+            inner: client::TxClient::from_ids(
+                api_client_url,
+                api_key_private_key,
+                account_index,
+                api_key_index,
+                chain_id,
+            )?,
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// Get the account index
+    pub fn account_index(&self) -> i64 {
+        self.inner.account_index()
+    }
+
+    /// Get the API key index
+    pub fn api_key_index(&self) -> u8 {
+        self.inner.api_key_index()
+    }
+
+    /// Create an auth token for private REST endpoints, valid until `expiry`
+    pub fn create_auth_token(&self, expiry: DateTime<Utc>) -> Result<String> {
+        self.inner.create_auth_token(expiry)
+    }
+
+    /// The key transactions are signed with
+    pub fn key_manager(&self) -> Arc<dyn KeyManager + Send + Sync> {
+        self.inner.key_manager()
+    }
+
+    /// Blocking HTTP client sharing this client's runtime
+    pub fn http(&self) -> Option<HTTPClient> {
+        self.inner.http().map(|inner| HTTPClient {
+            inner: inner.clone(),
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Authenticate private requests with experimental self-signed tokens
+    pub fn enable_experimental_auth_tokens(&mut self) -> Result<()> {
+        self.inner.enable_experimental_auth_tokens()
+    }
+
+    /// Switch to a different API key
+    pub fn switch_api_key(&mut self, api_key: u8) {
+        self.inner.switch_api_key(api_key);
+    }
+
+    /// Allocate nonces locally from `store`
+    pub fn set_nonce_store(&mut self, store: Arc<dyn NonceStore>) {
+        self.inner.set_nonce_store(store);
+    }
+
+    /// What happens to transactions signed behind an unused nonce
+    pub fn set_nonce_gap_policy(&mut self, policy: NonceGapPolicy) {
+        self.inner.set_nonce_gap_policy(policy);
+    }
+
+    /// How long a nonce may go unsent before it is released
+    pub fn set_nonce_hold_timeout(&mut self, timeout: Duration) {
+        self.inner.set_nonce_hold_timeout(timeout);
+    }
+
+    /// How signatures are written in transaction payloads
+    pub fn set_signature_encoding(&mut self, encoding: SignatureEncoding) {
+        self.inner.set_signature_encoding(encoding);
+    }
+
+    /// Nonces from the nonce store whose submission result isn't known yet
+    pub fn in_flight_nonces(&self) -> usize {
+        self.inner.in_flight_nonces()
+    }
+
+    /// Default expiry for transactions of `kind` signed without `expired_at`
+    pub fn set_default_expiry(&mut self, kind: TxKind, expiry: Duration) {
+        self.inner.set_default_expiry(kind, expiry);
+    }
+
+    /// Validity a transaction must have left when it is signed and sent
+    pub fn set_min_remaining_validity(&mut self, min: Option<Duration>) {
+        self.inner.set_min_remaining_validity(min);
+    }
+
+    /// Correct default expiries by the offset of the local clock from the server's
+    pub fn set_clock_skew_compensation(&mut self, config: Option<client::ClockSkewConfig>) {
+        self.inner.set_clock_skew_compensation(config);
+    }
+
+    /// Throttle new orders and grouped orders through `throttle`
+    pub fn set_order_throttle(&mut self, throttle: Arc<OrderThrottle>) {
+        self.inner.set_order_throttle(throttle);
+    }
+
+    /// Consult `breaker` before every submission and record its outcome
+    pub fn set_circuit_breaker(&mut self, breaker: Arc<CircuitBreaker>) {
+        self.inner.set_circuit_breaker(breaker);
+    }
+
+    /// Settings read on every use
+    pub fn runtime_config(&self) -> &RuntimeConfig {
+        self.inner.runtime_config()
+    }
+
+    /// Change the runtime settings; the next transaction is checked under them
+    pub fn update_config(&self, f: impl FnOnce(&mut ClientRuntimeConfig)) -> Result<()> {
+        self.inner.update_config(f)
+    }
+
+    /// Get the configured nonce store, if any
+    pub fn nonce_store(&self) -> Option<&Arc<dyn NonceStore>> {
+        self.inner.nonce_store()
+    }
+
+    /// Build, validate and hash a transaction without signing it
+    pub fn build_unsigned<R: client::TxRequest>(
+        &self,
+        req: &R,
+        opts: TransactOpts,
+    ) -> Result<R::Tx> {
+        self.runtime.block_on(self.inner.build_unsigned(req, opts))
+    }
+
+    /// Validate, hash and sign a transaction in place
+    pub fn sign_prepared<T: Resignable>(&self, tx_info: &mut T) -> Result<()> {
+        self.inner.sign_prepared(tx_info)
+    }
+
+    /// Split a batch of orders into transactions and waves
+    pub fn plan_bulk_orders(
+        &self,
+        reqs: Vec<CreateOrderTxReq>,
+        policy: BulkPolicy,
+    ) -> Result<BulkPlan> {
+        self.inner.plan_bulk_orders(reqs, policy)
+    }
+
+    /// Submit an externally signed transaction exactly as it was received
+    pub fn send_envelope(&self, envelope: &TxEnvelope) -> Result<TxResponse> {
+        self.runtime.block_on(self.inner.send_envelope(envelope))
+    }
+
+    /// Send a signed transaction to the API
+    pub fn send_transaction<T: TxInfo>(&self, tx_info: &T) -> Result<TxResponse> {
+        self.runtime.block_on(self.inner.send_transaction(tx_info))
+    }
+
+    /// Send a transaction built by this client, re-signing it if stale
+    pub fn send_transaction_resigning<T: Resignable>(&self, tx_info: &mut T) -> Result<TxResponse> {
+        self.runtime
+            .block_on(self.inner.send_transaction_resigning(tx_info))
+    }
+
+    blocking_methods! {
+        /// Reconcile the nonce store with the server's next nonce
+        fn sync_nonce_store(&self) -> i64;
+        /// Start the nonce store over from the server's next nonce
+        fn refresh_nonce(&self) -> i64;
+        /// Fill in defaults for transaction options
+        fn fill_default_opts(&self, opts: Option<TransactOpts>) -> TransactOpts;
+        /// Offset of the server clock from the local one
+        fn check_clock_skew(&self) -> chrono::Duration;
+        /// Fill in defaults for transaction options of a transaction kind
+        fn fill_default_opts_for(&self, kind: TxKind, opts: Option<TransactOpts>) -> TransactOpts;
+        /// Create and sign an order
+        fn create_order(&self, req: &CreateOrderTxReq, opts: Option<TransactOpts>) -> L2CreateOrderTxInfo;
+        /// Create and sign an order cancellation
+        fn cancel_order(&self, req: &CancelOrderTxReq, opts: Option<TransactOpts>) -> L2CancelOrderTxInfo;
+        /// Cancel by client order index, by exchange index once it resolves
+        fn cancel_by_client_index_resolved(&self, resolver: &OrderIndexResolver, market_index: u8, client_order_index: i64, wait: Duration, opts: Option<TransactOpts>) -> L2CancelOrderTxInfo;
+        /// Create and sign an order modification
+        fn modify_order(&self, req: &ModifyOrderTxReq, opts: Option<TransactOpts>) -> L2ModifyOrderTxInfo;
+        /// Create and sign a cancel-all
+        fn cancel_all_orders(&self, req: &CancelAllOrdersTxReq, opts: Option<TransactOpts>) -> L2CancelAllOrdersTxInfo;
+        /// Cancel every open order of one market with per-order cancels
+        fn cancel_all_orders_for_market(&self, market_index: MarketIndex, account: &AccountSnapshot, opts: Option<TransactOpts>) -> Vec<CancelOutcome>;
+        /// Submit two orders together, cancelling one if the other fails
+        fn create_paired_orders(&self, leg_a: &CreateOrderTxReq, leg_b: &CreateOrderTxReq, opts: Option<TransactOpts>) -> PairedResult;
+        /// Sign and send a bulk plan, one wave at a time
+        fn execute_bulk(&self, plan: &BulkPlan, opts: Option<TransactOpts>) -> BulkReport;
+        /// Create and sign grouped orders
+        fn create_grouped_orders(&self, req: &CreateGroupedOrdersTxReq, opts: Option<TransactOpts>) -> L2CreateGroupedOrdersTxInfo;
+        /// Create and sign a transfer
+        fn transfer(&self, req: &TransferTxReq, opts: Option<TransactOpts>) -> L2TransferTxInfo;
+        /// Create and sign a transfer paying the currently quoted fee
+        fn transfer_auto_fee(&self, to_account_index: AccountIndex, usdc_amount: Decimal, memo: [u8; 32], max_fee: Decimal, opts: Option<TransactOpts>) -> L2TransferTxInfo;
+        /// Create and sign a withdrawal
+        fn withdraw(&self, req: &WithdrawTxReq, opts: Option<TransactOpts>) -> L2WithdrawTxInfo;
+        /// Send a withdrawal once and find out whether it executed
+        fn withdraw_confirmed(&self, usdc_amount: Usdc, opts: Option<TransactOpts>, confirm_timeout: Duration) -> WithdrawOutcome;
+        /// Create and sign a public key change
+        fn change_pub_key(&self, req: &ChangePubKeyReq, opts: Option<TransactOpts>) -> L2ChangePubKeyTxInfo;
+        /// Register an API key with the L1 wallet's signature and send it
+        fn register_api_key_with_l1(&self, l1_signer: &L1Signer, new_api_public_key: &[u8], opts: Option<TransactOpts>) -> TxResponse;
+        /// Register a new API key and sign with it once confirmed
+        fn rotate_api_key(&self, new_key_manager: Arc<dyn KeyManager + Send + Sync>, opts: Option<TransactOpts>, confirm_timeout: Duration) -> RotationReport;
+        /// Create and sign a leverage update
+        fn update_leverage(&self, req: &UpdateLeverageTxReq, opts: Option<TransactOpts>) -> L2UpdateLeverageTxInfo;
+        /// Create and sign a margin update
+        fn update_margin(&self, req: &UpdateMarginTxReq, opts: Option<TransactOpts>) -> L2UpdateMarginTxInfo;
+        /// Create and sign a sub-account creation
+        fn create_sub_account(&self, opts: Option<TransactOpts>) -> L2CreateSubAccountTxInfo;
+        /// Create and sign a public pool creation
+        fn create_public_pool(&self, req: &CreatePublicPoolTxReq, opts: Option<TransactOpts>) -> L2CreatePublicPoolTxInfo;
+        /// Create and sign a public pool update
+        fn update_public_pool(&self, req: &UpdatePublicPoolTxReq, opts: Option<TransactOpts>) -> L2UpdatePublicPoolTxInfo;
+        /// Create and sign a share mint
+        fn mint_shares(&self, req: &MintSharesTxReq, opts: Option<TransactOpts>) -> L2MintSharesTxInfo;
+        /// Create and sign a share burn
+        fn burn_shares(&self, req: &BurnSharesTxReq, opts: Option<TransactOpts>) -> L2BurnSharesTxInfo;
+        /// Burn all shares held in a public pool except `leave_dust`
+        fn burn_all_shares(&self, public_pool_index: AccountIndex, leave_dust: i64, opts: Option<TransactOpts>) -> L2BurnSharesTxInfo;
+        /// Create and sign an order from [`OrderParams`]
+        fn create_order_with(&self, params: OrderParams, kind: OrderKind, opts: Option<TransactOpts>) -> L2CreateOrderTxInfo;
+        /// Create and sign a limit order pegged to a book
+        fn create_pegged_order(&self, req: &PeggedOrderReq, book: &OrderBook, book_offset: Option<i64>, rules: &MarketRules, opts: Option<TransactOpts>) -> PeggedOrder;
+        /// Create a reduce-only market order closing a position or part of it
+        fn close_position(&self, market_index: MarketIndex, position: &Position, price: Price, portion: Option<Decimal>, opts: Option<TransactOpts>) -> L2CreateOrderTxInfo;
+        /// Create and sign a leverage update from a multiplier
+        fn update_leverage_with_multiplier(&self, market_index: MarketIndex, leverage: u16, margin_mode: u8, opts: Option<TransactOpts>) -> L2UpdateLeverageTxInfo;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::test_support;
+
+    fn test_client(url: &str) -> TxClient {
+        TxClient {
+            inner: test_support::test_client(url),
+            runtime: new_runtime().unwrap(),
+        }
+    }
+
+    fn opts() -> Option<TransactOpts> {
+        Some(TransactOpts {
+            expired_at: 4_000_000_000_000,
+            nonce: Some(7),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_offline_signing() {
+        let client = test_client("");
+        let tx = client
+            .create_order_with(
+                test_support::order_params(0, Side::Buy, 1_000, 100_000),
+                OrderKind::Limit,
+                opts(),
+            )
+            .unwrap();
+        assert_eq!(tx.nonce, 7);
+        assert!(tx.sig.is_some());
+        assert!(client.http().is_none());
+    }
+
+    #[test]
+    fn test_mocked_submission() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+            .create();
+
+        let client = test_client(&server.url());
+        let tx = client
+            .create_order(
+                &CreateOrderTxReq {
+                    market_index: 0,
+                    client_order_index: 1,
+                    base_amount: 1_000,
+                    price: 100_000,
+                    is_ask: 0,
+                    order_type: ORDER_TYPE_LIMIT,
+                    time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+                    reduce_only: 0,
+                    trigger_price: 0,
+                    order_expiry: 0,
+                },
+                opts(),
+            )
+            .unwrap();
+        let response = client.send_transaction(&tx).unwrap();
+
+        assert_eq!(response.tx_hash.as_deref(), Some("0xabc"));
+        mock.assert();
+    }
+}
@@ -0,0 +1,357 @@
+//! Rolling top-of-book statistics
+//!
+//! [`RollingBookStats`] keeps a window of mid price, spread and top-of-book
+//! imbalance samples. Feed it live with [`RollingBookStats::subscribe`] or
+//! manually with [`on_book`](RollingBookStats::on_book) /
+//! [`on_book_at`](RollingBookStats::on_book_at) in backtests.
+//!
+//! All statistics are `f64` and return `None` instead of NaN when the window
+//! doesn't hold enough samples.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::ws_client::{OrderBook, PriceLevel, WsClient};
+
+/// Window limits for [`RollingBookStats`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookStatsConfig {
+    /// Maximum number of samples kept
+    pub max_samples: usize,
+    /// Samples older than this (relative to the newest) are dropped
+    pub max_age: Option<Duration>,
+}
+
+impl Default for BookStatsConfig {
+    fn default() -> Self {
+        Self {
+            max_samples: 1_000,
+            max_age: None,
+        }
+    }
+}
+
+/// Top-of-book sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookSample {
+    pub at: DateTime<Utc>,
+    /// `(best_bid + best_ask) / 2`
+    pub mid: f64,
+    /// `(best_ask - best_bid) / mid * 10_000`
+    pub spread_bps: f64,
+    /// `(bid_size - ask_size) / (bid_size + ask_size)` at the top level, in `[-1, 1]`
+    pub imbalance: f64,
+}
+
+impl BookSample {
+    /// Sample the top of `book`; `None` if a side is empty, unparsable or crossed
+    pub fn from_book(book: &OrderBook, at: DateTime<Utc>) -> Option<Self> {
+        let (bid, bid_size) = best_level(&book.bids, |a, b| a > b)?;
+        let (ask, ask_size) = best_level(&book.asks, |a, b| a < b)?;
+        if ask < bid || bid <= 0.0 {
+            return None;
+        }
+
+        let mid = (bid + ask) / 2.0;
+        let depth = bid_size + ask_size;
+        Some(Self {
+            at,
+            mid,
+            spread_bps: (ask - bid) / mid * 10_000.0,
+            imbalance: if depth > 0.0 {
+                (bid_size - ask_size) / depth
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+/// Best `(price, size)` among levels, where `better(a, b)` means `a` beats `b`
+fn best_level(levels: &[PriceLevel], better: impl Fn(f64, f64) -> bool) -> Option<(f64, f64)> {
+    levels
+        .iter()
+        .filter_map(|level| {
+            let price = level.price.parse::<f64>().ok()?;
+            let size = level.size.parse::<f64>().ok()?;
+            (price.is_finite() && size > 0.0).then_some((price, size))
+        })
+        .reduce(|best, level| if better(level.0, best.0) { level } else { best })
+}
+
+/// Rolling window of top-of-book samples for one market
+#[derive(Debug, Clone)]
+pub struct RollingBookStats {
+    config: BookStatsConfig,
+    samples: VecDeque<BookSample>,
+    clock: SharedClock,
+}
+
+impl RollingBookStats {
+    /// Create an empty window
+    pub fn new(config: BookStatsConfig) -> Self {
+        Self {
+            config,
+            samples: VecDeque::new(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Take the time of [`on_book`](Self::on_book) samples from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Feed order book updates of `market_id` from `ws` into a shared window
+    pub fn subscribe(
+        ws: &WsClient,
+        market_id: u32,
+        config: BookStatsConfig,
+    ) -> Arc<Mutex<RollingBookStats>> {
+        let stats = Arc::new(Mutex::new(Self::new(config)));
+        let window = stats.clone();
+        ws.on_order_book(market_id, move |_, book| {
+            window.lock().unwrap().on_book(&book);
+        });
+        stats
+    }
+
+    /// Add a sample taken now; returns whether the book produced one
+    pub fn on_book(&mut self, book: &OrderBook) -> bool {
+        self.on_book_at(book, self.clock.now())
+    }
+
+    /// Add a sample with an explicit timestamp, e.g. from recorded data
+    pub fn on_book_at(&mut self, book: &OrderBook, at: DateTime<Utc>) -> bool {
+        match BookSample::from_book(book, at) {
+            Some(sample) => {
+                self.push(sample);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a precomputed sample, evicting by count and age
+    pub fn push(&mut self, sample: BookSample) {
+        self.samples.push_back(sample);
+        while self.samples.len() > self.config.max_samples {
+            self.samples.pop_front();
+        }
+        if let Some(max_age) = self
+            .config
+            .max_age
+            .and_then(|a| chrono::Duration::from_std(a).ok())
+        {
+            while self
+                .samples
+                .front()
+                .is_some_and(|oldest| sample.at - oldest.at > max_age)
+            {
+                self.samples.pop_front();
+            }
+        }
+    }
+
+    /// Samples in the window, oldest first
+    pub fn samples(&self) -> &VecDeque<BookSample> {
+        &self.samples
+    }
+
+    /// Most recent sample
+    pub fn last(&self) -> Option<&BookSample> {
+        self.samples.back()
+    }
+
+    /// Realized volatility: root mean square of log mid returns between samples
+    ///
+    /// Per-sample, not annualized. `None` with fewer than two samples.
+    pub fn realized_vol(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let returns: Vec<f64> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(a, b)| (b.mid / a.mid).ln())
+            .collect();
+        let mean_square = returns.iter().map(|r| r * r).sum::<f64>() / returns.len() as f64;
+        Some(mean_square.sqrt())
+    }
+
+    /// Mean spread in basis points; `None` when empty
+    pub fn mean_spread_bps(&self) -> Option<f64> {
+        mean(self.samples.iter().map(|s| s.spread_bps))
+    }
+
+    /// Mean top-of-book imbalance; `None` when empty
+    pub fn mean_imbalance(&self) -> Option<f64> {
+        mean(self.samples.iter().map(|s| s.imbalance))
+    }
+
+    /// Z-score of the latest spread against the window
+    ///
+    /// Uses the population standard deviation of all samples including the
+    /// latest. `None` with fewer than two samples or zero deviation.
+    pub fn zscore_of_current_spread(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let mean = self.mean_spread_bps()?;
+        let variance = self
+            .samples
+            .iter()
+            .map(|s| (s.spread_bps - mean).powi(2))
+            .sum::<f64>()
+            / self.samples.len() as f64;
+        let std = variance.sqrt();
+        if std == 0.0 {
+            return None;
+        }
+        Some((self.last()?.spread_bps - mean) / std)
+    }
+
+    /// Time-weighted exponential moving average of the mid
+    ///
+    /// Each sample gets weight `1 - 0.5^(dt / halflife)` where `dt` is the
+    /// time since the previous sample. `None` when empty.
+    pub fn ewma_mid(&self, halflife: Duration) -> Option<f64> {
+        let mut samples = self.samples.iter();
+        let first = samples.next()?;
+        let halflife = halflife.as_secs_f64();
+        let (mut ewma, mut prev_at) = (first.mid, first.at);
+
+        for sample in samples {
+            let dt = (sample.at - prev_at)
+                .to_std()
+                .unwrap_or_default()
+                .as_secs_f64();
+            let alpha = if halflife > 0.0 {
+                1.0 - 0.5f64.powf(dt / halflife)
+            } else {
+                1.0
+            };
+            ewma += alpha * (sample.mid - ewma);
+            prev_at = sample.at;
+        }
+        Some(ewma)
+    }
+}
+
+fn mean(values: impl ExactSizeIterator<Item = f64>) -> Option<f64> {
+    let n = values.len();
+    (n > 0).then(|| values.sum::<f64>() / n as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bid: &str, bid_size: &str, ask: &str, ask_size: &str) -> OrderBook {
+        let level = |price: &str, size: &str| PriceLevel {
+            price: price.to_string(),
+            size: size.to_string(),
+        };
+        OrderBook {
+            bids: vec![level("1.0", "9"), level(bid, bid_size)],
+            asks: vec![level("9999.0", "9"), level(ask, ask_size)],
+        }
+    }
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    fn assert_close(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_sample_from_book() {
+        let sample = BookSample::from_book(&book("99", "3", "101", "1"), at(0)).unwrap();
+        assert_eq!(sample.mid, 100.0);
+        assert_eq!(sample.spread_bps, 200.0);
+        assert_eq!(sample.imbalance, 0.5);
+
+        // Crossed or one-sided books are skipped
+        assert!(BookSample::from_book(&book("102", "1", "101", "1"), at(0)).is_none());
+        let one_sided = OrderBook {
+            bids: vec![],
+            asks: vec![PriceLevel {
+                price: "101".to_string(),
+                size: "1".to_string(),
+            }],
+        };
+        assert!(BookSample::from_book(&one_sided, at(0)).is_none());
+    }
+
+    #[test]
+    fn test_statistics_against_hand_computed_values() {
+        let mut stats = RollingBookStats::new(BookStatsConfig::default());
+        // Mids 100, 110, 99; spreads 200, 20000/110, 40000/99 bps
+        stats.on_book_at(&book("99", "1", "101", "1"), at(0));
+        stats.on_book_at(&book("109", "1", "111", "1"), at(10));
+        stats.on_book_at(&book("97", "1", "101", "1"), at(20));
+
+        let r1 = (110.0f64 / 100.0).ln();
+        let r2 = (99.0f64 / 110.0).ln();
+        assert_close(stats.realized_vol(), ((r1 * r1 + r2 * r2) / 2.0).sqrt());
+
+        let spreads = [200.0, 20_000.0 / 110.0, 40_000.0 / 99.0];
+        let mean = spreads.iter().sum::<f64>() / 3.0;
+        assert_close(stats.mean_spread_bps(), mean);
+        let std = (spreads.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / 3.0).sqrt();
+        assert_close(stats.zscore_of_current_spread(), (spreads[2] - mean) / std);
+
+        // One halflife between samples: each step moves halfway
+        let ewma = 100.0 + 0.5 * (110.0 - 100.0);
+        let ewma = ewma + 0.5 * (99.0 - ewma);
+        assert_close(stats.ewma_mid(Duration::from_secs(10)), ewma);
+    }
+
+    #[test]
+    fn test_small_windows_do_not_produce_nan() {
+        let mut stats = RollingBookStats::new(BookStatsConfig::default());
+        assert!(stats.realized_vol().is_none());
+        assert!(stats.mean_spread_bps().is_none());
+        assert!(stats.ewma_mid(Duration::from_secs(1)).is_none());
+
+        stats.on_book_at(&book("99", "1", "101", "1"), at(0));
+        assert!(stats.realized_vol().is_none());
+        assert!(stats.zscore_of_current_spread().is_none());
+        assert_close(stats.ewma_mid(Duration::from_secs(1)), 100.0);
+
+        // Constant spread has no deviation to score against
+        stats.on_book_at(&book("99", "1", "101", "1"), at(1));
+        assert!(stats.zscore_of_current_spread().is_none());
+        assert_close(stats.realized_vol(), 0.0);
+    }
+
+    #[test]
+    fn test_window_eviction() {
+        let mut stats = RollingBookStats::new(BookStatsConfig {
+            max_samples: 3,
+            max_age: Some(Duration::from_secs(15)),
+        });
+        for i in 0..4 {
+            stats.on_book_at(&book("99", "1", "101", "1"), at(i));
+        }
+        assert_eq!(stats.samples().len(), 3);
+        assert_eq!(stats.samples()[0].at, at(1));
+
+        stats.on_book_at(&book("99", "1", "101", "1"), at(17));
+        assert_eq!(stats.samples().len(), 3);
+        assert_eq!(stats.samples()[0].at, at(2));
+    }
+}
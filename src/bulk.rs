@@ -0,0 +1,703 @@
+//! Planning and sending many orders at once
+//!
+//! [`TxClient::plan_bulk_orders`] splits a batch of orders into
+//! transactions and waves of concurrent submissions according to a
+//! [`BulkPolicy`]; [`TxClient::execute_bulk`] signs and sends the plan and
+//! reports the outcome of every order by its position in the batch.
+//!
+//! ```no_run
+//! # async fn example(client: lighter_rs::client::TxClient, orders: Vec<lighter_rs::types::CreateOrderTxReq>) -> lighter_rs::Result<()> {
+//! use lighter_rs::bulk::BulkPolicy;
+//!
+//! let plan = client.plan_bulk_orders(orders, BulkPolicy::new().max_concurrency(4))?;
+//! let report = client.execute_bulk(&plan, None).await?;
+//! for outcome in report.failed() {
+//!     eprintln!("order {} failed: {:?}", outcome.index, outcome.result);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use futures_util::future::join_all;
+use std::fmt;
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::{GROUPING_TYPE_ONE_CANCELS_THE_OTHER, MAX_GROUPED_ORDER_COUNT};
+use crate::errors::{LighterError, Result};
+use crate::types::{
+    CreateGroupedOrdersTxReq, CreateOrderTxReq, L2CreateGroupedOrdersTxInfo, L2CreateOrderTxInfo,
+    TransactOpts,
+};
+
+/// How orders of a batch are put into transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BulkGrouping {
+    /// One transaction per order
+    #[default]
+    Independent,
+    /// Pair reduce-only orders of the same market and side into
+    /// one-cancels-the-other groups; other orders go alone
+    ///
+    /// Only for batches where such pairs are alternatives, e.g. a take
+    /// profit and a stop loss: a fill of one cancels the other.
+    OcoPairs,
+}
+
+/// What to do with the rest of a batch after an order fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Send no further waves
+    #[default]
+    Stop,
+    /// Send every wave
+    Continue,
+}
+
+/// Settings of [`TxClient::plan_bulk_orders`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BulkPolicy {
+    pub grouping: BulkGrouping,
+    /// Sign every transaction before sending the first
+    ///
+    /// Otherwise each wave is signed just before it is sent, and without a
+    /// nonce store the nonce of a rejected transaction is reused.
+    pub preallocate_nonces: bool,
+    /// Transactions sent concurrently in one wave
+    pub max_concurrency: usize,
+    pub on_error: OnError,
+    /// Print the plan instead of sending it
+    pub dry_run: bool,
+}
+
+impl BulkPolicy {
+    /// One transaction at a time, stopping at the first failure
+    pub fn new() -> Self {
+        Self {
+            grouping: BulkGrouping::Independent,
+            preallocate_nonces: false,
+            max_concurrency: 1,
+            on_error: OnError::Stop,
+            dry_run: false,
+        }
+    }
+
+    pub fn grouping(mut self, grouping: BulkGrouping) -> Self {
+        self.grouping = grouping;
+        self
+    }
+
+    pub fn preallocate_nonces(mut self) -> Self {
+        self.preallocate_nonces = true;
+        self
+    }
+
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency;
+        self
+    }
+
+    pub fn continue_on_error(mut self) -> Self {
+        self.on_error = OnError::Continue;
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+}
+
+impl Default for BulkPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One transaction of a [`BulkPlan`]
+#[derive(Debug, Clone)]
+pub enum BulkStep {
+    /// An order on its own
+    Single { index: usize, req: CreateOrderTxReq },
+    /// Orders sent as one grouped transaction
+    Grouped {
+        indices: Vec<usize>,
+        req: CreateGroupedOrdersTxReq,
+    },
+}
+
+impl BulkStep {
+    /// Positions in the batch of the step's orders
+    pub fn indices(&self) -> &[usize] {
+        match self {
+            BulkStep::Single { index, .. } => std::slice::from_ref(index),
+            BulkStep::Grouped { indices, .. } => indices,
+        }
+    }
+}
+
+/// Transactions for a batch of orders, in the order they are sent
+#[derive(Debug, Clone)]
+pub struct BulkPlan {
+    steps: Vec<BulkStep>,
+    policy: BulkPolicy,
+}
+
+impl BulkPlan {
+    pub fn steps(&self) -> &[BulkStep] {
+        &self.steps
+    }
+
+    pub fn policy(&self) -> &BulkPolicy {
+        &self.policy
+    }
+
+    /// Orders in the batch
+    pub fn order_count(&self) -> usize {
+        self.steps.iter().map(|step| step.indices().len()).sum()
+    }
+
+    /// Steps sent together, as indices into [`steps`](Self::steps)
+    pub fn waves(&self) -> Vec<Vec<usize>> {
+        (0..self.steps.len())
+            .collect::<Vec<_>>()
+            .chunks(self.policy.max_concurrency)
+            .map(<[usize]>::to_vec)
+            .collect()
+    }
+}
+
+impl fmt::Display for BulkPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let waves = self.waves();
+        writeln!(
+            f,
+            "{} orders in {} transactions and {} waves",
+            self.order_count(),
+            self.steps.len(),
+            waves.len()
+        )?;
+        for (wave_number, wave) in waves.iter().enumerate() {
+            writeln!(f, "wave {}:", wave_number + 1)?;
+            for &step in wave {
+                match &self.steps[step] {
+                    BulkStep::Single { index, req } => {
+                        writeln!(f, "  tx {}: order {} {}", step + 1, index, describe(req))?
+                    }
+                    BulkStep::Grouped { indices, req } => {
+                        writeln!(f, "  tx {}: one-cancels-the-other", step + 1)?;
+                        for (index, order) in indices.iter().zip(&req.orders) {
+                            writeln!(f, "    order {} {}", index, describe(order))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn describe(req: &CreateOrderTxReq) -> String {
+    format!(
+        "market {} {} {} @ {}{}",
+        req.market_index,
+        if req.is_ask == 1 { "sell" } else { "buy" },
+        req.base_amount,
+        req.price,
+        if req.reduce_only == 1 {
+            " reduce-only"
+        } else {
+            ""
+        }
+    )
+}
+
+/// What happened to one order of a batch
+#[derive(Debug, Clone)]
+pub enum BulkResult {
+    /// The order's transaction was accepted
+    Accepted(TxResponse),
+    /// The order's transaction couldn't be signed, failed or was rejected
+    Failed { reason: String },
+    /// Not sent: a dry run, or an earlier failure stopped the batch
+    NotSent,
+}
+
+/// Outcome of the order at `index` in the batch
+#[derive(Debug, Clone)]
+pub struct BulkOutcome {
+    pub index: usize,
+    /// Plan step that carried the order
+    pub step: usize,
+    pub result: BulkResult,
+}
+
+/// Outcomes of [`TxClient::execute_bulk`], one per order in batch order
+#[derive(Debug, Clone)]
+pub struct BulkReport {
+    pub outcomes: Vec<BulkOutcome>,
+}
+
+impl BulkReport {
+    pub fn accepted(&self) -> impl Iterator<Item = &BulkOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.result, BulkResult::Accepted(_)))
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &BulkOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.result, BulkResult::Failed { .. }))
+    }
+
+    pub fn not_sent(&self) -> impl Iterator<Item = &BulkOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome.result, BulkResult::NotSent))
+    }
+}
+
+/// Signed transaction of a step
+enum SignedStep {
+    Single(L2CreateOrderTxInfo),
+    Grouped(L2CreateGroupedOrdersTxInfo),
+}
+
+impl SignedStep {
+    fn nonce(&self) -> i64 {
+        match self {
+            SignedStep::Single(tx) => tx.nonce,
+            SignedStep::Grouped(tx) => tx.nonce,
+        }
+    }
+}
+
+/// Whether two orders can be one-cancels-the-other legs
+fn oco_compatible(a: &CreateOrderTxReq, b: &CreateOrderTxReq) -> bool {
+    a.reduce_only == 1
+        && b.reduce_only == 1
+        && a.market_index == b.market_index
+        && a.is_ask == b.is_ask
+}
+
+impl TxClient {
+    /// Split `reqs` into transactions and waves according to `policy`
+    ///
+    /// Steps keep the order of their first order in `reqs`. Fails if
+    /// `max_concurrency` is zero or two orders share a non-zero client
+    /// order index.
+    pub fn plan_bulk_orders(
+        &self,
+        reqs: Vec<CreateOrderTxReq>,
+        policy: BulkPolicy,
+    ) -> Result<BulkPlan> {
+        if policy.max_concurrency == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "Bulk max_concurrency must be at least 1".to_string(),
+            ));
+        }
+        let mut seen = std::collections::HashSet::new();
+        for req in &reqs {
+            if req.client_order_index != 0 && !seen.insert(req.client_order_index) {
+                return Err(LighterError::ValidationError(format!(
+                    "Client order index {} appears more than once in the batch",
+                    req.client_order_index
+                )));
+            }
+        }
+
+        let mut partner: Vec<Option<usize>> = vec![None; reqs.len()];
+        if policy.grouping == BulkGrouping::OcoPairs {
+            for i in 0..reqs.len() {
+                if partner[i].is_some() {
+                    continue;
+                }
+                let found = (i + 1..reqs.len())
+                    .find(|&j| partner[j].is_none() && oco_compatible(&reqs[i], &reqs[j]));
+                if let Some(j) = found {
+                    partner[i] = Some(j);
+                    partner[j] = Some(i);
+                }
+            }
+        }
+
+        let mut steps = Vec::new();
+        for (index, req) in reqs.iter().enumerate() {
+            match partner[index] {
+                Some(other) if other < index => {}
+                Some(other) => {
+                    let orders = vec![req.clone(), reqs[other].clone()];
+                    debug_assert!(orders.len() <= MAX_GROUPED_ORDER_COUNT as usize);
+                    steps.push(BulkStep::Grouped {
+                        indices: vec![index, other],
+                        req: CreateGroupedOrdersTxReq {
+                            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+                            orders,
+                        },
+                    });
+                }
+                None => steps.push(BulkStep::Single {
+                    index,
+                    req: req.clone(),
+                }),
+            }
+        }
+        Ok(BulkPlan { steps, policy })
+    }
+
+    /// Sign and send `plan`, one wave at a time
+    ///
+    /// Transactions of a wave are sent concurrently. With the nonce store in
+    /// use and no nonce in `opts`, each transaction takes the next nonce
+    /// from the store; otherwise nonces count up from the first one.
+    /// Failures of orders are reported in the [`BulkReport`], not returned.
+    pub async fn execute_bulk(
+        &self,
+        plan: &BulkPlan,
+        opts: Option<TransactOpts>,
+    ) -> Result<BulkReport> {
+        let mut outcomes: Vec<BulkOutcome> = plan
+            .steps
+            .iter()
+            .enumerate()
+            .flat_map(|(step, s)| {
+                s.indices().iter().map(move |&index| BulkOutcome {
+                    index,
+                    step,
+                    result: BulkResult::NotSent,
+                })
+            })
+            .collect();
+        outcomes.sort_by_key(|outcome| outcome.index);
+        if plan.policy.dry_run {
+            println!("{}", plan);
+            return Ok(BulkReport { outcomes });
+        }
+
+        let base = opts.unwrap_or_default();
+        let chain_nonces = self.nonce_store().is_none() || base.nonce.is_some();
+        let mut next_nonce = base.nonce;
+        let mut signed: Vec<Option<Result<SignedStep>>> =
+            (0..plan.steps.len()).map(|_| None).collect();
+
+        if plan.policy.preallocate_nonces {
+            for (step, slot) in signed.iter_mut().enumerate() {
+                let tx = self
+                    .sign_bulk_step(&plan.steps[step], &base, chain_nonces, next_nonce)
+                    .await;
+                if let (true, Ok(tx)) = (chain_nonces, &tx) {
+                    next_nonce = Some(tx.nonce() + 1);
+                }
+                *slot = Some(tx);
+            }
+        }
+
+        for wave in plan.waves() {
+            let mut txs = Vec::with_capacity(wave.len());
+            for &step in &wave {
+                let tx = match signed[step].take() {
+                    Some(tx) => tx,
+                    None => {
+                        let tx = self
+                            .sign_bulk_step(&plan.steps[step], &base, chain_nonces, next_nonce)
+                            .await;
+                        if let (true, Ok(tx)) = (chain_nonces, &tx) {
+                            next_nonce = Some(tx.nonce() + 1);
+                        }
+                        tx
+                    }
+                };
+                txs.push((step, tx));
+            }
+
+            let first_nonce = txs
+                .iter()
+                .find_map(|(_, tx)| tx.as_ref().ok().map(SignedStep::nonce));
+            let results = join_all(txs.into_iter().map(|(step, tx)| async move {
+                let result = match tx {
+                    Ok(SignedStep::Single(tx)) => self
+                        .send_transaction(&tx)
+                        .await
+                        .map(|response| (tx.nonce, response)),
+                    Ok(SignedStep::Grouped(tx)) => self
+                        .send_transaction(&tx)
+                        .await
+                        .map(|response| (tx.nonce, response)),
+                    Err(e) => Err(e),
+                };
+                (step, result)
+            }))
+            .await;
+
+            let mut failed = false;
+            let mut last_accepted = None;
+            for (step, result) in results {
+                let result = match result {
+                    Ok((nonce, response)) if response.code == 200 => {
+                        last_accepted = last_accepted.max(Some(nonce));
+                        BulkResult::Accepted(response)
+                    }
+                    Ok((_, response)) => BulkResult::Failed {
+                        reason: format!(
+                            "Rejected with code {}: {}",
+                            response.code,
+                            response.message.unwrap_or_default()
+                        ),
+                    },
+                    Err(e) => BulkResult::Failed {
+                        reason: e.to_string(),
+                    },
+                };
+                failed |= matches!(result, BulkResult::Failed { .. });
+                for outcome in outcomes.iter_mut().filter(|outcome| outcome.step == step) {
+                    outcome.result = result.clone();
+                }
+            }
+
+            // Rejected transactions at the end of a wave leave their nonces unused
+            if chain_nonces && !plan.policy.preallocate_nonces && failed {
+                next_nonce = last_accepted.map(|nonce| nonce + 1).or(first_nonce);
+            }
+            if failed && plan.policy.on_error == OnError::Stop {
+                break;
+            }
+        }
+
+        Ok(BulkReport { outcomes })
+    }
+
+    async fn sign_bulk_step(
+        &self,
+        step: &BulkStep,
+        base: &TransactOpts,
+        chain_nonces: bool,
+        next_nonce: Option<i64>,
+    ) -> Result<SignedStep> {
+        let opts = TransactOpts {
+            nonce: if chain_nonces { next_nonce } else { None },
+            ..base.clone()
+        };
+        match step {
+            BulkStep::Single { req, .. } => self
+                .create_order(req, Some(opts))
+                .await
+                .map(SignedStep::Single),
+            BulkStep::Grouped { req, .. } => self
+                .create_grouped_orders(req, Some(opts))
+                .await
+                .map(SignedStep::Grouped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{ORDER_TYPE_LIMIT, TIME_IN_FORCE_GOOD_TILL_TIME};
+    use crate::test_support::test_client;
+    use std::sync::{Arc, Mutex};
+
+    fn order(
+        client_order_index: i64,
+        market_index: u8,
+        is_ask: u8,
+        reduce_only: u8,
+    ) -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            market_index,
+            client_order_index,
+            base_amount: 1_000,
+            price: 100_000 + client_order_index as u32,
+            is_ask,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only,
+            trigger_price: 0,
+            order_expiry: 0,
+        }
+    }
+
+    /// Seven orders; 1 and 4, and 2 and 6, are reduce-only on the same
+    /// market and side
+    fn seven_orders() -> Vec<CreateOrderTxReq> {
+        vec![
+            order(1, 0, 0, 0),
+            order(2, 0, 1, 1),
+            order(3, 1, 0, 1),
+            order(4, 0, 0, 0),
+            order(5, 0, 1, 1),
+            order(6, 1, 1, 1),
+            order(7, 1, 0, 1),
+        ]
+    }
+
+    fn step_indices(plan: &BulkPlan) -> Vec<Vec<usize>> {
+        plan.steps()
+            .iter()
+            .map(|step| step.indices().to_vec())
+            .collect()
+    }
+
+    fn opts() -> Option<TransactOpts> {
+        Some(TransactOpts {
+            expired_at: chrono::Utc::now().timestamp_millis() + 600_000,
+            nonce: Some(10),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_plan_seven_orders() {
+        let client = test_client("");
+
+        let plan = client
+            .plan_bulk_orders(seven_orders(), BulkPolicy::new())
+            .unwrap();
+        assert_eq!(plan.steps().len(), 7);
+        assert_eq!(plan.waves().len(), 7);
+        assert_eq!(plan.order_count(), 7);
+
+        let plan = client
+            .plan_bulk_orders(seven_orders(), BulkPolicy::new().max_concurrency(3))
+            .unwrap();
+        assert_eq!(plan.waves(), vec![vec![0, 1, 2], vec![3, 4, 5], vec![6]]);
+
+        let plan = client
+            .plan_bulk_orders(
+                seven_orders(),
+                BulkPolicy::new()
+                    .grouping(BulkGrouping::OcoPairs)
+                    .max_concurrency(2),
+            )
+            .unwrap();
+        assert_eq!(
+            step_indices(&plan),
+            vec![vec![0], vec![1, 4], vec![2, 6], vec![3], vec![5]]
+        );
+        assert_eq!(plan.waves().len(), 3);
+        assert_eq!(plan.order_count(), 7);
+        match &plan.steps()[1] {
+            BulkStep::Grouped { req, .. } => {
+                assert_eq!(req.grouping_type, GROUPING_TYPE_ONE_CANCELS_THE_OTHER);
+                assert_eq!(req.orders[1].client_order_index, 5);
+            }
+            other => panic!("expected a group, got {:?}", other),
+        }
+        let printed = plan.to_string();
+        assert!(printed.starts_with("7 orders in 5 transactions and 3 waves"));
+        assert!(printed.contains("  tx 2: one-cancels-the-other\n    order 1 market 0 sell"));
+
+        assert!(client
+            .plan_bulk_orders(seven_orders(), BulkPolicy::new().max_concurrency(0))
+            .is_err());
+        let mut duplicated = seven_orders();
+        duplicated[6].client_order_index = 1;
+        assert!(matches!(
+            client.plan_bulk_orders(duplicated, BulkPolicy::new()),
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+
+    /// sendTx endpoint rejecting client order index 3 and recording the
+    /// client order indices and nonces it receives
+    async fn rejecting_server(
+        server: &mut mockito::Server,
+    ) -> (mockito::Mock, Arc<Mutex<Vec<(i64, i64)>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body_from_request(move |request| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                let tx: serde_json::Value =
+                    serde_json::from_str(body["tx_info"].as_str().unwrap()).unwrap();
+                let coi = tx["order_info"]["client_order_index"].as_i64().unwrap();
+                log.lock()
+                    .unwrap()
+                    .push((coi, tx["nonce"].as_i64().unwrap()));
+                if coi == 3 {
+                    br#"{"code":21706,"message":"invalid order"}"#.to_vec()
+                } else {
+                    br#"{"code":200,"tx_hash":"0xabc"}"#.to_vec()
+                }
+            })
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        (mock, received)
+    }
+
+    #[tokio::test]
+    async fn test_execute_stops_at_rejected_third_order() {
+        let mut server = mockito::Server::new_async().await;
+        let (mock, received) = rejecting_server(&mut server).await;
+        let client = test_client(&server.url());
+
+        let plan = client
+            .plan_bulk_orders(seven_orders(), BulkPolicy::new())
+            .unwrap();
+        let report = client.execute_bulk(&plan, opts()).await.unwrap();
+
+        let accepted: Vec<usize> = report.accepted().map(|o| o.index).collect();
+        let failed: Vec<usize> = report.failed().map(|o| o.index).collect();
+        let not_sent: Vec<usize> = report.not_sent().map(|o| o.index).collect();
+        assert_eq!(accepted, vec![0, 1]);
+        assert_eq!(failed, vec![2]);
+        assert_eq!(not_sent, vec![3, 4, 5, 6]);
+        assert!(matches!(
+            &report.outcomes[2].result,
+            BulkResult::Failed { reason } if reason.contains("21706")
+        ));
+        assert_eq!(*received.lock().unwrap(), vec![(1, 10), (2, 11), (3, 12)]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_execute_continues_and_reuses_rejected_nonce() {
+        let mut server = mockito::Server::new_async().await;
+        let (_mock, received) = rejecting_server(&mut server).await;
+        let client = test_client(&server.url());
+
+        let plan = client
+            .plan_bulk_orders(seven_orders(), BulkPolicy::new().continue_on_error())
+            .unwrap();
+        let report = client.execute_bulk(&plan, opts()).await.unwrap();
+
+        assert_eq!(report.accepted().count(), 6);
+        let failed: Vec<(usize, usize)> = report.failed().map(|o| (o.index, o.step)).collect();
+        assert_eq!(failed, vec![(2, 2)]);
+        let nonces: Vec<i64> = received.lock().unwrap().iter().map(|r| r.1).collect();
+        assert_eq!(nonces, vec![10, 11, 12, 12, 13, 14, 15]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_grouped_waves_and_dry_run() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200}"#)
+            .expect(5)
+            .create_async()
+            .await;
+        let client = test_client(&server.url());
+        let policy = BulkPolicy::new()
+            .grouping(BulkGrouping::OcoPairs)
+            .preallocate_nonces()
+            .max_concurrency(3);
+
+        let plan = client.plan_bulk_orders(seven_orders(), policy).unwrap();
+        let report = client.execute_bulk(&plan, opts()).await.unwrap();
+        assert_eq!(report.accepted().count(), 7);
+        let steps: Vec<usize> = report.outcomes.iter().map(|o| o.step).collect();
+        assert_eq!(steps, vec![0, 1, 2, 3, 1, 4, 2]);
+        mock.assert_async().await;
+
+        let plan = client
+            .plan_bulk_orders(seven_orders(), policy.dry_run())
+            .unwrap();
+        let report = client.execute_bulk(&plan, opts()).await.unwrap();
+        assert_eq!(report.not_sent().count(), 7);
+        mock.assert_async().await;
+    }
+}
@@ -1,908 +1,6685 @@
 //! HTTP client for interacting with the Lighter API
 
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, Stream};
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Duration;
 
-use crate::constants::*;
-use crate::errors::{LighterError, Result};
-use crate::signer::{PoseidonKeyManager, Signer};
+use crate::amendments::{AmendmentLog, ModifyAudit};
+use crate::clock::{SharedClock, SystemClock};
+use crate::constants::{
+    TxKind, AUTH_TOKEN_REFRESH_MARGIN_SECS, DEFAULT_AUTH_TOKEN_LIFETIME_SECS,
+    DEFAULT_CLOCK_SKEW_THRESHOLD_MS, DEFAULT_CLOCK_SKEW_TTL_SECS, DEFAULT_MAX_TX_BODY_BYTES,
+    DEFAULT_NONCE_HOLD_TIMEOUT_MS, DEFAULT_TX_EXPIRY_MS, MAX_AUTH_TOKEN_LIFETIME_SECS,
+    NIL_ORDER_EXPIRY, NIL_ORDER_TRIGGER_PRICE, ONE_USDC, PAIRED_ORDER_UNWIND_TIMEOUT_MS,
+    PUBLIC_KEY_LENGTH, TX_TYPE_L2_MINT_SHARES,
+};
+use crate::errors::{ErrorKind, LighterError, Result};
+use crate::fees::FeeSchedule;
+use crate::market_status::{MarketStatus, MarketStatuses};
+use crate::risk::CircuitBreaker;
+use crate::runtime_config::{ClientRuntimeConfig, RuntimeConfig};
+use crate::serde_util::{option_string_or_number_decimal, reject_extras, string_or_number_decimal};
+use crate::signer::l1::{L1Signer, OnboardingIntent};
+use crate::signer::nonce::{InFlightNonces, SendGuard, Turn};
+use crate::signer::{
+    field_elements_from_bytes, hash_to_quintic_extension, public_key_prefix, KeyManager,
+    NonceGapPolicy, NonceStore, PoseidonKeyManager,
+};
+use crate::tcp::TcpOptions;
+use crate::throttle::{OrderThrottle, ThrottleConfig};
+use crate::transport::{ReqwestTransport, Transport, TransportResponse};
+use crate::types::interop::TxEnvelope;
 use crate::types::*;
+use crate::utils::{checked_transfer_amount, validate_timestamp_ms};
+use crate::ws_client::{AccountOrder, AccountPosition, AccountSnapshot, OrderBook, PriceLevel};
+use std::collections::HashMap;
+
+type AuthTokenFn = dyn Fn(DateTime<Utc>) -> Result<String> + Send + Sync;
+
+/// Auth token cache for private endpoints
+struct AuthTokens {
+    create: Box<AuthTokenFn>,
+    lifetime: chrono::Duration,
+    current: Mutex<Option<(String, DateTime<Utc>)>>,
+}
+
+impl AuthTokens {
+    /// Cached token, or a new one when missing or within the refresh margin
+    fn get(&self, now: DateTime<Utc>) -> Result<String> {
+        let mut current = self.current.lock().unwrap();
+        let margin = chrono::Duration::seconds(AUTH_TOKEN_REFRESH_MARGIN_SECS);
+        if let Some((token, expiry)) = current.as_ref() {
+            if *expiry - now > margin {
+                return Ok(token.clone());
+            }
+        }
+        let expiry = now + self.lifetime;
+        let token = (self.create)(expiry)?;
+        *current = Some((token.clone(), expiry));
+        Ok(token)
+    }
+}
+
+/// Version segment of REST endpoint paths
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    /// Path segment, e.g. `v1`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+}
 
 /// HTTP Client for Lighter API
 #[derive(Clone)]
 pub struct HTTPClient {
-    client: Client,
+    transport: Arc<dyn Transport>,
+    /// Whether `transport` came from the caller rather than the settings below
+    custom_transport: bool,
+    request_timeout: Duration,
+    tcp: TcpOptions,
     endpoint: String,
+    path_prefix: String,
+    api_version: ApiVersion,
     fat_finger_protection: bool,
+    strict_parsing: bool,
+    max_body_size: usize,
+    gzip_requests: bool,
+    /// Set once the server answered a gzip request with 415
+    gzip_rejected: Arc<AtomicBool>,
+    auth: Option<Arc<AuthTokens>>,
+    rate_limit_retries: u32,
+    /// Rate limit headers of the latest response that had any
+    rate_limit: Arc<Mutex<Option<RateLimitStatus>>>,
 }
 
 impl HTTPClient {
     /// Create a new HTTP client
     pub fn new(base_url: &str) -> Result<Self> {
-        let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
-
-        Ok(Self {
-            client,
-            endpoint: base_url.to_string(),
-            fat_finger_protection: true,
-        })
+        let request_timeout = Duration::from_secs(30);
+        let tcp = TcpOptions::default();
+        let transport = ReqwestTransport::new(base_url, request_timeout, &tcp)?;
+        Ok(Self::from_transport(Arc::new(transport), false, base_url))
     }
 
-    /// Enable or disable fat finger protection
-    pub fn set_fat_finger_protection(&mut self, enabled: bool) {
-        self.fat_finger_protection = enabled;
+    /// Create a client sending requests through `transport`
+    ///
+    /// Request timeouts and TCP options are the transport's business;
+    /// [`api_url`](Self::api_url) returns paths only.
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        Self::from_transport(Arc::from(transport), true, "")
     }
 
-    /// Get the next nonce for an account and API key
-    pub async fn get_next_nonce(&self, account_index: i64, api_key_index: u8) -> Result<i64> {
-        let url = format!(
-            "{}/api/v1/nextNonce?account_index={}&api_key_index={}",
-            self.endpoint, account_index, api_key_index
-        );
+    fn from_transport(transport: Arc<dyn Transport>, custom: bool, base_url: &str) -> Self {
+        Self {
+            transport,
+            custom_transport: custom,
+            request_timeout: Duration::from_secs(30),
+            tcp: TcpOptions::default(),
+            endpoint: base_url.trim_end_matches('/').to_string(),
+            path_prefix: String::new(),
+            api_version: ApiVersion::default(),
+            fat_finger_protection: true,
+            strict_parsing: false,
+            max_body_size: DEFAULT_MAX_TX_BODY_BYTES,
+            gzip_requests: false,
+            gzip_rejected: Arc::new(AtomicBool::new(false)),
+            auth: None,
+            rate_limit_retries: 0,
+            rate_limit: Arc::new(Mutex::new(None)),
+        }
+    }
 
-        let response = self.client.get(&url).send().await?;
+    /// Send requests through `transport` from now on, keeping every other
+    /// setting, e.g. to mock the client of a [`TxClient`]
+    pub fn set_transport(&mut self, transport: Box<dyn Transport>) {
+        self.transport = Arc::from(transport);
+        self.custom_transport = true;
+        self.endpoint.clear();
+    }
 
-        if !response.status().is_success() {
-            return Err(LighterError::ApiError(format!(
-                "Failed to get nonce: {}",
-                response.status()
-            )));
-        }
+    /// Largest `sendTx` body to send, before compression; defaults to 256 KiB
+    ///
+    /// Larger bodies fail with [`LighterError::ValidationError`] without
+    /// being sent.
+    pub fn set_max_body_size(&mut self, bytes: usize) {
+        self.max_body_size = bytes;
+    }
 
-        #[derive(Deserialize)]
-        struct NonceResponse {
-            nonce: i64,
-        }
+    /// Send `sendTx` bodies gzip-compressed
+    ///
+    /// Only for gateways that accept `Content-Encoding: gzip`. If the server
+    /// answers 415 the request is resent uncompressed, and later requests
+    /// are no longer compressed.
+    pub fn set_gzip_requests(&mut self, enabled: bool) {
+        self.gzip_requests = enabled;
+        self.gzip_rejected.store(false, Ordering::Relaxed);
+    }
 
-        let nonce_response: NonceResponse = response.json().await?;
-        Ok(nonce_response.nonce)
+    /// Path prefix added by a gateway in front of the API, e.g. `/lighter`
+    ///
+    /// Leading and trailing slashes are optional.
+    pub fn set_path_prefix(&mut self, prefix: &str) {
+        let prefix = prefix.trim_matches('/');
+        self.path_prefix = if prefix.is_empty() {
+            String::new()
+        } else {
+            format!("/{}", prefix)
+        };
     }
 
-    /// Send a transaction to the Lighter API
+    /// Resend requests answered with 429 up to `retries` times; defaults
+    /// to none
     ///
-    /// # Arguments
-    /// * `tx_type` - Transaction type identifier
-    /// * `tx_info` - JSON-serialized transaction info
-    pub async fn send_tx(&self, tx_type: u8, tx_info: &str) -> Result<TxResponse> {
-        let url = format!("{}/api/v1/sendTx", self.endpoint);
+    /// Each resend waits exactly the `Retry-After` of the answer, or a
+    /// backoff doubling from one second when it has none.
+    pub fn set_rate_limit_retries(&mut self, retries: u32) {
+        self.rate_limit_retries = retries;
+    }
 
-        #[derive(serde::Serialize)]
-        struct SendTxRequest {
-            tx_type: u8,
-            tx_info: String,
-        }
+    /// Rate limit headers of the latest response that had any, successful
+    /// or not
+    ///
+    /// Shared by clones of the client.
+    pub fn last_rate_limit_status(&self) -> Option<RateLimitStatus> {
+        *self.rate_limit.lock().unwrap()
+    }
 
-        let request_body = SendTxRequest {
-            tx_type,
-            tx_info: tx_info.to_string(),
-        };
+    /// API version used in endpoint paths; defaults to [`ApiVersion::V1`]
+    pub fn set_api_version(&mut self, version: ApiVersion) {
+        self.api_version = version;
+    }
 
-        // Debug: print request
-        let request_json = serde_json::to_string(&request_body)?;
-        eprintln!("DEBUG - Sending request: {}", request_json);
+    /// Time limit of each request, from sending to reading the whole
+    /// response; defaults to 30 seconds
+    ///
+    /// Fails on clients with a transport of their own.
+    pub fn set_request_timeout(&mut self, timeout: Duration) -> Result<()> {
+        self.rebuild_transport(timeout, &self.tcp.clone())?;
+        self.request_timeout = timeout;
+        Ok(())
+    }
 
-        let response = self.client.post(&url).json(&request_body).send().await?;
+    /// Socket options of new connections; existing connections are dropped
+    ///
+    /// Fails on clients with a transport of their own.
+    pub fn set_tcp_options(&mut self, options: TcpOptions) -> Result<()> {
+        self.rebuild_transport(self.request_timeout, &options)?;
+        self.tcp = options;
+        Ok(())
+    }
 
-        if !response.status().is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(LighterError::ApiError(format!(
-                "Failed to send transaction: {}",
-                error_text
-            )));
+    fn rebuild_transport(&mut self, timeout: Duration, tcp: &TcpOptions) -> Result<()> {
+        if self.custom_transport {
+            return Err(LighterError::InvalidConfiguration(
+                "Request timeout and TCP options apply to the built-in transport only".to_string(),
+            ));
         }
+        self.transport = Arc::new(ReqwestTransport::new(&self.endpoint, timeout, tcp)?);
+        Ok(())
+    }
 
-        let tx_response: TxResponse = response.json().await?;
-        Ok(tx_response)
+    /// Full URL of an endpoint: `{base}{prefix}/api/{version}/{name}`
+    pub fn api_url(&self, name: &str) -> String {
+        format!("{}{}", self.endpoint, self.api_path(name))
     }
-}
 
-/// Response from send_tx API call
-#[derive(Debug, Clone, Deserialize)]
-pub struct TxResponse {
-    pub code: u16,
-    pub tx_hash: Option<String>,
-    pub message: Option<String>,
-}
+    /// Path of an endpoint after the base URL: `{prefix}/api/{version}/{name}`
+    fn api_path(&self, name: &str) -> String {
+        format!(
+            "{}/api/{}/{}",
+            self.path_prefix,
+            self.api_version.as_str(),
+            name.trim_start_matches('/')
+        )
+    }
 
-/// Transaction Client for signing and submitting transactions
-pub struct TxClient {
-    api_client: Option<HTTPClient>,
-    chain_id: u32,
-    key_manager: PoseidonKeyManager,
-    account_index: i64,
-    api_key_index: u8,
-}
+    /// Enable or disable fat finger protection
+    pub fn set_fat_finger_protection(&mut self, enabled: bool) {
+        self.fat_finger_protection = enabled;
+    }
 
-impl TxClient {
-    /// Create a new transaction client
+    /// Fail typed responses carrying keys this version doesn't know
     ///
-    /// # Arguments
-    /// * `api_client_url` - Base URL for the Lighter API (or empty string to disable API calls)
-    /// * `api_key_private_key` - Hex-encoded private key (with or without 0x prefix)
-    /// * `account_index` - Account index
-    /// * `api_key_index` - API key index
-    /// * `chain_id` - Chain ID
-    pub fn new(
-        api_client_url: &str,
-        api_key_private_key: &str,
-        account_index: i64,
-        api_key_index: u8,
-        chain_id: u32,
-    ) -> Result<Self> {
-        let key_manager = PoseidonKeyManager::from_hex(api_key_private_key)?;
-
-        let api_client = if !api_client_url.is_empty() {
-            Some(HTTPClient::new(api_client_url)?)
-        } else {
-            None
-        };
-
-        Ok(Self {
-            api_client,
-            chain_id,
-            key_manager,
-            account_index,
-            api_key_index,
-        })
+    /// Off by default: unknown keys of accounts, positions and orders are
+    /// kept in their `extras`. On, they fail with
+    /// [`LighterError::InvalidResponse`] naming the keys, e.g. to catch API
+    /// changes in CI.
+    pub fn set_strict_parsing(&mut self, enabled: bool) {
+        self.strict_parsing = enabled;
     }
 
-    /// Get the account index
-    pub fn account_index(&self) -> i64 {
-        self.account_index
+    /// Authenticate private requests with tokens from `create`
+    ///
+    /// `create` receives the expiry of the token to build; tokens are cached
+    /// for `lifetime` and recreated shortly before they expire. Without a
+    /// source, private requests fail with
+    /// [`LighterError::InvalidConfiguration`].
+    pub fn set_auth_token_source<F>(&mut self, lifetime: Duration, create: F) -> Result<()>
+    where
+        F: Fn(DateTime<Utc>) -> Result<String> + Send + Sync + 'static,
+    {
+        let lifetime = chrono::Duration::from_std(lifetime)
+            .ok()
+            .filter(|l| {
+                l.num_seconds() > AUTH_TOKEN_REFRESH_MARGIN_SECS
+                    && l.num_seconds() <= MAX_AUTH_TOKEN_LIFETIME_SECS
+            })
+            .ok_or_else(|| {
+                LighterError::InvalidConfiguration(format!(
+                    "Auth token lifetime must be between {}s and {}s",
+                    AUTH_TOKEN_REFRESH_MARGIN_SECS + 1,
+                    MAX_AUTH_TOKEN_LIFETIME_SECS
+                ))
+            })?;
+        self.auth = Some(Arc::new(AuthTokens {
+            create: Box::new(create),
+            lifetime,
+            current: Mutex::new(None),
+        }));
+        Ok(())
     }
 
-    /// Get the API key index
-    pub fn api_key_index(&self) -> u8 {
-        self.api_key_index
+    /// Drop the cached auth token so the next private request creates one
+    pub fn clear_auth_token(&self) {
+        if let Some(auth) = &self.auth {
+            *auth.current.lock().unwrap() = None;
+        }
     }
 
-    /// Get a reference to the key manager
-    pub fn key_manager(&self) -> &PoseidonKeyManager {
-        &self.key_manager
+    /// Current auth token, refreshed when close to expiry
+    pub fn auth_token(&self) -> Result<String> {
+        let auth = self.auth.as_ref().ok_or_else(|| {
+            LighterError::InvalidConfiguration(
+                "Private endpoints need an auth token; call set_auth_token_source".to_string(),
+            )
+        })?;
+        auth.get(Utc::now())
     }
 
-    /// Get a reference to the HTTP client
-    pub fn http(&self) -> Option<&HTTPClient> {
-        self.api_client.as_ref()
+    /// GET a private endpoint, e.g. `/api/v1/accountActiveOrders?...`
+    ///
+    /// The path is taken as is after the base URL and
+    /// [path prefix](Self::set_path_prefix). The auth token is sent in the
+    /// `Authorization` header.
+    pub async fn get_private<T: DeserializeOwned>(&self, path_and_query: &str) -> Result<T> {
+        let path = format!("{}{}", self.path_prefix, path_and_query);
+        self.send_private(&path, &[], path_and_query).await
     }
 
-    /// Switch to a different API key
-    pub fn switch_api_key(&mut self, api_key: u8) {
-        self.api_key_index = api_key;
+    /// GET a private endpoint by name with encoded query parameters
+    async fn get_private_query<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        query: &[(&str, String)],
+    ) -> Result<T> {
+        self.send_private(&self.api_path(name), query, name).await
     }
 
-    /// Fill in default transaction options
-    pub async fn fill_default_opts(&self, opts: Option<TransactOpts>) -> Result<TransactOpts> {
-        let mut opts = opts.unwrap_or_default();
+    async fn send_private<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        label: &str,
+    ) -> Result<T> {
+        let token = HeaderValue::from_str(&self.auth_token()?).map_err(|e| {
+            LighterError::InvalidConfiguration(format!("Auth token isn't a valid header: {}", e))
+        })?;
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::AUTHORIZATION, token);
+        let response = self.execute(path, Call::Get(query), &headers).await?;
 
-        if opts.expired_at == 0 {
-            use chrono::Utc;
-            // Default to 10 minutes from now
-            opts.expired_at = (Utc::now().timestamp_millis() + 600_000) - 1000;
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Private request {} failed: {}",
+                label, response.status
+            )));
         }
 
-        if opts.from_account_index.is_none() {
-            opts.from_account_index = Some(self.account_index);
-        }
+        response.parse()
+    }
 
-        if opts.api_key_index.is_none() {
-            opts.api_key_index = Some(self.api_key_index);
+    /// Current time according to the API server
+    ///
+    /// Taken from the `timestamp` of the status endpoint, or from the
+    /// response's `Date` header if the body has none. Either has one second
+    /// resolution.
+    pub async fn get_server_time(&self) -> Result<DateTime<Utc>> {
+        let path = format!("{}/", self.path_prefix);
+        let response = self
+            .execute(&path, Call::Get(&[]), &HeaderMap::new())
+            .await?;
+
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get server time: {}",
+                response.status
+            )));
         }
 
-        if opts.nonce.is_none() {
-            if let Some(client) = &self.api_client {
-                let nonce = client
-                    .get_next_nonce(
-                        opts.from_account_index.unwrap(),
-                        opts.api_key_index.unwrap(),
-                    )
-                    .await?;
-                opts.nonce = Some(nonce);
-            } else {
-                return Err(LighterError::MissingField(
-                    "nonce was not provided and HTTPClient is not available".to_string(),
-                ));
-            }
+        #[derive(Deserialize)]
+        struct StatusResponse {
+            timestamp: Option<i64>,
         }
 
-        Ok(opts)
+        let date = response
+            .header_str("date")
+            .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+            .map(|date| date.with_timezone(&Utc));
+        serde_json::from_str::<StatusResponse>(&response.body)
+            .ok()
+            .and_then(|status| status.timestamp)
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .or(date)
+            .ok_or_else(|| {
+                LighterError::InvalidResponse(
+                    "Server sent neither a timestamp nor a Date header".to_string(),
+                )
+            })
     }
 
-    /// Construct and sign a create order transaction
-    pub async fn create_order(
+    /// Get the next nonce for an account and API key
+    pub async fn get_next_nonce(
         &self,
-        req: &CreateOrderTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2CreateOrderTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            order_info: OrderInfo {
-                market_index: req.market_index,
-                client_order_index: req.client_order_index,
-                base_amount: req.base_amount,
-                price: req.price,
-                is_ask: req.is_ask,
-                order_type: req.order_type,
-                time_in_force: req.time_in_force,
-                reduce_only: req.reduce_only,
-                trigger_price: req.trigger_price,
-                order_expiry: req.order_expiry,
-            },
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        account_index: AccountIndex,
+        api_key_index: ApiKeyIndex,
+    ) -> Result<i64> {
+        let response = self
+            .get_api(
+                "nextNonce",
+                &[
+                    ("account_index", account_index.to_string()),
+                    ("api_key_index", api_key_index.to_string()),
+                ],
+            )
+            .await?;
 
-        // Validate
-        tx_info.validate()?;
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get nonce: {}",
+                response.status
+            )));
+        }
 
-        // Hash and sign
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
+        #[derive(Deserialize)]
+        struct NonceResponse {
+            nonce: i64,
+        }
 
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        let nonce_response: NonceResponse = response.parse()?;
+        Ok(nonce_response.nonce)
+    }
 
-        Ok(tx_info)
+    /// [`get_next_nonce`](Self::get_next_nonce) with raw integers,
+    /// validating their ranges
+    #[deprecated(note = "use `get_next_nonce` with `AccountIndex` and `ApiKeyIndex`")]
+    pub async fn get_next_nonce_raw(&self, account_index: i64, api_key_index: u8) -> Result<i64> {
+        self.get_next_nonce(
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+        )
+        .await
     }
 
-    /// Construct and sign a cancel order transaction
-    pub async fn cancel_order(
+    /// Get the public key registered for an account's API key
+    ///
+    /// Returns the key as hex without `0x`, or `None` if nothing is
+    /// registered at that index.
+    pub async fn get_api_key_public_key(
         &self,
-        req: &CancelOrderTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CancelOrderTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+        api_key_index: ApiKeyIndex,
+    ) -> Result<Option<String>> {
+        let response = self
+            .get_api(
+                "apikeys",
+                &[
+                    ("account_index", account_index.to_string()),
+                    ("api_key_index", api_key_index.to_string()),
+                ],
+            )
+            .await?;
 
-        let mut tx_info = L2CancelOrderTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            market_index: req.market_index,
-            index: req.index,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get API keys of account {}: {}",
+                account_index, response.status
+            )));
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        #[derive(Deserialize)]
+        struct ApiKey {
+            api_key_index: u8,
+            public_key: String,
+        }
 
-        Ok(tx_info)
+        #[derive(Deserialize)]
+        struct ApiKeysResponse {
+            #[serde(default)]
+            api_keys: Vec<ApiKey>,
+        }
+
+        let body: ApiKeysResponse = response.parse()?;
+        Ok(body
+            .api_keys
+            .into_iter()
+            .find(|key| key.api_key_index == api_key_index.get())
+            .map(|key| {
+                let digits = key.public_key.trim_start_matches("0x");
+                digits.to_ascii_lowercase()
+            })
+            .filter(|key| !key.is_empty()))
     }
 
-    /// Construct and sign a modify order transaction
-    pub async fn modify_order(
+    /// [`get_api_key_public_key`](Self::get_api_key_public_key) with raw
+    /// integers, validating their ranges
+    #[deprecated(note = "use `get_api_key_public_key` with `AccountIndex` and `ApiKeyIndex`")]
+    pub async fn get_api_key_public_key_raw(
         &self,
-        req: &ModifyOrderTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2ModifyOrderTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2ModifyOrderTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            market_index: req.market_index,
-            index: req.index,
-            base_amount: req.base_amount,
-            price: req.price,
-            trigger_price: req.trigger_price,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        account_index: i64,
+        api_key_index: u8,
+    ) -> Result<Option<String>> {
+        self.get_api_key_public_key(
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+        )
+        .await
+    }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+    /// Get resting orders of a market aggregated into price levels
+    ///
+    /// `limit` caps the number of orders fetched per side, so deep books
+    /// come back truncated.
+    pub async fn get_order_book(&self, market_id: u32, limit: u32) -> Result<RestOrderBook> {
+        let response = self
+            .get_api(
+                "orderBookOrders",
+                &[
+                    ("market_id", market_id.to_string()),
+                    ("limit", limit.to_string()),
+                ],
+            )
+            .await?;
 
-        Ok(tx_info)
-    }
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get order book {}: {}",
+                market_id, response.status
+            )));
+        }
 
-    /// Construct and sign a cancel all orders transaction
-    pub async fn cancel_all_orders(
-        &self,
-        req: &CancelAllOrdersTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CancelAllOrdersTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        #[derive(Deserialize)]
+        struct RestOrder {
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            price: Decimal,
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            remaining_base_amount: Decimal,
+        }
 
-        let mut tx_info = L2CancelAllOrdersTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            time_in_force: req.time_in_force,
-            time: req.time,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        #[derive(Deserialize)]
+        struct OrderBookOrders {
+            #[serde(default)]
+            asks: Vec<RestOrder>,
+            #[serde(default)]
+            bids: Vec<RestOrder>,
+            offset: Option<i64>,
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        fn aggregate(orders: Vec<RestOrder>) -> Vec<PriceLevel> {
+            let mut levels: Vec<(Decimal, Decimal)> = Vec::new();
+            for order in orders {
+                match levels.iter_mut().find(|(price, _)| *price == order.price) {
+                    Some((_, size)) => *size += order.remaining_base_amount,
+                    None => levels.push((order.price, order.remaining_base_amount)),
+                }
+            }
+            levels
+                .into_iter()
+                .map(|(price, size)| PriceLevel {
+                    price: price.normalize().to_string(),
+                    size: size.normalize().to_string(),
+                })
+                .collect()
+        }
 
-        Ok(tx_info)
+        let body: OrderBookOrders = response.parse()?;
+        Ok(RestOrderBook {
+            order_book: OrderBook {
+                asks: aggregate(body.asks),
+                bids: aggregate(body.bids),
+            },
+            offset: body.offset,
+        })
     }
 
-    /// Construct and sign a create grouped orders transaction
-    pub async fn create_grouped_orders(
-        &self,
-        req: &CreateGroupedOrdersTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateGroupedOrdersTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+    /// Get the maker and taker fees of a market
+    ///
+    /// The API quotes fees in percent; the schedule holds basis points.
+    pub async fn get_fee_schedule(&self, market_id: u32) -> Result<FeeSchedule> {
+        let response = self
+            .get_api("orderBooks", &[("market_id", market_id.to_string())])
+            .await?;
 
-        let orders: Vec<OrderInfo> = req
-            .orders
-            .iter()
-            .map(|o| OrderInfo {
-                market_index: o.market_index,
-                client_order_index: o.client_order_index,
-                base_amount: o.base_amount,
-                price: o.price,
-                is_ask: o.is_ask,
-                order_type: o.order_type,
-                time_in_force: o.time_in_force,
-                reduce_only: o.reduce_only,
-                trigger_price: o.trigger_price,
-                order_expiry: o.order_expiry,
-            })
-            .collect();
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get fees of market {}: {}",
+                market_id, response.status
+            )));
+        }
 
-        let mut tx_info = L2CreateGroupedOrdersTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            grouping_type: req.grouping_type,
-            orders,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        #[derive(Deserialize)]
+        struct MarketFees {
+            market_id: u32,
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            maker_fee: Decimal,
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            taker_fee: Decimal,
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        #[derive(Deserialize)]
+        struct OrderBooks {
+            #[serde(default)]
+            order_books: Vec<MarketFees>,
+        }
 
-        Ok(tx_info)
+        let body: OrderBooks = response.parse()?;
+        let market = body
+            .order_books
+            .into_iter()
+            .find(|m| m.market_id == market_id)
+            .ok_or_else(|| LighterError::ApiError(format!("Unknown market {}", market_id)))?;
+        let bps = Decimal::from(100);
+        Ok(FeeSchedule::new(
+            market.maker_fee * bps,
+            market.taker_fee * bps,
+        ))
     }
 
-    /// Construct and sign a transfer transaction
-    pub async fn transfer(
-        &self,
-        req: &TransferTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2TransferTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2TransferTxInfo {
-            from_account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            to_account_index: req.to_account_index,
-            usdc_amount: req.usdc_amount,
-            fee: req.fee,
-            memo: req.memo,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+    /// Get the trading status of a market
+    pub async fn get_market_status(&self, market_id: u32) -> Result<MarketStatus> {
+        let response = self
+            .get_api("orderBooks", &[("market_id", market_id.to_string())])
+            .await?;
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get status of market {}: {}",
+                market_id, response.status
+            )));
+        }
 
-        Ok(tx_info)
+        #[derive(Deserialize)]
+        struct Market {
+            market_id: u32,
+            status: MarketStatus,
+        }
+
+        #[derive(Deserialize)]
+        struct OrderBooks {
+            #[serde(default)]
+            order_books: Vec<Market>,
+        }
+
+        let body: OrderBooks = response.parse()?;
+        body.order_books
+            .into_iter()
+            .find(|m| m.market_id == market_id)
+            .map(|m| m.status)
+            .ok_or_else(|| LighterError::ApiError(format!("Unknown market {}", market_id)))
     }
 
-    /// Construct and sign a withdraw transaction
-    pub async fn withdraw(
+    /// Get one page of an account's inactive (filled, cancelled or expired) orders
+    ///
+    /// Private endpoint; see [`get_private`](Self::get_private).
+    pub async fn get_order_history(
         &self,
-        req: &WithdrawTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2WithdrawTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+        market: Option<MarketIndex>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Page<HistoricalOrder>> {
+        #[derive(Deserialize)]
+        struct OrdersPage {
+            #[serde(default)]
+            orders: Vec<HistoricalOrder>,
+            next_cursor: Option<String>,
+        }
 
-        let mut tx_info = L2WithdrawTxInfo {
-            from_account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            usdc_amount: req.usdc_amount,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        let query = history_query(account_index, market, cursor, limit);
+        let page: OrdersPage = self
+            .get_private_query("accountInactiveOrders", &query)
+            .await?;
+        Ok(Page {
+            items: page.orders,
+            next_cursor: page.next_cursor.filter(|c| !c.is_empty()),
+        })
+    }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+    /// Get one page of an account's trades
+    ///
+    /// Private endpoint; see [`get_private`](Self::get_private).
+    pub async fn get_trade_history(
+        &self,
+        account_index: AccountIndex,
+        market: Option<MarketIndex>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Page<Fill>> {
+        #[derive(Deserialize)]
+        struct TradesPage {
+            #[serde(default)]
+            trades: Vec<Fill>,
+            next_cursor: Option<String>,
+        }
 
-        Ok(tx_info)
+        let query = history_query(account_index, market, cursor, limit);
+        let page: TradesPage = self.get_private_query("trades", &query).await?;
+        Ok(Page {
+            items: page.trades,
+            next_cursor: page.next_cursor.filter(|c| !c.is_empty()),
+        })
     }
 
-    /// Construct and sign a change public key transaction
-    pub async fn change_pub_key(
+    /// Get the fee for a transfer between two accounts
+    ///
+    /// Private endpoint; see [`get_private`](Self::get_private).
+    pub async fn get_transfer_fee_info(
         &self,
-        req: &ChangePubKeyReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2ChangePubKeyTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+        to_account_index: AccountIndex,
+    ) -> Result<FeeInfo> {
+        self.get_private_query(
+            "transferFeeInfo",
+            &[
+                ("account_index", account_index.to_string()),
+                ("to_account_index", to_account_index.to_string()),
+            ],
+        )
+        .await
+    }
 
-        let mut tx_info = L2ChangePubKeyTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            pub_key: req.pub_key.clone(),
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+    /// [`get_order_history`](Self::get_order_history) with raw integers,
+    /// validating their ranges
+    #[deprecated(note = "use `get_order_history` with `AccountIndex` and `MarketIndex`")]
+    pub async fn get_order_history_raw(
+        &self,
+        account_index: i64,
+        market: Option<u8>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Page<HistoricalOrder>> {
+        let market = market.map(MarketIndex::new).transpose()?;
+        self.get_order_history(AccountIndex::new(account_index)?, market, cursor, limit)
+            .await
+    }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+    /// [`get_trade_history`](Self::get_trade_history) with raw integers,
+    /// validating their ranges
+    #[deprecated(note = "use `get_trade_history` with `AccountIndex` and `MarketIndex`")]
+    pub async fn get_trade_history_raw(
+        &self,
+        account_index: i64,
+        market: Option<u8>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<Page<Fill>> {
+        let market = market.map(MarketIndex::new).transpose()?;
+        self.get_trade_history(AccountIndex::new(account_index)?, market, cursor, limit)
+            .await
+    }
 
-        Ok(tx_info)
+    /// [`get_transfer_fee_info`](Self::get_transfer_fee_info) with raw
+    /// account indices, validating their ranges
+    #[deprecated(note = "use `get_transfer_fee_info` with `AccountIndex`")]
+    pub async fn get_transfer_fee_info_raw(
+        &self,
+        account_index: i64,
+        to_account_index: i64,
+    ) -> Result<FeeInfo> {
+        self.get_transfer_fee_info(
+            AccountIndex::new(account_index)?,
+            AccountIndex::new(to_account_index)?,
+        )
+        .await
     }
 
-    /// Construct and sign an update leverage transaction
-    pub async fn update_leverage(
+    /// All inactive orders of an account, following pagination
+    pub fn order_history_stream(
         &self,
-        req: &UpdateLeverageTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2UpdateLeverageTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+        market: Option<MarketIndex>,
+        options: PaginationOptions,
+    ) -> impl Stream<Item = Result<HistoricalOrder>> + '_ {
+        paginate(options, move |cursor| async move {
+            self.get_order_history(account_index, market, cursor.as_deref(), options.limit)
+                .await
+        })
+    }
 
-        let mut tx_info = L2UpdateLeverageTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            market_index: req.market_index,
-            initial_margin_fraction: req.initial_margin_fraction,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+    /// All trades of an account, following pagination
+    pub fn trade_history_stream(
+        &self,
+        account_index: AccountIndex,
+        market: Option<MarketIndex>,
+        options: PaginationOptions,
+    ) -> impl Stream<Item = Result<Fill>> + '_ {
+        paginate(options, move |cursor| async move {
+            self.get_trade_history(account_index, market, cursor.as_deref(), options.limit)
+                .await
+        })
+    }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+    /// Get the state of a public pool
+    ///
+    /// Pools are accounts, so this reads the pool's account entry.
+    pub async fn get_public_pool(&self, public_pool_index: AccountIndex) -> Result<PublicPoolInfo> {
+        let response = self
+            .get_api(
+                "account",
+                &[
+                    ("by", "index".to_string()),
+                    ("value", public_pool_index.to_string()),
+                ],
+            )
+            .await?;
 
-        Ok(tx_info)
-    }
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get public pool {}: {}",
+                public_pool_index, response.status
+            )));
+        }
 
-    /// Construct and sign an update margin transaction
-    pub async fn update_margin(
-        &self,
-        req: &UpdateMarginTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2UpdateMarginTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2UpdateMarginTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            market_index: req.market_index,
-            usdc_amount: req.usdc_amount,
-            direction: req.direction,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        #[derive(Deserialize)]
+        struct PoolDetails {
+            status: u8,
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            operator_fee: Decimal,
+            total_shares: i64,
+            operator_shares: i64,
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        #[derive(Deserialize)]
+        struct PoolAccount {
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            total_asset_value: Decimal,
+            pool_info: Option<PoolDetails>,
+        }
 
-        Ok(tx_info)
+        #[derive(Deserialize)]
+        struct AccountResponse {
+            #[serde(default)]
+            accounts: Vec<PoolAccount>,
+        }
+
+        let body: AccountResponse = response.parse()?;
+        let account = body.accounts.into_iter().next().ok_or_else(|| {
+            LighterError::ApiError(format!("Public pool {} not found", public_pool_index))
+        })?;
+        let pool = account.pool_info.ok_or_else(|| {
+            LighterError::ApiError(format!(
+                "Account {} is not a public pool",
+                public_pool_index
+            ))
+        })?;
+
+        Ok(PublicPoolInfo {
+            public_pool_index: public_pool_index.get(),
+            status: pool.status,
+            operator_fee: pool.operator_fee,
+            total_shares: pool.total_shares,
+            operator_shares: pool.operator_shares,
+            pool_equity: account.total_asset_value,
+        })
     }
 
-    /// Construct and sign a create sub account transaction
-    pub async fn create_sub_account(
+    /// Get the shares `account_index` holds in a public pool
+    ///
+    /// Reads the account's share entries and values them at the pool's
+    /// current share price. An account without shares gets a zero position.
+    pub async fn get_pool_position(
         &self,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateSubAccountTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+        public_pool_index: AccountIndex,
+    ) -> Result<PoolPosition> {
+        let response = self
+            .get_api(
+                "account",
+                &[
+                    ("by", "index".to_string()),
+                    ("value", account_index.to_string()),
+                ],
+            )
+            .await?;
 
-        let mut tx_info = L2CreateSubAccountTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get account {}: {}",
+                account_index, response.status
+            )));
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        #[derive(Deserialize)]
+        struct ShareEntry {
+            public_pool_index: i64,
+            shares_amount: i64,
+            #[serde(deserialize_with = "string_or_number_decimal")]
+            entry_usdc: Decimal,
+        }
 
-        Ok(tx_info)
-    }
+        #[derive(Deserialize)]
+        struct ShareAccount {
+            #[serde(default)]
+            shares: Vec<ShareEntry>,
+        }
 
-    /// Construct and sign a create public pool transaction
-    pub async fn create_public_pool(
-        &self,
-        req: &CreatePublicPoolTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreatePublicPoolTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2CreatePublicPoolTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            operator_fee: req.operator_fee,
-            initial_total_shares: req.initial_total_shares,
-            min_operator_share_rate: req.min_operator_share_rate,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
+        #[derive(Deserialize)]
+        struct AccountResponse {
+            #[serde(default)]
+            accounts: Vec<ShareAccount>,
+        }
+
+        let body: AccountResponse = response.parse()?;
+        let account = body.accounts.into_iter().next().ok_or_else(|| {
+            LighterError::ApiError(format!("Account {} not found", account_index))
+        })?;
+        let Some(entry) = account
+            .shares
+            .into_iter()
+            .find(|entry| entry.public_pool_index == public_pool_index.get())
+        else {
+            return Ok(PoolPosition {
+                public_pool_index: public_pool_index.get(),
+                shares: 0,
+                entry_usdc: Decimal::ZERO,
+                current_value: Decimal::ZERO,
+            });
         };
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        let pool = self.get_public_pool(public_pool_index).await?;
+        Ok(PoolPosition {
+            public_pool_index: public_pool_index.get(),
+            shares: entry.shares_amount,
+            entry_usdc: entry.entry_usdc,
+            current_value: Decimal::from(entry.shares_amount) * pool.share_price()?,
+        })
+    }
 
-        Ok(tx_info)
+    /// [`get_public_pool`](Self::get_public_pool) with a raw pool index,
+    /// validating its range
+    #[deprecated(note = "use `get_public_pool` with `AccountIndex`")]
+    pub async fn get_public_pool_raw(&self, public_pool_index: i64) -> Result<PublicPoolInfo> {
+        self.get_public_pool(AccountIndex::new(public_pool_index)?)
+            .await
     }
 
-    /// Construct and sign an update public pool transaction
-    pub async fn update_public_pool(
+    /// [`get_pool_position`](Self::get_pool_position) with raw account and
+    /// pool indices, validating their ranges
+    #[deprecated(note = "use `get_pool_position` with `AccountIndex`")]
+    pub async fn get_pool_position_raw(
         &self,
-        req: &UpdatePublicPoolTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2UpdatePublicPoolTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
-
-        let mut tx_info = L2UpdatePublicPoolTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            public_pool_index: req.public_pool_index,
-            status: req.status,
-            operator_fee: req.operator_fee,
-            min_operator_share_rate: req.min_operator_share_rate,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
-
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
-
-        Ok(tx_info)
+        account_index: i64,
+        public_pool_index: i64,
+    ) -> Result<PoolPosition> {
+        self.get_pool_position(
+            AccountIndex::new(account_index)?,
+            AccountIndex::new(public_pool_index)?,
+        )
+        .await
     }
 
-    /// Construct and sign a mint shares transaction
-    pub async fn mint_shares(
+    /// Get the collateral and positions of an account
+    ///
+    /// The account endpoint has no open orders or trades; fetch orders with
+    /// [`get_active_orders`](Self::get_active_orders). Positions are keyed by
+    /// market id like the `account_all` stream.
+    pub async fn get_account_snapshot(
         &self,
-        req: &MintSharesTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2MintSharesTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        account_index: AccountIndex,
+    ) -> Result<AccountSnapshot> {
+        let response = self
+            .get_api(
+                "account",
+                &[
+                    ("by", "index".to_string()),
+                    ("value", account_index.to_string()),
+                ],
+            )
+            .await?;
 
-        let mut tx_info = L2MintSharesTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            public_pool_index: req.public_pool_index,
-            share_amount: req.share_amount,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
-        };
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get account {}: {}",
+                account_index, response.status
+            )));
+        }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+        #[derive(Deserialize)]
+        struct RestAccount {
+            #[serde(default, deserialize_with = "option_string_or_number_decimal")]
+            collateral: Option<Decimal>,
+            #[serde(default)]
+            positions: Vec<AccountPosition>,
+        }
 
-        Ok(tx_info)
-    }
+        #[derive(Deserialize)]
+        struct AccountResponse {
+            #[serde(default)]
+            accounts: Vec<RestAccount>,
+        }
 
-    /// Construct and sign a burn shares transaction
-    pub async fn burn_shares(
-        &self,
-        req: &BurnSharesTxReq,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2BurnSharesTxInfo> {
-        let opts = self.fill_default_opts(opts).await?;
+        let body: AccountResponse = response.parse()?;
+        let account = body.accounts.into_iter().next().ok_or_else(|| {
+            LighterError::ApiError(format!("Account {} not found", account_index))
+        })?;
 
-        let mut tx_info = L2BurnSharesTxInfo {
-            account_index: opts.from_account_index.unwrap(),
-            api_key_index: opts.api_key_index.unwrap(),
-            public_pool_index: req.public_pool_index,
-            share_amount: req.share_amount,
-            expired_at: opts.expired_at,
-            nonce: opts.nonce.unwrap(),
-            sig: None,
-            signed_hash: None,
+        let snapshot = AccountSnapshot {
+            collateral: account.collateral,
+            positions: account
+                .positions
+                .into_iter()
+                .map(|position| (position.market_id.to_string(), position))
+                .collect(),
+            ..Default::default()
         };
+        if self.strict_parsing {
+            snapshot.check_strict()?;
+        }
+        Ok(snapshot)
+    }
 
-        tx_info.validate()?;
-        let msg_hash = tx_info.hash(self.chain_id)?;
-        let signature = self.key_manager.sign(&msg_hash)?;
-        tx_info.sig = Some(signature);
-        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+    /// [`get_account_snapshot`](Self::get_account_snapshot) with a raw
+    /// account index, validating its range
+    #[deprecated(note = "use `get_account_snapshot` with `AccountIndex`")]
+    pub async fn get_account_snapshot_raw(&self, account_index: i64) -> Result<AccountSnapshot> {
+        self.get_account_snapshot(AccountIndex::new(account_index)?)
+            .await
+    }
 
-        Ok(tx_info)
+    /// Status of a transaction by its hash, `None` if the API doesn't know it
+    pub async fn get_tx_status(&self, tx_hash: &str) -> Result<Option<TxStatus>> {
+        let response = self
+            .get_api(
+                "tx",
+                &[("by", "hash".to_string()), ("value", tx_hash.to_string())],
+            )
+            .await?;
+
+        if response.status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status.is_success() {
+            return Err(LighterError::ApiError(format!(
+                "Failed to get transaction {}: {}",
+                tx_hash, response.status
+            )));
+        }
+
+        #[derive(Deserialize)]
+        struct TxStatusResponse {
+            #[serde(default)]
+            code: Option<i64>,
+            #[serde(default)]
+            message: Option<String>,
+            #[serde(default)]
+            status: Option<i64>,
+        }
+
+        let body: TxStatusResponse = response.parse()?;
+        match (body.code, body.status) {
+            (Some(200) | None, Some(status)) => Ok(Some(TxStatus::from_code(status))),
+            (_, _)
+                if body
+                    .message
+                    .as_deref()
+                    .is_some_and(|m| m.to_ascii_lowercase().contains("not found")) =>
+            {
+                Ok(None)
+            }
+            (code, _) => Err(LighterError::ApiError(format!(
+                "Unexpected status of transaction {} (code {:?}): {}",
+                tx_hash,
+                code,
+                body.message.unwrap_or_default()
+            ))),
+        }
     }
 
-    // ========== Helper Methods ==========
+    /// Get the open orders of an account in one market; needs an auth token
+    pub async fn get_active_orders(
+        &self,
+        account_index: AccountIndex,
+        market_index: MarketIndex,
+    ) -> Result<Vec<AccountOrder>> {
+        #[derive(Deserialize)]
+        struct ActiveOrders {
+            #[serde(default)]
+            orders: Vec<AccountOrder>,
+        }
 
-    /// Create a limit order (convenience wrapper around create_order)
-    ///
-    /// Limit orders are placed on the order book at a specific price
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_limit_order(
+        let body: ActiveOrders = self
+            .get_private_query(
+                "accountActiveOrders",
+                &[
+                    ("account_index", account_index.to_string()),
+                    ("market_id", market_index.to_string()),
+                ],
+            )
+            .await?;
+        if self.strict_parsing {
+            for order in &body.orders {
+                reject_extras("AccountOrder", &order.extras)?;
+            }
+        }
+        Ok(body.orders)
+    }
+
+    /// [`get_active_orders`](Self::get_active_orders) with a raw account
+    /// index and market id, validating their ranges
+    #[deprecated(note = "use `get_active_orders` with `AccountIndex` and `MarketIndex`")]
+    pub async fn get_active_orders_raw(
+        &self,
+        account_index: i64,
+        market_id: u32,
+    ) -> Result<Vec<AccountOrder>> {
+        self.get_active_orders(
+            AccountIndex::new(account_index)?,
+            market_index_of(market_id)?,
+        )
+        .await
+    }
+
+    /// Send a transaction to the Lighter API
+    ///
+    /// # Arguments
+    /// * `tx_type` - Transaction type identifier
+    /// * `tx_info` - JSON-serialized transaction info
+    pub async fn send_tx(&self, tx_type: u8, tx_info: &str) -> Result<TxResponse> {
+        let path = self.api_path("sendTx");
+
+        #[derive(serde::Serialize)]
+        struct SendTxRequest {
+            tx_type: u8,
+            tx_info: String,
+        }
+
+        let request_body = SendTxRequest {
+            tx_type,
+            tx_info: tx_info.to_string(),
+        };
+
+        let request_json = serde_json::to_string(&request_body)?;
+
+        let payload_bytes = request_json.len();
+        if payload_bytes > self.max_body_size {
+            return Err(LighterError::ValidationError(format!(
+                "sendTx body is {} bytes, above the limit of {}",
+                payload_bytes, self.max_body_size
+            )));
+        }
+
+        let (response, compressed_bytes) = self.post_json(&path, request_json).await?;
+        let status = response.status;
+        let request_id = response.header_str("x-request-id").map(str::to_string);
+        let rate_limit_remaining = response
+            .header_str("x-ratelimit-remaining")
+            .and_then(|v| v.parse().ok());
+        let body = response.body;
+
+        if !status.is_success() {
+            if let Some(e) = Self::known_tx_error(tx_type, &body) {
+                return Err(e);
+            }
+            return Err(LighterError::TxSubmissionFailed {
+                status: status.as_u16(),
+                request_id,
+                body,
+            });
+        }
+
+        let mut tx_response: TxResponse = serde_json::from_str(&body).map_err(|e| {
+            LighterError::InvalidResponse(format!(
+                "Unexpected sendTx response (HTTP {}, request id {:?}): {}: {}",
+                status.as_u16(),
+                request_id,
+                e,
+                truncate_body(&body)
+            ))
+        })?;
+        tx_response.http_status = Some(status.as_u16());
+        tx_response.request_id = request_id;
+        tx_response.rate_limit_remaining = rate_limit_remaining;
+        tx_response.raw = Some(body);
+        tx_response.payload_bytes = Some(payload_bytes);
+        tx_response.compressed_bytes = compressed_bytes;
+        if tx_response.code != 200 {
+            if let Some(e) = tx_response
+                .message
+                .as_deref()
+                .and_then(|message| Self::known_tx_error(tx_type, message))
+            {
+                return Err(e);
+            }
+        }
+        Ok(tx_response)
+    }
+
+    /// POST a JSON body, gzip-compressed if enabled and not rejected before
+    ///
+    /// Returns the response and the compressed size when compression was used.
+    async fn post_json(
+        &self,
+        path: &str,
+        body: String,
+    ) -> Result<(TransportResponse, Option<usize>)> {
+        if self.gzip_requests && !self.gzip_rejected.load(Ordering::Relaxed) {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(body.as_bytes())
+                .expect("writing to a Vec cannot fail");
+            let compressed = encoder.finish().expect("writing to a Vec cannot fail");
+            let compressed_bytes = compressed.len();
+
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                reqwest::header::CONTENT_ENCODING,
+                HeaderValue::from_static("gzip"),
+            );
+            let response = self
+                .execute(path, Call::Post(&compressed), &headers)
+                .await?;
+            if response.status != StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                return Ok((response, Some(compressed_bytes)));
+            }
+            eprintln!("Server rejected gzip request body, sending uncompressed");
+            self.gzip_rejected.store(true, Ordering::Relaxed);
+        }
+
+        let response = self
+            .execute(path, Call::Post(body.as_bytes()), &HeaderMap::new())
+            .await?;
+        Ok((response, None))
+    }
+
+    /// GET an endpoint by name with encoded query parameters
+    async fn get_api(&self, name: &str, query: &[(&str, String)]) -> Result<TransportResponse> {
+        self.execute(&self.api_path(name), Call::Get(query), &HeaderMap::new())
+            .await
+    }
+
+    /// Send a request through the transport and record its rate limit headers
+    ///
+    /// A 429 answer is retried as set by
+    /// [`set_rate_limit_retries`](Self::set_rate_limit_retries), then fails
+    /// with [`LighterError::RateLimited`].
+    async fn execute(
+        &self,
+        path: &str,
+        call: Call<'_>,
+        headers: &HeaderMap,
+    ) -> Result<TransportResponse> {
+        let mut attempt = 0;
+        loop {
+            let response = match call {
+                Call::Get(query) => self.transport.get_json(path, query, headers).await?,
+                Call::Post(body) => self.transport.post_json(path, body, headers).await?,
+            };
+            let status = RateLimitStatus::from_headers(&response.headers);
+            if let Some(status) = status {
+                *self.rate_limit.lock().unwrap() = Some(status);
+            }
+            if response.status != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            let status = status.unwrap_or_default();
+            if attempt >= self.rate_limit_retries {
+                return Err(LighterError::RateLimited {
+                    retry_after: status.retry_after,
+                    remaining: status.remaining,
+                });
+            }
+            let backoff = RATE_LIMIT_BACKOFF * 2u32.pow(attempt.min(16));
+            tokio::time::sleep(status.retry_after.unwrap_or(backoff)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Map API rejections with a dedicated error variant
+    fn known_tx_error(tx_type: u8, message: &str) -> Option<LighterError> {
+        let lower = message.to_lowercase();
+        let pool_limit = lower.contains("pool")
+            && (lower.contains("limit") || lower.contains("max"))
+            && (lower.contains("invest") || lower.contains("count"));
+        (tx_type == TX_TYPE_L2_MINT_SHARES && pool_limit)
+            .then(|| LighterError::InvestedPublicPoolLimit(message.to_string()))
+    }
+}
+
+/// Longest body excerpt included in error messages
+const MAX_BODY_EXCERPT: usize = 1024;
+
+/// First [`MAX_BODY_EXCERPT`] bytes of a response body, cut at a char boundary
+fn truncate_body(body: &str) -> &str {
+    if body.len() <= MAX_BODY_EXCERPT {
+        return body;
+    }
+    let mut end = MAX_BODY_EXCERPT;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    &body[..end]
+}
+
+/// Order request from raw integers, for the deprecated order helpers
+#[allow(clippy::too_many_arguments)]
+fn raw_order_request(
+    kind: OrderKind,
+    market_index: u8,
+    client_order_index: i64,
+    base_amount: i64,
+    price: u32,
+    trigger_price: Option<u32>,
+    is_ask: u8,
+    reduce_only: bool,
+) -> CreateOrderTxReq {
+    CreateOrderTxReq {
+        market_index,
+        client_order_index,
+        base_amount,
+        price,
+        is_ask,
+        order_type: kind.order_type(),
+        time_in_force: kind.time_in_force(),
+        reduce_only: u8::from(reduce_only),
+        trigger_price: trigger_price.unwrap_or(NIL_ORDER_TRIGGER_PRICE),
+        order_expiry: NIL_ORDER_EXPIRY,
+    }
+}
+
+fn check_position_market(position: &Position, market_index: MarketIndex) -> Result<()> {
+    if position.market_index != market_index.get() {
+        return Err(LighterError::ValidationError(format!(
+            "Position is in market {}, not {}",
+            position.market_index, market_index
+        )));
+    }
+    Ok(())
+}
+
+/// Path and query of a paginated history endpoint
+fn history_query(
+    account_index: AccountIndex,
+    market: Option<MarketIndex>,
+    cursor: Option<&str>,
+    limit: u32,
+) -> Vec<(&'static str, String)> {
+    let mut query = vec![
+        ("account_index", account_index.to_string()),
+        ("limit", limit.to_string()),
+    ];
+    if let Some(market) = market {
+        query.push(("market_id", market.to_string()));
+    }
+    if let Some(cursor) = cursor {
+        query.push(("cursor", cursor.to_string()));
+    }
+    query
+}
+
+/// Market index of a market id as the REST and stream APIs number markets
+pub(crate) fn market_index_of(market_id: u32) -> Result<MarketIndex> {
+    u8::try_from(market_id)
+        .map_err(|_| {
+            LighterError::ValidationError(format!("Market id {} is out of range", market_id))
+        })
+        .and_then(MarketIndex::new)
+}
+
+/// Stream the items of successive pages from `fetch`
+///
+/// Page requests are spaced by `options.min_interval`. The stream ends after
+/// the last page, after `options.max_pages`, or after the first error.
+fn paginate<'a, T, F, Fut>(
+    options: PaginationOptions,
+    fetch: F,
+) -> impl Stream<Item = Result<T>> + 'a
+where
+    T: 'a,
+    F: FnMut(Option<String>) -> Fut + 'a,
+    Fut: Future<Output = Result<Page<T>>> + 'a,
+{
+    struct State<T, F> {
+        fetch: F,
+        cursor: Option<String>,
+        buffer: VecDeque<T>,
+        pages: usize,
+        done: bool,
+        last_request: Option<tokio::time::Instant>,
+    }
+
+    let state = State {
+        fetch,
+        cursor: None,
+        buffer: VecDeque::new(),
+        pages: 0,
+        done: false,
+        last_request: None,
+    };
+
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(item) = state.buffer.pop_front() {
+                return Some((Ok(item), state));
+            }
+            if state.done {
+                return None;
+            }
+            if let Some(last) = state.last_request {
+                tokio::time::sleep_until(last + options.min_interval).await;
+            }
+            state.last_request = Some(tokio::time::Instant::now());
+
+            match (state.fetch)(state.cursor.take()).await {
+                Ok(page) => {
+                    state.pages += 1;
+                    state.done = page.next_cursor.is_none()
+                        || page.items.is_empty()
+                        || options.max_pages.is_some_and(|max| state.pages >= max);
+                    state.cursor = page.next_cursor;
+                    state.buffer.extend(page.items);
+                }
+                Err(e) => {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }
+    })
+}
+
+/// Order book snapshot fetched over REST
+#[derive(Debug, Clone)]
+pub struct RestOrderBook {
+    pub order_book: OrderBook,
+    /// Offset the snapshot was taken at, when the API reports it
+    pub offset: Option<i64>,
+}
+
+/// First wait before resending a request answered 429 without `Retry-After`
+const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Rate limit headers of a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RateLimitStatus {
+    /// `X-RateLimit-Remaining`: requests left in the current window
+    pub remaining: Option<u32>,
+    /// `X-RateLimit-Reset`: time until the window resets
+    pub reset_after: Option<Duration>,
+    /// `Retry-After`: time to wait before sending again
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    /// Read the headers of a response; `None` if it has none of them
+    ///
+    /// `Retry-After` may be seconds or an HTTP date. `X-RateLimit-Reset`
+    /// may be seconds, or a Unix timestamp in seconds when it is that large.
+    pub fn from_headers(headers: &reqwest::header::HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let now = Utc::now();
+        let until = |at: DateTime<Utc>| (at - now).to_std().unwrap_or(Duration::ZERO);
+        let status = Self {
+            remaining: header("x-ratelimit-remaining").and_then(|v| v.trim().parse().ok()),
+            reset_after: header("x-ratelimit-reset").and_then(|v| {
+                let secs = v
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|s| s.is_finite() && *s >= 0.0)?;
+                if secs >= UNIX_TIMESTAMP_SECS_MIN {
+                    DateTime::from_timestamp(secs as i64, 0).map(until)
+                } else {
+                    Some(Duration::from_secs_f64(secs))
+                }
+            }),
+            retry_after: header("retry-after").and_then(|v| {
+                let v = v.trim();
+                v.parse::<u64>().ok().map(Duration::from_secs).or_else(|| {
+                    DateTime::parse_from_rfc2822(v)
+                        .ok()
+                        .map(|at| until(at.with_timezone(&Utc)))
+                })
+            }),
+        };
+        (status != Self::default()).then_some(status)
+    }
+}
+
+/// Reset values from this on are Unix timestamps, not durations
+const UNIX_TIMESTAMP_SECS_MIN: f64 = 1_000_000_000.0;
+
+/// Response from send_tx API call
+#[derive(Debug, Clone, Deserialize)]
+pub struct TxResponse {
+    pub code: u16,
+    pub tx_hash: Option<String>,
+    pub message: Option<String>,
+    /// HTTP status of the submission
+    #[serde(skip)]
+    pub http_status: Option<u16>,
+    /// `X-Request-Id` header, for support requests
+    #[serde(skip)]
+    pub request_id: Option<String>,
+    /// `X-RateLimit-Remaining` header
+    #[serde(skip)]
+    pub rate_limit_remaining: Option<u32>,
+    /// Response body as received
+    #[serde(skip)]
+    pub raw: Option<String>,
+    /// Size of the request body, before compression
+    #[serde(skip)]
+    pub payload_bytes: Option<usize>,
+    /// Size of the request body on the wire, when it was gzip-compressed
+    #[serde(skip)]
+    pub compressed_bytes: Option<usize>,
+}
+
+/// Execution status of a submitted transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Accepted and waiting to be executed
+    Pending,
+    Executed,
+    /// Included but failed, with no effect on the account
+    Failed,
+    /// Status code this client doesn't know
+    Other(i64),
+}
+
+impl TxStatus {
+    /// Status from the `status` field of the transaction endpoint
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            0 => TxStatus::Failed,
+            1 => TxStatus::Pending,
+            2 => TxStatus::Executed,
+            other => TxStatus::Other(other),
+        }
+    }
+}
+
+/// Result of cancelling one order in [`TxClient::cancel_all_orders_for_market`]
+#[derive(Debug)]
+pub struct CancelOutcome {
+    pub order_index: i64,
+    pub result: Result<TxResponse>,
+}
+
+/// One order of [`TxClient::create_paired_orders`]
+#[derive(Debug)]
+pub struct PairedLeg {
+    pub tx: L2CreateOrderTxInfo,
+    pub result: Result<TxResponse>,
+}
+
+impl PairedLeg {
+    /// Whether the exchange accepted the order
+    pub fn is_accepted(&self) -> bool {
+        matches!(&self.result, Ok(response) if response.code == 200)
+    }
+}
+
+/// Result of [`TxClient::create_paired_orders`]
+#[derive(Debug)]
+pub struct PairedResult {
+    pub a: PairedLeg,
+    pub b: PairedLeg,
+    /// Submission of the cancel for the accepted leg, when only one was accepted
+    pub unwind: Option<Result<TxResponse>>,
+    /// Whether that cancel was accepted
+    pub unwound: bool,
+}
+
+impl PairedResult {
+    /// Whether both legs were accepted
+    pub fn is_complete(&self) -> bool {
+        self.a.is_accepted() && self.b.is_accepted()
+    }
+}
+
+/// Result of [`TxClient::attach_tp_sl`]
+#[derive(Debug)]
+pub struct AttachedTpSl {
+    pub tx: L2CreateGroupedOrdersTxInfo,
+    /// Exchange response, if the group was submitted
+    pub response: Option<TxResponse>,
+}
+
+/// Settings for correcting default expiries by the server clock offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSkewConfig {
+    /// Offsets up to this size are ignored
+    pub threshold: Duration,
+    /// How long a measurement is reused
+    pub ttl: Duration,
+}
+
+impl Default for ClockSkewConfig {
+    fn default() -> Self {
+        Self {
+            threshold: Duration::from_millis(DEFAULT_CLOCK_SKEW_THRESHOLD_MS),
+            ttl: Duration::from_secs(DEFAULT_CLOCK_SKEW_TTL_SECS),
+        }
+    }
+}
+
+/// Clock offset compensation with the last measurement
+#[derive(Debug)]
+struct ClockSkew {
+    config: ClockSkewConfig,
+    measured: Mutex<Option<(tokio::time::Instant, chrono::Duration)>>,
+}
+
+impl ClockSkew {
+    /// Offset to apply: the last measurement if it exceeds the threshold
+    fn applied(&self) -> chrono::Duration {
+        let offset = self
+            .measured
+            .lock()
+            .unwrap()
+            .map_or(chrono::Duration::zero(), |(_, offset)| offset);
+        match offset.abs().to_std() {
+            Ok(size) if size > self.config.threshold => offset,
+            _ => chrono::Duration::zero(),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.measured
+            .lock()
+            .unwrap()
+            .is_none_or(|(at, _)| at.elapsed() >= self.config.ttl)
+    }
+}
+
+/// Sign `{expiry_secs}:{account_index}:{api_key_index}` into an auth token
+///
+/// The message is read as Goldilocks elements and hashed like a transaction
+/// before signing. The signature is appended in plain hex, the only form the
+/// API reads in tokens.
+///
+/// Until [`hash_to_quintic_extension`] implements Poseidon2, the hash is all
+/// zeros and the API won't accept the tokens.
+fn auth_token(
+    signer: &dyn KeyManager,
+    account_index: i64,
+    api_key_index: u8,
+    expiry: DateTime<Utc>,
+) -> Result<String> {
+    let message = format!("{}:{}:{}", expiry.timestamp(), account_index, api_key_index);
+    let msg_hash = hash_to_quintic_extension(&field_elements_from_bytes(message.as_bytes())?);
+    let signature = Signature::new(signer.sign(&msg_hash)?, SignatureEncoding::HexPlain);
+    Ok(format!("{}:{}", message, signature))
+}
+
+/// What an [`HTTPClient`] request sends through its transport
+#[derive(Clone, Copy)]
+enum Call<'a> {
+    Get(&'a [(&'a str, String)]),
+    Post(&'a [u8]),
+}
+
+/// Key a [`TxClient`] signs with, shared with its auth token source
+type CurrentKey = Arc<RwLock<Arc<dyn KeyManager + Send + Sync>>>;
+
+/// Transaction Client for signing and submitting transactions
+pub struct TxClient {
+    api_client: Option<HTTPClient>,
+    chain_id: u32,
+    key_manager: CurrentKey,
+    account_index: i64,
+    /// Shared with the auth token source, like the key
+    api_key_index: Arc<AtomicU8>,
+    nonce_store: Option<Arc<dyn NonceStore>>,
+    in_flight: Arc<InFlightNonces>,
+    nonce_gap_policy: NonceGapPolicy,
+    nonce_hold_timeout: Duration,
+    signature_encoding: SignatureEncoding,
+    default_expiries: HashMap<TxKind, Duration>,
+    runtime_config: RuntimeConfig,
+    order_throttle: Option<Arc<OrderThrottle>>,
+    clock_skew: Option<ClockSkew>,
+    clock: SharedClock,
+    amendment_log: Option<Arc<AmendmentLog>>,
+    market_statuses: Option<MarketStatuses>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl std::fmt::Debug for TxClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TxClient")
+            .field("account_index", &self.account_index)
+            .field("api_key_index", &self.api_key_index())
+            .field(
+                "public_key",
+                &public_key_prefix(self.key_manager().pub_key()),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl TxClient {
+    /// Create a new transaction client
+    ///
+    /// The indices and chain id are validated as by
+    /// [`from_ids`](Self::from_ids), which takes them already checked.
+    ///
+    /// # Arguments
+    /// * `api_client_url` - Base URL for the Lighter API (or empty string to disable API calls)
+    /// * `api_key_private_key` - Hex-encoded private key (with or without 0x prefix)
+    /// * `account_index` - Account index
+    /// * `api_key_index` - API key index
+    /// * `chain_id` - Chain ID
+    pub fn new(
+        api_client_url: &str,
+        api_key_private_key: &str,
+        account_index: i64,
+        api_key_index: u8,
+        chain_id: u32,
+    ) -> Result<Self> {
+        Self::from_ids(
+            api_client_url,
+            api_key_private_key,
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+            ChainId::new(chain_id)?,
+        )
+    }
+
+    /// Create a new transaction client from validated indices and chain id
+    pub fn from_ids(
+        api_client_url: &str,
+        api_key_private_key: &str,
+        account_index: AccountIndex,
+        api_key_index: ApiKeyIndex,
+        chain_id: ChainId,
+    ) -> Result<Self> {
+        let account_index = account_index.get();
+        let api_key_index = Arc::new(AtomicU8::new(api_key_index.get()));
+        let chain_id = chain_id.get();
+        let key_manager: CurrentKey = Arc::new(RwLock::new(Arc::new(
+            PoseidonKeyManager::from_hex(api_key_private_key)?,
+        )));
+
+        let api_client = if !api_client_url.is_empty() {
+            Some(HTTPClient::new(api_client_url)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            api_client,
+            chain_id,
+            key_manager,
+            account_index,
+            api_key_index,
+            nonce_store: None,
+            in_flight: Arc::default(),
+            nonce_gap_policy: NonceGapPolicy::default(),
+            nonce_hold_timeout: Duration::from_millis(DEFAULT_NONCE_HOLD_TIMEOUT_MS),
+            signature_encoding: SignatureEncoding::default(),
+            default_expiries: HashMap::new(),
+            runtime_config: RuntimeConfig::default(),
+            order_throttle: None,
+            clock_skew: None,
+            clock: Arc::new(SystemClock),
+            amendment_log: None,
+            market_statuses: None,
+            circuit_breaker: None,
+        })
+    }
+
+    /// Get the account index
+    pub fn account_index(&self) -> i64 {
+        self.account_index
+    }
+
+    /// Get the API key index
+    pub fn api_key_index(&self) -> u8 {
+        self.api_key_index.load(Ordering::SeqCst)
+    }
+
+    /// Create an auth token for private REST endpoints, valid until `expiry`
+    ///
+    /// Experimental: the layout follows the Python SDK's
+    /// `create_auth_token_with_expiry`,
+    /// `{expiry_secs}:{account_index}:{api_key_index}:{signature_hex}`, but
+    /// no SDK token has been pinned to check it against and the API won't
+    /// accept these tokens until Poseidon2 is implemented. `expiry` is
+    /// checked against the client's clock, see [`set_clock`](Self::set_clock).
+    pub fn create_auth_token(&self, expiry: DateTime<Utc>) -> Result<String> {
+        let lifetime = (expiry - self.now()).num_seconds();
+        if lifetime <= 0 || lifetime > MAX_AUTH_TOKEN_LIFETIME_SECS {
+            return Err(LighterError::ValidationError(format!(
+                "Auth token expiry must be within {}s from now, got {}s",
+                MAX_AUTH_TOKEN_LIFETIME_SECS, lifetime
+            )));
+        }
+        auth_token(
+            self.key_manager().as_ref(),
+            self.account_index,
+            self.api_key_index(),
+            expiry,
+        )
+    }
+
+    /// The key transactions are signed with
+    ///
+    /// Each signing operation takes the key once, so a concurrent
+    /// [`rotate_api_key`](Self::rotate_api_key) never mixes keys within one
+    /// transaction.
+    pub fn key_manager(&self) -> Arc<dyn KeyManager + Send + Sync> {
+        self.key_manager.read().unwrap().clone()
+    }
+
+    /// Sign with `key_manager` from now on, returning the previous key
+    pub(crate) fn replace_key_manager(
+        &self,
+        key_manager: Arc<dyn KeyManager + Send + Sync>,
+    ) -> Arc<dyn KeyManager + Send + Sync> {
+        let previous = std::mem::replace(&mut *self.key_manager.write().unwrap(), key_manager);
+        if let Some(http) = &self.api_client {
+            http.clear_auth_token();
+        }
+        previous
+    }
+
+    /// Get a reference to the HTTP client
+    pub fn http(&self) -> Option<&HTTPClient> {
+        self.api_client.as_ref()
+    }
+
+    /// Authenticate private requests with tokens from
+    /// [`create_auth_token`](Self::create_auth_token)
+    ///
+    /// Experimental and off by default, see `create_auth_token`; until then
+    /// private endpoints need tokens from elsewhere through
+    /// [`HTTPClient::set_auth_token_source`]. Tokens follow
+    /// [`switch_api_key`](Self::switch_api_key) and key rotation.
+    pub fn enable_experimental_auth_tokens(&mut self) -> Result<()> {
+        let (signer, key_index) = (self.key_manager.clone(), self.api_key_index.clone());
+        let account_index = self.account_index;
+        let http = self.api_client.as_mut().ok_or_else(|| {
+            LighterError::MissingField("HTTPClient is required for auth tokens".to_string())
+        })?;
+        http.set_auth_token_source(
+            Duration::from_secs(DEFAULT_AUTH_TOKEN_LIFETIME_SECS as u64),
+            move |expiry| {
+                let signer = signer.read().unwrap().clone();
+                let key_index = key_index.load(Ordering::SeqCst);
+                auth_token(signer.as_ref(), account_index, key_index, expiry)
+            },
+        )
+    }
+
+    /// Get a mutable reference to the HTTP client, e.g. to set a path prefix
+    pub fn http_mut(&mut self) -> Option<&mut HTTPClient> {
+        self.api_client.as_mut()
+    }
+
+    /// Switch to a different API key
+    ///
+    /// Auth tokens are signed for the new index from now on; a cached one is
+    /// dropped.
+    pub fn switch_api_key(&mut self, api_key: u8) {
+        self.api_key_index.store(api_key, Ordering::SeqCst);
+        if let Some(http) = &self.api_client {
+            http.clear_auth_token();
+        }
+    }
+
+    /// Allocate nonces locally from `store` instead of calling `nextNonce`
+    /// for every transaction
+    ///
+    /// Keys the store has no state for are initialized from the server on
+    /// first use. Call [`sync_nonce_store`](Self::sync_nonce_store) at startup
+    /// to reconcile persisted state with the server explicitly.
+    pub fn set_nonce_store(&mut self, store: Arc<dyn NonceStore>) {
+        self.nonce_store = Some(store);
+    }
+
+    /// What happens to transactions signed behind a nonce from the nonce
+    /// store that was rejected or never sent (default
+    /// [`FailFast`](NonceGapPolicy::FailFast))
+    ///
+    /// Transactions with nonces from the store are sent without waiting for
+    /// lower ones; the policy applies to those not sent yet when a lower
+    /// nonce turns out unused.
+    pub fn set_nonce_gap_policy(&mut self, policy: NonceGapPolicy) {
+        self.nonce_gap_policy = policy;
+    }
+
+    /// How long a nonce from the store may go unsent before it counts as
+    /// unused and is released (default 10 seconds)
+    pub fn set_nonce_hold_timeout(&mut self, timeout: Duration) {
+        self.nonce_hold_timeout = timeout;
+    }
+
+    /// How signatures are written in transaction payloads (default
+    /// [`ByteArray`](SignatureEncoding::ByteArray) until a captured payload
+    /// confirms a string encoding)
+    ///
+    /// Auth tokens keep plain hex whatever this is set to.
+    pub fn set_signature_encoding(&mut self, encoding: SignatureEncoding) {
+        self.signature_encoding = encoding;
+    }
+
+    pub fn signature_encoding(&self) -> SignatureEncoding {
+        self.signature_encoding
+    }
+
+    /// Nonces from the nonce store whose submission result isn't known yet
+    pub fn in_flight_nonces(&self) -> usize {
+        self.in_flight.count(self.nonce_key())
+    }
+
+    /// Default expiry for transactions of `kind` signed without `expired_at`
+    ///
+    /// Kinds without a default use 10 minutes.
+    pub fn set_default_expiry(&mut self, kind: TxKind, expiry: Duration) {
+        self.default_expiries.insert(kind, expiry);
+    }
+
+    /// Validity a transaction must have left when it is signed and when it
+    /// is sent; `None` disables the check
+    ///
+    /// Defaults to 5 seconds. Transactions closer to `expired_at` fail with
+    /// [`LighterError::ExpiredAtInvalid`] instead of wasting a nonce.
+    pub fn set_min_remaining_validity(&mut self, min: Option<Duration>) {
+        let min_ms = min.map(|min| min.as_millis() as u64);
+        // Any validity is valid, so this can't fail
+        let _ = self.update_config(|config| config.min_remaining_validity_ms = min_ms);
+    }
+
+    /// Settings read on every use, see [`RuntimeConfig`]
+    pub fn runtime_config(&self) -> &RuntimeConfig {
+        &self.runtime_config
+    }
+
+    /// Read settings from `config`, e.g. one shared with a `WsClient`
+    pub fn set_runtime_config(&mut self, config: RuntimeConfig) {
+        self.runtime_config = config;
+    }
+
+    /// Change the runtime settings; the next transaction is checked under them
+    ///
+    /// Nothing changes if the result is invalid.
+    pub fn update_config(&self, f: impl FnOnce(&mut ClientRuntimeConfig)) -> Result<()> {
+        self.runtime_config.update(f)
+    }
+
+    /// Correct default expiries by the offset of the local clock from the
+    /// server's; `None` disables it
+    ///
+    /// The offset is measured with [`check_clock_skew`](Self::check_clock_skew)
+    /// when a transaction is filled in and the last measurement is older than
+    /// `ttl`, and applied only if it exceeds `threshold`. Expiries set by the
+    /// caller are left alone.
+    pub fn set_clock_skew_compensation(&mut self, config: Option<ClockSkewConfig>) {
+        self.clock_skew = config.map(|config| ClockSkew {
+            config,
+            measured: Mutex::new(None),
+        });
+    }
+
+    /// Offset of the server clock from the local one
+    ///
+    /// Positive when the local clock is behind. Requests and responses are
+    /// assumed to take equally long.
+    pub async fn check_clock_skew(&self) -> Result<chrono::Duration> {
+        let client = self.api_client.as_ref().ok_or_else(|| {
+            LighterError::MissingField("HTTPClient is required to check the clock skew".to_string())
+        })?;
+        let sent = Utc::now();
+        let server_time = client.get_server_time().await?;
+        let received = Utc::now();
+        Ok(server_time - (sent + (received - sent) / 2))
+    }
+
+    /// Measure the clock offset again if compensating and the last
+    /// measurement expired
+    ///
+    /// A failed measurement counts as no offset until the next one.
+    async fn refresh_clock_skew(&self) {
+        let Some(skew) = &self.clock_skew else {
+            return;
+        };
+        if self.api_client.is_none() || !skew.is_stale() {
+            return;
+        }
+        let offset = self.check_clock_skew().await.unwrap_or_else(|e| {
+            eprintln!("Failed to check clock skew: {}", e);
+            chrono::Duration::zero()
+        });
+        if offset
+            .abs()
+            .to_std()
+            .is_ok_and(|size| size > skew.config.threshold)
+        {
+            println!(
+                "Local clock is off by {}ms from the server; correcting expiries",
+                -offset.num_milliseconds()
+            );
+        }
+        *skew.measured.lock().unwrap() = Some((tokio::time::Instant::now(), offset));
+    }
+
+    /// Read the time for default expiries from `clock` instead of the
+    /// system clock, e.g. a [`ReplayClock`](crate::clock::ReplayClock)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Current time, by the server clock when compensating
+    fn now(&self) -> DateTime<Utc> {
+        let offset = self
+            .clock_skew
+            .as_ref()
+            .map_or(chrono::Duration::zero(), ClockSkew::applied);
+        self.clock.now() + offset
+    }
+
+    /// Current time in milliseconds, see [`now`](Self::now)
+    pub(crate) fn now_ms(&self) -> i64 {
+        self.now().timestamp_millis()
+    }
+
+    /// `expired_at` from now using the default expiry of `kind`
+    fn default_expired_at(&self, kind: Option<TxKind>) -> i64 {
+        let expiry_ms = kind
+            .and_then(|k| self.default_expiries.get(&k))
+            .map_or(DEFAULT_TX_EXPIRY_MS, |d| d.as_millis() as i64);
+        (self.now_ms() + expiry_ms) - 1000
+    }
+
+    /// Fail if `expired_at` is closer than the minimum remaining validity
+    fn check_remaining_validity(&self, expired_at: i64) -> Result<()> {
+        let Some(min) = self.runtime_config.get().min_remaining_validity() else {
+            return Ok(());
+        };
+        let min_remaining_ms = min.as_millis() as i64;
+        let remaining_ms = expired_at - self.now_ms();
+        if remaining_ms < min_remaining_ms {
+            return Err(LighterError::ExpiredAtInvalid {
+                remaining_ms,
+                min_remaining_ms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Throttle new orders and grouped orders through `throttle`
+    ///
+    /// Orders are admitted before a nonce is allocated, so waiting in
+    /// [`ThrottleMode::Wait`](crate::throttle::ThrottleMode::Wait) doesn't
+    /// shorten their expiry. Rates set in the runtime config replace those
+    /// `throttle` was created with.
+    pub fn set_order_throttle(&mut self, throttle: Arc<OrderThrottle>) {
+        self.order_throttle = Some(throttle);
+    }
+
+    /// Get the configured order throttle, if any
+    pub fn order_throttle(&self) -> Option<&Arc<OrderThrottle>> {
+        self.order_throttle.as_ref()
+    }
+
+    /// Record every signed modify in `log`
+    pub fn set_amendment_log(&mut self, log: Arc<AmendmentLog>) {
+        self.amendment_log = Some(log);
+    }
+
+    /// Get the amendment log, if any
+    pub fn amendment_log(&self) -> Option<&Arc<AmendmentLog>> {
+        self.amendment_log.as_ref()
+    }
+
+    /// Refuse new orders in markets that `statuses` says won't take them
+    ///
+    /// Orders fail with [`LighterError::MarketNotActive`] before a nonce is
+    /// used; reduce-only orders still go through in a
+    /// [`MarketStatus::ReduceOnly`] market. Markets without a known status
+    /// aren't gated.
+    pub fn set_market_statuses(&mut self, statuses: MarketStatuses) {
+        self.market_statuses = Some(statuses);
+    }
+
+    /// Get the market statuses orders are gated on, if any
+    pub fn market_statuses(&self) -> Option<&MarketStatuses> {
+        self.market_statuses.as_ref()
+    }
+
+    /// Consult `breaker` before every submission and record its outcome
+    ///
+    /// While the circuit is open, sending fails with
+    /// [`LighterError::CircuitOpen`] and the nonce stays unused. Accepted
+    /// transactions count as successes; rejections and failed requests as
+    /// failures. Errors from local checks count as neither.
+    pub fn set_circuit_breaker(&mut self, breaker: Arc<CircuitBreaker>) {
+        self.circuit_breaker = Some(breaker);
+    }
+
+    /// Get the circuit breaker submissions go through, if any
+    pub fn circuit_breaker(&self) -> Option<&Arc<CircuitBreaker>> {
+        self.circuit_breaker.as_ref()
+    }
+
+    /// Get the configured nonce store, if any
+    pub fn nonce_store(&self) -> Option<&Arc<dyn NonceStore>> {
+        self.nonce_store.as_ref()
+    }
+
+    /// Reconcile the nonce store with the server's next nonce for the
+    /// current account and API key, taking the larger of the two
+    pub async fn sync_nonce_store(&self) -> Result<i64> {
+        let store = self.nonce_store.as_ref().ok_or_else(|| {
+            LighterError::InvalidConfiguration("No nonce store configured".to_string())
+        })?;
+        self.reconcile_nonce(store, self.account_index, self.api_key_index())
+            .await
+    }
+
+    /// Start the nonce store over from the server's next nonce
+    ///
+    /// Transactions being sent are waited for; ones signed with nonces from
+    /// the store and not sent yet fail with [`LighterError::NonceGap`] when
+    /// they are. Returns the new next nonce.
+    pub async fn refresh_nonce(&self) -> Result<i64> {
+        let store = self.nonce_store.as_ref().ok_or_else(|| {
+            LighterError::InvalidConfiguration("No nonce store configured".to_string())
+        })?;
+        let client = self
+            .api_client
+            .as_ref()
+            .ok_or_else(|| LighterError::MissingField("HTTPClient is not available".to_string()))?;
+        let key = self.nonce_key();
+        self.in_flight.invalidate(key).await;
+        let next = client
+            .get_next_nonce(AccountIndex::new(key.0)?, ApiKeyIndex::new(key.1)?)
+            .await?;
+        self.with_nonce_store(store, move |in_flight, store| {
+            in_flight.reset(store.as_ref(), key, next)
+        })
+        .await?;
+        Ok(next)
+    }
+
+    fn nonce_key(&self) -> (i64, u8) {
+        (self.account_index, self.api_key_index())
+    }
+
+    /// Run `call` on the nonce store, on the blocking thread pool if the
+    /// store blocks on I/O
+    async fn with_nonce_store<R, F>(&self, store: &Arc<dyn NonceStore>, call: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Arc<InFlightNonces>, &Arc<dyn NonceStore>) -> Result<R> + Send + 'static,
+    {
+        if !store.blocks() {
+            return call(&self.in_flight, store);
+        }
+        let (in_flight, store) = (self.in_flight.clone(), store.clone());
+        tokio::task::spawn_blocking(move || call(&in_flight, &store))
+            .await
+            .map_err(|e| LighterError::Other(format!("Nonce store call failed: {}", e)))?
+    }
+
+    async fn reconcile_nonce(
+        &self,
+        store: &Arc<dyn NonceStore>,
+        account_index: i64,
+        api_key_index: u8,
+    ) -> Result<i64> {
+        let client = self.api_client.as_ref().ok_or_else(|| {
+            LighterError::MissingField(
+                "nonce store is not initialized and HTTPClient is not available".to_string(),
+            )
+        })?;
+        let server_next = client
+            .get_next_nonce(
+                AccountIndex::new(account_index)?,
+                ApiKeyIndex::new(api_key_index)?,
+            )
+            .await?;
+        self.with_nonce_store(store, move |_, store| {
+            store.reconcile(account_index, api_key_index, server_next)
+        })
+        .await
+    }
+
+    async fn allocate_nonce(&self, account_index: i64, api_key_index: u8) -> Result<i64> {
+        if let Some(store) = &self.nonce_store {
+            let key = (account_index, api_key_index);
+            let (policy, hold) = (self.nonce_gap_policy, self.nonce_hold_timeout);
+            let allocate = move |in_flight: &Arc<InFlightNonces>, store: &Arc<dyn NonceStore>| {
+                in_flight.allocate(store.as_ref(), key, policy, hold)
+            };
+            if let Some(nonce) = self.with_nonce_store(store, allocate).await? {
+                return Ok(nonce);
+            }
+            self.reconcile_nonce(store, account_index, api_key_index)
+                .await?;
+            return self
+                .with_nonce_store(store, allocate)
+                .await?
+                .ok_or_else(|| {
+                    LighterError::Other("Nonce store failed to allocate a nonce".to_string())
+                });
+        }
+
+        if let Some(client) = &self.api_client {
+            client
+                .get_next_nonce(
+                    AccountIndex::new(account_index)?,
+                    ApiKeyIndex::new(api_key_index)?,
+                )
+                .await
+        } else {
+            Err(LighterError::MissingField(
+                "nonce was not provided and HTTPClient is not available".to_string(),
+            ))
+        }
+    }
+
+    /// Fill in default transaction options
+    ///
+    /// A missing `expired_at` defaults to 10 minutes from now.
+    pub async fn fill_default_opts(&self, opts: Option<TransactOpts>) -> Result<TransactOpts> {
+        self.fill_opts(None, opts).await
+    }
+
+    /// Fill in default transaction options for a transaction of `kind`
+    ///
+    /// A missing `expired_at` uses the kind's
+    /// [default expiry](Self::set_default_expiry).
+    pub async fn fill_default_opts_for(
+        &self,
+        kind: TxKind,
+        opts: Option<TransactOpts>,
+    ) -> Result<TransactOpts> {
+        self.fill_opts(Some(kind), opts).await
+    }
+
+    async fn fill_opts(
+        &self,
+        kind: Option<TxKind>,
+        opts: Option<TransactOpts>,
+    ) -> Result<TransactOpts> {
+        let mut opts = opts.unwrap_or_default();
+        self.refresh_clock_skew().await;
+
+        if opts.expired_at == 0 {
+            opts.expired_at = self.default_expired_at(kind);
+        } else if opts.auto_convert_seconds && ExpiryMs::looks_like_seconds(opts.expired_at) {
+            opts.expired_at *= 1000;
+        }
+        validate_timestamp_ms(opts.expired_at, "expired_at")?;
+        self.check_remaining_validity(opts.expired_at)?;
+
+        if opts.from_account_index.is_none() {
+            opts.from_account_index = Some(self.account_index);
+        }
+
+        if opts.api_key_index.is_none() {
+            opts.api_key_index = Some(self.api_key_index());
+        }
+
+        if opts.nonce.is_none() {
+            let nonce = self
+                .allocate_nonce(opts.require_account_index()?, opts.require_api_key_index()?)
+                .await?;
+            opts.nonce = Some(nonce);
+        }
+
+        Ok(opts)
+    }
+
+    /// Admit grouped orders: every resting leg is checked for self-crossing,
+    /// and each market takes capacity once
+    ///
+    /// Trigger legs (take-profit, stop-loss) don't rest until triggered and
+    /// are not checked.
+    async fn admit_grouped(
+        &self,
+        throttle: &OrderThrottle,
+        req: &CreateGroupedOrdersTxReq,
+    ) -> Result<()> {
+        let config = self.throttle_config(throttle);
+        let mut markets = Vec::new();
+        for order in &req.orders {
+            if config.prevent_self_cross && order.trigger_price == 0 {
+                throttle.check_self_cross(order.market_index, order.is_ask == 1, order.price)?;
+            }
+            if !markets.contains(&order.market_index) {
+                markets.push(order.market_index);
+            }
+        }
+        for market_index in markets {
+            throttle.acquire_with(&config, market_index).await?;
+        }
+        Ok(())
+    }
+
+    /// Throttle settings of the runtime config, or those `throttle` was created with
+    fn throttle_config(&self, throttle: &OrderThrottle) -> ThrottleConfig {
+        self.runtime_config
+            .get()
+            .order_throttle
+            .unwrap_or(*throttle.config())
+    }
+
+    /// Reject an `order_expiry` given in seconds or beyond `MAX_TIMESTAMP`
+    fn check_order_expiry(order_expiry: i64) -> Result<()> {
+        if order_expiry > NIL_ORDER_EXPIRY {
+            validate_timestamp_ms(order_expiry, "order_expiry")?;
+        }
+        Ok(())
+    }
+
+    /// Check a reduce-only order against the position supplied in `opts`
+    ///
+    /// Skipped when the order is not reduce-only or no position is known for
+    /// its market. With `auto_clamp_reduce_only` the order size is shrunk to
+    /// the position size instead of rejected.
+    fn check_reduce_only(order: &mut OrderInfo, opts: &TransactOpts) -> Result<()> {
+        if order.reduce_only != 1 {
+            return Ok(());
+        }
+        if let Some(position) = opts.position_for(order.market_index) {
+            order.base_amount = position.check_reduce_only(
+                order.is_ask,
+                order.base_amount,
+                opts.auto_clamp_reduce_only,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Construct and sign a create order transaction
+    pub async fn create_order(
+        &self,
+        req: &CreateOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        if let Some(statuses) = &self.market_statuses {
+            statuses.check(u32::from(req.market_index), req.reduce_only == 1)?;
+        }
+        if let Some(throttle) = &self.order_throttle {
+            let config = self.throttle_config(throttle);
+            throttle
+                .admit_with(&config, req.market_index, req.is_ask == 1, req.price)
+                .await?;
+        }
+        let opts = self.fill_default_opts_for(TxKind::Order, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a cancel order transaction
+    pub async fn cancel_order(
+        &self,
+        req: &CancelOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelOrderTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Order, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a modify order transaction
+    pub async fn modify_order(
+        &self,
+        req: &ModifyOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2ModifyOrderTxInfo> {
+        self.modify_order_audited(req, ModifyAudit::default(), opts)
+            .await
+    }
+
+    /// Construct and sign a modify order transaction, recording `audit` with
+    /// it in the [amendment log](Self::set_amendment_log)
+    pub async fn modify_order_audited(
+        &self,
+        req: &ModifyOrderTxReq,
+        audit: ModifyAudit,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2ModifyOrderTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Order, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        if let Some(log) = &self.amendment_log {
+            log.record(req, audit, tx_info.signed_hash.clone());
+        }
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a cancel all orders transaction
+    pub async fn cancel_all_orders(
+        &self,
+        req: &CancelAllOrdersTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelAllOrdersTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Order, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Cancel every open order of one market, leaving other markets alone
+    ///
+    /// The protocol's cancel-all has no market filter, so this signs and
+    /// sends one cancel per order of `market_index` found in `account`
+    /// (e.g. from [`WsClient::get_account_snapshot`](crate::ws_client::WsClient::get_account_snapshot)).
+    /// Nonces are sequential: from the nonce store if one is set, otherwise
+    /// starting at `opts.nonce` or the server's next nonce and advancing
+    /// after each accepted cancel. Returns one outcome per order.
+    pub async fn cancel_all_orders_for_market(
+        &self,
+        market_index: MarketIndex,
+        account: &AccountSnapshot,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<CancelOutcome>> {
+        let market_index = market_index.get();
+        let mut order_indices: Vec<i64> = account
+            .orders
+            .values()
+            .flatten()
+            .filter(|order| order.market_index == u32::from(market_index))
+            .map(|order| order.order_index)
+            .collect();
+        order_indices.sort_unstable();
+        order_indices.dedup();
+
+        let opts = opts.unwrap_or_default();
+        let mut next_nonce = opts.nonce;
+        let mut outcomes = Vec::with_capacity(order_indices.len());
+
+        for order_index in order_indices {
+            let req = CancelOrderTxReq::by_order_index(market_index, order_index)?;
+            let order_opts = TransactOpts {
+                nonce: next_nonce,
+                ..opts.clone()
+            };
+
+            let result = async {
+                let tx = self.cancel_order(&req, Some(order_opts)).await?;
+                let response = self.send_transaction(&tx).await?;
+                Ok((tx.nonce, response))
+            }
+            .await;
+
+            let result = match result {
+                Ok((nonce, response)) => {
+                    if self.nonce_store.is_none() {
+                        // Rejected cancels leave the nonce unused
+                        next_nonce = Some(if response.code == 200 {
+                            nonce + 1
+                        } else {
+                            nonce
+                        });
+                    }
+                    Ok(response)
+                }
+                Err(e) => Err(e),
+            };
+            outcomes.push(CancelOutcome {
+                order_index,
+                result,
+            });
+        }
+
+        Ok(outcomes)
+    }
+
+    /// [`cancel_all_orders_for_market`](Self::cancel_all_orders_for_market)
+    /// with a raw market index, validating its range
+    #[deprecated(note = "use `cancel_all_orders_for_market` with `MarketIndex`")]
+    pub async fn cancel_all_orders_for_market_raw(
+        &self,
+        market_index: u8,
+        account: &AccountSnapshot,
+        opts: Option<TransactOpts>,
+    ) -> Result<Vec<CancelOutcome>> {
+        self.cancel_all_orders_for_market(MarketIndex::new(market_index)?, account, opts)
+            .await
+    }
+
+    /// Sign and send two orders, cancelling one if the other fails
+    ///
+    /// Both legs are signed before anything is sent, with consecutive nonces
+    /// (or two from the nonce store), then submitted concurrently. If only
+    /// one leg is accepted, it is cancelled by its client order index,
+    /// best effort and within `PAIRED_ORDER_UNWIND_TIMEOUT_MS`. The pair
+    /// is not atomic: the accepted leg may fill before the cancel lands.
+    pub async fn create_paired_orders(
+        &self,
+        leg_a: &CreateOrderTxReq,
+        leg_b: &CreateOrderTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<PairedResult> {
+        let tx_a = self.create_order(leg_a, opts.clone()).await?;
+        let opts_b = self.following_opts(&opts, tx_a.nonce);
+        let tx_b = self.create_order(leg_b, Some(opts_b)).await?;
+
+        let (result_a, result_b) =
+            tokio::join!(self.send_transaction(&tx_a), self.send_transaction(&tx_b));
+        let a = PairedLeg {
+            tx: tx_a,
+            result: result_a,
+        };
+        let b = PairedLeg {
+            tx: tx_b,
+            result: result_b,
+        };
+
+        // The cancel follows the last nonce in use; a rejected second leg
+        // leaves its own unused
+        let (accepted, failed, last_nonce) = match (a.is_accepted(), b.is_accepted()) {
+            (true, false) => (&a, &b, a.tx.nonce),
+            (false, true) => (&b, &a, b.tx.nonce),
+            _ => {
+                return Ok(PairedResult {
+                    a,
+                    b,
+                    unwind: None,
+                    unwound: false,
+                })
+            }
+        };
+
+        eprintln!(
+            "Paired order leg in market {} failed; cancelling the leg in market {}",
+            failed.tx.order_info.market_index, accepted.tx.order_info.market_index
+        );
+        let unwind_opts = self.following_opts(&opts, last_nonce);
+        let req = CancelOrderTxReq::by_client_order_index(
+            accepted.tx.order_info.market_index,
+            accepted.tx.order_info.client_order_index,
+        );
+        let unwind = tokio::time::timeout(
+            Duration::from_millis(PAIRED_ORDER_UNWIND_TIMEOUT_MS),
+            async {
+                let tx = self.cancel_order(&req?, Some(unwind_opts)).await?;
+                self.send_transaction(&tx).await
+            },
+        )
+        .await
+        .unwrap_or(Err(LighterError::Timeout));
+        let unwound = matches!(&unwind, Ok(response) if response.code == 200);
+
+        Ok(PairedResult {
+            a,
+            b,
+            unwind: Some(unwind),
+            unwound,
+        })
+    }
+
+    /// Options for the transaction after one signed with `nonce`
+    ///
+    /// The nonce store allocates the next one when it is in use and the
+    /// caller didn't pick nonces; otherwise it is `nonce + 1`.
+    fn following_opts(&self, opts: &Option<TransactOpts>, nonce: i64) -> TransactOpts {
+        let opts = opts.clone().unwrap_or_default();
+        let from_store = self.nonce_store.is_some() && opts.nonce.is_none();
+        TransactOpts {
+            nonce: (!from_store).then_some(nonce + 1),
+            ..opts
+        }
+    }
+
+    /// Construct and sign a create grouped orders transaction
+    pub async fn create_grouped_orders(
+        &self,
+        req: &CreateGroupedOrdersTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateGroupedOrdersTxInfo> {
+        if let Some(statuses) = &self.market_statuses {
+            for order in &req.orders {
+                statuses.check(u32::from(order.market_index), order.reduce_only == 1)?;
+            }
+        }
+        if let Some(throttle) = &self.order_throttle {
+            self.admit_grouped(throttle, req).await?;
+        }
+        let opts = self.fill_default_opts_for(TxKind::Order, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a transfer transaction
+    pub async fn transfer(
+        &self,
+        req: &TransferTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2TransferTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Transfer, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a transfer paying the currently quoted fee
+    ///
+    /// `usdc_amount` is converted to protocol units and checked against the
+    /// transfer limits. Fails with [`LighterError::TransferFeeAboveLimit`]
+    /// when the quoted fee exceeds `max_fee` USDC.
+    pub async fn transfer_auto_fee(
+        &self,
+        to_account_index: AccountIndex,
+        usdc_amount: Decimal,
+        memo: [u8; 32],
+        max_fee: Decimal,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2TransferTxInfo> {
+        let usdc_amount = checked_transfer_amount(usdc_amount)?;
+        let client = self.api_client.as_ref().ok_or_else(|| {
+            LighterError::MissingField(
+                "HTTPClient is required to quote the transfer fee".to_string(),
+            )
+        })?;
+        let fee = client
+            .get_transfer_fee_info(AccountIndex::new(self.account_index)?, to_account_index)
+            .await?
+            .transfer_fee;
+        let max_fee = max_fee
+            .checked_mul(Decimal::from(ONE_USDC))
+            .and_then(|limit| limit.floor().to_i64())
+            .unwrap_or(i64::MAX);
+        if fee > max_fee {
+            return Err(LighterError::TransferFeeAboveLimit { fee, max_fee });
+        }
+
+        self.transfer(
+            &TransferTxReq::from_raw_units(to_account_index.get(), usdc_amount, fee, memo),
+            opts,
+        )
+        .await
+    }
+
+    /// [`transfer_auto_fee`](Self::transfer_auto_fee) with a raw account
+    /// index, validating its range
+    #[deprecated(note = "use `transfer_auto_fee` with `AccountIndex`")]
+    pub async fn transfer_auto_fee_raw(
+        &self,
+        to_account_index: i64,
+        usdc_amount: Decimal,
+        memo: [u8; 32],
+        max_fee: Decimal,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2TransferTxInfo> {
+        self.transfer_auto_fee(
+            AccountIndex::new(to_account_index)?,
+            usdc_amount,
+            memo,
+            max_fee,
+            opts,
+        )
+        .await
+    }
+
+    /// Construct and sign a withdraw transaction
+    pub async fn withdraw(
+        &self,
+        req: &WithdrawTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2WithdrawTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Transfer, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a change public key transaction
+    pub async fn change_pub_key(
+        &self,
+        req: &ChangePubKeyReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2ChangePubKeyTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Admin, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Register `new_api_public_key` under this client's API key index and send it
+    ///
+    /// Builds and signs a change public key transaction, adds the L1
+    /// wallet's signature over its [`OnboardingIntent`] and submits it. The
+    /// new key is only usable once the transaction is accepted.
+    pub async fn register_api_key_with_l1(
+        &self,
+        l1_signer: &L1Signer,
+        new_api_public_key: &[u8],
+        opts: Option<TransactOpts>,
+    ) -> Result<TxResponse> {
+        if new_api_public_key.len() != PUBLIC_KEY_LENGTH {
+            return Err(LighterError::PubKeyInvalid);
+        }
+        let req = ChangePubKeyReq {
+            pub_key: new_api_public_key.to_vec(),
+        };
+        let mut tx = self.change_pub_key(&req, opts).await?;
+        let signature = l1_signer.sign_onboarding_message(&OnboardingIntent::from_tx(&tx))?;
+        tx.l1_sig = Some(signature.to_hex());
+        self.send_transaction(&tx).await
+    }
+
+    /// Construct and sign an update leverage transaction
+    pub async fn update_leverage(
+        &self,
+        req: &UpdateLeverageTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2UpdateLeverageTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Admin, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign an update margin transaction
+    pub async fn update_margin(
+        &self,
+        req: &UpdateMarginTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2UpdateMarginTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Transfer, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a create sub account transaction
+    pub async fn create_sub_account(
+        &self,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateSubAccountTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Admin, opts).await?;
+
+        let mut tx_info = L2CreateSubAccountTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        };
+
+        tx_info.validate()?;
+        let msg_hash = tx_info.hash(self.chain_id)?;
+        let signature = self.key_manager().sign(&msg_hash)?;
+        tx_info.sig = Some(Signature::new(signature, self.signature_encoding));
+        tx_info.signed_hash = Some(hex::encode(&msg_hash));
+
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a create public pool transaction
+    pub async fn create_public_pool(
+        &self,
+        req: &CreatePublicPoolTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreatePublicPoolTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Admin, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign an update public pool transaction
+    pub async fn update_public_pool(
+        &self,
+        req: &UpdatePublicPoolTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2UpdatePublicPoolTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Admin, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Construct and sign a mint shares transaction
+    pub async fn mint_shares(
+        &self,
+        req: &MintSharesTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2MintSharesTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Transfer, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    /// Burn all shares held in a public pool except `leave_dust`
+    ///
+    /// The position is read over REST for the account in `opts` (this
+    /// client's account by default) before anything is signed, so an
+    /// account without shares to burn gets an error and uses no nonce.
+    pub async fn burn_all_shares(
+        &self,
+        public_pool_index: AccountIndex,
+        leave_dust: i64,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2BurnSharesTxInfo> {
+        let client = self.api_client.as_ref().ok_or_else(|| {
+            LighterError::MissingField(
+                "HTTPClient is required to look up the pool position".to_string(),
+            )
+        })?;
+        let account_index = opts
+            .as_ref()
+            .and_then(|o| o.from_account_index)
+            .unwrap_or(self.account_index);
+        let position = client
+            .get_pool_position(AccountIndex::new(account_index)?, public_pool_index)
+            .await?;
+        let req = BurnSharesTxReq::for_position(&position, leave_dust)?;
+        self.burn_shares(&req, opts).await
+    }
+
+    /// [`burn_all_shares`](Self::burn_all_shares) with a raw pool index,
+    /// validating its range
+    #[deprecated(note = "use `burn_all_shares` with `AccountIndex`")]
+    pub async fn burn_all_shares_raw(
+        &self,
+        public_pool_index: i64,
+        leave_dust: i64,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2BurnSharesTxInfo> {
+        self.burn_all_shares(AccountIndex::new(public_pool_index)?, leave_dust, opts)
+            .await
+    }
+
+    /// Construct and sign a burn shares transaction
+    pub async fn burn_shares(
+        &self,
+        req: &BurnSharesTxReq,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2BurnSharesTxInfo> {
+        let opts = self.fill_default_opts_for(TxKind::Transfer, opts).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        self.sign_prepared(&mut tx_info)?;
+        Ok(tx_info)
+    }
+
+    // ========== Helper Methods ==========
+
+    /// Create an order of the given kind from [`OrderParams`]
+    ///
+    /// Trigger kinds (take profit and stop loss) require `params.trigger_price`,
+    /// limit and market orders must not set it.
+    pub async fn create_order_with(
+        &self,
+        params: OrderParams,
+        kind: OrderKind,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        if kind.is_trigger() != params.trigger_price.is_some() {
+            return Err(LighterError::OrderTriggerPriceInvalid);
+        }
+
+        self.create_order(&params.to_request(kind), opts).await
+    }
+
+    /// Create a reduce-only market order closing `position`, or `portion` of it
+    ///
+    /// The size is taken from the position's own base units and the side is
+    /// the opposite of the position; `price` is the protection price. See
+    /// [`Position::close_amount`] for how portions are rounded. A flat
+    /// position fails without using a nonce.
+    pub async fn close_position(
+        &self,
+        market_index: MarketIndex,
+        position: &Position,
+        price: Price,
+        portion: Option<Decimal>,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        check_position_market(position, market_index)?;
+        let base_amount = position.close_amount(portion)?;
+        let req = OrderParams::new(market_index, position.closing_side(), base_amount, price)
+            .reduce_only(true)
+            .to_request(OrderKind::Market);
+        self.create_order(&req, opts).await
+    }
+
+    /// [`close_position`](Self::close_position) with raw integers, validating
+    /// their ranges
+    #[deprecated(note = "use `close_position` with `MarketIndex` and `Price`")]
+    pub async fn close_position_raw(
+        &self,
+        market_index: u8,
+        position: &Position,
+        price: u32,
+        portion: Option<Decimal>,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        let (market_index, price) = (MarketIndex::new(market_index)?, Price::new(price)?);
+        self.close_position(market_index, position, price, portion, opts)
+            .await
+    }
+
+    /// Attach a one-cancels-the-other take-profit and stop-loss pair to `position`
+    ///
+    /// Both legs are reduce-only limit triggers sized to the whole position on
+    /// its closing side; see [`TpSl::to_request`] for the checks against the
+    /// mark. The group is signed, and sent too when `submit` is set. Nothing
+    /// uses a nonce if the checks fail.
+    pub async fn attach_tp_sl(
+        &self,
+        market_index: MarketIndex,
+        position: &Position,
+        tp_sl: TpSl,
+        submit: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<AttachedTpSl> {
+        check_position_market(position, market_index)?;
+        let req = tp_sl.to_request(position)?;
+        let tx = self.create_grouped_orders(&req, opts).await?;
+        let response = if submit {
+            Some(self.send_transaction(&tx).await?)
+        } else {
+            None
+        };
+        Ok(AttachedTpSl { tx, response })
+    }
+
+    /// [`attach_tp_sl`](Self::attach_tp_sl) with a raw market index,
+    /// validating its range
+    #[deprecated(note = "use `attach_tp_sl` with `MarketIndex`")]
+    pub async fn attach_tp_sl_raw(
+        &self,
+        market_index: u8,
+        position: &Position,
+        tp_sl: TpSl,
+        submit: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<AttachedTpSl> {
+        self.attach_tp_sl(
+            MarketIndex::new(market_index)?,
+            position,
+            tp_sl,
+            submit,
+            opts,
+        )
+        .await
+    }
+
+    /// Create a limit order (convenience wrapper around create_order)
+    ///
+    /// Limit orders are placed on the order book at a specific price
+    #[deprecated(note = "use `create_order_with` with `OrderParams` and `OrderKind::Limit`")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        price: u32,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::Limit,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            None,
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Create a market order (convenience wrapper around create_order)
+    ///
+    /// Market orders execute immediately at the best available price
+    #[deprecated(note = "use `create_order_with` with `OrderParams` and `OrderKind::Market`")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_market_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        price: u32,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::Market,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            None,
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Create a take profit order
+    #[deprecated(note = "use `create_order_with` with `OrderParams` and `OrderKind::TakeProfit`")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_tp_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u32,
+        price: u32,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::TakeProfit,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            Some(trigger_price),
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Create a take profit limit order
+    #[deprecated(
+        note = "use `create_order_with` with `OrderParams` and `OrderKind::TakeProfitLimit`"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_tp_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u32,
+        price: u32,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::TakeProfitLimit,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            Some(trigger_price),
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Create a stop loss order
+    #[deprecated(note = "use `create_order_with` with `OrderParams` and `OrderKind::StopLoss`")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_sl_order(
         &self,
         market_index: u8,
         client_order_index: i64,
         base_amount: i64,
+        trigger_price: u32,
+        price: u32,
+        is_ask: u8,
+        reduce_only: bool,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CreateOrderTxInfo> {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::StopLoss,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            Some(trigger_price),
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Create a stop loss limit order
+    #[deprecated(
+        note = "use `create_order_with` with `OrderParams` and `OrderKind::StopLossLimit`"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_sl_limit_order(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        base_amount: i64,
+        trigger_price: u32,
         price: u32,
         is_ask: u8,
         reduce_only: bool,
         opts: Option<TransactOpts>,
     ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
+        // Passed through unchecked so out-of-range values still fail validation
+        let req = raw_order_request(
+            OrderKind::StopLossLimit,
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            Some(trigger_price),
+            is_ask,
+            reduce_only,
+        );
+
+        self.create_order(&req, opts).await
+    }
+
+    /// Update leverage with a user-friendly leverage parameter
+    ///
+    /// # Arguments
+    /// * `market_index` - The market to update leverage for
+    /// * `leverage` - Leverage multiplier (e.g., 5 for 5x, 10 for 10x)
+    /// * `margin_mode` - MARGIN_MODE_CROSS or MARGIN_MODE_ISOLATED
+    /// * `opts` - Optional transaction options
+    pub async fn update_leverage_with_multiplier(
+        &self,
+        market_index: MarketIndex,
+        leverage: u16,
+        margin_mode: u8,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2UpdateLeverageTxInfo> {
+        if leverage == 0 {
+            return Err(LighterError::ValidationError(
+                "Leverage must be greater than 0".to_string(),
+            ));
+        }
+
+        // Convert leverage to initial margin fraction
+        // IMF = 10,000 / leverage (Python: imf = int(10_000 / leverage))
+        let initial_margin_fraction = 10_000 / leverage;
+
+        let req = UpdateLeverageTxReq {
+            market_index: market_index.get(),
+            initial_margin_fraction,
+            margin_mode,
+        };
+
+        self.update_leverage(&req, opts).await
+    }
+
+    /// [`update_leverage_with_multiplier`](Self::update_leverage_with_multiplier)
+    /// with a raw market index, validating its range
+    #[deprecated(note = "use `update_leverage_with_multiplier` with `MarketIndex`")]
+    pub async fn update_leverage_with_multiplier_raw(
+        &self,
+        market_index: u8,
+        leverage: u16,
+        margin_mode: u8,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2UpdateLeverageTxInfo> {
+        self.update_leverage_with_multiplier(
+            MarketIndex::new(market_index)?,
+            leverage,
+            margin_mode,
+            opts,
+        )
+        .await
+    }
+
+    /// Send a transaction built by this client, signing it again first if it
+    /// is too close to expiry
+    ///
+    /// The new signature keeps the nonce and uses the default expiry for the
+    /// transaction's kind, so the nonce isn't wasted on a rejected send.
+    ///
+    /// With [`NonceGapPolicy::Renumber`], a transaction behind an unused
+    /// nonce is signed again with its shifted nonce.
+    pub async fn send_transaction_resigning<T: Resignable>(
+        &self,
+        tx_info: &mut T,
+    ) -> Result<TxResponse> {
+        let guard = self.nonce_turn(tx_info).await?;
+        let resign = match guard.turn {
+            Turn::Send {
+                nonce,
+                shifted_past: Some(_),
+                ..
+            } => {
+                eprintln!(
+                    "Re-signing transaction with nonce {} behind an unused nonce",
+                    nonce
+                );
+                tx_info.set_nonce(nonce);
+                true
+            }
+            _ => match tx_info
+                .get_expired_at()
+                .map(|at| self.check_remaining_validity(at))
+            {
+                Some(Err(LighterError::ExpiredAtInvalid { remaining_ms, .. })) => {
+                    eprintln!(
+                        "Re-signing transaction with {}ms of validity left",
+                        remaining_ms
+                    );
+                    true
+                }
+                _ => false,
+            },
+        };
+        if resign {
+            if let Err(e) = self.resign(tx_info) {
+                guard.settle(false).await?;
+                return Err(e);
+            }
+        }
+        self.submit(tx_info, guard).await
+    }
+
+    fn resign<T: Resignable>(&self, tx_info: &mut T) -> Result<()> {
+        tx_info.set_expired_at(self.default_expired_at(TxKind::of(tx_info.get_tx_type())));
+        self.sign_in_place(tx_info)
+    }
+
+    /// Build, validate and hash a transaction without signing it
+    ///
+    /// The nonce must be given in `opts`; other defaults are filled in as
+    /// for the signing methods. The result has `signed_hash` set to the hash
+    /// a signature would cover and `sig` left `None`. Order throttling is not
+    /// applied. [`sign_prepared`](Self::sign_prepared) finishes the
+    /// transaction exactly as the one-shot method would have.
+    ///
+    /// Transaction hashes are placeholders until Poseidon2 is implemented,
+    /// so `signed_hash` doesn't tell transactions apart yet.
+    pub async fn build_unsigned<R: TxRequest>(&self, req: &R, opts: TransactOpts) -> Result<R::Tx> {
+        if opts.nonce.is_none() {
+            return Err(LighterError::MissingField(
+                "nonce is required to build an unsigned transaction".to_string(),
+            ));
+        }
+        let opts = self.fill_default_opts_for(R::KIND, Some(opts)).await?;
+        let mut tx_info = req.build_tx(&opts)?;
+        tx_info.validate()?;
+        let msg_hash = tx_info.hash(self.chain_id)?;
+        tx_info.set_signed_hash(hex::encode(&msg_hash));
+        Ok(tx_info)
+    }
+
+    /// Validate, hash and sign a transaction in place
+    pub fn sign_prepared<T: Resignable>(&self, tx_info: &mut T) -> Result<()> {
+        self.sign_in_place(tx_info)?;
+        if self.nonce_store.is_some() {
+            if let (Some(key), Some(nonce), Some(hash)) = (
+                tx_info.get_nonce_key(),
+                tx_info.get_nonce(),
+                tx_info.get_tx_hash(),
+            ) {
+                self.in_flight.signed(key, nonce, hash);
+            }
+        }
+        Ok(())
+    }
+
+    fn sign_in_place<T: Resignable>(&self, tx_info: &mut T) -> Result<()> {
+        tx_info.validate()?;
+        let msg_hash = tx_info.hash(self.chain_id)?;
+        let signature = self.key_manager().sign(&msg_hash)?;
+        tx_info.set_signature(
+            Signature::new(signature, self.signature_encoding),
+            hex::encode(&msg_hash),
+        );
+        Ok(())
+    }
+
+    /// Submit an externally signed transaction exactly as it was received
+    ///
+    /// See [`interop`](crate::types::interop) for reading Python SDK payloads.
+    pub async fn send_envelope(&self, envelope: &TxEnvelope) -> Result<TxResponse> {
+        self.send_transaction(envelope).await
+    }
+
+    /// Send a signed transaction to the API
+    ///
+    /// Fails with [`LighterError::ExpiredAtInvalid`] if the transaction is
+    /// too close to expiry to be accepted; see
+    /// [`set_min_remaining_validity`](Self::set_min_remaining_validity).
+    ///
+    /// A transaction signed with a nonce from the nonce store fails with
+    /// [`LighterError::NonceGap`] behind one that went unused; see
+    /// [`set_nonce_gap_policy`](Self::set_nonce_gap_policy).
+    ///
+    /// # Arguments
+    /// * `tx_info` - Any type implementing TxInfo trait
+    pub async fn send_transaction<T: TxInfo>(&self, tx_info: &T) -> Result<TxResponse> {
+        let guard = self.nonce_turn(tx_info).await?;
+        if let Turn::Send {
+            shifted_past: Some(gap),
+            ..
+        } = guard.turn
+        {
+            // Signed with a nonce that can't be used any more
+            guard.settle(false).await?;
+            return Err(LighterError::NonceGap { failed_nonce: gap });
+        }
+        self.submit(tx_info, guard).await
+    }
+
+    /// Mark a transaction with a nonce from the store as being sent
+    async fn nonce_turn<T: TxInfo>(&self, tx_info: &T) -> Result<SendGuard> {
+        let (Some(store), Some(key)) = (&self.nonce_store, tx_info.get_nonce_key()) else {
+            return Ok(SendGuard::untracked());
+        };
+        let (nonce, hash) = (tx_info.get_nonce(), tx_info.get_tx_hash());
+        let (policy, hold) = (self.nonce_gap_policy, self.nonce_hold_timeout);
+        self.with_nonce_store(store, move |in_flight, store| {
+            in_flight.turn(store, key, nonce, hash.as_deref(), policy, hold)
+        })
+        .await
+    }
+
+    /// Send and settle the nonce with the result
+    ///
+    /// Rejections leave the nonce unused. Failures without an answer may
+    /// have used it and count as used, as does a send that is cancelled.
+    async fn submit<T: TxInfo>(&self, tx_info: &T, guard: SendGuard) -> Result<TxResponse> {
+        let admission = match self.circuit_breaker.as_deref().map(CircuitBreaker::admit) {
+            Some(Err(e)) => {
+                guard.settle(false).await?;
+                return Err(e);
+            }
+            admission => admission.transpose()?,
+        };
+        let result = self.submit_now(tx_info).await;
+        if let Some(admission) = admission {
+            match &result {
+                Ok(response) if response.code == 200 => admission.record_success(),
+                // Never reached the server; the admission is given back
+                Err(e) if matches!(e.kind(), ErrorKind::Validation | ErrorKind::Configuration) => {}
+                _ => admission.record_failure(),
+            }
+        }
+        let used = match &result {
+            Ok(response) => response.code == 200,
+            Err(e) => matches!(e.kind(), ErrorKind::Transport | ErrorKind::Server),
+        };
+        if matches!(&result, Ok(response) if response.code == 200) {
+            self.confirm_nonce(tx_info).await;
+        }
+        // The send's outcome stands whatever the store does
+        if let Err(e) = guard.settle(used).await {
+            eprintln!("Failed to settle nonce of a sent transaction: {}", e);
+        }
+        result
+    }
+
+    /// Record an accepted nonce in the store
+    ///
+    /// The transaction has executed whatever happens here, so a failure is
+    /// logged rather than returned.
+    async fn confirm_nonce<T: TxInfo>(&self, tx_info: &T) {
+        let (Some(store), Some(nonce), Some((account_index, api_key_index))) = (
+            &self.nonce_store,
+            tx_info.get_nonce(),
+            tx_info.get_nonce_key(),
+        ) else {
+            return;
+        };
+        let confirmed = self
+            .with_nonce_store(store, move |_, store| {
+                store.confirm(account_index, api_key_index, nonce)
+            })
+            .await;
+        if let Err(e) = confirmed {
+            eprintln!(
+                "Failed to confirm nonce {} for account {} api key {}: {}",
+                nonce, account_index, api_key_index, e
+            );
+        }
+    }
+
+    async fn submit_now<T: TxInfo>(&self, tx_info: &T) -> Result<TxResponse> {
+        if let Some(expired_at) = tx_info.get_expired_at() {
+            self.check_remaining_validity(expired_at)?;
+        }
+        if let Some(client) = &self.api_client {
+            let tx_type = tx_info.get_tx_type();
+            let tx_json = tx_info.get_tx_info()?;
+            client.send_tx(tx_type, &tx_json).await
+        } else {
+            Err(LighterError::InvalidConfiguration(
+                "HTTPClient is not configured. Provide a valid API URL when creating TxClient."
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+/// Request that can be built into an unsigned transaction
+///
+/// Implemented for every request type with a signing method on
+/// [`TxClient`]; see [`TxClient::build_unsigned`].
+pub trait TxRequest {
+    /// Transaction the request builds
+    type Tx: Resignable;
+    /// Kind of the transaction, for its default expiry
+    const KIND: TxKind;
+
+    /// Build the transaction without signing it
+    ///
+    /// `opts` must be filled in, e.g. by [`TxClient::fill_default_opts_for`];
+    /// a missing account, API key or nonce fails with
+    /// [`LighterError::MissingField`].
+    fn build_tx(&self, opts: &TransactOpts) -> Result<Self::Tx>;
+}
+
+impl TxRequest for CreateOrderTxReq {
+    type Tx = L2CreateOrderTxInfo;
+    const KIND: TxKind = TxKind::Order;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2CreateOrderTxInfo> {
+        TxClient::check_order_expiry(self.order_expiry)?;
+
+        let mut tx = L2CreateOrderTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            order_info: OrderInfo::from(self),
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        };
+
+        TxClient::check_reduce_only(&mut tx.order_info, opts)?;
+        Ok(tx)
+    }
+}
+
+impl TxRequest for CancelOrderTxReq {
+    type Tx = L2CancelOrderTxInfo;
+    const KIND: TxKind = TxKind::Order;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2CancelOrderTxInfo> {
+        Ok(L2CancelOrderTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            market_index: self.market_index,
+            index: self.index,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for ModifyOrderTxReq {
+    type Tx = L2ModifyOrderTxInfo;
+    const KIND: TxKind = TxKind::Order;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2ModifyOrderTxInfo> {
+        Ok(L2ModifyOrderTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            market_index: self.market_index,
+            index: self.index,
+            base_amount: self.base_amount,
+            price: self.price,
+            trigger_price: self.trigger_price,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for CancelAllOrdersTxReq {
+    type Tx = L2CancelAllOrdersTxInfo;
+    const KIND: TxKind = TxKind::Order;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2CancelAllOrdersTxInfo> {
+        Ok(L2CancelAllOrdersTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            time_in_force: self.time_in_force,
+            time: self.time,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for CreateGroupedOrdersTxReq {
+    type Tx = L2CreateGroupedOrdersTxInfo;
+    const KIND: TxKind = TxKind::Order;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2CreateGroupedOrdersTxInfo> {
+        let mut orders: Vec<OrderInfo> = self.orders.iter().map(OrderInfo::from).collect();
+
+        for order in orders.iter_mut() {
+            TxClient::check_order_expiry(order.order_expiry)?;
+            TxClient::check_reduce_only(order, opts)?;
+        }
+
+        Ok(L2CreateGroupedOrdersTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            grouping_type: self.grouping_type,
+            orders,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for TransferTxReq {
+    type Tx = L2TransferTxInfo;
+    const KIND: TxKind = TxKind::Transfer;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2TransferTxInfo> {
+        Ok(L2TransferTxInfo {
+            from_account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            to_account_index: self.to_account_index(),
+            usdc_amount: self.amount().units(),
+            fee: self.fee().units(),
+            memo: self.memo(),
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for WithdrawTxReq {
+    type Tx = L2WithdrawTxInfo;
+    const KIND: TxKind = TxKind::Transfer;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2WithdrawTxInfo> {
+        Ok(L2WithdrawTxInfo {
+            from_account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            usdc_amount: self.units(),
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for ChangePubKeyReq {
+    type Tx = L2ChangePubKeyTxInfo;
+    const KIND: TxKind = TxKind::Admin;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2ChangePubKeyTxInfo> {
+        Ok(L2ChangePubKeyTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            pub_key: self.pub_key.clone(),
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            l1_sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for UpdateLeverageTxReq {
+    type Tx = L2UpdateLeverageTxInfo;
+    const KIND: TxKind = TxKind::Admin;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2UpdateLeverageTxInfo> {
+        Ok(L2UpdateLeverageTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            market_index: self.market_index,
+            initial_margin_fraction: self.initial_margin_fraction,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for UpdateMarginTxReq {
+    type Tx = L2UpdateMarginTxInfo;
+    const KIND: TxKind = TxKind::Transfer;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2UpdateMarginTxInfo> {
+        Ok(L2UpdateMarginTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            market_index: self.market_index,
+            usdc_amount: self.usdc_amount,
+            direction: self.direction,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for CreatePublicPoolTxReq {
+    type Tx = L2CreatePublicPoolTxInfo;
+    const KIND: TxKind = TxKind::Admin;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2CreatePublicPoolTxInfo> {
+        Ok(L2CreatePublicPoolTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            operator_fee: self.operator_fee,
+            initial_total_shares: self.initial_total_shares,
+            min_operator_share_rate: self.min_operator_share_rate,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for UpdatePublicPoolTxReq {
+    type Tx = L2UpdatePublicPoolTxInfo;
+    const KIND: TxKind = TxKind::Admin;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2UpdatePublicPoolTxInfo> {
+        Ok(L2UpdatePublicPoolTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            public_pool_index: self.public_pool_index,
+            status: self.status,
+            operator_fee: self.operator_fee,
+            min_operator_share_rate: self.min_operator_share_rate,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for MintSharesTxReq {
+    type Tx = L2MintSharesTxInfo;
+    const KIND: TxKind = TxKind::Transfer;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2MintSharesTxInfo> {
+        Ok(L2MintSharesTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            public_pool_index: self.public_pool_index,
+            share_amount: self.share_amount,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+impl TxRequest for BurnSharesTxReq {
+    type Tx = L2BurnSharesTxInfo;
+    const KIND: TxKind = TxKind::Transfer;
+
+    fn build_tx(&self, opts: &TransactOpts) -> Result<L2BurnSharesTxInfo> {
+        Ok(L2BurnSharesTxInfo {
+            account_index: opts.require_account_index()?,
+            api_key_index: opts.require_api_key_index()?,
+            public_pool_index: self.public_pool_index,
+            share_amount: self.share_amount,
+            expired_at: opts.expired_at,
+            nonce: opts.require_nonce()?,
+            sig: None,
+            signed_hash: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::test_support::{order_params, test_client, test_client_for, TEST_KEY};
+    use crate::transport::MockTransport;
+
+    /// Fixed `expired_at` far enough in the future to pass the validity check
+    const TEST_EXPIRED_AT: i64 = 4_000_000_000_000;
+
+    fn offline_client() -> TxClient {
+        test_client("")
+    }
+
+    fn market(index: u8) -> MarketIndex {
+        MarketIndex::new(index).unwrap()
+    }
+
+    fn account(index: i64) -> AccountIndex {
+        AccountIndex::new(index).unwrap()
+    }
+
+    fn api_key(index: u8) -> ApiKeyIndex {
+        ApiKeyIndex::new(index).unwrap()
+    }
+
+    fn price(ticks: u32) -> Price {
+        Price::new(ticks).unwrap()
+    }
+
+    /// Client whose requests are answered by the returned mock
+    fn mocked_client() -> (TxClient, Arc<MockTransport>) {
+        let mock = Arc::new(MockTransport::new());
+        let mut client = test_client("http://127.0.0.1:1");
+        client
+            .http_mut()
+            .unwrap()
+            .set_transport(Box::new(mock.clone()));
+        (client, mock)
+    }
+
+    fn offline_opts(positions: Vec<Position>) -> TransactOpts {
+        TransactOpts {
+            expired_at: TEST_EXPIRED_AT,
+            nonce: Some(1),
+            positions,
+            ..Default::default()
+        }
+    }
+
+    fn reduce_only_order(market_index: u8, is_ask: u8, base_amount: i64) -> CreateOrderTxReq {
+        CreateOrderTxReq {
             market_index,
+            client_order_index: 1,
+            base_amount,
+            price: 100_000,
+            is_ask,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 1,
+            trigger_price: 0,
+            order_expiry: 0,
+        }
+    }
+
+    #[test]
+    fn test_http_client_creation() {
+        let client = HTTPClient::new("https://api.lighter.xyz");
+        assert!(client.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_create_order_reduce_only_without_position_is_unchecked() {
+        let client = offline_client();
+        let req = reduce_only_order(0, 0, 5_000);
+
+        let tx = client.create_order(&req, Some(offline_opts(vec![]))).await;
+        assert_eq!(tx.unwrap().order_info.base_amount, 5_000);
+    }
+
+    #[tokio::test]
+    async fn test_create_order_reduce_only_rejects_oversize() {
+        let client = offline_client();
+        let req = reduce_only_order(0, 1, 5_000);
+        let opts = offline_opts(vec![Position::new(0, 1_000)]);
+
+        let result = client.create_order(&req, Some(opts)).await;
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::ReduceOnlyExceedsPosition { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_order_reduce_only_auto_clamp() {
+        let client = offline_client();
+        let req = reduce_only_order(0, 1, 5_000);
+        let mut opts = offline_opts(vec![Position::new(0, 1_000)]);
+        opts.auto_clamp_reduce_only = true;
+
+        let tx = client.create_order(&req, Some(opts)).await.unwrap();
+        assert_eq!(tx.order_info.base_amount, 1_000);
+    }
+
+    #[tokio::test]
+    async fn test_grouped_orders_check_each_reduce_only_leg() {
+        let client = offline_client();
+        let mut open_leg = reduce_only_order(1, 0, 5_000);
+        open_leg.reduce_only = 0;
+        let req = CreateGroupedOrdersTxReq {
+            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+            orders: vec![open_leg, reduce_only_order(0, 0, 500)],
+        };
+        let opts = offline_opts(vec![Position::new(0, -1_000), Position::new(1, 0)]);
+        assert!(client
+            .create_grouped_orders(&req, Some(opts.clone()))
+            .await
+            .is_ok());
+
+        let req = CreateGroupedOrdersTxReq {
+            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+            orders: vec![reduce_only_order(0, 0, 500), reduce_only_order(0, 1, 500)],
+        };
+        let result = client.create_grouped_orders(&req, Some(opts)).await;
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::ReduceOnlyWouldIncreasePosition { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tp_sl_attaches_to_a_long_position() {
+        let client = offline_client();
+        let long = Position::new(0, 1_000);
+        let tp_sl = TpSl::new(300_000, 330_000, 285_000).stop_loss_limit(280_000);
+        let attached = client
+            .attach_tp_sl(
+                market(0),
+                &long,
+                tp_sl,
+                false,
+                Some(offline_opts(vec![long])),
+            )
+            .await
+            .unwrap();
+        assert!(attached.response.is_none());
+
+        let tx = attached.tx;
+        assert_eq!(tx.grouping_type, GROUPING_TYPE_ONE_CANCELS_THE_OTHER);
+        let [tp, sl] = &tx.orders[..] else {
+            panic!("{:?}", tx.orders)
+        };
+        assert_eq!(tp.order_type, ORDER_TYPE_TAKE_PROFIT_LIMIT);
+        assert_eq!((tp.trigger_price, tp.price), (330_000, 326_700));
+        assert_eq!(sl.order_type, ORDER_TYPE_STOP_LOSS_LIMIT);
+        assert_eq!((sl.trigger_price, sl.price), (285_000, 280_000));
+        for leg in &tx.orders {
+            assert_eq!((leg.reduce_only, leg.is_ask), (1, 1));
+            assert_eq!(leg.base_amount, 1_000);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tp_sl_attaches_to_a_short_position() {
+        let client = offline_client();
+        let short = Position::new(2, -750);
+        let tp_sl = TpSl::new(300_000, 270_000, 315_000).limit_offset_bps(200);
+        let tx = client
+            .attach_tp_sl(
+                market(2),
+                &short,
+                tp_sl,
+                false,
+                Some(offline_opts(vec![short])),
+            )
+            .await
+            .unwrap()
+            .tx;
+        assert_eq!(
+            (tx.orders[0].trigger_price, tx.orders[0].price),
+            (270_000, 275_400)
+        );
+        assert_eq!(
+            (tx.orders[1].trigger_price, tx.orders[1].price),
+            (315_000, 321_300)
+        );
+        for leg in &tx.orders {
+            assert_eq!((leg.reduce_only, leg.is_ask), (1, 0));
+            assert_eq!((leg.market_index, leg.base_amount), (2, 750));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tp_sl_rejects_inverted_triggers() {
+        let client = offline_client();
+        let long = Position::new(0, 1_000);
+        let short = Position::new(0, -1_000);
+        // Swapped for a long, which is what a short would use, and vice versa
+        let inverted = TpSl::new(300_000, 285_000, 330_000);
+        let flipped = TpSl::new(300_000, 330_000, 285_000);
+        for (position, tp_sl) in [(long, inverted), (short, flipped)] {
+            let result = client
+                .attach_tp_sl(
+                    market(0),
+                    &position,
+                    tp_sl,
+                    false,
+                    Some(offline_opts(vec![])),
+                )
+                .await;
+            assert!(matches!(result, Err(LighterError::ValidationError(_))));
+        }
+
+        // A trigger at the mark fires at once and is rejected too
+        let at_mark = TpSl::new(300_000, 300_000, 285_000);
+        assert!(at_mark.to_request(&long).is_err());
+        assert!(flipped.to_request(&Position::new(0, 0)).is_err());
+        assert!(client
+            .attach_tp_sl(market(1), &long, flipped, false, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tp_sl_submits_the_group() {
+        let mut server = mockito::Server::new_async().await;
+        let grouped = send_tx_mock(&mut server, TX_TYPE_L2_CREATE_GROUPED_ORDERS, 1)
+            .with_body(r#"{"code":200,"tx_hash":"0xg"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server.url());
+        let long = Position::new(0, 1_000);
+        let attached = client
+            .attach_tp_sl(
+                market(0),
+                &long,
+                TpSl::new(300_000, 330_000, 285_000),
+                true,
+                Some(offline_opts(vec![long])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(attached.response.unwrap().tx_hash.as_deref(), Some("0xg"));
+        grouped.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_nonce_store_initialized_from_server_once() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":42}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut client = test_client(&server.url());
+        let store: Arc<dyn NonceStore> = Arc::new(crate::signer::InMemoryNonceStore::new());
+        client.set_nonce_store(store.clone());
+
+        let first = client.fill_default_opts(None).await.unwrap();
+        let second = client.fill_default_opts(None).await.unwrap();
+
+        assert_eq!(first.nonce, Some(42));
+        assert_eq!(second.nonce, Some(43));
+        mock.assert_async().await;
+    }
+
+    /// Client with a nonce store at 0 and ten orders signed from it; the
+    /// server rejects the fifth sendTx
+    async fn nonce_gap_setup(
+        policy: NonceGapPolicy,
+    ) -> (Arc<TxClient>, Arc<MockTransport>, Vec<L2CreateOrderTxInfo>) {
+        let (mut client, mock) = mocked_client();
+        let store = Arc::new(crate::signer::InMemoryNonceStore::new());
+        store.reset(12345, 0, 0).unwrap();
+        client.set_nonce_store(store);
+        client.set_nonce_gap_policy(policy);
+        for sent in 0..10 {
+            let code = if sent == 4 { 21120 } else { 200 };
+            mock.on_post("/api/v1/sendTx", serde_json::json!({"code": code}));
+        }
+
+        let mut txs = Vec::new();
+        for client_order_index in 0..10 {
+            let opts = TransactOpts {
+                expired_at: TEST_EXPIRED_AT,
+                ..Default::default()
+            };
+            let req = pair_leg(0, client_order_index);
+            txs.push(client.create_order(&req, Some(opts)).await.unwrap());
+        }
+        assert_eq!(client.in_flight_nonces(), 10);
+        (Arc::new(client), mock, txs)
+    }
+
+    /// Send the first five transactions in order, up to the rejection, and
+    /// the rest concurrently, highest nonce first
+    async fn send_past_rejection(
+        client: &Arc<TxClient>,
+        mut txs: Vec<L2CreateOrderTxInfo>,
+        resigning: bool,
+    ) -> Vec<Result<TxResponse>> {
+        let rest = txs.split_off(5);
+        let mut results = Vec::new();
+        for tx in &txs {
+            results.push(client.send_transaction(tx).await);
+        }
+        results.extend(send_concurrently(client, rest, resigning).await);
+        results
+    }
+
+    /// Send every transaction concurrently, highest nonce first
+    async fn send_concurrently(
+        client: &Arc<TxClient>,
+        txs: Vec<L2CreateOrderTxInfo>,
+        resigning: bool,
+    ) -> Vec<Result<TxResponse>> {
+        let handles: Vec<_> = txs
+            .into_iter()
+            .rev()
+            .map(|mut tx| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    if resigning {
+                        client.send_transaction_resigning(&mut tx).await
+                    } else {
+                        client.send_transaction(&tx).await
+                    }
+                })
+            })
+            .collect();
+        let mut results = Vec::new();
+        for handle in handles {
+            results.push(handle.await.unwrap());
+        }
+        results.reverse();
+        results
+    }
+
+    /// Nonce and client order index of each sendTx, by nonce
+    fn sent_orders(mock: &MockTransport) -> Vec<(i64, i64)> {
+        let mut sent: Vec<_> = mock
+            .requests()
+            .iter()
+            .map(|request| {
+                let body = request.json_body().unwrap();
+                let tx: serde_json::Value =
+                    serde_json::from_str(body["tx_info"].as_str().unwrap()).unwrap();
+                (
+                    tx["nonce"].as_i64().unwrap(),
+                    tx["order_info"]["client_order_index"].as_i64().unwrap(),
+                )
+            })
+            .collect();
+        sent.sort_unstable();
+        sent
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_nonce_gap_fails_fast() {
+        let (client, mock, txs) = nonce_gap_setup(NonceGapPolicy::FailFast).await;
+        let results = send_past_rejection(&client, txs, false).await;
+
+        for (i, result) in results.iter().enumerate() {
+            match i {
+                0..=3 => assert_eq!(result.as_ref().unwrap().code, 200),
+                4 => assert_eq!(result.as_ref().unwrap().code, 21120),
+                _ => assert!(
+                    matches!(result, Err(LighterError::NonceGap { failed_nonce: 4 })),
+                    "{:?}",
+                    result
+                ),
+            }
+        }
+        // Nothing was sent behind the rejection
+        assert_eq!(
+            sent_orders(&mock),
+            (0..5).map(|i| (i, i)).collect::<Vec<_>>()
+        );
+        assert_eq!(client.in_flight_nonces(), 0);
+
+        let opts = TransactOpts {
+            expired_at: TEST_EXPIRED_AT,
+            ..Default::default()
+        };
+        let next = client
+            .create_order(&pair_leg(0, 10), Some(opts))
+            .await
+            .unwrap();
+        assert_eq!(next.nonce, 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_nonce_gap_renumbers() {
+        let (client, mock, txs) = nonce_gap_setup(NonceGapPolicy::Renumber).await;
+        let results = send_past_rejection(&client, txs, true).await;
+
+        for (i, result) in results.iter().enumerate() {
+            let expected = if i == 4 { 21120 } else { 200 };
+            assert_eq!(result.as_ref().unwrap().code, expected);
+        }
+        // Orders behind the rejection moved down one nonce
+        let mut expected: Vec<(i64, i64)> = (0..5).map(|i| (i, i)).collect();
+        expected.extend((5..10).map(|i| (i - 1, i)));
+        assert_eq!(sent_orders(&mock), expected);
+        assert_eq!(client.in_flight_nonces(), 0);
+        let state = client.nonce_store().unwrap().state(12345, 0).unwrap();
+        assert_eq!(state.next, 9);
+
+        // Without re-signing, a renumbered transaction can't be sent
+        let (client, mock, txs) = nonce_gap_setup(NonceGapPolicy::Renumber).await;
+        let results = send_past_rejection(&client, txs, false).await;
+        assert_eq!(results[4].as_ref().unwrap().code, 21120);
+        assert!(matches!(
+            results[5],
+            Err(LighterError::NonceGap { failed_nonce: 4 })
+        ));
+        assert_eq!(mock.requests().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_nonce_invalidates_unsent() {
+        let (client, mock, txs) = nonce_gap_setup(NonceGapPolicy::Renumber).await;
+        mock.on_get(
+            "/api/v1/nextNonce",
+            serde_json::json!({"code": 200, "nonce": 3}),
+        );
+        client.send_transaction(&txs[0]).await.unwrap();
+        assert_eq!(client.in_flight_nonces(), 9);
+
+        assert_eq!(client.refresh_nonce().await.unwrap(), 3);
+        assert_eq!(client.in_flight_nonces(), 0);
+        assert!(matches!(
+            client.send_transaction(&txs[1]).await,
+            Err(LighterError::NonceGap { failed_nonce: 1 })
+        ));
+        assert_eq!(mock.requests().len(), 2);
+
+        let opts = TransactOpts {
+            expired_at: TEST_EXPIRED_AT,
+            ..Default::default()
+        };
+        let tx = client
+            .create_order(&pair_leg(0, 10), Some(opts))
+            .await
+            .unwrap();
+        assert_eq!(tx.nonce, 3);
+        assert_eq!(client.send_transaction(&tx).await.unwrap().code, 200);
+    }
+
+    /// In-memory store that refuses to confirm
+    #[derive(Default)]
+    struct ConfirmFailingStore(crate::signer::InMemoryNonceStore);
+
+    impl NonceStore for ConfirmFailingStore {
+        fn next(&self, account_index: i64, api_key_index: u8) -> Result<Option<i64>> {
+            self.0.next(account_index, api_key_index)
+        }
+
+        fn confirm(&self, _: i64, _: u8, _: i64) -> Result<()> {
+            Err(LighterError::Other("disk full".to_string()))
+        }
+
+        fn reset(&self, account_index: i64, api_key_index: u8, next: i64) -> Result<()> {
+            self.0.reset(account_index, api_key_index, next)
+        }
+
+        fn state(
+            &self,
+            account_index: i64,
+            api_key_index: u8,
+        ) -> Option<crate::signer::NonceState> {
+            self.0.state(account_index, api_key_index)
+        }
+
+        fn reconcile(
+            &self,
+            account_index: i64,
+            api_key_index: u8,
+            server_next: i64,
+        ) -> Result<i64> {
+            self.0.reconcile(account_index, api_key_index, server_next)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accepted_send_survives_failed_confirm() {
+        let (mut client, mock) = mocked_client();
+        let store = Arc::new(ConfirmFailingStore::default());
+        store.reset(12345, 0, 0).unwrap();
+        client.set_nonce_store(store.clone());
+        let breaker = Arc::new(
+            CircuitBreaker::new(crate::risk::CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        client.set_circuit_breaker(breaker.clone());
+        mock.on_post("/api/v1/sendTx", serde_json::json!({"code": 200}));
+        mock.on_post("/api/v1/sendTx", serde_json::json!({"code": 200}));
+        let opts = || {
+            Some(TransactOpts {
+                expired_at: TEST_EXPIRED_AT,
+                ..Default::default()
+            })
+        };
+        let first = client.create_order(&pair_leg(0, 0), opts()).await.unwrap();
+        let second = client.create_order(&pair_leg(0, 1), opts()).await.unwrap();
+
+        assert_eq!(client.send_transaction(&first).await.unwrap().code, 200);
+        // The accepted nonce stays used: nothing is wound back or reissued
+        assert_eq!(store.state(12345, 0).unwrap().next, 2);
+        assert_eq!(client.send_transaction(&second).await.unwrap().code, 200);
+        assert_eq!(client.in_flight_nonces(), 0);
+        assert_eq!(sent_orders(&mock), vec![(0, 0), (1, 1)]);
+        let third = client.create_order(&pair_leg(0, 2), opts()).await.unwrap();
+        assert_eq!(third.nonce, 2);
+        assert_eq!(breaker.state(), crate::risk::CircuitState::Closed);
+    }
+
+    /// Transport whose sendTx hangs while `stalled` is set
+    struct StallingTransport {
+        inner: Arc<MockTransport>,
+        stalled: AtomicBool,
+    }
+
+    impl crate::transport::Transport for StallingTransport {
+        fn get_json<'a>(
+            &'a self,
+            path: &'a str,
+            query: &'a [(&'a str, String)],
+            headers: &'a HeaderMap,
+        ) -> crate::transport::TransportFuture<'a> {
+            self.inner.get_json(path, query, headers)
+        }
+
+        fn post_json<'a>(
+            &'a self,
+            path: &'a str,
+            body: &'a [u8],
+            headers: &'a HeaderMap,
+        ) -> crate::transport::TransportFuture<'a> {
+            if self.stalled.load(Ordering::SeqCst) {
+                return Box::pin(std::future::pending());
+            }
+            self.inner.post_json(path, body, headers)
+        }
+    }
+
+    /// In-memory store that claims to block, recording the threads that
+    /// write to it
+    #[derive(Default)]
+    struct ThreadRecordingStore {
+        inner: crate::signer::InMemoryNonceStore,
+        threads: std::sync::Mutex<Vec<std::thread::ThreadId>>,
+    }
+
+    impl ThreadRecordingStore {
+        fn record(&self) {
+            self.threads
+                .lock()
+                .unwrap()
+                .push(std::thread::current().id());
+        }
+    }
+
+    impl NonceStore for ThreadRecordingStore {
+        fn next(&self, account_index: i64, api_key_index: u8) -> Result<Option<i64>> {
+            self.record();
+            self.inner.next(account_index, api_key_index)
+        }
+
+        fn confirm(&self, account_index: i64, api_key_index: u8, nonce: i64) -> Result<()> {
+            self.record();
+            self.inner.confirm(account_index, api_key_index, nonce)
+        }
+
+        fn reset(&self, account_index: i64, api_key_index: u8, next: i64) -> Result<()> {
+            self.record();
+            self.inner.reset(account_index, api_key_index, next)
+        }
+
+        fn state(
+            &self,
+            account_index: i64,
+            api_key_index: u8,
+        ) -> Option<crate::signer::NonceState> {
+            self.inner.state(account_index, api_key_index)
+        }
+
+        fn reconcile(
+            &self,
+            account_index: i64,
+            api_key_index: u8,
+            server_next: i64,
+        ) -> Result<i64> {
+            self.record();
+            self.inner
+                .reconcile(account_index, api_key_index, server_next)
+        }
+
+        fn blocks(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blocking_store_is_written_off_the_runtime() {
+        let mock = Arc::new(MockTransport::new());
+        let transport = Arc::new(StallingTransport {
+            inner: mock.clone(),
+            stalled: false.into(),
+        });
+        let mut client = test_client("http://127.0.0.1:1");
+        client
+            .http_mut()
+            .unwrap()
+            .set_transport(Box::new(transport.clone()));
+        let store = Arc::new(ThreadRecordingStore::default());
+        store.inner.reset(12345, 0, 0).unwrap();
+        client.set_nonce_store(store.clone());
+        mock.on_post("/api/v1/sendTx", serde_json::json!({"code": 21120}));
+        mock.on_get(
+            "/api/v1/nextNonce",
+            serde_json::json!({"code": 200, "nonce": 1}),
+        );
+        let client = Arc::new(client);
+        let opts = || {
+            Some(TransactOpts {
+                expired_at: TEST_EXPIRED_AT,
+                ..Default::default()
+            })
+        };
+
+        // A rejection winds the store back, failing the order behind it
+        let rejected = client.create_order(&pair_leg(0, 0), opts()).await.unwrap();
+        let behind = client.create_order(&pair_leg(0, 1), opts()).await.unwrap();
+        assert_eq!(
+            client.send_transaction(&rejected).await.unwrap().code,
+            21120
+        );
+        assert!(matches!(
+            client.send_transaction(&behind).await,
+            Err(LighterError::NonceGap { failed_nonce: 0 })
+        ));
+        assert_eq!(store.state(12345, 0).unwrap().next, 0);
+
+        // A cancelled send is settled after it is dropped
+        let stuck = client.create_order(&pair_leg(0, 2), opts()).await.unwrap();
+        transport.stalled.store(true, Ordering::SeqCst);
+        let send = {
+            let client = client.clone();
+            tokio::spawn(async move { client.send_transaction(&stuck).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        send.abort();
+        assert!(send.await.unwrap_err().is_cancelled());
+        let next = tokio::time::timeout(Duration::from_secs(1), client.refresh_nonce())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(next, 1);
+
+        let threads = store.threads.lock().unwrap();
+        // Two allocations and the wind-back, the third allocation, the reset
+        assert_eq!(threads.len(), 5);
+        let runtime = std::thread::current().id();
+        assert!(threads.iter().all(|&thread| thread != runtime));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_send_settles_nonce() {
+        let mock = Arc::new(MockTransport::new());
+        let transport = Arc::new(StallingTransport {
+            inner: mock.clone(),
+            stalled: true.into(),
+        });
+        let mut client = test_client("http://127.0.0.1:1");
+        client
+            .http_mut()
+            .unwrap()
+            .set_transport(Box::new(transport.clone()));
+        let store = Arc::new(crate::signer::InMemoryNonceStore::new());
+        store.reset(12345, 0, 0).unwrap();
+        client.set_nonce_store(store);
+        mock.on_post("/api/v1/sendTx", serde_json::json!({"code": 200}));
+        mock.on_get(
+            "/api/v1/nextNonce",
+            serde_json::json!({"code": 200, "nonce": 2}),
+        );
+        let client = Arc::new(client);
+        let opts = || {
+            Some(TransactOpts {
+                expired_at: TEST_EXPIRED_AT,
+                ..Default::default()
+            })
+        };
+        let low = client.create_order(&pair_leg(0, 0), opts()).await.unwrap();
+        let high = client.create_order(&pair_leg(0, 1), opts()).await.unwrap();
+
+        let stuck = {
+            let client = client.clone();
+            tokio::spawn(async move { client.send_transaction(&low).await })
+        };
+        tokio::task::yield_now().await;
+        // The higher nonce goes out while the lower one is still being sent
+        transport.stalled.store(false, Ordering::SeqCst);
+        assert_eq!(client.send_transaction(&high).await.unwrap().code, 200);
+        assert_eq!(client.in_flight_nonces(), 1);
+
+        // Cancelled mid-flight, the lower nonce may have been used
+        stuck.abort();
+        assert!(stuck.await.unwrap_err().is_cancelled());
+        assert_eq!(client.in_flight_nonces(), 0);
+        let next = tokio::time::timeout(Duration::from_secs(1), client.refresh_nonce())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(next, 2);
+        let tx = client.create_order(&pair_leg(0, 2), opts()).await.unwrap();
+        assert_eq!(tx.nonce, 2);
+        assert_eq!(client.send_transaction(&tx).await.unwrap().code, 200);
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_deprecated_helpers_match_previous_requests() {
+        let client = offline_client();
+        let opts = || Some(offline_opts(vec![]));
+        let legacy = |order_type, time_in_force, trigger_price| CreateOrderTxReq {
+            market_index: 3,
+            client_order_index: 77,
+            base_amount: 5_000,
+            price: 100_000,
+            is_ask: 1,
+            order_type,
+            time_in_force,
+            reduce_only: 0,
+            trigger_price,
+            order_expiry: 0,
+        };
+
+        let cases = vec![
+            (
+                client
+                    .create_limit_order(3, 77, 5_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(ORDER_TYPE_LIMIT, TIME_IN_FORCE_GOOD_TILL_TIME, 0),
+            ),
+            (
+                client
+                    .create_market_order(3, 77, 5_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(ORDER_TYPE_MARKET, TIME_IN_FORCE_IMMEDIATE_OR_CANCEL, 0),
+            ),
+            (
+                client
+                    .create_tp_order(3, 77, 5_000, 90_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(
+                    ORDER_TYPE_TAKE_PROFIT,
+                    TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+                    90_000,
+                ),
+            ),
+            (
+                client
+                    .create_tp_limit_order(3, 77, 5_000, 90_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(
+                    ORDER_TYPE_TAKE_PROFIT_LIMIT,
+                    TIME_IN_FORCE_GOOD_TILL_TIME,
+                    90_000,
+                ),
+            ),
+            (
+                client
+                    .create_sl_order(3, 77, 5_000, 90_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(
+                    ORDER_TYPE_STOP_LOSS,
+                    TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+                    90_000,
+                ),
+            ),
+            (
+                client
+                    .create_sl_limit_order(3, 77, 5_000, 90_000, 100_000, 1, false, opts())
+                    .await,
+                legacy(
+                    ORDER_TYPE_STOP_LOSS_LIMIT,
+                    TIME_IN_FORCE_GOOD_TILL_TIME,
+                    90_000,
+                ),
+            ),
+        ];
+
+        for (tx, expected) in cases {
+            assert_eq!(tx.unwrap().order_info, OrderInfo::from(&expected));
+        }
+
+        // Out-of-range is_ask still reaches validation unchanged
+        let result = client
+            .create_limit_order(3, 77, 5_000, 100_000, 2, false, opts())
+            .await;
+        assert!(matches!(result.unwrap_err(), LighterError::IsAskInvalid));
+    }
+
+    #[tokio::test]
+    async fn test_create_order_with_trigger_must_match_kind() {
+        let client = offline_client();
+        let params = order_params(0, Side::Sell, 1_000, 100_000).reduce_only(true);
+
+        let result = client
+            .create_order_with(params, OrderKind::StopLoss, Some(offline_opts(vec![])))
+            .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::OrderTriggerPriceInvalid
+        ));
+
+        let result = client
+            .create_order_with(
+                params.trigger(price(95_000)),
+                OrderKind::Limit,
+                Some(offline_opts(vec![])),
+            )
+            .await;
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::OrderTriggerPriceInvalid
+        ));
+
+        let tx = client
+            .create_order_with(
+                params.trigger(price(95_000)),
+                OrderKind::StopLossLimit,
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tx.order_info.order_type, ORDER_TYPE_STOP_LOSS_LIMIT);
+        assert_eq!(tx.order_info.trigger_price, 95_000);
+        assert_eq!(tx.order_info.reduce_only, 1);
+        assert_eq!(tx.order_info.is_ask, 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_at_units() {
+        let client = offline_client();
+        let req = order_params(0, Side::Buy, 1_000, 100_000).to_request(OrderKind::Limit);
+
+        // Seconds passed where milliseconds are expected
+        let opts = TransactOpts {
+            expired_at: TEST_EXPIRED_AT / 1000,
+            ..offline_opts(vec![])
+        };
+        let err = client
+            .create_order(&req, Some(opts.clone()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("looks like seconds"), "{}", err);
+
+        // ...unless conversion is requested
+        let opts = TransactOpts {
+            auto_convert_seconds: true,
+            ..opts
+        };
+        let tx = client.create_order(&req, Some(opts)).await.unwrap();
+        assert_eq!(tx.expired_at, TEST_EXPIRED_AT);
+
+        // Far future
+        let opts = TransactOpts {
+            expired_at: MAX_TIMESTAMP + 1,
+            ..offline_opts(vec![])
+        };
+        assert!(client.create_order(&req, Some(opts)).await.is_err());
+
+        // Valid values pass through unchanged
+        let tx = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_eq!(tx.expired_at, TEST_EXPIRED_AT);
+    }
+
+    #[tokio::test]
+    async fn test_order_expiry_units() {
+        let client = offline_client();
+        let params = order_params(0, Side::Buy, 1_000, 100_000);
+
+        let req = params
+            .expiry(OrderExpiry::At(1_700_000_000))
+            .to_request(OrderKind::Limit);
+        let err = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("order_expiry"), "{}", err);
+
+        let expiry = ExpiryMs::from_secs(1_700_000_000).unwrap();
+        let req = params.expiry(expiry.into()).to_request(OrderKind::Limit);
+        let tx = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_eq!(tx.order_info.order_expiry, 1_700_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_get_public_pool() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on_get(
+            "/api/v1/account",
+            serde_json::json!({"code": 200, "total": 1, "accounts": [{
+                "index": 281474976710650u64,
+                "total_asset_value": "3000.5",
+                "pool_info": {"status": 0, "operator_fee": "10", "min_operator_share_rate": "5",
+                    "total_shares": 2000000, "operator_shares": 200000}
+            }]}),
+        );
+
+        let http = HTTPClient::with_transport(Box::new(mock.clone()));
+        let pool = http
+            .get_public_pool(account(281474976710650))
+            .await
+            .unwrap();
+        assert_eq!(
+            mock.requests()[0].query_param("value"),
+            Some("281474976710650")
+        );
+        assert_eq!(pool.total_shares, 2_000_000);
+        assert_eq!(pool.operator_shares, 200_000);
+        assert_eq!(pool.pool_equity, Decimal::new(30005, 1));
+        assert_eq!(pool.operator_fee, Decimal::from(10));
+    }
+
+    #[tokio::test]
+    async fn test_strict_parsing_of_account() {
+        let mock = Arc::new(MockTransport::new());
+        mock.on_get(
+            "/api/v1/account",
+            serde_json::json!({"code": 200, "accounts": [{
+                "index": 7,
+                "collateral": "100",
+                "positions": [{"market_id": 0, "sign": -1, "position": "2",
+                    "avg_entry_price": "3000", "margin_mode": 0}]
+            }]}),
+        );
+
+        let mut http = HTTPClient::with_transport(Box::new(mock));
+        let snapshot = http.get_account_snapshot(account(7)).await.unwrap();
+        assert_eq!(snapshot.positions["0"].size, Decimal::from(-2));
+        assert_eq!(
+            snapshot.positions["0"].extras["margin_mode"],
+            serde_json::json!(0)
+        );
+
+        http.set_strict_parsing(true);
+        let err = http.get_account_snapshot(account(7)).await.unwrap_err();
+        assert!(matches!(err, LighterError::InvalidResponse(_)), "{:?}", err);
+        assert!(err.to_string().contains("margin_mode"), "{}", err);
+    }
+
+    async fn pool_position_mocks(server: &mut mockito::ServerGuard, shares: &str) {
+        server
+            .mock("GET", "/api/v1/account")
+            .match_query(mockito::Matcher::UrlEncoded("value".into(), "12345".into()))
+            .with_body(format!(
+                r#"{{"code":200,"total":1,"accounts":[{{"index":12345,"shares":{}}}]}}"#,
+                shares
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v1/account")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "value".into(),
+                "281474976710650".into(),
+            ))
+            .with_body(
+                r#"{"code":200,"total":1,"accounts":[{"index":281474976710650,
+                "total_asset_value":"3000","pool_info":{"status":0,"operator_fee":"10",
+                "min_operator_share_rate":"5","total_shares":2000000,"operator_shares":200000}}]}"#,
+            )
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_burn_all_shares() {
+        let mut server = mockito::Server::new_async().await;
+        pool_position_mocks(
+            &mut server,
+            r#"[{"public_pool_index":7,"shares_amount":10,"entry_usdc":"1"},
+                {"public_pool_index":281474976710650,"shares_amount":1500,"entry_usdc":"2.1"}]"#,
+        )
+        .await;
+
+        let client = test_client(&server.url());
+        let position = client
+            .http()
+            .unwrap()
+            .get_pool_position(account(12345), account(281474976710650))
+            .await
+            .unwrap();
+        assert_eq!(position.shares, 1500);
+        assert_eq!(position.entry_usdc, Decimal::new(21, 1));
+        // 0.0015 USDC per share
+        assert_eq!(position.current_value, Decimal::new(225, 2));
+
+        let tx = client
+            .burn_all_shares(account(281474976710650), 0, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_eq!(tx.public_pool_index, 281474976710650);
+        assert_eq!(tx.share_amount, 1500);
+        assert!(tx.sig.is_some());
+
+        let tx = client
+            .burn_all_shares(account(281474976710650), 1, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_eq!(tx.share_amount, 1499);
+    }
+
+    #[tokio::test]
+    async fn test_burn_all_shares_without_shares_signs_nothing() {
+        let mut server = mockito::Server::new_async().await;
+        pool_position_mocks(
+            &mut server,
+            r#"[{"public_pool_index":7,"shares_amount":10,"entry_usdc":"1"}]"#,
+        )
+        .await;
+        let nonce = server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let position = client
+            .http()
+            .unwrap()
+            .get_pool_position(account(12345), account(281474976710650))
+            .await
+            .unwrap();
+        assert_eq!(position.shares, 0);
+        assert_eq!(position.current_value, Decimal::ZERO);
+
+        let err = client
+            .burn_all_shares(account(281474976710650), 0, None)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, LighterError::ValidationError(msg) if msg.contains("0 shares held")),
+            "{}",
+            err
+        );
+        // Negative dust would burn shares that aren't held
+        let err = client
+            .burn_all_shares(account(281474976710650), -1, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, LighterError::ValidationError(_)));
+        nonce.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_invested_pool_limit_error() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":21120,"message":"max invested public pool count reached"}"#)
+            .create_async()
+            .await;
+
+        let http = HTTPClient::new(&server.url()).unwrap();
+        let err = http
+            .send_tx(TX_TYPE_L2_MINT_SHARES, "{}")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, LighterError::InvestedPublicPoolLimit(_)),
+            "{}",
+            err
+        );
+
+        // Other transaction types keep the plain response
+        let response = http.send_tx(TX_TYPE_L2_TRANSFER, "{}").await.unwrap();
+        assert_eq!(response.code, 21120);
+    }
+
+    fn account_with_orders(orders: serde_json::Value) -> AccountSnapshot {
+        serde_json::from_value(serde_json::json!({ "orders": orders })).unwrap()
+    }
+
+    fn open_order(order_index: i64, market_index: u32) -> serde_json::Value {
+        serde_json::json!({"order_index": order_index, "market_index": market_index,
+            "is_ask": false, "price": "1", "remaining_base_amount": "1"})
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_orders_for_market() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":5}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut nonce_mocks = Vec::new();
+        for (nonce, index) in [(5, 281474976710656i64), (6, 281474976710657)] {
+            nonce_mocks.push(
+                server
+                    .mock("POST", "/api/v1/sendTx")
+                    .match_body(mockito::Matcher::AllOf(vec![
+                        mockito::Matcher::Regex(format!(r#"nonce\\":{}[,}}]"#, nonce)),
+                        mockito::Matcher::Regex(format!(r#"index\\":{}[,}}]"#, index)),
+                    ]))
+                    .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+                    .expect(1)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let client = test_client(&server.url());
+        let account = account_with_orders(serde_json::json!({
+            "0": [open_order(281474976710657, 0), open_order(281474976710656, 0)],
+            "1": [open_order(281474976710658, 1)],
+        }));
+
+        let outcomes = client
+            .cancel_all_orders_for_market(market(0), &account, None)
+            .await
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].order_index, 281474976710656);
+        assert!(outcomes
+            .iter()
+            .all(|o| matches!(&o.result, Ok(r) if r.code == 200)));
+        for mock in nonce_mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_raw_market_shims_validate() {
+        let client = offline_client();
+        let account = AccountSnapshot::default();
+        let long = Position::new(2, 1_234);
+        assert!(matches!(
+            client
+                .cancel_all_orders_for_market_raw(MAX_MARKET_INDEX + 1, &account, None)
+                .await,
+            Err(LighterError::MarketIndexTooHigh(_))
+        ));
+        assert!(matches!(
+            client
+                .close_position_raw(2, &long, 0, None, Some(offline_opts(vec![long])))
+                .await,
+            Err(LighterError::PriceTooLow(0))
+        ));
+        let tx = client
+            .close_position_raw(2, &long, 90_000, None, Some(offline_opts(vec![long])))
+            .await
+            .unwrap();
+        assert_eq!(tx.order_info.price, 90_000);
+        let tp_sl = TpSl::new(300_000, 330_000, 285_000);
+        assert!(matches!(
+            client
+                .attach_tp_sl_raw(MAX_MARKET_INDEX + 1, &long, tp_sl, false, None)
+                .await,
+            Err(LighterError::MarketIndexTooHigh(_))
+        ));
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_raw_account_shims_validate() {
+        let mock = Arc::new(MockTransport::new());
+        let http = HTTPClient::with_transport(Box::new(mock.clone()));
+        assert!(matches!(
+            http.get_api_key_public_key_raw(12345, u8::MAX).await,
+            Err(LighterError::ApiKeyIndexTooHigh(_))
+        ));
+        assert!(matches!(
+            http.get_active_orders_raw(12345, 256).await,
+            Err(LighterError::ValidationError(_))
+        ));
+        assert!(matches!(
+            http.get_transfer_fee_info_raw(12345, -1).await,
+            Err(LighterError::AccountIndexTooLow(-1))
+        ));
+        assert!(matches!(
+            http.get_pool_position_raw(12345, i64::MAX).await,
+            Err(LighterError::AccountIndexTooHigh(_))
+        ));
+        assert!(matches!(
+            http.get_order_history_raw(12345, Some(MAX_MARKET_INDEX + 1), None, 10)
+                .await,
+            Err(LighterError::MarketIndexTooHigh(_))
+        ));
+        assert!(mock.requests().is_empty());
+
+        let client = offline_client();
+        assert!(matches!(
+            client
+                .transfer_auto_fee_raw(-1, Decimal::ONE, [0; 32], Decimal::ONE, None)
+                .await,
+            Err(LighterError::AccountIndexTooLow(-1))
+        ));
+        assert!(matches!(
+            client.burn_all_shares_raw(-1, 0, None).await,
+            Err(LighterError::AccountIndexTooLow(-1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clock_skew_compensates_default_expiry() {
+        let mut server = mockito::Server::new_async().await;
+        let server_now = Utc::now() + chrono::Duration::seconds(40);
+        let status = server
+            .mock("GET", "/")
+            .with_header(
+                "date",
+                &server_now.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+            )
+            .with_body("ok")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut client = test_client(&server.url());
+        client.set_clock_skew_compensation(Some(ClockSkewConfig::default()));
+        let opts = || {
+            Some(TransactOpts {
+                nonce: Some(1),
+                ..Default::default()
+            })
+        };
+        let first = client.fill_default_opts(opts()).await.unwrap();
+        let second = client.fill_default_opts(opts()).await.unwrap();
+
+        // Date headers are whole seconds
+        let expected = server_now.timestamp_millis() + DEFAULT_TX_EXPIRY_MS - 1000;
+        for expired_at in [first.expired_at, second.expired_at] {
+            assert!((expired_at - expected).abs() < 2_000, "{}", expired_at);
+        }
+        status.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_check_clock_skew_prefers_status_timestamp() {
+        let mut server = mockito::Server::new_async().await;
+        let server_now = Utc::now() - chrono::Duration::seconds(30);
+        server
+            .mock("GET", "/")
+            .with_header("date", "Mon, 01 Jan 2024 00:00:00 GMT")
+            .with_body(format!(
+                r#"{{"status":200,"network_id":1,"timestamp":{}}}"#,
+                server_now.timestamp()
+            ))
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let skew = client.check_clock_skew().await.unwrap();
+        assert!((skew.num_seconds() + 30).abs() <= 1, "{}", skew);
+
+        // Small offsets are measured but not applied
+        let mut client = test_client(&server.url());
+        client.set_clock_skew_compensation(Some(ClockSkewConfig {
+            threshold: Duration::from_secs(60),
+            ..Default::default()
+        }));
+        let opts = client
+            .fill_default_opts(Some(TransactOpts {
+                nonce: Some(1),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        let expected = Utc::now().timestamp_millis() + DEFAULT_TX_EXPIRY_MS - 1000;
+        assert!((opts.expired_at - expected).abs() < 2_000);
+    }
+
+    async fn assert_two_phase_matches<R: TxRequest>(client: &TxClient, req: &R, signed: R::Tx) {
+        let mut prepared = client
+            .build_unsigned(req, offline_opts(vec![]))
+            .await
+            .unwrap();
+        assert!(prepared.get_tx_hash().is_some());
+        assert_eq!(prepared.get_tx_hash(), signed.get_tx_hash());
+        client.sign_prepared(&mut prepared).unwrap();
+        assert_eq!(
+            prepared.get_tx_info().unwrap(),
+            signed.get_tx_info().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_signing_matches_one_shot() {
+        let client = offline_client();
+
+        let order = reduce_only_order(0, 1, 1_000);
+        let signed = client
+            .create_order(&order, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_two_phase_matches(&client, &order, signed).await;
+
+        let cancel = CancelOrderTxReq {
+            market_index: 0,
+            index: 77,
+        };
+        let signed = client
+            .cancel_order(&cancel, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_two_phase_matches(&client, &cancel, signed).await;
+
+        let withdraw = WithdrawTxReq::usdc("2.5".parse().unwrap()).unwrap();
+        let signed = client
+            .withdraw(&withdraw, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_two_phase_matches(&client, &withdraw, signed).await;
+
+        let unsigned = client
+            .build_unsigned(&cancel, offline_opts(vec![]))
+            .await
+            .unwrap();
+        assert!(unsigned.sig.is_none());
+
+        let without_nonce = TransactOpts {
+            nonce: None,
+            ..offline_opts(vec![])
+        };
+        assert!(matches!(
+            client.build_unsigned(&cancel, without_nonce).await,
+            Err(LighterError::MissingField(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_tx_requires_filled_opts() {
+        let order = reduce_only_order(0, 1, 1_000);
+        let withdraw = WithdrawTxReq::usdc("2.5".parse().unwrap()).unwrap();
+        let filled = TransactOpts {
+            from_account_index: Some(12345),
+            api_key_index: Some(0),
+            ..offline_opts(vec![])
+        };
+        assert!(order.build_tx(&filled).is_ok());
+
+        for (unfilled, field) in [
+            (TransactOpts::default(), "from_account_index"),
+            (
+                TransactOpts {
+                    api_key_index: None,
+                    ..filled.clone()
+                },
+                "api_key_index",
+            ),
+            (
+                TransactOpts {
+                    nonce: None,
+                    ..filled.clone()
+                },
+                "nonce",
+            ),
+        ] {
+            assert!(matches!(
+                order.build_tx(&unfilled),
+                Err(LighterError::MissingField(f)) if f == field
+            ));
+            assert!(matches!(
+                withdraw.build_tx(&unfilled),
+                Err(LighterError::MissingField(f)) if f == field
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_unsigned_leaves_signature_unset() {
+        let client = offline_client();
+        let mut tx = client
+            .build_unsigned(&reduce_only_order(0, 1, 1_000), offline_opts(vec![]))
+            .await
+            .unwrap();
+        assert!(tx.signed_hash.is_some());
+        assert!(tx.sig.is_none());
+
+        client.sign_prepared(&mut tx).unwrap();
+        assert!(tx.sig.is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "transaction hashes are placeholders until Poseidon2 is implemented"]
+    async fn test_build_unsigned_hashes_distinct_requests_apart() {
+        let client = offline_client();
+        let mut hashes = std::collections::HashSet::new();
+        for base_amount in [1_000, 2_000] {
+            let tx = client
+                .build_unsigned(&reduce_only_order(0, 1, base_amount), offline_opts(vec![]))
+                .await
+                .unwrap();
+            hashes.insert(tx.signed_hash.unwrap());
+        }
+        assert_eq!(hashes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_close_position() {
+        let client = offline_client();
+        let long = Position::new(2, 1_234);
+        let short = Position::new(2, -1_234);
+
+        let tx = client
+            .close_position(
+                market(2),
+                &long,
+                price(90_000),
+                None,
+                Some(offline_opts(vec![long])),
+            )
+            .await
+            .unwrap();
+        let order = &tx.order_info;
+        assert_eq!(
+            (order.market_index, order.is_ask, order.base_amount),
+            (2, 1, 1_234)
+        );
+        assert_eq!(
+            (order.reduce_only, order.order_type),
+            (1, ORDER_TYPE_MARKET)
+        );
+        assert_eq!(order.price, 90_000);
+
+        let tx = client
+            .close_position(
+                market(2),
+                &short,
+                price(110_000),
+                Some(Decimal::new(25, 2)),
+                Some(offline_opts(vec![short])),
+            )
+            .await
+            .unwrap();
+        assert_eq!((tx.order_info.is_ask, tx.order_info.base_amount), (0, 308));
+
+        let flat = Position::new(2, 0);
+        assert!(matches!(
+            client
+                .close_position(
+                    market(2),
+                    &flat,
+                    price(90_000),
+                    None,
+                    Some(offline_opts(vec![]))
+                )
+                .await,
+            Err(LighterError::ValidationError(_))
+        ));
+        assert!(client
+            .close_position(
+                market(3),
+                &long,
+                price(90_000),
+                None,
+                Some(offline_opts(vec![]))
+            )
+            .await
+            .is_err());
+    }
+
+    fn pair_leg(market_index: u8, client_order_index: i64) -> CreateOrderTxReq {
+        CreateOrderTxReq {
             client_order_index,
-            base_amount,
-            price,
-            is_ask,
-            order_type: ORDER_TYPE_LIMIT,
-            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
-            reduce_only: if reduce_only { 1 } else { 0 },
-            trigger_price: 0,
-            order_expiry: 0,
+            reduce_only: 0,
+            ..reduce_only_order(market_index, 0, 1_000)
+        }
+    }
+
+    fn send_tx_mock(server: &mut mockito::ServerGuard, tx_type: u8, nonce: i64) -> mockito::Mock {
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::Regex(format!(r#""tx_type":{},"#, tx_type)),
+                mockito::Matcher::Regex(format!(r#"nonce\\":{}[,}}]"#, nonce)),
+            ]))
+    }
+
+    #[tokio::test]
+    async fn test_paired_orders_unwind_accepted_leg() {
+        let mut server = mockito::Server::new_async().await;
+        let accepted = send_tx_mock(&mut server, TX_TYPE_L2_CREATE_ORDER, 1)
+            .with_body(r#"{"code":200,"tx_hash":"0xa"}"#)
+            .create_async()
+            .await;
+        let rejected = send_tx_mock(&mut server, TX_TYPE_L2_CREATE_ORDER, 2)
+            .with_status(400)
+            .with_body(r#"{"code":21701,"message":"not enough margin"}"#)
+            .create_async()
+            .await;
+        // The rejected leg's nonce is reused for the cancel
+        let cancel = send_tx_mock(&mut server, TX_TYPE_L2_CANCEL_ORDER, 2)
+            .with_body(r#"{"code":200,"tx_hash":"0xc"}"#)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let result = client
+            .create_paired_orders(
+                &pair_leg(0, 77),
+                &pair_leg(1, 78),
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!((result.a.tx.nonce, result.b.tx.nonce), (1, 2));
+        assert!(result.a.is_accepted());
+        assert!(!result.b.is_accepted());
+        assert!(!result.is_complete());
+        assert!(result.unwound);
+        assert!(matches!(result.unwind, Some(Ok(ref r)) if r.tx_hash.as_deref() == Some("0xc")));
+        accepted.assert_async().await;
+        rejected.assert_async().await;
+        cancel.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_paired_orders_both_accepted() {
+        let mut server = mockito::Server::new_async().await;
+        let sent = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::Regex(r#""tx_type":14,"#.to_string()))
+            .with_body(r#"{"code":200,"tx_hash":"0xa"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let result = client
+            .create_paired_orders(
+                &pair_leg(0, 77),
+                &pair_leg(1, 78),
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap();
+
+        assert!(result.is_complete());
+        assert!(result.unwind.is_none());
+        assert!(!result.unwound);
+        sent.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancel_for_market_reuses_nonce_after_rejection() {
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::Regex(r#"nonce\\":9[,}]"#.to_string()))
+            .with_body(r#"{"code":400,"message":"order not found"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let account = account_with_orders(serde_json::json!({
+            "2": [open_order(281474976710656, 2), open_order(281474976710657, 2)],
+        }));
+        let opts = TransactOpts {
+            nonce: Some(9),
+            ..Default::default()
+        };
+
+        let outcomes = client
+            .cancel_all_orders_for_market(market(2), &account, Some(opts))
+            .await
+            .unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes
+            .iter()
+            .all(|o| matches!(&o.result, Ok(r) if r.code == 400)));
+        rejected.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_register_api_key_with_l1_sends_wallet_signature() {
+        let wallet = L1Signer::from_private_key(
+            "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+        )
+        .unwrap();
+        let new_key = vec![9u8; PUBLIC_KEY_LENGTH];
+        let intent = OnboardingIntent {
+            pub_key: new_key.clone(),
+            nonce: 4,
+            account_index: 12345,
+            api_key_index: 2,
+        };
+        let expected = wallet.sign_onboarding_message(&intent).unwrap().to_hex();
+
+        let mut server = mockito::Server::new_async().await;
+        let sent = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::Regex(format!(r#""tx_type":{},"#, TX_TYPE_L2_CHANGE_PUB_KEY)),
+                mockito::Matcher::Regex(format!(r#"l1_sig\\":\\"{}\\""#, expected)),
+            ]))
+            .with_body(r#"{"code":200,"tx_hash":"0xk"}"#)
+            .create_async()
+            .await;
+
+        let client = test_client_for(&server.url(), 12345, 2);
+        let opts = TransactOpts {
+            nonce: Some(4),
+            expired_at: TEST_EXPIRED_AT,
+            ..Default::default()
+        };
+        let response = client
+            .register_api_key_with_l1(&wallet, &new_key, Some(opts))
+            .await
+            .unwrap();
+        assert_eq!(response.code, 200);
+        sent.assert_async().await;
+
+        let result = client
+            .register_api_key_with_l1(&wallet, &new_key[..20], None)
+            .await;
+        assert!(matches!(result, Err(LighterError::PubKeyInvalid)));
+    }
+
+    #[test]
+    fn test_create_auth_token_format() {
+        let client = offline_client();
+        let expiry = DateTime::from_timestamp(Utc::now().timestamp() + 600, 0).unwrap();
+
+        let token = client.create_auth_token(expiry).unwrap();
+        let parts: Vec<&str> = token.split(':').collect();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], expiry.timestamp().to_string());
+        assert_eq!(parts[1], "12345");
+        assert_eq!(parts[2], "0");
+        assert_eq!(parts[3].len(), SIGNATURE_LENGTH * 2);
+
+        let past = Utc::now() - chrono::Duration::seconds(1);
+        assert!(client.create_auth_token(past).is_err());
+        let too_far = Utc::now() + chrono::Duration::hours(9);
+        assert!(client.create_auth_token(too_far).is_err());
+    }
+
+    #[test]
+    fn test_create_auth_token_uses_client_clock() {
+        let replayed = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let mut client = offline_client();
+        client.set_clock(Arc::new(crate::clock::ReplayClock::new(replayed)));
+
+        let expiry = replayed + chrono::Duration::seconds(600);
+        let token = client.create_auth_token(expiry).unwrap();
+        assert!(token.starts_with("1700000600:"));
+        // Within the lifetime by the system clock, long past by the client's
+        let system_expiry = Utc::now() + chrono::Duration::seconds(600);
+        assert!(client.create_auth_token(system_expiry).is_err());
+    }
+
+    /// Signs with the hash it was given, zero-padded
+    #[derive(Default)]
+    struct EchoKey {
+        hashes: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl crate::signer::Signer for EchoKey {
+        fn sign(&self, hashed_message: &[u8]) -> Result<Vec<u8>> {
+            self.hashes.lock().unwrap().push(hashed_message.to_vec());
+            let mut signature = hashed_message.to_vec();
+            signature.resize(SIGNATURE_LENGTH, 0);
+            Ok(signature)
+        }
+    }
+
+    impl KeyManager for EchoKey {
+        fn pub_key(&self) -> &[u8] {
+            &[0u8; PUBLIC_KEY_LENGTH]
+        }
+
+        fn pub_key_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+            [0u8; PUBLIC_KEY_LENGTH]
+        }
+
+        #[cfg(feature = "expose-secrets")]
+        fn prv_key_bytes(&self) -> Vec<u8> {
+            vec![0u8; 40]
+        }
+    }
+
+    #[test]
+    fn test_auth_token_message_layout() {
+        // "1700000000:12345:2" as little-endian words, the last zero-padded
+        let elements = vec![0x3030303030303731, 0x35343332313a3030, 0x323a];
+        assert_eq!(
+            field_elements_from_bytes(b"1700000000:12345:2").unwrap(),
+            elements
+        );
+
+        let key = EchoKey::default();
+        let expiry = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let token = auth_token(&key, 12345, 2, expiry).unwrap();
+        let hash = hash_to_quintic_extension(&elements);
+        assert_eq!(*key.hashes.lock().unwrap(), vec![hash.clone()]);
+
+        let mut signature = hash;
+        signature.resize(SIGNATURE_LENGTH, 0);
+        assert_eq!(
+            token,
+            format!("1700000000:12345:2:{}", hex::encode(signature))
+        );
+    }
+
+    /// The signed hash covers the message
+    ///
+    /// No token from the Python SDK's `create_auth_token_with_expiry` has
+    /// been recorded yet; once Poseidon2 is implemented, pin one for these
+    /// inputs here and drop the `ignore`.
+    #[test]
+    #[ignore = "hash_to_quintic_extension returns zeros until Poseidon2 is implemented"]
+    fn test_auth_token_hash_depends_on_message() {
+        let key = EchoKey::default();
+        let expiry = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        auth_token(&key, 12345, 2, expiry).unwrap();
+        auth_token(&key, 12345, 3, expiry).unwrap();
+
+        let hashes = key.hashes.lock().unwrap();
+        assert!(hashes[0].iter().any(|&byte| byte != 0));
+        assert_ne!(hashes[0], hashes[1]);
+    }
+
+    #[tokio::test]
+    async fn test_signature_encoding_of_payloads() {
+        let mut client = offline_client();
+        let req = reduce_only_order(0, 1, 100);
+        let sig_of = |tx: &L2CreateOrderTxInfo| {
+            let info: serde_json::Value = serde_json::from_str(&tx.get_tx_info().unwrap()).unwrap();
+            info["sig"].clone()
+        };
+
+        let tx = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        let sig = sig_of(&tx);
+        assert_eq!(sig.as_array().unwrap().len(), SIGNATURE_LENGTH);
+
+        client.set_signature_encoding(SignatureEncoding::Base64);
+        let tx = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        let sig = sig_of(&tx).as_str().unwrap().to_string();
+        assert_eq!(SignatureEncoding::sniff(&sig), SignatureEncoding::Base64);
+        assert_eq!(sig.parse::<Signature>().unwrap().len(), SIGNATURE_LENGTH);
+
+        client.set_signature_encoding(SignatureEncoding::Hex0x);
+        let tx = client
+            .create_order(&req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        let sig = sig_of(&tx).as_str().unwrap().to_string();
+        assert!(sig.starts_with("0x"));
+        assert_eq!(sig.len(), 2 + SIGNATURE_LENGTH * 2);
+
+        // Tokens don't follow the payload encoding
+        let expiry = DateTime::from_timestamp(Utc::now().timestamp() + 600, 0).unwrap();
+        let token = client.create_auth_token(expiry).unwrap();
+        let token_sig = token.rsplit(':').next().unwrap();
+        assert_eq!(
+            SignatureEncoding::sniff(token_sig),
+            SignatureEncoding::HexPlain
+        );
+    }
+
+    #[test]
+    fn test_auth_token_cache_refreshes_near_expiry() {
+        let created = Arc::new(Mutex::new(0));
+        let count = created.clone();
+        let tokens = AuthTokens {
+            create: Box::new(move |expiry| {
+                *count.lock().unwrap() += 1;
+                Ok(expiry.timestamp().to_string())
+            }),
+            lifetime: chrono::Duration::seconds(600),
+            current: Mutex::new(None),
+        };
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let first = tokens.get(start).unwrap();
+        assert_eq!(
+            tokens.get(start + chrono::Duration::seconds(500)).unwrap(),
+            first
+        );
+        assert_eq!(*created.lock().unwrap(), 1);
+
+        // Within the refresh margin a new token is created
+        let later = start + chrono::Duration::seconds(600 - AUTH_TOKEN_REFRESH_MARGIN_SECS);
+        assert_ne!(tokens.get(later).unwrap(), first);
+        assert_eq!(*created.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_get_private_sends_auth_header() {
+        let (client, mock) = mocked_client();
+        let http = client.http().unwrap();
+        let token = http.auth_token().unwrap();
+        assert!(token.contains(":12345:0:"));
+        mock.on_get(
+            "/api/v1/accountActiveOrders",
+            serde_json::json!({"code": 200, "orders": []}),
+        );
+
+        for _ in 0..2 {
+            let body: serde_json::Value = http
+                .get_private("/api/v1/accountActiveOrders?account_index=12345&market_id=0")
+                .await
+                .unwrap();
+            assert_eq!(body["code"], 200);
+        }
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            assert_eq!(request.headers["authorization"], token.as_str());
+            assert_eq!(request.query_param("market_id"), Some("0"));
+        }
+    }
+
+    #[test]
+    fn test_switch_api_key_signs_new_tokens() {
+        let (mut client, _mock) = mocked_client();
+        let before = client.http().unwrap().auth_token().unwrap();
+        assert!(before.contains(":12345:0:"));
+
+        client.switch_api_key(3);
+        let after = client.http().unwrap().auth_token().unwrap();
+        assert!(after.contains(":12345:3:"), "{}", after);
+        assert_eq!(client.api_key_index(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_get_private_without_auth_source() {
+        let http = HTTPClient::new("http://127.0.0.1:1").unwrap();
+        let result: Result<serde_json::Value> =
+            http.get_private("/api/v1/accountActiveOrders").await;
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::InvalidConfiguration(_)
+        ));
+
+        // Self-signed tokens are opt-in
+        let mut client = TxClient::from_ids(
+            "http://127.0.0.1:1",
+            TEST_KEY,
+            account(12345),
+            api_key(0),
+            ChainId::new(1).unwrap(),
+        )
+        .unwrap();
+        assert!(client.http().unwrap().auth_token().is_err());
+        client.enable_experimental_auth_tokens().unwrap();
+        assert!(client.http().unwrap().auth_token().is_ok());
+        assert!(offline_client().enable_experimental_auth_tokens().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_preserves_raw_response() {
+        let mut server = mockito::Server::new_async().await;
+        let http = HTTPClient::new(&server.url()).unwrap();
+        let body = r#"{"code":200,"tx_hash":"0xabc","predicted_execution_time_ms":3}"#;
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .with_header("x-request-id", "req-1")
+            .with_header("x-ratelimit-remaining", "42")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let response = http.send_tx(TX_TYPE_L2_CREATE_ORDER, "{}").await.unwrap();
+        assert_eq!(response.tx_hash.as_deref(), Some("0xabc"));
+        assert_eq!(response.http_status, Some(200));
+        assert_eq!(response.request_id.as_deref(), Some("req-1"));
+        assert_eq!(response.rate_limit_remaining, Some(42));
+        assert_eq!(response.raw.as_deref(), Some(body));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_options_keep_the_request_timeout() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":3}"#)
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_request_timeout(Duration::from_secs(3)).unwrap();
+        let options = TcpOptions::default()
+            .nodelay(true)
+            .keepalive(Duration::from_secs(5))
+            .keepalive_interval(Duration::from_secs(1))
+            .local_address("127.0.0.1".parse().unwrap());
+        http.set_tcp_options(options).unwrap();
+
+        assert_eq!(http.request_timeout, Duration::from_secs(3));
+        assert_eq!(http.tcp, options);
+        assert_eq!(
+            http.get_next_nonce(account(12345), api_key(0))
+                .await
+                .unwrap(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_custom_transport_refuses_socket_settings() {
+        let mut http = HTTPClient::with_transport(Box::new(MockTransport::new()));
+        assert_eq!(http.api_url("nextNonce"), "/api/v1/nextNonce");
+        assert!(matches!(
+            http.set_request_timeout(Duration::from_secs(3)),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+        assert!(http.set_tcp_options(TcpOptions::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_with_retry_after() {
+        let mut server = mockito::Server::new_async().await;
+        let http = HTTPClient::new(&server.url()).unwrap();
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_status(429)
+            .with_header("retry-after", "7")
+            .with_header("x-ratelimit-remaining", "0")
+            .create_async()
+            .await;
+
+        let error = http
+            .get_next_nonce(account(12345), api_key(0))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            LighterError::RateLimited {
+                retry_after: Some(d),
+                remaining: Some(0),
+            } if d == Duration::from_secs(7)
+        ));
+        assert!(error.is_retryable());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+        let status = http.last_rate_limit_status().unwrap();
+        assert_eq!(status.retry_after, Some(Duration::from_secs(7)));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_without_headers() {
+        let mock = MockTransport::new();
+        mock.respond(
+            reqwest::Method::POST,
+            "/api/v1/sendTx",
+            TransportResponse::new(StatusCode::TOO_MANY_REQUESTS, "too many requests"),
+        );
+        let http = HTTPClient::with_transport(Box::new(mock));
+
+        let error = http
+            .send_tx(TX_TYPE_L2_CREATE_ORDER, "{}")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            LighterError::RateLimited {
+                retry_after: None,
+                remaining: None,
+            }
+        ));
+        assert_eq!(error.kind(), crate::errors::ErrorKind::Throttled);
+        assert_eq!(http.last_rate_limit_status(), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_quota_of_successful_responses() {
+        let mock = MockTransport::new();
+        mock.respond(
+            reqwest::Method::GET,
+            "/api/v1/nextNonce",
+            TransportResponse::json(&serde_json::json!({"code": 200, "nonce": 5}))
+                .header("x-ratelimit-remaining", "42")
+                .header("x-ratelimit-reset", "30"),
+        );
+        let http = HTTPClient::with_transport(Box::new(mock));
+        let clone = http.clone();
+
+        assert_eq!(
+            http.get_next_nonce(account(12345), api_key(0))
+                .await
+                .unwrap(),
+            5
+        );
+        let expected = RateLimitStatus {
+            remaining: Some(42),
+            reset_after: Some(Duration::from_secs(30)),
+            retry_after: None,
+        };
+        assert_eq!(clone.last_rate_limit_status(), Some(expected));
+    }
+
+    #[test]
+    fn test_rate_limit_header_formats() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert_eq!(RateLimitStatus::from_headers(&headers), None);
+        let in_a_minute = Utc::now() + chrono::Duration::seconds(60);
+        headers.insert(
+            "retry-after",
+            in_a_minute
+                .to_rfc2822()
+                .replace("+0000", "GMT")
+                .parse()
+                .unwrap(),
+        );
+        headers.insert(
+            "x-ratelimit-reset",
+            in_a_minute.timestamp().to_string().parse().unwrap(),
+        );
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        for wait in [status.retry_after.unwrap(), status.reset_after.unwrap()] {
+            assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60));
+        }
+
+        // Dates in the past mean no wait
+        headers.insert(
+            "retry-after",
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        let status = RateLimitStatus::from_headers(&headers).unwrap();
+        assert_eq!(status.retry_after, Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_retries_wait_for_retry_after() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .expect(2)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":9}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_rate_limit_retries(2);
+
+        // Without the header the backoff would wait at least a second
+        let started = std::time::Instant::now();
+        assert_eq!(
+            http.get_next_nonce(account(12345), api_key(0))
+                .await
+                .unwrap(),
+            9
+        );
+        assert!(started.elapsed() < RATE_LIMIT_BACKOFF);
+        limited.assert_async().await;
+        ok.assert_async().await;
+
+        // Retries exhausted
+        http.set_rate_limit_retries(0);
+        limited.remove_async().await;
+        ok.remove_async().await;
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_status(429)
+            .create_async()
+            .await;
+        assert!(matches!(
+            http.get_next_nonce(account(12345), api_key(0)).await,
+            Err(LighterError::RateLimited { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_odd_bodies() {
+        let mut server = mockito::Server::new_async().await;
+        let http = HTTPClient::new(&server.url()).unwrap();
+
+        let html = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(format!("<html>{}</html>", "x".repeat(2000)))
+            .create_async()
+            .await;
+        match http.send_tx(TX_TYPE_L2_CREATE_ORDER, "{}").await {
+            Err(LighterError::InvalidResponse(message)) => {
+                assert!(message.contains("<html>xxx"), "{}", message);
+                assert!(message.len() < 1300);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        html.remove_async().await;
+
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .with_status(502)
+            .with_header("x-request-id", "req-2")
+            .with_body("upstream unavailable")
+            .create_async()
+            .await;
+        match http.send_tx(TX_TYPE_L2_CREATE_ORDER, "{}").await {
+            Err(LighterError::TxSubmissionFailed {
+                status,
+                request_id,
+                body,
+            }) => {
+                assert_eq!(status, 502);
+                assert_eq!(request_id.as_deref(), Some("req-2"));
+                assert_eq!(body, "upstream unavailable");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_truncate_body_respects_char_boundaries() {
+        let body = "é".repeat(600);
+        let excerpt = truncate_body(&body);
+        assert_eq!(excerpt.len(), MAX_BODY_EXCERPT);
+        assert_eq!(truncate_body("short"), "short");
+    }
+
+    #[tokio::test]
+    async fn test_default_expiry_per_tx_kind() {
+        let mut client = offline_client();
+        client.set_default_expiry(TxKind::Order, Duration::from_secs(300));
+        client.set_default_expiry(TxKind::Transfer, Duration::from_secs(3_600));
+        let nonce_only = || {
+            Some(TransactOpts {
+                nonce: Some(1),
+                ..Default::default()
+            })
+        };
+        let transfer_req = TransferTxReq::usdc(2, Decimal::ONE, Decimal::ZERO, [0u8; 32]).unwrap();
+
+        let now = Utc::now().timestamp_millis();
+        let transfer = client.transfer(&transfer_req, nonce_only()).await.unwrap();
+        let order = client
+            .create_order(&reduce_only_order(0, 0, 1_000), nonce_only())
+            .await
+            .unwrap();
+        let fallback = client.fill_default_opts(nonce_only()).await.unwrap();
+
+        let lifetime = |expired_at: i64| (expired_at - now) / 1000;
+        assert!((3_595..=3_600).contains(&lifetime(transfer.expired_at)));
+        assert!((295..=300).contains(&lifetime(order.expired_at)));
+        assert!((595..=600).contains(&lifetime(fallback.expired_at)));
+
+        // Explicit opts always win
+        let explicit = client
+            .transfer(&transfer_req, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert_eq!(explicit.expired_at, TEST_EXPIRED_AT);
+    }
+
+    #[test]
+    fn test_tx_kind_classification() {
+        assert_eq!(TxKind::of(TX_TYPE_L2_CREATE_ORDER), Some(TxKind::Order));
+        assert_eq!(TxKind::of(TX_TYPE_L2_CHANGE_PUB_KEY), Some(TxKind::Admin));
+        assert_eq!(TxKind::of(TX_TYPE_L2_WITHDRAW), Some(TxKind::Transfer));
+        assert_eq!(TxKind::of(TX_TYPE_INTERNAL_CLAIM_ORDER), None);
+    }
+
+    #[tokio::test]
+    async fn test_trade_history_stream_follows_pages() {
+        let mut server = mockito::Server::new_async().await;
+        let trade = |id: i64| {
+            format!(
+                r#"{{"trade_id":{},"tx_hash":"0x{}","market_id":0,"size":"1.5","price":"3000.12","usd_amount":"4500.18","ask_account_id":7,"bid_account_id":12345,"is_maker_ask":true,"timestamp":1700000000123}}"#,
+                id, id
+            )
         };
+        let first = server
+            .mock("GET", "/api/v1/trades")
+            .match_query(mockito::Matcher::Exact(
+                "account_index=12345&limit=2&market_id=0".into(),
+            ))
+            .match_header("authorization", mockito::Matcher::Regex(":12345:0:".into()))
+            .with_body(format!(
+                r#"{{"code":200,"next_cursor":"page2","trades":[{},{}]}}"#,
+                trade(1),
+                trade(2)
+            ))
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/api/v1/trades")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "cursor".into(),
+                "page2".into(),
+            ))
+            .with_body(format!(r#"{{"code":200,"trades":[{}]}}"#, trade(3)))
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let options = PaginationOptions {
+            limit: 2,
+            min_interval: Duration::from_millis(50),
+            max_pages: None,
+        };
+        let started = std::time::Instant::now();
+        let fills: Vec<Fill> = futures_util::StreamExt::collect::<Vec<_>>(
+            client
+                .http()
+                .unwrap()
+                .trade_history_stream(account(12345), Some(market(0)), options),
+        )
+        .await
+        .into_iter()
+        .collect::<Result<_>>()
+        .unwrap();
+
+        assert!(started.elapsed() >= options.min_interval);
+        assert_eq!(
+            fills.iter().map(|f| f.trade_id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(fills[0].price, Decimal::new(300_012, 2));
+        assert_eq!(fills[0].side_for(12345), Some(Side::Buy));
+        assert_eq!(fills[0].is_maker_for(12345), Some(false));
+        assert_eq!(fills[0].timestamp.timestamp_millis(), 1_700_000_000_123);
+        first.assert_async().await;
+        second.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_order_history_page() {
+        let (client, mock) = mocked_client();
+        mock.respond(
+            reqwest::Method::GET,
+            "/api/v1/accountInactiveOrders",
+            TransportResponse::new(
+                StatusCode::OK,
+                r#"{"code":200,"next_cursor":"","orders":[{"order_index":5,"client_order_index":9,"market_index":1,"is_ask":false,"type":"limit","status":"filled","price":"10.5","initial_base_amount":"2","filled_base_amount":"2","filled_quote_amount":"20.5","timestamp":1700000000}]}"#,
+            ),
+        );
+
+        let page = client
+            .http()
+            .unwrap()
+            .get_order_history(account(12345), None, None, 10)
+            .await
+            .unwrap();
+
+        assert_eq!(page.next_cursor, None);
+        let order = &page.items[0];
+        assert_eq!(order.side(), Side::Buy);
+        assert_eq!(order.average_fill_price(), Some(Decimal::new(1025, 2)));
+        assert_eq!(order.timestamp.timestamp(), 1_700_000_000);
+    }
+
+    async fn fee_mock(server: &mut mockito::ServerGuard, fee: i64) -> mockito::Mock {
+        server
+            .mock("GET", "/api/v1/transferFeeInfo")
+            .match_query(mockito::Matcher::Exact(
+                "account_index=12345&to_account_index=678".to_string(),
+            ))
+            .match_header("authorization", mockito::Matcher::Any)
+            .with_body(format!(r#"{{"code":200,"transfer_fee_usdc":{}}}"#, fee))
+            .create_async()
+            .await
+    }
 
-        self.create_order(&req, opts).await
+    #[tokio::test]
+    async fn test_get_transfer_fee_info() {
+        let (client, mock) = mocked_client();
+        mock.on_get(
+            "/api/v1/transferFeeInfo",
+            serde_json::json!({"code": 200, "transfer_fee_usdc": 250_000}),
+        );
+
+        let info = client
+            .http()
+            .unwrap()
+            .get_transfer_fee_info(account(12345), account(678))
+            .await
+            .unwrap();
+
+        assert_eq!(info.transfer_fee, 250_000);
+        assert_eq!(info.fee_usdc(), Decimal::new(25, 2));
+        let request = &mock.requests()[0];
+        assert_eq!(request.query_param("to_account_index"), Some("678"));
+        assert!(request.headers.contains_key("authorization"));
     }
 
-    /// Create a market order (convenience wrapper around create_order)
-    ///
-    /// Market orders execute immediately at the best available price
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_market_order(
-        &self,
-        market_index: u8,
-        client_order_index: i64,
-        base_amount: i64,
-        price: u32,
-        is_ask: u8,
-        reduce_only: bool,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
-            market_index,
-            client_order_index,
+    #[tokio::test]
+    async fn test_transfer_auto_fee_rejects_fee_above_limit() {
+        let mut server = mockito::Server::new_async().await;
+        let _mock = fee_mock(&mut server, 1_500_000).await;
+
+        let client = test_client(&server.url());
+        let err = client
+            .transfer_auto_fee(
+                account(678),
+                Decimal::from(10),
+                [0; 32],
+                Decimal::ONE,
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            LighterError::TransferFeeAboveLimit {
+                fee: 1_500_000,
+                max_fee: 1_000_000
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_auto_fee_fills_request() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = fee_mock(&mut server, 250_000).await;
+
+        let client = test_client(&server.url());
+        let mut memo = [0u8; 32];
+        memo[..4].copy_from_slice(b"rent");
+        let tx = client
+            .transfer_auto_fee(
+                account(678),
+                Decimal::new(12_345, 2),
+                memo,
+                Decimal::ONE,
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(tx.to_account_index, 678);
+        assert_eq!(tx.usdc_amount, 123_450_000);
+        assert_eq!(tx.fee, 250_000);
+        assert_eq!(tx.memo, memo);
+        assert!(tx.sig.is_some());
+        mock.assert();
+
+        // Amounts are checked before the fee is quoted
+        assert!(matches!(
+            client
+                .transfer_auto_fee(account(678), Decimal::ZERO, memo, Decimal::ONE, None)
+                .await,
+            Err(LighterError::TransferAmountTooLow(0))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_modifies_are_recorded_with_their_diff() {
+        use crate::amendments::{
+            AmendmentDiff, AmendmentLog, FieldChange, ModifyAudit, OrderValues,
+        };
+
+        let (tx, mut audit) = tokio::sync::mpsc::unbounded_channel();
+        let log = Arc::new(AmendmentLog::new(8).with_sink(tx));
+        let mut client = offline_client();
+        client.set_amendment_log(log.clone());
+        let modify = |base_amount, price| ModifyOrderTxReq {
+            market_index: 1,
+            index: 281_475_028_317_823,
             base_amount,
             price,
-            is_ask,
-            order_type: ORDER_TYPE_MARKET,
-            time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
-            reduce_only: if reduce_only { 1 } else { 0 },
             trigger_price: 0,
-            order_expiry: 0,
+        };
+        let resting = OrderValues {
+            base_amount: 1_000,
+            price: 100_000,
+            trigger_price: 0,
         };
 
-        self.create_order(&req, opts).await
+        let first = client
+            .modify_order_audited(
+                &modify(1_000, 100_500),
+                ModifyAudit::reason("chase the bid").previous(resting),
+                Some(offline_opts(vec![])),
+            )
+            .await
+            .unwrap();
+        // Previous values come from the log from now on
+        client
+            .modify_order(&modify(600, 100_500), Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+
+        let amendments = log.get(281_475_028_317_823);
+        assert_eq!(amendments.len(), 2);
+        assert_eq!(amendments[0].reason.as_deref(), Some("chase the bid"));
+        assert_eq!(amendments[0].tx_hash, first.signed_hash);
+        assert_eq!(
+            amendments[0].diff,
+            Some(AmendmentDiff {
+                price: Some(FieldChange {
+                    old: 100_000,
+                    new: 100_500
+                }),
+                ..Default::default()
+            })
+        );
+        assert_eq!(amendments[1].reason, None);
+        assert!(amendments[1].at >= amendments[0].at);
+
+        let sent: Vec<_> = std::iter::from_fn(|| audit.try_recv().ok()).collect();
+        assert_eq!(sent, amendments);
+        assert_eq!(
+            serde_json::to_value(sent[1].diff).unwrap(),
+            serde_json::json!({"base_amount": {"old": 1_000, "new": 600}})
+        );
     }
 
-    /// Create a take profit order
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_tp_order(
-        &self,
-        market_index: u8,
-        client_order_index: i64,
-        base_amount: i64,
-        trigger_price: u32,
-        price: u32,
-        is_ask: u8,
-        reduce_only: bool,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
-            market_index,
-            client_order_index,
-            base_amount,
-            price,
-            is_ask,
-            order_type: ORDER_TYPE_TAKE_PROFIT,
-            time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
-            reduce_only: if reduce_only { 1 } else { 0 },
-            trigger_price,
-            order_expiry: 0,
-        };
+    #[tokio::test]
+    async fn test_order_throttle_applies_to_new_orders() {
+        use crate::throttle::{RestingQuote, ThrottleConfig, ThrottleMode};
 
-        self.create_order(&req, opts).await
+        let mut client = offline_client();
+        let throttle = Arc::new(
+            OrderThrottle::new(ThrottleConfig {
+                orders_per_sec: 1.0,
+                burst: 1,
+                mode: ThrottleMode::FailFast,
+                prevent_self_cross: true,
+            })
+            .unwrap(),
+        );
+        throttle.set_resting_quotes(
+            0,
+            vec![RestingQuote {
+                is_ask: true,
+                price: 100_000,
+            }],
+        );
+        client.set_order_throttle(throttle);
+
+        let mut order = reduce_only_order(0, 0, 1_000);
+        order.reduce_only = 0;
+        order.price = 100_000;
+        assert!(matches!(
+            client
+                .create_order(&order, Some(offline_opts(vec![])))
+                .await,
+            Err(LighterError::SelfCross { .. })
+        ));
+
+        order.price = 99_000;
+        client
+            .create_order(&order, Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+        assert!(matches!(
+            client
+                .create_order(&order, Some(offline_opts(vec![])))
+                .await,
+            Err(LighterError::Throttled { .. })
+        ));
     }
 
-    /// Create a take profit limit order
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_tp_limit_order(
-        &self,
-        market_index: u8,
-        client_order_index: i64,
-        base_amount: i64,
-        trigger_price: u32,
-        price: u32,
-        is_ask: u8,
-        reduce_only: bool,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
-            market_index,
-            client_order_index,
-            base_amount,
-            price,
-            is_ask,
-            order_type: ORDER_TYPE_TAKE_PROFIT_LIMIT,
-            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
-            reduce_only: if reduce_only { 1 } else { 0 },
-            trigger_price,
-            order_expiry: 0,
+    #[tokio::test]
+    async fn test_market_status_gates_new_orders() {
+        async fn sent(client: &TxClient, order: &CreateOrderTxReq) -> Result<L2CreateOrderTxInfo> {
+            let long = Position {
+                market_index: 0,
+                base_amount: 1_000,
+            };
+            client
+                .create_order(order, Some(offline_opts(vec![long])))
+                .await
+        }
+
+        let (mut client, mock) = mocked_client();
+        let statuses = MarketStatuses::new();
+        client.set_market_statuses(statuses.clone());
+
+        let reducing = reduce_only_order(0, 1, 1_000);
+        let opening = CreateOrderTxReq {
+            reduce_only: 0,
+            ..reducing.clone()
         };
 
-        self.create_order(&req, opts).await
+        // Markets without a known status aren't gated
+        sent(&client, &opening).await.unwrap();
+
+        statuses.set(0, MarketStatus::ReduceOnly);
+        sent(&client, &reducing).await.unwrap();
+        assert!(matches!(
+            sent(&client, &opening).await,
+            Err(LighterError::MarketNotActive(MarketStatus::ReduceOnly))
+        ));
+
+        statuses.set(0, MarketStatus::Halted);
+        for order in [&opening, &reducing] {
+            let err = sent(&client, order).await.unwrap_err();
+            assert!(matches!(
+                err,
+                LighterError::MarketNotActive(MarketStatus::Halted)
+            ));
+            assert!(!err.is_retryable());
+        }
+        let grouped = CreateGroupedOrdersTxReq {
+            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+            orders: vec![reducing.clone(), reducing],
+        };
+        assert!(matches!(
+            client
+                .create_grouped_orders(&grouped, Some(offline_opts(vec![])))
+                .await,
+            Err(LighterError::MarketNotActive(MarketStatus::Halted))
+        ));
+
+        statuses.set(0, MarketStatus::Active);
+        sent(&client, &opening).await.unwrap();
+        assert!(mock.requests().is_empty());
     }
 
-    /// Create a stop loss order
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_sl_order(
-        &self,
-        market_index: u8,
-        client_order_index: i64,
-        base_amount: i64,
-        trigger_price: u32,
-        price: u32,
-        is_ask: u8,
-        reduce_only: bool,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
-            market_index,
-            client_order_index,
-            base_amount,
-            price,
-            is_ask,
-            order_type: ORDER_TYPE_STOP_LOSS,
-            time_in_force: TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
-            reduce_only: if reduce_only { 1 } else { 0 },
-            trigger_price,
-            order_expiry: 0,
+    #[tokio::test]
+    async fn test_circuit_breaker_records_submissions() {
+        use crate::risk::{CircuitBreakerConfig, CircuitState};
+
+        let (mut client, mock) = mocked_client();
+        let breaker = Arc::new(
+            CircuitBreaker::new(CircuitBreakerConfig {
+                failure_threshold: 2,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        client.set_circuit_breaker(breaker.clone());
+        for code in [200, 21120, 21120] {
+            mock.on_post("/api/v1/sendTx", serde_json::json!({"code": code}));
+        }
+        let opts = TransactOpts {
+            expired_at: TEST_EXPIRED_AT,
+            nonce: Some(1),
+            ..Default::default()
         };
+        let tx = client
+            .create_order(&pair_leg(0, 1), Some(opts))
+            .await
+            .unwrap();
 
-        self.create_order(&req, opts).await
+        assert_eq!(client.send_transaction(&tx).await.unwrap().code, 200);
+        // Rejections count as failures
+        for _ in 0..2 {
+            assert_eq!(client.send_transaction(&tx).await.unwrap().code, 21120);
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        let err = client.send_transaction(&tx).await.unwrap_err();
+        assert!(matches!(
+            err,
+            LighterError::CircuitOpen {
+                retry_after: Some(_)
+            }
+        ));
+        assert!(err.is_retryable());
+        assert_eq!(mock.requests().len(), 3);
     }
 
-    /// Create a stop loss limit order
-    #[allow(clippy::too_many_arguments)]
-    pub async fn create_sl_limit_order(
-        &self,
-        market_index: u8,
-        client_order_index: i64,
-        base_amount: i64,
-        trigger_price: u32,
-        price: u32,
-        is_ask: u8,
-        reduce_only: bool,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2CreateOrderTxInfo> {
-        let req = CreateOrderTxReq {
-            market_index,
-            client_order_index,
-            base_amount,
-            price,
-            is_ask,
-            order_type: ORDER_TYPE_STOP_LOSS_LIMIT,
-            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
-            reduce_only: if reduce_only { 1 } else { 0 },
-            trigger_price,
-            order_expiry: 0,
+    #[tokio::test]
+    async fn test_dropped_probe_is_given_back() {
+        use crate::clock::ReplayClock;
+        use crate::risk::{CircuitBreakerConfig, CircuitState};
+
+        let mock = Arc::new(MockTransport::new());
+        let transport = Arc::new(StallingTransport {
+            inner: mock.clone(),
+            stalled: true.into(),
+        });
+        let mut client = test_client("http://127.0.0.1:1");
+        client
+            .http_mut()
+            .unwrap()
+            .set_transport(Box::new(transport.clone()));
+        let clock = Arc::new(ReplayClock::new(
+            DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        ));
+        let breaker = Arc::new(
+            CircuitBreaker::new(CircuitBreakerConfig {
+                failure_threshold: 1,
+                ..Default::default()
+            })
+            .unwrap()
+            .with_clock(clock.clone()),
+        );
+        client.set_circuit_breaker(breaker.clone());
+        mock.on_post("/api/v1/sendTx", serde_json::json!({"code": 200}));
+        let opts = TransactOpts {
+            expired_at: TEST_EXPIRED_AT,
+            nonce: Some(1),
+            ..Default::default()
         };
+        let tx = client
+            .create_order(&pair_leg(0, 1), Some(opts))
+            .await
+            .unwrap();
+        breaker.record_failure();
+        clock.advance(breaker.config().cool_down);
 
-        self.create_order(&req, opts).await
+        // The probe's send is dropped before an answer
+        let probe = tokio::time::timeout(Duration::from_millis(50), client.send_transaction(&tx));
+        assert!(probe.await.is_err());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        transport.stalled.store(false, Ordering::SeqCst);
+        assert_eq!(client.send_transaction(&tx).await.unwrap().code, 200);
+        assert_eq!(breaker.state(), CircuitState::Closed);
     }
 
-    /// Update leverage with a user-friendly leverage parameter
-    ///
-    /// # Arguments
-    /// * `market_index` - The market to update leverage for
-    /// * `leverage` - Leverage multiplier (e.g., 5 for 5x, 10 for 10x)
-    /// * `margin_mode` - MARGIN_MODE_CROSS or MARGIN_MODE_ISOLATED
-    /// * `opts` - Optional transaction options
-    pub async fn update_leverage_with_multiplier(
-        &self,
-        market_index: u8,
-        leverage: u16,
-        margin_mode: u8,
-        opts: Option<TransactOpts>,
-    ) -> Result<L2UpdateLeverageTxInfo> {
-        if leverage == 0 {
-            return Err(LighterError::ValidationError(
-                "Leverage must be greater than 0".to_string(),
-            ));
+    #[tokio::test]
+    async fn test_get_market_status() {
+        let (client, mock) = mocked_client();
+        mock.on_get(
+            "/api/v1/orderBooks",
+            serde_json::json!({"code": 200, "order_books": [
+                {"market_id": 1, "symbol": "BTC", "status": "active"},
+                {"market_id": 2, "symbol": "ETH", "status": "reduce_only"}
+            ]}),
+        );
+        let http = client.http().unwrap();
+
+        assert_eq!(
+            http.get_market_status(2).await.unwrap(),
+            MarketStatus::ReduceOnly
+        );
+        assert_eq!(mock.requests()[0].query_param("market_id"), Some("2"));
+        assert!(http.get_market_status(3).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_envelope_submits_payload_untouched() {
+        let payload = r#"{"AccountIndex":12345,"ApiKeyIndex":2,"MarketIndex":0,"Index":77,"ExpiredAt":4000000000000,"Nonce":42,"Sig":"AQID"}"#;
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "tx_type": TX_TYPE_L2_CANCEL_ORDER,
+                "tx_info": payload,
+            })))
+            .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+            .create_async()
+            .await;
+
+        let client = test_client_for(&server.url(), 12345, 2);
+        let envelope =
+            crate::types::interop::from_python_sdk_json(TX_TYPE_L2_CANCEL_ORDER, payload).unwrap();
+        assert_eq!(envelope.signature(), Some([1u8, 2, 3].as_slice()));
+        let response = client.send_envelope(&envelope).await.unwrap();
+
+        assert_eq!(response.tx_hash.as_deref(), Some("0xabc"));
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_api_url_composition() {
+        let mut http = HTTPClient::new("https://api.example.com/").unwrap();
+        assert_eq!(
+            http.api_url("nextNonce"),
+            "https://api.example.com/api/v1/nextNonce"
+        );
+
+        for prefix in ["/gw/lighter", "gw/lighter/", "/gw/lighter/"] {
+            http.set_path_prefix(prefix);
+            assert_eq!(
+                http.api_url("sendTx"),
+                "https://api.example.com/gw/lighter/api/v1/sendTx"
+            );
         }
 
-        // Convert leverage to initial margin fraction
-        // IMF = 10,000 / leverage (Python: imf = int(10_000 / leverage))
-        let initial_margin_fraction = 10_000 / leverage;
+        http.set_api_version(ApiVersion::V2);
+        assert_eq!(
+            http.api_url("/orderBookOrders"),
+            "https://api.example.com/gw/lighter/api/v2/orderBookOrders"
+        );
 
-        let req = UpdateLeverageTxReq {
-            market_index,
-            initial_margin_fraction,
-            margin_mode,
+        http.set_path_prefix("/");
+        assert_eq!(
+            http.api_url("sendTx"),
+            "https://api.example.com/api/v2/sendTx"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_prefix_and_query_encoding() {
+        let mut server = mockito::Server::new_async().await;
+        let nonce = server
+            .mock("GET", "/gw/api/v2/nextNonce")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("account_index".into(), "12345".into()),
+                mockito::Matcher::UrlEncoded("api_key_index".into(), "0".into()),
+            ]))
+            .with_body(r#"{"nonce":9}"#)
+            .create_async()
+            .await;
+        let trades = server
+            .mock("GET", "/gw/api/v2/trades")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "cursor".into(),
+                "a&b=c d".into(),
+            ))
+            .with_body(r#"{"trades":[]}"#)
+            .create_async()
+            .await;
+
+        let mut client = test_client(&server.url());
+        let http = client.http_mut().unwrap();
+        http.set_path_prefix("gw/");
+        http.set_api_version(ApiVersion::V2);
+        let http = client.http().unwrap();
+
+        assert_eq!(
+            http.get_next_nonce(account(12345), api_key(0))
+                .await
+                .unwrap(),
+            9
+        );
+        let page = http
+            .get_trade_history(account(12345), None, Some("a&b=c d"), 10)
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+        nonce.assert_async().await;
+        trades.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_runtime_config_applies_to_the_next_order() {
+        use crate::throttle::{ThrottleConfig, ThrottleMode};
+
+        let mut client = offline_client();
+        let slow = ThrottleConfig {
+            orders_per_sec: 0.001,
+            burst: 1,
+            mode: ThrottleMode::FailFast,
+            prevent_self_cross: false,
+        };
+        client.set_order_throttle(Arc::new(OrderThrottle::new(slow).unwrap()));
+        let req = order_params(0, Side::Buy, 1_000, 100_000).to_request(OrderKind::Limit);
+        let opts = TransactOpts {
+            expired_at: Utc::now().timestamp_millis() + 60_000,
+            ..offline_opts(vec![])
         };
 
-        self.update_leverage(&req, opts).await
+        client.create_order(&req, Some(opts.clone())).await.unwrap();
+        assert!(matches!(
+            client.create_order(&req, Some(opts.clone())).await,
+            Err(LighterError::Throttled { .. })
+        ));
+        // The bucket refills at the new rate straight away
+        client
+            .update_config(|config| {
+                config.order_throttle = Some(ThrottleConfig {
+                    orders_per_sec: 1e9,
+                    ..slow
+                })
+            })
+            .unwrap();
+        client.create_order(&req, Some(opts.clone())).await.unwrap();
+
+        client
+            .update_config(|config| config.min_remaining_validity_ms = Some(120_000))
+            .unwrap();
+        assert!(matches!(
+            client.create_order(&req, Some(opts.clone())).await,
+            Err(LighterError::ExpiredAtInvalid {
+                min_remaining_ms: 120_000,
+                ..
+            })
+        ));
+
+        // An invalid update changes nothing
+        assert!(client
+            .update_config(|config| {
+                config.min_remaining_validity_ms = None;
+                config.order_throttle = Some(ThrottleConfig { burst: 0, ..slow });
+            })
+            .is_err());
+        assert_eq!(
+            client.runtime_config().get().min_remaining_validity_ms,
+            Some(120_000)
+        );
     }
 
-    /// Send a signed transaction to the API
-    ///
-    /// # Arguments
-    /// * `tx_info` - Any type implementing TxInfo trait
-    pub async fn send_transaction<T: TxInfo>(&self, tx_info: &T) -> Result<TxResponse> {
-        if let Some(client) = &self.api_client {
-            let tx_type = tx_info.get_tx_type();
-            let tx_json = tx_info.get_tx_info()?;
-            client.send_tx(tx_type, &tx_json).await
-        } else {
-            Err(LighterError::InvalidConfiguration(
-                "HTTPClient is not configured. Provide a valid API URL when creating TxClient."
-                    .to_string(),
+    #[tokio::test]
+    async fn test_refuses_to_sign_close_to_expiry() {
+        let mut client = offline_client();
+        let req = order_params(0, Side::Buy, 1_000, 100_000).to_request(OrderKind::Limit);
+        let opts = TransactOpts {
+            expired_at: Utc::now().timestamp_millis() + 2_000,
+            ..offline_opts(vec![])
+        };
+
+        match client.create_order(&req, Some(opts.clone())).await {
+            Err(LighterError::ExpiredAtInvalid {
+                remaining_ms,
+                min_remaining_ms: 5_000,
+            }) => assert!(remaining_ms <= 2_000),
+            other => panic!("expected ExpiredAtInvalid, got {:?}", other),
+        }
+        assert!(matches!(
+            client.fill_default_opts(Some(opts.clone())).await,
+            Err(LighterError::ExpiredAtInvalid { .. })
+        ));
+
+        client.set_min_remaining_validity(Some(Duration::from_secs(1)));
+        assert!(client.create_order(&req, Some(opts.clone())).await.is_ok());
+        client.set_min_remaining_validity(None);
+        let past = TransactOpts {
+            expired_at: Utc::now().timestamp_millis() - 1_000,
+            ..opts
+        };
+        assert!(client.create_order(&req, Some(past)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_send_checks_and_resigns_stale_transactions() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server.url());
+        let mut tx = client
+            .create_order(&reduce_only_order(0, 0, 1_000), Some(offline_opts(vec![])))
+            .await
+            .unwrap();
+
+        // Time passed between signing and sending
+        tx.expired_at = Utc::now().timestamp_millis() + 1_000;
+        assert!(matches!(
+            client.send_transaction(&tx).await,
+            Err(LighterError::ExpiredAtInvalid { .. })
+        ));
+
+        let response = client.send_transaction_resigning(&mut tx).await.unwrap();
+        assert_eq!(response.tx_hash.as_deref(), Some("0xabc"));
+        // Same nonce, fresh default expiry
+        assert_eq!(tx.nonce, 1);
+        let lifetime = tx.expired_at - Utc::now().timestamp_millis();
+        assert!((590_000..=600_000).contains(&lifetime), "{}", lifetime);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_body_size_guard() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .expect(0)
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_max_body_size(64);
+
+        let err = http
+            .send_tx(TX_TYPE_L2_CREATE_GROUPED_ORDERS, &"x".repeat(64))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, LighterError::ValidationError(_)), "{}", err);
+        mock.assert_async().await;
+    }
+
+    fn gunzip(body: &[u8]) -> String {
+        let mut decoded = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::GzDecoder::new(body), &mut decoded)
+            .unwrap();
+        decoded
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_gzip_round_trip() {
+        let tx_info = format!(r#"{{"orders":"{}"}}"#, "a".repeat(2_000));
+        let expected = serde_json::json!({
+            "tx_type": TX_TYPE_L2_CREATE_GROUPED_ORDERS,
+            "tx_info": tx_info,
+        });
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_header("content-encoding", "gzip")
+            .match_request(move |request| {
+                let body = gunzip(request.body().unwrap());
+                serde_json::from_str::<serde_json::Value>(&body).unwrap() == expected
+            })
+            .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_gzip_requests(true);
+
+        let response = http
+            .send_tx(TX_TYPE_L2_CREATE_GROUPED_ORDERS, &tx_info)
+            .await
+            .unwrap();
+        let payload = response.payload_bytes.unwrap();
+        let compressed = response.compressed_bytes.unwrap();
+        assert!(payload > 2_000);
+        assert!(compressed < payload / 10, "{} of {}", compressed, payload);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_tx_gzip_falls_back_on_415() {
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_header("content-encoding", "gzip")
+            .with_status(415)
+            .expect(1)
+            .create_async()
+            .await;
+        let plain = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_header("content-encoding", mockito::Matcher::Missing)
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"tx_type": TX_TYPE_L2_CANCEL_ORDER}),
             ))
+            .with_body(r#"{"code":200,"tx_hash":"0xabc"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_gzip_requests(true);
+
+        for _ in 0..2 {
+            let response = http.send_tx(TX_TYPE_L2_CANCEL_ORDER, "{}").await.unwrap();
+            assert_eq!(response.code, 200);
+            assert_eq!(response.compressed_bytes, None);
+            assert!(response.payload_bytes.is_some());
         }
+        // Only the first request tried gzip
+        rejected.assert_async().await;
+        plain.assert_async().await;
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    #[allow(deprecated)]
+    fn test_raw_constructor_validates_ids() {
+        let client = TxClient::new("", TEST_KEY, 12345, 2, 304).unwrap();
+        assert_eq!(client.account_index(), 12345);
+        assert_eq!(client.api_key_index(), 2);
+
+        // Out-of-range values, e.g. from transposed arguments
+        assert!(matches!(
+            TxClient::new("", TEST_KEY, 12345, 0, 0),
+            Err(LighterError::InvalidConfiguration(_))
+        ));
+        assert!(matches!(
+            TxClient::new("", TEST_KEY, -1, 0, 304),
+            Err(LighterError::AccountIndexTooLow(-1))
+        ));
+        assert!(matches!(
+            TxClient::new("", TEST_KEY, 12345, NIL_API_KEY_INDEX, 304),
+            Err(LighterError::ApiKeyIndexTooHigh(_))
+        ));
+    }
 
     #[test]
-    fn test_http_client_creation() {
-        let client = HTTPClient::new("https://api.lighter.xyz");
-        assert!(client.is_ok());
+    fn test_debug_redacts_private_key() {
+        let client = offline_client();
+        let debug = format!("{:?}", client);
+        assert!(debug.contains("account_index: 12345"));
+        assert!(debug.contains("api_key_index: 0"));
+        assert!(!debug.contains("1234567890abcdef"));
+        assert!(!debug.to_lowercase().contains("private"));
     }
 }
@@ -0,0 +1,86 @@
+//! Sources of the current time
+//!
+//! Time-dependent logic reads "now" from a [`Clock`]: default expiries of
+//! [`TxClient`](crate::client::TxClient) and the sample times of
+//! [`RollingBookStats`](crate::book_stats::RollingBookStats) and
+//! [`OrderFlowAnalyzer`](crate::order_flow::OrderFlowAnalyzer). The
+//! [`SystemClock`] is used unless another one is set. A [`ReplayClock`]
+//! is moved along by [`ReplayDriver`](crate::replay::ReplayDriver), so that
+//! logic sees the recorded times while a capture is replayed.
+//!
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use lighter_rs::clock::{Clock, ReplayClock};
+//!
+//! let start = Utc.timestamp_millis_opt(1_700_000_000_000).unwrap();
+//! let clock = ReplayClock::new(start);
+//! clock.advance(std::time::Duration::from_millis(250));
+//! assert_eq!(clock.now_ms(), 1_700_000_000_250);
+//! ```
+
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Source of the current time
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Current time in milliseconds since the Unix epoch
+    fn now_ms(&self) -> i64 {
+        self.now().timestamp_millis()
+    }
+}
+
+/// Shared clock as held by clients and analyzers
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Clock that only moves when told to, with millisecond resolution
+///
+/// Clones share the time, so one clone can be handed to clients while the
+/// replay moves another.
+#[derive(Debug, Clone)]
+pub struct ReplayClock {
+    now_ms: Arc<AtomicI64>,
+}
+
+impl ReplayClock {
+    /// Clock stopped at `start`
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now_ms: Arc::new(AtomicI64::new(start.timestamp_millis())),
+        }
+    }
+
+    /// Move the clock to `at`, backwards too
+    pub fn set(&self, at: DateTime<Utc>) {
+        self.now_ms.store(at.timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let by = i64::try_from(by.as_millis()).unwrap_or(i64::MAX);
+        self.now_ms.fetch_add(by, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ReplayClock {
+    fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.now_ms()).unwrap_or_default()
+    }
+
+    fn now_ms(&self) -> i64 {
+        self.now_ms.load(Ordering::Relaxed)
+    }
+}
@@ -20,6 +20,76 @@ pub const TX_TYPE_L2_UPDATE_LEVERAGE: u8 = 20;
 pub const TX_TYPE_L2_CREATE_GROUPED_ORDERS: u8 = 28;
 pub const TX_TYPE_L2_UPDATE_MARGIN: u8 = 29;
 
+/// Class of a transaction for policies such as default expiry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxKind {
+    /// Creating, modifying and cancelling orders
+    Order,
+    /// Key, account, pool and leverage management
+    Admin,
+    /// Moving funds: transfers, withdrawals, margin and pool shares
+    Transfer,
+}
+
+impl TxKind {
+    /// Kind of an L2 transaction type; `None` for unknown types
+    pub const fn of(tx_type: u8) -> Option<TxKind> {
+        match tx_type {
+            TX_TYPE_L2_CREATE_ORDER
+            | TX_TYPE_L2_CANCEL_ORDER
+            | TX_TYPE_L2_CANCEL_ALL_ORDERS
+            | TX_TYPE_L2_MODIFY_ORDER
+            | TX_TYPE_L2_CREATE_GROUPED_ORDERS => Some(TxKind::Order),
+            TX_TYPE_L2_CHANGE_PUB_KEY
+            | TX_TYPE_L2_CREATE_SUB_ACCOUNT
+            | TX_TYPE_L2_CREATE_PUBLIC_POOL
+            | TX_TYPE_L2_UPDATE_PUBLIC_POOL
+            | TX_TYPE_L2_UPDATE_LEVERAGE => Some(TxKind::Admin),
+            TX_TYPE_L2_TRANSFER
+            | TX_TYPE_L2_WITHDRAW
+            | TX_TYPE_L2_UPDATE_MARGIN
+            | TX_TYPE_L2_MINT_SHARES
+            | TX_TYPE_L2_BURN_SHARES => Some(TxKind::Transfer),
+            _ => None,
+        }
+    }
+}
+
+// Default transaction expiry when no per-kind default is set
+pub const DEFAULT_TX_EXPIRY_MS: i64 = 600_000;
+// Largest sendTx request body sent, before compression
+pub const DEFAULT_MAX_TX_BODY_BYTES: usize = 256 * 1024;
+// Validity a transaction must have left when it is signed or sent
+pub const DEFAULT_MIN_REMAINING_VALIDITY_MS: i64 = 5_000;
+// Clock offset from the server above which default expiries are corrected
+pub const DEFAULT_CLOCK_SKEW_THRESHOLD_MS: u64 = 2_000;
+// How long a measured clock offset is reused before measuring again
+pub const DEFAULT_CLOCK_SKEW_TTL_SECS: u64 = 300;
+// How often a market recorder flushes its open segment
+pub const DEFAULT_RECORDER_FLUSH_INTERVAL_SECS: u64 = 5;
+// Frames buffered between a client's stream and its recorder
+pub const DEFAULT_RECORDER_TAP_CAPACITY: usize = 10_000;
+// Longest wait for the cancel that unwinds a half-filled order pair
+pub const PAIRED_ORDER_UNWIND_TIMEOUT_MS: u64 = 5_000;
+// Distance of a take-profit or stop-loss limit price past its trigger
+pub const DEFAULT_TP_SL_LIMIT_OFFSET_BPS: u32 = 100;
+// Longest wait for the first stream message during a self-test
+pub const DEFAULT_SELF_TEST_WS_TIMEOUT_SECS: u64 = 10;
+// How long a locally allocated nonce may go unsent before it is released
+pub const DEFAULT_NONCE_HOLD_TIMEOUT_MS: u64 = 10_000;
+// Longest wait for a sent order to show up in the account stream
+pub const DEFAULT_ORDER_ACK_TIMEOUT_MS: u64 = 10_000;
+// Latest order acknowledgment latencies kept for percentiles
+pub const DEFAULT_ORDER_LATENCY_WINDOW: usize = 1_024;
+// Failures in a row that open a circuit breaker
+pub const DEFAULT_CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+// How long an open circuit breaker refuses submissions
+pub const DEFAULT_CIRCUIT_COOL_DOWN_MS: u64 = 30_000;
+// Probe submissions a half-open circuit breaker lets through
+pub const DEFAULT_CIRCUIT_HALF_OPEN_PROBES: u32 = 1;
+// Longest wait for background tasks to stop after cancellation
+pub const DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS: u64 = 5_000;
+
 // Transaction Types - Internal
 pub const TX_TYPE_INTERNAL_CLAIM_ORDER: u8 = 21;
 pub const TX_TYPE_INTERNAL_CANCEL_ORDER: u8 = 22;
@@ -70,9 +140,19 @@ pub const HASH_LENGTH: usize = 32;
 pub const PRIVATE_KEY_LENGTH: usize = 40;
 pub const PUBLIC_KEY_LENGTH: usize = 40;
 pub const SIGNATURE_LENGTH: usize = 80;
+/// Length of a Poseidon2 hash, an element of the quintic extension field
+pub const FIELD_HASH_LENGTH: usize = 40;
+/// Goldilocks field modulus, 2^64 - 2^32 + 1
+pub const GOLDILOCKS_MODULUS: u64 = 0xffff_ffff_0000_0001;
+
+// Auth Tokens
+pub const MAX_AUTH_TOKEN_LIFETIME_SECS: i64 = 8 * 60 * 60;
+pub const DEFAULT_AUTH_TOKEN_LIFETIME_SECS: i64 = 10 * 60;
+pub const AUTH_TOKEN_REFRESH_MARGIN_SECS: i64 = 60;
 
 // USDC and Precision
 pub const ONE_USDC: i64 = 1_000_000;
+pub const USDC_DECIMALS: u32 = 6;
 pub const FEE_TICK: i64 = 1_000_000;
 pub const MARGIN_FRACTION_TICK: i64 = 10_000;
 pub const SHARE_TICK: i64 = 10_000;
@@ -146,6 +226,9 @@ pub const MAX_GROUPED_ORDER_COUNT: i64 = 3;
 
 // Timestamp Limits
 pub const MAX_TIMESTAMP: i64 = (1i64 << 48) - 1;
+/// Smallest plausible millisecond timestamp (2001-09-09); smaller values are
+/// almost certainly seconds
+pub const MIN_MILLIS_TIMESTAMP: i64 = 1_000_000_000_000;
 
 // Exchange Limits
 pub const MAX_EXCHANGE_USDC: i64 = (1i64 << 60) - 1;
@@ -163,6 +246,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_constants_validity() {
         assert!(MAX_ACCOUNT_INDEX > MIN_ACCOUNT_INDEX);
         assert!(MAX_API_KEY_INDEX > MIN_API_KEY_INDEX);
@@ -0,0 +1,461 @@
+//! Dead man's switch kept alive by a renewal loop
+//!
+//! [`TxClient::spawn_dead_mans_switch`] schedules a cancel-all of the
+//! account's orders `horizon` from now and pushes it back every `period`
+//! for as long as the process runs. If the process dies or loses its
+//! connection, renewals stop and the exchange pulls the orders when the last
+//! scheduled cancel comes due.
+//!
+//! ```no_run
+//! # async fn example(client: std::sync::Arc<lighter_rs::client::TxClient>) -> lighter_rs::Result<()> {
+//! use std::time::Duration;
+//!
+//! let switch = client.spawn_dead_mans_switch(Duration::from_secs(60), Duration::from_secs(600))?;
+//! // ... trade ...
+//! switch.disarm().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::client::{TxClient, TxResponse};
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::tasks::{ShutdownReport, TaskSet};
+use crate::types::CancelAllOrdersTxReq;
+
+/// Dead man's switch settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmsConfig {
+    /// Time between renewals; must be shorter than `horizon`
+    pub period: Duration,
+    /// How far ahead each renewal schedules the cancel-all, from
+    /// `MIN_ORDER_CANCEL_ALL_PERIOD` to `MAX_ORDER_CANCEL_ALL_PERIOD`
+    pub horizon: Duration,
+    /// Delay before retrying a failed renewal, doubled after each further
+    /// failure up to `period`; a rate limited renewal waits the API's
+    /// `Retry-After` instead
+    pub retry_backoff: Duration,
+}
+
+impl DmsConfig {
+    /// Settings with a one second initial retry backoff
+    pub fn new(period: Duration, horizon: Duration) -> Self {
+        Self {
+            period,
+            horizon,
+            retry_backoff: Duration::from_secs(1),
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let horizon_ms = i64::try_from(self.horizon.as_millis()).unwrap_or(i64::MAX);
+        if !(MIN_ORDER_CANCEL_ALL_PERIOD..=MAX_ORDER_CANCEL_ALL_PERIOD).contains(&horizon_ms) {
+            return Err(LighterError::InvalidConfiguration(format!(
+                "Dead man's switch horizon must be between {}ms and {}ms, got {}ms",
+                MIN_ORDER_CANCEL_ALL_PERIOD, MAX_ORDER_CANCEL_ALL_PERIOD, horizon_ms
+            )));
+        }
+        if self.period.is_zero() || self.period >= self.horizon {
+            return Err(LighterError::InvalidConfiguration(format!(
+                "Dead man's switch period must be positive and shorter than the horizon, got {:?}",
+                self.period
+            )));
+        }
+        if self.retry_backoff.is_zero() {
+            return Err(LighterError::InvalidConfiguration(
+                "Dead man's switch retry backoff must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Renewal failure reported to the alert callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DmsAlert {
+    /// Failed renewals in a row, including this one
+    pub consecutive_failures: u32,
+    pub error: String,
+    /// When the last accepted renewal pulls the orders, in milliseconds;
+    /// `None` if no renewal has been accepted yet
+    pub cancel_at_ms: Option<i64>,
+}
+
+/// State of a dead man's switch
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DmsStatus {
+    /// When the orders are pulled unless renewed again, in milliseconds;
+    /// `None` until the first renewal is accepted
+    pub cancel_at_ms: Option<i64>,
+    /// Accepted renewals, including the first one
+    pub renewals: u64,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+type AlertHandler = Arc<dyn Fn(&DmsAlert) + Send + Sync>;
+
+/// Renewal task of a switch, taken by whoever stops it first
+pub(crate) type Renewals = Arc<Mutex<Option<TaskSet>>>;
+
+/// Running dead man's switch
+///
+/// Dropping the handle stops the renewals but does **not** abort the
+/// scheduled cancel-all: the account's orders are still pulled at
+/// [`DmsStatus::cancel_at_ms`]. That is what makes it a dead man's switch,
+/// since a crashed process never gets to disarm it. Call
+/// [`disarm`](Self::disarm) to stop renewing and abort the scheduled cancel,
+/// or [`shutdown`](Self::shutdown) to wait for the renewals to stop.
+#[derive(Debug)]
+pub struct DmsHandle {
+    client: Arc<TxClient>,
+    status: Arc<Mutex<DmsStatus>>,
+    renewals: Renewals,
+}
+
+impl DmsHandle {
+    /// Current state of the switch
+    pub fn status(&self) -> DmsStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Stop renewing and abort the scheduled cancel-all
+    ///
+    /// Waits for a renewal in flight to finish first, so it can't land after
+    /// the abort.
+    pub async fn disarm(self) -> Result<TxResponse> {
+        stop_renewals(&self.renewals, shutdown_timeout()).await;
+
+        let response = self.client.abort_scheduled_cancel_all().await?;
+        if response.code == 200 {
+            self.status.lock().unwrap().cancel_at_ms = None;
+        }
+        Ok(response)
+    }
+
+    /// Stop renewing and wait for a renewal in flight, leaving the
+    /// scheduled cancel-all in place
+    pub async fn shutdown(self) -> ShutdownReport {
+        stop_renewals(&self.renewals, shutdown_timeout()).await
+    }
+
+    /// Status shared with the renewal task, outliving the handle
+    pub(crate) fn shared_status(&self) -> Arc<Mutex<DmsStatus>> {
+        self.status.clone()
+    }
+
+    /// Renewal task, for stopping it without the handle
+    pub(crate) fn shared_renewals(&self) -> Renewals {
+        self.renewals.clone()
+    }
+}
+
+impl Drop for DmsHandle {
+    /// Stop renewing, leaving the scheduled cancel-all in place
+    fn drop(&mut self) {
+        self.renewals.lock().unwrap().take();
+    }
+}
+
+/// Stop the renewal task unless it was stopped already
+pub(crate) async fn stop_renewals(renewals: &Renewals, timeout: Duration) -> ShutdownReport {
+    let tasks = renewals.lock().unwrap().take();
+    match tasks {
+        Some(tasks) => tasks.shutdown(timeout).await,
+        None => ShutdownReport::default(),
+    }
+}
+
+fn shutdown_timeout() -> Duration {
+    Duration::from_millis(DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS)
+}
+
+impl TxClient {
+    /// Keep a scheduled cancel-all of the account's orders `horizon` ahead,
+    /// renewing it every `period`
+    ///
+    /// Renewal failures are logged to stderr; use
+    /// [`spawn_dead_mans_switch_with`](Self::spawn_dead_mans_switch_with) to
+    /// handle them. See [`DmsHandle`] for what dropping the handle does.
+    pub fn spawn_dead_mans_switch(
+        self: &Arc<Self>,
+        period: Duration,
+        horizon: Duration,
+    ) -> Result<DmsHandle> {
+        self.spawn_dead_mans_switch_with(DmsConfig::new(period, horizon), |alert| {
+            eprintln!(
+                "Dead man's switch renewal failed ({} in a row): {}",
+                alert.consecutive_failures, alert.error
+            );
+        })
+    }
+
+    /// Start a dead man's switch, calling `on_alert` after every failed
+    /// renewal
+    ///
+    /// The first renewal is sent right away. Each renewal signs a
+    /// `CANCEL_ALL_SCHEDULED` cancel-all with a nonce from the client's
+    /// nonce store, or the server's next nonce without one. Must be called
+    /// within a Tokio runtime.
+    pub fn spawn_dead_mans_switch_with<F>(
+        self: &Arc<Self>,
+        config: DmsConfig,
+        on_alert: F,
+    ) -> Result<DmsHandle>
+    where
+        F: Fn(&DmsAlert) + Send + Sync + 'static,
+    {
+        config.validate()?;
+        let status = Arc::new(Mutex::new(DmsStatus::default()));
+        let mut tasks = TaskSet::new();
+        let (client, shared, on_alert) = (self.clone(), status.clone(), Arc::new(on_alert));
+        tasks.spawn_graceful("dead_mans_switch", move |token| {
+            renew_loop(client, config, shared, token, on_alert)
+        });
+        Ok(DmsHandle {
+            client: self.clone(),
+            status,
+            renewals: Arc::new(Mutex::new(Some(tasks))),
+        })
+    }
+
+    /// Abort the account's scheduled cancel-all
+    pub(crate) async fn abort_scheduled_cancel_all(&self) -> Result<TxResponse> {
+        let req = CancelAllOrdersTxReq {
+            time_in_force: CANCEL_ALL_ABORT_SCHEDULED,
+            time: 0,
+        };
+        let tx = self.cancel_all_orders(&req, None).await?;
+        self.send_transaction(&tx).await
+    }
+
+    /// Schedule the cancel-all `horizon` from now, returning when it fires
+    async fn renew_dead_mans_switch(&self, horizon: Duration) -> Result<i64> {
+        let cancel_at_ms = self.now_ms() + horizon.as_millis() as i64;
+        let req = CancelAllOrdersTxReq {
+            time_in_force: CANCEL_ALL_SCHEDULED,
+            time: cancel_at_ms,
+        };
+        let tx = self.cancel_all_orders(&req, None).await?;
+        let response = self.send_transaction(&tx).await?;
+        if response.code != 200 {
+            return Err(LighterError::ApiError(format!(
+                "Scheduled cancel-all rejected with code {}: {}",
+                response.code,
+                response.message.unwrap_or_default()
+            )));
+        }
+        Ok(cancel_at_ms)
+    }
+}
+
+async fn renew_loop(
+    client: Arc<TxClient>,
+    config: DmsConfig,
+    status: Arc<Mutex<DmsStatus>>,
+    cancelled: CancellationToken,
+    on_alert: AlertHandler,
+) -> Result<()> {
+    let mut delay = Duration::ZERO;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = cancelled.cancelled() => return Ok(()),
+        }
+
+        match client.renew_dead_mans_switch(config.horizon).await {
+            Ok(cancel_at_ms) => {
+                let mut status = status.lock().unwrap();
+                status.cancel_at_ms = Some(cancel_at_ms);
+                status.renewals += 1;
+                status.consecutive_failures = 0;
+                delay = config.period;
+            }
+            Err(e) => {
+                let retry_after = e.retry_after();
+                let alert = {
+                    let mut status = status.lock().unwrap();
+                    status.consecutive_failures += 1;
+                    status.last_error = Some(e.to_string());
+                    DmsAlert {
+                        consecutive_failures: status.consecutive_failures,
+                        error: e.to_string(),
+                        cancel_at_ms: status.cancel_at_ms,
+                    }
+                };
+                on_alert(&alert);
+                // A rate limit says exactly when to try again
+                let doublings = (alert.consecutive_failures - 1).min(16);
+                delay = retry_after.unwrap_or_else(|| {
+                    (config.retry_backoff * 2u32.pow(doublings)).min(config.period)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    const HORIZON: Duration = Duration::from_secs(600);
+
+    fn test_client(url: &str) -> Arc<TxClient> {
+        Arc::new(test_support::test_client(url))
+    }
+
+    fn cancel_all_body(time_in_force: u8) -> mockito::Matcher {
+        mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex(r#""tx_type":16,"#.to_string()),
+            mockito::Matcher::Regex(format!(r#"time_in_force\\":{}[,}}]"#, time_in_force)),
+        ])
+    }
+
+    /// Renewal endpoint counting the renewals it accepts
+    async fn mock_renewals(server: &mut mockito::Server) -> Arc<AtomicUsize> {
+        let count = Arc::new(AtomicUsize::new(0));
+        let hits = count.clone();
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(cancel_all_body(CANCEL_ALL_SCHEDULED))
+            .with_body_from_request(move |_| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                br#"{"code":200,"tx_hash":"0xabc"}"#.to_vec()
+            })
+            .create_async()
+            .await;
+        count
+    }
+
+    async fn mock_nonce(server: &mut mockito::Server) {
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":5}"#)
+            .create_async()
+            .await;
+    }
+
+    async fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out waiting for {}", what);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let minute = Duration::from_secs(60);
+        assert!(DmsConfig::new(minute, HORIZON).validate().is_ok());
+        // Horizon below the protocol minimum of five minutes
+        assert!(DmsConfig::new(minute, minute * 4).validate().is_err());
+        assert!(DmsConfig::new(Duration::ZERO, HORIZON).validate().is_err());
+        assert!(DmsConfig::new(HORIZON, HORIZON).validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_renews_every_period_and_disarms() {
+        let mut server = mockito::Server::new_async().await;
+        mock_nonce(&mut server).await;
+        let renewals = mock_renewals(&mut server).await;
+        let abort = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(cancel_all_body(CANCEL_ALL_ABORT_SCHEDULED))
+            .with_body(r#"{"code":200,"tx_hash":"0xdef"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let started = Instant::now();
+        let switch = client
+            .spawn_dead_mans_switch(Duration::from_millis(100), HORIZON)
+            .unwrap();
+
+        wait_until("three renewals", || switch.status().renewals >= 3).await;
+        // First renewal right away, then one per period
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        let status = switch.status();
+        let cancel_at = status.cancel_at_ms.unwrap();
+        let expected = client.now_ms() + HORIZON.as_millis() as i64;
+        assert!((expected - cancel_at).abs() < 1_000);
+        assert_eq!(status.consecutive_failures, 0);
+
+        let response = switch.disarm().await.unwrap();
+        assert_eq!(response.tx_hash.as_deref(), Some("0xdef"));
+        abort.assert_async().await;
+
+        // No renewal after disarming
+        let renewed = renewals.load(Ordering::SeqCst);
+        assert!(renewed >= 3);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(renewals.load(Ordering::SeqCst), renewed);
+    }
+
+    #[tokio::test]
+    async fn test_alerts_on_consecutive_failures() {
+        let mut server = mockito::Server::new_async().await;
+        mock_nonce(&mut server).await;
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .with_status(503)
+            .with_body("unavailable")
+            .create_async()
+            .await;
+
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let seen = alerts.clone();
+        let config = DmsConfig {
+            retry_backoff: Duration::from_millis(10),
+            ..DmsConfig::new(Duration::from_secs(60), HORIZON)
+        };
+        let switch = test_client(&server.url())
+            .spawn_dead_mans_switch_with(config, move |alert| {
+                seen.lock().unwrap().push(alert.clone());
+            })
+            .unwrap();
+
+        wait_until("three alerts", || alerts.lock().unwrap().len() >= 3).await;
+        let alerts = alerts.lock().unwrap().clone();
+        let counts: Vec<u32> = alerts.iter().map(|a| a.consecutive_failures).collect();
+        assert_eq!(&counts[..3], &[1, 2, 3]);
+        assert!(alerts.iter().all(|a| a.cancel_at_ms.is_none()));
+
+        let status = switch.status();
+        assert_eq!(status.renewals, 0);
+        assert!(status.consecutive_failures >= 3);
+        assert!(status.last_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_drop_leaves_scheduled_cancel() {
+        let mut server = mockito::Server::new_async().await;
+        mock_nonce(&mut server).await;
+        let renewals = mock_renewals(&mut server).await;
+        let abort = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(cancel_all_body(CANCEL_ALL_ABORT_SCHEDULED))
+            .expect(0)
+            .create_async()
+            .await;
+
+        let switch = test_client(&server.url())
+            .spawn_dead_mans_switch(Duration::from_millis(50), HORIZON)
+            .unwrap();
+        wait_until("first renewal", || switch.status().renewals >= 1).await;
+        drop(switch);
+
+        let renewed = renewals.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(renewals.load(Ordering::SeqCst), renewed);
+        abort.assert_async().await;
+    }
+}
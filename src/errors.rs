@@ -1,12 +1,57 @@
 //! Error types for the Lighter Protocol SDK
+//!
+//! [`LighterError`] gains variants over time, so match on it with a
+//! wildcard arm, or branch on its coarse [`ErrorKind`]:
+//!
+//! ```
+//! use lighter_rs::errors::{ErrorKind, LighterError};
+//!
+//! fn describe(error: &LighterError) -> &'static str {
+//!     match error {
+//!         LighterError::Throttled { .. } => "slow down",
+//!         e if e.is_validation() => "fix the request",
+//!         e if e.is_retryable() => "try again",
+//!         e => match e.kind() {
+//!             ErrorKind::Auth => "check the API key",
+//!             _ => "give up",
+//!         },
+//!     }
+//! }
+//!
+//! assert_eq!(describe(&LighterError::NonceTooLow(0)), "fix the request");
+//! assert_eq!(describe(&LighterError::Timeout), "try again");
+//! ```
 
 use thiserror::Error;
 
 /// Result type alias using LighterError
 pub type Result<T> = std::result::Result<T, LighterError>;
 
+/// Coarse category of a [`LighterError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A value or request failed local checks
+    Validation,
+    /// The server refused the request as invalid
+    Rejected,
+    /// Credentials, keys or signatures are missing or wrong
+    Auth,
+    /// Held back by a rate limit, locally or by the server
+    Throttled,
+    /// The request or its answer was lost on the way
+    Transport,
+    /// The server failed or answered with something unreadable
+    Server,
+    /// The client is set up wrongly
+    Configuration,
+    /// Anything else, such as a failed callback
+    Other,
+}
+
 /// Main error type for the Lighter SDK
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum LighterError {
     // Account and API Key Errors
     #[error(
@@ -122,6 +167,12 @@ pub enum LighterError {
     #[error("Order trigger price is invalid")]
     OrderTriggerPriceInvalid,
 
+    #[error("Reduce-only order would increase position {position} in market {market_index}")]
+    ReduceOnlyWouldIncreasePosition { market_index: u8, position: i64 },
+
+    #[error("Reduce-only base amount {base_amount} exceeds position size {position}")]
+    ReduceOnlyExceedsPosition { base_amount: i64, position: i64 },
+
     #[error("Order expiry is invalid")]
     OrderExpiryInvalid,
 
@@ -198,6 +249,12 @@ pub enum LighterError {
     )]
     PoolBurnShareAmountTooHigh(i64),
 
+    #[error(
+        "Account already invests in the maximum of {} public pools: {0}",
+        crate::constants::MAX_INVESTED_PUBLIC_POOL_COUNT
+    )]
+    InvestedPublicPoolLimit(String),
+
     // Transfer and Withdrawal Errors
     #[error(
         "Withdrawal amount {0} is too low, minimum is {}",
@@ -232,6 +289,9 @@ pub enum LighterError {
     )]
     TransferFeeTooHigh,
 
+    #[error("Quoted transfer fee {fee} exceeds the limit of {max_fee}")]
+    TransferFeeAboveLimit { fee: i64, max_fee: i64 },
+
     #[error(
         "To account index {0} is too low, minimum is {}",
         crate::constants::MIN_ACCOUNT_INDEX
@@ -276,8 +336,13 @@ pub enum LighterError {
     #[error("Nonce {0} is too low, minimum is {}", crate::constants::MIN_NONCE)]
     NonceTooLow(i64),
 
-    #[error("ExpiredAt is invalid")]
-    ExpiredAtInvalid,
+    #[error(
+        "ExpiredAt leaves {remaining_ms}ms of validity, at least {min_remaining_ms}ms required"
+    )]
+    ExpiredAtInvalid {
+        remaining_ms: i64,
+        min_remaining_ms: i64,
+    },
 
     #[error("Public key is invalid")]
     PubKeyInvalid,
@@ -320,9 +385,61 @@ pub enum LighterError {
     #[error("Invalid response from server: {0}")]
     InvalidResponse(String),
 
+    #[error("Failed to send transaction (HTTP {status}, request id {request_id:?}): {body}")]
+    TxSubmissionFailed {
+        status: u16,
+        request_id: Option<String>,
+        body: String,
+    },
+
     #[error("Network timeout")]
     Timeout,
 
+    #[error("Order throttled, retry after {retry_after:?}")]
+    Throttled { retry_after: std::time::Duration },
+
+    /// A circuit breaker refused the submission; `retry_after` is what is
+    /// left of its cool-down, `None` while half-open probes are out
+    #[error("Circuit breaker open, retry after {retry_after:?}")]
+    CircuitOpen {
+        retry_after: Option<std::time::Duration>,
+    },
+
+    /// The API answered 429; `retry_after` is its `Retry-After` header and
+    /// `remaining` its `X-RateLimit-Remaining`, when sent
+    #[error("Rate limited by the API, retry after {retry_after:?} ({remaining:?} requests left)")]
+    RateLimited {
+        retry_after: Option<std::time::Duration>,
+        remaining: Option<u32>,
+    },
+
+    #[error("Order at {price} in market {market_index} would cross own resting order at {resting_price}")]
+    SelfCross {
+        market_index: u8,
+        price: u32,
+        resting_price: u32,
+    },
+
+    /// A transaction signed with a lower nonce was rejected or never sent,
+    /// so this one would have been refused
+    #[error("Nonce {failed_nonce} went unused, transaction behind it not sent")]
+    NonceGap { failed_nonce: i64 },
+
+    #[error("Market is {0}, order not sent")]
+    MarketNotActive(crate::market_status::MarketStatus),
+
+    #[error("Subscription to {channel} failed: {reason}")]
+    SubscriptionFailed { channel: String, reason: String },
+
+    #[error("WebSocket callback panicked: {0}")]
+    CallbackPanicked(String),
+
+    #[error("Client is shut down, request not sent")]
+    ShutDown,
+
+    #[error("Unsupported IPC format version {actual}, expected {expected}")]
+    IpcVersionMismatch { expected: u8, actual: u8 },
+
     // JSON Errors
     #[error("JSON serialization/deserialization error: {0}")]
     JsonError(#[from] serde_json::Error),
@@ -352,3 +469,176 @@ impl From<&str> for LighterError {
         LighterError::Other(s.to_string())
     }
 }
+
+impl LighterError {
+    /// Coarse category of the error
+    pub fn kind(&self) -> ErrorKind {
+        use LighterError::*;
+        match self {
+            PubKeyInvalid
+            | InvalidSignature
+            | InvalidPrivateKeyLength { .. }
+            | InvalidPublicKeyLength { .. }
+            | HexParseError(_)
+            | CryptoError(_) => ErrorKind::Auth,
+            HttpError(e) if e.is_builder() => ErrorKind::Configuration,
+            HttpError(e) if e.is_decode() => ErrorKind::Server,
+            HttpError(e) => e
+                .status()
+                .map_or(ErrorKind::Transport, |status| status_kind(status.as_u16())),
+            TxSubmissionFailed { status, .. } => status_kind(*status),
+            ApiError(_) | SubscriptionFailed { .. } | MarketNotActive(_) | NonceGap { .. } => {
+                ErrorKind::Rejected
+            }
+            InvalidResponse(_) => ErrorKind::Server,
+            Timeout => ErrorKind::Transport,
+            Throttled { .. } | RateLimited { .. } | CircuitOpen { .. } => ErrorKind::Throttled,
+            JsonError(e) => match e.classify() {
+                serde_json::error::Category::Io => ErrorKind::Transport,
+                _ => ErrorKind::Server,
+            },
+            InvalidConfiguration(_) => ErrorKind::Configuration,
+            CallbackPanicked(_) | ShutDown | IpcVersionMismatch { .. } | Other(_) => {
+                ErrorKind::Other
+            }
+            _ => ErrorKind::Validation,
+        }
+    }
+
+    /// Whether a value or request failed local checks
+    pub fn is_validation(&self) -> bool {
+        self.kind() == ErrorKind::Validation
+    }
+
+    /// Whether the request or its answer was lost on the way
+    pub fn is_transport(&self) -> bool {
+        self.kind() == ErrorKind::Transport
+    }
+
+    /// Whether credentials, keys or signatures are at fault
+    pub fn is_auth(&self) -> bool {
+        self.kind() == ErrorKind::Auth
+    }
+
+    /// Whether the same request may succeed later
+    ///
+    /// True for transport failures, server failures and rate limits. A
+    /// transaction that failed this way may still have been executed, so
+    /// resend it with the same nonce.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::Transport | ErrorKind::Server | ErrorKind::Throttled
+        )
+    }
+
+    /// How long to wait before retrying, when the error says so
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            LighterError::Throttled { retry_after } => Some(*retry_after),
+            LighterError::RateLimited { retry_after, .. } => *retry_after,
+            LighterError::CircuitOpen { retry_after } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+/// Category of an HTTP error status
+fn status_kind(status: u16) -> ErrorKind {
+    match status {
+        401 | 403 => ErrorKind::Auth,
+        429 => ErrorKind::Throttled,
+        500.. => ErrorKind::Server,
+        _ => ErrorKind::Rejected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn submission_failed(status: u16) -> LighterError {
+        LighterError::TxSubmissionFailed {
+            status,
+            request_id: None,
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_kinds_of_each_group() {
+        let cases = [
+            (LighterError::PriceTooLow(0), ErrorKind::Validation),
+            (
+                LighterError::ValidationError("x".into()),
+                ErrorKind::Validation,
+            ),
+            (LighterError::ApiError("x".into()), ErrorKind::Rejected),
+            (submission_failed(400), ErrorKind::Rejected),
+            (submission_failed(401), ErrorKind::Auth),
+            (LighterError::InvalidSignature, ErrorKind::Auth),
+            (submission_failed(429), ErrorKind::Throttled),
+            (
+                LighterError::Throttled {
+                    retry_after: Duration::from_secs(1),
+                },
+                ErrorKind::Throttled,
+            ),
+            (
+                LighterError::RateLimited {
+                    retry_after: None,
+                    remaining: Some(0),
+                },
+                ErrorKind::Throttled,
+            ),
+            (
+                LighterError::CircuitOpen { retry_after: None },
+                ErrorKind::Throttled,
+            ),
+            (LighterError::Timeout, ErrorKind::Transport),
+            (submission_failed(502), ErrorKind::Server),
+            (LighterError::InvalidResponse("x".into()), ErrorKind::Server),
+            (
+                serde_json::from_str::<u8>("{").unwrap_err().into(),
+                ErrorKind::Server,
+            ),
+            (
+                LighterError::InvalidConfiguration("x".into()),
+                ErrorKind::Configuration,
+            ),
+            (LighterError::CallbackPanicked("x".into()), ErrorKind::Other),
+            (LighterError::ShutDown, ErrorKind::Other),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(error.kind(), kind, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert!(LighterError::NonceTooLow(0).is_validation());
+        assert!(!LighterError::NonceTooLow(0).is_retryable());
+        assert!(LighterError::Timeout.is_transport());
+        assert!(LighterError::Timeout.is_retryable());
+        assert!(submission_failed(503).is_retryable());
+        assert!(!submission_failed(503).is_transport());
+        assert!(!submission_failed(400).is_retryable());
+        assert!(submission_failed(403).is_auth());
+        assert!(!submission_failed(403).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_http_errors() {
+        // Nothing listens on port 1
+        let error: LighterError = reqwest::get("http://127.0.0.1:1/")
+            .await
+            .unwrap_err()
+            .into();
+        assert_eq!(error.kind(), ErrorKind::Transport);
+        assert!(error.is_retryable());
+
+        let error: LighterError = reqwest::get("not a url").await.unwrap_err().into();
+        assert_eq!(error.kind(), ErrorKind::Configuration);
+    }
+}
@@ -0,0 +1,360 @@
+//! Maker and taker fees of markets
+//!
+//! A [`FeeSchedule`] holds a market's fee rates in basis points. It comes
+//! from [`HTTPClient::get_fee_schedule`] or is set by hand, and
+//! [`FeeSchedules`] caches schedules per market for a while. Fees of an
+//! order, of an account's resting orders, and the spread a pair of quotes
+//! needs to cover them follow from it.
+//!
+//! ```
+//! use lighter_rs::fees::{breakeven_spread_bps, estimate_fee, FeeSchedule, FillType};
+//! use lighter_rs::peg::MarketRules;
+//! use lighter_rs::types::CreateOrderTxReq;
+//! use rust_decimal::Decimal;
+//!
+//! let schedule = FeeSchedule::new(Decimal::new(2, 0), Decimal::new(5, 0));
+//! // 1.5 at 3000.00, with 4 size and 2 price decimals
+//! let order = CreateOrderTxReq { base_amount: 15_000, price: 300_000, ..Default::default() };
+//! let rules = MarketRules::new(4, 2);
+//!
+//! let fee = estimate_fee(&order, &schedule, &rules, FillType::Taker)?;
+//! assert_eq!(fee.to_string(), "2.250000");
+//! assert_eq!(breakeven_spread_bps(&schedule), Decimal::new(4, 0));
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::client::HTTPClient;
+use crate::errors::{LighterError, Result};
+use crate::peg::MarketRules;
+use crate::types::{CreateOrderTxReq, Usdc};
+use crate::ws_client::AccountSnapshot;
+
+const BPS: u32 = 10_000;
+
+/// Whether a fill added liquidity or took it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillType {
+    Maker,
+    Taker,
+}
+
+/// Fee rates of a market in basis points of the notional
+///
+/// Negative rates are rebates. The default charges nothing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    pub maker_bps: Decimal,
+    pub taker_bps: Decimal,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_bps: Decimal, taker_bps: Decimal) -> Self {
+        Self {
+            maker_bps,
+            taker_bps,
+        }
+    }
+
+    /// Rate of a fill in basis points
+    pub fn bps(&self, fill: FillType) -> Decimal {
+        match fill {
+            FillType::Maker => self.maker_bps,
+            FillType::Taker => self.taker_bps,
+        }
+    }
+
+    /// Fee of a fill of `notional` USDC
+    ///
+    /// Rounded up to the next USDC unit, so fees are never underestimated
+    /// and rebates never overestimated.
+    pub fn fee(&self, notional: Decimal, fill: FillType) -> Result<Usdc> {
+        let fee = notional
+            .checked_mul(self.bps(fill))
+            .map(|fee| fee / Decimal::from(BPS))
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!("Fee on {} overflows", notional))
+            })?;
+        Usdc::from_decimal_rounded(fee, RoundingStrategy::ToPositiveInfinity)
+    }
+}
+
+/// Fee of `order` if it fills completely as `fill`
+///
+/// `rules` gives the market's decimals the order's integer size and price
+/// are scaled by.
+pub fn estimate_fee(
+    order: &CreateOrderTxReq,
+    schedule: &FeeSchedule,
+    rules: &MarketRules,
+    fill: FillType,
+) -> Result<Usdc> {
+    let scaled = |units: i64, decimals: u32| {
+        if decimals > 28 {
+            return Err(LighterError::ValidationError(format!(
+                "Market decimals too large: {}",
+                decimals
+            )));
+        }
+        Ok(Decimal::new(units, decimals))
+    };
+    let size = scaled(order.base_amount, rules.size_decimals)?;
+    let price = scaled(i64::from(order.price), rules.price_decimals)?;
+    let notional = size.checked_mul(price).ok_or_else(|| {
+        LighterError::ValidationError(format!("Notional of {} at {} overflows", size, price))
+    })?;
+    schedule.fee(notional, fill)
+}
+
+/// Spread in basis points of the price a bid and an ask must be apart for
+/// both filling as maker to break even
+///
+/// Negative when maker fees are rebates.
+pub fn breakeven_spread_bps(schedule: &FeeSchedule) -> Decimal {
+    schedule.maker_bps * Decimal::from(2)
+}
+
+/// Maker fees the account's resting orders would pay if they all filled
+///
+/// The figure to subtract from PnL for a view net of fees. Fails if a market
+/// with open orders has no schedule in `schedules`.
+pub fn open_order_fees(
+    account: &AccountSnapshot,
+    schedules: &HashMap<u32, FeeSchedule>,
+) -> Result<Usdc> {
+    let mut total = Usdc::ZERO;
+    for order in account.orders.values().flatten() {
+        if order.is_terminal() || order.remaining_base_amount.is_zero() {
+            continue;
+        }
+        let schedule = schedules.get(&order.market_index).ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "No fee schedule for market {}",
+                order.market_index
+            ))
+        })?;
+        let notional = order
+            .remaining_base_amount
+            .checked_mul(order.price)
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!(
+                    "Notional of order {} overflows",
+                    order.order_index
+                ))
+            })?;
+        total = total.checked_add(schedule.fee(notional, FillType::Maker)?)?;
+    }
+    Ok(total)
+}
+
+/// Fee schedules by market, fetched on first use and kept for a time to live
+///
+/// Schedules [`set`](Self::set) by hand never expire. Without an
+/// [`HTTPClient`] only those are known.
+pub struct FeeSchedules {
+    http: Option<HTTPClient>,
+    ttl: Duration,
+    /// Schedule and when it was fetched, `None` if set by hand
+    cached: Mutex<HashMap<u32, (FeeSchedule, Option<Instant>)>>,
+}
+
+impl FeeSchedules {
+    /// Fetch schedules through `http`, refetching them after `ttl`
+    pub fn new(http: HTTPClient, ttl: Duration) -> Self {
+        Self {
+            http: Some(http),
+            ttl,
+            cached: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Only schedules set by hand
+    pub fn manual() -> Self {
+        Self {
+            http: None,
+            ttl: Duration::ZERO,
+            cached: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use `schedule` for a market instead of fetching it
+    pub fn set(&self, market_id: u32, schedule: FeeSchedule) {
+        self.cached
+            .lock()
+            .unwrap()
+            .insert(market_id, (schedule, None));
+    }
+
+    /// Forget a market's schedule, fetched or set
+    pub fn invalidate(&self, market_id: u32) {
+        self.cached.lock().unwrap().remove(&market_id);
+    }
+
+    /// Schedule of a market, fetched if not cached or expired
+    pub async fn get(&self, market_id: u32) -> Result<FeeSchedule> {
+        let cached = self.cached.lock().unwrap().get(&market_id).copied();
+        match cached {
+            Some((schedule, None)) => return Ok(schedule),
+            Some((schedule, Some(fetched))) if fetched.elapsed() < self.ttl => return Ok(schedule),
+            _ => {}
+        }
+        let Some(http) = &self.http else {
+            return Err(LighterError::InvalidConfiguration(format!(
+                "No fee schedule set for market {}",
+                market_id
+            )));
+        };
+        let schedule = http.get_fee_schedule(market_id).await?;
+        self.cached
+            .lock()
+            .unwrap()
+            .insert(market_id, (schedule, Some(Instant::now())));
+        Ok(schedule)
+    }
+
+    /// Schedules of several markets, e.g. for [`open_order_fees`]
+    pub async fn get_all(
+        &self,
+        market_ids: impl IntoIterator<Item = u32>,
+    ) -> Result<HashMap<u32, FeeSchedule>> {
+        let mut schedules = HashMap::new();
+        for market_id in market_ids {
+            schedules.insert(market_id, self.get(market_id).await?);
+        }
+        Ok(schedules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_client::AccountOrder;
+
+    fn dec(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    /// 2 bps maker, 5 bps taker
+    fn schedule() -> FeeSchedule {
+        FeeSchedule::new(dec("2"), dec("5"))
+    }
+
+    fn order(base_amount: i64, price: u32) -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            base_amount,
+            price,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_maker_and_taker_fees() {
+        let rules = MarketRules::new(4, 2);
+        // 1.5 at 3000.00 is 4500 USDC
+        let order = order(15_000, 300_000);
+        let fee = |schedule: &FeeSchedule, fill| estimate_fee(&order, schedule, &rules, fill);
+
+        assert_eq!(
+            fee(&schedule(), FillType::Maker).unwrap(),
+            Usdc::from_units(900_000)
+        );
+        assert_eq!(
+            fee(&schedule(), FillType::Taker).unwrap(),
+            Usdc::from_units(2_250_000)
+        );
+
+        // Rebates are negative, and fractions of a unit round up
+        let rebate = FeeSchedule::new(dec("-0.25"), dec("3"));
+        assert_eq!(
+            fee(&rebate, FillType::Maker).unwrap(),
+            Usdc::from_units(-112_500)
+        );
+        let odd = estimate_fee(&self::order(1, 333), &rebate, &rules, FillType::Taker).unwrap();
+        assert_eq!(odd, Usdc::from_units(1));
+
+        let zero = FeeSchedule::default();
+        for fill in [FillType::Maker, FillType::Taker] {
+            assert_eq!(fee(&zero, fill).unwrap(), Usdc::ZERO);
+        }
+    }
+
+    #[test]
+    fn test_breakeven_spread() {
+        assert_eq!(breakeven_spread_bps(&schedule()), dec("4"));
+        assert_eq!(breakeven_spread_bps(&FeeSchedule::default()), Decimal::ZERO);
+        let rebate = FeeSchedule::new(dec("-0.5"), dec("2.5"));
+        assert_eq!(breakeven_spread_bps(&rebate), dec("-1"));
+    }
+
+    #[test]
+    fn test_open_order_fees() {
+        let resting =
+            |market_index, price: &str, remaining: &str, status: Option<&str>| AccountOrder {
+                order_index: 1,
+                client_order_index: 1,
+                market_index,
+                is_ask: false,
+                price: dec(price),
+                remaining_base_amount: dec(remaining),
+                status: status.map(str::to_string),
+                extras: Default::default(),
+            };
+        let mut account = AccountSnapshot::default();
+        account.orders.insert(
+            "0".to_string(),
+            vec![
+                resting(0, "3000", "1.5", Some("open")),
+                resting(0, "3000", "9", Some("filled")),
+            ],
+        );
+        account
+            .orders
+            .insert("1".to_string(), vec![resting(1, "50", "10", None)]);
+
+        let mut schedules = HashMap::from([(0, schedule())]);
+        assert!(open_order_fees(&account, &schedules).is_err());
+        schedules.insert(1, FeeSchedule::default());
+        assert_eq!(
+            open_order_fees(&account, &schedules).unwrap(),
+            Usdc::from_units(900_000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_schedules_are_cached() {
+        let mut server = mockito::Server::new_async().await;
+        let fetched = server
+            .mock("GET", "/api/v1/orderBooks")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "market_id".into(),
+                "3".into(),
+            ))
+            .with_body(
+                r#"{"code":200,"order_books":[{"market_id":3,"maker_fee":"0.0020","taker_fee":"0.0200"}]}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let http = HTTPClient::new(&server.url()).unwrap();
+
+        let schedules = FeeSchedules::new(http.clone(), Duration::from_secs(60));
+        let expected = FeeSchedule::new(dec("0.2"), dec("2"));
+        assert_eq!(schedules.get(3).await.unwrap(), expected);
+        assert_eq!(schedules.get(3).await.unwrap(), expected);
+
+        // Expired at once
+        let uncached = FeeSchedules::new(http, Duration::ZERO);
+        uncached.get(3).await.unwrap();
+        fetched.assert_async().await;
+
+        uncached.set(3, FeeSchedule::default());
+        assert_eq!(uncached.get(3).await.unwrap(), FeeSchedule::default());
+        assert!(FeeSchedules::manual().get(3).await.is_err());
+    }
+}
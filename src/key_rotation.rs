@@ -0,0 +1,484 @@
+//! Switching a running client to a new API key
+//!
+//! [`TxClient::rotate_api_key`] registers the new key with a change public
+//! key transaction signed by the current one, waits until the exchange
+//! reports it, and only then signs with the new key. Transactions signed
+//! before the switch keep the old key throughout.
+//!
+//! ```no_run
+//! # async fn example(client: lighter_rs::client::TxClient) -> Result<(), Box<dyn std::error::Error>> {
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//! use lighter_rs::signer::PoseidonKeyManager;
+//!
+//! let new_key = Arc::new(PoseidonKeyManager::from_hex(&std::env::var("NEW_API_KEY")?)?);
+//! let report = client
+//!     .rotate_api_key(new_key, None, Duration::from_secs(60))
+//!     .await?;
+//! println!("switched after {:?}", report.total);
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::{TxClient, TxStatus};
+use crate::errors::{LighterError, Result};
+use crate::signer::KeyManager;
+use crate::types::{
+    AccountIndex, ApiKeyIndex, ChangePubKeyReq, L2ChangePubKeyTxInfo, TransactOpts,
+};
+use crate::withdraw::is_ambiguous;
+
+/// Time between lookups of a submitted key change
+const ROTATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How a key change was found to have executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyConfirmedBy {
+    /// The transaction endpoint reported it executed
+    TxStatus,
+    /// The API key endpoint listed the new public key
+    KeyLookup,
+}
+
+/// Timing of a completed [`TxClient::rotate_api_key`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationReport {
+    /// Hash of the change public key transaction
+    pub tx_hash: String,
+    /// Public keys in hex
+    pub old_public_key: String,
+    pub new_public_key: String,
+    pub confirmed_by: KeyConfirmedBy,
+    /// When signing switched to the new key
+    pub swapped_at: DateTime<Utc>,
+    /// Signing and sending the key change
+    pub submitted_in: Duration,
+    /// From sending until the new key was confirmed
+    pub confirmed_in: Duration,
+    /// From confirmation until signing switched
+    pub swapped_in: Duration,
+    pub total: Duration,
+}
+
+impl TxClient {
+    /// Register `new_key_manager` under this client's API key index and
+    /// sign with it once the exchange has it
+    ///
+    /// The change public key transaction is signed with the current key and
+    /// sent once, also after an ambiguous failure. It is then looked up by
+    /// hash, and the API key is looked up by index, until either shows the
+    /// change executed; signing switches right away. Transactions already
+    /// being signed finish with the old key, which the exchange rejects
+    /// from then on.
+    ///
+    /// On any error the client keeps the old key. A change that is not
+    /// confirmed within `confirm_timeout` may still execute before the
+    /// transaction's `expired_at`.
+    pub async fn rotate_api_key(
+        &self,
+        new_key_manager: Arc<dyn KeyManager + Send + Sync>,
+        opts: Option<TransactOpts>,
+        confirm_timeout: Duration,
+    ) -> Result<RotationReport> {
+        if self.http().is_none() {
+            return Err(LighterError::InvalidConfiguration(
+                "HTTPClient is required to rotate the API key".to_string(),
+            ));
+        }
+        let started = Instant::now();
+        let old_key_manager = self.key_manager();
+        if old_key_manager.pub_key() == new_key_manager.pub_key() {
+            return Err(LighterError::ValidationError(
+                "The new API key is the one already in use".to_string(),
+            ));
+        }
+
+        let req = ChangePubKeyReq {
+            pub_key: new_key_manager.pub_key().to_vec(),
+        };
+        let tx = self.change_pub_key(&req, opts).await?;
+        let tx_hash = tx
+            .signed_hash
+            .clone()
+            .ok_or_else(|| LighterError::MissingField("signed_hash".to_string()))?;
+        let deadline = started + confirm_timeout;
+
+        match self.send_transaction(&tx).await {
+            Ok(response) if response.code == 200 => {}
+            Ok(response) => {
+                return Err(LighterError::ApiError(format!(
+                    "Key change {} rejected with code {}: {}",
+                    tx_hash,
+                    response.code,
+                    response.message.unwrap_or_default()
+                )))
+            }
+            Err(e) if is_ambiguous(&e) => eprintln!(
+                "Key change {} may have been submitted ({}); checking its status",
+                tx_hash, e
+            ),
+            Err(e) => return Err(e),
+        }
+        let sent = Instant::now();
+
+        let new_public_key = hex::encode(new_key_manager.pub_key());
+        let confirmed_by = self
+            .await_key_change(&tx, &tx_hash, &new_public_key, deadline)
+            .await?;
+        let confirmed = Instant::now();
+
+        self.replace_key_manager(new_key_manager);
+        let swapped = Instant::now();
+
+        Ok(RotationReport {
+            tx_hash,
+            old_public_key: hex::encode(old_key_manager.pub_key()),
+            new_public_key,
+            confirmed_by,
+            swapped_at: Utc::now(),
+            submitted_in: sent - started,
+            confirmed_in: confirmed - sent,
+            swapped_in: swapped - confirmed,
+            total: swapped - started,
+        })
+    }
+
+    /// Poll until the key change executed, failed or `deadline` passed
+    async fn await_key_change(
+        &self,
+        tx: &L2ChangePubKeyTxInfo,
+        tx_hash: &str,
+        new_public_key: &str,
+        deadline: Instant,
+    ) -> Result<KeyConfirmedBy> {
+        let client = self.http().expect("checked before signing");
+        loop {
+            let status = match client.get_tx_status(tx_hash).await {
+                Ok(Some(TxStatus::Executed)) => return Ok(KeyConfirmedBy::TxStatus),
+                Ok(Some(TxStatus::Failed)) => {
+                    return Err(LighterError::ApiError(format!(
+                        "Key change {} failed on execution",
+                        tx_hash
+                    )))
+                }
+                Ok(Some(status)) => format!("transaction is {:?}", status),
+                Ok(None) if Utc::now().timestamp_millis() > tx.expired_at => {
+                    return Err(LighterError::ApiError(format!(
+                        "Key change {} not found after expiry",
+                        tx_hash
+                    )))
+                }
+                Ok(None) => "transaction not found yet".to_string(),
+                Err(e) => format!("status unavailable: {}", e),
+            };
+            let registered = client
+                .get_api_key_public_key(
+                    AccountIndex::new(self.account_index())?,
+                    ApiKeyIndex::new(self.api_key_index())?,
+                )
+                .await;
+            if matches!(&registered, Ok(Some(key)) if key == new_public_key) {
+                return Ok(KeyConfirmedBy::KeyLookup);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(LighterError::ApiError(format!(
+                    "Key change {} not confirmed in time ({}); still signing with the old key",
+                    tx_hash, status
+                )));
+            }
+            tokio::time::sleep(ROTATION_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+    use crate::signer::Signer;
+    use crate::test_support::test_client_for;
+    use crate::types::CreateOrderTxReq;
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+
+    /// Key whose public key and signatures are all `tag` bytes
+    struct MockKey {
+        tag: u8,
+        public_key: Vec<u8>,
+        log: Arc<Mutex<Vec<String>>>,
+        /// Signals that a signature started, then waits to be released
+        gate: Option<Mutex<(mpsc::Sender<()>, mpsc::Receiver<()>)>>,
+    }
+
+    impl MockKey {
+        fn new(tag: u8, log: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self {
+                tag,
+                public_key: vec![tag; PUBLIC_KEY_LENGTH],
+                log: log.clone(),
+                gate: None,
+            }
+        }
+    }
+
+    impl Signer for MockKey {
+        fn sign(&self, _hashed_message: &[u8]) -> crate::Result<Vec<u8>> {
+            if let Some(gate) = &self.gate {
+                let gate = gate.lock().unwrap();
+                gate.0.send(()).unwrap();
+                gate.1.recv().unwrap();
+            }
+            self.log.lock().unwrap().push(format!("sign {}", self.tag));
+            Ok(vec![self.tag; SIGNATURE_LENGTH])
+        }
+    }
+
+    impl KeyManager for MockKey {
+        fn pub_key(&self) -> &[u8] {
+            &self.public_key
+        }
+
+        fn pub_key_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+            [self.tag; PUBLIC_KEY_LENGTH]
+        }
+
+        #[cfg(feature = "expose-secrets")]
+        fn prv_key_bytes(&self) -> Vec<u8> {
+            vec![self.tag; 40]
+        }
+    }
+
+    fn test_client(url: &str, key: MockKey) -> TxClient {
+        let mut client = test_client_for(url, 12345, 2);
+        client.set_min_remaining_validity(None);
+        client.replace_key_manager(Arc::new(key));
+        client
+    }
+
+    fn opts() -> Option<TransactOpts> {
+        Some(TransactOpts {
+            expired_at: Utc::now().timestamp_millis() + 60_000,
+            nonce: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn order_signature(client: &TxClient) -> Vec<u8> {
+        let req = CreateOrderTxReq {
+            market_index: 1,
+            base_amount: 10,
+            price: 100,
+            ..Default::default()
+        };
+        let mut tx = crate::client::TxRequest::build_tx(
+            &req,
+            &TransactOpts {
+                from_account_index: Some(12345),
+                api_key_index: Some(2),
+                nonce: Some(2),
+                expired_at: Utc::now().timestamp_millis() + 60_000,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        client.sign_prepared(&mut tx).unwrap();
+        tx.sig.unwrap().into_bytes()
+    }
+
+    fn logged(log: &Arc<Mutex<Vec<String>>>, entry: &'static str) -> impl Fn() {
+        let log = log.clone();
+        move || log.lock().unwrap().push(entry.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_switches_after_confirmation() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut server = mockito::Server::new_async().await;
+        let on_send = logged(&log, "send");
+        let submit = server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::Regex(r#""tx_type":8,"#.to_string()))
+            .with_body_from_request(move |_| {
+                on_send();
+                br#"{"code":200}"#.to_vec()
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let lookups = Arc::new(Mutex::new(0));
+        let counter = lookups.clone();
+        let on_status = logged(&log, "status");
+        let _status = server
+            .mock("GET", "/api/v1/tx")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(move |_| {
+                on_status();
+                let mut lookups = counter.lock().unwrap();
+                *lookups += 1;
+                let status = if *lookups < 2 { 1 } else { 2 };
+                format!(r#"{{"code":200,"status":{}}}"#, status).into()
+            })
+            .create_async()
+            .await;
+        let _keys = server
+            .mock("GET", "/api/v1/apikeys")
+            .match_query(mockito::Matcher::Any)
+            .with_body(format!(
+                r#"{{"api_keys":[{{"api_key_index":2,"public_key":"{}"}}]}}"#,
+                hex::encode([1u8; PUBLIC_KEY_LENGTH])
+            ))
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url(), MockKey::new(1, &log));
+        let report = client
+            .rotate_api_key(
+                Arc::new(MockKey::new(2, &log)),
+                opts(),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.confirmed_by, KeyConfirmedBy::TxStatus);
+        assert_eq!(report.old_public_key, hex::encode([1u8; PUBLIC_KEY_LENGTH]));
+        assert_eq!(report.new_public_key, hex::encode([2u8; PUBLIC_KEY_LENGTH]));
+        assert_eq!(report.tx_hash.len(), 80);
+        assert!(report.confirmed_in >= ROTATION_POLL_INTERVAL);
+        assert!(report.total >= report.submitted_in + report.confirmed_in);
+        assert_eq!(client.key_manager().pub_key(), [2u8; PUBLIC_KEY_LENGTH]);
+        assert_eq!(order_signature(&client), vec![2u8; SIGNATURE_LENGTH]);
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["sign 1", "send", "status", "status", "sign 2"]
+        );
+        submit.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_confirmed_by_key_lookup() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut server = mockito::Server::new_async().await;
+        let _submit = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200}"#)
+            .create_async()
+            .await;
+        let _status = server
+            .mock("GET", "/api/v1/tx")
+            .match_query(mockito::Matcher::Any)
+            .with_status(503)
+            .create_async()
+            .await;
+        let _keys = server
+            .mock("GET", "/api/v1/apikeys")
+            .match_query(mockito::Matcher::Any)
+            .with_body(format!(
+                r#"{{"api_keys":[{{"api_key_index":2,"public_key":"0x{}"}}]}}"#,
+                hex::encode([2u8; PUBLIC_KEY_LENGTH])
+            ))
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url(), MockKey::new(1, &log));
+        let report = client
+            .rotate_api_key(
+                Arc::new(MockKey::new(2, &log)),
+                opts(),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.confirmed_by, KeyConfirmedBy::KeyLookup);
+        assert_eq!(client.key_manager().pub_key(), [2u8; PUBLIC_KEY_LENGTH]);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_the_old_key_unless_confirmed() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":21109,"message":"invalid nonce"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let client = test_client(&server.url(), MockKey::new(1, &log));
+        let new_key = || Arc::new(MockKey::new(2, &log));
+
+        let error = client
+            .rotate_api_key(new_key(), opts(), Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("21109"), "{}", error);
+        assert_eq!(client.key_manager().pub_key(), [1u8; PUBLIC_KEY_LENGTH]);
+        rejected.assert_async().await;
+        rejected.remove_async().await;
+
+        let _submit = server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200}"#)
+            .create_async()
+            .await;
+        let _status = server
+            .mock("GET", "/api/v1/tx")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"status":1}"#)
+            .create_async()
+            .await;
+        let _keys = server
+            .mock("GET", "/api/v1/apikeys")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"api_keys":[]}"#)
+            .create_async()
+            .await;
+        let error = client
+            .rotate_api_key(new_key(), opts(), Duration::from_millis(600))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Pending"), "{}", error);
+        assert_eq!(client.key_manager().pub_key(), [1u8; PUBLIC_KEY_LENGTH]);
+        assert_eq!(order_signature(&client), vec![1u8; SIGNATURE_LENGTH]);
+
+        let same = client
+            .rotate_api_key(
+                Arc::new(MockKey::new(1, &log)),
+                opts(),
+                Duration::from_secs(5),
+            )
+            .await;
+        assert!(matches!(same, Err(LighterError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_signing_in_flight_at_the_swap_keeps_the_old_key() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel();
+        let old_key = MockKey {
+            gate: Some(Mutex::new((started_tx, release_rx))),
+            ..MockKey::new(1, &log)
+        };
+        let client = Arc::new(test_client("", old_key));
+
+        let signing = {
+            let client = client.clone();
+            std::thread::spawn(move || order_signature(&client))
+        };
+        started_rx.recv().unwrap();
+        client.replace_key_manager(Arc::new(MockKey::new(2, &log)));
+        assert_eq!(order_signature(&client), vec![2u8; SIGNATURE_LENGTH]);
+        release_tx.send(()).unwrap();
+
+        assert_eq!(signing.join().unwrap(), vec![1u8; SIGNATURE_LENGTH]);
+        assert_eq!(*log.lock().unwrap(), ["sign 2", "sign 1"]);
+    }
+}
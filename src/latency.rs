@@ -0,0 +1,388 @@
+//! Latency from order submission to its first sight in the account stream
+//!
+//! [`OrderLatency`] times each order from the moment
+//! [`send_transaction`](crate::client::TxClient::send_transaction) returned
+//! to the first [`AccountEvent::OrderPlaced`] or [`AccountEvent::Fill`] for
+//! it, and keeps the latest samples for [`stats`](OrderLatency::stats).
+//! Orders not seen within the acknowledgment timeout are counted apart
+//! instead of entering the samples. [`LighterClient`](crate::lighter_client::LighterClient)
+//! wires one up; see
+//! [`latency_stats`](crate::lighter_client::LighterClient::latency_stats).
+//!
+//! With the `metrics` feature, samples are also recorded in the
+//! `lighter_order_ack_latency_seconds` histogram and timeouts counted in
+//! `lighter_order_ack_timeouts_total`.
+//!
+//! ```
+//! use lighter_rs::latency::{LatencyConfig, OrderLatency};
+//! use lighter_rs::ws_client::AccountEvent;
+//!
+//! let latency = OrderLatency::new(LatencyConfig::default());
+//! latency.expect(7);
+//! latency.submitted(7);
+//! latency.apply(&AccountEvent::OrderPlaced {
+//!     market: 0,
+//!     order_index: 1_000,
+//!     client_order_index: 7,
+//! });
+//! assert_eq!(latency.stats().count, 1);
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::constants::{DEFAULT_ORDER_ACK_TIMEOUT_MS, DEFAULT_ORDER_LATENCY_WINDOW};
+use crate::ws_client::AccountEvent;
+
+/// Window and timeout of an [`OrderLatency`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyConfig {
+    /// Latest samples the percentiles are taken over
+    pub window: usize,
+    /// Orders not seen in the stream within this count as timed out
+    pub ack_timeout: Duration,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            window: DEFAULT_ORDER_LATENCY_WINDOW,
+            ack_timeout: Duration::from_millis(DEFAULT_ORDER_ACK_TIMEOUT_MS),
+        }
+    }
+}
+
+/// Distribution of the samples in the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Samples in the window
+    pub count: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+    /// Orders never seen within the timeout, since creation
+    pub timed_out: u64,
+}
+
+/// An order waiting for its first event
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    expected_ms: i64,
+    submitted_ms: Option<i64>,
+    /// Seen before `send_transaction` returned
+    seen_ms: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct LatencyState {
+    pending: HashMap<i64, Pending>,
+    /// Fills whose order wasn't known yet, by order index
+    early_fills: HashMap<i64, i64>,
+    samples: VecDeque<Duration>,
+    timed_out: u64,
+}
+
+/// Submission-to-acknowledgment latencies of orders, keyed by client order
+/// index
+#[derive(Debug)]
+pub struct OrderLatency {
+    config: LatencyConfig,
+    clock: SharedClock,
+    state: Mutex<LatencyState>,
+}
+
+impl OrderLatency {
+    pub fn new(config: LatencyConfig) -> Self {
+        Self {
+            config,
+            clock: Arc::new(SystemClock),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Take submission and event times from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Announce an order before it is sent, so an event racing ahead of
+    /// [`submitted`](Self::submitted) is still matched
+    pub fn expect(&self, client_order_index: i64) {
+        let now = self.clock.now_ms();
+        self.state.lock().unwrap().pending.insert(
+            client_order_index,
+            Pending {
+                expected_ms: now,
+                submitted_ms: None,
+                seen_ms: None,
+            },
+        );
+    }
+
+    /// Start timing an order; `send_transaction` just returned
+    ///
+    /// An order seen before it was submitted counts as zero latency.
+    pub fn submitted(&self, client_order_index: i64) {
+        let now = self.clock.now_ms();
+        let mut state = self.state.lock().unwrap();
+        let pending = state.pending.entry(client_order_index).or_insert(Pending {
+            expected_ms: now,
+            submitted_ms: None,
+            seen_ms: None,
+        });
+        if pending.seen_ms.is_some() {
+            state.pending.remove(&client_order_index);
+            self.record(&mut state, Duration::ZERO);
+        } else {
+            pending.submitted_ms = Some(now);
+        }
+    }
+
+    /// Stop tracking an order that wasn't sent or was rejected
+    pub fn forget(&self, client_order_index: i64) {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .remove(&client_order_index);
+    }
+
+    /// Match an account event against the orders being timed
+    ///
+    /// A fill names the order index only, so it is matched once the
+    /// placement that maps it to a client order index arrives, at the time
+    /// of the fill.
+    pub fn apply(&self, event: &AccountEvent) {
+        let now = self.clock.now_ms();
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state, now);
+        match event {
+            AccountEvent::OrderPlaced {
+                order_index,
+                client_order_index,
+                ..
+            } => {
+                let seen = state.early_fills.remove(order_index).unwrap_or(now);
+                self.seen(&mut state, *client_order_index, seen);
+            }
+            AccountEvent::Fill { order_index, .. } => {
+                state.early_fills.entry(*order_index).or_insert(now);
+            }
+            AccountEvent::OrderRejected {
+                client_order_index, ..
+            } => {
+                state.pending.remove(client_order_index);
+            }
+            _ => {}
+        }
+    }
+
+    /// Distribution of the latest samples and the timeouts so far
+    pub fn stats(&self) -> LatencyStats {
+        let now = self.clock.now_ms();
+        let mut state = self.state.lock().unwrap();
+        self.expire(&mut state, now);
+
+        let mut sorted: Vec<Duration> = state.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        // Nearest rank
+        let percentile = |p: usize| {
+            let rank = (sorted.len() * p).div_ceil(100).max(1);
+            sorted.get(rank - 1).copied().unwrap_or_default()
+        };
+        LatencyStats {
+            count: sorted.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: sorted.last().copied().unwrap_or_default(),
+            timed_out: state.timed_out,
+        }
+    }
+
+    /// Orders sent or announced and not seen yet
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    fn seen(&self, state: &mut LatencyState, client_order_index: i64, at_ms: i64) {
+        let Some(pending) = state.pending.get_mut(&client_order_index) else {
+            return;
+        };
+        match pending.submitted_ms {
+            Some(submitted_ms) => {
+                let latency = Duration::from_millis((at_ms - submitted_ms).max(0) as u64);
+                state.pending.remove(&client_order_index);
+                self.record(state, latency);
+            }
+            None => {
+                pending.seen_ms.get_or_insert(at_ms);
+            }
+        }
+    }
+
+    fn record(&self, state: &mut LatencyState, latency: Duration) {
+        if state.samples.len() == self.config.window.max(1) {
+            state.samples.pop_front();
+        }
+        state.samples.push_back(latency);
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("lighter_order_ack_latency_seconds").record(latency.as_secs_f64());
+    }
+
+    /// Count submitted orders past the timeout and drop stale entries
+    fn expire(&self, state: &mut LatencyState, now_ms: i64) {
+        let timeout = i64::try_from(self.config.ack_timeout.as_millis()).unwrap_or(i64::MAX);
+        let cutoff = now_ms.saturating_sub(timeout);
+        let mut timed_out = 0;
+        state.pending.retain(|_, pending| {
+            match pending.submitted_ms {
+                Some(submitted_ms) if submitted_ms < cutoff => timed_out += 1,
+                // Announced and never submitted nor forgotten
+                None if pending.expected_ms < cutoff => {}
+                _ => return true,
+            }
+            false
+        });
+        state.early_fills.retain(|_, at_ms| *at_ms >= cutoff);
+        state.timed_out += timed_out;
+        #[cfg(feature = "metrics")]
+        if timed_out > 0 {
+            metrics::counter!("lighter_order_ack_timeouts_total").increment(timed_out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ReplayClock;
+    use crate::types::Side;
+    use chrono::DateTime;
+    use rust_decimal::Decimal;
+
+    fn setup(window: usize) -> (OrderLatency, ReplayClock) {
+        let clock = ReplayClock::new(DateTime::from_timestamp_millis(1_700_000_000_000).unwrap());
+        let latency = OrderLatency::new(LatencyConfig {
+            window,
+            ack_timeout: Duration::from_secs(5),
+        })
+        .with_clock(Arc::new(clock.clone()));
+        (latency, clock)
+    }
+
+    fn placed(order_index: i64, client_order_index: i64) -> AccountEvent {
+        AccountEvent::OrderPlaced {
+            market: 0,
+            order_index,
+            client_order_index,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_percentiles_over_window() {
+        let (latency, clock) = setup(100);
+        for cid in 1..=100 {
+            latency.expect(cid);
+            latency.submitted(cid);
+            clock.advance(ms(cid as u64));
+            latency.apply(&placed(cid + 1_000, cid));
+        }
+
+        let stats = latency.stats();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.p50, ms(50));
+        assert_eq!(stats.p90, ms(90));
+        assert_eq!(stats.p99, ms(99));
+        assert_eq!(stats.max, ms(100));
+        assert_eq!(stats.timed_out, 0);
+        assert_eq!(latency.pending(), 0);
+
+        // The window slides: the 1..=10ms samples give way to 200ms ones
+        for cid in 101..=110 {
+            latency.submitted(cid);
+            clock.advance(ms(200));
+            latency.apply(&placed(cid + 1_000, cid));
+        }
+        let stats = latency.stats();
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.p50, ms(60));
+        assert_eq!(stats.max, ms(200));
+    }
+
+    #[test]
+    fn test_unacknowledged_orders_time_out() {
+        let (latency, clock) = setup(10);
+        latency.expect(1);
+        latency.submitted(1);
+        latency.expect(2);
+        latency.submitted(2);
+        latency.expect(3);
+        latency.forget(3);
+
+        clock.advance(ms(4_000));
+        latency.apply(&placed(1_001, 1));
+        clock.advance(ms(1_500));
+        // Too late for order 2, which left the pending set
+        latency.apply(&placed(1_002, 2));
+
+        let stats = latency.stats();
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.max, ms(4_000));
+        assert_eq!(stats.timed_out, 1);
+        assert_eq!(latency.pending(), 0);
+
+        // Rejected orders neither count nor time out
+        latency.expect(4);
+        latency.submitted(4);
+        latency.apply(&AccountEvent::OrderRejected {
+            market: 0,
+            order_index: 1_004,
+            client_order_index: 4,
+            reason: "canceled-post-only".to_string(),
+        });
+        clock.advance(ms(10_000));
+        assert_eq!(latency.stats().timed_out, 1);
+    }
+
+    #[test]
+    fn test_fill_and_early_events() {
+        let (latency, clock) = setup(10);
+
+        // A taker order fills before the update shows it placed
+        latency.expect(1);
+        latency.submitted(1);
+        clock.advance(ms(30));
+        latency.apply(&AccountEvent::Fill {
+            market: 0,
+            price: Decimal::ONE,
+            size: Decimal::ONE,
+            side: Side::Buy,
+            order_index: 1_001,
+        });
+        clock.advance(ms(5));
+        latency.apply(&placed(1_001, 1));
+
+        // The stream beats send_transaction returning
+        latency.expect(2);
+        clock.advance(ms(10));
+        latency.apply(&placed(1_002, 2));
+        latency.submitted(2);
+
+        // Other clients' orders are ignored
+        latency.apply(&placed(1_003, 3));
+
+        let stats = latency.stats();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.p50, Duration::ZERO);
+        assert_eq!(stats.max, ms(30));
+    }
+}
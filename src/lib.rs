@@ -14,22 +14,45 @@
 //! - `signer`: Cryptographic key management and signing functionality
 //! - `types`: Transaction types and request builders
 //! - `client`: HTTP client for API interactions
+//! - `ws_client`: WebSocket client for order book and account streams
+//! - `lighter_client`: High-level facade combining signing, REST and streams
+//! - `alerts`: Threshold alerts on account state
+//! - `amendments`: Audit log of order modifications
+//! - `book_stats`: Rolling mid, spread and imbalance statistics
+//! - `bulk`: Planning and sending large batches of orders
+//! - `fees`: Maker and taker fee schedules and estimates
+//! - `latency`: Submission-to-acknowledgment latency of orders
+//! - `market_status`: Market trading status and gating of new orders
+//! - `order_flow`: Traded volume, level removals and trade-throughs of a market
+//! - `tcp`: Socket options of REST and WebSocket connections
+//! - `transport`: Pluggable HTTP transport, with a scripted mock (`test-util` feature)
+//! - `throttle`: Per-market order throttling and self-cross checks
+//! - `risk`: Circuit breaker consulted before submissions
+//! - `runtime_config`: Limits and policies changed while clients run
+//! - `recorder`: Rotating on-disk capture of stream messages
+//! - `replay`: Paced replay of recordings through a stream client's handlers
+//! - `clock`: Wall and replay clocks read by time-dependent logic
+//! - `tasks`: Background task sets with cancellation and bounded shutdown
+//! - `self_test`: Startup checks of credentials and connectivity
+//! - `withdraw`: Withdrawals confirmed without resubmitting
+//! - `key_rotation`: Switching a running client to a new API key
+//! - `blocking`: Synchronous transaction clients (`blocking` feature)
 //! - `errors`: Error types and handling
 //!
 //! ## Example
 //!
 //! ```rust,no_run
 //! use lighter_rs::client::TxClient;
-//! use lighter_rs::types::CreateOrderTxReq;
+//! use lighter_rs::types::{AccountIndex, ApiKeyIndex, ChainId, CreateOrderTxReq};
 //!
 //! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
 //! // Create a transaction client
-//! let tx_client = TxClient::new(
+//! let tx_client = TxClient::from_ids(
 //!     "https://api.lighter.xyz",
 //!     "your_api_key_hex",
-//!     12345,  // account_index
-//!     0,      // api_key_index
-//!     1,      // chain_id
+//!     AccountIndex::new(12345)?,
+//!     ApiKeyIndex::new(0)?,
+//!     ChainId::new(304)?,
 //! )?;
 //!
 //! // Create and submit an order
@@ -39,18 +62,51 @@
 //! # }
 //! ```
 
+pub mod alerts;
+pub mod amendments;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod book_stats;
+pub mod bulk;
 pub mod client;
+pub mod clock;
 pub mod constants;
+pub mod dead_mans_switch;
 pub mod errors;
+pub mod fees;
+pub mod key_rotation;
+pub mod latency;
+pub mod lighter_client;
+pub mod market_status;
+pub mod network;
+pub mod order_flow;
+pub mod order_tracker;
+pub mod peg;
+pub mod recorder;
+pub mod replay;
+pub mod risk;
+pub mod runtime_config;
+pub mod self_test;
+pub mod serde_util;
 pub mod signer;
+pub mod tasks;
+pub mod tcp;
+pub mod throttle;
+pub mod transport;
 pub mod types;
 pub mod utils;
+pub mod withdraw;
 pub mod ws_client;
 
+#[cfg(test)]
+mod test_support;
+
 // Re-export commonly used types
 pub use client::TxResponse;
 pub use constants::*;
 pub use errors::{LighterError, Result};
+pub use lighter_client::{Credentials, LighterClient};
+pub use network::Network;
 pub use signer::{KeyManager, Signer};
 pub use types::{TransactOpts, TxInfo};
 
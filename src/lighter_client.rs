@@ -0,0 +1,1447 @@
+//! High-level client combining signing, REST submission and the WebSocket stream
+//!
+//! [`LighterClient`] owns a [`TxClient`], an [`OrderTracker`] and a
+//! [`WsClient`] that is started on first use and reconnected with backoff
+//! until [`shutdown`](LighterClient::shutdown). Clones share all state.
+//!
+//! Shutdown stops the components in dependency order: new orders are
+//! refused, the [recorder](LighterClientBuilder::recorder) is flushed and
+//! closed, dead man's switches stop renewing (and are disarmed if
+//! [configured](LighterClientBuilder::disarm_switches_on_shutdown)), then
+//! the stream is closed. Dropping the last clone cancels the background
+//! tasks without waiting for them.
+//!
+//! ```rust,no_run
+//! use lighter_rs::lighter_client::{Credentials, LighterClient};
+//! use lighter_rs::network::Network;
+//! use lighter_rs::types::{
+//!     AccountIndex, ApiKeyIndex, BaseAmount, MarketIndex, OrderParams, Price, Side,
+//! };
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let client = LighterClient::builder(
+//!     Network::Testnet,
+//!     Credentials::new("0x...", AccountIndex::new(12345)?, ApiKeyIndex::new(0)?),
+//! )
+//! .markets(vec![0])
+//! .build()?;
+//!
+//! let mut events = client.events();
+//! let placed = client
+//!     .place_limit(OrderParams::new(
+//!         MarketIndex::new(0)?,
+//!         Side::Buy,
+//!         BaseAmount::new(1_000)?,
+//!         Price::new(300_000)?,
+//!     ))
+//!     .await?;
+//! println!("placed {}", placed.client_order_index);
+//!
+//! while let Ok(event) = events.recv().await {
+//!     println!("{:?}", event);
+//! }
+//! client.shutdown().await;
+//! # Ok(())
+//! # }
+//! ```
+
+use rust_decimal::Decimal;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::client::{CancelOutcome, TxClient, TxResponse};
+use crate::constants::{
+    CANCEL_ALL_IMMEDIATE, DEFAULT_RECORDER_TAP_CAPACITY, DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS,
+    MAX_CLIENT_ORDER_INDEX,
+};
+use crate::dead_mans_switch::{stop_renewals, DmsHandle, DmsStatus, Renewals};
+use crate::errors::{LighterError, Result};
+use crate::latency::{LatencyConfig, LatencyStats, OrderLatency};
+use crate::market_status::MarketStatusChange;
+use crate::network::Network;
+use crate::order_tracker::{OrderStatus, OrderTracker};
+use crate::recorder::MarketRecorder;
+use crate::self_test::{SelfTestOptions, SelfTestReport};
+use crate::signer::NonceStore;
+use crate::tasks::{ShutdownReport, StopFailure, TaskSet};
+use crate::types::{
+    AccountIndex, ApiKeyIndex, CancelAllOrdersTxReq, ChainId, MarketIndex, OrderKind, OrderParams,
+    SignatureEncoding,
+};
+use crate::ws_client::{
+    AccountEvent, AccountSnapshot, MarketView, OrderBook, RawWsMessage, WsClient, WsConfig, WsEvent,
+};
+
+/// API key credentials for one account
+#[derive(Clone)]
+pub struct Credentials {
+    pub private_key: String,
+    pub account_index: i64,
+    pub api_key_index: u8,
+}
+
+impl Credentials {
+    /// Create credentials from a hex private key
+    pub fn new(
+        private_key: impl Into<String>,
+        account_index: AccountIndex,
+        api_key_index: ApiKeyIndex,
+    ) -> Self {
+        Self {
+            private_key: private_key.into(),
+            account_index: account_index.get(),
+            api_key_index: api_key_index.get(),
+        }
+    }
+
+    /// Create credentials from raw integers, validating their ranges
+    #[deprecated(note = "use `new` with `AccountIndex` and `ApiKeyIndex`")]
+    pub fn new_raw(
+        private_key: impl Into<String>,
+        account_index: i64,
+        api_key_index: u8,
+    ) -> Result<Self> {
+        Ok(Self::new(
+            private_key,
+            AccountIndex::new(account_index)?,
+            ApiKeyIndex::new(api_key_index)?,
+        ))
+    }
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("private_key", &"<redacted>")
+            .field("account_index", &self.account_index)
+            .field("api_key_index", &self.api_key_index)
+            .finish()
+    }
+}
+
+/// Event from the managed WebSocket stream
+#[derive(Debug, Clone)]
+pub enum LighterEvent {
+    OrderBook {
+        market_id: String,
+        order_book: OrderBook,
+    },
+    Account {
+        account_id: String,
+        event: AccountEvent,
+    },
+    /// The stream ended; a reconnect follows unless shut down
+    Disconnected { error: Option<String> },
+    /// A market's trading status changed; see
+    /// [`LighterClientBuilder::market_status_gate`]
+    MarketStatus { change: MarketStatusChange },
+    /// The connection stayed down past the grace period set with
+    /// [`LighterClientBuilder::cancel_all_on_disconnect`]
+    DisconnectCancel { outcome: DisconnectCancelOutcome },
+}
+
+/// What the cancel-all on disconnect did
+#[derive(Debug, Clone)]
+pub enum DisconnectCancelOutcome {
+    /// Sent; the response code says whether it was accepted
+    Submitted(TxResponse),
+    /// Signing or sending failed
+    Failed(String),
+    /// Skipped since an armed dead man's switch pulls the orders at
+    /// `cancel_at_ms`
+    SuppressedBySwitch { cancel_at_ms: i64 },
+    /// Skipped since the connection came back just before sending
+    Reconnected,
+}
+
+/// Settings of the cancel-all on disconnect
+#[derive(Debug, Clone, Copy)]
+struct DisconnectCancel {
+    grace: Duration,
+    when_switch_armed: bool,
+}
+
+/// Dead man's switch started through the client
+#[derive(Clone)]
+struct Switch {
+    status: Arc<Mutex<DmsStatus>>,
+    renewals: Renewals,
+}
+
+type Switches = Arc<Mutex<Vec<Switch>>>;
+
+/// Recorder of the stream's raw frames, started with the stream
+enum Recording {
+    Off,
+    Ready(Box<MarketRecorder>, mpsc::Receiver<RawWsMessage>),
+    Running(TaskSet),
+}
+
+/// Order accepted by `sendTx`
+#[derive(Debug, Clone)]
+pub struct SubmittedOrder {
+    pub client_order_index: i64,
+    pub response: TxResponse,
+}
+
+/// Order confirmed open (or filled) by the account stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedOrder {
+    pub client_order_index: i64,
+    pub order_index: i64,
+    /// [`OrderStatus::Open`] or [`OrderStatus::Filled`]
+    pub status: OrderStatus,
+    /// Base amount filled when the order was first seen
+    pub fills_so_far: Decimal,
+    pub tx_hash: Option<String>,
+}
+
+/// Builder for [`LighterClient`]
+pub struct LighterClientBuilder {
+    network: Network,
+    credentials: Credentials,
+    markets: Vec<u32>,
+    nonce_store: Option<Arc<dyn NonceStore>>,
+    ws_config: WsConfig,
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+    event_capacity: usize,
+    cancel_all_on_disconnect: bool,
+    disconnect_cancel: DisconnectCancel,
+    market_status_gate: bool,
+    signature_encoding: Option<SignatureEncoding>,
+    latency: LatencyConfig,
+    recorder: Option<MarketRecorder>,
+    disarm_switches_on_shutdown: bool,
+    shutdown_timeout: Duration,
+}
+
+impl LighterClientBuilder {
+    /// Order book markets to subscribe to
+    pub fn markets(mut self, markets: Vec<u32>) -> Self {
+        self.markets = markets;
+        self
+    }
+
+    /// Allocate nonces locally from this store
+    pub fn nonce_store(mut self, store: Arc<dyn NonceStore>) -> Self {
+        self.nonce_store = Some(store);
+        self
+    }
+
+    /// WebSocket connection settings
+    pub fn ws_config(mut self, config: WsConfig) -> Self {
+        self.ws_config = config;
+        self
+    }
+
+    /// Initial and maximum delay between reconnect attempts (default 500ms, 30s)
+    pub fn reconnect_delay(mut self, initial: Duration, max: Duration) -> Self {
+        self.reconnect_delay = initial;
+        self.max_reconnect_delay = max;
+        self
+    }
+
+    /// Capacity of the event broadcast channel (default 1024)
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// Follow the trading status of the configured markets and refuse new
+    /// orders in those that won't take them (default off)
+    ///
+    /// Orders fail with [`LighterError::MarketNotActive`] instead of being
+    /// sent; reduce-only orders pass in a reduce-only market. Status
+    /// changes are reported as [`LighterEvent::MarketStatus`].
+    pub fn market_status_gate(mut self, enabled: bool) -> Self {
+        self.market_status_gate = enabled;
+        self
+    }
+
+    /// How transaction signatures are written (defaults to the network's
+    /// [`signature_encoding`](Network::signature_encoding))
+    pub fn signature_encoding(mut self, encoding: SignatureEncoding) -> Self {
+        self.signature_encoding = Some(encoding);
+        self
+    }
+
+    /// Window and acknowledgment timeout of the order latencies behind
+    /// [`LighterClient::latency_stats`]
+    pub fn latency(mut self, config: LatencyConfig) -> Self {
+        self.latency = config;
+        self
+    }
+
+    /// Record the stream's raw frames, starting with the stream
+    ///
+    /// The recording is flushed and closed on
+    /// [`shutdown`](LighterClient::shutdown).
+    pub fn recorder(mut self, recorder: MarketRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Abort the scheduled cancel-all of dead man's switches started with
+    /// [`LighterClient::spawn_dead_mans_switch`] on shutdown (default off)
+    ///
+    /// Renewals stop either way; left armed, the switch pulls the orders
+    /// when its last scheduled cancel comes due.
+    pub fn disarm_switches_on_shutdown(mut self, enabled: bool) -> Self {
+        self.disarm_switches_on_shutdown = enabled;
+        self
+    }
+
+    /// How long shutdown waits for each component's tasks before aborting
+    /// them (default 5s)
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Cancel all open orders when the stream stays disconnected past the
+    /// grace period (default off)
+    ///
+    /// Fires once per outage, after a connection was up, and reports a
+    /// [`LighterEvent::DisconnectCancel`]. Only the stream is down, so the
+    /// cancel goes out over REST.
+    pub fn cancel_all_on_disconnect(mut self, enabled: bool) -> Self {
+        self.cancel_all_on_disconnect = enabled;
+        self
+    }
+
+    /// How long the stream may be down before cancelling (default 5s)
+    pub fn disconnect_cancel_grace(mut self, grace: Duration) -> Self {
+        self.disconnect_cancel.grace = grace;
+        self
+    }
+
+    /// Cancel on disconnect even while a dead man's switch started with
+    /// [`LighterClient::spawn_dead_mans_switch`] is armed (default off)
+    pub fn disconnect_cancel_when_switch_armed(mut self, enabled: bool) -> Self {
+        self.disconnect_cancel.when_switch_armed = enabled;
+        self
+    }
+
+    /// Build the client and run [`LighterClient::self_test`]
+    ///
+    /// A key or index the client can't be built with is reported as a
+    /// failed step rather than an error.
+    pub async fn self_test(self) -> SelfTestReport {
+        match self.build() {
+            Ok(client) => client.self_test().await,
+            Err(e) => SelfTestReport::from_build_error(e),
+        }
+    }
+
+    /// Build the client; the WebSocket stream starts on first use
+    pub fn build(self) -> Result<LighterClient> {
+        let mut tx = TxClient::from_ids(
+            self.network.api_url(),
+            &self.credentials.private_key,
+            AccountIndex::new(self.credentials.account_index)?,
+            ApiKeyIndex::new(self.credentials.api_key_index)?,
+            ChainId::new(self.network.chain_id())?,
+        )?;
+        if let Some(store) = self.nonce_store {
+            tx.set_nonce_store(store);
+        }
+        tx.set_signature_encoding(
+            self.signature_encoding
+                .unwrap_or_else(|| self.network.signature_encoding()),
+        );
+
+        let mut ws = WsClient::builder()
+            .url(self.network.ws_url())
+            .order_books(self.markets.clone())
+            .accounts(vec![self.credentials.account_index])
+            .config(self.ws_config);
+        if self.market_status_gate {
+            ws = ws.market_stats(self.markets.clone());
+        }
+        let recording = match self.recorder {
+            Some(recorder) => {
+                let (tap, frames) = mpsc::channel(DEFAULT_RECORDER_TAP_CAPACITY);
+                ws = ws.raw_message_tap(tap);
+                Recording::Ready(Box::new(recorder), frames)
+            }
+            None => Recording::Off,
+        };
+        let ws = ws.build()?;
+        if self.market_status_gate {
+            tx.set_market_statuses(ws.market_statuses());
+        }
+
+        let tracker = Arc::new(OrderTracker::new());
+        let latency = Arc::new(OrderLatency::new(self.latency));
+        let (events, _) = broadcast::channel(self.event_capacity);
+
+        let sender = events.clone();
+        ws.on_any_order_book(move |market_id, order_book| {
+            let _ = sender.send(LighterEvent::OrderBook {
+                market_id,
+                order_book,
+            });
+        });
+        let sender = events.clone();
+        let account_tracker = tracker.clone();
+        let account_latency = latency.clone();
+        ws.on_account_event(move |account_id, event| {
+            account_latency.apply(&event);
+            account_tracker.apply(&event);
+            let _ = sender.send(LighterEvent::Account { account_id, event });
+        });
+        let sender = events.clone();
+        ws.on_ws_event(move |event| {
+            if let WsEvent::MarketStatus(change) = event {
+                let _ = sender.send(LighterEvent::MarketStatus { change });
+            }
+        });
+
+        let seed = chrono::Utc::now().timestamp_millis() % MAX_CLIENT_ORDER_INDEX;
+
+        Ok(LighterClient {
+            inner: Arc::new(Inner {
+                network: self.network,
+                account_index: self.credentials.account_index,
+                markets: self.markets,
+                tx: Arc::new(tx),
+                ws: Arc::new(ws),
+                tracker,
+                latency,
+                events,
+                closed: AtomicBool::new(false),
+                stream: Mutex::new(None),
+                recording: Mutex::new(recording),
+                next_client_order_index: AtomicI64::new(seed.max(1)),
+                reconnect_delay: self.reconnect_delay,
+                max_reconnect_delay: self.max_reconnect_delay,
+                disconnect_cancel: self
+                    .cancel_all_on_disconnect
+                    .then_some(self.disconnect_cancel),
+                switches: Switches::default(),
+                disarm_switches_on_shutdown: self.disarm_switches_on_shutdown,
+                shutdown_timeout: self.shutdown_timeout,
+            }),
+        })
+    }
+}
+
+struct Inner {
+    network: Network,
+    account_index: i64,
+    markets: Vec<u32>,
+    tx: Arc<TxClient>,
+    ws: Arc<WsClient>,
+    tracker: Arc<OrderTracker>,
+    latency: Arc<OrderLatency>,
+    events: broadcast::Sender<LighterEvent>,
+    closed: AtomicBool,
+    /// Stream and disconnect watchdog, once started
+    stream: Mutex<Option<TaskSet>>,
+    recording: Mutex<Recording>,
+    next_client_order_index: AtomicI64,
+    reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+    disconnect_cancel: Option<DisconnectCancel>,
+    switches: Switches,
+    disarm_switches_on_shutdown: bool,
+    shutdown_timeout: Duration,
+}
+
+/// High-level Lighter client, cheap to clone
+#[derive(Clone)]
+pub struct LighterClient {
+    inner: Arc<Inner>,
+}
+
+impl std::fmt::Debug for LighterClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LighterClient")
+            .field("network", &self.inner.network)
+            .field("account_index", &self.inner.account_index)
+            .finish()
+    }
+}
+
+impl LighterClient {
+    /// Start building a client for a network and account
+    pub fn builder(network: Network, credentials: Credentials) -> LighterClientBuilder {
+        LighterClientBuilder {
+            network,
+            credentials,
+            markets: Vec::new(),
+            nonce_store: None,
+            ws_config: WsConfig::default(),
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+            event_capacity: 1024,
+            cancel_all_on_disconnect: false,
+            disconnect_cancel: DisconnectCancel {
+                grace: Duration::from_secs(5),
+                when_switch_armed: false,
+            },
+            market_status_gate: false,
+            signature_encoding: None,
+            latency: LatencyConfig::default(),
+            recorder: None,
+            disarm_switches_on_shutdown: false,
+            shutdown_timeout: Duration::from_millis(DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS),
+        }
+    }
+
+    /// Network this client talks to
+    pub fn network(&self) -> &Network {
+        &self.inner.network
+    }
+
+    /// Underlying transaction client
+    pub fn tx_client(&self) -> &TxClient {
+        &self.inner.tx
+    }
+
+    /// Underlying WebSocket client, e.g. to register per-market handlers
+    pub fn ws_client(&self) -> &WsClient {
+        &self.inner.ws
+    }
+
+    /// Orders placed through this client
+    pub fn order_tracker(&self) -> &OrderTracker {
+        &self.inner.tracker
+    }
+
+    /// Latency from `send_transaction` returning to the first sight of each
+    /// order placed through this client in the account stream
+    ///
+    /// See [`OrderLatency`]; orders not seen within the configured timeout
+    /// are only counted in `timed_out`.
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.inner.latency.stats()
+    }
+
+    /// Subscribe to stream events, starting the stream if needed
+    pub fn events(&self) -> broadcast::Receiver<LighterEvent> {
+        let receiver = self.inner.events.subscribe();
+        self.ensure_stream();
+        receiver
+    }
+
+    /// Current order book for a market, starting the stream if needed
+    pub async fn order_book(&self, market_id: u32) -> Option<OrderBook> {
+        self.ensure_stream();
+        self.inner.ws.get_order_book(&market_id.to_string()).await
+    }
+
+    /// Current account snapshot, starting the stream if needed
+    pub async fn account(&self) -> Option<Arc<AccountSnapshot>> {
+        self.ensure_stream();
+        self.inner
+            .ws
+            .get_account(&self.inner.account_index.to_string())
+            .await
+    }
+
+    /// Book, own open orders and position of one market, read together
+    ///
+    /// See [`WsClient::market_view`]; starts the stream if needed.
+    pub async fn market_view(&self, market_id: u32) -> Option<MarketView> {
+        self.ensure_stream();
+        self.inner
+            .ws
+            .market_view(market_id, &self.inner.account_index.to_string())
+            .await
+    }
+
+    /// Sign and submit a limit order and register it with the order tracker
+    ///
+    /// A client order index is assigned if `params` doesn't carry one.
+    pub async fn place_limit(&self, params: OrderParams) -> Result<SubmittedOrder> {
+        self.place(params, OrderKind::Limit).await
+    }
+
+    /// Sign and submit an order of any kind and register it with the order tracker
+    ///
+    /// The order is tracked before it is sent so stream events can't race
+    /// ahead of it. Fails with [`LighterError::ShutDown`] once the client is
+    /// shut down.
+    pub async fn place(&self, mut params: OrderParams, kind: OrderKind) -> Result<SubmittedOrder> {
+        if self.inner.closed.load(Ordering::SeqCst) {
+            return Err(LighterError::ShutDown);
+        }
+        let client_order_index = match params.client_order_index {
+            Some(index) => index,
+            None => {
+                let index = self.next_client_order_index();
+                params.client_order_index = Some(index);
+                index
+            }
+        };
+
+        let tx = self.inner.tx.create_order_with(params, kind, None).await?;
+
+        let tracker = &self.inner.tracker;
+        let latency = &self.inner.latency;
+        tracker.register(params.market_index.get(), client_order_index, None);
+        tracker.set_order_expiry(client_order_index, tx.order_info.order_expiry);
+        latency.expect(client_order_index);
+        let response = match self.inner.tx.send_transaction(&tx).await {
+            Ok(response) => response,
+            Err(e) => {
+                latency.forget(client_order_index);
+                tracker.reject(client_order_index, e.to_string());
+                return Err(e);
+            }
+        };
+        tracker.set_tx_hash(client_order_index, response.tx_hash.clone());
+
+        if response.code == 200 {
+            latency.submitted(client_order_index);
+        } else {
+            latency.forget(client_order_index);
+            let reason = response
+                .message
+                .clone()
+                .unwrap_or_else(|| format!("code {}", response.code));
+            self.inner
+                .tracker
+                .reject(client_order_index, reason.clone());
+            return Err(LighterError::ApiError(format!(
+                "Order {} rejected: {}",
+                client_order_index, reason
+            )));
+        }
+
+        Ok(SubmittedOrder {
+            client_order_index,
+            response,
+        })
+    }
+
+    /// Submit an order and wait until it is open on the book
+    ///
+    /// Starts the stream if needed, submits via [`place`](Self::place) and
+    /// resolves once the account stream shows the order open or filled.
+    /// Rejections at submission or in the stream fail right away; an order
+    /// cancelled before it was seen open (e.g. an unfilled IOC) fails too.
+    pub async fn place_and_await_open(
+        &self,
+        params: OrderParams,
+        kind: OrderKind,
+        timeout: Duration,
+    ) -> Result<PlacedOrder> {
+        self.ensure_stream();
+        let submitted = self.place(params, kind).await?;
+        let client_order_index = submitted.client_order_index;
+
+        let order = self
+            .inner
+            .tracker
+            .wait_resolved(client_order_index, timeout)
+            .await?;
+        match order.status {
+            OrderStatus::Open | OrderStatus::Filled => Ok(PlacedOrder {
+                client_order_index,
+                order_index: order.order_index.unwrap_or_default(),
+                status: order.status,
+                fills_so_far: order.filled,
+                tx_hash: order.tx_hash,
+            }),
+            OrderStatus::Rejected => Err(LighterError::ApiError(format!(
+                "Order {} rejected: {}",
+                client_order_index,
+                order.reject_reason.unwrap_or_default()
+            ))),
+            status => Err(LighterError::ApiError(format!(
+                "Order {} ended as {:?} before it was open",
+                client_order_index, status
+            ))),
+        }
+    }
+
+    /// Sign and submit an immediate cancel of all open orders
+    pub async fn cancel_all(&self) -> Result<TxResponse> {
+        let req = CancelAllOrdersTxReq {
+            time_in_force: CANCEL_ALL_IMMEDIATE,
+            time: 0,
+        };
+        let tx = self.inner.tx.cancel_all_orders(&req, None).await?;
+        self.inner.tx.send_transaction(&tx).await
+    }
+
+    /// Start a dead man's switch on this client's account
+    ///
+    /// See [`TxClient::spawn_dead_mans_switch`]. While the switch is armed
+    /// the cancel-all on disconnect is skipped unless
+    /// [`disconnect_cancel_when_switch_armed`](LighterClientBuilder::disconnect_cancel_when_switch_armed)
+    /// is set.
+    pub fn spawn_dead_mans_switch(&self, period: Duration, horizon: Duration) -> Result<DmsHandle> {
+        let switch = self.inner.tx.spawn_dead_mans_switch(period, horizon)?;
+        let mut switches = self.inner.switches.lock().unwrap();
+        let now_ms = self.inner.tx.now_ms();
+        // Forget switches whose handle is gone and whose cancel is past
+        switches.retain(|switch| {
+            Arc::strong_count(&switch.status) > 1
+                || switch
+                    .status
+                    .lock()
+                    .unwrap()
+                    .cancel_at_ms
+                    .is_some_and(|at| at > now_ms)
+        });
+        switches.push(Switch {
+            status: switch.shared_status(),
+            renewals: switch.shared_renewals(),
+        });
+        Ok(switch)
+    }
+
+    /// Cancel the open orders of one market known from the account stream
+    ///
+    /// See [`TxClient::cancel_all_orders_for_market`]; returns no outcomes
+    /// before the first account snapshot arrives.
+    pub async fn cancel_all_for_market(
+        &self,
+        market_index: MarketIndex,
+    ) -> Result<Vec<CancelOutcome>> {
+        let account = self.account().await.unwrap_or_default();
+        self.inner
+            .tx
+            .cancel_all_orders_for_market(market_index, &account, None)
+            .await
+    }
+
+    /// Check credentials and connectivity without submitting anything
+    ///
+    /// Runs [`TxClient::self_test`] against this network's stream and the
+    /// first configured market, or market 0.
+    pub async fn self_test(&self) -> SelfTestReport {
+        let options = SelfTestOptions {
+            ws_url: Some(self.inner.network.ws_url()),
+            market_id: self.inner.markets.first().copied().unwrap_or(0),
+            ..Default::default()
+        };
+        self.inner.tx.self_test(&options).await
+    }
+
+    /// Handle that shuts the client down, usable after this client is moved
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Stop the background tasks and wait for them to finish
+    ///
+    /// See [`ShutdownHandle::shutdown`].
+    pub async fn shutdown(&self) -> ShutdownReport {
+        self.shutdown_handle().shutdown().await
+    }
+
+    fn next_client_order_index(&self) -> i64 {
+        let index = self
+            .inner
+            .next_client_order_index
+            .fetch_add(1, Ordering::Relaxed);
+        // Wrap into the valid client order index range
+        (index - 1).rem_euclid(MAX_CLIENT_ORDER_INDEX) + 1
+    }
+
+    fn ensure_stream(&self) {
+        {
+            let mut stream = self.inner.stream.lock().unwrap();
+            if stream.is_some() || self.inner.closed.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let ws = self.inner.ws.clone();
+            let events = self.inner.events.clone();
+            let delays = (self.inner.reconnect_delay, self.inner.max_reconnect_delay);
+            let mut tasks = TaskSet::new();
+            tasks.spawn("stream", run_stream(ws.clone(), events.clone(), delays));
+            if let Some(config) = self.inner.disconnect_cancel {
+                tasks.spawn(
+                    "disconnect_cancel",
+                    cancel_on_disconnect(
+                        self.inner.tx.clone(),
+                        ws.connection_state(),
+                        self.inner.switches.clone(),
+                        events,
+                        config,
+                    ),
+                );
+            }
+            *stream = Some(tasks);
+        }
+
+        let mut recording = self.inner.recording.lock().unwrap();
+        if let Recording::Ready(recorder, frames) =
+            std::mem::replace(&mut *recording, Recording::Off)
+        {
+            let mut tasks = TaskSet::new();
+            tasks.spawn_graceful("recorder", |cancel| recorder.run_until(frames, cancel));
+            *recording = Recording::Running(tasks);
+        }
+    }
+}
+
+/// Run the WebSocket client until cancelled, reconnecting with backoff
+async fn run_stream(
+    ws: Arc<WsClient>,
+    events: broadcast::Sender<LighterEvent>,
+    (initial_delay, max_delay): (Duration, Duration),
+) -> Result<()> {
+    let mut delay = initial_delay;
+    loop {
+        let result = ws.run(|_, _| {}, |_, _| {}).await;
+
+        let error = result.as_ref().err().map(|e| e.to_string());
+        if let Some(error) = &error {
+            eprintln!(
+                "WebSocket stream error: {}; reconnecting in {:?}",
+                error, delay
+            );
+        } else {
+            delay = initial_delay;
+        }
+        let _ = events.send(LighterEvent::Disconnected { error });
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(max_delay);
+    }
+}
+
+/// Cancel all orders whenever the connection stays down for the grace period
+async fn cancel_on_disconnect(
+    tx: Arc<TxClient>,
+    mut connected: watch::Receiver<bool>,
+    switches: Switches,
+    events: broadcast::Sender<LighterEvent>,
+    config: DisconnectCancel,
+) -> Result<()> {
+    loop {
+        // An outage starts when an open connection ends
+        for up in [true, false] {
+            if !wait_connection(&mut connected, up).await {
+                return Ok(());
+            }
+        }
+
+        let reconnected =
+            tokio::time::timeout(config.grace, wait_connection(&mut connected, true)).await;
+        if reconnected.is_ok() {
+            continue;
+        }
+
+        let outcome = submit_disconnect_cancel(&tx, &connected, &switches, config).await;
+        match &outcome {
+            DisconnectCancelOutcome::Submitted(response) if response.code == 200 => {}
+            outcome => eprintln!("Cancel-all on disconnect: {:?}", outcome),
+        }
+        let _ = events.send(LighterEvent::DisconnectCancel { outcome });
+    }
+}
+
+/// Wait until the connection is `up`; false once the client is gone
+async fn wait_connection(connected: &mut watch::Receiver<bool>, up: bool) -> bool {
+    connected.wait_for(|state| *state == up).await.is_ok()
+}
+
+async fn submit_disconnect_cancel(
+    tx: &TxClient,
+    connected: &watch::Receiver<bool>,
+    switches: &Switches,
+    config: DisconnectCancel,
+) -> DisconnectCancelOutcome {
+    if *connected.borrow() {
+        return DisconnectCancelOutcome::Reconnected;
+    }
+    if !config.when_switch_armed {
+        let now_ms = tx.now_ms();
+        let armed = switches
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|switch| switch.status.lock().unwrap().cancel_at_ms)
+            .filter(|&at| at > now_ms)
+            .min();
+        if let Some(cancel_at_ms) = armed {
+            return DisconnectCancelOutcome::SuppressedBySwitch { cancel_at_ms };
+        }
+    }
+
+    let req = CancelAllOrdersTxReq {
+        time_in_force: CANCEL_ALL_IMMEDIATE,
+        time: 0,
+    };
+    let signed = match tx.cancel_all_orders(&req, None).await {
+        Ok(signed) => signed,
+        Err(e) => return DisconnectCancelOutcome::Failed(e.to_string()),
+    };
+    // Signing may fetch a nonce, long enough for a reconnect to land
+    if *connected.borrow() {
+        return DisconnectCancelOutcome::Reconnected;
+    }
+    match tx.send_transaction(&signed).await {
+        Ok(response) => DisconnectCancelOutcome::Submitted(response),
+        Err(e) => DisconnectCancelOutcome::Failed(e.to_string()),
+    }
+}
+
+/// Shuts down a [`LighterClient`]
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Inner>,
+}
+
+impl ShutdownHandle {
+    /// Stop the client's background tasks in dependency order and wait for
+    /// them
+    ///
+    /// New orders are refused first, then the recorder is flushed and
+    /// closed, dead man's switches stop renewing and are disarmed if
+    /// configured, and finally the stream is closed. Each step waits up to
+    /// the [shutdown timeout](LighterClientBuilder::shutdown_timeout);
+    /// tasks that fail or overrun are in the report. The stream is not
+    /// restarted afterwards; other signing and REST calls keep working.
+    pub async fn shutdown(self) -> ShutdownReport {
+        let inner = &self.inner;
+        inner.closed.store(true, Ordering::SeqCst);
+        let mut report = ShutdownReport::default();
+
+        let recording = std::mem::replace(&mut *inner.recording.lock().unwrap(), Recording::Off);
+        match recording {
+            Recording::Running(tasks) => report.merge(tasks.shutdown(inner.shutdown_timeout).await),
+            Recording::Ready(recorder, _) => {
+                if let Err(e) = recorder.close() {
+                    report.fail("recorder", StopFailure::Failed(e.to_string()));
+                }
+            }
+            Recording::Off => {}
+        }
+
+        let switches = inner.switches.lock().unwrap().clone();
+        for switch in &switches {
+            report.merge(stop_renewals(&switch.renewals, inner.shutdown_timeout).await);
+        }
+        if inner.disarm_switches_on_shutdown {
+            if let Err(e) = disarm_switches(&inner.tx, &switches).await {
+                report.fail("dead_mans_switch", StopFailure::Failed(e.to_string()));
+            }
+        }
+
+        let stream = inner.stream.lock().unwrap().take();
+        if let Some(tasks) = stream {
+            report.merge(tasks.shutdown(inner.shutdown_timeout).await);
+        }
+        report
+    }
+}
+
+/// Abort the scheduled cancel-all if a switch still has one pending
+async fn disarm_switches(tx: &TxClient, switches: &[Switch]) -> Result<()> {
+    let now_ms = tx.now_ms();
+    let armed = switches.iter().any(|switch| {
+        switch
+            .status
+            .lock()
+            .unwrap()
+            .cancel_at_ms
+            .is_some_and(|at| at > now_ms)
+    });
+    if !armed {
+        return Ok(());
+    }
+
+    let response = tx.abort_scheduled_cancel_all().await?;
+    if response.code != 200 {
+        return Err(LighterError::ApiError(format!(
+            "Scheduled cancel-all abort rejected with code {}: {}",
+            response.code,
+            response.message.unwrap_or_default()
+        )));
+    }
+    for switch in switches {
+        switch.status.lock().unwrap().cancel_at_ms = None;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{
+        order_params, spawn_flapping_ws_server, spawn_mock_ws_server, TEST_KEY,
+    };
+    use crate::types::Side;
+
+    fn test_client(api_url: String, ws_addr: std::net::SocketAddr) -> LighterClient {
+        test_builder(api_url, ws_addr).build().unwrap()
+    }
+
+    fn test_builder(api_url: String, ws_addr: std::net::SocketAddr) -> LighterClientBuilder {
+        LighterClient::builder(
+            Network::Custom {
+                api_url,
+                ws_url: format!("ws://{}/stream", ws_addr),
+                chain_id: 300,
+            },
+            Credentials::new(
+                TEST_KEY,
+                AccountIndex::new(7).unwrap(),
+                ApiKeyIndex::new(0).unwrap(),
+            ),
+        )
+        .markets(vec![0])
+        .reconnect_delay(Duration::from_millis(20), Duration::from_millis(50))
+    }
+
+    async fn mock_http() -> mockito::ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":1}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200,"tx_hash":"0xfeed"}"#)
+            .create_async()
+            .await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_place_limit_tracked_through_stream() {
+        let http = mock_http().await;
+        let frames = vec![
+            r#"{"type":"subscribed/account_all","channel":"account_all:7","orders":{}}"#
+                .to_string(),
+            r#"{"type":"update/account_all","channel":"account_all:7","orders":{"0":[{"order_index":281474976710700,"client_order_index":55,"market_index":0,"is_ask":false,"price":"30","remaining_base_amount":"1"}]}}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[{"price":"29","size":"1"}]}}"#.to_string(),
+        ];
+        let ws_addr = spawn_mock_ws_server(frames).await;
+        let client = test_client(http.url(), ws_addr);
+
+        let placed = client
+            .place_limit(order_params(0, Side::Buy, 1_000, 300_000).client_order_index(55))
+            .await
+            .unwrap();
+        assert_eq!(placed.client_order_index, 55);
+        assert_eq!(placed.response.tx_hash.as_deref(), Some("0xfeed"));
+        assert_eq!(
+            client.order_tracker().get(55).unwrap().status,
+            OrderStatus::Pending
+        );
+
+        let mut events = client.events();
+        let mut saw_book = false;
+        let mut saw_placed = false;
+        while !(saw_book && saw_placed) {
+            match tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap()
+            {
+                LighterEvent::OrderBook { market_id, .. } => saw_book = market_id == "0",
+                LighterEvent::Account { event, .. } => {
+                    saw_placed = matches!(event, AccountEvent::OrderPlaced { .. })
+                }
+                _ => {}
+            }
+        }
+
+        let order = client.order_tracker().get(55).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.order_index, Some(281474976710700));
+        let latency = client.latency_stats();
+        assert_eq!((latency.count, latency.timed_out), (1, 0));
+        assert!(client.order_book(0).await.is_some());
+        assert!(client.account().await.is_some());
+        let view = client.market_view(0).await.unwrap();
+        assert!(view.book.is_some());
+        assert_eq!(view.my_orders.len(), 1);
+        assert!(client.market_view(1).await.is_none());
+
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_place_limit_rejection_and_cancel_all() {
+        let mut http = mockito::Server::new_async().await;
+        http.mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":1}"#)
+            .create_async()
+            .await;
+        http.mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":400,"message":"invalid nonce"}"#)
+            .create_async()
+            .await;
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let client = test_client(http.url(), ws_addr);
+
+        let result = client
+            .place_limit(order_params(0, Side::Sell, 1_000, 300_000))
+            .await;
+        assert!(matches!(result, Err(LighterError::ApiError(_))));
+        let rejected = client.order_tracker().open_orders();
+        assert!(rejected.is_empty());
+
+        let response = client.cancel_all().await.unwrap();
+        assert_eq!(response.code, 400);
+    }
+
+    /// Apply `event` once the order with `client_order_index` is tracked
+    fn inject_after_register(client: &LighterClient, client_order_index: i64, event: AccountEvent) {
+        let client = client.clone();
+        tokio::spawn(async move {
+            while client.order_tracker().get(client_order_index).is_none() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            client.order_tracker().apply(&event);
+        });
+    }
+
+    #[tokio::test]
+    async fn test_place_and_await_open() {
+        let http = mock_http().await;
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let client = test_client(http.url(), ws_addr);
+
+        inject_after_register(
+            &client,
+            61,
+            AccountEvent::OrderPlaced {
+                market: 0,
+                order_index: 4200,
+                client_order_index: 61,
+            },
+        );
+        let placed = client
+            .place_and_await_open(
+                order_params(0, Side::Buy, 1_000, 300_000).client_order_index(61),
+                OrderKind::Limit,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            placed,
+            PlacedOrder {
+                client_order_index: 61,
+                order_index: 4200,
+                status: OrderStatus::Open,
+                fills_so_far: Decimal::ZERO,
+                tx_hash: Some("0xfeed".to_string()),
+            }
+        );
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_place_and_await_open_rejections() {
+        let http = mock_http().await;
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let client = test_client(http.url(), ws_addr);
+
+        // Rejected in the stream: resolves well before the timeout
+        inject_after_register(
+            &client,
+            62,
+            AccountEvent::OrderRejected {
+                market: 0,
+                order_index: 4201,
+                client_order_index: 62,
+                reason: "canceled-post-only".to_string(),
+            },
+        );
+        let started = std::time::Instant::now();
+        let err = client
+            .place_and_await_open(
+                order_params(0, Side::Buy, 1_000, 300_000).client_order_index(62),
+                OrderKind::Limit,
+                Duration::from_secs(30),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("canceled-post-only"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Nothing arrives: the caller gives up and the waiter is released
+        let pending = client.place_and_await_open(
+            order_params(0, Side::Buy, 1_000, 300_000).client_order_index(63),
+            OrderKind::Limit,
+            Duration::from_secs(30),
+        );
+        assert!(tokio::time::timeout(Duration::from_millis(200), pending)
+            .await
+            .is_err());
+        assert_eq!(client.order_tracker().waiters(), 0);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_stream_and_clones_share_state() {
+        let http = mock_http().await;
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let client = test_client(http.url(), ws_addr);
+        let clone = client.clone();
+
+        let mut events = clone.events();
+        // The mock closes immediately, so the stream reports a disconnect
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(event, LighterEvent::Disconnected { .. }));
+
+        tokio::time::timeout(Duration::from_secs(5), client.shutdown_handle().shutdown())
+            .await
+            .unwrap();
+        assert!(clone.inner.stream.lock().unwrap().is_none());
+
+        // Stream access after shutdown doesn't restart it
+        clone.order_book(0).await;
+        assert!(client.inner.stream.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_components_in_order_without_leaks() {
+        use crate::constants::{CANCEL_ALL_ABORT_SCHEDULED, CANCEL_ALL_SCHEDULED};
+        use crate::recorder::{RecordingReader, RotationPolicy};
+
+        let cancel_all = |time_in_force: u8| {
+            mockito::Matcher::Regex(format!(r#"time_in_force\\":{}[,}}]"#, time_in_force))
+        };
+        let mut http = mockito::Server::new_async().await;
+        http.mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":1}"#)
+            .create_async()
+            .await;
+        http.mock("POST", "/api/v1/sendTx")
+            .match_body(cancel_all(CANCEL_ALL_SCHEDULED))
+            .with_body(r#"{"code":200,"tx_hash":"0xfeed"}"#)
+            .create_async()
+            .await;
+        let abort = http
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(cancel_all(CANCEL_ALL_ABORT_SCHEDULED))
+            .with_body(r#"{"code":200,"tx_hash":"0xdead"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let frames = vec![
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[{"price":"29","size":"1"}]}}"#.to_string(),
+        ];
+        let ws_addr = spawn_mock_ws_server(frames).await;
+        let dir =
+            std::env::temp_dir().join(format!("lighter-rs-client-shutdown-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let client = test_builder(http.url(), ws_addr)
+            .cancel_all_on_disconnect(true)
+            .disconnect_cancel_grace(Duration::from_secs(60))
+            .recorder(MarketRecorder::new(&dir, RotationPolicy::Hourly).unwrap())
+            .disarm_switches_on_shutdown(true)
+            .build()
+            .unwrap();
+
+        let switch = client
+            .spawn_dead_mans_switch(Duration::from_secs(300), Duration::from_secs(600))
+            .unwrap();
+        let mut events = client.events();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while switch.status().cancel_at_ms.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            while !matches!(events.recv().await, Ok(LighterEvent::OrderBook { .. })) {}
+        })
+        .await
+        .unwrap();
+
+        let report = tokio::time::timeout(Duration::from_secs(5), client.shutdown())
+            .await
+            .unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+        let mut stopped = report.stopped.clone();
+        stopped.sort();
+        assert_eq!(
+            stopped,
+            vec![
+                "dead_mans_switch",
+                "disconnect_cancel",
+                "recorder",
+                "stream"
+            ]
+        );
+        abort.assert_async().await;
+        assert_eq!(switch.status().cancel_at_ms, None);
+
+        // Every task set is drained
+        assert!(client.inner.stream.lock().unwrap().is_none());
+        assert!(matches!(
+            *client.inner.recording.lock().unwrap(),
+            Recording::Off
+        ));
+        assert!(switch.shared_renewals().lock().unwrap().is_none());
+        assert_eq!(client.shutdown().await, ShutdownReport::default());
+
+        // The recording was flushed and closed with the book frame in it
+        assert!(RecordingReader::open(&dir)
+            .unwrap()
+            .messages()
+            .any(|m| m.unwrap().text.contains("order_book:0")));
+        assert!(matches!(
+            client
+                .place_limit(order_params(0, Side::Buy, 1_000, 300_000))
+                .await,
+            Err(LighterError::ShutDown)
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    async fn next_disconnect_cancel(
+        events: &mut broadcast::Receiver<LighterEvent>,
+        within: Duration,
+    ) -> Option<DisconnectCancelOutcome> {
+        tokio::time::timeout(within, async {
+            loop {
+                if let LighterEvent::DisconnectCancel { outcome } = events.recv().await.unwrap() {
+                    return outcome;
+                }
+            }
+        })
+        .await
+        .ok()
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_on_disconnect_after_grace() {
+        let http = mock_http().await;
+        // One connection, then the server is gone
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let grace = Duration::from_millis(200);
+        let client = test_builder(http.url(), ws_addr)
+            .cancel_all_on_disconnect(true)
+            .disconnect_cancel_grace(grace)
+            .build()
+            .unwrap();
+
+        let mut events = client.events();
+        let outcome = next_disconnect_cancel(&mut events, Duration::from_secs(5))
+            .await
+            .unwrap();
+        match outcome {
+            DisconnectCancelOutcome::Submitted(response) => {
+                assert_eq!(response.tx_hash.as_deref(), Some("0xfeed"))
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        assert!(!client.ws_client().is_connected());
+
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancel_waits_out_grace() {
+        let mut http = mockito::Server::new_async().await;
+        let send = http
+            .mock("POST", "/api/v1/sendTx")
+            .expect(0)
+            .create_async()
+            .await;
+        // The first connection drops and the reconnect holds
+        let ws_addr = spawn_flapping_ws_server().await;
+        let client = test_builder(http.url(), ws_addr)
+            .cancel_all_on_disconnect(true)
+            .disconnect_cancel_grace(Duration::from_secs(1))
+            .build()
+            .unwrap();
+
+        let mut events = client.events();
+        assert!(
+            next_disconnect_cancel(&mut events, Duration::from_millis(1500))
+                .await
+                .is_none()
+        );
+        assert!(client.ws_client().is_connected());
+        send.assert_async().await;
+
+        client.shutdown().await;
+        assert!(!client.ws_client().is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancel_suppressed_by_armed_switch() {
+        let mut http = mockito::Server::new_async().await;
+        http.mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":1}"#)
+            .create_async()
+            .await;
+        // Only the switch's renewal goes out
+        let send = http
+            .mock("POST", "/api/v1/sendTx")
+            .with_body(r#"{"code":200,"tx_hash":"0xfeed"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let ws_addr = spawn_mock_ws_server(vec![]).await;
+        let client = test_builder(http.url(), ws_addr)
+            .cancel_all_on_disconnect(true)
+            .disconnect_cancel_grace(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let switch = client
+            .spawn_dead_mans_switch(Duration::from_secs(300), Duration::from_secs(600))
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while switch.status().cancel_at_ms.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut events = client.events();
+        let outcome = next_disconnect_cancel(&mut events, Duration::from_secs(5))
+            .await
+            .unwrap();
+        match outcome {
+            DisconnectCancelOutcome::SuppressedBySwitch { cancel_at_ms } => {
+                assert_eq!(Some(cancel_at_ms), switch.status().cancel_at_ms)
+            }
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+        send.assert_async().await;
+
+        drop(switch);
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_cancel_skipped_after_late_reconnect() {
+        let mut http = mockito::Server::new_async().await;
+        let send = http
+            .mock("POST", "/api/v1/sendTx")
+            .expect(0)
+            .create_async()
+            .await;
+        let client = test_client(http.url(), "127.0.0.1:9".parse().unwrap());
+        let config = DisconnectCancel {
+            grace: Duration::ZERO,
+            when_switch_armed: false,
+        };
+
+        let (_, connected) = watch::channel(true);
+        let outcome =
+            submit_disconnect_cancel(&client.inner.tx, &connected, &Switches::default(), config)
+                .await;
+        assert!(matches!(outcome, DisconnectCancelOutcome::Reconnected));
+        send.assert_async().await;
+    }
+}
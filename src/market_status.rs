@@ -0,0 +1,222 @@
+//! Trading status of markets and gating of new orders
+//!
+//! A halted market rejects new orders, so sending them only burns rate
+//! limit. [`MarketStatuses`] holds the latest [`MarketStatus`] of each
+//! market, filled from the `market_stats` channel by
+//! [`WsClientBuilder::market_stats`](crate::ws_client::WsClientBuilder::market_stats)
+//! or from REST with
+//! [`HTTPClient::get_market_status`](crate::client::HTTPClient::get_market_status).
+//! Given to [`TxClient::set_market_statuses`](crate::client::TxClient::set_market_statuses),
+//! it fails new orders in markets that won't take them with
+//! [`LighterError::MarketNotActive`] before anything is signed.
+//!
+//! ```
+//! use lighter_rs::market_status::{MarketStatus, MarketStatuses};
+//!
+//! let statuses = MarketStatuses::new();
+//! statuses.set(0, MarketStatus::ReduceOnly);
+//! assert!(statuses.check(0, true).is_ok());
+//! assert!(statuses.check(0, false).is_err());
+//! // Markets without a known status aren't gated
+//! assert!(statuses.check(1, false).is_ok());
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::errors::{LighterError, Result};
+
+/// Whether a market takes new orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MarketStatus {
+    Active,
+    /// No new orders, e.g. during an auction
+    Halted,
+    /// Only orders that shrink a position
+    ReduceOnly,
+    /// Delisted or not yet open
+    Closed,
+}
+
+impl MarketStatus {
+    /// Whether a new order, reduce-only or not, is accepted
+    pub fn accepts(self, reduce_only: bool) -> bool {
+        match self {
+            MarketStatus::Active => true,
+            MarketStatus::ReduceOnly => reduce_only,
+            MarketStatus::Halted | MarketStatus::Closed => false,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MarketStatus::Active => "active",
+            MarketStatus::Halted => "halted",
+            MarketStatus::ReduceOnly => "reduce_only",
+            MarketStatus::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for MarketStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MarketStatus {
+    type Err = LighterError;
+
+    /// Parse a status as the API sends it; `paused` and `auction` read as
+    /// [`Halted`](MarketStatus::Halted), `inactive` as
+    /// [`Closed`](MarketStatus::Closed)
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            "active" => Ok(MarketStatus::Active),
+            "halted" | "paused" | "auction" => Ok(MarketStatus::Halted),
+            "reduce_only" => Ok(MarketStatus::ReduceOnly),
+            "closed" | "inactive" => Ok(MarketStatus::Closed),
+            _ => Err(LighterError::InvalidResponse(format!(
+                "Unknown market status {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl Serialize for MarketStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MarketStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A market's status changed, or was seen for the first time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketStatusChange {
+    pub market_id: u32,
+    pub previous: Option<MarketStatus>,
+    pub status: MarketStatus,
+}
+
+/// Latest status of each market, cheap to clone and shared by clones
+#[derive(Debug, Clone, Default)]
+pub struct MarketStatuses {
+    by_market: Arc<Mutex<HashMap<u32, MarketStatus>>>,
+}
+
+impl MarketStatuses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Status of a market, `None` until one is known
+    pub fn get(&self, market_id: u32) -> Option<MarketStatus> {
+        self.by_market.lock().unwrap().get(&market_id).copied()
+    }
+
+    /// Record a market's status; returns the change unless it is the same
+    pub fn set(&self, market_id: u32, status: MarketStatus) -> Option<MarketStatusChange> {
+        let previous = self.by_market.lock().unwrap().insert(market_id, status);
+        (previous != Some(status)).then_some(MarketStatusChange {
+            market_id,
+            previous,
+            status,
+        })
+    }
+
+    /// Fail with [`LighterError::MarketNotActive`] if the market is known
+    /// not to accept a new order
+    pub fn check(&self, market_id: u32, reduce_only: bool) -> Result<()> {
+        match self.get(market_id) {
+            Some(status) if !status.accepts(reduce_only) => {
+                Err(LighterError::MarketNotActive(status))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Status in a `market_stats` message, if it carries one
+pub(crate) fn parse_stats(message: &Value) -> Result<Option<MarketStatus>> {
+    match message
+        .get("market_stats")
+        .and_then(|stats| stats.get("status"))
+    {
+        None | Some(Value::Null) => Ok(None),
+        Some(status) => Ok(Some(MarketStatus::deserialize(status)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_gating_per_status() {
+        let statuses = MarketStatuses::new();
+        for (status, plain, reduce_only) in [
+            (MarketStatus::Active, true, true),
+            (MarketStatus::ReduceOnly, false, true),
+            (MarketStatus::Halted, false, false),
+            (MarketStatus::Closed, false, false),
+        ] {
+            statuses.set(3, status);
+            assert_eq!(statuses.check(3, false).is_ok(), plain, "{}", status);
+            assert_eq!(statuses.check(3, true).is_ok(), reduce_only, "{}", status);
+        }
+        assert!(matches!(
+            statuses.check(3, true),
+            Err(LighterError::MarketNotActive(MarketStatus::Closed))
+        ));
+        assert!(statuses.check(4, false).is_ok());
+    }
+
+    #[test]
+    fn test_set_reports_transitions_only() {
+        let statuses = MarketStatuses::new();
+        let shared = statuses.clone();
+        assert_eq!(
+            statuses.set(1, MarketStatus::Active),
+            Some(MarketStatusChange {
+                market_id: 1,
+                previous: None,
+                status: MarketStatus::Active
+            })
+        );
+        assert_eq!(statuses.set(1, MarketStatus::Active), None);
+        let change = statuses.set(1, MarketStatus::Halted).unwrap();
+        assert_eq!(change.previous, Some(MarketStatus::Active));
+        assert_eq!(shared.get(1), Some(MarketStatus::Halted));
+    }
+
+    #[test]
+    fn test_parse_stats() {
+        let status = |stats: Value| parse_stats(&json!({ "market_stats": stats }));
+        assert_eq!(
+            status(json!({"market_id": 0, "status": "reduce-only"})).unwrap(),
+            Some(MarketStatus::ReduceOnly)
+        );
+        assert_eq!(
+            status(json!({"status": "AUCTION"})).unwrap(),
+            Some(MarketStatus::Halted)
+        );
+        assert_eq!(status(json!({"index_price": "1"})).unwrap(), None);
+        assert!(status(json!({"status": "melting"})).is_err());
+        assert_eq!(
+            serde_json::to_value(MarketStatus::ReduceOnly).unwrap(),
+            json!("reduce_only")
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Lighter network endpoints
+
+use crate::types::SignatureEncoding;
+
+/// Lighter deployment to connect to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Network {
+    /// Mainnet (chain id 304)
+    Mainnet,
+    /// Testnet (chain id 300)
+    Testnet,
+    /// Custom deployment, e.g. a local simulator
+    Custom {
+        /// REST base URL, e.g. `http://127.0.0.1:8080`
+        api_url: String,
+        /// Full WebSocket URL, e.g. `ws://127.0.0.1:8080/stream`
+        ws_url: String,
+        chain_id: u32,
+    },
+}
+
+impl Network {
+    /// REST base URL
+    pub fn api_url(&self) -> &str {
+        match self {
+            Network::Mainnet => "https://mainnet.zklighter.elliot.ai",
+            Network::Testnet => "https://api-testnet.lighter.xyz",
+            Network::Custom { api_url, .. } => api_url,
+        }
+    }
+
+    /// Full WebSocket stream URL
+    pub fn ws_url(&self) -> String {
+        match self {
+            Network::Mainnet => "wss://mainnet.zklighter.elliot.ai/stream".to_string(),
+            Network::Testnet => "wss://api-testnet.lighter.xyz/stream".to_string(),
+            Network::Custom { ws_url, .. } => ws_url.clone(),
+        }
+    }
+
+    /// Chain id used when signing
+    pub fn chain_id(&self) -> u32 {
+        match self {
+            Network::Mainnet => 304,
+            Network::Testnet => 300,
+            Network::Custom { chain_id, .. } => *chain_id,
+        }
+    }
+
+    /// How transaction signatures are written for this deployment
+    ///
+    /// The default byte array everywhere until a captured payload confirms
+    /// a string encoding (see [`SignatureEncoding`]); custom deployments are
+    /// assumed to mirror mainnet and testnet.
+    pub fn signature_encoding(&self) -> SignatureEncoding {
+        SignatureEncoding::ByteArray
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_endpoints() {
+        assert_eq!(Network::Mainnet.chain_id(), 304);
+        assert_eq!(Network::Testnet.chain_id(), 300);
+        assert_eq!(
+            Network::Testnet.ws_url(),
+            "wss://api-testnet.lighter.xyz/stream"
+        );
+
+        let custom = Network::Custom {
+            api_url: "http://127.0.0.1:1".to_string(),
+            ws_url: "ws://127.0.0.1:2/stream".to_string(),
+            chain_id: 7,
+        };
+        assert_eq!(custom.api_url(), "http://127.0.0.1:1");
+        assert_eq!(custom.ws_url(), "ws://127.0.0.1:2/stream");
+        assert_eq!(custom.chain_id(), 7);
+        assert_eq!(custom.signature_encoding(), SignatureEncoding::ByteArray);
+    }
+}
@@ -0,0 +1,546 @@
+//! Order flow metrics of one market
+//!
+//! [`OrderFlowAnalyzer`] consumes order books, book deltas and trades in
+//! delivery order and after each input reports [`FlowMetrics`]: signed
+//! traded volume over a rolling window, an exponentially weighted pressure,
+//! how often levels near the top are removed, and trade-throughs, where the
+//! best bid or ask is fully consumed by trades within a short window.
+//!
+//! Metrics only use inputs already delivered; windows end at the time of the
+//! latest input. [`subscribe`](OrderFlowAnalyzer::subscribe) feeds books from
+//! a [`WsClient`]; trades come from whatever source the caller has and are
+//! passed to [`on_trade`](OrderFlowAnalyzer::on_trade).
+
+use chrono::{DateTime, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::types::Side;
+use crate::ws_client::{OrderBook, PriceLevel, WsClient};
+
+/// Windows of an [`OrderFlowAnalyzer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderFlowConfig {
+    /// Window of traded volume and level removals
+    pub window: Duration,
+    /// Levels from the top of each side whose removal is counted
+    pub top_levels: usize,
+    /// Trades consuming the best level within this window are a trade-through
+    pub trade_through_window: Duration,
+    /// Halflife of [`FlowMetrics::pressure`]
+    pub pressure_halflife: Duration,
+}
+
+impl Default for OrderFlowConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(10),
+            top_levels: 5,
+            trade_through_window: Duration::from_secs(1),
+            pressure_halflife: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A public trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlowTrade {
+    pub price: Decimal,
+    pub size: Decimal,
+    /// Side of the taker; buys consume asks and sells consume bids
+    pub aggressor: Side,
+}
+
+/// The best level of a side was consumed by trades
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TradeThrough {
+    /// Side of the book that was consumed
+    pub side: Side,
+    pub price: Decimal,
+    /// Largest size shown at the level while it was being traded
+    pub shown: Decimal,
+    /// Volume traded at or through the level within the window
+    pub traded: Decimal,
+    pub at: DateTime<Utc>,
+}
+
+/// Metrics after one input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlowMetrics {
+    pub at: DateTime<Utc>,
+    /// Buy volume minus sell volume within the window
+    pub signed_volume: Decimal,
+    /// Signed traded volume, exponentially decayed with the configured halflife
+    pub pressure: f64,
+    /// Bid levels near the top removed within the window
+    pub bid_removals: usize,
+    /// Ask levels near the top removed within the window
+    pub ask_removals: usize,
+    /// Bid removals per second over the window
+    pub bid_removal_rate: f64,
+    /// Ask removals per second over the window
+    pub ask_removal_rate: f64,
+    /// Trade-through detected by this input
+    pub trade_through: Option<TradeThrough>,
+}
+
+/// Trades against the current best level of one side
+#[derive(Debug, Clone)]
+struct Touch {
+    price: Decimal,
+    shown: Decimal,
+    trades: VecDeque<(DateTime<Utc>, Decimal)>,
+    fired: bool,
+}
+
+type TradeThroughCallback = Box<dyn FnMut(&TradeThrough) + Send>;
+
+/// Order flow state of one market
+pub struct OrderFlowAnalyzer {
+    config: OrderFlowConfig,
+    bids: BTreeMap<Decimal, Decimal>,
+    asks: BTreeMap<Decimal, Decimal>,
+    has_book: bool,
+    trades: VecDeque<(DateTime<Utc>, Decimal)>,
+    removals: VecDeque<(DateTime<Utc>, Side)>,
+    pressure: f64,
+    touches: [Option<Touch>; 2],
+    last_at: Option<DateTime<Utc>>,
+    callback: Option<TradeThroughCallback>,
+    clock: SharedClock,
+}
+
+impl std::fmt::Debug for OrderFlowAnalyzer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OrderFlowAnalyzer")
+            .field("config", &self.config)
+            .field("bid_levels", &self.bids.len())
+            .field("ask_levels", &self.asks.len())
+            .field("pressure", &self.pressure)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OrderFlowAnalyzer {
+    /// Analyzer without a book yet
+    pub fn new(config: OrderFlowConfig) -> Self {
+        Self {
+            config,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            has_book: false,
+            trades: VecDeque::new(),
+            removals: VecDeque::new(),
+            pressure: 0.0,
+            touches: [None, None],
+            last_at: None,
+            callback: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Take the time of updates without an explicit one from `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Call `callback` on every trade-through
+    pub fn on_trade_through<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&TradeThrough) + Send + 'static,
+    {
+        self.callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Feed order book updates of `market_id` from `ws` into a shared analyzer
+    pub fn subscribe(self, ws: &WsClient, market_id: u32) -> Arc<Mutex<OrderFlowAnalyzer>> {
+        let analyzer = Arc::new(Mutex::new(self));
+        let shared = analyzer.clone();
+        ws.on_order_book(market_id, move |_, book| {
+            shared.lock().unwrap().on_book(&book);
+        });
+        analyzer
+    }
+
+    /// Replace the book with a full one received now
+    pub fn on_book(&mut self, book: &OrderBook) -> FlowMetrics {
+        self.on_book_at(book, self.clock.now())
+    }
+
+    /// Replace the book with a full one; levels near the top that are gone
+    /// count as removed
+    pub fn on_book_at(&mut self, book: &OrderBook, at: DateTime<Utc>) -> FlowMetrics {
+        let at = self.advance(at);
+        let bids = levels_by_price(&book.bids);
+        let asks = levels_by_price(&book.asks);
+        if self.has_book {
+            for side in [Side::Buy, Side::Sell] {
+                let new = match side {
+                    Side::Buy => &bids,
+                    Side::Sell => &asks,
+                };
+                let removed = self
+                    .top_prices(side)
+                    .into_iter()
+                    .filter(|price| !new.contains_key(price))
+                    .count();
+                self.removals
+                    .extend(std::iter::repeat_n((at, side), removed));
+            }
+        }
+        self.bids = bids;
+        self.asks = asks;
+        self.has_book = true;
+        self.observe_touches();
+        self.metrics(at, None)
+    }
+
+    /// Apply an order book delta received now
+    pub fn on_delta(&mut self, delta: &OrderBook) -> FlowMetrics {
+        self.on_delta_at(delta, self.clock.now())
+    }
+
+    /// Apply an order book delta; levels sent with size zero are removed
+    pub fn on_delta_at(&mut self, delta: &OrderBook, at: DateTime<Utc>) -> FlowMetrics {
+        let at = self.advance(at);
+        for (side, levels) in [(Side::Buy, &delta.bids), (Side::Sell, &delta.asks)] {
+            let top = self.top_prices(side);
+            let book = match side {
+                Side::Buy => &mut self.bids,
+                Side::Sell => &mut self.asks,
+            };
+            for level in levels {
+                let (Ok(price), Ok(size)) = (
+                    Decimal::from_str(&level.price),
+                    Decimal::from_str(&level.size),
+                ) else {
+                    continue;
+                };
+                if size > Decimal::ZERO {
+                    book.insert(price, size);
+                } else if book.remove(&price).is_some() && top.contains(&price) {
+                    self.removals.push_back((at, side));
+                }
+            }
+        }
+        self.observe_touches();
+        self.metrics(at, None)
+    }
+
+    /// Record a trade delivered now
+    pub fn on_trade(&mut self, trade: &FlowTrade) -> FlowMetrics {
+        self.on_trade_at(trade, self.clock.now())
+    }
+
+    /// Record a trade
+    pub fn on_trade_at(&mut self, trade: &FlowTrade, at: DateTime<Utc>) -> FlowMetrics {
+        let at = self.advance(at);
+        let signed = match trade.aggressor {
+            Side::Buy => trade.size,
+            Side::Sell => -trade.size,
+        };
+        self.trades.push_back((at, signed));
+        self.pressure += signed.to_f64().unwrap_or(0.0);
+
+        let fired = self.trade_through(trade, at);
+        if let (Some(fired), Some(callback)) = (&fired, &mut self.callback) {
+            callback(fired);
+        }
+        self.metrics(at, fired)
+    }
+
+    /// Best price of a side, if any
+    pub fn best(&self, side: Side) -> Option<(Decimal, Decimal)> {
+        match side {
+            Side::Buy => self.bids.iter().next_back(),
+            Side::Sell => self.asks.iter().next(),
+        }
+        .map(|(price, size)| (*price, *size))
+    }
+
+    /// Move the clock to `at`, never backwards, and age the windows
+    fn advance(&mut self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let at = self.last_at.map_or(at, |last| last.max(at));
+        if let Some(last) = self.last_at {
+            let dt = (at - last).to_std().unwrap_or_default().as_secs_f64();
+            let halflife = self.config.pressure_halflife.as_secs_f64();
+            self.pressure *= if halflife > 0.0 {
+                0.5f64.powf(dt / halflife)
+            } else {
+                0.0
+            };
+        }
+        self.last_at = Some(at);
+
+        let window =
+            chrono::Duration::from_std(self.config.window).unwrap_or(chrono::Duration::MAX);
+        while self.trades.front().is_some_and(|(t, _)| at - *t > window) {
+            self.trades.pop_front();
+        }
+        while self.removals.front().is_some_and(|(t, _)| at - *t > window) {
+            self.removals.pop_front();
+        }
+        at
+    }
+
+    /// Prices of the top levels of a side, best first
+    fn top_prices(&self, side: Side) -> Vec<Decimal> {
+        let n = self.config.top_levels;
+        match side {
+            Side::Buy => self.bids.keys().rev().take(n).copied().collect(),
+            Side::Sell => self.asks.keys().take(n).copied().collect(),
+        }
+    }
+
+    /// Raise the shown size of touched levels that grew
+    fn observe_touches(&mut self) {
+        for side in [Side::Buy, Side::Sell] {
+            let best = self.best(side);
+            if let Some(touch) = &mut self.touches[side_slot(side)] {
+                if let Some((_, size)) = best.filter(|(price, _)| *price == touch.price) {
+                    touch.shown = touch.shown.max(size);
+                }
+            }
+        }
+    }
+
+    /// Track a trade against the best level it hits; `Some` once the level
+    /// is consumed
+    fn trade_through(&mut self, trade: &FlowTrade, at: DateTime<Utc>) -> Option<TradeThrough> {
+        let side = match trade.aggressor {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let (best, shown) = self.best(side)?;
+        let through = match side {
+            Side::Buy => trade.price < best,
+            Side::Sell => trade.price > best,
+        };
+
+        let slot = &mut self.touches[side_slot(side)];
+        let touch = match slot {
+            Some(touch) if touch.price == best => touch,
+            _ => slot.insert(Touch {
+                price: best,
+                shown,
+                trades: VecDeque::new(),
+                fired: false,
+            }),
+        };
+        let window = chrono::Duration::from_std(self.config.trade_through_window)
+            .unwrap_or(chrono::Duration::MAX);
+        while touch.trades.front().is_some_and(|(t, _)| at - *t > window) {
+            touch.trades.pop_front();
+        }
+        touch.trades.push_back((at, trade.size));
+        let traded: Decimal = touch.trades.iter().map(|(_, size)| size).sum();
+        if touch.fired || (!through && traded < touch.shown) {
+            return None;
+        }
+        touch.fired = true;
+        Some(TradeThrough {
+            side,
+            price: touch.price,
+            shown: touch.shown,
+            traded,
+            at,
+        })
+    }
+
+    fn metrics(&self, at: DateTime<Utc>, trade_through: Option<TradeThrough>) -> FlowMetrics {
+        let removals = |side| self.removals.iter().filter(|(_, s)| *s == side).count();
+        let (bid_removals, ask_removals) = (removals(Side::Buy), removals(Side::Sell));
+        let secs = self.config.window.as_secs_f64();
+        let rate = |count: usize| if secs > 0.0 { count as f64 / secs } else { 0.0 };
+        FlowMetrics {
+            at,
+            signed_volume: self.trades.iter().map(|(_, signed)| signed).sum(),
+            pressure: self.pressure,
+            bid_removals,
+            ask_removals,
+            bid_removal_rate: rate(bid_removals),
+            ask_removal_rate: rate(ask_removals),
+            trade_through,
+        }
+    }
+}
+
+fn side_slot(side: Side) -> usize {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+/// Non-empty levels keyed by numeric price; unparsable levels are skipped
+fn levels_by_price(levels: &[PriceLevel]) -> BTreeMap<Decimal, Decimal> {
+    levels
+        .iter()
+        .filter_map(|level| {
+            let price = Decimal::from_str(&level.price).ok()?;
+            let size = Decimal::from_str(&level.size).ok()?;
+            (size > Decimal::ZERO).then_some((price, size))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(1_700_000_000_000 + millis).unwrap()
+    }
+
+    fn dec(text: &str) -> Decimal {
+        Decimal::from_str(text).unwrap()
+    }
+
+    fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|(price, size)| PriceLevel {
+                    price: price.to_string(),
+                    size: size.to_string(),
+                })
+                .collect()
+        };
+        OrderBook {
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    fn trade(price: &str, size: &str, aggressor: Side) -> FlowTrade {
+        FlowTrade {
+            price: dec(price),
+            size: dec(size),
+            aggressor,
+        }
+    }
+
+    fn config() -> OrderFlowConfig {
+        OrderFlowConfig {
+            window: Duration::from_secs(2),
+            top_levels: 2,
+            trade_through_window: Duration::from_millis(500),
+            pressure_halflife: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn test_scripted_flow() {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let seen = fired.clone();
+        let mut flow = OrderFlowAnalyzer::new(config())
+            .on_trade_through(move |t| seen.lock().unwrap().push(*t));
+
+        // Bids 99 / 98 / 97, asks 101 / 102 / 103
+        let m = flow.on_book_at(
+            &book(
+                &[("99", "3"), ("98", "5"), ("97", "5")],
+                &[("101", "2"), ("102", "4"), ("103", "4")],
+            ),
+            at(0),
+        );
+        assert_eq!(
+            (m.signed_volume, m.bid_removals, m.ask_removals),
+            (Decimal::ZERO, 0, 0)
+        );
+
+        // A buy of 1.5 at the ask: not yet through
+        let m = flow.on_trade_at(&trade("101", "1.5", Side::Buy), at(100));
+        assert_eq!(m.signed_volume, dec("1.5"));
+        assert_eq!(m.pressure, 1.5);
+        assert_eq!(m.trade_through, None);
+
+        // The rest of the ask within the window consumes it
+        let m = flow.on_trade_at(&trade("101", "0.5", Side::Buy), at(400));
+        assert_eq!(m.signed_volume, dec("2"));
+        let through = m.trade_through.unwrap();
+        assert_eq!(
+            (through.side, through.price, through.shown, through.traded),
+            (Side::Sell, dec("101"), dec("2"), dec("2"))
+        );
+        // Fires once per level
+        let m = flow.on_trade_at(&trade("101", "0.1", Side::Buy), at(450));
+        assert_eq!(m.trade_through, None);
+        let pressure = m.pressure;
+        assert_eq!(fired.lock().unwrap().len(), 1);
+
+        // The delta removes the consumed ask (top) and a deep bid (not top)
+        let m = flow.on_delta_at(&book(&[("97", "0")], &[("101", "0")]), at(1_400));
+        assert_eq!((m.bid_removals, m.ask_removals), (0, 1));
+        assert_eq!(m.ask_removal_rate, 0.5);
+        // Decayed since the last trade, 0.95 halflives ago
+        assert!((m.pressure - pressure * 0.5f64.powf(0.95)).abs() < 1e-12);
+        assert_eq!(flow.best(Side::Sell), Some((dec("102"), dec("4"))));
+
+        // A full book without the best two bids: two top removals
+        let m = flow.on_book_at(
+            &book(&[("96", "1")], &[("102", "4"), ("103", "4")]),
+            at(1_500),
+        );
+        assert_eq!((m.bid_removals, m.ask_removals), (2, 1));
+        assert_eq!(m.bid_removal_rate, 1.0);
+
+        // Sells spread over longer than the window don't consume the bid
+        flow.on_trade_at(&trade("96", "0.6", Side::Sell), at(2_000));
+        let m = flow.on_trade_at(&trade("96", "0.6", Side::Sell), at(2_600));
+        assert_eq!(m.trade_through, None);
+        // Trades up to 400ms are out of the window, the removals still in it
+        assert_eq!(m.signed_volume, dec("-1.2"));
+        assert_eq!((m.bid_removals, m.ask_removals), (2, 1));
+
+        // A sell below the best bid is a trade-through on its own
+        let m = flow.on_trade_at(&trade("95", "0.1", Side::Sell), at(2_700));
+        let through = m.trade_through.unwrap();
+        assert_eq!((through.side, through.price), (Side::Buy, dec("96")));
+        assert_eq!(through.traded, dec("0.7"));
+
+        // Everything ages out
+        let m = flow.on_book_at(
+            &book(&[("96", "1")], &[("102", "4"), ("103", "4")]),
+            at(10_000),
+        );
+        assert_eq!(
+            (m.signed_volume, m.bid_removals, m.ask_removals),
+            (Decimal::ZERO, 0, 0)
+        );
+        assert_eq!(fired.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_no_look_ahead_and_growing_levels() {
+        let mut flow = OrderFlowAnalyzer::new(config());
+        // Trades before any book are counted but can't trade through
+        let m = flow.on_trade_at(&trade("100", "5", Side::Buy), at(0));
+        assert_eq!((m.signed_volume, m.trade_through), (dec("5"), None));
+
+        flow.on_book_at(&book(&[("99", "1")], &[("100", "1")]), at(10));
+        flow.on_trade_at(&trade("99", "0.5", Side::Sell), at(20));
+        let m = flow.on_trade_at(&trade("100", "0.5", Side::Buy), at(30));
+        assert_eq!(m.trade_through, None);
+        // The level grows while traded; the larger size has to be consumed
+        flow.on_delta_at(&book(&[], &[("100", "3")]), at(40));
+        let m = flow.on_trade_at(&trade("100", "2", Side::Buy), at(50));
+        assert_eq!(m.trade_through, None);
+        let m = flow.on_trade_at(&trade("100", "0.5", Side::Buy), at(60));
+        assert_eq!(
+            m.trade_through.map(|t| (t.shown, t.traded)),
+            Some((dec("3"), dec("3")))
+        );
+
+        // Out-of-order timestamps don't move the clock back
+        let m = flow.on_trade_at(&trade("100", "1", Side::Buy), at(0));
+        assert_eq!(m.at, at(60));
+    }
+}
@@ -0,0 +1,1049 @@
+//! Client-side tracking of submitted orders
+//!
+//! [`OrderTracker`] records orders by client order index when they are
+//! submitted and advances their status from the account events of the
+//! WebSocket stream. [`OrderTracker::wait_resolved`] lets callers wait for
+//! a submitted order to show up.
+//!
+//! No account event is guaranteed when an order reaches its `order_expiry`;
+//! [`OrderTracker::spawn_expiry_sweeper`] marks such orders by the clock.
+//!
+//! [`OrderIndexResolver`] only maps client order indices to the order
+//! indices the exchange assigns, for every order of the account, so orders
+//! can be modified or cancelled by the index the exchange knows.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+use crate::client::{HTTPClient, TxClient};
+use crate::constants::{
+    DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS, NIL_CLIENT_ORDER_INDEX, NIL_ORDER_EXPIRY,
+};
+use crate::errors::{LighterError, Result};
+use crate::tasks::{ShutdownReport, TaskSet};
+use crate::types::{
+    AccountIndex, CancelOrderTxReq, L2CancelOrderTxInfo, MarketIndex, TransactOpts,
+};
+use crate::ws_client::{AccountEvent, AccountSnapshot};
+
+/// Lifecycle state of a tracked order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    /// Accepted by `sendTx` but not yet seen in the account stream
+    Pending,
+    /// Resting on the book
+    Open,
+    /// Completely filled
+    Filled,
+    /// Cancelled, expired or otherwise removed without a full fill
+    Cancelled,
+    /// Rejected at submission
+    Rejected,
+    /// Past its `order_expiry` by the local clock, not yet confirmed gone
+    ExpiryPending,
+    /// Expired, as found by the expiry sweeper
+    Expired,
+}
+
+impl OrderStatus {
+    /// Whether the order can no longer change
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled
+                | OrderStatus::Cancelled
+                | OrderStatus::Rejected
+                | OrderStatus::Expired
+        )
+    }
+}
+
+/// Order known to the tracker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackedOrder {
+    pub market_index: u8,
+    pub client_order_index: i64,
+    /// Exchange-assigned index, known once the order shows up in the stream
+    pub order_index: Option<i64>,
+    pub status: OrderStatus,
+    /// Base amount filled so far
+    pub filled: Decimal,
+    pub tx_hash: Option<String>,
+    /// Reason given for a rejection
+    pub reject_reason: Option<String>,
+    /// Expiry in milliseconds, `None` if unknown or the order doesn't expire
+    #[serde(default)]
+    pub order_expiry: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    orders: HashMap<i64, TrackedOrder>,
+    by_order_index: HashMap<i64, i64>,
+    /// `(order_expiry, client_order_index)` of orders to sweep, earliest first
+    expiries: BTreeSet<(i64, i64)>,
+}
+
+/// Tracks submitted orders by client order index
+#[derive(Debug)]
+pub struct OrderTracker {
+    state: Mutex<TrackerState>,
+    /// Bumped after every change so waiters can re-check
+    changes: watch::Sender<()>,
+}
+
+impl Default for OrderTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderTracker {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(TrackerState::default()),
+            changes: watch::channel(()).0,
+        }
+    }
+
+    /// Record a submitted order as pending
+    pub fn register(&self, market_index: u8, client_order_index: i64, tx_hash: Option<String>) {
+        self.state.lock().unwrap().orders.insert(
+            client_order_index,
+            TrackedOrder {
+                market_index,
+                client_order_index,
+                order_index: None,
+                status: OrderStatus::Pending,
+                filled: Decimal::ZERO,
+                tx_hash,
+                reject_reason: None,
+                order_expiry: None,
+            },
+        );
+        self.changes.send_replace(());
+    }
+
+    /// Record the `order_expiry` an order was signed with
+    ///
+    /// Orders with [`NIL_ORDER_EXPIRY`] or any other non-positive expiry are
+    /// never swept.
+    pub fn set_order_expiry(&self, client_order_index: i64, order_expiry: i64) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let Some(order) = state.orders.get_mut(&client_order_index) else {
+            return;
+        };
+        if let Some(previous) = order.order_expiry.take() {
+            state.expiries.remove(&(previous, client_order_index));
+        }
+        if order_expiry > NIL_ORDER_EXPIRY {
+            order.order_expiry = Some(order_expiry);
+            state.expiries.insert((order_expiry, client_order_index));
+        }
+    }
+
+    /// Mark open and pending orders whose expiry is at or before `now_ms`
+    /// as [`OrderStatus::ExpiryPending`] and return them
+    ///
+    /// Orders that reached another state first are dropped from the sweep.
+    pub fn sweep_expired(&self, now_ms: i64) -> Vec<TrackedOrder> {
+        let mut swept = Vec::new();
+        {
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            while let Some(&(expiry, client_order_index)) = state.expiries.first() {
+                if expiry > now_ms {
+                    break;
+                }
+                state.expiries.pop_first();
+                if let Some(order) = state.orders.get_mut(&client_order_index) {
+                    if matches!(order.status, OrderStatus::Pending | OrderStatus::Open) {
+                        order.status = OrderStatus::ExpiryPending;
+                        swept.push(order.clone());
+                    }
+                }
+            }
+        }
+        if !swept.is_empty() {
+            self.changes.send_replace(());
+        }
+        swept
+    }
+
+    /// Settle an [`ExpiryPending`](OrderStatus::ExpiryPending) order as
+    /// expired, or as open again if it is `still_open`
+    ///
+    /// `None` if the order isn't pending expiry any more.
+    fn settle_expiry(&self, client_order_index: i64, still_open: bool) -> Option<TrackedOrder> {
+        let settled = {
+            let mut state = self.state.lock().unwrap();
+            let order = state.orders.get_mut(&client_order_index)?;
+            if order.status != OrderStatus::ExpiryPending {
+                return None;
+            }
+            order.status = if still_open {
+                OrderStatus::Open
+            } else {
+                OrderStatus::Expired
+            };
+            order.clone()
+        };
+        self.changes.send_replace(());
+        Some(settled)
+    }
+
+    /// Sweep expired orders every `config.interval` until the returned
+    /// sweeper is dropped
+    ///
+    /// Every transition is reported by the sweeper: to
+    /// [`ExpiryPending`](OrderStatus::ExpiryPending) when the order is past
+    /// its expiry, then after `config.grace` to
+    /// [`Expired`](OrderStatus::Expired), or back to
+    /// [`Open`](OrderStatus::Open) if the REST check finds it still active.
+    /// Orders the stream settles during the grace period aren't reported
+    /// again.
+    ///
+    /// Expiries are compared with the wall clock as of the start, advanced
+    /// by the Tokio clock.
+    pub fn spawn_expiry_sweeper(self: &Arc<Self>, config: ExpirySweepConfig) -> ExpirySweeper {
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut tasks = TaskSet::new();
+        tasks.spawn("expiry_sweeper", sweep(self.clone(), config, tx));
+        ExpirySweeper { events, tasks }
+    }
+
+    /// Attach the transaction hash once `sendTx` answers
+    pub fn set_tx_hash(&self, client_order_index: i64, tx_hash: Option<String>) {
+        if let Some(order) = self
+            .state
+            .lock()
+            .unwrap()
+            .orders
+            .get_mut(&client_order_index)
+        {
+            order.tx_hash = tx_hash;
+        }
+    }
+
+    /// Mark an order as rejected
+    pub fn reject(&self, client_order_index: i64, reason: impl Into<String>) {
+        if let Some(order) = self
+            .state
+            .lock()
+            .unwrap()
+            .orders
+            .get_mut(&client_order_index)
+        {
+            order.status = OrderStatus::Rejected;
+            order.reject_reason = Some(reason.into());
+        }
+        self.changes.send_replace(());
+    }
+
+    /// Advance tracked orders from an account event
+    ///
+    /// Events for orders that were not registered are ignored.
+    pub fn apply(&self, event: &AccountEvent) {
+        self.apply_locked(event);
+        self.changes.send_replace(());
+    }
+
+    fn apply_locked(&self, event: &AccountEvent) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+
+        match event {
+            AccountEvent::OrderPlaced {
+                order_index,
+                client_order_index,
+                ..
+            } => {
+                if let Some(order) = state.orders.get_mut(client_order_index) {
+                    order.order_index = Some(*order_index);
+                    if order.status == OrderStatus::Pending {
+                        order.status = OrderStatus::Open;
+                    }
+                    state
+                        .by_order_index
+                        .insert(*order_index, *client_order_index);
+                }
+            }
+            AccountEvent::Fill {
+                order_index, size, ..
+            } => {
+                if let Some(order) = Self::by_order_index(state, *order_index) {
+                    order.filled += *size;
+                }
+            }
+            AccountEvent::OrderFilled { order_index, .. } => {
+                if let Some(order) = Self::by_order_index(state, *order_index) {
+                    order.status = OrderStatus::Filled;
+                }
+            }
+            AccountEvent::OrderCancelled { order_index, .. } => {
+                if let Some(order) = Self::by_order_index(state, *order_index) {
+                    order.status = OrderStatus::Cancelled;
+                }
+            }
+            AccountEvent::OrderRejected {
+                order_index,
+                client_order_index,
+                reason,
+                ..
+            } => {
+                if let Some(order) = state.orders.get_mut(client_order_index) {
+                    order.order_index = Some(*order_index);
+                    order.status = OrderStatus::Rejected;
+                    order.reject_reason = Some(reason.clone());
+                }
+            }
+            AccountEvent::BalanceChanged { .. } | AccountEvent::PositionChanged { .. } => {}
+        }
+    }
+
+    fn by_order_index(state: &mut TrackerState, order_index: i64) -> Option<&mut TrackedOrder> {
+        let client_order_index = state.by_order_index.get(&order_index)?;
+        state.orders.get_mut(client_order_index)
+    }
+
+    /// Look up an order by client order index
+    pub fn get(&self, client_order_index: i64) -> Option<TrackedOrder> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .get(&client_order_index)
+            .cloned()
+    }
+
+    /// Wait until an order is no longer pending and return it
+    ///
+    /// Resolves as soon as the order is open, filled, cancelled or rejected;
+    /// fails with [`LighterError::Timeout`] otherwise. Dropping the future
+    /// releases the waiter.
+    pub async fn wait_resolved(
+        &self,
+        client_order_index: i64,
+        timeout: Duration,
+    ) -> Result<TrackedOrder> {
+        let mut changes = self.changes.subscribe();
+        let wait = async {
+            loop {
+                match self.get(client_order_index) {
+                    None => {
+                        return Err(LighterError::ValidationError(format!(
+                            "Client order index {} is not tracked",
+                            client_order_index
+                        )))
+                    }
+                    Some(order) if order.status != OrderStatus::Pending => return Ok(order),
+                    Some(_) => {}
+                }
+                // The sender lives as long as `self`
+                let _ = changes.changed().await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LighterError::Timeout)?
+    }
+
+    /// Number of [`wait_resolved`](Self::wait_resolved) calls in progress
+    pub fn waiters(&self) -> usize {
+        self.changes.receiver_count()
+    }
+
+    /// Orders that are pending or open
+    pub fn open_orders(&self) -> Vec<TrackedOrder> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .values()
+            .filter(|o| !o.status.is_terminal())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Settings of [`OrderTracker::spawn_expiry_sweeper`]
+#[derive(Clone)]
+pub struct ExpirySweepConfig {
+    /// Time between sweeps; orders are found expired up to this late
+    pub interval: Duration,
+    /// Time an order stays pending expiry before it is settled, for the
+    /// stream to report it first
+    pub grace: Duration,
+    /// Client and account to check the active orders of before settling
+    pub confirm: Option<(HTTPClient, i64)>,
+}
+
+impl Default for ExpirySweepConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            grace: Duration::from_secs(5),
+            confirm: None,
+        }
+    }
+}
+
+impl ExpirySweepConfig {
+    /// Settle orders by the active orders of `account_index` on the REST API
+    ///
+    /// If the request fails, the order is settled as expired.
+    pub fn confirm_with(mut self, http: HTTPClient, account_index: i64) -> Self {
+        self.confirm = Some((http, account_index));
+        self
+    }
+}
+
+/// Transitions found by a running expiry sweep
+///
+/// Dropping it stops the sweep.
+#[derive(Debug)]
+pub struct ExpirySweeper {
+    events: mpsc::UnboundedReceiver<TrackedOrder>,
+    tasks: TaskSet,
+}
+
+impl ExpirySweeper {
+    /// The next order whose status the sweeper changed
+    pub async fn next(&mut self) -> Option<TrackedOrder> {
+        self.events.recv().await
+    }
+
+    /// Stop the sweep and wait for it to finish
+    pub async fn shutdown(self) -> ShutdownReport {
+        self.tasks
+            .shutdown(Duration::from_millis(DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS))
+            .await
+    }
+}
+
+async fn sweep(
+    tracker: Arc<OrderTracker>,
+    config: ExpirySweepConfig,
+    events: mpsc::UnboundedSender<TrackedOrder>,
+) -> Result<()> {
+    let start = tokio::time::Instant::now();
+    let start_ms = chrono::Utc::now().timestamp_millis();
+    // Swept orders by when their grace period ends; the grace is fixed, so
+    // they are in order
+    let mut settling = VecDeque::new();
+    let mut ticks = tokio::time::interval(config.interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        let now = ticks.tick().await;
+        let now_ms = start_ms + now.duration_since(start).as_millis() as i64;
+        for order in tracker.sweep_expired(now_ms) {
+            settling.push_back((now + config.grace, order.client_order_index));
+            if events.send(order).is_err() {
+                return Ok(());
+            }
+        }
+        while settling.front().is_some_and(|(due, _)| *due <= now) {
+            let (_, client_order_index) = settling.pop_front().unwrap();
+            let still_open = match &config.confirm {
+                Some((http, account_index)) => {
+                    is_active(&tracker, http, *account_index, client_order_index).await
+                }
+                None => false,
+            };
+            if let Some(order) = tracker.settle_expiry(client_order_index, still_open) {
+                if events.send(order).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Whether the REST API lists the order as active; false if it can't be asked
+async fn is_active(
+    tracker: &OrderTracker,
+    http: &HTTPClient,
+    account_index: i64,
+    client_order_index: i64,
+) -> bool {
+    let Some(order) = tracker.get(client_order_index) else {
+        return false;
+    };
+    let active = match (
+        AccountIndex::new(account_index),
+        MarketIndex::new(order.market_index),
+    ) {
+        (Ok(account_index), Ok(market_index)) => {
+            http.get_active_orders(account_index, market_index).await
+        }
+        (Err(e), _) | (_, Err(e)) => Err(e),
+    };
+    match active {
+        Ok(active) => active.iter().any(|active| {
+            active.client_order_index == client_order_index
+                || Some(active.order_index) == order.order_index
+        }),
+        Err(_) => false,
+    }
+}
+
+/// Exchange order index of one order, and when it left the book
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    order_index: i64,
+    terminal_at: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct ResolverState {
+    /// By market and client order index
+    by_client: HashMap<(u32, i64), IndexEntry>,
+    /// Client order index by market and order index
+    by_order: HashMap<(u32, i64), i64>,
+}
+
+/// Maps client order indices to exchange order indices, per market
+///
+/// Fed with the account events and snapshots of the stream. Mappings of
+/// filled, cancelled and rejected orders are dropped `ttl` after the order
+/// left the book.
+#[derive(Debug)]
+pub struct OrderIndexResolver {
+    state: Mutex<ResolverState>,
+    ttl: Duration,
+    /// Bumped after every change so waiters can re-check
+    changes: watch::Sender<()>,
+}
+
+impl OrderIndexResolver {
+    /// Create an empty resolver keeping finished orders for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            state: Mutex::new(ResolverState::default()),
+            ttl,
+            changes: watch::channel(()).0,
+        }
+    }
+
+    /// Learn from an account event
+    pub fn apply(&self, event: &AccountEvent) {
+        let now = Instant::now();
+        {
+            let mut state = self.state.lock().unwrap();
+            match event {
+                AccountEvent::OrderPlaced {
+                    market,
+                    order_index,
+                    client_order_index,
+                } => state.insert(*market, *client_order_index, *order_index, None),
+                AccountEvent::OrderRejected {
+                    market,
+                    order_index,
+                    client_order_index,
+                    ..
+                } => state.insert(*market, *client_order_index, *order_index, Some(now)),
+                AccountEvent::OrderFilled {
+                    market,
+                    order_index,
+                }
+                | AccountEvent::OrderCancelled {
+                    market,
+                    order_index,
+                } => state.finish(*market, *order_index, now),
+                AccountEvent::Fill { .. }
+                | AccountEvent::BalanceChanged { .. }
+                | AccountEvent::PositionChanged { .. } => {}
+            }
+            state.evict(now, self.ttl);
+        }
+        self.changes.send_replace(());
+    }
+
+    /// Learn every order of an account snapshot
+    ///
+    /// Orders without a client order index are skipped.
+    pub fn apply_snapshot(&self, snapshot: &AccountSnapshot) {
+        let now = Instant::now();
+        {
+            let mut state = self.state.lock().unwrap();
+            for order in snapshot.orders.values().flatten() {
+                if order.client_order_index == NIL_CLIENT_ORDER_INDEX {
+                    continue;
+                }
+                let terminal_at = order.is_terminal().then_some(now);
+                state.insert(
+                    order.market_index,
+                    order.client_order_index,
+                    order.order_index,
+                    terminal_at,
+                );
+            }
+            state.evict(now, self.ttl);
+        }
+        self.changes.send_replace(());
+    }
+
+    /// Exchange order index of an order, if it has been seen
+    pub fn resolve(&self, market_index: u8, client_order_index: i64) -> Option<i64> {
+        self.state
+            .lock()
+            .unwrap()
+            .by_client
+            .get(&(u32::from(market_index), client_order_index))
+            .map(|entry| entry.order_index)
+    }
+
+    /// Client order index of an exchange order, if it has been seen
+    pub fn client_order_index(&self, market_index: u8, order_index: i64) -> Option<i64> {
+        self.state
+            .lock()
+            .unwrap()
+            .by_order
+            .get(&(u32::from(market_index), order_index))
+            .copied()
+    }
+
+    /// Wait until an order's exchange index is known
+    ///
+    /// Fails with [`LighterError::Timeout`] if it doesn't show up in time.
+    pub async fn await_resolution(
+        &self,
+        market_index: u8,
+        client_order_index: i64,
+        timeout: Duration,
+    ) -> Result<i64> {
+        let mut changes = self.changes.subscribe();
+        let wait = async {
+            loop {
+                if let Some(order_index) = self.resolve(market_index, client_order_index) {
+                    return order_index;
+                }
+                // The sender lives as long as `self`
+                let _ = changes.changed().await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LighterError::Timeout)
+    }
+
+    /// Number of orders mapped
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().by_client.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ResolverState {
+    fn insert(
+        &mut self,
+        market: u32,
+        client_order_index: i64,
+        order_index: i64,
+        terminal_at: Option<Instant>,
+    ) {
+        let entry = self
+            .by_client
+            .entry((market, client_order_index))
+            .or_insert(IndexEntry {
+                order_index,
+                terminal_at: None,
+            });
+        if entry.order_index != order_index {
+            // The client order index was reused for a new order
+            self.by_order.remove(&(market, entry.order_index));
+            *entry = IndexEntry {
+                order_index,
+                terminal_at: None,
+            };
+        }
+        entry.terminal_at = entry.terminal_at.or(terminal_at);
+        self.by_order
+            .insert((market, order_index), client_order_index);
+    }
+
+    fn finish(&mut self, market: u32, order_index: i64, now: Instant) {
+        if let Some(client_order_index) = self.by_order.get(&(market, order_index)) {
+            if let Some(entry) = self.by_client.get_mut(&(market, *client_order_index)) {
+                entry.terminal_at.get_or_insert(now);
+            }
+        }
+    }
+
+    fn evict(&mut self, now: Instant, ttl: Duration) {
+        let by_order = &mut self.by_order;
+        self.by_client.retain(|(market, _), entry| {
+            let keep = entry
+                .terminal_at
+                .is_none_or(|at| now.duration_since(at) < ttl);
+            if !keep {
+                by_order.remove(&(*market, entry.order_index));
+            }
+            keep
+        });
+    }
+}
+
+impl TxClient {
+    /// Sign a cancel of an order known by its client order index
+    ///
+    /// Waits up to `wait` for `resolver` to learn the order's exchange
+    /// index and cancels by it; if it doesn't, cancels by the client order
+    /// index instead.
+    pub async fn cancel_by_client_index_resolved(
+        &self,
+        resolver: &OrderIndexResolver,
+        market_index: u8,
+        client_order_index: i64,
+        wait: Duration,
+        opts: Option<TransactOpts>,
+    ) -> Result<L2CancelOrderTxInfo> {
+        let req = match resolver
+            .await_resolution(market_index, client_order_index, wait)
+            .await
+        {
+            Ok(order_index) => CancelOrderTxReq::by_order_index(market_index, order_index)?,
+            Err(LighterError::Timeout) => {
+                CancelOrderTxReq::by_client_order_index(market_index, client_order_index)?
+            }
+            Err(e) => return Err(e),
+        };
+        self.cancel_order(&req, opts).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_client;
+    use crate::types::Side;
+
+    #[test]
+    fn test_order_lifecycle() {
+        let tracker = OrderTracker::new();
+        tracker.register(0, 11, Some("0xabc".to_string()));
+        assert_eq!(tracker.get(11).unwrap().status, OrderStatus::Pending);
+
+        tracker.apply(&AccountEvent::OrderPlaced {
+            market: 0,
+            order_index: 900,
+            client_order_index: 11,
+        });
+        let order = tracker.get(11).unwrap();
+        assert_eq!(order.status, OrderStatus::Open);
+        assert_eq!(order.order_index, Some(900));
+
+        tracker.apply(&AccountEvent::Fill {
+            market: 0,
+            price: Decimal::from(10),
+            size: Decimal::new(5, 1),
+            side: Side::Buy,
+            order_index: 900,
+        });
+        assert_eq!(tracker.get(11).unwrap().filled, Decimal::new(5, 1));
+        assert_eq!(tracker.open_orders().len(), 1);
+
+        tracker.apply(&AccountEvent::OrderFilled {
+            market: 0,
+            order_index: 900,
+        });
+        assert_eq!(tracker.get(11).unwrap().status, OrderStatus::Filled);
+        assert!(tracker.open_orders().is_empty());
+    }
+
+    #[test]
+    fn test_reject_and_unknown_events() {
+        let tracker = OrderTracker::new();
+        tracker.register(1, 12, None);
+        tracker.reject(12, "nonce too low");
+
+        let order = tracker.get(12).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(order.reject_reason.as_deref(), Some("nonce too low"));
+
+        // Events for orders placed elsewhere are ignored
+        tracker.apply(&AccountEvent::OrderCancelled {
+            market: 1,
+            order_index: 5,
+        });
+        assert!(tracker.get(5).is_none());
+    }
+
+    fn placed(market: u32, order_index: i64, client_order_index: i64) -> AccountEvent {
+        AccountEvent::OrderPlaced {
+            market,
+            order_index,
+            client_order_index,
+        }
+    }
+
+    #[test]
+    fn test_resolver_maps_both_ways_per_market() {
+        let resolver = OrderIndexResolver::new(Duration::from_secs(60));
+        resolver.apply(&placed(0, 281474976710700, 7));
+        resolver.apply(&placed(1, 281474976710701, 7));
+
+        assert_eq!(resolver.resolve(0, 7), Some(281474976710700));
+        assert_eq!(resolver.resolve(1, 7), Some(281474976710701));
+        assert_eq!(resolver.resolve(2, 7), None);
+        assert_eq!(resolver.client_order_index(1, 281474976710701), Some(7));
+
+        let snapshot: AccountSnapshot = serde_json::from_value(serde_json::json!({
+            "orders": {"3": [
+                {"order_index": 281474976710800i64, "client_order_index": 9, "market_index": 3,
+                 "is_ask": true, "price": "1", "remaining_base_amount": "1", "status": "open"},
+                {"order_index": 281474976710801i64, "market_index": 3,
+                 "is_ask": true, "price": "1", "remaining_base_amount": "1", "status": "open"}
+            ]}
+        }))
+        .unwrap();
+        resolver.apply_snapshot(&snapshot);
+        assert_eq!(resolver.resolve(3, 9), Some(281474976710800));
+        assert_eq!(resolver.len(), 3);
+    }
+
+    #[test]
+    fn test_resolver_evicts_finished_orders_after_ttl() {
+        let resolver = OrderIndexResolver::new(Duration::from_millis(30));
+        resolver.apply(&placed(0, 281474976710700, 7));
+        resolver.apply(&placed(0, 281474976710701, 8));
+        resolver.apply(&AccountEvent::OrderFilled {
+            market: 0,
+            order_index: 281474976710700,
+        });
+        // Still resolvable until the TTL has passed
+        assert_eq!(resolver.resolve(0, 7), Some(281474976710700));
+
+        std::thread::sleep(Duration::from_millis(40));
+        resolver.apply(&AccountEvent::BalanceChanged {
+            delta: Decimal::ONE,
+        });
+        assert_eq!(resolver.resolve(0, 7), None);
+        assert_eq!(resolver.client_order_index(0, 281474976710700), None);
+        // Open orders are never evicted
+        assert_eq!(resolver.resolve(0, 8), Some(281474976710701));
+        assert_eq!(resolver.len(), 1);
+    }
+
+    fn offline_client() -> TxClient {
+        test_client("")
+    }
+
+    fn offline_opts() -> Option<TransactOpts> {
+        Some(TransactOpts {
+            nonce: Some(1),
+            expired_at: 4_000_000_000_000,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cancel_by_client_index_waits_for_resolution() {
+        let client = offline_client();
+        let resolver = std::sync::Arc::new(OrderIndexResolver::new(Duration::from_secs(60)));
+
+        let events = resolver.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            events.apply(&placed(2, 281474976710900, 31));
+        });
+        let tx = client
+            .cancel_by_client_index_resolved(
+                &resolver,
+                2,
+                31,
+                Duration::from_secs(5),
+                offline_opts(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tx.index, 281474976710900);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_by_client_index_falls_back_after_timeout() {
+        let client = offline_client();
+        let resolver = OrderIndexResolver::new(Duration::from_secs(60));
+
+        let tx = client
+            .cancel_by_client_index_resolved(
+                &resolver,
+                2,
+                32,
+                Duration::from_millis(20),
+                offline_opts(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tx.index, 32);
+        assert!(matches!(
+            resolver
+                .await_resolution(2, 32, Duration::from_millis(5))
+                .await,
+            Err(LighterError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_sweep_expired_in_expiry_order() {
+        let tracker = OrderTracker::new();
+        for (client_order_index, expiry) in
+            [(1, 2_000), (2, 1_000), (3, NIL_ORDER_EXPIRY), (4, 1_500)]
+        {
+            tracker.register(0, client_order_index, None);
+            tracker.set_order_expiry(client_order_index, expiry);
+        }
+        tracker.apply(&placed(0, 904, 4));
+        tracker.apply(&AccountEvent::OrderFilled {
+            market: 0,
+            order_index: 904,
+        });
+
+        assert!(tracker.sweep_expired(999).is_empty());
+        let swept = tracker.sweep_expired(1_000);
+        assert_eq!(swept.len(), 1);
+        assert_eq!(swept[0].client_order_index, 2);
+        assert_eq!(tracker.get(2).unwrap().status, OrderStatus::ExpiryPending);
+
+        // Filled orders and orders without an expiry are never swept
+        let swept: Vec<_> = tracker
+            .sweep_expired(i64::MAX)
+            .iter()
+            .map(|o| o.client_order_index)
+            .collect();
+        assert_eq!(swept, [1]);
+        assert_eq!(tracker.get(3).unwrap().status, OrderStatus::Pending);
+        assert_eq!(tracker.get(4).unwrap().status, OrderStatus::Filled);
+        assert!(tracker.sweep_expired(i64::MAX).is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expiry_sweeper_transitions_on_time() {
+        let tracker = Arc::new(OrderTracker::new());
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        tracker.register(0, 41, None);
+        tracker.set_order_expiry(41, now_ms + 1_000);
+        tracker.register(0, 42, None);
+        tracker.apply(&placed(0, 942, 42));
+        tracker.set_order_expiry(42, now_ms + 1_000);
+
+        let start = tokio::time::Instant::now();
+        let mut sweeper = tracker.spawn_expiry_sweeper(ExpirySweepConfig {
+            interval: Duration::from_millis(100),
+            grace: Duration::from_millis(500),
+            confirm: None,
+        });
+        tokio::time::sleep(Duration::from_millis(950)).await;
+        assert_eq!(tracker.get(41).unwrap().status, OrderStatus::Pending);
+
+        for _ in 0..2 {
+            let order = sweeper.next().await.unwrap();
+            assert_eq!(order.status, OrderStatus::ExpiryPending);
+            assert_eq!(start.elapsed(), Duration::from_millis(1_000));
+        }
+        // The stream reports one of them during the grace period
+        tracker.apply(&AccountEvent::OrderCancelled {
+            market: 0,
+            order_index: 942,
+        });
+
+        let order = sweeper.next().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(1_500));
+        assert_eq!(
+            (order.client_order_index, order.status),
+            (41, OrderStatus::Expired)
+        );
+        assert_eq!(tracker.get(42).unwrap().status, OrderStatus::Cancelled);
+        assert!(tracker.open_orders().is_empty());
+
+        let report = sweeper.shutdown().await;
+        assert_eq!(report.stopped, vec!["expiry_sweeper".to_string()]);
+        assert!(report.is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_expiry_sweeper_confirms_with_rest() {
+        let mut server = mockito::Server::new_async().await;
+        let still_active = server
+            .mock("GET", "/api/v1/accountActiveOrders")
+            .match_query(mockito::Matcher::UrlEncoded("market_id".into(), "0".into()))
+            .with_body(
+                r#"{"code":200,"orders":[{"order_index":950,"client_order_index":51,"market_index":0,"is_ask":false,"price":"1","remaining_base_amount":"1","status":"open"}]}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let failing = server
+            .mock("GET", "/api/v1/accountActiveOrders")
+            .match_query(mockito::Matcher::UrlEncoded("market_id".into(), "1".into()))
+            .with_status(500)
+            .create_async()
+            .await;
+        let client = test_client(&server.url());
+
+        let tracker = Arc::new(OrderTracker::new());
+        let past = chrono::Utc::now().timestamp_millis() - 1_000;
+        for (market, client_order_index) in [(0, 51), (0, 52), (1, 53)] {
+            tracker.register(market, client_order_index, None);
+            tracker.set_order_expiry(client_order_index, past);
+        }
+        let config = ExpirySweepConfig {
+            interval: Duration::from_millis(10),
+            grace: Duration::from_millis(20),
+            confirm: None,
+        }
+        .confirm_with(client.http().unwrap().clone(), 12345);
+        let mut sweeper = tracker.spawn_expiry_sweeper(config);
+
+        let mut settled = HashMap::new();
+        while settled.len() < 3 {
+            let order = sweeper.next().await.unwrap();
+            if order.status != OrderStatus::ExpiryPending {
+                settled.insert(order.client_order_index, order.status);
+            }
+        }
+        // Still listed as active, not listed, and the request failed
+        assert_eq!(settled[&51], OrderStatus::Open);
+        assert_eq!(settled[&52], OrderStatus::Expired);
+        assert_eq!(settled[&53], OrderStatus::Expired);
+        still_active.assert_async().await;
+        failing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_wait_resolved() {
+        let tracker = std::sync::Arc::new(OrderTracker::new());
+        tracker.register(0, 21, None);
+
+        let events = tracker.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            events.apply(&AccountEvent::OrderRejected {
+                market: 0,
+                order_index: 950,
+                client_order_index: 21,
+                reason: "canceled-post-only".to_string(),
+            });
+        });
+        let order = tracker
+            .wait_resolved(21, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(order.reject_reason.as_deref(), Some("canceled-post-only"));
+
+        tracker.register(0, 22, None);
+        let result = tracker.wait_resolved(22, Duration::from_millis(20)).await;
+        assert!(matches!(result, Err(LighterError::Timeout)));
+        assert_eq!(tracker.waiters(), 0);
+    }
+}
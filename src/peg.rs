@@ -0,0 +1,522 @@
+//! Limit prices pegged to the best bid, best ask or mid of a book
+//!
+//! [`Peg::quote`] turns a local [`OrderBook`] into a limit price on the
+//! market's tick grid; [`TxClient::create_pegged_order`] signs an order at
+//! that price and returns the price and book offset it was based on, so the
+//! decision can be audited later.
+//!
+//! ```
+//! use lighter_rs::peg::{MarketRules, Peg};
+//! use lighter_rs::types::Side;
+//! use lighter_rs::ws_client::{OrderBook, PriceLevel};
+//!
+//! let level = |price: &str| PriceLevel { price: price.into(), size: "1".into() };
+//! let book = OrderBook { asks: vec![level("100.05")], bids: vec![level("99.95")] };
+//! let rules = MarketRules::new(4, 2);
+//!
+//! let quote = Peg::ImproveTicks(1).quote(Side::Buy, &book, &rules, false)?;
+//! assert_eq!(quote.price, 9_996);
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::client::TxClient;
+use crate::constants::MIN_ORDER_PRICE;
+use crate::errors::{LighterError, Result};
+use crate::fees::{breakeven_spread_bps, FeeSchedule};
+use crate::types::{
+    BaseAmount, L2CreateOrderTxInfo, MarketIndex, OrderKind, OrderParams, Price, Side, TransactOpts,
+};
+use crate::utils::checked_base_amount;
+use crate::ws_client::{OrderBook, PriceLevel};
+
+/// Where a pegged order's limit price sits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peg {
+    /// At the best price of the order's own side
+    Join,
+    /// This many ticks better than the best price of the order's own side
+    ImproveTicks(u32),
+    /// This many ticks worse than the best price of the order's own side
+    BehindTicks(u32),
+    /// Away from the mid by this many basis points, below it for buys and
+    /// above it for sells; negative values move towards the other side
+    ///
+    /// Needs both sides of the book.
+    MidOffsetBps(Decimal),
+}
+
+/// Price and size precision of a market
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketRules {
+    pub size_decimals: u32,
+    pub price_decimals: u32,
+    /// Smallest price step in price units; 1 unless the market says otherwise
+    pub price_tick: u32,
+}
+
+impl MarketRules {
+    /// Rules with a tick of one price unit
+    pub fn new(size_decimals: u32, price_decimals: u32) -> Self {
+        Self {
+            size_decimals,
+            price_decimals,
+            price_tick: 1,
+        }
+    }
+
+    /// Set the tick size in price units
+    pub fn price_tick(mut self, price_tick: u32) -> Self {
+        self.price_tick = price_tick;
+        self
+    }
+
+    /// A decimal price in price units, rounded to the tick away from the
+    /// other side: down for buys, up for sells
+    fn price_units(&self, price: Decimal, side: Side) -> Result<i64> {
+        let scale = Decimal::from(10u64.checked_pow(self.price_decimals).ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "Price decimals too large: {}",
+                self.price_decimals
+            ))
+        })?);
+        let tick = Decimal::from(self.price_tick);
+        let ticks = price.checked_mul(scale).map(|units| units / tick);
+        let rounded = match side {
+            Side::Buy => ticks.map(|t| t.floor()),
+            Side::Sell => ticks.map(|t| t.ceil()),
+        };
+        rounded
+            .and_then(|t| (t * tick).to_i64())
+            .ok_or_else(|| LighterError::ValidationError(format!("Price {} overflows", price)))
+    }
+}
+
+/// A pegged price and the book it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PegQuote {
+    /// Limit price in price units
+    pub price: u32,
+    /// Best bid in price units, `None` if there were no bids
+    pub best_bid: Option<i64>,
+    /// Best ask in price units, `None` if there were no asks
+    pub best_ask: Option<i64>,
+}
+
+impl PegQuote {
+    /// Spread in basis points of the mid between this price and its mirror
+    /// on the other side of the mid
+    ///
+    /// Twice the distance from the mid, negative for a buy above or a sell
+    /// below it; `None` unless the book had both sides.
+    pub fn spread_bps(&self, side: Side) -> Option<Decimal> {
+        let (bid, ask) = (self.best_bid?, self.best_ask?);
+        let mid = Decimal::from(bid + ask) / Decimal::from(2);
+        if mid.is_zero() {
+            return None;
+        }
+        let away = match side {
+            Side::Buy => mid - Decimal::from(self.price),
+            Side::Sell => Decimal::from(self.price) - mid,
+        };
+        Some(away * Decimal::from(2 * 10_000) / mid)
+    }
+}
+
+impl Peg {
+    /// Limit price for an order on `side`
+    ///
+    /// Fails if the side the peg refers to is empty, or if the price would
+    /// cross the other side and `allow_cross` is false.
+    pub fn quote(
+        self,
+        side: Side,
+        book: &OrderBook,
+        rules: &MarketRules,
+        allow_cross: bool,
+    ) -> Result<PegQuote> {
+        if rules.price_tick == 0 {
+            return Err(LighterError::ValidationError(
+                "Price tick must be at least 1".to_string(),
+            ));
+        }
+        let best_bid = best_price(&book.bids, Side::Buy)?
+            .map(|p| rules.price_units(p, Side::Buy))
+            .transpose()?;
+        let best_ask = best_price(&book.asks, Side::Sell)?
+            .map(|p| rules.price_units(p, Side::Sell))
+            .transpose()?;
+        let tick = i64::from(rules.price_tick);
+        // Positive moves towards the other side
+        let toward = |ticks: u32| match side {
+            Side::Buy => i64::from(ticks) * tick,
+            Side::Sell => -i64::from(ticks) * tick,
+        };
+
+        let price = match self {
+            Peg::Join | Peg::ImproveTicks(_) | Peg::BehindTicks(_) => {
+                let (own, name) = match side {
+                    Side::Buy => (best_bid, "bids"),
+                    Side::Sell => (best_ask, "asks"),
+                };
+                let own = own.ok_or_else(|| {
+                    LighterError::ValidationError(format!("No {} to peg to", name))
+                })?;
+                match self {
+                    Peg::ImproveTicks(n) => own + toward(n),
+                    Peg::BehindTicks(n) => own - toward(n),
+                    _ => own,
+                }
+            }
+            Peg::MidOffsetBps(bps) => {
+                let (Some(bid), Some(ask)) = (best_bid, best_ask) else {
+                    return Err(LighterError::ValidationError(
+                        "Mid peg needs both sides of the book".to_string(),
+                    ));
+                };
+                let mid = Decimal::from(bid + ask) / Decimal::from(2);
+                let offset = mid * bps / Decimal::from(10_000);
+                let target = match side {
+                    Side::Buy => mid - offset,
+                    Side::Sell => mid + offset,
+                };
+                let ticks = target / Decimal::from(tick);
+                let ticks = match side {
+                    Side::Buy => ticks.floor(),
+                    Side::Sell => ticks.ceil(),
+                };
+                (ticks * Decimal::from(tick)).to_i64().ok_or_else(|| {
+                    LighterError::ValidationError(format!("Mid peg price {} overflows", target))
+                })?
+            }
+        };
+
+        let crosses = match side {
+            Side::Buy => best_ask.is_some_and(|ask| price >= ask),
+            Side::Sell => best_bid.is_some_and(|bid| price <= bid),
+        };
+        if crosses && !allow_cross {
+            return Err(LighterError::ValidationError(format!(
+                "Pegged {:?} price {} crosses the book (bid {:?}, ask {:?})",
+                side, price, best_bid, best_ask
+            )));
+        }
+
+        let price = u32::try_from(price)
+            .ok()
+            .filter(|p| *p >= MIN_ORDER_PRICE)
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!("Pegged price {} is out of range", price))
+            })?;
+        Ok(PegQuote {
+            price,
+            best_bid,
+            best_ask,
+        })
+    }
+}
+
+/// Highest bid or lowest ask of a side, ignoring empty levels
+fn best_price(levels: &[PriceLevel], side: Side) -> Result<Option<Decimal>> {
+    let mut best: Option<Decimal> = None;
+    for level in levels {
+        let parse = |text: &str| {
+            text.parse::<Decimal>().map_err(|_| {
+                LighterError::ValidationError(format!("Invalid book level {:?}", level))
+            })
+        };
+        if parse(&level.size)?.is_zero() {
+            continue;
+        }
+        let price = parse(&level.price)?;
+        best = Some(match (best, side) {
+            (Some(b), Side::Buy) => b.max(price),
+            (Some(b), Side::Sell) => b.min(price),
+            (None, _) => price,
+        });
+    }
+    Ok(best)
+}
+
+/// Request for [`TxClient::create_pegged_order`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeggedOrderReq {
+    pub market_index: MarketIndex,
+    pub side: Side,
+    /// Size in base units, scaled by the market's size decimals
+    pub size: Decimal,
+    pub peg: Peg,
+    /// Allow a price at or through the other side of the book
+    pub allow_cross: bool,
+    /// Refuse prices whose [`PegQuote::spread_bps`] is below this
+    pub min_spread_bps: Option<Decimal>,
+}
+
+impl PeggedOrderReq {
+    /// Request that refuses to cross the book
+    pub fn new(market_index: MarketIndex, side: Side, size: Decimal, peg: Peg) -> Self {
+        Self {
+            market_index,
+            side,
+            size,
+            peg,
+            allow_cross: false,
+            min_spread_bps: None,
+        }
+    }
+
+    /// Set whether the price may cross the book
+    pub fn allow_cross(mut self, allow_cross: bool) -> Self {
+        self.allow_cross = allow_cross;
+        self
+    }
+
+    /// Refuse to quote tighter than the breakeven spread of `schedule` plus
+    /// `margin_bps`
+    pub fn min_spread(mut self, schedule: &FeeSchedule, margin_bps: Decimal) -> Self {
+        self.min_spread_bps = Some(breakeven_spread_bps(schedule) + margin_bps);
+        self
+    }
+}
+
+/// A signed pegged order with what its price was based on
+#[derive(Debug, Clone)]
+pub struct PeggedOrder {
+    pub tx: L2CreateOrderTxInfo,
+    pub quote: PegQuote,
+    /// Offset of the book the price was computed from
+    pub book_offset: Option<i64>,
+}
+
+impl TxClient {
+    /// Sign a limit order priced by a [`Peg`] against `book`
+    ///
+    /// `book` and `book_offset` should be read together, e.g. from
+    /// [`WsClient::market_view`](crate::ws_client::WsClient::market_view).
+    /// Nothing is sent; the price is as fresh as the book passed in.
+    pub async fn create_pegged_order(
+        &self,
+        req: &PeggedOrderReq,
+        book: &OrderBook,
+        book_offset: Option<i64>,
+        rules: &MarketRules,
+        opts: Option<TransactOpts>,
+    ) -> Result<PeggedOrder> {
+        let quote = req.peg.quote(req.side, book, rules, req.allow_cross)?;
+        if let Some(min) = req.min_spread_bps {
+            let spread = quote.spread_bps(req.side).ok_or_else(|| {
+                LighterError::ValidationError(
+                    "Minimum spread needs both sides of the book".to_string(),
+                )
+            })?;
+            if spread < min {
+                return Err(LighterError::ValidationError(format!(
+                    "Pegged {:?} price {} quotes {} bps, below the minimum of {}",
+                    req.side,
+                    quote.price,
+                    spread.round_dp(2),
+                    min
+                )));
+            }
+        }
+        let base_amount = BaseAmount::new(checked_base_amount(req.size, rules.size_decimals)?)?;
+        let params = OrderParams::new(
+            req.market_index,
+            req.side,
+            base_amount,
+            Price::new(quote.price)?,
+        );
+        let tx = self
+            .create_order(&params.to_request(OrderKind::Limit), opts)
+            .await?;
+        Ok(PeggedOrder {
+            tx,
+            quote,
+            book_offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_client;
+
+    fn level(price: &str, size: &str) -> PriceLevel {
+        PriceLevel {
+            price: price.to_string(),
+            size: size.to_string(),
+        }
+    }
+
+    /// Bid 99.95, ask 100.05, with deeper and emptied levels out of order
+    fn book() -> OrderBook {
+        OrderBook {
+            asks: vec![
+                level("100.10", "1"),
+                level("100.05", "2"),
+                level("100.01", "0"),
+            ],
+            bids: vec![
+                level("99.90", "1"),
+                level("99.99", "0"),
+                level("99.95", "2"),
+            ],
+        }
+    }
+
+    fn quote(peg: Peg, side: Side) -> Result<u32> {
+        peg.quote(side, &book(), &MarketRules::new(4, 2), false)
+            .map(|q| q.price)
+    }
+
+    #[test]
+    fn test_tick_pegs_on_both_sides() {
+        assert_eq!(quote(Peg::Join, Side::Buy).unwrap(), 9_995);
+        assert_eq!(quote(Peg::Join, Side::Sell).unwrap(), 10_005);
+        assert_eq!(quote(Peg::ImproveTicks(2), Side::Buy).unwrap(), 9_997);
+        assert_eq!(quote(Peg::ImproveTicks(2), Side::Sell).unwrap(), 10_003);
+        assert_eq!(quote(Peg::BehindTicks(3), Side::Buy).unwrap(), 9_992);
+        assert_eq!(quote(Peg::BehindTicks(3), Side::Sell).unwrap(), 10_008);
+
+        let quoted = Peg::Join
+            .quote(Side::Buy, &book(), &MarketRules::new(4, 2), false)
+            .unwrap();
+        assert_eq!(
+            (quoted.best_bid, quoted.best_ask),
+            (Some(9_995), Some(10_005))
+        );
+    }
+
+    #[test]
+    fn test_mid_offset_rounds_away_from_the_book() {
+        // Mid is 10_000; 3 bps of it is 3 units
+        assert_eq!(
+            quote(Peg::MidOffsetBps(Decimal::from(3)), Side::Buy).unwrap(),
+            9_997
+        );
+        assert_eq!(
+            quote(Peg::MidOffsetBps(Decimal::from(3)), Side::Sell).unwrap(),
+            10_003
+        );
+        // 0.5 bps is half a unit, rounded down for the buy and up for the sell
+        let half = Decimal::new(5, 1);
+        assert_eq!(quote(Peg::MidOffsetBps(half), Side::Buy).unwrap(), 9_999);
+        assert_eq!(quote(Peg::MidOffsetBps(half), Side::Sell).unwrap(), 10_001);
+        assert_eq!(quote(Peg::MidOffsetBps(-half), Side::Buy).unwrap(), 10_000);
+    }
+
+    #[test]
+    fn test_crossing_prices_need_allow_cross() {
+        let rules = MarketRules::new(4, 2);
+        for (peg, side) in [
+            (Peg::ImproveTicks(10), Side::Buy),
+            (Peg::ImproveTicks(10), Side::Sell),
+            (Peg::MidOffsetBps(Decimal::from(-5)), Side::Buy),
+            (Peg::MidOffsetBps(Decimal::from(-5)), Side::Sell),
+        ] {
+            assert!(peg.quote(side, &book(), &rules, false).is_err());
+            assert!(peg.quote(side, &book(), &rules, true).is_ok());
+        }
+        assert_eq!(
+            Peg::ImproveTicks(10)
+                .quote(Side::Buy, &book(), &rules, true)
+                .unwrap()
+                .price,
+            10_005
+        );
+    }
+
+    #[test]
+    fn test_empty_sides() {
+        let rules = MarketRules::new(4, 2);
+        let bids_only = OrderBook {
+            asks: vec![],
+            ..book()
+        };
+        assert!(Peg::MidOffsetBps(Decimal::ZERO)
+            .quote(Side::Buy, &bids_only, &rules, false)
+            .is_err());
+        assert!(Peg::Join
+            .quote(Side::Sell, &bids_only, &rules, false)
+            .is_err());
+        // Nothing to cross, so any improvement is allowed
+        let quoted = Peg::ImproveTicks(100)
+            .quote(Side::Buy, &bids_only, &rules, false)
+            .unwrap();
+        assert_eq!((quoted.price, quoted.best_ask), (10_095, None));
+    }
+
+    #[test]
+    fn test_coarser_tick() {
+        // A tick of 10 units; the book's prices are rounded away from the other side
+        let rules = MarketRules::new(4, 2).price_tick(10);
+        let quoted = Peg::Join.quote(Side::Buy, &book(), &rules, false).unwrap();
+        assert_eq!(quoted.price, 9_990);
+        let quoted = Peg::ImproveTicks(1)
+            .quote(Side::Sell, &book(), &rules, false)
+            .unwrap();
+        assert_eq!(quoted.price, 10_000);
+        assert!(Peg::Join
+            .quote(
+                Side::Buy,
+                &book(),
+                &MarketRules::new(4, 2).price_tick(0),
+                false
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_pegged_order_records_its_basis() {
+        let client = test_client("");
+        let opts = TransactOpts {
+            nonce: Some(1),
+            expired_at: 4_000_000_000_000,
+            ..Default::default()
+        };
+        let req = PeggedOrderReq::new(
+            MarketIndex::new(3).unwrap(),
+            Side::Sell,
+            Decimal::new(15, 1),
+            Peg::ImproveTicks(1),
+        );
+
+        let order = client
+            .create_pegged_order(
+                &req,
+                &book(),
+                Some(42),
+                &MarketRules::new(4, 2),
+                Some(opts.clone()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(order.quote.price, 10_004);
+        assert_eq!(order.book_offset, Some(42));
+        assert_eq!(order.tx.order_info.price, 10_004);
+        assert_eq!(order.tx.order_info.base_amount, 15_000);
+        assert_eq!(order.tx.order_info.is_ask, 1);
+        // 4 ticks above a mid of 100.00 on each side
+        assert_eq!(order.quote.spread_bps(Side::Sell), Some(Decimal::new(8, 0)));
+
+        // Breakeven of 2 bps maker fees is 4 bps
+        let schedule = FeeSchedule::new(Decimal::new(2, 0), Decimal::new(5, 0));
+        let sign = |margin_bps: i64| {
+            let req = req.min_spread(&schedule, Decimal::new(margin_bps, 0));
+            let (client, opts) = (&client, opts.clone());
+            async move {
+                client
+                    .create_pegged_order(&req, &book(), None, &MarketRules::new(4, 2), Some(opts))
+                    .await
+            }
+        };
+        assert!(sign(4).await.is_ok());
+        assert!(matches!(
+            sign(5).await,
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+}
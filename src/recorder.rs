@@ -0,0 +1,661 @@
+//! Long-running capture of stream messages to disk
+//!
+//! [`MarketRecorder`] writes the frames of a
+//! [raw message tap](crate::ws_client::WsClientBuilder::raw_message_tap) to
+//! gzip-compressed ndjson segments, starting a new segment hourly or by size,
+//! and lists every segment in a `manifest.json` next to them. Each new
+//! connection is marked by a [`Recorded::SessionStart`] line, so reconnects
+//! stay visible in the capture. [`RecordingReader`] reads the segments back
+//! in order, e.g. to [`replay`](RecordingReader::replay) them.
+//!
+//! ```rust,no_run
+//! use lighter_rs::recorder::{MarketRecorder, RotationPolicy};
+//! use lighter_rs::ws_client::WsClient;
+//! use tokio::sync::mpsc;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let (tap, frames) = mpsc::channel(10_000);
+//! let client = WsClient::builder()
+//!     .host("mainnet.zklighter.elliot.ai")
+//!     .order_books(vec![0, 1])
+//!     .raw_message_tap(tap)
+//!     .build()?;
+//! let recorder = MarketRecorder::new("capture", RotationPolicy::Hourly)?;
+//! let recording = tokio::spawn(recorder.run(frames));
+//! // client.run(...).await?;
+//! # drop(client);
+//! recording.await??;
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Lines, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use crate::constants::DEFAULT_RECORDER_FLUSH_INTERVAL_SECS;
+use crate::errors::{LighterError, Result};
+use crate::ws_client::subscriptions::normalize_channel;
+use crate::ws_client::{replay_raw_messages, RawWsMessage, WsHandler};
+
+/// File listing the segments of a recording
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// When [`MarketRecorder`] starts a new segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPolicy {
+    /// With the first message of every UTC hour, by receive time
+    Hourly,
+    /// Once a segment holds this many bytes, before compression
+    BySize(u64),
+}
+
+/// Manifest entry of one segment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SegmentInfo {
+    /// File name within the recording directory
+    pub file: String,
+    /// Receive time of the first message
+    pub start: DateTime<Utc>,
+    /// Receive time of the last message
+    pub end: DateTime<Utc>,
+    pub messages: u64,
+    /// Markets seen on order book and trade channels
+    pub markets: Vec<u32>,
+}
+
+/// Segments of a recording, as stored in `manifest.json`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub segments: Vec<SegmentInfo>,
+}
+
+impl Manifest {
+    /// Read the manifest of a recording directory; empty if there is none
+    pub fn load(dir: &Path) -> Result<Self> {
+        match fs::read(dir.join(MANIFEST_FILE)) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(io_error(dir, e)),
+        }
+    }
+
+    fn save(&self, dir: &Path) -> Result<()> {
+        let path = dir.join(MANIFEST_FILE);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?).map_err(|e| io_error(dir, e))?;
+        fs::rename(&tmp_path, &path).map_err(|e| io_error(dir, e))
+    }
+}
+
+/// One line of a segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Recorded {
+    /// A new connection; frames after it belong to session `session`
+    SessionStart {
+        session: u64,
+        received_at: DateTime<Utc>,
+    },
+    Message(RawWsMessage),
+}
+
+/// Running totals of a [`MarketRecorder`]
+#[derive(Debug, Default)]
+pub struct RecorderCounters {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    segments: AtomicU64,
+    sessions: AtomicU64,
+}
+
+impl RecorderCounters {
+    /// Messages written
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written, before compression
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Segments started
+    pub fn segments(&self) -> u64 {
+        self.segments.load(Ordering::Relaxed)
+    }
+
+    /// Connections seen
+    pub fn sessions(&self) -> u64 {
+        self.sessions.load(Ordering::Relaxed)
+    }
+}
+
+/// Segment being written
+struct Segment {
+    encoder: GzEncoder<File>,
+    info: SegmentInfo,
+    markets: BTreeSet<u32>,
+    bytes: u64,
+}
+
+/// Writes raw stream messages to rotating gzip ndjson segments
+///
+/// Recording into a directory that already holds a recording appends new
+/// segments to its manifest.
+pub struct MarketRecorder {
+    dir: PathBuf,
+    rotation: RotationPolicy,
+    flush_interval: Duration,
+    manifest: Manifest,
+    current: Option<Segment>,
+    session: u64,
+    counters: Arc<RecorderCounters>,
+}
+
+impl std::fmt::Debug for MarketRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MarketRecorder")
+            .field("dir", &self.dir)
+            .field("rotation", &self.rotation)
+            .field("segments", &self.manifest.segments.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl MarketRecorder {
+    /// Record into `dir`, creating it if needed
+    pub fn new(dir: impl AsRef<Path>, rotation: RotationPolicy) -> Result<Self> {
+        if rotation == RotationPolicy::BySize(0) {
+            return Err(LighterError::InvalidConfiguration(
+                "Segment size limit must be positive".to_string(),
+            ));
+        }
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+        let manifest = Manifest::load(&dir)?;
+        Ok(Self {
+            dir,
+            rotation,
+            flush_interval: Duration::from_secs(DEFAULT_RECORDER_FLUSH_INTERVAL_SECS),
+            manifest,
+            current: None,
+            session: 0,
+            counters: Arc::new(RecorderCounters::default()),
+        })
+    }
+
+    /// How often [`run`](Self::run) flushes the open segment and manifest;
+    /// defaults to 5 seconds
+    pub fn set_flush_interval(&mut self, interval: Duration) {
+        self.flush_interval = interval;
+    }
+
+    /// Counters shared with the recorder, readable while it runs
+    pub fn counters(&self) -> Arc<RecorderCounters> {
+        self.counters.clone()
+    }
+
+    /// Segments written so far, including the open one
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Write one message, rotating first if the policy says so
+    ///
+    /// A `connected` frame starts a new session and is preceded by a
+    /// [`Recorded::SessionStart`] line.
+    pub fn record(&mut self, message: &RawWsMessage) -> Result<()> {
+        let envelope: Option<Envelope> = serde_json::from_str(&message.text).ok();
+        let envelope = envelope.unwrap_or_default();
+
+        if self.needs_rotation(message.received_at) {
+            self.finish_segment()?;
+        }
+        if self.current.is_none() {
+            self.start_segment(message.received_at)?;
+        }
+
+        if envelope.kind.as_deref() == Some("connected") {
+            self.session += 1;
+            self.counters.sessions.fetch_add(1, Ordering::Relaxed);
+            self.write_line(&Recorded::SessionStart {
+                session: self.session,
+                received_at: message.received_at,
+            })?;
+        }
+        self.write_line(&Recorded::Message(message.clone()))?;
+
+        let segment = self.current.as_mut().expect("segment was just started");
+        segment.info.end = message.received_at;
+        segment.info.messages += 1;
+        if let Some(market) = envelope.channel.as_deref().and_then(market_of) {
+            segment.markets.insert(market);
+        }
+        self.counters.messages.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Flush the open segment and update the manifest
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(segment) = self.current.as_mut() else {
+            return Ok(());
+        };
+        segment
+            .encoder
+            .flush()
+            .map_err(|e| io_error(&self.dir, e))?;
+        self.update_manifest()
+    }
+
+    /// Finish the open segment; the recording is complete afterwards
+    pub fn close(mut self) -> Result<()> {
+        self.finish_segment()
+    }
+
+    /// Record messages from a raw tap until its senders are dropped
+    ///
+    /// Flushes every [flush interval](Self::set_flush_interval) and closes
+    /// the recording at the end.
+    pub async fn run(self, frames: mpsc::Receiver<RawWsMessage>) -> Result<()> {
+        self.run_until(frames, CancellationToken::new()).await
+    }
+
+    /// Record messages from a raw tap until its senders are dropped or
+    /// `cancel` is cancelled
+    ///
+    /// On cancellation the frames already in the channel are recorded
+    /// before the recording is closed.
+    pub async fn run_until(
+        mut self,
+        mut frames: mpsc::Receiver<RawWsMessage>,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        let mut flush = tokio::time::interval(self.flush_interval);
+        flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(frame) => self.record(&frame)?,
+                    None => break,
+                },
+                _ = flush.tick() => self.flush()?,
+                _ = cancel.cancelled() => {
+                    while let Ok(frame) = frames.try_recv() {
+                        self.record(&frame)?;
+                    }
+                    break;
+                }
+            }
+        }
+        self.close()
+    }
+
+    fn needs_rotation(&self, received_at: DateTime<Utc>) -> bool {
+        let Some(segment) = &self.current else {
+            return false;
+        };
+        match self.rotation {
+            RotationPolicy::Hourly => {
+                received_at.timestamp().div_euclid(3600)
+                    != segment.info.start.timestamp().div_euclid(3600)
+            }
+            RotationPolicy::BySize(limit) => segment.bytes >= limit,
+        }
+    }
+
+    fn start_segment(&mut self, start: DateTime<Utc>) -> Result<()> {
+        let file = format!(
+            "segment-{:05}-{}.ndjson.gz",
+            self.manifest.segments.len() + 1,
+            start.format("%Y%m%dT%H%M%SZ")
+        );
+        let handle = File::create(self.dir.join(&file)).map_err(|e| io_error(&self.dir, e))?;
+        let info = SegmentInfo {
+            file,
+            start,
+            end: start,
+            messages: 0,
+            markets: Vec::new(),
+        };
+        self.manifest.segments.push(info.clone());
+        self.current = Some(Segment {
+            encoder: GzEncoder::new(handle, Compression::default()),
+            info,
+            markets: BTreeSet::new(),
+            bytes: 0,
+        });
+        self.counters.segments.fetch_add(1, Ordering::Relaxed);
+        self.update_manifest()
+    }
+
+    fn finish_segment(&mut self) -> Result<()> {
+        self.update_manifest()?;
+        if let Some(segment) = self.current.take() {
+            let file = segment
+                .encoder
+                .finish()
+                .map_err(|e| io_error(&self.dir, e))?;
+            file.sync_all().map_err(|e| io_error(&self.dir, e))?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&mut self, record: &Recorded) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        let segment = self.current.as_mut().expect("no open segment");
+        segment
+            .encoder
+            .write_all(&line)
+            .map_err(|e| io_error(&self.dir, e))?;
+        segment.bytes += line.len() as u64;
+        self.counters
+            .bytes
+            .fetch_add(line.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Copy the open segment's totals into the manifest and save it
+    fn update_manifest(&mut self) -> Result<()> {
+        if let Some(segment) = &mut self.current {
+            segment.info.markets = segment.markets.iter().copied().collect();
+            if let Some(entry) = self.manifest.segments.last_mut() {
+                *entry = segment.info.clone();
+            }
+        }
+        self.manifest.save(&self.dir)
+    }
+}
+
+/// Fields of a frame the recorder looks at
+#[derive(Default, Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    channel: Option<String>,
+}
+
+/// Market of an `order_book:{id}` or `trade:{id}` channel
+fn market_of(channel: &str) -> Option<u32> {
+    let channel = normalize_channel(channel);
+    let (kind, id) = channel.split_once(':')?;
+    match kind {
+        "order_book" | "trade" => id.parse().ok(),
+        _ => None,
+    }
+}
+
+fn io_error(dir: &Path, e: std::io::Error) -> LighterError {
+    LighterError::Other(format!("Recording {:?}: {}", dir, e))
+}
+
+/// Reads a recording's segments back in time order
+///
+/// Segments are read as listed in the manifest, ordered by start time. The
+/// segment a running recorder is writing may end early until it is closed.
+pub struct RecordingReader {
+    dir: PathBuf,
+    segments: Vec<SegmentInfo>,
+    next_segment: usize,
+    lines: Option<Lines<BufReader<GzDecoder<File>>>>,
+}
+
+impl RecordingReader {
+    /// Open the recording in `dir`
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut segments = Manifest::load(&dir)?.segments;
+        segments.sort_by(|a, b| a.start.cmp(&b.start).then_with(|| a.file.cmp(&b.file)));
+        Ok(Self {
+            dir,
+            segments,
+            next_segment: 0,
+            lines: None,
+        })
+    }
+
+    /// Segments in reading order
+    pub fn segments(&self) -> &[SegmentInfo] {
+        &self.segments
+    }
+
+    /// Only the recorded messages, without session markers
+    pub fn messages(self) -> impl Iterator<Item = Result<RawWsMessage>> {
+        self.filter_map(|record| match record {
+            Ok(Recorded::Message(message)) => Some(Ok(message)),
+            Ok(Recorded::SessionStart { .. }) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Push every recorded message through [`replay`](crate::ws_client::replay_messages)'s
+    /// parsing; returns the number of messages
+    pub async fn replay<H: WsHandler>(self, handler: &mut H) -> Result<usize> {
+        replay_raw_messages(self.messages(), handler).await
+    }
+
+    fn open_next_segment(&mut self) -> Result<bool> {
+        let Some(segment) = self.segments.get(self.next_segment) else {
+            return Ok(false);
+        };
+        self.next_segment += 1;
+        let file = File::open(self.dir.join(&segment.file)).map_err(|e| io_error(&self.dir, e))?;
+        self.lines = Some(BufReader::new(GzDecoder::new(file)).lines());
+        Ok(true)
+    }
+}
+
+impl Iterator for RecordingReader {
+    type Item = Result<Recorded>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(lines) = &mut self.lines {
+                match lines.next() {
+                    Some(Ok(line)) if line.trim().is_empty() => continue,
+                    Some(Ok(line)) => return Some(serde_json::from_str(&line).map_err(Into::into)),
+                    Some(Err(e)) => {
+                        self.lines = None;
+                        return Some(Err(io_error(&self.dir, e)));
+                    }
+                    None => self.lines = None,
+                }
+            }
+            match self.open_next_segment() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_client::OrderBook;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lighter-rs-recorder-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn frame(at: DateTime<Utc>, text: String) -> RawWsMessage {
+        RawWsMessage {
+            received_at: at,
+            text,
+        }
+    }
+
+    fn book_snapshot(market: u32) -> String {
+        format!(
+            r#"{{"type":"subscribed/order_book","channel":"order_book:{}","order_book":{{"asks":[{{"price":"101.0","size":"1"}}],"bids":[{{"price":"99.0","size":"1"}}]}}}}"#,
+            market
+        )
+    }
+
+    fn book_update(market: u32, seq: u64) -> String {
+        format!(
+            r#"{{"type":"update/order_book","channel":"order_book:{}","order_book":{{"asks":[{{"price":"101.0","size":"{}"}}],"bids":[]}}}}"#,
+            market, seq
+        )
+    }
+
+    #[derive(Default)]
+    struct Books(Vec<(String, OrderBook)>);
+
+    impl WsHandler for Books {
+        fn on_order_book_update(&mut self, market_id: String, order_book: OrderBook) {
+            self.0.push((market_id, order_book));
+        }
+
+        fn on_account_update(&mut self, _account_id: String, _account: serde_json::Value) {}
+    }
+
+    #[tokio::test]
+    async fn test_rotation_and_read_back_in_order() {
+        let dir = temp_dir("size");
+        let mut recorder = MarketRecorder::new(&dir, RotationPolicy::BySize(8 * 1024)).unwrap();
+        let counters = recorder.counters();
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let mut sent = Vec::new();
+        for i in 0..300u64 {
+            let at = start + chrono::Duration::milliseconds(i as i64 * 10);
+            // The connection drops halfway through
+            if i == 0 || i == 150 {
+                sent.push(frame(at, r#"{"type":"connected"}"#.to_string()));
+                sent.push(frame(at, book_snapshot(0)));
+                sent.push(frame(at, book_snapshot(1)));
+            }
+            sent.push(frame(at, book_update((i % 2) as u32, i)));
+        }
+        for message in &sent {
+            recorder.record(message).unwrap();
+        }
+        recorder.close().unwrap();
+
+        assert_eq!(counters.messages(), 306);
+        assert_eq!(counters.sessions(), 2);
+        let manifest = Manifest::load(&dir).unwrap();
+        assert!(manifest.segments.len() > 1);
+        assert_eq!(manifest.segments.len() as u64, counters.segments());
+        assert_eq!(
+            manifest.segments.iter().map(|s| s.messages).sum::<u64>(),
+            306
+        );
+        assert_eq!(manifest.segments[0].markets, vec![0, 1]);
+        assert!(manifest
+            .segments
+            .windows(2)
+            .all(|pair| pair[0].end <= pair[1].start));
+
+        let records: Vec<Recorded> = RecordingReader::open(&dir)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let sessions: Vec<u64> = records
+            .iter()
+            .filter_map(|r| match r {
+                Recorded::SessionStart { session, .. } => Some(*session),
+                Recorded::Message(_) => None,
+            })
+            .collect();
+        assert_eq!(sessions, vec![1, 2]);
+
+        let read: Vec<String> = RecordingReader::open(&dir)
+            .unwrap()
+            .messages()
+            .map(|m| m.unwrap().text)
+            .collect();
+        let expected: Vec<String> = sent.iter().map(|m| m.text.clone()).collect();
+        assert_eq!(read, expected);
+
+        let mut books = Books::default();
+        let replayed = RecordingReader::open(&dir)
+            .unwrap()
+            .replay(&mut books)
+            .await
+            .unwrap();
+        assert_eq!(replayed, 306);
+        assert_eq!(books.0.len(), 304);
+        let (market, book) = books.0.last().unwrap();
+        assert_eq!((market.as_str(), book.asks[0].size.as_str()), ("1", "299"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hourly_rotation_and_run() {
+        let dir = temp_dir("hourly");
+        let recorder = MarketRecorder::new(&dir, RotationPolicy::Hourly).unwrap();
+        let counters = recorder.counters();
+        let (tap, frames) = mpsc::channel(16);
+        let recording = tokio::spawn(recorder.run(frames));
+
+        let start = DateTime::from_timestamp(1_700_002_700, 0).unwrap();
+        for i in 0..10 {
+            let at = start + chrono::Duration::minutes(i * 10);
+            tap.send(frame(at, book_update(3, i as u64))).await.unwrap();
+        }
+        drop(tap);
+        recording.await.unwrap().unwrap();
+
+        // 22:58 to 00:28 UTC spans three hours
+        let manifest = Manifest::load(&dir).unwrap();
+        let counts: Vec<u64> = manifest.segments.iter().map(|s| s.messages).collect();
+        assert_eq!(counts, vec![1, 6, 3]);
+        assert_eq!(counters.messages(), 10);
+        assert_eq!(manifest.segments[1].markets, vec![3]);
+
+        // A second run appends to the same recording
+        let mut recorder = MarketRecorder::new(&dir, RotationPolicy::Hourly).unwrap();
+        recorder
+            .record(&frame(
+                start + chrono::Duration::hours(3),
+                book_update(3, 10),
+            ))
+            .unwrap();
+        recorder.close().unwrap();
+        let reader = RecordingReader::open(&dir).unwrap();
+        assert_eq!(reader.segments().len(), 4);
+        assert_eq!(reader.messages().count(), 11);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_records_buffered_frames() {
+        let dir = temp_dir("cancel");
+        let recorder = MarketRecorder::new(&dir, RotationPolicy::Hourly).unwrap();
+        let (tap, frames) = mpsc::channel(16);
+        let cancel = CancellationToken::new();
+
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for i in 0..5 {
+            tap.send(frame(start, book_update(0, i))).await.unwrap();
+        }
+        cancel.cancel();
+        // The tap stays open; cancellation alone ends the run
+        recorder.run_until(frames, cancel).await.unwrap();
+
+        assert_eq!(RecordingReader::open(&dir).unwrap().messages().count(), 5);
+        drop(tap);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,490 @@
+//! Replay of recorded streams through a client's own handlers
+//!
+//! [`ReplayDriver`] feeds a recording made with
+//! [`MarketRecorder`](crate::recorder::MarketRecorder) through a
+//! [`WsClient`] the way [`WsClient::run`] feeds live frames: its books and
+//! account snapshots are updated and every handler registered on it is
+//! called, in the same order. Nothing is connected. Replay runs as fast as
+//! possible, in real time or at a multiple of it, optionally limited to a
+//! time window. A [`ReplayClock`] given to the driver follows the recorded
+//! receive times; hand clones of it to clients and analyzers (see
+//! [`clock`](crate::clock)) so their time-dependent logic sees the times it
+//! saw live.
+//!
+//! ```rust,no_run
+//! use lighter_rs::clock::ReplayClock;
+//! use lighter_rs::replay::{ReplayDriver, ReplaySpeed};
+//! use lighter_rs::ws_client::WsClient;
+//!
+//! # async fn example() -> lighter_rs::Result<()> {
+//! let ws = WsClient::builder().order_books(vec![0]).build()?;
+//! ws.on_order_book(0, |_, book| println!("best bid {:?}", book.bids.first()));
+//!
+//! let clock = ReplayClock::new(chrono::Utc::now());
+//! let summary = ReplayDriver::open("capture")?
+//!     .speed(ReplaySpeed::Times(10.0))
+//!     .clock(clock.clone())
+//!     .run(&ws, |_, _| {}, |_, _| {})
+//!     .await?;
+//! println!("replayed {} messages", summary.messages);
+//! # Ok(())
+//! # }
+//! ```
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::ReplayClock;
+use crate::errors::{LighterError, Result};
+use crate::recorder::{Recorded, RecordingReader};
+use crate::ws_client::{OrderBook, ReplaySession, WsClient};
+
+/// How fast recorded messages are replayed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReplaySpeed {
+    /// Without pauses
+    #[default]
+    AsFastAsPossible,
+    /// With the recorded gaps between messages
+    RealTime,
+    /// With the recorded gaps divided by this factor
+    Times(f64),
+}
+
+impl ReplaySpeed {
+    /// Factor the recorded gaps are divided by, `None` without pauses
+    fn factor(self) -> Result<Option<f64>> {
+        match self {
+            ReplaySpeed::AsFastAsPossible => Ok(None),
+            ReplaySpeed::RealTime => Ok(Some(1.0)),
+            ReplaySpeed::Times(factor) if factor.is_finite() && factor > 0.0 => Ok(Some(factor)),
+            ReplaySpeed::Times(factor) => Err(LighterError::InvalidConfiguration(format!(
+                "Replay speed must be positive and finite, got {}",
+                factor
+            ))),
+        }
+    }
+}
+
+/// What a replay went through
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplaySummary {
+    /// Messages delivered to the callbacks
+    pub messages: usize,
+    /// Messages before the start time, applied to state only
+    pub fast_forwarded: usize,
+    /// Recorded connections
+    pub sessions: usize,
+    /// Receive time of the last message applied
+    pub last_received_at: Option<DateTime<Utc>>,
+}
+
+type Records = Box<dyn Iterator<Item = Result<Recorded>> + Send>;
+
+/// Replays a recording through a [`WsClient`]
+pub struct ReplayDriver {
+    records: Records,
+    speed: ReplaySpeed,
+    start: Option<DateTime<Utc>>,
+    stop: Option<DateTime<Utc>>,
+    clock: Option<ReplayClock>,
+}
+
+impl std::fmt::Debug for ReplayDriver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayDriver")
+            .field("speed", &self.speed)
+            .field("start", &self.start)
+            .field("stop", &self.stop)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ReplayDriver {
+    /// Replay the recording in `dir`
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_records(RecordingReader::open(dir)?))
+    }
+
+    /// Replay records read elsewhere, in the given order
+    pub fn from_records<I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = Result<Recorded>>,
+        I::IntoIter: Send + 'static,
+    {
+        Self {
+            records: Box::new(records.into_iter()),
+            speed: ReplaySpeed::default(),
+            start: None,
+            stop: None,
+            clock: None,
+        }
+    }
+
+    /// Pacing of the replay (default as fast as possible)
+    pub fn speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Deliver only messages received at or after `start`
+    ///
+    /// Earlier messages are still applied to books and accounts, without
+    /// callbacks or pauses, so the replay starts from the state the live
+    /// client had.
+    pub fn start_at(mut self, start: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// End the replay at the first message received at or after `stop`
+    pub fn stop_at(mut self, stop: DateTime<Utc>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Set `clock` to each message's receive time before it is applied
+    pub fn clock(mut self, clock: ReplayClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Replay into `ws`, calling its registered handlers and these callbacks
+    /// as [`WsClient::run`] would
+    ///
+    /// Each session marker of the recording starts over like a reconnect.
+    /// Fails on unreadable records and on callback errors the client's
+    /// [`CallbackErrorPolicy`](crate::ws_client::CallbackErrorPolicy)
+    /// doesn't log and skip.
+    pub async fn run<F1, F2>(
+        self,
+        ws: &WsClient,
+        on_order_book_update: F1,
+        on_account_update: F2,
+    ) -> Result<ReplaySummary>
+    where
+        F1: Fn(String, OrderBook) + Send + Sync + 'static,
+        F2: Fn(String, Value) + Send + Sync + 'static,
+    {
+        let factor = self.speed.factor()?;
+        let on_order_book_update = Arc::new(move |market_id, order_book| {
+            on_order_book_update(market_id, order_book);
+            Ok::<(), LighterError>(())
+        });
+        let on_account_update = Arc::new(move |account_id, account| {
+            on_account_update(account_id, account);
+            Ok::<(), LighterError>(())
+        });
+
+        let mut summary = ReplaySummary::default();
+        let mut session: Option<ReplaySession<'_>> = None;
+        // Recorded and local time of the first delivered message
+        let mut anchor: Option<(DateTime<Utc>, tokio::time::Instant)> = None;
+        for record in self.records {
+            let message = match record? {
+                Recorded::SessionStart { .. } => {
+                    if let Some(session) = session.take() {
+                        session.finish(&on_order_book_update).await?;
+                    }
+                    session = Some(ws.replay_session());
+                    summary.sessions += 1;
+                    continue;
+                }
+                Recorded::Message(message) => message,
+            };
+            let at = message.received_at;
+            if self.stop.is_some_and(|stop| at >= stop) {
+                break;
+            }
+
+            let deliver = self.start.is_none_or(|start| at >= start);
+            if let (true, Some(factor)) = (deliver, factor) {
+                let (first_at, started) = *anchor.get_or_insert((at, tokio::time::Instant::now()));
+                let offset = (at - first_at).to_std().unwrap_or(Duration::ZERO);
+                tokio::time::sleep_until(started + offset.div_f64(factor)).await;
+            }
+            if let Some(clock) = &self.clock {
+                clock.set(at);
+            }
+
+            // A recording may start without a session marker
+            if session.is_none() {
+                session = Some(ws.replay_session());
+                summary.sessions += 1;
+            }
+            if let Some(session) = &session {
+                session
+                    .feed(
+                        &message.text,
+                        deliver,
+                        &on_order_book_update,
+                        &on_account_update,
+                    )
+                    .await?;
+            }
+            if deliver {
+                summary.messages += 1;
+            } else {
+                summary.fast_forwarded += 1;
+            }
+            summary.last_received_at = Some(at);
+        }
+
+        if let Some(session) = session {
+            session.finish(&on_order_book_update).await?;
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use crate::recorder::{MarketRecorder, RotationPolicy};
+    use crate::test_support::{spawn_mock_ws_server, test_client_for};
+    use crate::ws_client::{AccountEvent, PriceLevel, RawWsMessage, WsClientBuilder};
+    use std::sync::Mutex;
+
+    const T0_MS: i64 = 1_700_000_000_000;
+
+    fn at(step: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(T0_MS + step * 100).unwrap()
+    }
+
+    /// A connection's frames, 100ms apart
+    fn fixture() -> Vec<String> {
+        [
+            r#"{"type":"connected"}"#,
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101","size":"1"}],"bids":[{"price":"99","size":"2"}]}}"#,
+            r#"{"type":"subscribed/account_all","channel":"account_all:7","orders":{}}"#,
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101","size":"3"}],"bids":[]}}"#,
+            r#"{"type":"update/account_all","channel":"account_all:7","orders":{"0":[{"order_index":5,"client_order_index":11,"market_index":0,"is_ask":false,"price":"99","remaining_base_amount":"1"}]}}"#,
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[{"price":"99","size":"0"}]}}"#,
+            r#"{"type":"update/account_all","channel":"account_all:7","orders":{"0":[{"order_index":5,"client_order_index":11,"market_index":0,"is_ask":false,"price":"99","remaining_base_amount":"0","status":"filled"}]}}"#,
+        ]
+        .map(String::from)
+        .to_vec()
+    }
+
+    fn records() -> Vec<Result<Recorded>> {
+        let messages = fixture().into_iter().enumerate().map(|(step, text)| {
+            Ok(Recorded::Message(RawWsMessage {
+                received_at: at(step as i64),
+                text,
+            }))
+        });
+        std::iter::once(Ok(Recorded::SessionStart {
+            session: 1,
+            received_at: at(0),
+        }))
+        .chain(messages)
+        .collect()
+    }
+
+    /// Client logging its handler calls into a shared log
+    fn logged_client(builder: WsClientBuilder) -> (WsClient, Arc<Mutex<Vec<String>>>) {
+        let ws = builder
+            .order_books(vec![0])
+            .accounts(vec![7])
+            .build()
+            .unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let books = log.clone();
+        ws.on_order_book(0, move |_, book| {
+            let best = |levels: &[PriceLevel]| {
+                levels
+                    .first()
+                    .map_or("-".to_string(), |l| format!("{}x{}", l.price, l.size))
+            };
+            books
+                .lock()
+                .unwrap()
+                .push(format!("book {} / {}", best(&book.bids), best(&book.asks)));
+        });
+        let events = log.clone();
+        ws.on_account_event(move |_, event| {
+            let name = match event {
+                AccountEvent::OrderPlaced { order_index, .. } => format!("placed {}", order_index),
+                AccountEvent::OrderFilled { order_index, .. } => format!("filled {}", order_index),
+                other => format!("{:?}", other),
+            };
+            events.lock().unwrap().push(name);
+        });
+        (ws, log)
+    }
+
+    fn callbacks(
+        log: &Arc<Mutex<Vec<String>>>,
+    ) -> (
+        impl Fn(String, OrderBook) + Send + Sync + 'static,
+        impl Fn(String, Value) + Send + Sync + 'static,
+    ) {
+        let books = log.clone();
+        let accounts = log.clone();
+        (
+            move |market_id, _| {
+                books
+                    .lock()
+                    .unwrap()
+                    .push(format!("run book {}", market_id))
+            },
+            move |account_id, _| {
+                accounts
+                    .lock()
+                    .unwrap()
+                    .push(format!("run account {}", account_id))
+            },
+        )
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lighter-rs-replay-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_replay_reproduces_live_callbacks_and_state() {
+        let expected = [
+            "book 99x2 / 101x1",
+            "run book 0",
+            "run account 7",
+            "book 99x2 / 101x3",
+            "run book 0",
+            "placed 5",
+            "run account 7",
+            "book - / 101x3",
+            "run book 0",
+            "filled 5",
+            "run account 7",
+        ];
+
+        // Live, from a server sending the fixture
+        let addr = spawn_mock_ws_server(fixture()).await;
+        let (live, live_log) =
+            logged_client(WsClient::builder().url(format!("ws://{}/stream", addr)));
+        let (on_book, on_account) = callbacks(&live_log);
+        live.run(on_book, on_account).await.unwrap();
+        assert_eq!(*live_log.lock().unwrap(), expected);
+
+        // Replayed from a recording of the same frames
+        let dir = temp_dir("live");
+        let mut recorder = MarketRecorder::new(&dir, RotationPolicy::Hourly).unwrap();
+        for (step, text) in fixture().into_iter().enumerate() {
+            recorder
+                .record(&RawWsMessage {
+                    received_at: at(step as i64),
+                    text,
+                })
+                .unwrap();
+        }
+        recorder.close().unwrap();
+
+        let (ws, log) = logged_client(WsClient::builder());
+        let clock = ReplayClock::new(Utc::now());
+        let mut tx = test_client_for("", 7, 0);
+        tx.set_clock(Arc::new(clock.clone()));
+
+        let (on_book, on_account) = callbacks(&log);
+        let summary = ReplayDriver::open(&dir)
+            .unwrap()
+            .clock(clock.clone())
+            .run(&ws, on_book, on_account)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ReplaySummary {
+                messages: 7,
+                fast_forwarded: 0,
+                sessions: 1,
+                last_received_at: Some(at(6)),
+            }
+        );
+        assert_eq!(*log.lock().unwrap(), expected);
+
+        let book = ws.get_order_book("0").await.unwrap();
+        assert!(book.bids.is_empty());
+        assert_eq!(
+            (book.asks[0].price.as_str(), book.asks[0].size.as_str()),
+            ("101", "3")
+        );
+        let account = ws.get_account("7").await.unwrap();
+        assert!(account.orders.values().all(Vec::is_empty));
+        // Time-dependent logic saw the recorded time
+        assert_eq!(clock.now(), at(6));
+        assert_eq!(tx.now_ms(), at(6).timestamp_millis());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_replay_window_fast_forwards_and_stops() {
+        let (ws, log) = logged_client(WsClient::builder());
+        let clock = ReplayClock::new(Utc::now());
+        let (on_book, on_account) = callbacks(&log);
+        let summary = ReplayDriver::from_records(records())
+            .start_at(at(3))
+            .stop_at(at(6))
+            .clock(clock.clone())
+            .run(&ws, on_book, on_account)
+            .await
+            .unwrap();
+
+        assert_eq!(summary.messages, 3);
+        assert_eq!(summary.fast_forwarded, 3);
+        assert_eq!(summary.last_received_at, Some(at(5)));
+        assert_eq!(clock.now(), at(5));
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "book 99x2 / 101x3",
+                "run book 0",
+                "placed 5",
+                "run account 7",
+                "book - / 101x3",
+                "run book 0",
+            ]
+        );
+        // The fill after the window was never applied
+        let account = ws.get_account("7").await.unwrap();
+        assert_eq!(account.orders["0"].len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replay_speed() {
+        async fn elapsed(speed: ReplaySpeed) -> Result<Duration> {
+            let ws = WsClient::builder().order_books(vec![0]).build().unwrap();
+            let started = tokio::time::Instant::now();
+            ReplayDriver::from_records(records())
+                .speed(speed)
+                .run(&ws, |_, _| {}, |_, _| {})
+                .await?;
+            Ok(started.elapsed())
+        }
+
+        assert_eq!(
+            elapsed(ReplaySpeed::AsFastAsPossible).await.unwrap(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            elapsed(ReplaySpeed::RealTime).await.unwrap(),
+            Duration::from_millis(600)
+        );
+        assert_eq!(
+            elapsed(ReplaySpeed::Times(4.0)).await.unwrap(),
+            Duration::from_millis(150)
+        );
+        for factor in [0.0, -1.0, f64::NAN] {
+            assert!(matches!(
+                elapsed(ReplaySpeed::Times(factor)).await,
+                Err(LighterError::InvalidConfiguration(_))
+            ));
+        }
+    }
+}
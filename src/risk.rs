@@ -0,0 +1,492 @@
+//! Circuit breaker that stops submissions after repeated failures
+//!
+//! A [`CircuitBreaker`] is closed while submissions succeed. After
+//! `failure_threshold` failures in a row it opens and refuses everything
+//! with [`LighterError::CircuitOpen`] until the cool-down has passed. It then
+//! lets `half_open_probes` submissions through: if they all succeed it
+//! closes again, and the first failure opens it for another cool-down.
+//!
+//! [`TxClient::set_circuit_breaker`](crate::client::TxClient::set_circuit_breaker)
+//! makes `send_transaction` consult the breaker and record each outcome
+//! through an [`Admission`], which gives its probe back if the send is
+//! dropped. Strategies can feed in other signals with
+//! [`record_success`](CircuitBreaker::record_success) and
+//! [`record_failure`](CircuitBreaker::record_failure).
+//!
+//! ```
+//! use lighter_rs::risk::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+//!
+//! let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+//!     failure_threshold: 2,
+//!     ..Default::default()
+//! })?;
+//! breaker.record_failure();
+//! breaker.record_failure();
+//! assert_eq!(breaker.state(), CircuitState::Open);
+//! assert!(breaker.check().is_err());
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{SharedClock, SystemClock};
+use crate::constants::{
+    DEFAULT_CIRCUIT_COOL_DOWN_MS, DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+    DEFAULT_CIRCUIT_HALF_OPEN_PROBES,
+};
+use crate::errors::{LighterError, Result};
+
+/// Thresholds of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Failures in a row that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before probing
+    pub cool_down: Duration,
+    /// Submissions let through while half-open; all must succeed to close
+    pub half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+            cool_down: Duration::from_millis(DEFAULT_CIRCUIT_COOL_DOWN_MS),
+            half_open_probes: DEFAULT_CIRCUIT_HALF_OPEN_PROBES,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    fn validate(&self) -> Result<()> {
+        if self.failure_threshold == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "Circuit breaker failure threshold must be positive".to_string(),
+            ));
+        }
+        if self.half_open_probes == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "Circuit breaker half-open probe budget must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// Submissions go through
+    Closed,
+    /// Submissions are refused until the cool-down has passed
+    Open,
+    /// A limited number of probe submissions go through
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    state: CircuitState,
+    /// Failures in a row while closed
+    failures: u32,
+    opened_at: DateTime<Utc>,
+    /// Probes admitted and not yet recorded
+    probes_in_flight: u32,
+    probe_successes: u32,
+    /// Times the circuit opened, so that outcomes and probes of admissions
+    /// from before the latest opening are ignored
+    round: u64,
+}
+
+/// Three-state circuit breaker, shared behind an `Arc`
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    clock: SharedClock,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            clock: Arc::new(SystemClock),
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                failures: 0,
+                opened_at: DateTime::UNIX_EPOCH,
+                probes_in_flight: 0,
+                probe_successes: 0,
+                round: 0,
+            }),
+        })
+    }
+
+    /// Time the cool-down by `clock`
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn config(&self) -> &CircuitBreakerConfig {
+        &self.config
+    }
+
+    /// Current state; an open circuit past its cool-down reads as half-open
+    pub fn state(&self) -> CircuitState {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        state.state
+    }
+
+    /// Admit a submission, or fail with [`LighterError::CircuitOpen`]
+    ///
+    /// While half-open, each admitted submission takes a probe until its
+    /// outcome is recorded; use [`admit`](Self::admit) to have it given back
+    /// if that never happens.
+    pub fn check(&self) -> Result<()> {
+        self.take_probe().map(|_| ())
+    }
+
+    /// Admit a submission whose outcome is recorded through the returned
+    /// [`Admission`]; dropped unrecorded, it gives its probe back
+    ///
+    /// An outcome recorded after the circuit opened again is ignored, as it
+    /// says nothing about the server since then.
+    pub fn admit(&self) -> Result<Admission<'_>> {
+        let (round, probe) = self.take_probe()?;
+        Ok(Admission {
+            breaker: self,
+            round,
+            probe,
+            recorded: false,
+        })
+    }
+
+    /// Current round, and whether a half-open probe was taken
+    fn take_probe(&self) -> Result<(u64, bool)> {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        match state.state {
+            CircuitState::Closed => Ok((state.round, false)),
+            CircuitState::Open => {
+                let open_for = (self.clock.now() - state.opened_at)
+                    .to_std()
+                    .unwrap_or_default();
+                Err(LighterError::CircuitOpen {
+                    retry_after: Some(self.config.cool_down.saturating_sub(open_for)),
+                })
+            }
+            CircuitState::HalfOpen => {
+                let probes = state.probes_in_flight + state.probe_successes;
+                if probes >= self.config.half_open_probes {
+                    // Probes are out; their outcome decides
+                    return Err(LighterError::CircuitOpen { retry_after: None });
+                }
+                state.probes_in_flight += 1;
+                Ok((state.round, true))
+            }
+        }
+    }
+
+    /// Record a success; enough of them while half-open close the circuit
+    ///
+    /// Ignored while open.
+    pub fn record_success(&self) {
+        self.record_success_in(None);
+    }
+
+    /// [`record_success`](Self::record_success), ignored unless `round` is
+    /// current
+    fn record_success_in(&self, round: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        if round.is_some_and(|round| round != state.round) {
+            return;
+        }
+        match state.state {
+            CircuitState::Closed => state.failures = 0,
+            CircuitState::Open => {}
+            CircuitState::HalfOpen => {
+                state.probes_in_flight = state.probes_in_flight.saturating_sub(1);
+                state.probe_successes += 1;
+                if state.probe_successes >= self.config.half_open_probes {
+                    state.state = CircuitState::Closed;
+                    state.failures = 0;
+                }
+            }
+        }
+    }
+
+    /// Record a failure; opens the circuit at the threshold, or right away
+    /// while half-open
+    ///
+    /// While open, restarts the cool-down.
+    pub fn record_failure(&self) {
+        self.record_failure_in(None);
+    }
+
+    /// [`record_failure`](Self::record_failure), ignored unless `round` is
+    /// current
+    fn record_failure_in(&self, round: Option<u64>) {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        if round.is_some_and(|round| round != state.round) {
+            return;
+        }
+        match state.state {
+            CircuitState::Closed => {
+                state.failures += 1;
+                if state.failures >= self.config.failure_threshold {
+                    self.open(&mut state);
+                }
+            }
+            CircuitState::Open | CircuitState::HalfOpen => self.open(&mut state),
+        }
+    }
+
+    /// Return a probe of `round` whose outcome wasn't recorded
+    fn release(&self, round: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.state == CircuitState::HalfOpen && state.round == round {
+            state.probes_in_flight = state.probes_in_flight.saturating_sub(1);
+        }
+    }
+
+    fn open(&self, state: &mut BreakerState) {
+        state.state = CircuitState::Open;
+        state.opened_at = self.clock.now();
+        state.round += 1;
+        state.failures = 0;
+        state.probes_in_flight = 0;
+        state.probe_successes = 0;
+    }
+
+    /// Move an open circuit past its cool-down to half-open
+    fn refresh(&self, state: &mut BreakerState) {
+        if state.state == CircuitState::Open {
+            let open_for = (self.clock.now() - state.opened_at)
+                .to_std()
+                .unwrap_or_default();
+            if open_for >= self.config.cool_down {
+                state.state = CircuitState::HalfOpen;
+            }
+        }
+    }
+}
+
+/// A submission admitted by [`CircuitBreaker::admit`]
+///
+/// Dropped without recording an outcome, e.g. when the submission is
+/// cancelled or rejected before reaching the server, it gives its half-open
+/// probe back so the breaker doesn't wait for it forever. Outcomes and
+/// probes of an admission from before the circuit last opened are ignored.
+#[derive(Debug)]
+pub struct Admission<'a> {
+    breaker: &'a CircuitBreaker,
+    /// Round admitted in
+    round: u64,
+    /// Whether a half-open probe was taken
+    probe: bool,
+    recorded: bool,
+}
+
+impl Admission<'_> {
+    pub fn record_success(mut self) {
+        self.recorded = true;
+        self.breaker.record_success_in(Some(self.round));
+    }
+
+    pub fn record_failure(mut self) {
+        self.recorded = true;
+        self.breaker.record_failure_in(Some(self.round));
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if !self.recorded && self.probe {
+            self.breaker.release(self.round);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ReplayClock;
+
+    const COOL_DOWN: Duration = Duration::from_secs(30);
+
+    fn breaker(probes: u32) -> (CircuitBreaker, Arc<ReplayClock>) {
+        let clock = Arc::new(ReplayClock::new(
+            DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        ));
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 3,
+            cool_down: COOL_DOWN,
+            half_open_probes: probes,
+        })
+        .unwrap()
+        .with_clock(clock.clone());
+        (breaker, clock)
+    }
+
+    fn retry_after(breaker: &CircuitBreaker) -> Option<Duration> {
+        match breaker.check() {
+            Err(LighterError::CircuitOpen { retry_after }) => retry_after,
+            other => panic!("expected an open circuit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_closed_opens_at_threshold() {
+        let (breaker, clock) = breaker(1);
+        breaker.record_failure();
+        breaker.record_failure();
+        // A success resets the count
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.check().is_ok());
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(retry_after(&breaker), Some(COOL_DOWN));
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(retry_after(&breaker), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_open_ignores_successes_and_restarts_on_failure() {
+        let (breaker, clock) = breaker(1);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(Duration::from_secs(20));
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        breaker.record_failure();
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(retry_after(&breaker), Some(Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn test_half_open_probes_close_after_budget() {
+        let (breaker, clock) = breaker(2);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(COOL_DOWN);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+        // Budget used up until the probes report back
+        assert_eq!(retry_after(&breaker), None);
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(retry_after(&breaker), None);
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // Closed again with a fresh failure count
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_probe_failure_reopens() {
+        let (breaker, clock) = breaker(2);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(COOL_DOWN);
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(retry_after(&breaker), Some(COOL_DOWN));
+        clock.advance(COOL_DOWN);
+        // A new round of probes starts from scratch
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_err());
+    }
+
+    #[test]
+    fn test_dropped_admission_returns_probe() {
+        let (breaker, clock) = breaker(1);
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(COOL_DOWN);
+        let probe = breaker.admit().unwrap();
+        assert!(breaker.check().is_err());
+        drop(probe);
+        let probe = breaker.admit().unwrap();
+        probe.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // A probe of an earlier round doesn't free one of the next
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(COOL_DOWN);
+        let stale = breaker.admit().unwrap();
+        breaker.record_failure();
+        clock.advance(COOL_DOWN);
+        assert!(breaker.check().is_ok());
+        drop(stale);
+        assert_eq!(retry_after(&breaker), None);
+    }
+
+    #[test]
+    fn test_stale_admission_outcomes_ignored() {
+        let (breaker, clock) = breaker(1);
+        let before_open = breaker.admit().unwrap();
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        clock.advance(COOL_DOWN);
+        let probe = breaker.admit().unwrap();
+        // Admitted while closed, so it can't stand in for the probe
+        before_open.record_success();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(retry_after(&breaker), None);
+
+        // A probe of an earlier round neither closes nor reopens this one
+        breaker.record_failure();
+        clock.advance(COOL_DOWN);
+        let stale = probe;
+        let probe = breaker.admit().unwrap();
+        stale.record_failure();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        probe.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let zero_threshold = CircuitBreakerConfig {
+            failure_threshold: 0,
+            ..Default::default()
+        };
+        assert!(CircuitBreaker::new(zero_threshold).is_err());
+        let zero_probes = CircuitBreakerConfig {
+            half_open_probes: 0,
+            ..Default::default()
+        };
+        assert!(CircuitBreaker::new(zero_probes).is_err());
+    }
+}
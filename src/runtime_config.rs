@@ -0,0 +1,291 @@
+//! Settings that can change while clients run
+//!
+//! [`ClientRuntimeConfig`] groups the limits a long-running strategy tunes
+//! without restarting: order throttle rates, the validity a transaction must
+//! have left, and order book notify policies. A [`RuntimeConfig`] holds the
+//! current value; [`TxClient`](crate::client::TxClient) and
+//! [`WsClient`](crate::ws_client::WsClient) read it on every use, so an update
+//! applies from the next order or book update on.
+//!
+//! ```
+//! use lighter_rs::runtime_config::RuntimeConfig;
+//! use lighter_rs::ws_client::NotifyPolicy;
+//!
+//! let config = RuntimeConfig::default();
+//! config.update(|c| c.notify_policy = NotifyPolicy::BestOnly)?;
+//! assert_eq!(config.get().notify_policy, NotifyPolicy::BestOnly);
+//! # Ok::<(), lighter_rs::errors::LighterError>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc;
+
+use crate::constants::{DEFAULT_MIN_REMAINING_VALIDITY_MS, DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS};
+use crate::errors::{LighterError, Result};
+use crate::tasks::{ShutdownReport, TaskSet};
+use crate::throttle::ThrottleConfig;
+use crate::ws_client::NotifyPolicy;
+
+/// Client settings that take effect without a restart
+///
+/// Fields missing from a deserialized config take their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientRuntimeConfig {
+    /// Rates of the order throttle attached to a `TxClient`, `None` to keep
+    /// those it was created with
+    pub order_throttle: Option<ThrottleConfig>,
+    /// Validity in milliseconds a transaction must have left when it is
+    /// signed and sent, `None` to disable the check
+    pub min_remaining_validity_ms: Option<u64>,
+    /// Which order book updates invoke `WsClient` callbacks
+    pub notify_policy: NotifyPolicy,
+    /// Overrides of `notify_policy` by market
+    pub market_notify_policies: HashMap<u32, NotifyPolicy>,
+}
+
+impl Default for ClientRuntimeConfig {
+    fn default() -> Self {
+        Self {
+            order_throttle: None,
+            min_remaining_validity_ms: Some(DEFAULT_MIN_REMAINING_VALIDITY_MS as u64),
+            notify_policy: NotifyPolicy::default(),
+            market_notify_policies: HashMap::new(),
+        }
+    }
+}
+
+impl ClientRuntimeConfig {
+    /// Fail on settings no client can run with
+    pub fn validate(&self) -> Result<()> {
+        if let Some(throttle) = &self.order_throttle {
+            throttle.validate()?;
+        }
+        let no_levels = std::iter::once(&self.notify_policy)
+            .chain(self.market_notify_policies.values())
+            .any(|policy| *policy == NotifyPolicy::TopN(0));
+        if no_levels {
+            return Err(LighterError::InvalidConfiguration(
+                "NotifyPolicy::TopN needs at least one level".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// [`min_remaining_validity_ms`](Self::min_remaining_validity_ms) as a duration
+    pub fn min_remaining_validity(&self) -> Option<Duration> {
+        self.min_remaining_validity_ms.map(Duration::from_millis)
+    }
+
+    /// Notify policy of a market
+    pub fn notify_policy_for(&self, market_id: u32) -> NotifyPolicy {
+        self.market_notify_policies
+            .get(&market_id)
+            .copied()
+            .unwrap_or(self.notify_policy)
+    }
+}
+
+/// Shared, swappable [`ClientRuntimeConfig`]
+///
+/// Clones share the value; give the same handle to several clients to tune
+/// them together.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeConfig {
+    current: Arc<RwLock<Arc<ClientRuntimeConfig>>>,
+}
+
+impl RuntimeConfig {
+    /// Hold `config`, which must be valid
+    pub fn new(config: ClientRuntimeConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            current: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    /// The current settings
+    pub fn get(&self) -> Arc<ClientRuntimeConfig> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Change the settings with `f`
+    ///
+    /// Nothing changes if the result is invalid.
+    pub fn update(&self, f: impl FnOnce(&mut ClientRuntimeConfig)) -> Result<()> {
+        let mut current = self.current.write().unwrap();
+        let mut config = ClientRuntimeConfig::clone(&current);
+        f(&mut config);
+        config.validate()?;
+        *current = Arc::new(config);
+        Ok(())
+    }
+
+    /// Replace the settings; nothing changes if `config` is invalid
+    pub fn replace(&self, config: ClientRuntimeConfig) -> Result<()> {
+        self.update(|current| *current = config)
+    }
+
+    /// Load the settings from a JSON file whenever it changes
+    ///
+    /// The file is checked every `interval` and read when its modification
+    /// time or size changed, including once at the start. It holds a whole
+    /// [`ClientRuntimeConfig`]; a file that can't be read, parsed or
+    /// validated changes nothing and is reported by the watcher.
+    pub fn watch_file(&self, path: impl Into<PathBuf>, interval: Duration) -> ConfigWatcher {
+        let (tx, results) = mpsc::unbounded_channel();
+        let mut tasks = TaskSet::new();
+        tasks.spawn(
+            "config_watcher",
+            watch(self.clone(), path.into(), interval, tx),
+        );
+        ConfigWatcher { results, tasks }
+    }
+
+    fn load(&self, path: &Path) -> Result<()> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            LighterError::InvalidConfiguration(format!("Can't read {}: {}", path.display(), e))
+        })?;
+        let config: ClientRuntimeConfig = serde_json::from_str(&text).map_err(|e| {
+            LighterError::InvalidConfiguration(format!("Can't parse {}: {}", path.display(), e))
+        })?;
+        self.replace(config)
+    }
+}
+
+/// Outcome of each reload of a watched config file
+///
+/// Dropping it stops watching.
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    results: mpsc::UnboundedReceiver<Result<()>>,
+    tasks: TaskSet,
+}
+
+impl ConfigWatcher {
+    /// Wait for the next reload: `Ok` once applied, the error otherwise
+    pub async fn next(&mut self) -> Option<Result<()>> {
+        self.results.recv().await
+    }
+
+    /// Stop watching and wait for the watcher to finish
+    pub async fn shutdown(self) -> ShutdownReport {
+        self.tasks
+            .shutdown(Duration::from_millis(DEFAULT_TASK_SHUTDOWN_TIMEOUT_MS))
+            .await
+    }
+}
+
+async fn watch(
+    config: RuntimeConfig,
+    path: PathBuf,
+    interval: Duration,
+    results: mpsc::UnboundedSender<Result<()>>,
+) -> Result<()> {
+    // Modification time and size, `None` while the file is missing
+    let mut seen: Option<Option<(Option<SystemTime>, u64)>> = None;
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let version = std::fs::metadata(&path)
+            .map(|meta| (meta.modified().ok(), meta.len()))
+            .ok();
+        if seen == Some(version) {
+            continue;
+        }
+        seen = Some(version);
+        if results.send(config.load(&path)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::throttle::ThrottleMode;
+
+    #[test]
+    fn test_invalid_updates_change_nothing() {
+        let config = RuntimeConfig::default();
+        config
+            .update(|c| {
+                c.market_notify_policies.insert(3, NotifyPolicy::TopN(5));
+            })
+            .unwrap();
+        assert_eq!(config.get().notify_policy_for(3), NotifyPolicy::TopN(5));
+        assert_eq!(config.get().notify_policy_for(4), NotifyPolicy::Always);
+
+        let before = config.get();
+        assert!(config
+            .update(|c| c.notify_policy = NotifyPolicy::TopN(0))
+            .is_err());
+        let zero_rate = ThrottleConfig {
+            orders_per_sec: 0.0,
+            ..Default::default()
+        };
+        assert!(config
+            .update(|c| c.order_throttle = Some(zero_rate))
+            .is_err());
+        assert_eq!(config.get(), before);
+    }
+
+    #[test]
+    fn test_partial_json_takes_defaults() {
+        let config: ClientRuntimeConfig = serde_json::from_str(
+            r#"{
+                "order_throttle": {"orders_per_sec": 5.0, "burst": 2, "mode": "fail_fast", "prevent_self_cross": false},
+                "market_notify_policies": {"1": {"top_n": 3}, "2": "best_only"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.order_throttle.unwrap().mode, ThrottleMode::FailFast);
+        assert_eq!(config.notify_policy_for(1), NotifyPolicy::TopN(3));
+        assert_eq!(config.notify_policy_for(2), NotifyPolicy::BestOnly);
+        assert_eq!(
+            config.min_remaining_validity_ms,
+            ClientRuntimeConfig::default().min_remaining_validity_ms
+        );
+    }
+
+    /// Replace the file whole, so the watcher never reads it half written
+    fn write(path: &Path, text: &str) {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text).unwrap();
+        std::fs::rename(&tmp, path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_watch_file_applies_whole_files_only() {
+        let path = std::env::temp_dir().join(format!(
+            "lighter-rs-runtime-config-{}.json",
+            std::process::id()
+        ));
+        write(&path, r#"{"min_remaining_validity_ms": 1500}"#);
+        let config = RuntimeConfig::default();
+        let mut watcher = config.watch_file(&path, Duration::from_millis(10));
+
+        watcher.next().await.unwrap().unwrap();
+        assert_eq!(config.get().min_remaining_validity_ms, Some(1500));
+
+        // Truncated mid-write: reported, previous settings kept
+        write(&path, r#"{"min_remaining_validity_ms": 2500, "notify_po"#);
+        assert!(watcher.next().await.unwrap().is_err());
+        assert_eq!(config.get().min_remaining_validity_ms, Some(1500));
+
+        write(
+            &path,
+            r#"{"min_remaining_validity_ms": null, "notify_policy": "best_only"}"#,
+        );
+        watcher.next().await.unwrap().unwrap();
+        assert_eq!(config.get().min_remaining_validity_ms, None);
+        assert_eq!(config.get().notify_policy, NotifyPolicy::BestOnly);
+        assert!(watcher.shutdown().await.is_clean());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
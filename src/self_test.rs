@@ -0,0 +1,504 @@
+//! Startup checks of credentials and connectivity
+//!
+//! [`TxClient::self_test`] runs a fixed sequence of read-only checks and
+//! reports each one with a hint on how to fix it: the REST API answers, the
+//! account and API key index exist, the private key matches the registered
+//! public key, the stream delivers data, and an order can be signed. Nothing
+//! is ever sent to the chain.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::client::TxClient;
+use crate::constants::*;
+use crate::errors::{LighterError, Result};
+use crate::types::{AccountIndex, ApiKeyIndex, CreateOrderTxReq, TransactOpts};
+use crate::ws_client::WsClient;
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Passed,
+    Failed,
+    /// Not run, e.g. no WebSocket URL was given
+    Skipped,
+}
+
+/// One check of a [`SelfTestReport`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub status: StepStatus,
+    /// What was found, or the error
+    pub detail: String,
+    /// How to fix a failure
+    pub hint: Option<&'static str>,
+}
+
+/// Results of [`TxClient::self_test`], one step per check in order
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// Whether no step failed; skipped steps don't count as failures
+    pub fn passed(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| step.status != StepStatus::Failed)
+    }
+
+    /// Step with the given name
+    pub fn step(&self, name: &str) -> Option<&SelfTestStep> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    /// Steps that failed
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestStep> {
+        self.steps
+            .iter()
+            .filter(|step| step.status == StepStatus::Failed)
+    }
+
+    pub(crate) fn push(&mut self, name: &'static str, outcome: Result<String>, hint: &'static str) {
+        self.steps.push(match outcome {
+            Ok(detail) => SelfTestStep {
+                name,
+                status: StepStatus::Passed,
+                detail,
+                hint: None,
+            },
+            Err(e) => SelfTestStep {
+                name,
+                status: StepStatus::Failed,
+                detail: e.to_string(),
+                hint: Some(hint),
+            },
+        });
+    }
+
+    /// Report for a client that couldn't be built, with all checks skipped
+    pub(crate) fn from_build_error(error: LighterError) -> Self {
+        let mut report = Self::default();
+        let key_error = matches!(
+            error,
+            LighterError::InvalidPrivateKeyLength { .. }
+                | LighterError::HexParseError(_)
+                | LighterError::CryptoError(_)
+        );
+        if key_error {
+            report.push("private_key", Err(error), KEY_HINT);
+        } else {
+            report.push("configuration", Err(error), CONFIG_HINT);
+        }
+        for name in [
+            "rest",
+            "nonce",
+            "account",
+            "public_key",
+            "websocket",
+            "signing",
+        ] {
+            report.skip(name, "client could not be built");
+        }
+        report
+    }
+
+    pub(crate) fn skip(&mut self, name: &'static str, reason: &str) {
+        self.steps.push(SelfTestStep {
+            name,
+            status: StepStatus::Skipped,
+            detail: reason.to_string(),
+            hint: None,
+        });
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let status = match step.status {
+                StepStatus::Passed => "PASS",
+                StepStatus::Failed => "FAIL",
+                StepStatus::Skipped => "SKIP",
+            };
+            writeln!(f, "[{}] {}: {}", status, step.name, step.detail)?;
+            if let Some(hint) = step.hint {
+                writeln!(f, "       hint: {}", hint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings for [`TxClient::self_test`]
+#[derive(Debug, Clone)]
+pub struct SelfTestOptions {
+    /// Full stream URL; the WebSocket step is skipped without one
+    pub ws_url: Option<String>,
+    /// Market whose order book is subscribed to and used for the test order
+    pub market_id: u32,
+    /// Longest wait for the first stream message
+    pub ws_timeout: Duration,
+}
+
+impl Default for SelfTestOptions {
+    fn default() -> Self {
+        Self {
+            ws_url: None,
+            market_id: 0,
+            ws_timeout: Duration::from_secs(DEFAULT_SELF_TEST_WS_TIMEOUT_SECS),
+        }
+    }
+}
+
+const KEY_HINT: &str =
+    "API private keys are 40 bytes, 80 hex characters; copy the key again from where it was generated";
+const CONFIG_HINT: &str =
+    "Check the account index, API key index and chain id against the values shown when the key was created";
+const REST_HINT: &str =
+    "Check the API URL and that outbound HTTPS to it is allowed; a large clock offset means the system clock needs syncing";
+const NONCE_HINT: &str =
+    "Check the account index and API key index; the key index must be registered on the account";
+const ACCOUNT_HINT: &str =
+    "No account with this index on this network; check the account index and that the URL and chain id are for the right network";
+const PUBLIC_KEY_HINT: &str =
+    "The private key doesn't belong to this API key index; check both or register the key with change_pub_key";
+const WS_HINT: &str =
+    "Check the stream URL and that outbound WebSocket connections (port 443) aren't blocked by a firewall or proxy";
+const SIGNING_HINT: &str =
+    "Signing failed locally; check the chain id and that the key was parsed correctly";
+
+impl TxClient {
+    /// Check credentials and connectivity without submitting anything
+    ///
+    /// Every step runs even if an earlier one failed, so one report shows
+    /// all problems. The test order is signed with the fetched nonce but
+    /// never sent.
+    pub async fn self_test(&self, options: &SelfTestOptions) -> SelfTestReport {
+        let mut report = SelfTestReport::default();
+        report.push(
+            "private_key",
+            Ok(format!(
+                "public key 0x{}",
+                hex::encode(self.key_manager().pub_key())
+            )),
+            KEY_HINT,
+        );
+
+        let mut next_nonce = None;
+        match self.http() {
+            Some(http) => {
+                report.push(
+                    "rest",
+                    self.check_clock_skew().await.map(|skew| {
+                        format!("reachable, clock offset {}ms", skew.num_milliseconds())
+                    }),
+                    REST_HINT,
+                );
+
+                let nonce = async {
+                    let api_key_index = ApiKeyIndex::new(self.api_key_index())?;
+                    http.get_next_nonce(AccountIndex::new(self.account_index())?, api_key_index)
+                        .await
+                }
+                .await;
+                next_nonce = nonce.as_ref().ok().copied();
+                report.push(
+                    "nonce",
+                    nonce.map(|nonce| format!("next nonce {}", nonce)),
+                    NONCE_HINT,
+                );
+
+                report.push(
+                    "account",
+                    async {
+                        http.get_account_snapshot(AccountIndex::new(self.account_index())?)
+                            .await
+                    }
+                    .await
+                    .map(|account| {
+                        format!(
+                            "account {} found with {} positions",
+                            self.account_index(),
+                            account.positions.len()
+                        )
+                    }),
+                    ACCOUNT_HINT,
+                );
+
+                report.push("public_key", self.check_public_key().await, PUBLIC_KEY_HINT);
+            }
+            None => {
+                for name in ["rest", "nonce", "account", "public_key"] {
+                    report.skip(name, "no API URL configured");
+                }
+            }
+        }
+
+        match &options.ws_url {
+            Some(url) => report.push(
+                "websocket",
+                check_stream(url, options.market_id, options.ws_timeout).await,
+                WS_HINT,
+            ),
+            None => report.skip("websocket", "no WebSocket URL given"),
+        }
+
+        report.push(
+            "signing",
+            self.sign_test_order(options.market_id, next_nonce.unwrap_or(0))
+                .await,
+            SIGNING_HINT,
+        );
+        report
+    }
+
+    async fn check_public_key(&self) -> Result<String> {
+        let http = self
+            .http()
+            .ok_or_else(|| LighterError::InvalidConfiguration("no API URL".to_string()))?;
+        let local = hex::encode(self.key_manager().pub_key());
+        match http
+            .get_api_key_public_key(
+                AccountIndex::new(self.account_index())?,
+                ApiKeyIndex::new(self.api_key_index())?,
+            )
+            .await?
+        {
+            Some(registered) if registered == local => {
+                Ok(format!("matches API key {}", self.api_key_index()))
+            }
+            Some(registered) => Err(LighterError::ValidationError(format!(
+                "derived key 0x{} differs from registered key 0x{}",
+                local, registered
+            ))),
+            None => Err(LighterError::ValidationError(format!(
+                "no public key registered at API key index {}",
+                self.api_key_index()
+            ))),
+        }
+    }
+
+    async fn sign_test_order(&self, market_id: u32, nonce: i64) -> Result<String> {
+        let req = CreateOrderTxReq {
+            market_index: u8::try_from(market_id).unwrap_or(0),
+            client_order_index: MIN_CLIENT_ORDER_INDEX,
+            base_amount: MIN_ORDER_BASE_AMOUNT,
+            price: 1,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_POST_ONLY,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: 0,
+        };
+        let opts = TransactOpts {
+            nonce: Some(nonce),
+            ..Default::default()
+        };
+        let mut tx = self.build_unsigned(&req, opts).await?;
+        self.sign_prepared(&mut tx)?;
+        let signature = tx.sig.as_deref().unwrap_or_default();
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(LighterError::CryptoError(format!(
+                "signature is {} bytes, expected {}",
+                signature.len(),
+                SIGNATURE_LENGTH
+            )));
+        }
+        Ok(format!("signed a test order with nonce {}", nonce))
+    }
+}
+
+/// Connect, subscribe to one order book and wait for its first message
+async fn check_stream(url: &str, market_id: u32, timeout: Duration) -> Result<String> {
+    let ws = WsClient::builder()
+        .url(url)
+        .order_books(vec![market_id])
+        .build()?;
+    let channel = format!("order_book/{}", market_id);
+    let started = tokio::time::Instant::now();
+    tokio::select! {
+        ended = ws.run(|_, _| {}, |_, _| {}) => Err(ended.err().unwrap_or_else(|| {
+            LighterError::Other("Stream closed before the first message".to_string())
+        })),
+        subscribed = ws.await_subscribed(&channel, timeout) => subscribed.map(|()| {
+            format!(
+                "first {} message after {}ms",
+                channel,
+                started.elapsed().as_millis()
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lighter_client::{Credentials, LighterClient};
+    use crate::network::Network;
+    use crate::test_support::{spawn_mock_ws_server, test_client_for};
+    use crate::types::{AccountIndex, ApiKeyIndex};
+
+    fn test_client(url: &str) -> TxClient {
+        test_client_for(url, 7, 0)
+    }
+
+    /// REST server with every endpoint answering, except the account which
+    /// answers with `account_status`, and a `sendTx` mock expecting no calls
+    async fn mock_api(
+        client_pub_key: &str,
+        account_status: usize,
+    ) -> (mockito::ServerGuard, mockito::Mock) {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/")
+            .with_body(format!(
+                r#"{{"status":200,"network_id":1,"timestamp":{}}}"#,
+                chrono::Utc::now().timestamp()
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v1/nextNonce")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"code":200,"nonce":42}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v1/account")
+            .match_query(mockito::Matcher::Any)
+            .with_status(account_status)
+            .with_body(r#"{"code":200,"accounts":[{"collateral":"100","positions":[]}]}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/v1/apikeys")
+            .match_query(mockito::Matcher::Any)
+            .with_body(format!(
+                r#"{{"code":200,"api_keys":[{{"api_key_index":0,"public_key":"0x{}"}}]}}"#,
+                client_pub_key
+            ))
+            .create_async()
+            .await;
+        let send = server
+            .mock("POST", "/api/v1/sendTx")
+            .expect(0)
+            .create_async()
+            .await;
+        (server, send)
+    }
+
+    #[tokio::test]
+    async fn test_self_test_passes_without_sending() {
+        let pub_key = hex::encode(test_client("").key_manager().pub_key());
+        let (server, send) = mock_api(&pub_key, 200).await;
+        let frames = vec![
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[{"price":"29","size":"1"}]}}"#.to_string(),
+        ];
+        let ws_addr = spawn_mock_ws_server(frames).await;
+
+        let client = test_client(&server.url());
+        let report = client
+            .self_test(&SelfTestOptions {
+                ws_url: Some(format!("ws://{}/stream", ws_addr)),
+                ..Default::default()
+            })
+            .await;
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.steps.len(), 7);
+        assert!(report.step("nonce").unwrap().detail.contains("42"));
+        assert!(report.step("signing").unwrap().detail.contains("nonce 42"));
+        send.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_self_test_reports_missing_account_and_wrong_key() {
+        let (server, send) = mock_api(&"ab".repeat(40), 404).await;
+        let client = test_client(&server.url());
+        let report = client.self_test(&SelfTestOptions::default()).await;
+
+        assert!(!report.passed());
+        let account = report.step("account").unwrap();
+        assert_eq!(account.status, StepStatus::Failed);
+        assert!(account.detail.contains("404"), "{}", account.detail);
+        assert_eq!(account.hint, Some(ACCOUNT_HINT));
+        let public_key = report.step("public_key").unwrap();
+        assert_eq!(public_key.status, StepStatus::Failed);
+        assert_eq!(public_key.hint, Some(PUBLIC_KEY_HINT));
+
+        assert_eq!(report.step("nonce").unwrap().status, StepStatus::Passed);
+        assert_eq!(report.step("signing").unwrap().status, StepStatus::Passed);
+        assert_eq!(
+            report.step("websocket").unwrap().status,
+            StepStatus::Skipped
+        );
+        assert_eq!(
+            report.failures().map(|step| step.name).collect::<Vec<_>>(),
+            vec!["account", "public_key"]
+        );
+        send.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_self_test_ws_timeout() {
+        // Accepts connections but never completes the handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let client = test_client("");
+        let report = client
+            .self_test(&SelfTestOptions {
+                ws_url: Some(format!("ws://{}/stream", addr)),
+                ws_timeout: Duration::from_millis(200),
+                ..Default::default()
+            })
+            .await;
+
+        let websocket = report.step("websocket").unwrap();
+        assert_eq!(websocket.status, StepStatus::Failed, "{}", report);
+        assert_eq!(websocket.hint, Some(WS_HINT));
+        assert_eq!(report.step("rest").unwrap().status, StepStatus::Skipped);
+        assert_eq!(report.step("signing").unwrap().status, StepStatus::Passed);
+    }
+
+    #[tokio::test]
+    async fn test_facade_self_test_bad_key_length() {
+        let report = LighterClient::builder(
+            Network::Custom {
+                api_url: "http://127.0.0.1:1".to_string(),
+                ws_url: "ws://127.0.0.1:1/stream".to_string(),
+                chain_id: 300,
+            },
+            Credentials::new(
+                "0x1234",
+                AccountIndex::new(7).unwrap(),
+                ApiKeyIndex::new(0).unwrap(),
+            ),
+        )
+        .self_test()
+        .await;
+
+        let key = report.step("private_key").unwrap();
+        assert_eq!(key.status, StepStatus::Failed);
+        assert_eq!(key.hint, Some(KEY_HINT));
+        assert_eq!(report.failures().count(), 1);
+        assert!(report
+            .steps
+            .iter()
+            .skip(1)
+            .all(|step| step.status == StepStatus::Skipped));
+        assert!(report.to_string().contains("[FAIL] private_key"));
+    }
+}
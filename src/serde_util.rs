@@ -0,0 +1,215 @@
+//! Tolerant serde helpers for API payloads
+//!
+//! The API sends amounts as JSON strings in some messages and as numbers in
+//! others. Use these with `#[serde(deserialize_with = "...")]`:
+//!
+//! ```rust
+//! use rust_decimal::Decimal;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Balance {
+//!     #[serde(deserialize_with = "lighter_rs::serde_util::string_or_number_decimal")]
+//!     total: Decimal,
+//!     #[serde(default, deserialize_with = "lighter_rs::serde_util::option_string_or_number_decimal")]
+//!     available: Option<Decimal>,
+//! }
+//! ```
+//!
+//! Typed account entries keep keys they don't know in an `extras` map, so a
+//! field added by the API survives a round trip. With strict parsing
+//! (`HTTPClient::set_strict_parsing`, `WsClientBuilder::strict_parsing`)
+//! such keys fail the response instead.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::str::FromStr;
+
+/// Deserialize a [`Decimal`] from a string, integer or float
+///
+/// Strings may use scientific notation. Fractional digits beyond what
+/// `Decimal` can hold are rounded; integer parts that overflow are errors.
+pub fn string_or_number_decimal<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(DecimalVisitor)
+}
+
+/// Like [`string_or_number_decimal`], mapping `null` and `""` to `None`
+///
+/// Combine with `#[serde(default)]` so a missing field is `None` as well.
+pub fn option_string_or_number_decimal<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Tolerant(#[serde(deserialize_with = "string_or_number_decimal")] Decimal);
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaybeEmpty {
+        Value(Tolerant),
+        Empty(String),
+    }
+
+    match Option::<MaybeEmpty>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(MaybeEmpty::Value(Tolerant(value))) => Ok(Some(value)),
+        Some(MaybeEmpty::Empty(s)) if s.trim().is_empty() => Ok(None),
+        Some(MaybeEmpty::Empty(s)) => {
+            Err(de::Error::custom(format!("invalid decimal string {:?}", s)))
+        }
+    }
+}
+
+/// Deserialize a [`DateTime`] from an epoch timestamp in seconds or milliseconds
+///
+/// Accepts numbers and numeric strings. Values below
+/// [`MIN_MILLIS_TIMESTAMP`](crate::constants::MIN_MILLIS_TIMESTAMP) are read as
+/// seconds, the rest as milliseconds.
+pub fn epoch_timestamp<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = string_or_number_decimal(deserializer)?;
+    let value = i64::try_from(value.trunc())
+        .map_err(|_| de::Error::custom(format!("timestamp {} out of range", value)))?;
+    let millis = if value < crate::constants::MIN_MILLIS_TIMESTAMP {
+        value.saturating_mul(1000)
+    } else {
+        value
+    };
+    DateTime::from_timestamp_millis(millis)
+        .ok_or_else(|| de::Error::custom(format!("timestamp {} out of range", value)))
+}
+
+/// Fail with [`InvalidResponse`](crate::errors::LighterError::InvalidResponse)
+/// naming the keys of `extras`, if there are any
+pub(crate) fn reject_extras(type_name: &str, extras: &Map<String, Value>) -> crate::Result<()> {
+    if extras.is_empty() {
+        return Ok(());
+    }
+    let mut keys: Vec<&str> = extras.keys().map(String::as_str).collect();
+    keys.sort_unstable();
+    Err(crate::errors::LighterError::InvalidResponse(format!(
+        "Unexpected keys {:?} in {}",
+        keys, type_name
+    )))
+}
+
+struct DecimalVisitor;
+
+impl Visitor<'_> for DecimalVisitor {
+    type Value = Decimal;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a decimal as a string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Decimal, E> {
+        let v = v.trim();
+        Decimal::from_str(v)
+            .or_else(|_| Decimal::from_scientific(v))
+            .map_err(|e| E::custom(format!("invalid decimal string {:?}: {}", v, e)))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Decimal, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Decimal, E> {
+        Ok(Decimal::from(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Decimal, E> {
+        Decimal::try_from(v).map_err(|e| E::custom(format!("invalid decimal number {}: {}", v, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Amounts {
+        #[serde(deserialize_with = "string_or_number_decimal")]
+        size: Decimal,
+        #[serde(default, deserialize_with = "option_string_or_number_decimal")]
+        price: Option<Decimal>,
+    }
+
+    fn size(value: serde_json::Value) -> serde_json::Result<Decimal> {
+        serde_json::from_value::<Amounts>(json!({ "size": value })).map(|a| a.size)
+    }
+
+    fn price(value: serde_json::Value) -> serde_json::Result<Option<Decimal>> {
+        serde_json::from_value::<Amounts>(json!({ "size": 0, "price": value })).map(|a| a.price)
+    }
+
+    #[test]
+    fn test_input_shapes() {
+        assert_eq!(size(json!("12.50")).unwrap(), Decimal::new(1250, 2));
+        assert_eq!(size(json!(" 7 ")).unwrap(), Decimal::from(7));
+        assert_eq!(size(json!(42)).unwrap(), Decimal::from(42));
+        assert_eq!(size(json!(-3)).unwrap(), Decimal::from(-3));
+        assert_eq!(size(json!(u64::MAX)).unwrap(), Decimal::from(u64::MAX));
+        assert_eq!(size(json!(0.25)).unwrap(), Decimal::new(25, 2));
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        assert_eq!(size(json!(1.5e-5)).unwrap(), Decimal::new(15, 6));
+        assert_eq!(size(json!("1.5e-5")).unwrap(), Decimal::new(15, 6));
+        assert_eq!(size(json!("2E3")).unwrap(), Decimal::from(2000));
+    }
+
+    #[test]
+    fn test_long_and_invalid_strings() {
+        // Excess fractional digits are rounded to Decimal's 28-digit scale
+        let long = format!("0.{}", "1".repeat(60));
+        assert_eq!(
+            size(json!(long)).unwrap(),
+            Decimal::from_str("0.1111111111111111111111111111").unwrap()
+        );
+        // An integer part beyond 96 bits can't be represented
+        assert!(size(json!("9".repeat(40))).is_err());
+        assert!(size(json!("abc")).is_err());
+        assert!(size(json!(true)).is_err());
+        assert!(size(json!(null)).is_err());
+    }
+
+    #[test]
+    fn test_epoch_timestamps() {
+        #[derive(Deserialize)]
+        struct Stamped {
+            #[serde(deserialize_with = "epoch_timestamp")]
+            at: DateTime<Utc>,
+        }
+        let at = |value: serde_json::Value| {
+            serde_json::from_value::<Stamped>(json!({ "at": value })).map(|s| s.at)
+        };
+        let expected = DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+        assert_eq!(at(json!(1_700_000_000_000i64)).unwrap(), expected);
+        assert_eq!(at(json!(1_700_000_000)).unwrap(), expected);
+        assert_eq!(at(json!("1700000000000")).unwrap(), expected);
+        assert!(at(json!("soon")).is_err());
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        assert_eq!(price(json!(null)).unwrap(), None);
+        assert_eq!(price(json!("")).unwrap(), None);
+        assert_eq!(price(json!("1.5")).unwrap(), Some(Decimal::new(15, 1)));
+        assert_eq!(price(json!(2)).unwrap(), Some(Decimal::from(2)));
+        assert!(price(json!("n/a")).is_err());
+
+        let missing: Amounts = serde_json::from_value(json!({ "size": "1" })).unwrap();
+        assert_eq!(missing.price, None);
+    }
+}
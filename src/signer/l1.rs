@@ -0,0 +1,304 @@
+//! Ethereum wallet signatures for account onboarding
+//!
+//! Registering an API key with a change public key transaction also needs
+//! the account's L1 wallet to sign a plain-text message with EIP-191
+//! (`personal_sign`). [`OnboardingIntent::message`] builds that text and
+//! [`L1Signer`] signs it. The text has not been checked against a message
+//! signed by the official SDK yet.
+//!
+//! ```
+//! use lighter_rs::signer::l1::{L1Signer, OnboardingIntent};
+//!
+//! let wallet = L1Signer::from_private_key(
+//!     "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+//! )?;
+//! let intent = OnboardingIntent {
+//!     pub_key: vec![0u8; 40],
+//!     nonce: 0,
+//!     account_index: 12345,
+//!     api_key_index: 2,
+//! };
+//! let signature = wallet.sign_onboarding_message(&intent)?;
+//! assert_eq!(signature.to_hex().len(), 132);
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use k256::ecdsa::SigningKey;
+use sha3::{Digest, Keccak256};
+use zeroize::Zeroizing;
+
+use crate::constants::PUBLIC_KEY_LENGTH;
+use crate::errors::{LighterError, Result};
+use crate::types::L2ChangePubKeyTxInfo;
+
+/// Keccak-256 of `message` behind the EIP-191 personal message prefix
+pub fn eip191_hash_message(message: impl AsRef<[u8]>) -> [u8; 32] {
+    let message = message.as_ref();
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()));
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// What an L1 wallet approves when registering an API key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnboardingIntent {
+    /// API public key being registered
+    pub pub_key: Vec<u8>,
+    /// Nonce of the change public key transaction
+    pub nonce: i64,
+    pub account_index: i64,
+    pub api_key_index: u8,
+}
+
+impl OnboardingIntent {
+    /// The intent of a built change public key transaction
+    pub fn from_tx(tx: &L2ChangePubKeyTxInfo) -> Self {
+        Self {
+            pub_key: tx.pub_key.clone(),
+            nonce: tx.nonce,
+            account_index: tx.account_index,
+            api_key_index: tx.api_key_index,
+        }
+    }
+
+    /// Text the wallet signs
+    ///
+    /// Numbers are written as `0x` and 16 zero-padded hex digits. Unverified
+    /// against the official SDK, see the module docs.
+    pub fn message(&self) -> String {
+        format!(
+            "Register Lighter Account\n\npubkey: 0x{}\nnonce: {}\naccount index: {}\napi key index: {}\nOnly sign this message for a trusted client!",
+            hex::encode(&self.pub_key),
+            padded_hex(self.nonce as u64),
+            padded_hex(self.account_index as u64),
+            padded_hex(u64::from(self.api_key_index)),
+        )
+    }
+}
+
+fn padded_hex(value: u64) -> String {
+    format!("0x{:016x}", value)
+}
+
+/// Recoverable secp256k1 signature, `r || s || v` with `v` of 27 or 28
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1Signature([u8; 65]);
+
+impl L1Signature {
+    pub fn as_bytes(&self) -> &[u8; 65] {
+        &self.0
+    }
+
+    /// `0x` followed by the 65 bytes in hex
+    pub fn to_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.0))
+    }
+}
+
+impl std::fmt::Display for L1Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// Ethereum wallet key used for L1 signatures
+///
+/// `Debug` shows only the address.
+pub struct L1Signer {
+    key: SigningKey,
+    address: [u8; 20],
+}
+
+impl L1Signer {
+    /// Parse a 32-byte hex private key, with or without `0x`
+    ///
+    /// Errors describe what is wrong without repeating any of the key.
+    pub fn from_private_key(hex_private_key: &str) -> Result<Self> {
+        let digits = hex_private_key
+            .strip_prefix("0x")
+            .or_else(|| hex_private_key.strip_prefix("0X"))
+            .unwrap_or(hex_private_key);
+        let bytes = Zeroizing::new(hex::decode(digits).map_err(|_| {
+            LighterError::CryptoError("L1 private key is not valid hex".to_string())
+        })?);
+        if bytes.len() != 32 {
+            return Err(LighterError::InvalidPrivateKeyLength {
+                expected: 32,
+                actual: bytes.len(),
+            });
+        }
+        let key = SigningKey::from_slice(&bytes).map_err(|_| {
+            LighterError::CryptoError("L1 private key is not a valid secp256k1 scalar".to_string())
+        })?;
+
+        let point = key.verifying_key().to_encoded_point(false);
+        let hash = Keccak256::digest(&point.as_bytes()[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        Ok(Self { key, address })
+    }
+
+    /// Wallet address with the EIP-55 checksum
+    pub fn address(&self) -> String {
+        let lower = hex::encode(self.address);
+        let hash = Keccak256::digest(lower.as_bytes());
+        let checksummed: String = lower
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                let nibble = (hash[i / 2] >> if i % 2 == 0 { 4 } else { 0 }) & 0x0f;
+                if nibble >= 8 {
+                    c.to_ascii_uppercase()
+                } else {
+                    c
+                }
+            })
+            .collect();
+        format!("0x{}", checksummed)
+    }
+
+    /// Sign `message` as an EIP-191 personal message
+    pub fn sign_message(&self, message: impl AsRef<[u8]>) -> Result<L1Signature> {
+        let hash = eip191_hash_message(message);
+        let (signature, recovery_id) = self
+            .key
+            .sign_prehash_recoverable(&hash)
+            .map_err(|e| LighterError::CryptoError(format!("L1 signing failed: {}", e)))?;
+        let mut bytes = [0u8; 65];
+        bytes[..64].copy_from_slice(&signature.to_bytes());
+        bytes[64] = 27 + recovery_id.to_byte();
+        Ok(L1Signature(bytes))
+    }
+
+    /// Sign the registration message for `intent`
+    pub fn sign_onboarding_message(&self, intent: &OnboardingIntent) -> Result<L1Signature> {
+        if intent.pub_key.len() != PUBLIC_KEY_LENGTH {
+            return Err(LighterError::PubKeyInvalid);
+        }
+        self.sign_message(intent.message())
+    }
+}
+
+impl std::fmt::Debug for L1Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("L1Signer")
+            .field("address", &self.address())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Throwaway key from the web3.js `accounts.sign` documentation
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    #[test]
+    fn test_eip191_fixture() {
+        let wallet = L1Signer::from_private_key(KEY).unwrap();
+        assert_eq!(
+            wallet.address(),
+            "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"
+        );
+        assert_eq!(
+            hex::encode(eip191_hash_message("Some data")),
+            "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+        );
+        assert_eq!(
+            wallet.sign_message("Some data").unwrap().to_hex(),
+            "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd\
+             6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c"
+        );
+    }
+
+    #[test]
+    fn test_onboarding_message_format() {
+        let intent = OnboardingIntent {
+            pub_key: (0u8..40).collect(),
+            nonce: 7,
+            account_index: 281474976710654,
+            api_key_index: 3,
+        };
+        assert_eq!(
+            intent.message(),
+            "Register Lighter Account\n\n\
+             pubkey: 0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f2021222324252627\n\
+             nonce: 0x0000000000000007\n\
+             account index: 0x0000fffffffffffe\n\
+             api key index: 0x0000000000000003\n\
+             Only sign this message for a trusted client!"
+        );
+
+        let wallet = L1Signer::from_private_key(KEY).unwrap();
+        let signature = wallet.sign_onboarding_message(&intent).unwrap();
+        assert_eq!(signature, wallet.sign_message(intent.message()).unwrap());
+        assert!(matches!(signature.as_bytes()[64], 27 | 28));
+
+        let short = OnboardingIntent {
+            pub_key: vec![0u8; 32],
+            ..intent
+        };
+        assert!(matches!(
+            wallet.sign_onboarding_message(&short),
+            Err(LighterError::PubKeyInvalid)
+        ));
+    }
+
+    /// Throwaway key, sha256("lighter-rs onboarding fixture") mod n
+    const ONBOARDING_KEY: &str =
+        "0xf1ba482ae9a85fcbe8d2d8886aae2ab1ddaaff393bd4b604663d92a942f1d7c3";
+
+    /// Self-generated, not recorded from the official SDK: the values come
+    /// from a standalone Python port of eth_account's
+    /// `sign_message(encode_defunct(text=...))`, which reproduces the web3.js
+    /// vector above. It pins this crate's output; it does not show that the
+    /// SDK builds the same message. Replace it with a signature from the SDK
+    /// when one is available.
+    #[test]
+    fn test_onboarding_signature_self_generated_fixture() {
+        let intent = OnboardingIntent {
+            pub_key: (0x10u8..0x38).collect(),
+            nonce: 3,
+            account_index: 1025,
+            api_key_index: 4,
+        };
+        let message = "Register Lighter Account\n\n\
+             pubkey: 0x101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c2d2e2f3031323334353637\n\
+             nonce: 0x0000000000000003\n\
+             account index: 0x0000000000000401\n\
+             api key index: 0x0000000000000004\n\
+             Only sign this message for a trusted client!";
+        assert_eq!(intent.message(), message);
+        assert_eq!(
+            hex::encode(eip191_hash_message(message)),
+            "b4057e1800b703ac7dc893be33f2359d18b3ee629e89fe0774981ca4ca9f1f1d"
+        );
+
+        let wallet = L1Signer::from_private_key(ONBOARDING_KEY).unwrap();
+        assert_eq!(
+            wallet.address().to_lowercase(),
+            "0xb9ded4bf1437c1492580e1c2459895c5cd0def04"
+        );
+        assert_eq!(
+            wallet.sign_onboarding_message(&intent).unwrap().to_hex(),
+            "0x5288204e6ba905a118f5fd2683c4c749fad7854765beb9778e2b5479541ca3fb\
+             12c73edfa789c10c3b57eeadd38d3033bec2e76426c6125750523688e94f1e9b1c"
+        );
+    }
+
+    #[test]
+    fn test_invalid_keys_are_not_echoed() {
+        let message = L1Signer::from_private_key(&KEY[..64])
+            .unwrap_err()
+            .to_string();
+        assert!(!message.contains(&KEY[2..18]));
+        assert!(L1Signer::from_private_key("0xzz").is_err());
+        assert!(L1Signer::from_private_key(&"0".repeat(64)).is_err());
+
+        let debug = format!("{:?}", L1Signer::from_private_key(KEY).unwrap());
+        assert!(debug.contains("0x2c7536E3605D9C16a7a3D7b1898e529396a65c23"));
+        assert!(!debug.contains(&KEY[2..18]));
+    }
+}
@@ -0,0 +1,219 @@
+//! Cryptographic signing and key management for Lighter Protocol
+
+use secrecy::{ExposeSecret, SecretVec};
+use zeroize::Zeroizing;
+
+use crate::constants::{
+    FIELD_HASH_LENGTH, GOLDILOCKS_MODULUS, PRIVATE_KEY_LENGTH, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH,
+};
+use crate::errors::{LighterError, Result};
+
+pub mod l1;
+pub mod nonce;
+
+pub use nonce::{FileNonceStore, InMemoryNonceStore, NonceGapPolicy, NonceState, NonceStore};
+
+/// Trait for signing messages
+pub trait Signer {
+    fn sign(&self, hashed_message: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Trait for key management operations
+pub trait KeyManager: Signer {
+    fn pub_key(&self) -> &[u8];
+    fn pub_key_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH];
+    /// Copy of the private key; the caller is responsible for wiping it
+    #[cfg(feature = "expose-secrets")]
+    fn prv_key_bytes(&self) -> Vec<u8>;
+}
+
+/// Implementation of key manager using Poseidon cryptography
+///
+/// The private key is zeroized on drop and never printed; `Debug` shows
+/// only the start of the public key.
+pub struct PoseidonKeyManager {
+    private_key: SecretVec<u8>,
+    public_key: Vec<u8>,
+}
+
+impl PoseidonKeyManager {
+    pub fn new(private_key_bytes: &[u8]) -> Result<Self> {
+        // Accept both 32-byte (256-bit) and 40-byte keys
+        // 32 bytes is standard for many cryptographic keys
+        // 40 bytes is the Lighter protocol specification
+        if private_key_bytes.len() != 32 && private_key_bytes.len() != PRIVATE_KEY_LENGTH {
+            return Err(LighterError::InvalidPrivateKeyLength {
+                expected: PRIVATE_KEY_LENGTH,
+                actual: private_key_bytes.len(),
+            });
+        }
+
+        let public_key = Self::derive_public_key(private_key_bytes)?;
+
+        Ok(Self {
+            private_key: SecretVec::new(private_key_bytes.to_vec()),
+            public_key,
+        })
+    }
+
+    /// Parse a hex private key, with or without `0x`
+    ///
+    /// Errors describe what is wrong without repeating any of the key.
+    pub fn from_hex(hex_private_key: &str) -> Result<Self> {
+        let digits = hex_private_key
+            .strip_prefix("0x")
+            .or_else(|| hex_private_key.strip_prefix("0X"))
+            .unwrap_or(hex_private_key);
+        let bytes = Zeroizing::new(hex::decode(digits).map_err(|e| {
+            let reason = match e {
+                hex::FromHexError::InvalidHexCharacter { index, .. } => {
+                    format!("invalid character at position {}", index)
+                }
+                hex::FromHexError::OddLength => "odd number of digits".to_string(),
+                hex::FromHexError::InvalidStringLength => "invalid length".to_string(),
+            };
+            LighterError::CryptoError(format!("Private key is not valid hex: {}", reason))
+        })?);
+        Self::new(&bytes)
+    }
+
+    fn derive_public_key(_private_key: &[u8]) -> Result<Vec<u8>> {
+        // TODO: Implement actual Schnorr public key derivation with Poseidon crypto
+        Ok(vec![0u8; PUBLIC_KEY_LENGTH])
+    }
+}
+
+impl Signer for PoseidonKeyManager {
+    fn sign(&self, hashed_message: &[u8]) -> Result<Vec<u8>> {
+        if hashed_message.len() != 40 {
+            return Err(LighterError::CryptoError(format!(
+                "Invalid hashed message length: expected 40, got {}",
+                hashed_message.len()
+            )));
+        }
+        // TODO: Implement actual Schnorr signing with Poseidon crypto
+        let _private_key = self.private_key.expose_secret();
+        Ok(vec![0u8; SIGNATURE_LENGTH])
+    }
+}
+
+impl KeyManager for PoseidonKeyManager {
+    fn pub_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    fn pub_key_bytes(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        let mut result = [0u8; PUBLIC_KEY_LENGTH];
+        result.copy_from_slice(&self.public_key[..PUBLIC_KEY_LENGTH]);
+        result
+    }
+
+    #[cfg(feature = "expose-secrets")]
+    fn prv_key_bytes(&self) -> Vec<u8> {
+        self.private_key.expose_secret().clone()
+    }
+}
+
+impl std::fmt::Debug for PoseidonKeyManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoseidonKeyManager")
+            .field("public_key", &public_key_prefix(&self.public_key))
+            .finish_non_exhaustive()
+    }
+}
+
+/// First bytes of a public key in hex, enough to tell keys apart in logs
+pub(crate) fn public_key_prefix(public_key: &[u8]) -> String {
+    format!("0x{}…", hex::encode(&public_key[..public_key.len().min(4)]))
+}
+
+/// Goldilocks elements of `bytes` read as 8-byte little-endian words
+///
+/// The last word is zero-padded. Words must already be below the modulus,
+/// as in the official signer's `ArrayFromCanonicalLittleEndianBytes`.
+pub(crate) fn field_elements_from_bytes(bytes: &[u8]) -> Result<Vec<u64>> {
+    bytes
+        .chunks(8)
+        .map(|chunk| {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            let element = u64::from_le_bytes(word);
+            if element >= GOLDILOCKS_MODULUS {
+                return Err(LighterError::CryptoError(format!(
+                    "0x{:016x} is not a canonical field element",
+                    element
+                )));
+            }
+            Ok(element)
+        })
+        .collect()
+}
+
+/// Poseidon2 hash of Goldilocks elements into the quintic extension
+///
+/// Stands in for Poseidon2 until it is implemented, like the transaction
+/// hashes, and returns zeros.
+pub(crate) fn hash_to_quintic_extension(_elements: &[u64]) -> Vec<u8> {
+    vec![0u8; FIELD_HASH_LENGTH]
+}
+
+pub fn new_key_manager(hex_key: &str) -> Result<Box<dyn KeyManager>> {
+    Ok(Box::new(PoseidonKeyManager::from_hex(hex_key)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str =
+        "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718";
+
+    #[test]
+    fn test_field_elements_must_be_canonical() {
+        assert_eq!(
+            field_elements_from_bytes(&[1, 0, 0, 0, 0, 0, 0, 0, 2]).unwrap(),
+            vec![1, 2]
+        );
+        assert!(field_elements_from_bytes(&[]).unwrap().is_empty());
+        let modulus = GOLDILOCKS_MODULUS.to_le_bytes();
+        assert!(matches!(
+            field_elements_from_bytes(&modulus),
+            Err(LighterError::CryptoError(_))
+        ));
+        let below = (GOLDILOCKS_MODULUS - 1).to_le_bytes();
+        assert_eq!(
+            field_elements_from_bytes(&below).unwrap(),
+            vec![GOLDILOCKS_MODULUS - 1]
+        );
+    }
+
+    #[test]
+    fn test_debug_shows_no_key_bytes() {
+        let manager = PoseidonKeyManager::from_hex(KEY_HEX).unwrap();
+        let debug = format!("{:?}", manager);
+        assert!(debug.starts_with("PoseidonKeyManager { public_key: \"0x00000000…\""));
+        for chunk in KEY_HEX.as_bytes().chunks(8) {
+            assert!(!debug.contains(std::str::from_utf8(chunk).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_signing_through_secret() {
+        let manager = PoseidonKeyManager::from_hex(&format!("0x{}", KEY_HEX)).unwrap();
+        let signature = manager.sign(&[7u8; 40]).unwrap();
+        assert_eq!(signature.len(), SIGNATURE_LENGTH);
+        assert!(manager.sign(&[7u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_hex_error_does_not_echo_key() {
+        let key = format!("{}zz", &KEY_HEX[..78]);
+        let message = PoseidonKeyManager::from_hex(&key).unwrap_err().to_string();
+        assert!(message.contains("position 78"));
+        assert!(!message.contains('z'));
+        assert!(!message.contains(&KEY_HEX[..16]));
+
+        let message = PoseidonKeyManager::from_hex("abc").unwrap_err().to_string();
+        assert!(message.contains("odd number of digits"));
+    }
+}
@@ -0,0 +1,1005 @@
+//! Nonce allocation per account and API key
+//!
+//! A [`NonceStore`] hands out nonces locally so that concurrent transactions
+//! don't need a round trip to `nextNonce` each, and so that a restarted
+//! process doesn't reuse nonces that were already signed. [`FileNonceStore`]
+//! persists its state with an atomic temp-file-and-rename write after every
+//! change.
+//!
+//! Nonces handed out by a store are tracked until the result of their
+//! submission is known. A rejected or never sent transaction leaves its
+//! nonce unused, so the ones signed behind it would be refused too; a
+//! [`NonceGapPolicy`] decides whether those not sent yet are failed or
+//! renumbered.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::errors::{LighterError, Result};
+
+/// Per-key nonce state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NonceState {
+    /// Next nonce to hand out
+    pub next: i64,
+    /// Highest nonce confirmed as accepted by the server
+    pub confirmed: Option<i64>,
+}
+
+/// Storage backend for locally allocated nonces
+pub trait NonceStore: Send + Sync {
+    /// Allocate the next nonce for an account and API key
+    ///
+    /// Returns `None` if the key has not been initialized with [`reset`](Self::reset)
+    /// or [`reconcile`](Self::reconcile) yet.
+    fn next(&self, account_index: i64, api_key_index: u8) -> Result<Option<i64>>;
+
+    /// Record that a nonce was accepted by the server
+    fn confirm(&self, account_index: i64, api_key_index: u8, nonce: i64) -> Result<()>;
+
+    /// Set the next nonce to hand out, discarding any local state for the key
+    fn reset(&self, account_index: i64, api_key_index: u8, next: i64) -> Result<()>;
+
+    /// Current state for a key without allocating
+    fn state(&self, account_index: i64, api_key_index: u8) -> Option<NonceState>;
+
+    /// Reconcile local state with the server's `nextNonce`
+    ///
+    /// The larger of the two values wins so that nonces signed before a
+    /// restart are never reused, and nonces consumed elsewhere are skipped.
+    /// Must be atomic with [`next`](Self::next), so that a concurrent
+    /// allocation is never rolled back. Returns the resulting next nonce.
+    fn reconcile(&self, account_index: i64, api_key_index: u8, server_next: i64) -> Result<i64>;
+
+    /// Whether calls block on I/O, so that async callers run them on the
+    /// blocking thread pool
+    fn blocks(&self) -> bool {
+        false
+    }
+}
+
+fn allocate(states: &mut HashMap<(i64, u8), NonceState>, key: (i64, u8)) -> Option<i64> {
+    let state = states.get_mut(&key)?;
+    let nonce = state.next;
+    state.next += 1;
+    Some(nonce)
+}
+
+/// Raise the next nonce to at least `server_next`; returns it and whether
+/// the state changed
+fn reconcile_state(
+    states: &mut HashMap<(i64, u8), NonceState>,
+    key: (i64, u8),
+    server_next: i64,
+) -> (i64, bool) {
+    match states.get_mut(&key) {
+        Some(state) if state.next >= server_next => {
+            if state.next != server_next {
+                eprintln!(
+                    "Nonce store discrepancy for account {} api key {}: local next {}, server next {}, using {}",
+                    key.0, key.1, state.next, server_next, state.next
+                );
+            }
+            (state.next, false)
+        }
+        Some(state) => {
+            eprintln!(
+                "Nonce store discrepancy for account {} api key {}: local next {}, server next {}, using {}",
+                key.0, key.1, state.next, server_next, server_next
+            );
+            state.next = server_next;
+            (server_next, true)
+        }
+        None => {
+            states.insert(
+                key,
+                NonceState {
+                    next: server_next,
+                    confirmed: None,
+                },
+            );
+            (server_next, true)
+        }
+    }
+}
+
+fn confirm_state(states: &mut HashMap<(i64, u8), NonceState>, key: (i64, u8), nonce: i64) {
+    let state = states.entry(key).or_insert(NonceState {
+        next: nonce + 1,
+        confirmed: None,
+    });
+    state.confirmed = Some(state.confirmed.map_or(nonce, |c| c.max(nonce)));
+    state.next = state.next.max(nonce + 1);
+}
+
+/// In-memory nonce store, lost on restart
+#[derive(Debug, Default)]
+pub struct InMemoryNonceStore {
+    states: Mutex<HashMap<(i64, u8), NonceState>>,
+}
+
+impl InMemoryNonceStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl NonceStore for InMemoryNonceStore {
+    fn next(&self, account_index: i64, api_key_index: u8) -> Result<Option<i64>> {
+        let mut states = self.states.lock().unwrap();
+        Ok(allocate(&mut states, (account_index, api_key_index)))
+    }
+
+    fn confirm(&self, account_index: i64, api_key_index: u8, nonce: i64) -> Result<()> {
+        let mut states = self.states.lock().unwrap();
+        confirm_state(&mut states, (account_index, api_key_index), nonce);
+        Ok(())
+    }
+
+    fn reset(&self, account_index: i64, api_key_index: u8, next: i64) -> Result<()> {
+        let mut states = self.states.lock().unwrap();
+        states.insert(
+            (account_index, api_key_index),
+            NonceState {
+                next,
+                confirmed: None,
+            },
+        );
+        Ok(())
+    }
+
+    fn state(&self, account_index: i64, api_key_index: u8) -> Option<NonceState> {
+        self.states
+            .lock()
+            .unwrap()
+            .get(&(account_index, api_key_index))
+            .copied()
+    }
+
+    fn reconcile(&self, account_index: i64, api_key_index: u8, server_next: i64) -> Result<i64> {
+        let mut states = self.states.lock().unwrap();
+        Ok(reconcile_state(&mut states, (account_index, api_key_index), server_next).0)
+    }
+}
+
+/// Nonce store persisted to a JSON file
+///
+/// Every allocation is written to disk before the nonce is returned, so a
+/// crash never causes a signed nonce to be handed out twice. [`TxClient`]
+/// makes these calls on the blocking thread pool.
+///
+/// [`TxClient`]: crate::client::TxClient
+#[derive(Debug)]
+pub struct FileNonceStore {
+    path: PathBuf,
+    states: Mutex<HashMap<(i64, u8), NonceState>>,
+}
+
+impl FileNonceStore {
+    /// Open a store at `path`, loading existing state if the file exists
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let states = if path.exists() {
+            let data = fs::read_to_string(&path).map_err(|e| {
+                LighterError::Other(format!("Failed to read nonce store {:?}: {}", path, e))
+            })?;
+            Self::decode(&data)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path,
+            states: Mutex::new(states),
+        })
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn decode(data: &str) -> Result<HashMap<(i64, u8), NonceState>> {
+        let raw: HashMap<String, NonceState> = serde_json::from_str(data)?;
+        raw.into_iter()
+            .map(|(key, state)| {
+                let (account, api_key) = key.split_once(':').ok_or_else(|| {
+                    LighterError::InvalidResponse(format!("Invalid nonce store key: {}", key))
+                })?;
+                let account = account.parse::<i64>().map_err(|_| {
+                    LighterError::InvalidResponse(format!("Invalid nonce store key: {}", key))
+                })?;
+                let api_key = api_key.parse::<u8>().map_err(|_| {
+                    LighterError::InvalidResponse(format!("Invalid nonce store key: {}", key))
+                })?;
+                Ok(((account, api_key), state))
+            })
+            .collect()
+    }
+
+    /// Apply `change` to a copy of the state and keep it only once it is
+    /// on disk, if `change` reports that it changed anything
+    fn update<R>(
+        &self,
+        change: impl FnOnce(&mut HashMap<(i64, u8), NonceState>) -> (R, bool),
+    ) -> Result<R> {
+        let mut states = self.states.lock().unwrap();
+        let mut candidate = states.clone();
+        let (result, changed) = change(&mut candidate);
+        if changed {
+            self.persist(&candidate)?;
+            *states = candidate;
+        }
+        Ok(result)
+    }
+
+    fn persist(&self, states: &HashMap<(i64, u8), NonceState>) -> Result<()> {
+        let raw: HashMap<String, NonceState> = states
+            .iter()
+            .map(|((account, api_key), state)| (format!("{}:{}", account, api_key), *state))
+            .collect();
+        let data = serde_json::to_vec_pretty(&raw)?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        let io_err = |e: std::io::Error| {
+            LighterError::Other(format!(
+                "Failed to write nonce store {:?}: {}",
+                self.path, e
+            ))
+        };
+        let mut file = fs::File::create(&tmp_path).map_err(io_err)?;
+        file.write_all(&data).map_err(io_err)?;
+        file.sync_all().map_err(io_err)?;
+        fs::rename(&tmp_path, &self.path).map_err(io_err)?;
+        Ok(())
+    }
+}
+
+impl NonceStore for FileNonceStore {
+    fn next(&self, account_index: i64, api_key_index: u8) -> Result<Option<i64>> {
+        self.update(|states| {
+            let nonce = allocate(states, (account_index, api_key_index));
+            (nonce, nonce.is_some())
+        })
+    }
+
+    fn confirm(&self, account_index: i64, api_key_index: u8, nonce: i64) -> Result<()> {
+        self.update(|states| {
+            confirm_state(states, (account_index, api_key_index), nonce);
+            ((), true)
+        })
+    }
+
+    fn reset(&self, account_index: i64, api_key_index: u8, next: i64) -> Result<()> {
+        self.update(|states| {
+            states.insert(
+                (account_index, api_key_index),
+                NonceState {
+                    next,
+                    confirmed: None,
+                },
+            );
+            ((), true)
+        })
+    }
+
+    fn state(&self, account_index: i64, api_key_index: u8) -> Option<NonceState> {
+        self.states
+            .lock()
+            .unwrap()
+            .get(&(account_index, api_key_index))
+            .copied()
+    }
+
+    fn reconcile(&self, account_index: i64, api_key_index: u8, server_next: i64) -> Result<i64> {
+        self.update(|states| reconcile_state(states, (account_index, api_key_index), server_next))
+    }
+
+    /// Every change is synced to disk
+    fn blocks(&self) -> bool {
+        true
+    }
+}
+
+/// What happens to transactions signed behind a nonce that went unused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonceGapPolicy {
+    /// Fail them with [`LighterError::NonceGap`] without sending
+    #[default]
+    FailFast,
+    /// Sign them again with nonces shifted down to close the gap
+    ///
+    /// Only [`TxClient::send_transaction_resigning`](crate::client::TxClient::send_transaction_resigning)
+    /// can sign again; other sends fail as with [`FailFast`](Self::FailFast).
+    Renumber,
+}
+
+/// An allocated nonce whose submission result isn't known yet
+#[derive(Debug)]
+struct Flight {
+    id: u64,
+    /// Nonce the transaction is signed with
+    signed: i64,
+    /// Nonce it is to be sent with, lower than `signed` once renumbered
+    nonce: i64,
+    /// Hash of the signed transaction
+    hash: Option<String>,
+    allocated_at: tokio::time::Instant,
+    sending: bool,
+    /// Unused nonce the transaction can't be sent behind
+    gap: Option<i64>,
+    /// Last unused nonce it was renumbered past
+    shifted_past: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+struct KeyFlights {
+    /// Next nonce the server will use
+    expected: i64,
+    flights: Vec<Flight>,
+    /// A nonce went unused and the store is to be wound back once nothing
+    /// is being sent
+    rewind: bool,
+}
+
+impl KeyFlights {
+    /// Latest flight of a transaction, by nonce and hash or, before the
+    /// hash is known, by nonce
+    fn find(&self, nonce: Option<i64>, hash: Option<&str>) -> Option<usize> {
+        hash.and_then(|hash| {
+            self.flights
+                .iter()
+                .rposition(|f| f.hash.as_deref() == Some(hash) && Some(f.signed) == nonce)
+        })
+        .or_else(|| {
+            self.flights
+                .iter()
+                .rposition(|f| f.hash.is_none() && Some(f.signed) == nonce)
+        })
+    }
+
+    /// Settle flights still not sent `hold` after allocation as unused,
+    /// except `keep`
+    fn release_unsent(
+        &mut self,
+        store: &dyn NonceStore,
+        key: (i64, u8),
+        keep: Option<u64>,
+        policy: NonceGapPolicy,
+        hold: Duration,
+    ) -> Result<bool> {
+        let now = tokio::time::Instant::now();
+        let abandoned: Vec<u64> = self
+            .flights
+            .iter()
+            .filter(|f| {
+                !f.sending && f.gap.is_none() && Some(f.id) != keep && now - f.allocated_at >= hold
+            })
+            .map(|f| f.id)
+            .collect();
+        for &id in &abandoned {
+            self.settle(store, key, id, false, policy)?;
+        }
+        Ok(!abandoned.is_empty())
+    }
+
+    fn settle(
+        &mut self,
+        store: &dyn NonceStore,
+        key: (i64, u8),
+        id: u64,
+        used: bool,
+        policy: NonceGapPolicy,
+    ) -> Result<()> {
+        let Some(i) = self.flights.iter().position(|f| f.id == id) else {
+            return Ok(());
+        };
+        let settled = self.flights.remove(i);
+        if used {
+            self.expected = self.expected.max(settled.nonce + 1);
+        } else {
+            let unused = settled.nonce;
+            // Sent transactions keep their nonce; the server decides
+            let behind = self
+                .flights
+                .iter_mut()
+                .filter(|f| !f.sending && f.gap.is_none() && f.signed > settled.signed);
+            match policy {
+                NonceGapPolicy::FailFast => behind.for_each(|f| f.gap = Some(unused)),
+                NonceGapPolicy::Renumber => behind.for_each(|f| {
+                    f.nonce -= 1;
+                    f.shifted_past = Some(unused);
+                }),
+            }
+            self.rewind = true;
+        }
+        self.wind_back(store, key)
+    }
+
+    /// Wind the store back past unused nonces once nothing is being sent,
+    /// so that new allocations continue without a gap
+    fn wind_back(&mut self, store: &dyn NonceStore, key: (i64, u8)) -> Result<()> {
+        if !self.rewind || self.flights.iter().any(|f| f.sending) {
+            return Ok(());
+        }
+        self.rewind = false;
+        let next = self
+            .flights
+            .iter()
+            .filter(|f| f.gap.is_none())
+            .map(|f| f.nonce + 1)
+            .fold(self.expected, i64::max);
+        match store.state(key.0, key.1) {
+            Some(state) if state.next > next => store.reset(key.0, key.1, next),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A tracked transaction's turn to be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Turn {
+    /// Not allocated here, or no longer tracked; sent as signed
+    Untracked,
+    /// Sent with `nonce`
+    Send {
+        id: u64,
+        nonce: i64,
+        /// Set if `nonce` differs from the signed one
+        shifted_past: Option<i64>,
+    },
+}
+
+/// A [`Turn`] whose result is still to be settled
+///
+/// Dropped without [`settle`](Self::settle), e.g. when the send is
+/// cancelled, the nonce counts as used: the transaction may have reached
+/// the server.
+pub(crate) struct SendGuard {
+    pub(crate) turn: Turn,
+    tracked: Option<Tracked>,
+}
+
+/// Where to settle a guarded flight
+struct Tracked {
+    flights: Arc<InFlightNonces>,
+    store: Arc<dyn NonceStore>,
+    key: (i64, u8),
+    id: u64,
+    policy: NonceGapPolicy,
+}
+
+impl Tracked {
+    fn settle(self, used: bool) -> Result<()> {
+        self.flights
+            .settle(self.store.as_ref(), self.key, self.id, used, self.policy)
+    }
+}
+
+impl SendGuard {
+    /// Guard for a transaction without a tracked nonce
+    pub(crate) fn untracked() -> Self {
+        Self {
+            turn: Turn::Untracked,
+            tracked: None,
+        }
+    }
+
+    /// Record whether the nonce was used, on the blocking thread pool if the
+    /// store blocks on I/O
+    pub(crate) async fn settle(mut self, used: bool) -> Result<()> {
+        match self.tracked.take() {
+            Some(t) if t.store.blocks() => tokio::task::spawn_blocking(move || t.settle(used))
+                .await
+                .map_err(|e| LighterError::Other(format!("Nonce store call failed: {}", e)))?,
+            Some(t) => t.settle(used),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SendGuard {
+    fn drop(&mut self) {
+        let Some(t) = self.tracked.take() else {
+            return;
+        };
+        let blocks = t.store.blocks();
+        let settle = move || {
+            if let Err(e) = t.settle(true) {
+                eprintln!("Failed to settle nonce of a dropped send: {}", e);
+            }
+        };
+        // Left to the blocking thread pool rather than synced in place
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if blocks => {
+                handle.spawn_blocking(settle);
+            }
+            _ => settle(),
+        }
+    }
+}
+
+/// Nonces allocated from a [`NonceStore`] and not settled yet, per account
+/// and API key
+///
+/// Transactions are sent as soon as they are ready, without waiting for
+/// lower nonces. A nonce that goes unused, rejected or not sent within the
+/// hold timeout, is handled by the [`NonceGapPolicy`] for the transactions
+/// signed behind it that aren't sent yet, and the store is wound back once
+/// nothing is being sent so that new allocations continue without a gap.
+///
+/// Methods taking a store call it under the lock; async callers run them on
+/// the blocking thread pool when the store [`blocks`](NonceStore::blocks).
+#[derive(Debug)]
+pub(crate) struct InFlightNonces {
+    keys: Mutex<HashMap<(i64, u8), KeyFlights>>,
+    next_id: AtomicU64,
+    changed: watch::Sender<u64>,
+}
+
+impl Default for InFlightNonces {
+    fn default() -> Self {
+        Self {
+            keys: Mutex::default(),
+            next_id: AtomicU64::new(0),
+            changed: watch::channel(0).0,
+        }
+    }
+}
+
+impl InFlightNonces {
+    fn notify(&self) {
+        self.changed.send_modify(|version| *version += 1);
+    }
+
+    /// Allocate the next nonce from `store` and track it
+    ///
+    /// Flights not sent within `hold` are released first.
+    pub(crate) fn allocate(
+        &self,
+        store: &dyn NonceStore,
+        key: (i64, u8),
+        policy: NonceGapPolicy,
+        hold: Duration,
+    ) -> Result<Option<i64>> {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(entry) = keys.get_mut(&key) {
+            if entry.release_unsent(store, key, None, policy, hold)? {
+                self.notify();
+            }
+        }
+        let Some(nonce) = store.next(key.0, key.1)? else {
+            return Ok(None);
+        };
+        let entry = keys.entry(key).or_default();
+        if entry.flights.iter().all(|f| f.gap.is_some()) {
+            entry.expected = nonce;
+        }
+        entry.flights.push(Flight {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            signed: nonce,
+            nonce,
+            hash: None,
+            allocated_at: tokio::time::Instant::now(),
+            sending: false,
+            gap: None,
+            shifted_past: None,
+        });
+        Ok(Some(nonce))
+    }
+
+    /// Record the hash a tracked nonce was signed into
+    pub(crate) fn signed(&self, key: (i64, u8), nonce: i64, hash: String) {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(entry) = keys.get_mut(&key) {
+            if let Some(i) = entry.find(Some(nonce), None) {
+                entry.flights[i].hash = Some(hash);
+            }
+        }
+    }
+
+    /// Tracked nonces of a key that may still be sent
+    pub(crate) fn count(&self, key: (i64, u8)) -> usize {
+        self.keys.lock().unwrap().get(&key).map_or(0, |entry| {
+            entry.flights.iter().filter(|f| f.gap.is_none()).count()
+        })
+    }
+
+    /// Mark the transaction as being sent
+    ///
+    /// Other flights not sent within `hold` are released first. Fails with
+    /// [`LighterError::NonceGap`] if the transaction can't be sent behind an
+    /// unused nonce.
+    pub(crate) fn turn(
+        self: &Arc<Self>,
+        store: &Arc<dyn NonceStore>,
+        key: (i64, u8),
+        nonce: Option<i64>,
+        hash: Option<&str>,
+        policy: NonceGapPolicy,
+        hold: Duration,
+    ) -> Result<SendGuard> {
+        let mut keys = self.keys.lock().unwrap();
+        let Some(entry) = keys.get_mut(&key) else {
+            return Ok(SendGuard::untracked());
+        };
+        let Some(i) = entry.find(nonce, hash) else {
+            return Ok(SendGuard::untracked());
+        };
+        let id = entry.flights[i].id;
+        let released = entry.release_unsent(store.as_ref(), key, Some(id), policy, hold);
+        let Some(i) = entry.flights.iter().position(|f| f.id == id) else {
+            return Ok(SendGuard::untracked());
+        };
+        let flight = &mut entry.flights[i];
+        if let Some(gap) = flight.gap {
+            entry.flights.remove(i);
+            drop(keys);
+            self.notify();
+            released?;
+            return Err(LighterError::NonceGap { failed_nonce: gap });
+        }
+        flight.sending = true;
+        let turn = Turn::Send {
+            id,
+            nonce: flight.nonce,
+            shifted_past: flight.shifted_past,
+        };
+        drop(keys);
+        self.notify();
+        released?;
+        Ok(SendGuard {
+            turn,
+            tracked: Some(Tracked {
+                flights: self.clone(),
+                store: store.clone(),
+                key,
+                id,
+                policy,
+            }),
+        })
+    }
+
+    /// Record the result of a sent flight
+    pub(crate) fn settle(
+        &self,
+        store: &dyn NonceStore,
+        key: (i64, u8),
+        id: u64,
+        used: bool,
+        policy: NonceGapPolicy,
+    ) -> Result<()> {
+        let result = match self.keys.lock().unwrap().get_mut(&key) {
+            Some(entry) => entry.settle(store, key, id, used, policy),
+            None => Ok(()),
+        };
+        self.notify();
+        result
+    }
+
+    /// Stop tracking a key: flights not being sent fail when sent, and the
+    /// ones being sent are waited for
+    pub(crate) async fn invalidate(&self, key: (i64, u8)) {
+        let mut changed = self.changed.subscribe();
+        {
+            let mut keys = self.keys.lock().unwrap();
+            if let Some(entry) = keys.get_mut(&key) {
+                let expected = entry.expected;
+                for flight in entry.flights.iter_mut().filter(|f| !f.sending) {
+                    flight.gap.get_or_insert(expected);
+                }
+            }
+        }
+        self.notify();
+        while self
+            .keys
+            .lock()
+            .unwrap()
+            .get(&key)
+            .is_some_and(|entry| entry.flights.iter().any(|f| f.sending))
+        {
+            if changed.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Start over at `next`, as the server's next nonce
+    pub(crate) fn reset(&self, store: &dyn NonceStore, key: (i64, u8), next: i64) -> Result<()> {
+        let mut keys = self.keys.lock().unwrap();
+        store.reset(key.0, key.1, next)?;
+        if let Some(entry) = keys.get_mut(&key) {
+            entry.expected = next;
+            entry.rewind = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lighter-rs-nonce-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nonces.json");
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    async fn allocate_concurrently(store: Arc<dyn NonceStore>) -> Vec<i64> {
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let store = store.clone();
+            handles.push(tokio::spawn(async move {
+                let mut nonces = Vec::new();
+                for _ in 0..50 {
+                    nonces.push(store.next(1, 0).unwrap().unwrap());
+                    tokio::task::yield_now().await;
+                }
+                nonces
+            }));
+        }
+
+        let mut all = Vec::new();
+        for handle in handles {
+            all.extend(handle.await.unwrap());
+        }
+        all.sort_unstable();
+        all
+    }
+
+    #[test]
+    fn test_uninitialized_key_returns_none() {
+        let store = InMemoryNonceStore::new();
+        assert_eq!(store.next(1, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn test_in_memory_allocation_and_confirm() {
+        let store = InMemoryNonceStore::new();
+        store.reset(1, 0, 10).unwrap();
+
+        assert_eq!(store.next(1, 0).unwrap(), Some(10));
+        assert_eq!(store.next(1, 0).unwrap(), Some(11));
+        assert_eq!(store.next(1, 1).unwrap(), None);
+
+        store.confirm(1, 0, 10).unwrap();
+        let state = store.state(1, 0).unwrap();
+        assert_eq!(state.next, 12);
+        assert_eq!(state.confirmed, Some(10));
+    }
+
+    #[test]
+    fn test_reconcile_takes_max() {
+        let store = InMemoryNonceStore::new();
+
+        // No local state: server wins
+        assert_eq!(store.reconcile(1, 0, 5).unwrap(), 5);
+
+        // Local ahead of server (in-flight txs not yet seen by the server)
+        store.reset(1, 0, 20).unwrap();
+        assert_eq!(store.reconcile(1, 0, 15).unwrap(), 20);
+
+        // Server ahead of local (nonces consumed by another process)
+        assert_eq!(store.reconcile(1, 0, 30).unwrap(), 30);
+        assert_eq!(store.next(1, 0).unwrap(), Some(30));
+    }
+
+    #[test]
+    fn test_reconcile_never_rolls_back_allocations() {
+        let store = Arc::new(InMemoryNonceStore::new());
+        store.reset(1, 0, 0).unwrap();
+        let allocators: Vec<_> = (0..4)
+            .map(|_| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    (0..200)
+                        .map(|_| store.next(1, 0).unwrap().unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        // The server has seen an earlier allocation than the latest
+        for _ in 0..200 {
+            let server_next = store.state(1, 0).unwrap().next;
+            store.reconcile(1, 0, server_next).unwrap();
+        }
+
+        let mut nonces: Vec<i64> = allocators
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        nonces.sort_unstable();
+        assert_eq!(nonces, (0..800).collect::<Vec<i64>>());
+        assert_eq!(store.state(1, 0).unwrap().next, 800);
+    }
+
+    #[test]
+    fn test_file_store_survives_restart() {
+        let path = temp_path("restart");
+        {
+            let store = FileNonceStore::open(&path).unwrap();
+            store.reset(7, 2, 100).unwrap();
+            assert_eq!(store.next(7, 2).unwrap(), Some(100));
+            assert_eq!(store.next(7, 2).unwrap(), Some(101));
+            store.confirm(7, 2, 100).unwrap();
+        }
+
+        let reopened = FileNonceStore::open(&path).unwrap();
+        let state = reopened.state(7, 2).unwrap();
+        assert_eq!(state.next, 102);
+        assert_eq!(state.confirmed, Some(100));
+        assert_eq!(reopened.reconcile(7, 2, 101).unwrap(), 102);
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_file_store_failed_write_keeps_state() {
+        let path = temp_path("failed-write");
+        let store = FileNonceStore::open(&path).unwrap();
+        store.reset(1, 0, 10).unwrap();
+
+        // A directory in the way of the temp file fails every write
+        let tmp_path = path.with_extension("tmp");
+        fs::create_dir_all(&tmp_path).unwrap();
+        assert!(store.next(1, 0).is_err());
+        assert!(store.confirm(1, 0, 10).is_err());
+        assert!(store.reconcile(1, 0, 20).is_err());
+        assert_eq!(
+            store.state(1, 0),
+            Some(NonceState {
+                next: 10,
+                confirmed: None
+            })
+        );
+
+        fs::remove_dir(&tmp_path).unwrap();
+        assert_eq!(store.next(1, 0).unwrap(), Some(10));
+        let reopened = FileNonceStore::open(&path).unwrap();
+        assert_eq!(reopened.state(1, 0).unwrap().next, 11);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_allocation_in_memory() {
+        let store = Arc::new(InMemoryNonceStore::new());
+        store.reset(1, 0, 0).unwrap();
+
+        let nonces = allocate_concurrently(store).await;
+        assert_eq!(nonces, (0..400).collect::<Vec<i64>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_allocation_file_backed() {
+        let path = temp_path("concurrent");
+        let store = Arc::new(FileNonceStore::open(&path).unwrap());
+        store.reset(1, 0, 1_000).unwrap();
+
+        let nonces = allocate_concurrently(store).await;
+        assert_eq!(nonces, (1_000..1_400).collect::<Vec<i64>>());
+
+        let reopened = FileNonceStore::open(&path).unwrap();
+        assert_eq!(reopened.state(1, 0).unwrap().next, 1_400);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unsent_nonce_is_unused_after_hold() {
+        let hold = Duration::from_secs(2);
+        for policy in [NonceGapPolicy::FailFast, NonceGapPolicy::Renumber] {
+            let store: Arc<dyn NonceStore> = Arc::new(InMemoryNonceStore::new());
+            store.reset(1, 0, 5).unwrap();
+            let flights = Arc::new(InFlightNonces::default());
+            flights
+                .allocate(store.as_ref(), (1, 0), policy, hold)
+                .unwrap();
+            tokio::time::sleep(hold / 2).await;
+            for _ in 0..2 {
+                flights
+                    .allocate(store.as_ref(), (1, 0), policy, hold)
+                    .unwrap();
+            }
+
+            // Nonce 5 is never sent
+            tokio::time::sleep(hold / 2).await;
+            let turn = flights
+                .turn(&store, (1, 0), Some(6), None, policy, hold)
+                .map(|guard| guard.turn);
+            match policy {
+                NonceGapPolicy::FailFast => {
+                    assert!(matches!(
+                        turn,
+                        Err(LighterError::NonceGap { failed_nonce: 5 })
+                    ));
+                    assert_eq!(store.state(1, 0).unwrap().next, 5);
+                    assert_eq!(flights.count((1, 0)), 0);
+                }
+                NonceGapPolicy::Renumber => {
+                    assert!(matches!(
+                        turn,
+                        Ok(Turn::Send {
+                            nonce: 5,
+                            shifted_past: Some(5),
+                            ..
+                        })
+                    ));
+                    assert_eq!(store.state(1, 0).unwrap().next, 7);
+                    assert_eq!(flights.count((1, 0)), 1);
+                }
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sends_do_not_wait_for_lower_nonces() {
+        let hold = Duration::from_secs(2);
+        let policy = NonceGapPolicy::FailFast;
+        let store: Arc<dyn NonceStore> = Arc::new(InMemoryNonceStore::new());
+        store.reset(1, 0, 5).unwrap();
+        let flights = Arc::new(InFlightNonces::default());
+        for _ in 0..3 {
+            flights
+                .allocate(store.as_ref(), (1, 0), policy, hold)
+                .unwrap();
+        }
+
+        let high = flights
+            .turn(&store, (1, 0), Some(7), None, policy, hold)
+            .unwrap();
+        assert!(matches!(high.turn, Turn::Send { nonce: 7, .. }));
+        let low = flights
+            .turn(&store, (1, 0), Some(5), None, policy, hold)
+            .unwrap();
+
+        // Nonce 5 is rejected while 7 is out: 6 fails, the store waits for 7
+        low.settle(false).await.unwrap();
+        assert!(matches!(
+            flights.turn(&store, (1, 0), Some(6), None, policy, hold),
+            Err(LighterError::NonceGap { failed_nonce: 5 })
+        ));
+        assert_eq!(store.state(1, 0).unwrap().next, 8);
+        high.settle(false).await.unwrap();
+        assert_eq!(store.state(1, 0).unwrap().next, 5);
+        assert_eq!(flights.count((1, 0)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_send_counts_as_used() {
+        let hold = Duration::from_secs(2);
+        let policy = NonceGapPolicy::FailFast;
+        let store: Arc<dyn NonceStore> = Arc::new(InMemoryNonceStore::new());
+        store.reset(1, 0, 5).unwrap();
+        let flights = Arc::new(InFlightNonces::default());
+        for _ in 0..2 {
+            flights
+                .allocate(store.as_ref(), (1, 0), policy, hold)
+                .unwrap();
+        }
+
+        let guard = flights
+            .turn(&store, (1, 0), Some(5), None, policy, hold)
+            .unwrap();
+        drop(guard);
+        assert_eq!(flights.count((1, 0)), 1);
+        tokio::time::timeout(Duration::from_secs(1), flights.invalidate((1, 0)))
+            .await
+            .unwrap();
+        flights.reset(store.as_ref(), (1, 0), 6).unwrap();
+        assert_eq!(
+            flights
+                .allocate(store.as_ref(), (1, 0), policy, hold)
+                .unwrap(),
+            Some(6)
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Background tasks that are cancelled and awaited together
+//!
+//! Components that spawn tasks keep them in a [`TaskSet`], a [`JoinSet`]
+//! paired with a [`CancellationToken`], and expose an `async fn shutdown`
+//! that cancels the token, waits for the tasks up to a timeout and returns a
+//! [`ShutdownReport`] naming the tasks that failed or didn't stop in time.
+//! Dropping a set only cancels the token: tasks wind down on their own and
+//! nothing blocks.
+//!
+//! ```
+//! use lighter_rs::tasks::TaskSet;
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let mut tasks = TaskSet::new();
+//! tasks.spawn("ticker", async {
+//!     loop {
+//!         tokio::time::sleep(Duration::from_millis(10)).await;
+//!     }
+//! });
+//! let report = tasks.shutdown(Duration::from_secs(1)).await;
+//! assert!(report.is_clean());
+//! assert_eq!(report.stopped, vec!["ticker".to_string()]);
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::{self, JoinError, JoinSet};
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::Result;
+
+/// Why a task didn't stop cleanly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopFailure {
+    /// Still running at the deadline, so it was aborted
+    TimedOut,
+    /// Panicked, with the panic message
+    Panicked(String),
+    /// Returned an error
+    Failed(String),
+}
+
+impl fmt::Display for StopFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopFailure::TimedOut => f.write_str("did not stop in time"),
+            StopFailure::Panicked(message) => write!(f, "panicked: {}", message),
+            StopFailure::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+/// A task that didn't stop cleanly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskFailure {
+    pub task: String,
+    pub reason: StopFailure,
+}
+
+/// Outcome of a shutdown
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Tasks that stopped cleanly, in the order they stopped
+    pub stopped: Vec<String>,
+    pub failed: Vec<TaskFailure>,
+}
+
+impl ShutdownReport {
+    /// Whether every task stopped cleanly
+    pub fn is_clean(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Add the outcome of another shutdown
+    pub fn merge(&mut self, other: ShutdownReport) {
+        self.stopped.extend(other.stopped);
+        self.failed.extend(other.failed);
+    }
+
+    pub(crate) fn fail(&mut self, task: impl Into<String>, reason: StopFailure) {
+        self.failed.push(TaskFailure {
+            task: task.into(),
+            reason,
+        });
+    }
+}
+
+/// Named tasks sharing a cancellation token
+///
+/// Tasks must be spawned within a Tokio runtime. Dropping the set cancels
+/// the token and detaches the tasks.
+#[derive(Debug, Default)]
+pub struct TaskSet {
+    tasks: JoinSet<Result<()>>,
+    names: HashMap<task::Id, String>,
+    token: CancellationToken,
+}
+
+impl TaskSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token cancelled when the set shuts down or is dropped
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Run `task` until it finishes or the set is cancelled
+    ///
+    /// Cancellation drops the future wherever it is waiting; use
+    /// [`spawn_graceful`](Self::spawn_graceful) for tasks that must finish
+    /// what they are doing.
+    pub fn spawn<F>(&mut self, name: impl Into<String>, task: F)
+    where
+        F: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.spawn_graceful(name, |token| async move {
+            tokio::select! {
+                _ = token.cancelled() => Ok(()),
+                result = task => result,
+            }
+        });
+    }
+
+    /// Run the task built by `task`, which returns by itself once the token
+    /// it is given is cancelled
+    pub fn spawn_graceful<F, Fut>(&mut self, name: impl Into<String>, task: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let handle = self.tasks.spawn(task(self.token.clone()));
+        self.names.insert(handle.id(), name.into());
+    }
+
+    /// Cancel the tasks without waiting for them
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    /// Tasks not yet joined
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Cancel the tasks and wait up to `timeout` for them to stop
+    ///
+    /// Tasks still running at the deadline are aborted and reported as
+    /// [`StopFailure::TimedOut`]. The set is empty afterwards.
+    pub async fn shutdown(mut self, timeout: Duration) -> ShutdownReport {
+        self.token.cancel();
+        let mut report = ShutdownReport::default();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match tokio::time::timeout_at(deadline, self.tasks.join_next_with_id()).await {
+                Ok(Some(joined)) => self.record(joined, &mut report),
+                Ok(None) => return report,
+                Err(_) => break,
+            }
+        }
+
+        self.tasks.abort_all();
+        while let Some(joined) = self.tasks.join_next_with_id().await {
+            self.record(joined, &mut report);
+        }
+        report
+    }
+
+    fn record(
+        &mut self,
+        joined: std::result::Result<(task::Id, Result<()>), JoinError>,
+        report: &mut ShutdownReport,
+    ) {
+        let (id, outcome) = match joined {
+            Ok((id, Ok(()))) => (id, None),
+            Ok((id, Err(e))) => (id, Some(StopFailure::Failed(e.to_string()))),
+            Err(e) if e.is_panic() => {
+                let id = e.id();
+                (id, Some(StopFailure::Panicked(panic_message(e))))
+            }
+            Err(e) => (e.id(), Some(StopFailure::TimedOut)),
+        };
+        let name = self.names.remove(&id).unwrap_or_else(|| id.to_string());
+        match outcome {
+            None => report.stopped.push(name),
+            Some(reason) => report.fail(name, reason),
+        }
+    }
+}
+
+impl Drop for TaskSet {
+    /// Cancel the tasks and let them finish in the background
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.tasks.detach_all();
+    }
+}
+
+fn panic_message(error: JoinError) -> String {
+    let payload = error.into_panic();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::LighterError;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_shutdown_reports_each_task() {
+        let finished = Arc::new(AtomicBool::new(false));
+        let mut tasks = TaskSet::new();
+        tasks.spawn("idle", std::future::pending());
+        let done = finished.clone();
+        tasks.spawn_graceful("graceful", |token| async move {
+            token.cancelled().await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            done.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        tasks.spawn_graceful("stuck", |_| std::future::pending());
+        // Cancelled before their first poll, plain tasks would stop cleanly
+        tasks.spawn_graceful("failing", |_| async {
+            Err(LighterError::Other("broken".to_string()))
+        });
+        tasks.spawn_graceful("panicking", |_| async { panic!("boom") });
+        assert_eq!(tasks.len(), 5);
+
+        let report = tasks.shutdown(Duration::from_millis(200)).await;
+        assert!(finished.load(Ordering::SeqCst));
+        let mut stopped = report.stopped.clone();
+        stopped.sort();
+        assert_eq!(stopped, vec!["graceful", "idle"]);
+
+        let mut failed: Vec<_> = report
+            .failed
+            .iter()
+            .map(|f| (f.task.as_str(), f.reason.clone()))
+            .collect();
+        failed.sort_by_key(|(task, _)| *task);
+        assert_eq!(
+            failed,
+            vec![
+                ("failing", StopFailure::Failed("broken".to_string())),
+                ("panicking", StopFailure::Panicked("boom".to_string())),
+                ("stuck", StopFailure::TimedOut),
+            ]
+        );
+        assert!(!report.is_clean());
+    }
+
+    #[tokio::test]
+    async fn test_drop_cancels_without_blocking() {
+        let mut tasks = TaskSet::new();
+        let token = tasks.token();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tasks.spawn_graceful("graceful", |token| async move {
+            token.cancelled().await;
+            let _ = tx.send(());
+            Ok(())
+        });
+        drop(tasks);
+        assert!(token.is_cancelled());
+        tokio::time::timeout(Duration::from_secs(1), rx)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
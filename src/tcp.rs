@@ -0,0 +1,167 @@
+//! TCP socket options of REST and WebSocket connections
+//!
+//! [`TcpOptions`] set on [`HTTPClient::set_tcp_options`] or
+//! [`WsConfig::tcp`] apply to every connection the client opens. Options
+//! left at `None` keep each client's own default.
+//!
+//! ```
+//! use lighter_rs::tcp::TcpOptions;
+//! use std::time::Duration;
+//!
+//! let options = TcpOptions::default()
+//!     .nodelay(true)
+//!     .keepalive(Duration::from_secs(5))
+//!     .keepalive_interval(Duration::from_secs(1))
+//!     .local_address("10.0.0.2".parse().unwrap());
+//! ```
+//!
+//! [`HTTPClient::set_tcp_options`]: crate::client::HTTPClient::set_tcp_options
+//! [`WsConfig::tcp`]: crate::ws_client::WsConfig::tcp
+
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::{TcpSocket, TcpStream};
+
+/// Socket options of outgoing TCP connections
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Disable Nagle's algorithm; `None` keeps the client's default, which
+    /// is on for REST and off for WebSocket connections
+    pub nodelay: Option<bool>,
+    /// Idle time before keepalive probes are sent; `None` keeps the REST
+    /// default of 15 seconds and no keepalive on WebSocket connections
+    pub keepalive: Option<Duration>,
+    /// Time between keepalive probes, `None` for the system default
+    pub keepalive_interval: Option<Duration>,
+    /// Local address to connect from, selecting the interface
+    pub local_address: Option<IpAddr>,
+}
+
+impl TcpOptions {
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    pub fn keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Set the time between probes; takes effect with [`keepalive`](Self::keepalive)
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local_address = Some(address);
+        self
+    }
+
+    /// Apply the options to a REST client under construction
+    pub(crate) fn apply_to(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(nodelay) = self.nodelay {
+            builder = builder.tcp_nodelay(nodelay);
+        }
+        if let Some(idle) = self.keepalive {
+            builder = builder.tcp_keepalive(idle);
+        }
+        if let Some(interval) = self.keepalive_interval {
+            builder = builder.tcp_keepalive_interval(interval);
+        }
+        if let Some(address) = self.local_address {
+            builder = builder.local_address(address);
+        }
+        builder
+    }
+
+    /// Connect to `host`, trying each address it resolves to in turn
+    pub(crate) async fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for address in tokio::net::lookup_host((host, port)).await? {
+            match self.connect_to(address).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} resolved to no addresses", host),
+            )
+        }))
+    }
+
+    async fn connect_to(&self, address: SocketAddr) -> io::Result<TcpStream> {
+        let socket = match address {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+        if let Some(local) = self.local_address {
+            if local.is_ipv4() != address.is_ipv4() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Can't reach {} from {}", address, local),
+                ));
+            }
+            socket.bind(SocketAddr::new(local, 0))?;
+        }
+        if let Some(idle) = self.keepalive {
+            let mut keepalive = TcpKeepalive::new().with_time(idle);
+            if let Some(interval) = self.keepalive_interval {
+                keepalive = keepalive.with_interval(interval);
+            }
+            SockRef::from(&socket).set_tcp_keepalive(&keepalive)?;
+        }
+        let stream = socket.connect(address).await?;
+        if let Some(nodelay) = self.nodelay {
+            stream.set_nodelay(nodelay)?;
+        }
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_options_apply_to_the_socket() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let options = TcpOptions::default()
+            .nodelay(true)
+            .keepalive(Duration::from_secs(7))
+            .keepalive_interval(Duration::from_secs(2))
+            .local_address("127.0.0.1".parse().unwrap());
+        let stream = options.connect("localhost", port).await.unwrap();
+        let socket = SockRef::from(&stream);
+        assert!(socket.tcp_nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        assert_eq!(socket.tcp_keepalive_time().unwrap(), Duration::from_secs(7));
+        assert_eq!(
+            socket.tcp_keepalive_interval().unwrap(),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            stream.local_addr().unwrap().ip(),
+            options.local_address.unwrap()
+        );
+
+        // Defaults leave the socket alone
+        let plain = TcpOptions::default()
+            .connect("127.0.0.1", port)
+            .await
+            .unwrap();
+        let socket = SockRef::from(&plain);
+        assert!(!socket.tcp_nodelay().unwrap());
+        assert!(!socket.keepalive().unwrap());
+
+        let v6 = TcpOptions::default().local_address("::1".parse().unwrap());
+        assert!(v6.connect("127.0.0.1", port).await.is_err());
+    }
+}
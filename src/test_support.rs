@@ -0,0 +1,125 @@
+//! Shared helpers for unit tests
+
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::client::TxClient;
+use crate::types::{
+    AccountIndex, ApiKeyIndex, BaseAmount, ChainId, MarketIndex, OrderParams, Price, Side,
+};
+
+/// Throwaway API private key for unit tests
+pub(crate) const TEST_KEY: &str =
+    "0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+
+/// Client signing with [`TEST_KEY`] for account 12345 and API key 0 on
+/// chain 1, with auth tokens enabled; an empty `url` leaves it offline
+pub(crate) fn test_client(url: &str) -> TxClient {
+    test_client_for(url, 12345, 0)
+}
+
+/// [`test_client`] for another account or API key
+pub(crate) fn test_client_for(url: &str, account_index: i64, api_key_index: u8) -> TxClient {
+    let mut client = TxClient::from_ids(
+        url,
+        TEST_KEY,
+        AccountIndex::new(account_index).unwrap(),
+        ApiKeyIndex::new(api_key_index).unwrap(),
+        ChainId::new(1).unwrap(),
+    )
+    .unwrap();
+    if !url.is_empty() {
+        client.enable_experimental_auth_tokens().unwrap();
+    }
+    client
+}
+
+/// [`OrderParams`] from raw values known to be in range
+pub(crate) fn order_params(
+    market_index: u8,
+    side: Side,
+    base_amount: i64,
+    price: u32,
+) -> OrderParams {
+    OrderParams::new(
+        MarketIndex::new(market_index).unwrap(),
+        side,
+        BaseAmount::new(base_amount).unwrap(),
+        Price::new(price).unwrap(),
+    )
+}
+
+/// Serve `frames` to a single WebSocket client connection, then close
+pub(crate) async fn spawn_mock_ws_server(frames: Vec<String>) -> SocketAddr {
+    spawn_mock_ws_server_for(frames, 1).await
+}
+
+/// Serve `frames` to each of `connections` consecutive client connections
+pub(crate) async fn spawn_mock_ws_server_for(
+    frames: Vec<String>,
+    connections: usize,
+) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        for _ in 0..connections {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for frame in &frames {
+                // The client may hang up early
+                if ws.send(Message::Text(frame.clone())).await.is_err() {
+                    break;
+                }
+            }
+            let _ = ws.close(None).await;
+            // Drain subscriptions and the close handshake
+            while let Some(Ok(_)) = ws.next().await {}
+        }
+    });
+    addr
+}
+
+/// Serve `bursts` to a single client connection, pausing after each burst,
+/// then close
+pub(crate) async fn spawn_mock_ws_server_in_bursts(
+    bursts: Vec<Vec<String>>,
+    pause: std::time::Duration,
+) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let (mut write, mut read) = ws.split();
+        // Keep reading so the client's subscriptions never back up
+        tokio::spawn(async move { while let Some(Ok(_)) = read.next().await {} });
+        for burst in bursts {
+            for frame in burst {
+                if write.send(Message::Text(frame)).await.is_err() {
+                    return;
+                }
+            }
+            tokio::time::sleep(pause).await;
+        }
+        let _ = write.close().await;
+    });
+    addr
+}
+
+/// Close the first client connection right away and hold the second open
+pub(crate) async fn spawn_flapping_ws_server() -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let _ = ws.close(None).await;
+        while let Some(Ok(_)) = ws.next().await {}
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(_)) = ws.next().await {}
+    });
+    addr
+}
@@ -0,0 +1,359 @@
+//! Client-side order throttling
+//!
+//! [`OrderThrottle`] spaces out new orders per market with a token bucket
+//! and can refuse orders that would trade against the account's own resting
+//! quotes. Attach one with
+//! [`TxClient::set_order_throttle`](crate::client::TxClient::set_order_throttle).
+//!
+//! The throttle only sees the resting quotes it is told about; keep it up to
+//! date with [`OrderThrottle::sync_resting_quotes`] from the account stream.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::errors::{LighterError, Result};
+use crate::utils::checked_price;
+use crate::ws_client::AccountSnapshot;
+
+/// What happens to an order that arrives while its market is throttled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleMode {
+    /// Wait until the market has capacity again
+    #[default]
+    Wait,
+    /// Fail with [`LighterError::Throttled`]
+    FailFast,
+}
+
+/// Order throttle settings
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ThrottleConfig {
+    /// Sustained orders per second and market
+    pub orders_per_sec: f64,
+    /// Orders a market may send back to back after being idle
+    pub burst: u32,
+    pub mode: ThrottleMode,
+    /// Refuse orders that would cross the account's own resting quotes
+    pub prevent_self_cross: bool,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            orders_per_sec: 10.0,
+            burst: 10,
+            mode: ThrottleMode::Wait,
+            prevent_self_cross: false,
+        }
+    }
+}
+
+impl ThrottleConfig {
+    /// Fail unless the rate is positive and the burst at least 1
+    pub fn validate(&self) -> Result<()> {
+        if !(self.orders_per_sec.is_finite() && self.orders_per_sec > 0.0) {
+            return Err(LighterError::InvalidConfiguration(format!(
+                "orders_per_sec must be positive, got {}",
+                self.orders_per_sec
+            )));
+        }
+        if self.burst == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "burst must be at least 1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Resting order of the account, in wire units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestingQuote {
+    pub is_ask: bool,
+    pub price: u32,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-market token bucket with an optional self-cross check
+#[derive(Debug)]
+pub struct OrderThrottle {
+    config: ThrottleConfig,
+    buckets: Mutex<HashMap<u8, Bucket>>,
+    resting: Mutex<HashMap<u8, Vec<RestingQuote>>>,
+}
+
+impl OrderThrottle {
+    /// Create a throttle; the rate must be positive and the burst at least 1
+    pub fn new(config: ThrottleConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            resting: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Replace the known resting quotes of a market
+    pub fn set_resting_quotes(&self, market_index: u8, quotes: Vec<RestingQuote>) {
+        self.resting.lock().unwrap().insert(market_index, quotes);
+    }
+
+    /// Replace a market's resting quotes with the open orders of `account`
+    ///
+    /// `price_decimals` is the market's price precision, used to convert
+    /// order prices to wire units.
+    pub fn sync_resting_quotes(
+        &self,
+        account: &AccountSnapshot,
+        market_index: u8,
+        price_decimals: u32,
+    ) -> Result<()> {
+        let quotes = account
+            .orders
+            .values()
+            .flatten()
+            .filter(|order| order.market_index == market_index as u32 && !order.is_terminal())
+            .map(|order| {
+                Ok(RestingQuote {
+                    is_ask: order.is_ask,
+                    price: checked_price(order.price, price_decimals)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.set_resting_quotes(market_index, quotes);
+        Ok(())
+    }
+
+    /// Fail if an order would trade against one of the account's resting quotes
+    pub fn check_self_cross(&self, market_index: u8, is_ask: bool, price: u32) -> Result<()> {
+        let resting = self.resting.lock().unwrap();
+        let crossed = resting
+            .get(&market_index)
+            .into_iter()
+            .flatten()
+            .filter(|quote| quote.is_ask != is_ask)
+            .find(|quote| {
+                if is_ask {
+                    price <= quote.price
+                } else {
+                    price >= quote.price
+                }
+            });
+        match crossed {
+            Some(quote) => Err(LighterError::SelfCross {
+                market_index,
+                price,
+                resting_price: quote.price,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Take capacity for one order in a market, waiting or failing per the mode
+    pub async fn acquire(&self, market_index: u8) -> Result<()> {
+        self.acquire_with(&self.config, market_index).await
+    }
+
+    /// [`acquire`](Self::acquire) under `config` instead of the throttle's own
+    ///
+    /// Markets keep their buckets, so a new rate or burst applies to the
+    /// capacity already accrued.
+    pub async fn acquire_with(&self, config: &ThrottleConfig, market_index: u8) -> Result<()> {
+        loop {
+            match self.try_take(config, market_index) {
+                Ok(()) => return Ok(()),
+                Err(retry_after) => match config.mode {
+                    ThrottleMode::FailFast => return Err(LighterError::Throttled { retry_after }),
+                    ThrottleMode::Wait => tokio::time::sleep(retry_after).await,
+                },
+            }
+        }
+    }
+
+    /// Admit a new order: the self-cross check (if enabled), then capacity
+    pub async fn admit(&self, market_index: u8, is_ask: bool, price: u32) -> Result<()> {
+        self.admit_with(&self.config, market_index, is_ask, price)
+            .await
+    }
+
+    /// [`admit`](Self::admit) under `config` instead of the throttle's own
+    pub async fn admit_with(
+        &self,
+        config: &ThrottleConfig,
+        market_index: u8,
+        is_ask: bool,
+        price: u32,
+    ) -> Result<()> {
+        if config.prevent_self_cross {
+            self.check_self_cross(market_index, is_ask, price)?;
+        }
+        self.acquire_with(config, market_index).await
+    }
+
+    /// Take a token, or return how long until one is available
+    fn try_take(
+        &self,
+        config: &ThrottleConfig,
+        market_index: u8,
+    ) -> std::result::Result<(), Duration> {
+        let now = Instant::now();
+        let burst = config.burst as f64;
+        let rate = config.orders_per_sec;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(market_index).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(mode: ThrottleMode) -> OrderThrottle {
+        OrderThrottle::new(ThrottleConfig {
+            orders_per_sec: 2.0,
+            burst: 2,
+            mode,
+            prevent_self_cross: true,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_mode_spaces_a_burst() {
+        let throttle = throttle(ThrottleMode::Wait);
+        let start = Instant::now();
+        for _ in 0..10 {
+            throttle.acquire(0).await.unwrap();
+        }
+        // Two go out at once, the other eight at 2/sec
+        assert!(start.elapsed() >= Duration::from_secs(4));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // Markets have separate buckets
+        let start = Instant::now();
+        throttle.acquire(1).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fail_fast_mode() {
+        let throttle = throttle(ThrottleMode::FailFast);
+        let mut results = Vec::new();
+        for _ in 0..10 {
+            results.push(throttle.acquire(0).await);
+        }
+
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 2);
+        match &results[2] {
+            Err(LighterError::Throttled { retry_after }) => {
+                assert_eq!(*retry_after, Duration::from_millis(500))
+            }
+            other => panic!("expected Throttled, got {:?}", other),
+        }
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert!(throttle.acquire(0).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_self_cross_check() {
+        let throttle = throttle(ThrottleMode::FailFast);
+        throttle.set_resting_quotes(
+            0,
+            vec![
+                RestingQuote {
+                    is_ask: true,
+                    price: 100_000,
+                },
+                RestingQuote {
+                    is_ask: false,
+                    price: 99_000,
+                },
+            ],
+        );
+
+        assert!(matches!(
+            throttle.admit(0, false, 100_000).await,
+            Err(LighterError::SelfCross {
+                market_index: 0,
+                price: 100_000,
+                resting_price: 100_000
+            })
+        ));
+        assert!(matches!(
+            throttle.admit(0, true, 98_500).await,
+            Err(LighterError::SelfCross {
+                resting_price: 99_000,
+                ..
+            })
+        ));
+        assert!(throttle.admit(0, false, 99_999).await.is_ok());
+        assert!(throttle.admit(0, true, 99_001).await.is_ok());
+        // Other markets are unaffected
+        assert!(throttle.admit(1, false, 200_000).await.is_ok());
+    }
+
+    #[test]
+    fn test_sync_from_account() {
+        let account: AccountSnapshot = serde_json::from_value(serde_json::json!({
+            "orders": {"0": [
+                {"order_index": 1, "market_index": 0, "is_ask": true, "price": "10.5", "remaining_base_amount": "1", "status": "open"},
+                {"order_index": 2, "market_index": 0, "is_ask": false, "price": "9", "remaining_base_amount": "1", "status": "filled"}
+            ]}
+        }))
+        .unwrap();
+        let throttle = throttle(ThrottleMode::FailFast);
+        throttle.sync_resting_quotes(&account, 0, 2).unwrap();
+
+        assert!(throttle.check_self_cross(0, false, 1_050).is_err());
+        // The filled bid is no longer resting
+        assert!(throttle.check_self_cross(0, true, 900).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        for config in [
+            ThrottleConfig {
+                orders_per_sec: 0.0,
+                ..Default::default()
+            },
+            ThrottleConfig {
+                burst: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                OrderThrottle::new(config),
+                Err(LighterError::InvalidConfiguration(_))
+            ));
+        }
+    }
+}
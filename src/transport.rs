@@ -0,0 +1,374 @@
+//! HTTP transport behind [`HTTPClient`]
+//!
+//! [`HTTPClient`] builds each request's path, query, headers and body and
+//! hands them to a [`Transport`]. [`ReqwestTransport`] sends them over the
+//! network and is used unless another transport is set with
+//! [`HTTPClient::with_transport`]. With the `test-util` feature,
+//! [`MockTransport`] answers from scripted responses, so code built on the
+//! client can be tested without a server:
+//!
+//! ```
+//! # #[cfg(feature = "test-util")]
+//! # async fn example() -> lighter_rs::Result<()> {
+//! use lighter_rs::client::HTTPClient;
+//! use lighter_rs::transport::MockTransport;
+//! use lighter_rs::types::{AccountIndex, ApiKeyIndex};
+//! use std::sync::Arc;
+//!
+//! let mock = Arc::new(MockTransport::new());
+//! mock.on_get("/api/v1/nextNonce", serde_json::json!({"code": 200, "nonce": 7}));
+//! let http = HTTPClient::with_transport(Box::new(mock.clone()));
+//!
+//! let nonce = http
+//!     .get_next_nonce(AccountIndex::new(12345)?, ApiKeyIndex::new(0)?)
+//!     .await?;
+//! assert_eq!(nonce, 7);
+//! assert_eq!(mock.requests()[0].query_param("account_index"), Some("12345"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`HTTPClient`]: crate::client::HTTPClient
+//! [`HTTPClient::with_transport`]: crate::client::HTTPClient::with_transport
+
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::errors::Result;
+use crate::tcp::TcpOptions;
+
+#[cfg(any(test, feature = "test-util"))]
+pub use mock::{MockTransport, RecordedRequest};
+
+/// Future returned by [`Transport`] methods
+pub type TransportFuture<'a> = BoxFuture<'a, Result<TransportResponse>>;
+
+/// Answer to a request, whatever its status
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TransportResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// 200 answer with a JSON body
+    pub fn json(body: &serde_json::Value) -> Self {
+        Self::new(StatusCode::OK, body.to_string())
+    }
+
+    /// Add a header; panics if `name` or `value` isn't a valid header
+    pub fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers
+            .insert(name, value.parse().expect("valid header value"));
+        self
+    }
+
+    /// Value of a header, if present and text
+    pub fn header_str(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    /// Parse the body as JSON
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_str(&self.body)?)
+    }
+}
+
+/// Sends requests of an [`HTTPClient`](crate::client::HTTPClient)
+///
+/// Paths start with `/` and follow the base URL, including any path prefix
+/// and API version, e.g. `/api/v1/nextNonce`. A path may carry its own query
+/// string, to which `query` is appended. Rate limit retries and status
+/// handling stay with the client, so a transport answers every status.
+pub trait Transport: Send + Sync {
+    fn get_json<'a>(
+        &'a self,
+        path: &'a str,
+        query: &'a [(&'a str, String)],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a>;
+
+    /// POST `body`, a JSON document, possibly compressed as `headers` say
+    fn post_json<'a>(
+        &'a self,
+        path: &'a str,
+        body: &'a [u8],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn get_json<'a>(
+        &'a self,
+        path: &'a str,
+        query: &'a [(&'a str, String)],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a> {
+        (**self).get_json(path, query, headers)
+    }
+
+    fn post_json<'a>(
+        &'a self,
+        path: &'a str,
+        body: &'a [u8],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a> {
+        (**self).post_json(path, body, headers)
+    }
+}
+
+/// Transport over a `reqwest` client
+#[derive(Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+    base_url: String,
+}
+
+impl ReqwestTransport {
+    /// Send to `base_url` with a per-request `timeout` and socket options
+    pub fn new(base_url: &str, timeout: Duration, tcp: &TcpOptions) -> Result<Self> {
+        Ok(Self {
+            client: tcp.apply_to(Client::builder().timeout(timeout)).build()?,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send(request: RequestBuilder, headers: &HeaderMap) -> Result<TransportResponse> {
+        let response = request.headers(headers.clone()).send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        Ok(TransportResponse {
+            status,
+            headers,
+            body: response.text().await?,
+        })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn get_json<'a>(
+        &'a self,
+        path: &'a str,
+        query: &'a [(&'a str, String)],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a> {
+        let mut request = self.client.get(self.url(path));
+        if !query.is_empty() {
+            request = request.query(query);
+        }
+        Box::pin(Self::send(request, headers))
+    }
+
+    fn post_json<'a>(
+        &'a self,
+        path: &'a str,
+        body: &'a [u8],
+        headers: &'a HeaderMap,
+    ) -> TransportFuture<'a> {
+        let request = self
+            .client
+            .post(self.url(path))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_vec());
+        Box::pin(Self::send(request, headers))
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+mod mock {
+    use super::*;
+    use crate::errors::LighterError;
+    use reqwest::Method;
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::Mutex;
+
+    /// A request a [`MockTransport`] received
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct RecordedRequest {
+        pub method: Method,
+        /// Path without its query string
+        pub path: String,
+        /// Parameters of the path's query string and the query, in order
+        pub query: Vec<(String, String)>,
+        pub headers: HeaderMap,
+        pub body: Option<Vec<u8>>,
+    }
+
+    impl RecordedRequest {
+        /// First value of a query parameter
+        pub fn query_param(&self, name: &str) -> Option<&str> {
+            self.query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        }
+
+        /// Body parsed as JSON, `None` without a body or if it isn't JSON
+        pub fn json_body(&self) -> Option<serde_json::Value> {
+            serde_json::from_slice(self.body.as_deref()?).ok()
+        }
+    }
+
+    /// Transport answering from scripted responses
+    ///
+    /// Responses are scripted per method and path and served in order; the
+    /// last one of a path keeps being served. A request to a path without
+    /// responses fails with [`LighterError::InvalidConfiguration`]. Every
+    /// request is recorded, so share the mock through an `Arc` to inspect
+    /// them.
+    #[derive(Debug, Default)]
+    pub struct MockTransport {
+        routes: Mutex<HashMap<(Method, String), VecDeque<TransportResponse>>>,
+        requests: Mutex<Vec<RecordedRequest>>,
+    }
+
+    impl MockTransport {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queue a response to `method` requests of `path`
+        pub fn respond(&self, method: Method, path: &str, response: TransportResponse) {
+            self.routes
+                .lock()
+                .unwrap()
+                .entry((method, path.to_string()))
+                .or_default()
+                .push_back(response);
+        }
+
+        /// Queue a 200 JSON answer to GETs of `path`
+        pub fn on_get(&self, path: &str, body: serde_json::Value) {
+            self.respond(Method::GET, path, TransportResponse::json(&body));
+        }
+
+        /// Queue a 200 JSON answer to POSTs to `path`
+        pub fn on_post(&self, path: &str, body: serde_json::Value) {
+            self.respond(Method::POST, path, TransportResponse::json(&body));
+        }
+
+        /// Requests received so far, oldest first
+        pub fn requests(&self) -> Vec<RecordedRequest> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn answer(
+            &self,
+            method: Method,
+            path: &str,
+            query: &[(&str, String)],
+            headers: &HeaderMap,
+            body: Option<&[u8]>,
+        ) -> Result<TransportResponse> {
+            let (path, inline) = path.split_once('?').unwrap_or((path, ""));
+            let query = inline
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (key.to_string(), value.to_string())
+                })
+                .chain(query.iter().map(|(k, v)| (k.to_string(), v.clone())))
+                .collect();
+            self.requests.lock().unwrap().push(RecordedRequest {
+                method: method.clone(),
+                path: path.to_string(),
+                query,
+                headers: headers.clone(),
+                body: body.map(<[u8]>::to_vec),
+            });
+
+            let mut routes = self.routes.lock().unwrap();
+            let responses = routes
+                .get_mut(&(method.clone(), path.to_string()))
+                .filter(|responses| !responses.is_empty())
+                .ok_or_else(|| {
+                    LighterError::InvalidConfiguration(format!(
+                        "MockTransport has no response for {} {}",
+                        method, path
+                    ))
+                })?;
+            Ok(if responses.len() > 1 {
+                responses.pop_front().unwrap()
+            } else {
+                responses[0].clone()
+            })
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn get_json<'a>(
+            &'a self,
+            path: &'a str,
+            query: &'a [(&'a str, String)],
+            headers: &'a HeaderMap,
+        ) -> TransportFuture<'a> {
+            let response = self.answer(Method::GET, path, query, headers, None);
+            Box::pin(async move { response })
+        }
+
+        fn post_json<'a>(
+            &'a self,
+            path: &'a str,
+            body: &'a [u8],
+            headers: &'a HeaderMap,
+        ) -> TransportFuture<'a> {
+            let response = self.answer(Method::POST, path, &[], headers, Some(body));
+            Box::pin(async move { response })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_mock_serves_scripted_responses_in_order() {
+        let mock = MockTransport::new();
+        mock.on_get("/api/v1/a", json!({"n": 1}));
+        mock.on_get("/api/v1/a", json!({"n": 2}));
+        let headers = HeaderMap::new();
+
+        for expected in [1, 2, 2] {
+            let response = mock
+                .get_json("/api/v1/a?x=1", &[("y", "2".to_string())], &headers)
+                .await
+                .unwrap();
+            assert_eq!(
+                response.parse::<serde_json::Value>().unwrap()["n"],
+                expected
+            );
+        }
+        assert!(mock.post_json("/api/v1/a", b"{}", &headers).await.is_err());
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].path, "/api/v1/a");
+        assert_eq!(requests[0].query_param("x"), Some("1"));
+        assert_eq!(requests[0].query_param("y"), Some("2"));
+        assert_eq!(requests[3].json_body(), Some(json!({})));
+    }
+}
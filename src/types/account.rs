@@ -0,0 +1,206 @@
+//! Account state types used for client-side order checks
+
+use crate::constants::MIN_ORDER_BASE_AMOUNT;
+use crate::errors::{LighterError, Result};
+use crate::types::{BaseAmount, Side};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// Open position in a single market
+///
+/// `base_amount` is signed and expressed in the market's integer base units:
+/// positive for long, negative for short, zero for flat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub market_index: u8,
+    pub base_amount: i64,
+}
+
+impl Position {
+    /// Create a new position
+    pub fn new(market_index: u8, base_amount: i64) -> Self {
+        Self {
+            market_index,
+            base_amount,
+        }
+    }
+
+    /// Whether the position is long
+    pub fn is_long(&self) -> bool {
+        self.base_amount > 0
+    }
+
+    /// Whether the position is short
+    pub fn is_short(&self) -> bool {
+        self.base_amount < 0
+    }
+
+    /// Absolute position size in base units
+    pub fn abs_base_amount(&self) -> i64 {
+        self.base_amount.saturating_abs()
+    }
+
+    /// Check that a reduce-only order can only shrink this position
+    ///
+    /// The order side must be opposite to the position sign (sell to reduce a
+    /// long, buy to reduce a short) and its size must not exceed the absolute
+    /// position size. With `auto_clamp` an oversized order is shrunk to the
+    /// position size instead of rejected.
+    ///
+    /// Returns the base amount the order should be submitted with.
+    pub fn check_reduce_only(&self, is_ask: u8, base_amount: i64, auto_clamp: bool) -> Result<i64> {
+        let reduces = (self.is_long() && is_ask == 1) || (self.is_short() && is_ask == 0);
+        if !reduces {
+            return Err(LighterError::ReduceOnlyWouldIncreasePosition {
+                market_index: self.market_index,
+                position: self.base_amount,
+            });
+        }
+
+        let max = self.abs_base_amount();
+        if base_amount > max {
+            if auto_clamp {
+                return Ok(max);
+            }
+            return Err(LighterError::ReduceOnlyExceedsPosition {
+                base_amount,
+                position: max,
+            });
+        }
+
+        Ok(base_amount)
+    }
+
+    /// Side of an order that reduces this position
+    pub fn closing_side(&self) -> Side {
+        if self.is_short() {
+            Side::Buy
+        } else {
+            Side::Sell
+        }
+    }
+
+    /// Base amount that closes the position, or `portion` of it
+    ///
+    /// The full close uses the position's own units. A portion must be in
+    /// `(0, 1]` and is rounded down to whole base units; closes below
+    /// `MIN_ORDER_BASE_AMOUNT`, including any close of a flat position, fail.
+    pub fn close_amount(&self, portion: Option<Decimal>) -> Result<BaseAmount> {
+        let size = self.abs_base_amount();
+        let amount = match portion {
+            None => size,
+            Some(portion) if portion > Decimal::ZERO && portion <= Decimal::ONE => {
+                (Decimal::from(size) * portion)
+                    .floor()
+                    .to_i64()
+                    .unwrap_or(0)
+            }
+            Some(portion) => {
+                return Err(LighterError::ValidationError(format!(
+                    "Portion to close must be in (0, 1], got {}",
+                    portion
+                )))
+            }
+        };
+        if amount < MIN_ORDER_BASE_AMOUNT {
+            return Err(LighterError::ValidationError(format!(
+                "Nothing to close in market {}: position {}, close amount {}",
+                self.market_index, self.base_amount, amount
+            )));
+        }
+        BaseAmount::new(amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduce_only_long_position() {
+        let position = Position::new(0, 1_000);
+
+        assert_eq!(position.check_reduce_only(1, 400, false).unwrap(), 400);
+
+        let result = position.check_reduce_only(0, 400, false);
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::ReduceOnlyWouldIncreasePosition { .. }
+        ));
+    }
+
+    #[test]
+    fn test_reduce_only_short_position() {
+        let position = Position::new(0, -1_000);
+
+        assert_eq!(position.check_reduce_only(0, 1_000, false).unwrap(), 1_000);
+
+        let result = position.check_reduce_only(1, 400, false);
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::ReduceOnlyWouldIncreasePosition { .. }
+        ));
+    }
+
+    #[test]
+    fn test_reduce_only_exact_close() {
+        let position = Position::new(0, 2_500);
+        assert_eq!(position.check_reduce_only(1, 2_500, false).unwrap(), 2_500);
+    }
+
+    #[test]
+    fn test_reduce_only_oversize_rejected() {
+        let position = Position::new(0, 2_500);
+        let result = position.check_reduce_only(1, 2_501, false);
+        assert!(matches!(
+            result.unwrap_err(),
+            LighterError::ReduceOnlyExceedsPosition {
+                base_amount: 2_501,
+                position: 2_500
+            }
+        ));
+    }
+
+    #[test]
+    fn test_reduce_only_clamped() {
+        let position = Position::new(0, -2_500);
+        assert_eq!(position.check_reduce_only(0, 9_000, true).unwrap(), 2_500);
+    }
+
+    #[test]
+    fn test_reduce_only_flat_position() {
+        let position = Position::new(0, 0);
+        assert!(position.check_reduce_only(0, 1, true).is_err());
+        assert!(position.check_reduce_only(1, 1, true).is_err());
+    }
+
+    #[test]
+    fn test_close_amount() {
+        let long = Position::new(0, 1_001);
+        assert_eq!(long.close_amount(None).unwrap().get(), 1_001);
+        assert_eq!(
+            long.close_amount(Some(Decimal::new(5, 1))).unwrap().get(),
+            500
+        );
+        assert_eq!(long.close_amount(Some(Decimal::ONE)).unwrap().get(), 1_001);
+        assert_eq!(long.closing_side(), Side::Sell);
+
+        let short = Position::new(0, -3);
+        assert_eq!(
+            short.close_amount(Some(Decimal::new(9, 1))).unwrap().get(),
+            2
+        );
+        assert_eq!(short.closing_side(), Side::Buy);
+        // Floors to zero
+        assert!(short.close_amount(Some(Decimal::new(1, 1))).is_err());
+
+        for portion in [Decimal::ZERO, Decimal::new(-5, 1), Decimal::new(15, 1)] {
+            assert!(matches!(
+                long.close_amount(Some(portion)),
+                Err(LighterError::ValidationError(_))
+            ));
+        }
+        assert!(Position::new(0, 0).close_amount(None).is_err());
+    }
+}
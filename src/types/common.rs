@@ -1,7 +1,11 @@
 //! Common types and structures used across transactions
 
-use crate::errors::Result;
+use super::{Position, Signature};
+use crate::errors::{LighterError, Result};
+use crate::utils::validate_timestamp_ms;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Transaction options for customizing transaction parameters
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -13,6 +17,122 @@ pub struct TransactOpts {
     pub nonce: Option<i64>,
     #[serde(default)]
     pub dry_run: bool,
+    /// Current positions used to check reduce-only orders before signing.
+    /// Orders in markets without an entry here are not checked.
+    #[serde(default)]
+    pub positions: Vec<Position>,
+    /// Shrink oversized reduce-only orders to the position size instead of
+    /// rejecting them
+    #[serde(default)]
+    pub auto_clamp_reduce_only: bool,
+    /// Multiply an `expired_at` that looks like seconds by 1000 instead of
+    /// rejecting it
+    #[serde(default)]
+    pub auto_convert_seconds: bool,
+}
+
+impl TransactOpts {
+    /// Look up the supplied position for a market, if any
+    pub fn position_for(&self, market_index: u8) -> Option<&Position> {
+        self.positions
+            .iter()
+            .find(|p| p.market_index == market_index)
+    }
+
+    /// `from_account_index` of filled-in options
+    pub fn require_account_index(&self) -> Result<i64> {
+        self.from_account_index
+            .ok_or_else(|| LighterError::MissingField("from_account_index".to_string()))
+    }
+
+    /// `api_key_index` of filled-in options
+    pub fn require_api_key_index(&self) -> Result<u8> {
+        self.api_key_index
+            .ok_or_else(|| LighterError::MissingField("api_key_index".to_string()))
+    }
+
+    /// `nonce` of filled-in options
+    pub fn require_nonce(&self) -> Result<i64> {
+        self.nonce
+            .ok_or_else(|| LighterError::MissingField("nonce".to_string()))
+    }
+}
+
+/// Timestamp in milliseconds since the epoch, for `expired_at` and `order_expiry`
+///
+/// Constructors reject values that look like seconds or exceed
+/// `MAX_TIMESTAMP`.
+///
+/// ```
+/// use lighter_rs::types::ExpiryMs;
+/// use std::time::Duration;
+///
+/// let expiry = ExpiryMs::after(Duration::from_secs(3600)).unwrap();
+/// assert!(ExpiryMs::new(expiry.as_millis() / 1000).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i64", into = "i64")]
+pub struct ExpiryMs(i64);
+
+impl ExpiryMs {
+    /// Wrap a millisecond timestamp
+    pub fn new(millis: i64) -> Result<Self> {
+        validate_timestamp_ms(millis, "Expiry")?;
+        Ok(Self(millis))
+    }
+
+    /// Wrap a timestamp in seconds
+    pub fn from_secs(secs: i64) -> Result<Self> {
+        Self::new(secs.checked_mul(1000).ok_or_else(|| {
+            LighterError::ValidationError(format!("Expiry {} seconds overflows", secs))
+        })?)
+    }
+
+    /// Expiry at a point in time
+    pub fn at(time: DateTime<Utc>) -> Result<Self> {
+        Self::new(time.timestamp_millis())
+    }
+
+    /// Expiry `duration` from now
+    pub fn after(duration: Duration) -> Result<Self> {
+        let duration = chrono::Duration::from_std(duration)
+            .map_err(|e| LighterError::ValidationError(format!("Expiry duration: {}", e)))?;
+        Self::at(Utc::now() + duration)
+    }
+
+    /// Milliseconds since the epoch
+    pub fn as_millis(self) -> i64 {
+        self.0
+    }
+
+    /// Whether a raw value looks like a timestamp in seconds
+    ///
+    /// Seconds stay below 10^10 until the year 2286.
+    pub fn looks_like_seconds(value: i64) -> bool {
+        (1..10_000_000_000).contains(&value)
+    }
+}
+
+impl TryFrom<i64> for ExpiryMs {
+    type Error = LighterError;
+
+    fn try_from(millis: i64) -> Result<Self> {
+        Self::new(millis)
+    }
+}
+
+impl TryFrom<DateTime<Utc>> for ExpiryMs {
+    type Error = LighterError;
+
+    fn try_from(time: DateTime<Utc>) -> Result<Self> {
+        Self::at(time)
+    }
+}
+
+impl From<ExpiryMs> for i64 {
+    fn from(expiry: ExpiryMs) -> i64 {
+        expiry.0
+    }
 }
 
 /// Trait that all transaction types must implement
@@ -26,15 +146,84 @@ pub trait TxInfo {
     /// Get the transaction hash (if signed)
     fn get_tx_hash(&self) -> Option<String>;
 
+    /// Get the nonce the transaction was built with
+    fn get_nonce(&self) -> Option<i64> {
+        None
+    }
+
+    /// Get the account and API key the nonce belongs to
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        None
+    }
+
+    /// Get the `expired_at` the transaction was signed with
+    fn get_expired_at(&self) -> Option<i64> {
+        None
+    }
+
     /// Validate the transaction
     fn validate(&self) -> Result<()>;
 
+    /// Validate the transaction, reporting every failing rule at once
+    ///
+    /// The default reports only the error of [`validate`](Self::validate);
+    /// types that override it list that error first.
+    fn validate_all(&self) -> std::result::Result<(), Vec<LighterError>> {
+        self.validate().map_err(|e| vec![e])
+    }
+
     /// Hash the transaction for signing
     fn hash(&self, lighter_chain_id: u32) -> Result<Vec<u8>>;
 }
 
+/// Transaction that can be signed again with a new expiry or nonce
+///
+/// Used by [`TxClient::send_transaction_resigning`](crate::client::TxClient::send_transaction_resigning).
+pub trait Resignable: TxInfo {
+    /// Replace `expired_at`; the old signature no longer matches
+    fn set_expired_at(&mut self, expired_at: i64);
+
+    /// Replace the nonce; the old signature no longer matches
+    fn set_nonce(&mut self, nonce: i64);
+
+    /// Attach a signature and the hash it covers
+    fn set_signature(&mut self, sig: Signature, signed_hash: String);
+
+    /// Record the hash of an unsigned transaction, removing any signature
+    fn set_signed_hash(&mut self, signed_hash: String);
+}
+
+/// Implement [`Resignable`] for transactions with `expired_at`, `sig` and
+/// `signed_hash` fields
+macro_rules! impl_resignable {
+    ($($tx:ty),* $(,)?) => {
+        $(
+            impl $crate::types::Resignable for $tx {
+                fn set_expired_at(&mut self, expired_at: i64) {
+                    self.expired_at = expired_at;
+                }
+
+                fn set_nonce(&mut self, nonce: i64) {
+                    self.nonce = nonce;
+                }
+
+                fn set_signature(&mut self, sig: $crate::types::Signature, signed_hash: String) {
+                    self.sig = Some(sig);
+                    self.signed_hash = Some(signed_hash);
+                }
+
+                fn set_signed_hash(&mut self, signed_hash: String) {
+                    self.sig = None;
+                    self.signed_hash = Some(signed_hash);
+                }
+            }
+        )*
+    };
+}
+pub(crate) use impl_resignable;
+
 /// Order information structure used in order-related transactions
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct OrderInfo {
     pub market_index: u8,
     pub client_order_index: i64,
@@ -0,0 +1,120 @@
+//! Order and trade history returned by the REST API
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::Side;
+use crate::serde_util::{epoch_timestamp, string_or_number_decimal};
+
+/// One page of a paginated endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// How history streams walk through pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaginationOptions {
+    /// Items requested per page
+    pub limit: u32,
+    /// Minimum time between page requests, to stay under the API rate limit
+    pub min_interval: Duration,
+    /// Stop after this many pages
+    pub max_pages: Option<usize>,
+}
+
+impl Default for PaginationOptions {
+    fn default() -> Self {
+        Self {
+            limit: 100,
+            min_interval: Duration::from_millis(250),
+            max_pages: None,
+        }
+    }
+}
+
+/// Order that is no longer resting: filled, cancelled or expired
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoricalOrder {
+    pub order_index: i64,
+    pub client_order_index: i64,
+    pub market_index: u8,
+    pub is_ask: bool,
+    #[serde(rename = "type")]
+    pub order_type: String,
+    pub status: String,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub price: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub initial_base_amount: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub filled_base_amount: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub filled_quote_amount: Decimal,
+    #[serde(default)]
+    pub reduce_only: bool,
+    #[serde(deserialize_with = "epoch_timestamp")]
+    pub timestamp: DateTime<Utc>,
+}
+
+impl HistoricalOrder {
+    /// Side of the order
+    pub fn side(&self) -> Side {
+        if self.is_ask {
+            Side::Sell
+        } else {
+            Side::Buy
+        }
+    }
+
+    /// Volume-weighted fill price; `None` when nothing was filled
+    pub fn average_fill_price(&self) -> Option<Decimal> {
+        (!self.filled_base_amount.is_zero())
+            .then(|| self.filled_quote_amount / self.filled_base_amount)
+    }
+}
+
+/// Executed trade
+///
+/// The API reports both counterparties; use [`side_for`](Self::side_for)
+/// and [`is_maker_for`](Self::is_maker_for) to view it from one account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    pub trade_id: i64,
+    pub tx_hash: String,
+    pub market_id: u8,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub size: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub price: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub usd_amount: Decimal,
+    pub ask_account_id: i64,
+    pub bid_account_id: i64,
+    pub is_maker_ask: bool,
+    #[serde(deserialize_with = "epoch_timestamp")]
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Fill {
+    /// Side `account_index` traded on; `None` if it isn't a counterparty
+    pub fn side_for(&self, account_index: i64) -> Option<Side> {
+        if account_index == self.bid_account_id {
+            Some(Side::Buy)
+        } else if account_index == self.ask_account_id {
+            Some(Side::Sell)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `account_index` provided liquidity; `None` if it isn't a counterparty
+    pub fn is_maker_for(&self, account_index: i64) -> Option<bool> {
+        self.side_for(account_index)
+            .map(|side| (side == Side::Sell) == self.is_maker_ask)
+    }
+}
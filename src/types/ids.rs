@@ -0,0 +1,187 @@
+//! Newtypes for indices and amounts that are easy to transpose
+//!
+//! Each type validates its range on construction and serializes as the
+//! bare integer, so JSON is unchanged.
+//!
+//! ```
+//! use lighter_rs::types::{AccountIndex, ApiKeyIndex, ChainId};
+//!
+//! let account = AccountIndex::new(12345)?;
+//! let key = ApiKeyIndex::try_from(0u8)?;
+//! assert_eq!(i64::from(account), 12345);
+//! assert!(ChainId::new(0).is_err());
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::validation::*;
+use crate::errors::{LighterError, Result};
+
+macro_rules! id_newtype {
+    ($(#[$meta:meta])* $name:ident($raw:ty, $raw_name:literal), $validate:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = $raw_name, into = $raw_name)]
+        pub struct $name($raw);
+
+        impl $name {
+            /// Validate and wrap a raw value
+            pub fn new(value: $raw) -> Result<Self> {
+                let validate: fn($raw) -> Result<()> = $validate;
+                validate(value)?;
+                Ok(Self(value))
+            }
+
+            /// The raw value
+            pub fn get(self) -> $raw {
+                self.0
+            }
+        }
+
+        impl TryFrom<$raw> for $name {
+            type Error = LighterError;
+
+            fn try_from(value: $raw) -> Result<Self> {
+                Self::new(value)
+            }
+        }
+
+        impl From<$name> for $raw {
+            fn from(value: $name) -> $raw {
+                value.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+id_newtype!(
+    /// Account index, from `MIN_ACCOUNT_INDEX` to `MAX_ACCOUNT_INDEX`
+    AccountIndex(i64, "i64"),
+    validate_account_index
+);
+
+id_newtype!(
+    /// API key index, up to `MAX_API_KEY_INDEX`
+    ApiKeyIndex(u8, "u8"),
+    validate_api_key_index
+);
+
+id_newtype!(
+    /// Market index, up to `MAX_MARKET_INDEX`
+    MarketIndex(u8, "u8"),
+    validate_market_index
+);
+
+id_newtype!(
+    /// Chain id used when signing, e.g. 304 for mainnet; never zero
+    ChainId(u32, "u32"),
+    |chain_id| {
+        if chain_id == 0 {
+            return Err(LighterError::InvalidConfiguration(
+                "chain id must not be zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+);
+
+id_newtype!(
+    /// Order price in wire units (ticks), at least `MIN_ORDER_PRICE`
+    Price(u32, "u32"),
+    validate_price
+);
+
+id_newtype!(
+    /// Order size in wire units, from `MIN_ORDER_BASE_AMOUNT` to
+    /// `MAX_ORDER_BASE_AMOUNT`
+    BaseAmount(i64, "i64"),
+    validate_base_amount
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+
+    #[test]
+    fn test_constructor_validation() {
+        assert!(AccountIndex::new(MIN_ACCOUNT_INDEX).is_ok());
+        assert!(matches!(
+            AccountIndex::new(-1),
+            Err(LighterError::AccountIndexTooLow(-1))
+        ));
+        assert!(matches!(
+            AccountIndex::new(MAX_ACCOUNT_INDEX + 1),
+            Err(LighterError::AccountIndexTooHigh(_))
+        ));
+        assert!(ApiKeyIndex::new(MAX_API_KEY_INDEX).is_ok());
+        assert!(ApiKeyIndex::new(NIL_API_KEY_INDEX).is_err());
+        assert!(MarketIndex::new(0).is_ok());
+        assert!(matches!(
+            MarketIndex::new(255),
+            Err(LighterError::MarketIndexTooHigh(255))
+        ));
+        assert!(ChainId::new(304).is_ok());
+        assert!(ChainId::new(0).is_err());
+        assert!(Price::new(MAX_ORDER_PRICE).is_ok());
+        assert!(matches!(Price::new(0), Err(LighterError::PriceTooLow(0))));
+        assert!(BaseAmount::new(MAX_ORDER_BASE_AMOUNT).is_ok());
+        assert!(matches!(
+            BaseAmount::new(0),
+            Err(LighterError::BaseAmountTooLow(0))
+        ));
+        assert!(matches!(
+            BaseAmount::new(MAX_ORDER_BASE_AMOUNT + 1),
+            Err(LighterError::BaseAmountTooHigh(_))
+        ));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let account = AccountIndex::try_from(12345i64).unwrap();
+        assert_eq!(account.get(), 12345);
+        assert_eq!(i64::from(account), 12345);
+        assert_eq!(account.to_string(), "12345");
+        assert_eq!(u32::from(ChainId::try_from(300u32).unwrap()), 300);
+    }
+
+    #[test]
+    fn test_serialized_as_bare_integers() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Ids {
+            account_index: AccountIndex,
+            api_key_index: ApiKeyIndex,
+            market_index: MarketIndex,
+            price: Price,
+            base_amount: BaseAmount,
+        }
+        let ids = Ids {
+            account_index: AccountIndex::new(12345).unwrap(),
+            api_key_index: ApiKeyIndex::new(2).unwrap(),
+            market_index: MarketIndex::new(1).unwrap(),
+            price: Price::new(300_000).unwrap(),
+            base_amount: BaseAmount::new(1_000).unwrap(),
+        };
+        let json = serde_json::json!({
+            "account_index": 12345,
+            "api_key_index": 2,
+            "market_index": 1,
+            "price": 300_000,
+            "base_amount": 1_000,
+        });
+        assert_eq!(serde_json::to_value(&ids).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Ids>(json).unwrap(), ids);
+
+        // Out-of-range values are rejected when deserializing too
+        assert!(serde_json::from_str::<MarketIndex>("255").is_err());
+        assert!(serde_json::from_str::<ChainId>("0").is_err());
+    }
+}
@@ -1,14 +1,25 @@
 //! Transaction types and request builders for the Lighter Protocol
 
+pub mod account;
 pub mod common;
+pub mod history;
+pub mod ids;
+pub mod interop;
 pub mod orders;
 pub mod pools;
+pub mod signature;
 pub mod transfers;
+pub mod usdc;
 pub mod validation;
 
 // Re-export commonly used types
+pub use account::*;
 pub use common::*;
+pub use history::*;
+pub use ids::*;
 pub use orders::*;
 pub use pools::*;
+pub use signature::*;
 pub use transfers::*;
+pub use usdc::*;
 pub use validation::*;
@@ -1,12 +1,19 @@
 //! Order-related transaction types
 
-use super::{OrderInfo, TxInfo};
+use super::common::impl_resignable;
+use super::validation::{
+    validate_account_index, validate_api_key_index, validate_base_amount, validate_market_index,
+    validate_price, Checks,
+};
+use super::{BaseAmount, ExpiryMs, MarketIndex, OrderInfo, Position, Price, Signature, TxInfo};
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
+use crate::utils::{checked_base_amount, checked_price};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 /// Create Order Transaction Request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CreateOrderTxReq {
     pub market_index: u8,
     pub client_order_index: i64,
@@ -20,6 +27,309 @@ pub struct CreateOrderTxReq {
     pub order_expiry: i64,
 }
 
+impl Default for CreateOrderTxReq {
+    /// A good-till-time limit buy with every other field at its nil value
+    fn default() -> Self {
+        Self {
+            market_index: 0,
+            client_order_index: NIL_CLIENT_ORDER_INDEX,
+            base_amount: NIL_ORDER_BASE_AMOUNT,
+            price: NIL_ORDER_PRICE,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: NIL_ORDER_TRIGGER_PRICE,
+            order_expiry: NIL_ORDER_EXPIRY,
+        }
+    }
+}
+
+impl Default for OrderInfo {
+    /// Same values as [`CreateOrderTxReq::default`]
+    fn default() -> Self {
+        Self::from(&CreateOrderTxReq::default())
+    }
+}
+
+// Both conversions destructure exhaustively, so a field added to one
+// struct and not the other fails to compile here
+impl From<&CreateOrderTxReq> for OrderInfo {
+    fn from(req: &CreateOrderTxReq) -> Self {
+        let CreateOrderTxReq {
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            order_type,
+            time_in_force,
+            reduce_only,
+            trigger_price,
+            order_expiry,
+        } = *req;
+        Self {
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            order_type,
+            time_in_force,
+            reduce_only,
+            trigger_price,
+            order_expiry,
+        }
+    }
+}
+
+impl From<&OrderInfo> for CreateOrderTxReq {
+    fn from(order: &OrderInfo) -> Self {
+        let OrderInfo {
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            order_type,
+            time_in_force,
+            reduce_only,
+            trigger_price,
+            order_expiry,
+        } = *order;
+        Self {
+            market_index,
+            client_order_index,
+            base_amount,
+            price,
+            is_ask,
+            order_type,
+            time_in_force,
+            reduce_only,
+            trigger_price,
+            order_expiry,
+        }
+    }
+}
+
+/// Order side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// Wire value of the `is_ask` field
+    pub fn is_ask(self) -> u8 {
+        match self {
+            Side::Buy => 0,
+            Side::Sell => 1,
+        }
+    }
+
+    /// The opposite side
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        }
+    }
+}
+
+/// Kind of order, which determines its order type and time in force
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderKind {
+    Limit,
+    Market,
+    TakeProfit,
+    TakeProfitLimit,
+    StopLoss,
+    StopLossLimit,
+}
+
+impl OrderKind {
+    /// Wire value of the `order_type` field
+    pub fn order_type(self) -> u8 {
+        match self {
+            OrderKind::Limit => ORDER_TYPE_LIMIT,
+            OrderKind::Market => ORDER_TYPE_MARKET,
+            OrderKind::TakeProfit => ORDER_TYPE_TAKE_PROFIT,
+            OrderKind::TakeProfitLimit => ORDER_TYPE_TAKE_PROFIT_LIMIT,
+            OrderKind::StopLoss => ORDER_TYPE_STOP_LOSS,
+            OrderKind::StopLossLimit => ORDER_TYPE_STOP_LOSS_LIMIT,
+        }
+    }
+
+    /// Time in force used for this kind of order
+    pub fn time_in_force(self) -> u8 {
+        match self {
+            OrderKind::Limit | OrderKind::TakeProfitLimit | OrderKind::StopLossLimit => {
+                TIME_IN_FORCE_GOOD_TILL_TIME
+            }
+            OrderKind::Market | OrderKind::TakeProfit | OrderKind::StopLoss => {
+                TIME_IN_FORCE_IMMEDIATE_OR_CANCEL
+            }
+        }
+    }
+
+    /// Whether the order needs a trigger price
+    pub fn is_trigger(self) -> bool {
+        !matches!(self, OrderKind::Limit | OrderKind::Market)
+    }
+}
+
+/// Expiry of a resting order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderExpiry {
+    /// No explicit expiry (`NIL_ORDER_EXPIRY`)
+    #[default]
+    Nil,
+    /// Expire at a timestamp in milliseconds
+    At(i64),
+}
+
+impl From<ExpiryMs> for OrderExpiry {
+    fn from(expiry: ExpiryMs) -> Self {
+        OrderExpiry::At(expiry.as_millis())
+    }
+}
+
+impl OrderExpiry {
+    /// Wire value of the `order_expiry` field
+    pub fn as_millis(self) -> i64 {
+        match self {
+            OrderExpiry::Nil => NIL_ORDER_EXPIRY,
+            OrderExpiry::At(ts) => ts,
+        }
+    }
+}
+
+/// Parameters for creating an order of any [`OrderKind`]
+///
+/// ```
+/// use lighter_rs::types::{BaseAmount, MarketIndex, OrderKind, OrderParams, Price, Side};
+///
+/// let params = OrderParams::new(
+///     MarketIndex::new(0)?,
+///     Side::Sell,
+///     BaseAmount::new(1_000)?,
+///     Price::new(300_000)?,
+/// )
+/// .trigger(Price::new(290_000)?)
+/// .reduce_only(true);
+/// let req = params.to_request(OrderKind::StopLossLimit);
+/// assert_eq!(req.is_ask, 1);
+/// assert_eq!(req.reduce_only, 1);
+/// # Ok::<(), lighter_rs::LighterError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderParams {
+    pub market_index: MarketIndex,
+    pub side: Side,
+    pub base_amount: BaseAmount,
+    pub price: Price,
+    pub trigger_price: Option<Price>,
+    pub reduce_only: bool,
+    pub client_order_index: Option<i64>,
+    pub expiry: OrderExpiry,
+}
+
+impl OrderParams {
+    /// Create parameters with no trigger, not reduce-only and no expiry
+    pub fn new(
+        market_index: MarketIndex,
+        side: Side,
+        base_amount: BaseAmount,
+        price: Price,
+    ) -> Self {
+        Self {
+            market_index,
+            side,
+            base_amount,
+            price,
+            trigger_price: None,
+            reduce_only: false,
+            client_order_index: None,
+            expiry: OrderExpiry::Nil,
+        }
+    }
+
+    /// [`new`](Self::new) with raw integers, validating their ranges
+    #[deprecated(note = "use `new` with `MarketIndex`, `BaseAmount` and `Price`")]
+    pub fn new_raw(market_index: u8, side: Side, base_amount: i64, price: u32) -> Result<Self> {
+        Ok(Self::new(
+            MarketIndex::new(market_index)?,
+            side,
+            BaseAmount::new(base_amount)?,
+            Price::new(price)?,
+        ))
+    }
+
+    /// Set the trigger price
+    pub fn trigger(mut self, trigger_price: Price) -> Self {
+        self.trigger_price = Some(trigger_price);
+        self
+    }
+
+    /// Set the reduce-only flag
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    /// Set the client order index
+    pub fn client_order_index(mut self, client_order_index: i64) -> Self {
+        self.client_order_index = Some(client_order_index);
+        self
+    }
+
+    /// Set the order expiry
+    pub fn expiry(mut self, expiry: OrderExpiry) -> Self {
+        self.expiry = expiry;
+        self
+    }
+
+    /// Create parameters from decimal size and price
+    ///
+    /// Values are scaled by the market's size and price decimals with
+    /// [`checked_base_amount`] and [`checked_price`], so out-of-range inputs
+    /// are rejected instead of wrapping.
+    pub fn from_decimals(
+        market_index: MarketIndex,
+        side: Side,
+        size: Decimal,
+        size_decimals: u32,
+        price: Decimal,
+        price_decimals: u32,
+    ) -> Result<Self> {
+        Ok(Self::new(
+            market_index,
+            side,
+            BaseAmount::new(checked_base_amount(size, size_decimals)?)?,
+            Price::new(checked_price(price, price_decimals)?)?,
+        ))
+    }
+
+    /// Build the create order request for an order kind
+    pub fn to_request(&self, kind: OrderKind) -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            market_index: self.market_index.get(),
+            client_order_index: self.client_order_index.unwrap_or(NIL_CLIENT_ORDER_INDEX),
+            base_amount: self.base_amount.get(),
+            price: self.price.get(),
+            is_ask: self.side.is_ask(),
+            order_type: kind.order_type(),
+            time_in_force: kind.time_in_force(),
+            reduce_only: u8::from(self.reduce_only),
+            trigger_price: self
+                .trigger_price
+                .map_or(NIL_ORDER_TRIGGER_PRICE, Price::get),
+            order_expiry: self.expiry.as_millis(),
+        }
+    }
+}
+
 /// L2 Create Order Transaction Info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct L2CreateOrderTxInfo {
@@ -29,7 +339,7 @@ pub struct L2CreateOrderTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -47,29 +357,40 @@ impl TxInfo for L2CreateOrderTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
+        self.validate_all().map_err(first_error)
+    }
+
+    fn validate_all(&self) -> std::result::Result<(), Vec<LighterError>> {
+        let mut checks = Checks::default();
+
         // Validate account index
-        if self.account_index < MIN_ACCOUNT_INDEX {
-            return Err(LighterError::AccountIndexTooLow(self.account_index));
-        }
-        if self.account_index > MAX_ACCOUNT_INDEX {
-            return Err(LighterError::AccountIndexTooHigh(self.account_index));
-        }
+        checks.check(validate_account_index(self.account_index));
 
         // Validate API key index
-        if self.api_key_index > MAX_API_KEY_INDEX {
-            return Err(LighterError::ApiKeyIndexTooHigh(self.api_key_index));
-        }
+        checks.check(validate_api_key_index(self.api_key_index));
 
         // Validate order info
-        self.validate_order_info()?;
+        self.validate_order_info(&mut checks);
 
         // Validate nonce
         if self.nonce < MIN_NONCE {
-            return Err(LighterError::NonceTooLow(self.nonce));
+            checks.fail(LighterError::NonceTooLow(self.nonce));
         }
 
-        Ok(())
+        checks.finish()
     }
 
     fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
@@ -80,35 +401,66 @@ impl TxInfo for L2CreateOrderTxInfo {
 }
 
 impl L2CreateOrderTxInfo {
-    fn validate_order_info(&self) -> Result<()> {
+    fn validate_order_info(&self, checks: &mut Checks) {
         let order = &self.order_info;
 
         // Market index
-        if order.market_index > MAX_MARKET_INDEX {
-            return Err(LighterError::MarketIndexTooHigh(order.market_index));
-        }
+        checks.check(validate_market_index(order.market_index));
 
-        // Price
-        if order.price < MIN_ORDER_PRICE {
-            return Err(LighterError::PriceTooLow(order.price));
-        }
+        // Price; for market orders this is the protection price, so it must be
+        // set. The MAX_ORDER_PRICE bound is u32::MAX and is enforced where
+        // user input is converted, see `utils::checked_price`.
+        checks.check(validate_price(order.price));
 
         // IsAsk
         if order.is_ask != 0 && order.is_ask != 1 {
-            return Err(LighterError::IsAskInvalid);
+            checks.fail(LighterError::IsAskInvalid);
         }
-
-        Ok(())
     }
 }
 
 /// Cancel Order Transaction Request
+///
+/// `index` is either an exchange-assigned order index or a client order
+/// index. The two ranges don't overlap, so the server tells them apart by
+/// value; prefer the constructors, which check the value is in the range
+/// for the kind of index you mean.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CancelOrderTxReq {
     pub market_index: u8,
     pub index: i64,
 }
 
+impl CancelOrderTxReq {
+    /// Cancel by exchange-assigned order index
+    pub fn by_order_index(market_index: u8, order_index: i64) -> Result<Self> {
+        validate_order_index(order_index)?;
+        Ok(Self {
+            market_index,
+            index: order_index,
+        })
+    }
+
+    /// Cancel by the client order index the order was created with
+    pub fn by_client_order_index(market_index: u8, client_order_index: i64) -> Result<Self> {
+        if client_order_index < MIN_CLIENT_ORDER_INDEX {
+            return Err(LighterError::ClientOrderIndexTooLow(client_order_index));
+        }
+        if client_order_index > MAX_CLIENT_ORDER_INDEX {
+            return Err(LighterError::ClientOrderIndexTooHigh(client_order_index));
+        }
+        Ok(Self {
+            market_index,
+            index: client_order_index,
+        })
+    }
+
+    /// Whether `index` is a client order index rather than an order index
+    pub fn is_client_order_index(&self) -> bool {
+        self.index <= MAX_CLIENT_ORDER_INDEX
+    }
+}
+
 /// Modify Order Transaction Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModifyOrderTxReq {
@@ -133,6 +485,140 @@ pub struct CreateGroupedOrdersTxReq {
     pub orders: Vec<CreateOrderTxReq>,
 }
 
+/// Take-profit and stop-loss pair protecting an open position
+///
+/// Triggers are checked against `mark_price`: a long takes profit above the
+/// mark and stops out below it, a short the other way round. Limit prices
+/// default to `limit_offset_bps` past each trigger, on the side that lets the
+/// closing order fill.
+///
+/// ```
+/// use lighter_rs::types::{Position, TpSl};
+///
+/// let req = TpSl::new(300_000, 330_000, 285_000)
+///     .to_request(&Position::new(0, 1_000))
+///     .unwrap();
+/// assert_eq!(req.orders.len(), 2);
+/// assert!(req.orders.iter().all(|order| order.reduce_only == 1 && order.is_ask == 1));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TpSl {
+    pub mark_price: u32,
+    pub take_profit: u32,
+    pub stop_loss: u32,
+    pub take_profit_limit: Option<u32>,
+    pub stop_loss_limit: Option<u32>,
+    pub limit_offset_bps: u32,
+}
+
+impl TpSl {
+    /// Pair with default limit prices
+    pub fn new(mark_price: u32, take_profit: u32, stop_loss: u32) -> Self {
+        Self {
+            mark_price,
+            take_profit,
+            stop_loss,
+            take_profit_limit: None,
+            stop_loss_limit: None,
+            limit_offset_bps: DEFAULT_TP_SL_LIMIT_OFFSET_BPS,
+        }
+    }
+
+    /// Set the take-profit limit price
+    pub fn take_profit_limit(mut self, price: u32) -> Self {
+        self.take_profit_limit = Some(price);
+        self
+    }
+
+    /// Set the stop-loss limit price
+    pub fn stop_loss_limit(mut self, price: u32) -> Self {
+        self.stop_loss_limit = Some(price);
+        self
+    }
+
+    /// Set the offset of default limit prices from their triggers
+    pub fn limit_offset_bps(mut self, bps: u32) -> Self {
+        self.limit_offset_bps = bps;
+        self
+    }
+
+    /// Build the one-cancels-the-other group closing all of `position`
+    ///
+    /// Both legs are reduce-only and sized to the position; a flat position
+    /// or triggers on the wrong side of the mark are rejected.
+    pub fn to_request(&self, position: &Position) -> Result<CreateGroupedOrdersTxReq> {
+        let market_index = MarketIndex::new(position.market_index)?;
+        let base_amount = position.close_amount(None)?;
+        let side = position.closing_side();
+        let (tp_ok, sl_ok) = if position.is_long() {
+            (
+                self.take_profit > self.mark_price,
+                self.stop_loss < self.mark_price,
+            )
+        } else {
+            (
+                self.take_profit < self.mark_price,
+                self.stop_loss > self.mark_price,
+            )
+        };
+        if !tp_ok || !sl_ok {
+            let (above, below) = if position.is_long() {
+                ("take profit", "stop loss")
+            } else {
+                ("stop loss", "take profit")
+            };
+            return Err(LighterError::ValidationError(format!(
+                "Mark {} must be between the {} above and the {} below, got take profit {} and stop loss {}",
+                self.mark_price, above, below, self.take_profit, self.stop_loss
+            )));
+        }
+
+        let leg = |kind: OrderKind, trigger: u32, limit: Option<u32>| -> Result<CreateOrderTxReq> {
+            let price = match limit {
+                Some(price) => price,
+                None => self.default_limit(side, trigger)?,
+            };
+            Ok(
+                OrderParams::new(market_index, side, base_amount, Price::new(price)?)
+                    .trigger(Price::new(trigger)?)
+                    .reduce_only(true)
+                    .to_request(kind),
+            )
+        };
+        Ok(CreateGroupedOrdersTxReq {
+            grouping_type: GROUPING_TYPE_ONE_CANCELS_THE_OTHER,
+            orders: vec![
+                leg(
+                    OrderKind::TakeProfitLimit,
+                    self.take_profit,
+                    self.take_profit_limit,
+                )?,
+                leg(
+                    OrderKind::StopLossLimit,
+                    self.stop_loss,
+                    self.stop_loss_limit,
+                )?,
+            ],
+        })
+    }
+
+    /// Limit price `limit_offset_bps` past `trigger`: below it when selling,
+    /// above it when buying
+    fn default_limit(&self, side: Side, trigger: u32) -> Result<u32> {
+        let offset = u64::from(trigger) * u64::from(self.limit_offset_bps) / 10_000;
+        let price = match side {
+            Side::Sell => u64::from(trigger).saturating_sub(offset).max(1),
+            Side::Buy => u64::from(trigger) + offset,
+        };
+        u32::try_from(price).map_err(|_| {
+            LighterError::ValidationError(format!(
+                "Limit price {} past trigger {} is out of range",
+                price, trigger
+            ))
+        })
+    }
+}
+
 /// L2 Cancel Order Transaction Info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct L2CancelOrderTxInfo {
@@ -143,7 +629,7 @@ pub struct L2CancelOrderTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -161,17 +647,39 @@ impl TxInfo for L2CancelOrderTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
+        self.validate_all().map_err(first_error)
+    }
+
+    fn validate_all(&self) -> std::result::Result<(), Vec<LighterError>> {
+        let mut checks = Checks::default();
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
-            return Err(LighterError::AccountIndexTooLow(self.account_index));
+            checks.fail(LighterError::AccountIndexTooLow(self.account_index));
+        }
+        checks.check(validate_market_index(self.market_index));
+        // Either a client order index or an order index
+        if self.index < MIN_CLIENT_ORDER_INDEX {
+            checks.fail(LighterError::OrderIndexTooLow(self.index));
         }
-        if self.market_index > MAX_MARKET_INDEX {
-            return Err(LighterError::MarketIndexTooHigh(self.market_index));
+        if self.index > MAX_ORDER_INDEX {
+            checks.fail(LighterError::OrderIndexTooHigh(self.index));
         }
         if self.nonce < MIN_NONCE {
-            return Err(LighterError::NonceTooLow(self.nonce));
+            checks.fail(LighterError::NonceTooLow(self.nonce));
         }
-        Ok(())
+        checks.finish()
     }
 
     fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
@@ -180,6 +688,22 @@ impl TxInfo for L2CancelOrderTxInfo {
     }
 }
 
+/// First of the errors collected by a `validate_all`
+fn first_error(mut errors: Vec<LighterError>) -> LighterError {
+    errors.swap_remove(0)
+}
+
+/// Check that an exchange-assigned order index is in range
+fn validate_order_index(index: i64) -> Result<()> {
+    if index < MIN_ORDER_INDEX {
+        return Err(LighterError::OrderIndexTooLow(index));
+    }
+    if index > MAX_ORDER_INDEX {
+        return Err(LighterError::OrderIndexTooHigh(index));
+    }
+    Ok(())
+}
+
 /// L2 Modify Order Transaction Info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct L2ModifyOrderTxInfo {
@@ -193,7 +717,7 @@ pub struct L2ModifyOrderTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -211,14 +735,44 @@ impl TxInfo for L2ModifyOrderTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
+        self.validate_all().map_err(first_error)
+    }
+
+    fn validate_all(&self) -> std::result::Result<(), Vec<LighterError>> {
+        let mut checks = Checks::default();
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
-            return Err(LighterError::AccountIndexTooLow(self.account_index));
+            checks.fail(LighterError::AccountIndexTooLow(self.account_index));
         }
+        checks.check(validate_market_index(self.market_index));
+        checks.check(validate_order_index(self.index));
+
+        // Base amount
+        checks.check(validate_base_amount(self.base_amount));
+
+        // Price; MAX_ORDER_PRICE is u32::MAX so only the lower bound can fail.
+        // A modify always carries the new price, there is no "keep price" nil.
+        checks.check(validate_price(self.price));
+
+        // Trigger price is either NIL_ORDER_TRIGGER_PRICE or within
+        // MIN/MAX_ORDER_TRIGGER_PRICE, which together cover every u32.
+
         if self.nonce < MIN_NONCE {
-            return Err(LighterError::NonceTooLow(self.nonce));
+            checks.fail(LighterError::NonceTooLow(self.nonce));
         }
-        Ok(())
+        checks.finish()
     }
 
     fn hash(&self, _lighter_chain_id: u32) -> Result<Vec<u8>> {
@@ -237,7 +791,7 @@ pub struct L2CancelAllOrdersTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -255,6 +809,18 @@ impl TxInfo for L2CancelAllOrdersTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -281,7 +847,7 @@ pub struct L2CreateGroupedOrdersTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -299,6 +865,18 @@ impl TxInfo for L2CreateGroupedOrdersTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -318,10 +896,53 @@ impl TxInfo for L2CreateGroupedOrdersTxInfo {
     }
 }
 
+impl_resignable!(
+    L2CreateOrderTxInfo,
+    L2CancelOrderTxInfo,
+    L2ModifyOrderTxInfo,
+    L2CancelAllOrdersTxInfo,
+    L2CreateGroupedOrdersTxInfo,
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_order_info_conversions_copy_every_field() {
+        let req = CreateOrderTxReq {
+            market_index: 7,
+            client_order_index: 1234,
+            base_amount: 5_000,
+            price: 301_250,
+            is_ask: 1,
+            order_type: ORDER_TYPE_STOP_LOSS_LIMIT,
+            time_in_force: TIME_IN_FORCE_POST_ONLY,
+            reduce_only: 1,
+            trigger_price: 299_000,
+            order_expiry: 1_700_000_000_000,
+        };
+        let order = OrderInfo::from(&req);
+        assert_eq!(order.market_index, req.market_index);
+        assert_eq!(order.client_order_index, req.client_order_index);
+        assert_eq!(order.base_amount, req.base_amount);
+        assert_eq!(order.price, req.price);
+        assert_eq!(order.is_ask, req.is_ask);
+        assert_eq!(order.order_type, req.order_type);
+        assert_eq!(order.time_in_force, req.time_in_force);
+        assert_eq!(order.reduce_only, req.reduce_only);
+        assert_eq!(order.trigger_price, req.trigger_price);
+        assert_eq!(order.order_expiry, req.order_expiry);
+        assert_eq!(CreateOrderTxReq::from(&order), req);
+
+        let nil = OrderInfo::default();
+        assert_eq!(nil.client_order_index, NIL_CLIENT_ORDER_INDEX);
+        assert_eq!(nil.price, NIL_ORDER_PRICE);
+        assert_eq!(nil.trigger_price, NIL_ORDER_TRIGGER_PRICE);
+        assert_eq!(nil.order_expiry, NIL_ORDER_EXPIRY);
+        assert_eq!(CreateOrderTxReq::from(&nil), CreateOrderTxReq::default());
+    }
+
     fn create_valid_order_info() -> OrderInfo {
         OrderInfo {
             market_index: 0,
@@ -372,6 +993,36 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_create_order_validate_all_reports_every_error() {
+        let tx_info = L2CreateOrderTxInfo {
+            account_index: -1,
+            api_key_index: 255,
+            order_info: OrderInfo {
+                price: 0,
+                is_ask: 2,
+                ..create_valid_order_info()
+            },
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        let errors = tx_info.validate_all().unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(errors[0], LighterError::AccountIndexTooLow(-1)));
+        assert!(matches!(errors[1], LighterError::ApiKeyIndexTooHigh(255)));
+        assert!(matches!(errors[2], LighterError::PriceTooLow(0)));
+        assert!(matches!(errors[3], LighterError::IsAskInvalid));
+
+        // The single error is still the first one
+        assert!(matches!(
+            tx_info.validate().unwrap_err(),
+            LighterError::AccountIndexTooLow(-1)
+        ));
+    }
+
     #[test]
     fn test_create_order_account_index_too_high() {
         let tx_info = L2CreateOrderTxInfo {
@@ -490,7 +1141,7 @@ mod tests {
             account_index: 12345,
             api_key_index: 0,
             market_index: 0,
-            index: 123456,
+            index: MIN_ORDER_INDEX,
             expired_at: 1000000,
             nonce: 1,
             sig: None,
@@ -507,7 +1158,7 @@ mod tests {
             account_index: 12345,
             api_key_index: 0,
             market_index: 255,
-            index: 123456,
+            index: MIN_ORDER_INDEX,
             expired_at: 1000000,
             nonce: 1,
             sig: None,
@@ -528,7 +1179,7 @@ mod tests {
             account_index: 12345,
             api_key_index: 0,
             market_index: 0,
-            index: 123456,
+            index: MIN_ORDER_INDEX,
             base_amount: 2000000,
             price: 105000000,
             trigger_price: 0,
@@ -542,6 +1193,203 @@ mod tests {
         assert_eq!(tx_info.get_tx_type(), TX_TYPE_L2_MODIFY_ORDER);
     }
 
+    fn valid_modify_order() -> L2ModifyOrderTxInfo {
+        L2ModifyOrderTxInfo {
+            account_index: 12345,
+            api_key_index: 0,
+            market_index: 0,
+            index: MIN_ORDER_INDEX,
+            base_amount: 2000000,
+            price: 105000000,
+            trigger_price: 0,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        }
+    }
+
+    fn valid_cancel_order() -> L2CancelOrderTxInfo {
+        L2CancelOrderTxInfo {
+            account_index: 12345,
+            api_key_index: 0,
+            market_index: 0,
+            index: MIN_ORDER_INDEX,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_modify_order_bounds() {
+        type Case = (
+            &'static str,
+            fn(&mut L2ModifyOrderTxInfo),
+            Option<&'static str>,
+        );
+        let cases: Vec<Case> = vec![
+            ("max market", |tx| tx.market_index = MAX_MARKET_INDEX, None),
+            (
+                "market too high",
+                |tx| tx.market_index = 255,
+                Some("MarketIndexTooHigh"),
+            ),
+            ("min index", |tx| tx.index = MIN_ORDER_INDEX, None),
+            ("max index", |tx| tx.index = MAX_ORDER_INDEX, None),
+            (
+                "index too low",
+                |tx| tx.index = MIN_ORDER_INDEX - 1,
+                Some("OrderIndexTooLow"),
+            ),
+            ("client index", |tx| tx.index = 1, Some("OrderIndexTooLow")),
+            (
+                "index too high",
+                |tx| tx.index = MAX_ORDER_INDEX + 1,
+                Some("OrderIndexTooHigh"),
+            ),
+            (
+                "min base",
+                |tx| tx.base_amount = MIN_ORDER_BASE_AMOUNT,
+                None,
+            ),
+            (
+                "max base",
+                |tx| tx.base_amount = MAX_ORDER_BASE_AMOUNT,
+                None,
+            ),
+            (
+                "zero base",
+                |tx| tx.base_amount = 0,
+                Some("BaseAmountTooLow"),
+            ),
+            (
+                "negative base",
+                |tx| tx.base_amount = -1,
+                Some("BaseAmountTooLow"),
+            ),
+            (
+                "base too high",
+                |tx| tx.base_amount = MAX_ORDER_BASE_AMOUNT + 1,
+                Some("BaseAmountTooHigh"),
+            ),
+            ("min price", |tx| tx.price = MIN_ORDER_PRICE, None),
+            ("max price", |tx| tx.price = MAX_ORDER_PRICE, None),
+            ("zero price", |tx| tx.price = 0, Some("PriceTooLow")),
+            (
+                "nil trigger",
+                |tx| tx.trigger_price = NIL_ORDER_TRIGGER_PRICE,
+                None,
+            ),
+            (
+                "min trigger",
+                |tx| tx.trigger_price = MIN_ORDER_TRIGGER_PRICE,
+                None,
+            ),
+            (
+                "max trigger",
+                |tx| tx.trigger_price = MAX_ORDER_TRIGGER_PRICE,
+                None,
+            ),
+        ];
+
+        for (name, mutate, expected) in cases {
+            let mut tx = valid_modify_order();
+            mutate(&mut tx);
+            let result = tx.validate();
+            match expected {
+                None => assert!(result.is_ok(), "{}: {:?}", name, result),
+                Some(variant) => {
+                    let err = format!("{:?}", result.expect_err(name));
+                    assert!(err.starts_with(variant), "{}: got {}", name, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_order_bounds() {
+        type Case = (
+            &'static str,
+            fn(&mut L2CancelOrderTxInfo),
+            Option<&'static str>,
+        );
+        let cases: Vec<Case> = vec![
+            ("max market", |tx| tx.market_index = MAX_MARKET_INDEX, None),
+            (
+                "market too high",
+                |tx| tx.market_index = 255,
+                Some("MarketIndexTooHigh"),
+            ),
+            ("min index", |tx| tx.index = MIN_ORDER_INDEX, None),
+            ("max index", |tx| tx.index = MAX_ORDER_INDEX, None),
+            (
+                "min client index",
+                |tx| tx.index = MIN_CLIENT_ORDER_INDEX,
+                None,
+            ),
+            (
+                "max client index",
+                |tx| tx.index = MAX_CLIENT_ORDER_INDEX,
+                None,
+            ),
+            ("zero index", |tx| tx.index = 0, Some("OrderIndexTooLow")),
+            (
+                "negative index",
+                |tx| tx.index = -1,
+                Some("OrderIndexTooLow"),
+            ),
+            (
+                "index too high",
+                |tx| tx.index = MAX_ORDER_INDEX + 1,
+                Some("OrderIndexTooHigh"),
+            ),
+        ];
+
+        for (name, mutate, expected) in cases {
+            let mut tx = valid_cancel_order();
+            mutate(&mut tx);
+            let result = tx.validate();
+            match expected {
+                None => assert!(result.is_ok(), "{}: {:?}", name, result),
+                Some(variant) => {
+                    let err = format!("{:?}", result.expect_err(name));
+                    assert!(err.starts_with(variant), "{}: got {}", name, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_order_constructors() {
+        let req = CancelOrderTxReq::by_order_index(1, MIN_ORDER_INDEX).unwrap();
+        assert_eq!(req.index, MIN_ORDER_INDEX);
+        assert!(!req.is_client_order_index());
+
+        let req = CancelOrderTxReq::by_client_order_index(1, 42).unwrap();
+        assert_eq!(req.index, 42);
+        assert!(req.is_client_order_index());
+
+        // An order index passed as a client order index and vice versa
+        assert!(matches!(
+            CancelOrderTxReq::by_client_order_index(1, MIN_ORDER_INDEX),
+            Err(LighterError::ClientOrderIndexTooHigh(_))
+        ));
+        assert!(matches!(
+            CancelOrderTxReq::by_order_index(1, 42),
+            Err(LighterError::OrderIndexTooLow(42))
+        ));
+        assert!(matches!(
+            CancelOrderTxReq::by_client_order_index(1, 0),
+            Err(LighterError::ClientOrderIndexTooLow(0))
+        ));
+        assert!(matches!(
+            CancelOrderTxReq::by_order_index(1, MAX_ORDER_INDEX + 1),
+            Err(LighterError::OrderIndexTooHigh(_))
+        ));
+    }
+
     #[test]
     fn test_cancel_all_orders_validation_success() {
         let tx_info = L2CancelAllOrdersTxInfo {
@@ -621,4 +1469,53 @@ mod tests {
         assert!(json.contains("account_index"));
         assert!(json.contains("12345"));
     }
+
+    #[test]
+    fn test_market_order_requires_protection_price() {
+        let mut order_info = create_valid_order_info();
+        order_info.order_type = ORDER_TYPE_MARKET;
+        order_info.price = 0;
+
+        let tx_info = L2CreateOrderTxInfo {
+            account_index: 12345,
+            api_key_index: 0,
+            order_info,
+            expired_at: 1000000,
+            nonce: 1,
+            sig: None,
+            signed_hash: None,
+        };
+
+        assert!(matches!(
+            tx_info.validate().unwrap_err(),
+            LighterError::PriceTooLow(0)
+        ));
+    }
+
+    #[test]
+    fn test_order_params_from_decimals() {
+        let market = MarketIndex::new(1).unwrap();
+        let params = OrderParams::from_decimals(
+            market,
+            Side::Buy,
+            Decimal::new(25, 2),
+            4,
+            Decimal::new(6500012, 2),
+            2,
+        )
+        .unwrap();
+        assert_eq!(params.base_amount.get(), 2_500);
+        assert_eq!(params.price.get(), 6_500_012);
+
+        // BTC price at 1e8 scaling would wrap a plain `as u32` cast
+        let result = OrderParams::from_decimals(
+            market,
+            Side::Buy,
+            Decimal::ONE,
+            4,
+            Decimal::from(65_000),
+            8,
+        );
+        assert!(matches!(result, Err(LighterError::ValidationError(_))));
+    }
 }
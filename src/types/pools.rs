@@ -1,8 +1,11 @@
 //! Pool-related transaction types
 
-use super::TxInfo;
+use super::common::impl_resignable;
+use super::{Signature, TxInfo};
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 /// Create Public Pool Transaction Request
@@ -36,6 +39,136 @@ pub struct BurnSharesTxReq {
     pub share_amount: i64,
 }
 
+/// Public pool state, see [`HTTPClient::get_public_pool`](crate::client::HTTPClient::get_public_pool)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicPoolInfo {
+    pub public_pool_index: i64,
+    /// 0 for active, 1 for frozen
+    pub status: u8,
+    /// Operator fee as reported by the API
+    pub operator_fee: Decimal,
+    pub total_shares: i64,
+    pub operator_shares: i64,
+    /// Total asset value of the pool in USDC
+    pub pool_equity: Decimal,
+}
+
+/// Shares an account holds in a public pool, see
+/// [`HTTPClient::get_pool_position`](crate::client::HTTPClient::get_pool_position)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolPosition {
+    pub public_pool_index: i64,
+    /// Zero if the account holds no shares of the pool
+    pub shares: i64,
+    /// USDC paid for the shares
+    pub entry_usdc: Decimal,
+    /// Shares valued at the pool's current share price, in USDC
+    pub current_value: Decimal,
+}
+
+impl PublicPoolInfo {
+    /// Value of one share in USDC
+    ///
+    /// A pool without shares uses `INITIAL_POOL_SHARE_VALUE`; a pool with
+    /// shares but no equity has no meaningful share price.
+    pub fn share_price(&self) -> Result<Decimal> {
+        if self.total_shares == 0 {
+            return Ok(Decimal::from(INITIAL_POOL_SHARE_VALUE) / Decimal::from(ONE_USDC));
+        }
+        if self.pool_equity <= Decimal::ZERO {
+            return Err(LighterError::ValidationError(format!(
+                "Public pool {} has no equity, share price is undefined",
+                self.public_pool_index
+            )));
+        }
+        Ok(self.pool_equity / Decimal::from(self.total_shares))
+    }
+
+    /// Whole shares worth at most `usdc`, rounded down
+    pub fn shares_for_usdc(&self, usdc: Decimal) -> Result<i64> {
+        if usdc <= Decimal::ZERO {
+            return Err(LighterError::ValidationError(format!(
+                "USDC value must be positive, got {}",
+                usdc
+            )));
+        }
+        // Multiply before dividing to keep exact ratios exact
+        let shares = if self.total_shares == 0 {
+            usdc * Decimal::from(ONE_USDC) / Decimal::from(INITIAL_POOL_SHARE_VALUE)
+        } else {
+            self.share_price()?;
+            usdc.checked_mul(Decimal::from(self.total_shares))
+                .ok_or_else(|| {
+                    LighterError::ValidationError(format!("USDC value {} overflows", usdc))
+                })?
+                / self.pool_equity
+        };
+        shares.floor().to_i64().ok_or_else(|| {
+            LighterError::ValidationError(format!("Share amount {} overflows", shares))
+        })
+    }
+}
+
+impl MintSharesTxReq {
+    /// Mint shares worth at most `usdc` at the pool's current share price
+    pub fn for_usdc_value(pool: &PublicPoolInfo, usdc: Decimal) -> Result<Self> {
+        let share_amount = pool.shares_for_usdc(usdc)?;
+        if share_amount < MIN_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::PoolMintShareAmountTooLow(share_amount));
+        }
+        if share_amount > MAX_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::PoolMintShareAmountTooHigh(share_amount));
+        }
+        Ok(Self {
+            public_pool_index: pool.public_pool_index,
+            share_amount,
+        })
+    }
+}
+
+impl BurnSharesTxReq {
+    /// Burn every share of a position except `leave_dust`
+    ///
+    /// Fails with a validation error if nothing would be left to burn.
+    pub fn for_position(position: &PoolPosition, leave_dust: i64) -> Result<Self> {
+        if leave_dust < 0 {
+            return Err(LighterError::ValidationError(format!(
+                "Shares to leave must not be negative, got {}",
+                leave_dust
+            )));
+        }
+        let share_amount = position.shares.saturating_sub(leave_dust);
+        if share_amount < MIN_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::ValidationError(format!(
+                "Nothing to burn in public pool {}: {} shares held, {} to be left",
+                position.public_pool_index, position.shares, leave_dust
+            )));
+        }
+        if share_amount > MAX_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::PoolBurnShareAmountTooHigh(share_amount));
+        }
+        Ok(Self {
+            public_pool_index: position.public_pool_index,
+            share_amount,
+        })
+    }
+
+    /// Burn shares worth at most `usdc` at the pool's current share price
+    pub fn for_usdc_value(pool: &PublicPoolInfo, usdc: Decimal) -> Result<Self> {
+        let share_amount = pool.shares_for_usdc(usdc)?;
+        if share_amount < MIN_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::PoolBurnShareAmountTooLow(share_amount));
+        }
+        if share_amount > MAX_POOL_SHARES_TO_MINT_OR_BURN {
+            return Err(LighterError::PoolBurnShareAmountTooHigh(share_amount));
+        }
+        Ok(Self {
+            public_pool_index: pool.public_pool_index,
+            share_amount,
+        })
+    }
+}
+
 /// L2 Create Public Pool Transaction Info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct L2CreatePublicPoolTxInfo {
@@ -47,7 +180,7 @@ pub struct L2CreatePublicPoolTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -65,6 +198,18 @@ impl TxInfo for L2CreatePublicPoolTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -109,7 +254,7 @@ pub struct L2UpdatePublicPoolTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -127,6 +272,18 @@ impl TxInfo for L2UpdatePublicPoolTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -160,7 +317,7 @@ pub struct L2MintSharesTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -178,6 +335,18 @@ impl TxInfo for L2MintSharesTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -210,7 +379,7 @@ pub struct L2BurnSharesTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -228,6 +397,18 @@ impl TxInfo for L2BurnSharesTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -250,10 +431,97 @@ impl TxInfo for L2BurnSharesTxInfo {
     }
 }
 
+impl_resignable!(
+    L2CreatePublicPoolTxInfo,
+    L2UpdatePublicPoolTxInfo,
+    L2MintSharesTxInfo,
+    L2BurnSharesTxInfo,
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn pool(total_shares: i64, pool_equity: &str) -> PublicPoolInfo {
+        PublicPoolInfo {
+            public_pool_index: 281474976710650,
+            status: 0,
+            operator_fee: Decimal::new(10, 0),
+            total_shares,
+            operator_shares: total_shares / 10,
+            pool_equity: pool_equity.parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_shares_for_usdc_value() {
+        // 2,000,000 shares backed by 3,000 USDC: 0.0015 USDC per share
+        let pool = pool(2_000_000, "3000");
+        assert_eq!(pool.share_price().unwrap(), Decimal::new(15, 4));
+
+        let mint = MintSharesTxReq::for_usdc_value(&pool, Decimal::from(1_000)).unwrap();
+        assert_eq!(mint.share_amount, 666_666);
+        assert_eq!(mint.public_pool_index, pool.public_pool_index);
+
+        // Exact multiples don't lose a share to rounding
+        let burn = BurnSharesTxReq::for_usdc_value(&pool, Decimal::new(3, 0)).unwrap();
+        assert_eq!(burn.share_amount, 2_000);
+
+        // Just below one share rounds down to zero
+        assert!(matches!(
+            MintSharesTxReq::for_usdc_value(&pool, Decimal::new(14, 4)),
+            Err(LighterError::PoolMintShareAmountTooLow(0))
+        ));
+        assert!(matches!(
+            BurnSharesTxReq::for_usdc_value(&pool, Decimal::new(14999, 7)),
+            Err(LighterError::PoolBurnShareAmountTooLow(0))
+        ));
+        assert!(MintSharesTxReq::for_usdc_value(&pool, Decimal::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_burn_for_position() {
+        let position = PoolPosition {
+            public_pool_index: 3,
+            shares: 100,
+            entry_usdc: Decimal::ONE,
+            current_value: Decimal::ONE,
+        };
+        assert_eq!(
+            BurnSharesTxReq::for_position(&position, 0)
+                .unwrap()
+                .share_amount,
+            100
+        );
+        assert_eq!(
+            BurnSharesTxReq::for_position(&position, 99)
+                .unwrap()
+                .share_amount,
+            1
+        );
+        for leave_dust in [100, 150, -1] {
+            assert!(matches!(
+                BurnSharesTxReq::for_position(&position, leave_dust),
+                Err(LighterError::ValidationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_shares_for_usdc_empty_and_zero_equity_pools() {
+        // No shares yet: INITIAL_POOL_SHARE_VALUE applies
+        let empty = pool(0, "0");
+        let mint = MintSharesTxReq::for_usdc_value(&empty, Decimal::from(1_000)).unwrap();
+        assert_eq!(mint.share_amount, 1_000_000);
+
+        let wiped = pool(1_000_000, "0");
+        assert!(wiped.share_price().is_err());
+        assert!(matches!(
+            MintSharesTxReq::for_usdc_value(&wiped, Decimal::from(10)),
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+
     #[test]
     fn test_create_public_pool_validation_success() {
         let tx_info = L2CreatePublicPoolTxInfo {
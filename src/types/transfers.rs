@@ -1,22 +1,161 @@
 //! Transfer and withdrawal transaction types
 
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 /// Transfer Transaction Request
+///
+/// Build it with [`usdc`](Self::usdc), which takes USDC rather than protocol
+/// units; the fields become private in the next breaking release.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferTxReq {
+    #[deprecated(note = "use TransferTxReq::to_account_index()")]
     pub to_account_index: i64,
+    #[deprecated(note = "build with TransferTxReq::usdc and read TransferTxReq::amount()")]
     pub usdc_amount: i64,
+    #[deprecated(note = "build with TransferTxReq::usdc and read TransferTxReq::fee()")]
     pub fee: i64,
+    #[deprecated(note = "use TransferTxReq::memo()")]
     pub memo: [u8; 32],
 }
 
+#[allow(deprecated)]
+impl TransferTxReq {
+    /// Transfer `amount` USDC to an account, paying `fee` USDC
+    ///
+    /// Fails on more than six decimals, a negative fee, or an amount outside
+    /// `MIN_TRANSFER_AMOUNT..=MAX_TRANSFER_AMOUNT` once scaled.
+    pub fn usdc(
+        to_account_index: i64,
+        amount: Decimal,
+        fee: Decimal,
+        memo: [u8; 32],
+    ) -> Result<Self> {
+        let usdc_amount = Usdc::from_decimal(amount)?.units();
+        if usdc_amount < MIN_TRANSFER_AMOUNT {
+            return Err(LighterError::TransferAmountTooLow(usdc_amount));
+        }
+        if usdc_amount > MAX_TRANSFER_AMOUNT {
+            return Err(LighterError::TransferAmountTooHigh(usdc_amount));
+        }
+        let fee = Usdc::from_decimal(fee)?.units();
+        if fee < 0 {
+            return Err(LighterError::TransferFeeNegative);
+        }
+        Ok(Self::from_raw_units(
+            to_account_index,
+            usdc_amount,
+            fee,
+            memo,
+        ))
+    }
+
+    /// Build from amounts already in protocol units (see [`ONE_USDC`])
+    ///
+    /// Nothing is checked until the transaction is built.
+    pub fn from_raw_units(
+        to_account_index: i64,
+        usdc_amount: i64,
+        fee: i64,
+        memo: [u8; 32],
+    ) -> Self {
+        Self {
+            to_account_index,
+            usdc_amount,
+            fee,
+            memo,
+        }
+    }
+
+    /// Receiving account
+    pub fn to_account_index(&self) -> i64 {
+        self.to_account_index
+    }
+
+    /// Amount transferred
+    pub fn amount(&self) -> Usdc {
+        Usdc::from_units(self.usdc_amount)
+    }
+
+    /// Fee paid
+    pub fn fee(&self) -> Usdc {
+        Usdc::from_units(self.fee)
+    }
+
+    /// Memo attached to the transfer
+    pub fn memo(&self) -> [u8; 32] {
+        self.memo
+    }
+}
+
+/// Transfer fee quoted by the API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeInfo {
+    /// Fee in USDC protocol units (see [`ONE_USDC`])
+    #[serde(rename = "transfer_fee_usdc")]
+    pub transfer_fee: i64,
+}
+
+impl FeeInfo {
+    /// Fee in USDC
+    pub fn fee_usdc(&self) -> Decimal {
+        Decimal::new(self.transfer_fee, USDC_DECIMALS)
+    }
+
+    /// Fee as a [`Usdc`] amount
+    pub fn fee(&self) -> Usdc {
+        Usdc::from_units(self.transfer_fee)
+    }
+}
+
 /// Withdraw Transaction Request
+///
+/// Build it with [`usdc`](Self::usdc); the field becomes private in the next
+/// breaking release.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WithdrawTxReq {
+    #[deprecated(note = "build with WithdrawTxReq::usdc and read WithdrawTxReq::amount()")]
     pub usdc_amount: u64,
 }
 
+#[allow(deprecated)]
+impl WithdrawTxReq {
+    /// Withdraw `amount` USDC
+    ///
+    /// Fails on more than six decimals or an amount outside
+    /// `MIN_WITHDRAWAL_AMOUNT..=MAX_WITHDRAWAL_AMOUNT` once scaled.
+    pub fn usdc(amount: Decimal) -> Result<Self> {
+        let units = Usdc::from_decimal(amount)?.units();
+        let usdc_amount = u64::try_from(units).map_err(|_| {
+            LighterError::ValidationError(format!("Withdrawal amount {} is negative", amount))
+        })?;
+        if usdc_amount < MIN_WITHDRAWAL_AMOUNT {
+            return Err(LighterError::WithdrawalAmountTooLow(usdc_amount));
+        }
+        if usdc_amount > MAX_WITHDRAWAL_AMOUNT {
+            return Err(LighterError::WithdrawalAmountTooHigh(usdc_amount));
+        }
+        Ok(Self::from_raw_units(usdc_amount))
+    }
+
+    /// Build from an amount already in protocol units (see [`ONE_USDC`])
+    ///
+    /// Nothing is checked until the transaction is built.
+    pub fn from_raw_units(usdc_amount: u64) -> Self {
+        Self { usdc_amount }
+    }
+
+    /// Amount withdrawn
+    pub fn amount(&self) -> Usdc {
+        Usdc::from_units(i64::try_from(self.usdc_amount).unwrap_or(i64::MAX))
+    }
+
+    /// Amount withdrawn in protocol units
+    pub fn units(&self) -> u64 {
+        self.usdc_amount
+    }
+}
+
 /// Change Public Key Transaction Request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChangePubKeyReq {
@@ -39,7 +178,19 @@ pub struct UpdateMarginTxReq {
     pub direction: u8,
 }
 
-use super::TxInfo;
+impl UpdateMarginTxReq {
+    /// Move `amount` into or out of a market's isolated margin
+    pub fn usdc(market_index: u8, amount: Usdc, direction: u8) -> Self {
+        Self {
+            market_index,
+            usdc_amount: amount.units(),
+            direction,
+        }
+    }
+}
+
+use super::common::impl_resignable;
+use super::{Signature, TxInfo, Usdc};
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
 
@@ -55,7 +206,7 @@ pub struct L2TransferTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -73,6 +224,18 @@ impl TxInfo for L2TransferTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.from_account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.from_account_index < MIN_ACCOUNT_INDEX
             || self.from_account_index > MAX_ACCOUNT_INDEX
@@ -111,7 +274,7 @@ pub struct L2WithdrawTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -129,6 +292,18 @@ impl TxInfo for L2WithdrawTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.from_account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.from_account_index < MIN_ACCOUNT_INDEX
             || self.from_account_index > MAX_ACCOUNT_INDEX
@@ -161,7 +336,11 @@ pub struct L2ChangePubKeyTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
+    /// EIP-191 signature of the account's L1 wallet over
+    /// [`OnboardingIntent::message`](crate::signer::l1::OnboardingIntent::message)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub l1_sig: Option<String>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -179,6 +358,18 @@ impl TxInfo for L2ChangePubKeyTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -208,7 +399,7 @@ pub struct L2UpdateLeverageTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -226,6 +417,18 @@ impl TxInfo for L2UpdateLeverageTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -233,7 +436,7 @@ impl TxInfo for L2UpdateLeverageTxInfo {
         if self.market_index > MAX_MARKET_INDEX {
             return Err(LighterError::MarketIndexTooHigh(self.market_index));
         }
-        if self.initial_margin_fraction as i64 > MARGIN_FRACTION_TICK {
+        if i64::from(self.initial_margin_fraction) > MARGIN_FRACTION_TICK {
             return Err(LighterError::InitialMarginFractionTooHigh(
                 self.initial_margin_fraction,
             ));
@@ -261,7 +464,7 @@ pub struct L2UpdateMarginTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -279,6 +482,18 @@ impl TxInfo for L2UpdateMarginTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -310,7 +525,7 @@ pub struct L2CreateSubAccountTxInfo {
     pub expired_at: i64,
     pub nonce: i64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub sig: Option<Vec<u8>>,
+    pub sig: Option<Signature>,
     #[serde(skip)]
     pub signed_hash: Option<String>,
 }
@@ -328,6 +543,18 @@ impl TxInfo for L2CreateSubAccountTxInfo {
         self.signed_hash.clone()
     }
 
+    fn get_nonce(&self) -> Option<i64> {
+        Some(self.nonce)
+    }
+
+    fn get_nonce_key(&self) -> Option<(i64, u8)> {
+        Some((self.account_index, self.api_key_index))
+    }
+
+    fn get_expired_at(&self) -> Option<i64> {
+        Some(self.expired_at)
+    }
+
     fn validate(&self) -> Result<()> {
         if self.account_index < MIN_ACCOUNT_INDEX || self.account_index > MAX_ACCOUNT_INDEX {
             return Err(LighterError::AccountIndexTooLow(self.account_index));
@@ -344,9 +571,95 @@ impl TxInfo for L2CreateSubAccountTxInfo {
     }
 }
 
+impl_resignable!(
+    L2TransferTxInfo,
+    L2WithdrawTxInfo,
+    L2ChangePubKeyTxInfo,
+    L2UpdateLeverageTxInfo,
+    L2UpdateMarginTxInfo,
+    L2CreateSubAccountTxInfo,
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::str::FromStr;
+
+    fn dec(text: &str) -> Decimal {
+        Decimal::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_usdc_constructors() {
+        let amount: Usdc = "12.5".parse().unwrap();
+        let transfer = TransferTxReq::usdc(54321, dec("12.5"), dec("0.001"), [7u8; 32]).unwrap();
+        assert_eq!(transfer.to_account_index(), 54321);
+        assert_eq!(transfer.amount(), amount);
+        assert_eq!(transfer.fee(), Usdc::from_units(1000));
+        assert_eq!(transfer.memo(), [7u8; 32]);
+
+        let withdraw = WithdrawTxReq::usdc(dec("12.5")).unwrap();
+        assert_eq!(withdraw.units(), 12_500_000);
+        assert_eq!(withdraw.amount(), amount);
+
+        let margin = UpdateMarginTxReq::usdc(1, amount, MARGIN_ADD_TO_ISOLATED);
+        assert_eq!(margin.usdc_amount, 12_500_000);
+
+        let fee: FeeInfo = serde_json::from_str(r#"{"transfer_fee_usdc":2500}"#).unwrap();
+        assert_eq!(fee.fee().to_string(), "0.002500");
+    }
+
+    #[test]
+    fn test_usdc_constructors_scale_and_reject() {
+        let transfer =
+            |amount: &str, fee: &str| TransferTxReq::usdc(1, dec(amount), dec(fee), [0u8; 32]);
+        let max = Decimal::new(MAX_TRANSFER_AMOUNT, USDC_DECIMALS);
+
+        assert_eq!(transfer("0.000001", "0").unwrap().amount().units(), 1);
+        assert_eq!(
+            transfer("1.100000", "0").unwrap().amount().units(),
+            1_100_000
+        );
+        assert_eq!(
+            transfer(&max.to_string(), "0").unwrap().amount().units(),
+            MAX_TRANSFER_AMOUNT
+        );
+        assert!(transfer("0.0000001", "0").is_err());
+        assert!(transfer("1", "0.0000001").is_err());
+        assert!(matches!(
+            transfer("0", "0"),
+            Err(LighterError::TransferAmountTooLow(0))
+        ));
+        assert!(matches!(
+            transfer("-1", "0"),
+            Err(LighterError::TransferAmountTooLow(_))
+        ));
+        assert!(matches!(
+            transfer(&(max + dec("0.000001")).to_string(), "0"),
+            Err(LighterError::TransferAmountTooHigh(_))
+        ));
+        assert!(matches!(
+            transfer("1", "-0.01"),
+            Err(LighterError::TransferFeeNegative)
+        ));
+
+        let max = Decimal::from(MAX_WITHDRAWAL_AMOUNT) / Decimal::from(ONE_USDC);
+        assert_eq!(WithdrawTxReq::usdc(dec("0.000001")).unwrap().units(), 1);
+        assert_eq!(
+            WithdrawTxReq::usdc(max).unwrap().units(),
+            MAX_WITHDRAWAL_AMOUNT
+        );
+        assert!(WithdrawTxReq::usdc(dec("2.0000001")).is_err());
+        assert!(WithdrawTxReq::usdc(dec("-1")).is_err());
+        assert!(matches!(
+            WithdrawTxReq::usdc(Decimal::ZERO),
+            Err(LighterError::WithdrawalAmountTooLow(0))
+        ));
+        assert!(matches!(
+            WithdrawTxReq::usdc(max + dec("0.000001")),
+            Err(LighterError::WithdrawalAmountTooHigh(_))
+        ));
+    }
 
     #[test]
     fn test_transfer_validation_success() {
@@ -434,6 +747,7 @@ mod tests {
             expired_at: 1000000,
             nonce: 1,
             sig: None,
+            l1_sig: None,
             signed_hash: None,
         };
 
@@ -450,6 +764,7 @@ mod tests {
             expired_at: 1000000,
             nonce: 1,
             sig: None,
+            l1_sig: None,
             signed_hash: None,
         };
 
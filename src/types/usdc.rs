@@ -0,0 +1,327 @@
+//! USDC amounts in protocol units
+//!
+//! [`Usdc`] wraps the integer amount the protocol uses (see [`ONE_USDC`]),
+//! formats it with thousands separators and parses human strings without
+//! going through `f64`.
+//!
+//! ```
+//! use lighter_rs::types::Usdc;
+//!
+//! let amount: Usdc = "1234.56".parse()?;
+//! assert_eq!(amount.units(), 1_234_560_000);
+//! assert_eq!(amount.to_string(), "1,234.560000");
+//! assert!("0.1234567".parse::<Usdc>().is_err());
+//! # Ok::<(), lighter_rs::LighterError>(())
+//! ```
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+use crate::constants::{ONE_USDC, USDC_DECIMALS};
+use crate::errors::{LighterError, Result};
+
+/// Amount of USDC in protocol units
+///
+/// Serializes as the integer number of units and deserializes from either
+/// that integer or a decimal string such as `"1234.56"`. The `+`, `-` and
+/// unary `-` operators panic on overflow; use [`checked_add`](Self::checked_add)
+/// and [`checked_sub`](Self::checked_sub) to get an error instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Usdc(i64);
+
+impl Usdc {
+    pub const ZERO: Usdc = Usdc(0);
+    pub const ONE: Usdc = Usdc(ONE_USDC);
+    pub const MAX: Usdc = Usdc(i64::MAX);
+    pub const MIN: Usdc = Usdc(i64::MIN);
+
+    /// Wrap an amount already in protocol units
+    pub const fn from_units(units: i64) -> Self {
+        Self(units)
+    }
+
+    /// The amount in protocol units
+    pub const fn units(self) -> i64 {
+        self.0
+    }
+
+    /// Convert a USDC value, rejecting more than six decimal places
+    pub fn from_decimal(value: Decimal) -> Result<Self> {
+        if value.normalize().scale() > USDC_DECIMALS {
+            return Err(LighterError::ValidationError(format!(
+                "USDC amount {} has more than {} decimal places",
+                value, USDC_DECIMALS
+            )));
+        }
+        Self::scale(value)
+    }
+
+    /// Convert a USDC value, rounding to six decimal places first
+    pub fn from_decimal_rounded(value: Decimal, strategy: RoundingStrategy) -> Result<Self> {
+        Self::scale(value.round_dp_with_strategy(USDC_DECIMALS, strategy))
+    }
+
+    fn scale(value: Decimal) -> Result<Self> {
+        value
+            .checked_mul(Decimal::from(ONE_USDC))
+            .and_then(|units| i64::try_from(units).ok())
+            .map(Self)
+            .ok_or_else(|| {
+                LighterError::ValidationError(format!("USDC amount {} overflows", value))
+            })
+    }
+
+    /// The amount in USDC
+    pub fn to_decimal(self) -> Decimal {
+        Decimal::new(self.0, USDC_DECIMALS)
+    }
+
+    /// Add, failing instead of overflowing
+    pub fn checked_add(self, rhs: Usdc) -> Result<Usdc> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Self)
+            .ok_or_else(|| overflow("+", self, rhs))
+    }
+
+    /// Subtract, failing instead of overflowing
+    pub fn checked_sub(self, rhs: Usdc) -> Result<Usdc> {
+        self.0
+            .checked_sub(rhs.0)
+            .map(Self)
+            .ok_or_else(|| overflow("-", self, rhs))
+    }
+
+    /// Whether the amount is below zero
+    pub fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+}
+
+fn overflow(op: &str, lhs: Usdc, rhs: Usdc) -> LighterError {
+    LighterError::ValidationError(format!("USDC amount {} {} {} overflows", lhs, op, rhs))
+}
+
+impl Add for Usdc {
+    type Output = Usdc;
+
+    fn add(self, rhs: Usdc) -> Usdc {
+        match self.checked_add(rhs) {
+            Ok(sum) => sum,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl Sub for Usdc {
+    type Output = Usdc;
+
+    fn sub(self, rhs: Usdc) -> Usdc {
+        match self.checked_sub(rhs) {
+            Ok(difference) => difference,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+impl Neg for Usdc {
+    type Output = Usdc;
+
+    fn neg(self) -> Usdc {
+        match self.0.checked_neg() {
+            Some(units) => Self(units),
+            None => panic!("USDC amount -({}) overflows", self),
+        }
+    }
+}
+
+impl From<Usdc> for Decimal {
+    fn from(value: Usdc) -> Decimal {
+        value.to_decimal()
+    }
+}
+
+impl TryFrom<Decimal> for Usdc {
+    type Error = LighterError;
+
+    fn try_from(value: Decimal) -> Result<Self> {
+        Self::from_decimal(value)
+    }
+}
+
+impl fmt::Display for Usdc {
+    /// Formats as `1,234.567890`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = self.0.unsigned_abs();
+        let whole = (units / ONE_USDC as u64).to_string();
+        let fraction = units % ONE_USDC as u64;
+
+        let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+
+        let sign = if self.0 < 0 { "-" } else { "" };
+        f.pad(&format!(
+            "{}{}.{:0width$}",
+            sign,
+            grouped,
+            fraction,
+            width = USDC_DECIMALS as usize
+        ))
+    }
+}
+
+impl FromStr for Usdc {
+    type Err = LighterError;
+
+    /// Parse a USDC value such as `1234.56` or `1,234.56`
+    fn from_str(s: &str) -> Result<Self> {
+        let value = Decimal::from_str(&s.trim().replace(',', "")).map_err(|e| {
+            LighterError::ValidationError(format!("Invalid USDC amount {:?}: {}", s, e))
+        })?;
+        Self::from_decimal(value)
+    }
+}
+
+impl Serialize for Usdc {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Usdc {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct UsdcVisitor;
+
+        impl Visitor<'_> for UsdcVisitor {
+            type Value = Usdc;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an integer amount of USDC units or a decimal USDC string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<Usdc, E> {
+                Ok(Usdc(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<Usdc, E> {
+                i64::try_from(v)
+                    .map(Usdc)
+                    .map_err(|_| E::custom(format!("USDC amount {} overflows", v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Usdc, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(UsdcVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Usdc::from_units(1_234_567_890).to_string(), "1,234.567890");
+        assert_eq!(Usdc::from_units(-5).to_string(), "-0.000005");
+        assert_eq!(Usdc::ZERO.to_string(), "0.000000");
+        assert_eq!(Usdc::ONE.to_string(), "1.000000");
+        assert_eq!(Usdc::MIN.to_string(), "-9,223,372,036,854.775808");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1234.56".parse::<Usdc>().unwrap().units(), 1_234_560_000);
+        assert_eq!("1,234.56".parse::<Usdc>().unwrap().units(), 1_234_560_000);
+        assert_eq!("-0.5".parse::<Usdc>().unwrap().units(), -500_000);
+        // Trailing zeros don't count as extra places
+        assert_eq!("1.0000000".parse::<Usdc>().unwrap(), Usdc::ONE);
+        assert!("0.0000001".parse::<Usdc>().is_err());
+        assert!("abc".parse::<Usdc>().is_err());
+        assert!("10000000000000".parse::<Usdc>().is_err());
+    }
+
+    #[test]
+    fn test_explicit_rounding() {
+        let value = Decimal::new(12_345_678, 7); // 1.2345678
+        assert!(Usdc::from_decimal(value).is_err());
+        assert_eq!(
+            Usdc::from_decimal_rounded(value, RoundingStrategy::ToZero)
+                .unwrap()
+                .units(),
+            1_234_567
+        );
+        assert_eq!(
+            Usdc::from_decimal_rounded(value, RoundingStrategy::MidpointAwayFromZero)
+                .unwrap()
+                .units(),
+            1_234_568
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let near_max = Usdc::from_units(i64::MAX - 1);
+        assert_eq!(
+            near_max.checked_add(Usdc::from_units(1)).unwrap(),
+            Usdc::MAX
+        );
+        assert!(near_max.checked_add(Usdc::from_units(2)).is_err());
+        assert!(Usdc::MIN.checked_sub(Usdc::from_units(1)).is_err());
+        assert!(std::panic::catch_unwind(|| near_max + Usdc::ONE).is_err());
+        assert!(std::panic::catch_unwind(|| -Usdc::MIN).is_err());
+    }
+
+    #[test]
+    fn test_serde_forms() {
+        let amount: Usdc = serde_json::from_str("2500000").unwrap();
+        assert_eq!(amount.units(), 2_500_000);
+        let amount: Usdc = serde_json::from_str(r#""2.5""#).unwrap();
+        assert_eq!(amount.units(), 2_500_000);
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "2500000");
+        assert!(serde_json::from_str::<Usdc>("2.5").is_err());
+        assert!(serde_json::from_str::<Usdc>("18446744073709551615").is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_display_round_trips(units in any::<i64>()) {
+            let amount = Usdc::from_units(units);
+            prop_assert_eq!(amount.to_string().parse::<Usdc>().unwrap(), amount);
+        }
+
+        #[test]
+        fn prop_decimal_round_trips(units in any::<i64>()) {
+            let amount = Usdc::from_units(units);
+            prop_assert_eq!(Usdc::from_decimal(amount.to_decimal()).unwrap(), amount);
+        }
+
+        #[test]
+        fn prop_checked_add_matches_i64(a in any::<i64>(), b in any::<i64>()) {
+            let sum = Usdc::from_units(a).checked_add(Usdc::from_units(b));
+            match a.checked_add(b) {
+                Some(units) => prop_assert_eq!(sum.unwrap().units(), units),
+                None => prop_assert!(sum.is_err()),
+            }
+        }
+
+        #[test]
+        fn prop_seventh_place_rejected(units in -1_000_000_000_000i64..1_000_000_000_000, digit in 1i64..10) {
+            let value = Decimal::new(units * 10 + digit, USDC_DECIMALS + 1);
+            prop_assert!(Usdc::from_decimal(value).is_err());
+            prop_assert!(value.to_string().parse::<Usdc>().is_err());
+        }
+    }
+}
@@ -1,5 +1,9 @@
 //! Validation utilities for transaction types
 
+use serde::Serialize;
+use std::fmt;
+
+use super::CreateOrderTxReq;
 use crate::constants::*;
 use crate::errors::{LighterError, Result};
 
@@ -29,3 +33,308 @@ pub fn validate_market_index(index: u8) -> Result<()> {
     }
     Ok(())
 }
+
+/// Validate an order base amount
+pub fn validate_base_amount(amount: i64) -> Result<()> {
+    if amount < MIN_ORDER_BASE_AMOUNT {
+        return Err(LighterError::BaseAmountTooLow(amount));
+    }
+    if amount > MAX_ORDER_BASE_AMOUNT {
+        return Err(LighterError::BaseAmountTooHigh(amount));
+    }
+    Ok(())
+}
+
+/// Validate an order price; every value from `MIN_ORDER_PRICE` up fits
+pub fn validate_price(price: u32) -> Result<()> {
+    if price < MIN_ORDER_PRICE {
+        return Err(LighterError::PriceTooLow(price));
+    }
+    Ok(())
+}
+
+/// Collects the errors of several checks instead of stopping at the first
+#[derive(Default)]
+pub(crate) struct Checks(Vec<LighterError>);
+
+impl Checks {
+    pub(crate) fn check(&mut self, result: Result<()>) {
+        if let Err(e) = result {
+            self.0.push(e);
+        }
+    }
+
+    pub(crate) fn fail(&mut self, error: LighterError) {
+        self.0.push(error);
+    }
+
+    /// All errors in the order the checks ran
+    pub(crate) fn finish(self) -> std::result::Result<(), Vec<LighterError>> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(self.0)
+        }
+    }
+}
+
+/// Values a field accepts, for [`ValidationIssue`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllowedValues {
+    /// Inclusive range
+    Range {
+        min: i64,
+        max: i64,
+    },
+    AtLeast(i64),
+    OneOf(Vec<i64>),
+}
+
+impl fmt::Display for AllowedValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllowedValues::Range { min, max } => write!(f, "{}..={}", min, max),
+            AllowedValues::AtLeast(min) => write!(f, "at least {}", min),
+            AllowedValues::OneOf(values) => {
+                let values: Vec<String> = values.iter().map(i64::to_string).collect();
+                write!(f, "one of {}", values.join(", "))
+            }
+        }
+    }
+}
+
+/// One field of a request that failed a check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    /// Field name as in the request struct
+    pub field: &'static str,
+    /// The offending value
+    pub value: i64,
+    pub allowed: AllowedValues,
+    /// Human readable description, e.g. for a form error
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(field: &'static str, value: i64, allowed: AllowedValues) -> Self {
+        let message = format!("{} {} is not {}", field, value, allowed);
+        Self {
+            field,
+            value,
+            allowed,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Values from outside a request that [`check_order`] checks too
+///
+/// Fields left `None` are not checked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationCtx {
+    pub account_index: Option<i64>,
+    pub api_key_index: Option<u8>,
+    pub nonce: Option<i64>,
+}
+
+/// Check every field of an order request, without a client
+///
+/// Returns one issue per failing field, empty if the order is valid. The
+/// same rules are applied when the order is built and signed.
+pub fn check_order(req: &CreateOrderTxReq, ctx: &ValidationCtx) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let mut range = |field, value: i64, min: i64, max: i64| {
+        if !(min..=max).contains(&value) {
+            issues.push(ValidationIssue::new(
+                field,
+                value,
+                AllowedValues::Range { min, max },
+            ));
+        }
+    };
+
+    if let Some(account_index) = ctx.account_index {
+        range(
+            "account_index",
+            account_index,
+            MIN_ACCOUNT_INDEX,
+            MAX_ACCOUNT_INDEX,
+        );
+    }
+    if let Some(api_key_index) = ctx.api_key_index {
+        range(
+            "api_key_index",
+            api_key_index.into(),
+            MIN_API_KEY_INDEX.into(),
+            MAX_API_KEY_INDEX.into(),
+        );
+    }
+    range(
+        "market_index",
+        req.market_index.into(),
+        MIN_MARKET_INDEX.into(),
+        MAX_MARKET_INDEX.into(),
+    );
+    range(
+        "client_order_index",
+        req.client_order_index,
+        NIL_CLIENT_ORDER_INDEX,
+        MAX_CLIENT_ORDER_INDEX,
+    );
+    range(
+        "base_amount",
+        req.base_amount,
+        MIN_ORDER_BASE_AMOUNT,
+        MAX_ORDER_BASE_AMOUNT,
+    );
+    range(
+        "price",
+        req.price.into(),
+        MIN_ORDER_PRICE.into(),
+        MAX_ORDER_PRICE.into(),
+    );
+    range(
+        "order_type",
+        req.order_type.into(),
+        ORDER_TYPE_LIMIT.into(),
+        API_MAX_ORDER_TYPE.into(),
+    );
+    if req.order_expiry != NIL_ORDER_EXPIRY {
+        range(
+            "order_expiry",
+            req.order_expiry,
+            MIN_MILLIS_TIMESTAMP,
+            MAX_TIMESTAMP,
+        );
+    }
+
+    let mut one_of = |field, value: u8, allowed: &[u8]| {
+        if !allowed.contains(&value) {
+            issues.push(ValidationIssue::new(
+                field,
+                value.into(),
+                AllowedValues::OneOf(allowed.iter().map(|&v| v.into()).collect()),
+            ));
+        }
+    };
+    one_of("is_ask", req.is_ask, &[0, 1]);
+    one_of(
+        "time_in_force",
+        req.time_in_force,
+        &[
+            TIME_IN_FORCE_IMMEDIATE_OR_CANCEL,
+            TIME_IN_FORCE_GOOD_TILL_TIME,
+            TIME_IN_FORCE_POST_ONLY,
+        ],
+    );
+    one_of("reduce_only", req.reduce_only, &[0, 1]);
+
+    let is_trigger = matches!(
+        req.order_type,
+        ORDER_TYPE_STOP_LOSS
+            | ORDER_TYPE_STOP_LOSS_LIMIT
+            | ORDER_TYPE_TAKE_PROFIT
+            | ORDER_TYPE_TAKE_PROFIT_LIMIT
+    );
+    if is_trigger && req.trigger_price < MIN_ORDER_TRIGGER_PRICE {
+        issues.push(ValidationIssue::new(
+            "trigger_price",
+            req.trigger_price.into(),
+            AllowedValues::AtLeast(MIN_ORDER_TRIGGER_PRICE.into()),
+        ));
+    }
+    if let Some(nonce) = ctx.nonce {
+        if nonce < MIN_NONCE {
+            issues.push(ValidationIssue::new(
+                "nonce",
+                nonce,
+                AllowedValues::AtLeast(MIN_NONCE),
+            ));
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_order() -> CreateOrderTxReq {
+        CreateOrderTxReq {
+            market_index: 0,
+            client_order_index: 1,
+            base_amount: 1_000,
+            price: 100_000,
+            is_ask: 0,
+            order_type: ORDER_TYPE_LIMIT,
+            time_in_force: TIME_IN_FORCE_GOOD_TILL_TIME,
+            reduce_only: 0,
+            trigger_price: 0,
+            order_expiry: NIL_ORDER_EXPIRY,
+        }
+    }
+
+    #[test]
+    fn test_check_order_valid() {
+        let ctx = ValidationCtx {
+            account_index: Some(7),
+            api_key_index: Some(0),
+            nonce: Some(1),
+        };
+        assert!(check_order(&valid_order(), &ctx).is_empty());
+    }
+
+    #[test]
+    fn test_check_order_reports_every_issue() {
+        let req = CreateOrderTxReq {
+            market_index: 255,
+            base_amount: 0,
+            is_ask: 2,
+            order_type: ORDER_TYPE_STOP_LOSS,
+            ..valid_order()
+        };
+        let issues = check_order(&req, &ValidationCtx::default());
+
+        let fields: Vec<&str> = issues.iter().map(|issue| issue.field).collect();
+        assert_eq!(
+            fields,
+            vec!["market_index", "base_amount", "is_ask", "trigger_price"]
+        );
+        assert_eq!(issues[0].value, 255);
+        assert_eq!(issues[0].allowed, AllowedValues::Range { min: 0, max: 254 });
+        assert_eq!(issues[2].allowed, AllowedValues::OneOf(vec![0, 1]));
+        assert_eq!(issues[3].allowed, AllowedValues::AtLeast(1));
+        assert_eq!(
+            issues[1].to_string(),
+            "base_amount 0 is not 1..=281474976710655"
+        );
+    }
+
+    #[test]
+    fn test_check_order_uses_context() {
+        let ctx = ValidationCtx {
+            account_index: Some(-1),
+            api_key_index: Some(255),
+            nonce: Some(-1),
+        };
+        let req = CreateOrderTxReq {
+            order_expiry: 1_700_000_000,
+            ..valid_order()
+        };
+        let fields: Vec<&str> = check_order(&req, &ctx)
+            .iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec!["account_index", "api_key_index", "order_expiry", "nonce"]
+        );
+    }
+}
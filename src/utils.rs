@@ -1,7 +1,14 @@
 //! Utility functions for the Lighter SDK
 
+use crate::constants::{
+    MAX_ORDER_BASE_AMOUNT, MAX_TIMESTAMP, MAX_TRANSFER_AMOUNT, MIN_MILLIS_TIMESTAMP,
+    MIN_ORDER_BASE_AMOUNT, MIN_ORDER_PRICE, MIN_TRANSFER_AMOUNT, USDC_DECIMALS,
+};
 use crate::errors::{LighterError, Result};
+use crate::types::Fill;
 use hex;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 
 /// Convert hex string to bytes, handling optional 0x prefix
 pub fn hex_to_bytes(hex_str: &str) -> Result<Vec<u8>> {
@@ -40,6 +47,154 @@ pub fn validate_range<T: PartialOrd + std::fmt::Display>(
     Ok(())
 }
 
+/// Check that a timestamp is in milliseconds and at most `MAX_TIMESTAMP`
+///
+/// Values below `MIN_MILLIS_TIMESTAMP` are taken to be seconds and rejected
+/// with a hint at the millisecond value.
+pub fn validate_timestamp_ms(value: i64, field_name: &str) -> Result<()> {
+    if value < MIN_MILLIS_TIMESTAMP {
+        return Err(LighterError::ValidationError(format!(
+            "{} {} looks like seconds; expected milliseconds since the epoch (e.g. {})",
+            field_name,
+            value,
+            value.saturating_mul(1000)
+        )));
+    }
+    if value > MAX_TIMESTAMP {
+        return Err(LighterError::ValidationError(format!(
+            "{} {} is above the maximum timestamp {}",
+            field_name, value, MAX_TIMESTAMP
+        )));
+    }
+    Ok(())
+}
+
+/// Scale a decimal by `10^decimals`, rejecting results with a fractional part
+fn scale_decimal(value: Decimal, decimals: u32, field_name: &str) -> Result<Decimal> {
+    let factor = 10i64
+        .checked_pow(decimals)
+        .map(Decimal::from)
+        .ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "{} decimals too large: {}",
+                field_name, decimals
+            ))
+        })?;
+    let scaled = value.checked_mul(factor).ok_or_else(|| {
+        LighterError::ValidationError(format!("{} {} overflows when scaled", field_name, value))
+    })?;
+    if !scaled.fract().is_zero() {
+        return Err(LighterError::ValidationError(format!(
+            "{} {} has more than {} decimals",
+            field_name, value, decimals
+        )));
+    }
+    Ok(scaled)
+}
+
+/// Convert a decimal price to the integer price used on the wire
+///
+/// The price is scaled by `10^decimals` and must land on an integer in
+/// `MIN_ORDER_PRICE..=MAX_ORDER_PRICE`. Out-of-range values are rejected
+/// instead of wrapping.
+pub fn checked_price(price: Decimal, decimals: u32) -> Result<u32> {
+    let scaled = scale_decimal(price, decimals, "Price")?;
+    let value = scaled
+        .to_u64()
+        .and_then(|v| u32::try_from(v).ok())
+        .ok_or_else(|| {
+            LighterError::ValidationError(format!(
+                "Price {} at {} decimals does not fit in u32",
+                price, decimals
+            ))
+        })?;
+    if value < MIN_ORDER_PRICE {
+        return Err(LighterError::PriceTooLow(value));
+    }
+    Ok(value)
+}
+
+/// Convert a decimal size to the integer base amount used on the wire
+///
+/// The size is scaled by `10^decimals` and must land on an integer in
+/// `MIN_ORDER_BASE_AMOUNT..=MAX_ORDER_BASE_AMOUNT`.
+pub fn checked_base_amount(size: Decimal, decimals: u32) -> Result<i64> {
+    let scaled = scale_decimal(size, decimals, "Base amount")?;
+    let value = scaled.to_i64().ok_or_else(|| {
+        LighterError::ValidationError(format!(
+            "Base amount {} at {} decimals does not fit in i64",
+            size, decimals
+        ))
+    })?;
+    if value < MIN_ORDER_BASE_AMOUNT {
+        return Err(LighterError::BaseAmountTooLow(value));
+    }
+    if value > MAX_ORDER_BASE_AMOUNT {
+        return Err(LighterError::BaseAmountTooHigh(value));
+    }
+    Ok(value)
+}
+
+/// Convert a USDC amount to the integer transfer amount used on the wire
+///
+/// The amount may have at most [`USDC_DECIMALS`] decimals and must land in
+/// `MIN_TRANSFER_AMOUNT..=MAX_TRANSFER_AMOUNT`.
+pub fn checked_transfer_amount(amount: Decimal) -> Result<i64> {
+    let scaled = scale_decimal(amount, USDC_DECIMALS, "Transfer amount")?;
+    let value = scaled.to_i64().ok_or_else(|| {
+        LighterError::ValidationError(format!("Transfer amount {} does not fit in i64", amount))
+    })?;
+    if value < MIN_TRANSFER_AMOUNT {
+        return Err(LighterError::TransferAmountTooLow(value));
+    }
+    if value > MAX_TRANSFER_AMOUNT {
+        return Err(LighterError::TransferAmountTooHigh(value));
+    }
+    Ok(value)
+}
+
+/// Column order of [`export_fills_csv`]
+pub const FILLS_CSV_HEADER: &str =
+    "timestamp,trade_id,market_id,price,size,usd_amount,bid_account_id,ask_account_id,is_maker_ask,tx_hash";
+
+/// Write fills as CSV with a header row
+///
+/// Columns are [`FILLS_CSV_HEADER`]; timestamps are RFC 3339 in UTC with
+/// millisecond precision and amounts are written as plain decimals.
+pub fn export_fills_csv<W: std::io::Write>(mut writer: W, fills: &[Fill]) -> Result<()> {
+    let io_error = |e: std::io::Error| LighterError::Other(format!("CSV write error: {}", e));
+
+    writeln!(writer, "{}", FILLS_CSV_HEADER).map_err(io_error)?;
+    for fill in fills {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{}",
+            fill.timestamp
+                .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            fill.trade_id,
+            fill.market_id,
+            fill.price,
+            fill.size,
+            fill.usd_amount,
+            fill.bid_account_id,
+            fill.ask_account_id,
+            fill.is_maker_ask,
+            csv_field(&fill.tx_hash),
+        )
+        .map_err(io_error)?;
+    }
+    writer.flush().map_err(io_error)
+}
+
+/// Quote a CSV field when it contains a separator, quote or newline
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,4 +227,96 @@ mod tests {
         assert!(validate_range(0, 1, 10, "test").is_err());
         assert!(validate_range(11, 1, 10, "test").is_err());
     }
+
+    #[test]
+    fn test_checked_price() {
+        assert_eq!(checked_price(Decimal::new(300012, 2), 2).unwrap(), 300_012);
+        assert_eq!(checked_price(Decimal::from(u32::MAX), 0).unwrap(), u32::MAX);
+        assert_eq!(
+            checked_price(Decimal::from(i32::MAX as i64 + 1), 0).unwrap(),
+            2_147_483_648
+        );
+
+        // Just above u32::MAX must not wrap
+        assert!(matches!(
+            checked_price(Decimal::from(u32::MAX as u64 + 1), 0),
+            Err(LighterError::ValidationError(_))
+        ));
+        // BTC at 1e8 scaling overflows u32
+        assert!(checked_price(Decimal::from(65_000), 8).is_err());
+        assert!(checked_price(Decimal::new(-1, 0), 0).is_err());
+        assert!(matches!(
+            checked_price(Decimal::ZERO, 2),
+            Err(LighterError::PriceTooLow(0))
+        ));
+        // Sub-tick precision is rejected rather than truncated
+        assert!(checked_price(Decimal::new(3000125, 3), 2).is_err());
+    }
+
+    #[test]
+    fn test_checked_base_amount() {
+        assert_eq!(checked_base_amount(Decimal::new(15, 1), 4).unwrap(), 15_000);
+        assert_eq!(
+            checked_base_amount(Decimal::from(MAX_ORDER_BASE_AMOUNT), 0).unwrap(),
+            MAX_ORDER_BASE_AMOUNT
+        );
+        assert!(matches!(
+            checked_base_amount(Decimal::from(MAX_ORDER_BASE_AMOUNT + 1), 0),
+            Err(LighterError::BaseAmountTooHigh(_))
+        ));
+        assert!(matches!(
+            checked_base_amount(Decimal::from(i64::MAX) + Decimal::ONE, 0),
+            Err(LighterError::ValidationError(_))
+        ));
+        assert!(matches!(
+            checked_base_amount(Decimal::ZERO, 4),
+            Err(LighterError::BaseAmountTooLow(0))
+        ));
+    }
+
+    #[test]
+    fn test_checked_transfer_amount() {
+        assert_eq!(
+            checked_transfer_amount(Decimal::new(125, 1)).unwrap(),
+            12_500_000
+        );
+        assert_eq!(checked_transfer_amount(Decimal::new(1, 6)).unwrap(), 1);
+        assert!(matches!(
+            checked_transfer_amount(Decimal::new(1, 7)),
+            Err(LighterError::ValidationError(_))
+        ));
+        assert!(matches!(
+            checked_transfer_amount(Decimal::ZERO),
+            Err(LighterError::TransferAmountTooLow(0))
+        ));
+        assert!(matches!(
+            checked_transfer_amount(Decimal::new(MAX_TRANSFER_AMOUNT + 1, USDC_DECIMALS)),
+            Err(LighterError::TransferAmountTooHigh(_))
+        ));
+    }
+
+    #[test]
+    fn test_export_fills_csv_snapshot() {
+        let fill = |trade_id: i64, tx_hash: &str| Fill {
+            trade_id,
+            tx_hash: tx_hash.to_string(),
+            market_id: 0,
+            size: Decimal::new(15, 1),
+            price: Decimal::new(300_012, 2),
+            usd_amount: Decimal::new(450_018, 2),
+            ask_account_id: 7,
+            bid_account_id: 12345,
+            is_maker_ask: true,
+            timestamp: chrono::DateTime::from_timestamp_millis(1_700_000_000_123).unwrap(),
+        };
+
+        let mut out = Vec::new();
+        export_fills_csv(&mut out, &[fill(1, "0xab"), fill(2, "odd,\"hash\"")]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "timestamp,trade_id,market_id,price,size,usd_amount,bid_account_id,ask_account_id,is_maker_ask,tx_hash\n\
+             2023-11-14T22:13:20.123Z,1,0,3000.12,1.5,4500.18,12345,7,true,0xab\n\
+             2023-11-14T22:13:20.123Z,2,0,3000.12,1.5,4500.18,12345,7,true,\"odd,\"\"hash\"\"\"\n"
+        );
+    }
 }
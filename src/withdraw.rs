@@ -0,0 +1,454 @@
+//! Withdrawals that are submitted once and then confirmed
+//!
+//! A withdrawal whose submission timed out may or may not have reached the
+//! exchange, and sending it again risks withdrawing twice.
+//! [`TxClient::withdraw_confirmed`] never resubmits: after an ambiguous
+//! failure it looks the signed transaction up by hash until it can tell
+//! what happened.
+//!
+//! ```no_run
+//! # async fn example(client: lighter_rs::client::TxClient) -> lighter_rs::Result<()> {
+//! use std::time::Duration;
+//! use lighter_rs::types::Usdc;
+//! use lighter_rs::withdraw::WithdrawOutcome;
+//!
+//! let amount: Usdc = "25".parse()?;
+//! match client.withdraw_confirmed(amount, None, Duration::from_secs(60)).await? {
+//!     WithdrawOutcome::Confirmed { tx_hash, .. } => println!("withdrawn in {}", tx_hash),
+//!     WithdrawOutcome::NotExecuted { reason, .. } => println!("safe to retry: {}", reason),
+//!     WithdrawOutcome::Unknown { tx_hash, .. } => println!("check {} before retrying", tx_hash),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use rust_decimal::Decimal;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::{HTTPClient, TxClient, TxStatus};
+use crate::errors::{ErrorKind, LighterError, Result};
+use crate::types::{AccountIndex, L2WithdrawTxInfo, TransactOpts, Usdc, WithdrawTxReq};
+
+/// Time between lookups of a submitted withdrawal
+const WITHDRAW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How a withdrawal was found to have executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmedBy {
+    /// The transaction endpoint reported it executed
+    TxStatus,
+    /// The transaction endpoint was unavailable and the account's
+    /// collateral dropped by at least the amount
+    BalanceDelta,
+}
+
+/// What happened to a withdrawal sent by [`TxClient::withdraw_confirmed`]
+///
+/// Only [`NotExecuted`](Self::NotExecuted) means the withdrawal can be sent
+/// again.
+#[must_use]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WithdrawOutcome {
+    /// The withdrawal executed
+    Confirmed { tx_hash: String, by: ConfirmedBy },
+    /// The withdrawal was rejected, failed or expired unexecuted
+    NotExecuted { tx_hash: String, reason: String },
+    /// The withdrawal may still execute; don't retry before finding out
+    ///
+    /// The transaction can't execute after its `expired_at`.
+    Unknown {
+        tx_hash: String,
+        expired_at: i64,
+        reason: String,
+    },
+}
+
+impl WithdrawOutcome {
+    /// Hash of the signed withdrawal
+    pub fn tx_hash(&self) -> &str {
+        match self {
+            WithdrawOutcome::Confirmed { tx_hash, .. }
+            | WithdrawOutcome::NotExecuted { tx_hash, .. }
+            | WithdrawOutcome::Unknown { tx_hash, .. } => tx_hash,
+        }
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        matches!(self, WithdrawOutcome::Confirmed { .. })
+    }
+
+    /// Whether sending a new withdrawal can't withdraw twice
+    pub fn is_safe_to_retry(&self) -> bool {
+        matches!(self, WithdrawOutcome::NotExecuted { .. })
+    }
+}
+
+/// Whether a failed submission may still have reached the exchange
+///
+/// Failures to connect and rejections are definite; timeouts, broken
+/// connections, gateway errors and unreadable answers are not.
+pub(crate) fn is_ambiguous(error: &LighterError) -> bool {
+    match error {
+        LighterError::HttpError(e) if e.is_connect() => false,
+        e => matches!(e.kind(), ErrorKind::Transport | ErrorKind::Server),
+    }
+}
+
+impl TxClient {
+    /// Withdraw `usdc_amount`, sending it once, and find out whether it
+    /// executed
+    ///
+    /// The withdrawal is signed and its hash recorded before it is sent.
+    /// Whether the submission succeeds or fails ambiguously, the hash is
+    /// then looked up until the withdrawal executes, fails or expires, or
+    /// until `confirm_timeout` has passed since signing. While the
+    /// transaction endpoint is unavailable a drop in collateral of at least
+    /// the amount counts as executed.
+    ///
+    /// Errors are returned only for failures before anything was sent,
+    /// such as an invalid amount or a missing [`HTTPClient`].
+    pub async fn withdraw_confirmed(
+        &self,
+        usdc_amount: Usdc,
+        opts: Option<TransactOpts>,
+        confirm_timeout: Duration,
+    ) -> Result<WithdrawOutcome> {
+        let client = self.http().ok_or_else(|| {
+            LighterError::InvalidConfiguration(
+                "HTTPClient is required to confirm a withdrawal".to_string(),
+            )
+        })?;
+        let account_index = AccountIndex::new(self.account_index())?;
+        let deadline = Instant::now() + confirm_timeout;
+        let tx = self
+            .withdraw(&WithdrawTxReq::usdc(usdc_amount.to_decimal())?, opts)
+            .await?;
+        let tx_hash = tx
+            .signed_hash
+            .clone()
+            .ok_or_else(|| LighterError::MissingField("signed_hash".to_string()))?;
+        let collateral_before = client
+            .get_account_snapshot(account_index)
+            .await
+            .ok()
+            .and_then(|snapshot| snapshot.collateral);
+
+        let last_error = match self.send_transaction(&tx).await {
+            Ok(response) if response.code == 200 => None,
+            Ok(response) => {
+                return Ok(WithdrawOutcome::NotExecuted {
+                    tx_hash,
+                    reason: format!(
+                        "Rejected with code {}: {}",
+                        response.code,
+                        response.message.unwrap_or_default()
+                    ),
+                })
+            }
+            Err(e) if is_ambiguous(&e) => {
+                eprintln!(
+                    "Withdrawal {} may have been submitted ({}); checking its status",
+                    tx_hash, e
+                );
+                Some(e.to_string())
+            }
+            Err(e) => {
+                return Ok(WithdrawOutcome::NotExecuted {
+                    tx_hash,
+                    reason: e.to_string(),
+                })
+            }
+        };
+
+        let watch = WithdrawalWatch {
+            client,
+            account_index,
+            tx: &tx,
+            tx_hash,
+            collateral_before,
+        };
+        Ok(watch.poll(deadline, last_error).await)
+    }
+}
+
+/// A sent withdrawal being looked up
+struct WithdrawalWatch<'a> {
+    client: &'a HTTPClient,
+    account_index: AccountIndex,
+    tx: &'a L2WithdrawTxInfo,
+    tx_hash: String,
+    collateral_before: Option<Decimal>,
+}
+
+impl WithdrawalWatch<'_> {
+    async fn poll(self, deadline: Instant, last_error: Option<String>) -> WithdrawOutcome {
+        let submission = last_error.unwrap_or_else(|| "Submission accepted".to_string());
+        loop {
+            let status = match self.client.get_tx_status(&self.tx_hash).await {
+                Ok(Some(TxStatus::Executed)) => {
+                    return WithdrawOutcome::Confirmed {
+                        tx_hash: self.tx_hash,
+                        by: ConfirmedBy::TxStatus,
+                    }
+                }
+                Ok(Some(TxStatus::Failed)) => {
+                    return WithdrawOutcome::NotExecuted {
+                        tx_hash: self.tx_hash,
+                        reason: "Failed on execution".to_string(),
+                    }
+                }
+                Ok(Some(status)) => format!("Transaction is {:?}", status),
+                Ok(None) if chrono::Utc::now().timestamp_millis() > self.tx.expired_at => {
+                    return WithdrawOutcome::NotExecuted {
+                        tx_hash: self.tx_hash,
+                        reason: "Not found after expiry".to_string(),
+                    }
+                }
+                Ok(None) => "Transaction not found yet".to_string(),
+                Err(e) => {
+                    if self.balance_dropped().await {
+                        return WithdrawOutcome::Confirmed {
+                            tx_hash: self.tx_hash,
+                            by: ConfirmedBy::BalanceDelta,
+                        };
+                    }
+                    format!("Status unavailable: {}", e)
+                }
+            };
+
+            let now = Instant::now();
+            if now >= deadline {
+                return WithdrawOutcome::Unknown {
+                    tx_hash: self.tx_hash,
+                    expired_at: self.tx.expired_at,
+                    reason: format!("{}; {}", submission, status),
+                };
+            }
+            tokio::time::sleep(WITHDRAW_POLL_INTERVAL.min(deadline - now)).await;
+        }
+    }
+
+    /// Whether collateral fell by at least the amount since before sending
+    async fn balance_dropped(&self) -> bool {
+        let Some(before) = self.collateral_before else {
+            return false;
+        };
+        let amount = Usdc::from_units(self.tx.usdc_amount as i64).to_decimal();
+        match self.client.get_account_snapshot(self.account_index).await {
+            Ok(snapshot) => snapshot
+                .collateral
+                .is_some_and(|now| before - now >= amount),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn test_client(url: &str) -> TxClient {
+        let mut client = test_support::test_client(url);
+        client.set_min_remaining_validity(None);
+        client
+            .http_mut()
+            .unwrap()
+            .set_request_timeout(Duration::from_millis(200))
+            .unwrap();
+        client
+    }
+
+    /// Options for a withdrawal expiring `ms` from now
+    fn opts_expiring_in(ms: i64) -> Option<TransactOpts> {
+        Some(TransactOpts {
+            expired_at: chrono::Utc::now().timestamp_millis() + ms,
+            nonce: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn send_tx(server: &mut mockito::Server) -> mockito::Mock {
+        server
+            .mock("POST", "/api/v1/sendTx")
+            .match_body(mockito::Matcher::Regex(r#""tx_type":13,"#.to_string()))
+    }
+
+    fn tx_status(server: &mut mockito::Server, status: usize, body: &str) -> mockito::Mock {
+        server
+            .mock("GET", "/api/v1/tx")
+            .match_query(mockito::Matcher::Any)
+            .with_status(status)
+            .with_body(body)
+    }
+
+    #[tokio::test]
+    async fn test_timeout_then_executed_is_confirmed() {
+        let mut server = mockito::Server::new_async().await;
+        let submit = send_tx(&mut server)
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(400));
+                w.write_all(br#"{"code":200}"#)
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let status = tx_status(&mut server, 200, r#"{"code":200,"status":2}"#)
+            .expect_at_least(1)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let outcome = client
+            .withdraw_confirmed(Usdc::ONE, opts_expiring_in(60_000), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            outcome,
+            WithdrawOutcome::Confirmed {
+                by: ConfirmedBy::TxStatus,
+                ..
+            }
+        ));
+        assert_eq!(outcome.tx_hash().len(), 80);
+        submit.assert_async().await;
+        status.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_reset_after_send_then_expired_is_not_executed() {
+        let mut server = mockito::Server::new_async().await;
+        let submit = send_tx(&mut server)
+            .with_chunked_body(|_| Err(std::io::ErrorKind::ConnectionReset.into()))
+            .expect(1)
+            .create_async()
+            .await;
+        let _status = tx_status(&mut server, 404, "").create_async().await;
+
+        let client = test_client(&server.url());
+        let outcome = client
+            .withdraw_confirmed(Usdc::ONE, opts_expiring_in(600), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(outcome.is_safe_to_retry(), "{:?}", outcome);
+        assert!(matches!(
+            &outcome,
+            WithdrawOutcome::NotExecuted { reason, .. } if reason.contains("expiry")
+        ));
+        submit.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_gateway_error_confirmed_by_balance() {
+        let mut server = mockito::Server::new_async().await;
+        let submit = send_tx(&mut server)
+            .with_status(504)
+            .with_body("gateway timeout")
+            .expect(1)
+            .create_async()
+            .await;
+        let _status = tx_status(&mut server, 500, "").create_async().await;
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+        let _account = server
+            .mock("GET", "/api/v1/account")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(move |_| {
+                let collateral = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    "100.5"
+                } else {
+                    "99.5"
+                };
+                format!(r#"{{"accounts":[{{"collateral":"{}"}}]}}"#, collateral).into()
+            })
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let outcome = client
+            .withdraw_confirmed(Usdc::ONE, opts_expiring_in(60_000), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            outcome,
+            WithdrawOutcome::Confirmed {
+                by: ConfirmedBy::BalanceDelta,
+                ..
+            }
+        ));
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+        submit.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_still_pending_is_unknown() {
+        let mut server = mockito::Server::new_async().await;
+        let submit = send_tx(&mut server)
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_millis(400));
+                w.write_all(br#"{"code":200}"#)
+            })
+            .expect(1)
+            .create_async()
+            .await;
+        let _status = tx_status(&mut server, 200, r#"{"code":200,"status":1}"#)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let expired_at = chrono::Utc::now().timestamp_millis() + 60_000;
+        let outcome = client
+            .withdraw_confirmed(
+                Usdc::ONE,
+                opts_expiring_in(60_000),
+                Duration::from_millis(800),
+            )
+            .await
+            .unwrap();
+
+        match &outcome {
+            WithdrawOutcome::Unknown {
+                expired_at: at,
+                reason,
+                ..
+            } => {
+                assert!((*at - expired_at).abs() < 1_000);
+                assert!(reason.contains("Pending"), "{}", reason);
+            }
+            other => panic!("expected Unknown, got {:?}", other),
+        }
+        assert!(!outcome.is_safe_to_retry());
+        submit.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_rejection_is_not_executed_without_lookup() {
+        let mut server = mockito::Server::new_async().await;
+        let submit = send_tx(&mut server)
+            .with_status(400)
+            .with_body(r#"{"code":21120,"message":"insufficient collateral"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let status = tx_status(&mut server, 200, r#"{"code":200,"status":2}"#)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = test_client(&server.url());
+        let outcome = client
+            .withdraw_confirmed(Usdc::ONE, opts_expiring_in(60_000), Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(outcome.is_safe_to_retry(), "{:?}", outcome);
+        submit.assert_async().await;
+        status.assert_async().await;
+    }
+}
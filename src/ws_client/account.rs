@@ -0,0 +1,858 @@
+//! Typed `account_all` snapshots and the events derived from them
+//!
+//! The `account_all` channel sends a full snapshot on subscribe and partial
+//! updates afterwards. [`AccountSnapshot::apply`] merges each message into
+//! the stored snapshot and reports what changed as [`AccountEvent`]s, so
+//! callers can tell a fill from a repeated snapshot.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::errors::Result;
+use crate::serde_util::{option_string_or_number_decimal, reject_extras, string_or_number_decimal};
+use crate::types::{Side, Usdc};
+
+/// Position entry of an account message
+///
+/// `size` is signed: positive for long, negative for short, zero for flat.
+/// Both wire forms are read: an unsigned `position` with a `sign` (1 long,
+/// -1 short) or a `side` (`long`/`short`, `buy`/`sell`), and a signed
+/// `position` on its own. A sign or side contradicting a signed position is
+/// rejected. Positions serialize back to the unsigned form with `sign`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "WirePosition", into = "WirePosition")]
+pub struct AccountPosition {
+    pub market_id: u32,
+    /// Signed size in base units, positive for long
+    pub size: Decimal,
+    pub avg_entry_price: Option<Decimal>,
+    /// Keys not read into the fields above
+    pub extras: Map<String, Value>,
+}
+
+impl AccountPosition {
+    /// Position size with the sign applied, positive for long
+    #[deprecated(note = "use the signed `size` field")]
+    pub fn signed_size(&self) -> Decimal {
+        self.size
+    }
+
+    /// [`Side::Buy`] for a long, [`Side::Sell`] for a short, `None` if flat
+    pub fn side(&self) -> Option<Side> {
+        if self.size > Decimal::ZERO {
+            Some(Side::Buy)
+        } else if self.size < Decimal::ZERO {
+            Some(Side::Sell)
+        } else {
+            None
+        }
+    }
+
+    /// Unsigned position size
+    pub fn abs_size(&self) -> Decimal {
+        self.size.abs()
+    }
+}
+
+/// Position as sent by the API, in either sign convention
+#[derive(Serialize, Deserialize)]
+struct WirePosition {
+    market_id: u32,
+    #[serde(default)]
+    sign: Option<i32>,
+    #[serde(default, skip_serializing)]
+    side: Option<String>,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    position: Decimal,
+    #[serde(default, deserialize_with = "option_string_or_number_decimal")]
+    avg_entry_price: Option<Decimal>,
+    #[serde(flatten)]
+    extras: Map<String, Value>,
+}
+
+impl TryFrom<WirePosition> for AccountPosition {
+    type Error = String;
+
+    fn try_from(wire: WirePosition) -> std::result::Result<Self, String> {
+        let from_side = match wire.side.as_deref().map(str::to_ascii_lowercase).as_deref() {
+            None | Some("") => None,
+            Some("long" | "buy" | "bid") => Some(1),
+            Some("short" | "sell" | "ask") => Some(-1),
+            Some(other) => return Err(format!("unknown position side {:?}", other)),
+        };
+        let from_sign = wire.sign.filter(|sign| *sign != 0).map(i32::signum);
+        let direction = match (from_sign, from_side) {
+            (Some(sign), Some(side)) if sign != side => {
+                return Err(format!(
+                    "position sign {:?} contradicts side {:?}",
+                    wire.sign, wire.side
+                ))
+            }
+            (sign, side) => sign.or(side),
+        };
+        let size = match direction {
+            None => wire.position,
+            Some(_) if wire.position.is_zero() => Decimal::ZERO,
+            Some(1) if wire.position < Decimal::ZERO => {
+                return Err(format!(
+                    "negative position {} of market {} marked long",
+                    wire.position, wire.market_id
+                ))
+            }
+            Some(1) => wire.position,
+            Some(_) => -wire.position.abs(),
+        };
+        Ok(Self {
+            market_id: wire.market_id,
+            size,
+            avg_entry_price: wire.avg_entry_price,
+            extras: wire.extras,
+        })
+    }
+}
+
+impl From<AccountPosition> for WirePosition {
+    fn from(position: AccountPosition) -> Self {
+        Self {
+            market_id: position.market_id,
+            sign: Some(match position.side() {
+                Some(Side::Buy) => 1,
+                Some(Side::Sell) => -1,
+                None => 0,
+            }),
+            side: None,
+            position: position.size.abs(),
+            avg_entry_price: position.avg_entry_price,
+            extras: position.extras,
+        }
+    }
+}
+
+/// Open order entry of an account message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountOrder {
+    pub order_index: i64,
+    #[serde(default)]
+    pub client_order_index: i64,
+    pub market_index: u32,
+    pub is_ask: bool,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub price: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub remaining_base_amount: Decimal,
+    /// Server status such as `open`, `filled` or `canceled-post-only`
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Keys not read into the fields above
+    #[serde(flatten)]
+    pub extras: Map<String, Value>,
+}
+
+impl AccountOrder {
+    /// Whether the status says the order is no longer on the book
+    pub fn is_terminal(&self) -> bool {
+        !matches!(
+            self.status.as_deref(),
+            None | Some("open") | Some("pending") | Some("in-progress")
+        )
+    }
+
+    /// Whether the exchange refused the order, e.g. `canceled-post-only`
+    pub fn is_rejected(&self) -> bool {
+        self.status
+            .as_deref()
+            .is_some_and(|s| s.starts_with("canceled-"))
+    }
+}
+
+/// Trade entry of an account message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountTrade {
+    pub trade_id: i64,
+    pub market_id: u32,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub size: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    pub price: Decimal,
+    pub ask_id: i64,
+    pub bid_id: i64,
+    pub ask_account_id: i64,
+    pub bid_account_id: i64,
+    /// Keys not read into the fields above
+    #[serde(flatten)]
+    pub extras: Map<String, Value>,
+}
+
+/// Typed view of an `account_all` message
+///
+/// Maps are keyed by market id as sent by the server.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    #[serde(default, deserialize_with = "option_string_or_number_decimal")]
+    pub collateral: Option<Decimal>,
+    #[serde(default)]
+    pub positions: HashMap<String, AccountPosition>,
+    #[serde(default)]
+    pub orders: HashMap<String, Vec<AccountOrder>>,
+    #[serde(default)]
+    pub trades: HashMap<String, Vec<AccountTrade>>,
+    /// Keys not read into the fields above
+    #[serde(flatten)]
+    pub extras: Map<String, Value>,
+}
+
+/// Limits on the account state kept by a [`WsClient`](super::WsClient)
+///
+/// Events are derived from the kept state, so trimmed orders or trades that
+/// a later update repeats are reported again as placed orders or fills.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountRetention {
+    /// Drop a market's order list once it holds more orders than this
+    pub max_orders_per_market: Option<usize>,
+    /// Keep only this many of the newest trades per market
+    pub max_trades_per_market: Option<usize>,
+    /// Keep positions, orders and trades of these markets only
+    pub markets: Option<Vec<u32>>,
+}
+
+/// Where an account message passed to the account callback came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountUpdateSource {
+    /// REST snapshot fetched before the stream's own snapshot arrived
+    Bootstrap,
+    /// `subscribed/account_all` from the stream
+    Snapshot,
+    /// `update/account_all` from the stream
+    Update,
+}
+
+impl AccountUpdateSource {
+    /// Source of a raw account message, from its `type` field
+    pub fn of(message: &Value) -> Option<Self> {
+        match message.get("type")?.as_str()? {
+            "bootstrap/account_all" => Some(Self::Bootstrap),
+            "subscribed/account_all" => Some(Self::Snapshot),
+            "update/account_all" => Some(Self::Update),
+            _ => None,
+        }
+    }
+}
+
+/// Change to an account derived from `account_all` messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AccountEvent {
+    Fill {
+        market: u32,
+        price: Decimal,
+        size: Decimal,
+        side: Side,
+        order_index: i64,
+    },
+    OrderPlaced {
+        market: u32,
+        order_index: i64,
+        client_order_index: i64,
+    },
+    /// An order left the book after being completely filled
+    OrderFilled {
+        market: u32,
+        order_index: i64,
+    },
+    OrderCancelled {
+        market: u32,
+        order_index: i64,
+    },
+    /// The exchange refused a new order, `reason` is the server status
+    OrderRejected {
+        market: u32,
+        order_index: i64,
+        client_order_index: i64,
+        reason: String,
+    },
+    BalanceChanged {
+        delta: Decimal,
+    },
+    PositionChanged {
+        market: u32,
+        old: Decimal,
+        new: Decimal,
+    },
+}
+
+/// What a typed account handler receives, see [`WsClient::on_account`](super::WsClient::on_account)
+#[derive(Debug, Clone)]
+pub enum AccountUpdate {
+    /// An event derived from the latest message
+    Event(AccountEvent),
+    /// The account's state after the latest message, shared and not copied
+    Snapshot(Arc<AccountSnapshot>),
+}
+
+impl AccountSnapshot {
+    /// Collateral as a [`Usdc`] amount, failing if it has more than six
+    /// decimal places
+    pub fn collateral_usdc(&self) -> Result<Option<Usdc>> {
+        self.collateral.map(Usdc::from_decimal).transpose()
+    }
+
+    /// Fail with [`LighterError::InvalidResponse`](crate::LighterError::InvalidResponse)
+    /// if this or any entry in it kept unknown keys
+    pub(crate) fn check_strict(&self) -> Result<()> {
+        reject_extras("AccountSnapshot", &self.extras)?;
+        for position in self.positions.values() {
+            reject_extras("AccountPosition", &position.extras)?;
+        }
+        for order in self.orders.values().flatten() {
+            reject_extras("AccountOrder", &order.extras)?;
+        }
+        for trade in self.trades.values().flatten() {
+            reject_extras("AccountTrade", &trade.extras)?;
+        }
+        Ok(())
+    }
+
+    /// Signed notional of each open position, negative for shorts
+    ///
+    /// Positions are valued at `marks`, or at their average entry price for
+    /// markets without a mark; a position with neither is left out.
+    pub fn exposures(&self, marks: &HashMap<u32, Decimal>) -> HashMap<u32, Decimal> {
+        self.positions
+            .values()
+            .filter(|position| !position.size.is_zero())
+            .filter_map(|position| {
+                let price = marks
+                    .get(&position.market_id)
+                    .copied()
+                    .or(position.avg_entry_price)?;
+                Some((position.market_id, position.size * price))
+            })
+            .collect()
+    }
+
+    /// Merge a message into this snapshot and return the resulting events
+    ///
+    /// With `is_snapshot` the message replaces the stored state and its
+    /// trades are treated as history, not fills; the first snapshot produces
+    /// no events. Otherwise it is a partial
+    /// update: present positions and per-market order lists replace the
+    /// stored ones, and trades not seen before are reported as fills.
+    /// Orders whose status is terminal are reported and dropped from the
+    /// stored order lists.
+    pub fn apply(
+        &mut self,
+        account_index: i64,
+        mut message: AccountSnapshot,
+        is_snapshot: bool,
+    ) -> Vec<AccountEvent> {
+        let mut finished: HashMap<i64, AccountOrder> = HashMap::new();
+        for orders in message.orders.values_mut() {
+            orders.retain(|order| {
+                if order.is_terminal() {
+                    finished.insert(order.order_index, order.clone());
+                }
+                !order.is_terminal()
+            });
+        }
+
+        // The first snapshot only establishes the baseline
+        if is_snapshot && *self == Self::default() {
+            *self = message;
+            return Vec::new();
+        }
+
+        let mut events = Vec::new();
+        let mut filled_orders = HashSet::new();
+
+        if !is_snapshot {
+            for (market, trades) in &message.trades {
+                let known: HashSet<i64> = self
+                    .trades
+                    .get(market)
+                    .map(|t| t.iter().map(|trade| trade.trade_id).collect())
+                    .unwrap_or_default();
+
+                for trade in trades.iter().filter(|t| !known.contains(&t.trade_id)) {
+                    let (side, order_index) = if trade.bid_account_id == account_index {
+                        (Side::Buy, trade.bid_id)
+                    } else {
+                        (Side::Sell, trade.ask_id)
+                    };
+                    filled_orders.insert(order_index);
+                    events.push(AccountEvent::Fill {
+                        market: trade.market_id,
+                        price: trade.price,
+                        size: trade.size,
+                        side,
+                        order_index,
+                    });
+                }
+            }
+        }
+
+        if let (Some(old), Some(new)) = (self.collateral, message.collateral) {
+            if old != new {
+                events.push(AccountEvent::BalanceChanged { delta: new - old });
+            }
+        }
+
+        for (market, position) in &message.positions {
+            let old = self
+                .positions
+                .get(market)
+                .map(|position| position.size)
+                .unwrap_or_default();
+            let new = position.size;
+            if old != new {
+                events.push(AccountEvent::PositionChanged {
+                    market: position.market_id,
+                    old,
+                    new,
+                });
+            }
+        }
+
+        let known_orders: HashSet<i64> = self
+            .orders
+            .values()
+            .flatten()
+            .map(|o| o.order_index)
+            .collect();
+        let mut finished_new: Vec<&AccountOrder> = finished
+            .values()
+            .filter(|o| !known_orders.contains(&o.order_index))
+            .collect();
+        finished_new.sort_by_key(|o| o.order_index);
+        for order in finished_new {
+            if order.is_rejected() {
+                events.push(AccountEvent::OrderRejected {
+                    market: order.market_index,
+                    order_index: order.order_index,
+                    client_order_index: order.client_order_index,
+                    reason: order.status.clone().unwrap_or_default(),
+                });
+                continue;
+            }
+            // Orders that finished before an update showed them open
+            events.push(AccountEvent::OrderPlaced {
+                market: order.market_index,
+                order_index: order.order_index,
+                client_order_index: order.client_order_index,
+            });
+            if order.status.as_deref() == Some("filled") {
+                filled_orders.insert(order.order_index);
+                events.push(AccountEvent::OrderFilled {
+                    market: order.market_index,
+                    order_index: order.order_index,
+                });
+            } else {
+                events.push(AccountEvent::OrderCancelled {
+                    market: order.market_index,
+                    order_index: order.order_index,
+                });
+            }
+        }
+
+        for (market, orders) in &message.orders {
+            let old = self.orders.get(market).map(Vec::as_slice).unwrap_or(&[]);
+            for order in orders {
+                if !old.iter().any(|o| o.order_index == order.order_index) {
+                    events.push(AccountEvent::OrderPlaced {
+                        market: order.market_index,
+                        order_index: order.order_index,
+                        client_order_index: order.client_order_index,
+                    });
+                }
+            }
+            for order in old {
+                if orders.iter().any(|o| o.order_index == order.order_index) {
+                    continue;
+                }
+                let filled_status = finished
+                    .get(&order.order_index)
+                    .is_some_and(|o| o.status.as_deref() == Some("filled"));
+                if filled_status || filled_orders.contains(&order.order_index) {
+                    events.push(AccountEvent::OrderFilled {
+                        market: order.market_index,
+                        order_index: order.order_index,
+                    });
+                } else {
+                    events.push(AccountEvent::OrderCancelled {
+                        market: order.market_index,
+                        order_index: order.order_index,
+                    });
+                }
+            }
+        }
+
+        if is_snapshot {
+            *self = message;
+        } else {
+            if message.collateral.is_some() {
+                self.collateral = message.collateral;
+            }
+            self.positions.extend(message.positions);
+            self.orders.extend(message.orders);
+            self.trades.extend(message.trades);
+            self.extras.extend(message.extras);
+        }
+
+        events
+    }
+
+    /// Trim this snapshot to what `retention` keeps
+    pub fn retain(&mut self, retention: &AccountRetention) {
+        if let Some(markets) = &retention.markets {
+            let kept = |market: &String| {
+                market
+                    .parse::<u32>()
+                    .is_ok_and(|market| markets.contains(&market))
+            };
+            self.positions.retain(|market, _| kept(market));
+            self.orders.retain(|market, _| kept(market));
+            self.trades.retain(|market, _| kept(market));
+        }
+        if let Some(max) = retention.max_orders_per_market {
+            self.orders.retain(|_, orders| orders.len() <= max);
+        }
+        if let Some(max) = retention.max_trades_per_market {
+            for trades in self.trades.values_mut() {
+                if trades.len() > max {
+                    trades.sort_by_key(|trade| trade.trade_id);
+                    trades.drain(..trades.len() - max);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(value: serde_json::Value) -> AccountSnapshot {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn snapshot_fixture() -> AccountSnapshot {
+        parse(json!({
+            "type": "subscribed/account_all",
+            "channel": "account_all:7",
+            "collateral": "1000.00",
+            "positions": {
+                "0": {"market_id": 0, "sign": 1, "position": "0.0000", "avg_entry_price": "0"}
+            },
+            "orders": {
+                "0": [
+                    {"order_index": 1700, "client_order_index": 5, "market_index": 0,
+                     "is_ask": false, "price": "3000.00", "remaining_base_amount": "0.5000"}
+                ]
+            },
+            "trades": {
+                "0": [
+                    {"trade_id": 1, "market_id": 0, "size": "0.1000", "price": "2990.00",
+                     "ask_id": 1, "bid_id": 2, "ask_account_id": 9, "bid_account_id": 7}
+                ]
+            }
+        }))
+    }
+
+    #[test]
+    fn test_snapshot_then_fill_update() {
+        let mut state = AccountSnapshot::default();
+        let events = state.apply(7, snapshot_fixture(), true);
+        assert!(events.is_empty());
+
+        let update = parse(json!({
+            "type": "update/account_all",
+            "channel": "account_all:7",
+            "collateral": "998.50",
+            "positions": {
+                "0": {"market_id": 0, "sign": 1, "position": "0.5000"}
+            },
+            "orders": {"0": []},
+            "trades": {
+                "0": [
+                    {"trade_id": 2, "market_id": 0, "size": "0.5000", "price": "3000.00",
+                     "ask_id": 1800, "bid_id": 1700,
+                     "ask_account_id": 9, "bid_account_id": 7}
+                ]
+            }
+        }));
+        let events = state.apply(7, update, false);
+
+        let fills: Vec<_> = events
+            .iter()
+            .filter(|e| matches!(e, AccountEvent::Fill { .. }))
+            .collect();
+        assert_eq!(
+            fills,
+            vec![&AccountEvent::Fill {
+                market: 0,
+                price: Decimal::new(300000, 2),
+                size: Decimal::new(5000, 4),
+                side: Side::Buy,
+                order_index: 1700,
+            }]
+        );
+        assert!(events.contains(&AccountEvent::PositionChanged {
+            market: 0,
+            old: Decimal::ZERO,
+            new: Decimal::new(5000, 4),
+        }));
+        assert!(events.contains(&AccountEvent::BalanceChanged {
+            delta: Decimal::new(-150, 2),
+        }));
+        // The filled order disappearing is not a cancel
+        assert!(!events
+            .iter()
+            .any(|e| matches!(e, AccountEvent::OrderCancelled { .. })));
+        assert!(events.contains(&AccountEvent::OrderFilled {
+            market: 0,
+            order_index: 1700,
+        }));
+    }
+
+    #[test]
+    fn test_repeated_snapshot_has_no_events() {
+        let mut state = AccountSnapshot::default();
+        state.apply(7, snapshot_fixture(), true);
+        assert!(state.apply(7, snapshot_fixture(), true).is_empty());
+        // Replaying already-seen trades as an update doesn't produce fills
+        assert!(state.apply(7, snapshot_fixture(), false).is_empty());
+    }
+
+    #[test]
+    fn test_order_placed_and_cancelled() {
+        let mut state = AccountSnapshot::default();
+        state.apply(7, snapshot_fixture(), true);
+
+        let update = parse(json!({
+            "orders": {
+                "0": [
+                    {"order_index": 1900, "market_index": 0, "is_ask": true,
+                     "price": "3100.00", "remaining_base_amount": "1.0000"}
+                ]
+            }
+        }));
+        let events = state.apply(7, update, false);
+
+        assert_eq!(
+            events,
+            vec![
+                AccountEvent::OrderPlaced {
+                    market: 0,
+                    order_index: 1900,
+                    client_order_index: 0,
+                },
+                AccountEvent::OrderCancelled {
+                    market: 0,
+                    order_index: 1700
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_terminal_order_statuses() {
+        let mut state = AccountSnapshot::default();
+        state.apply(7, snapshot_fixture(), true);
+
+        let update = parse(json!({
+            "orders": {
+                "0": [
+                    {"order_index": 1700, "client_order_index": 5, "market_index": 0,
+                     "is_ask": false, "price": "3000.00", "remaining_base_amount": "0",
+                     "status": "filled"},
+                    {"order_index": 1900, "client_order_index": 6, "market_index": 0,
+                     "is_ask": true, "price": "2900.00", "remaining_base_amount": "1.0000",
+                     "status": "canceled-post-only"}
+                ]
+            }
+        }));
+        let events = state.apply(7, update, false);
+
+        assert_eq!(
+            events,
+            vec![
+                AccountEvent::OrderRejected {
+                    market: 0,
+                    order_index: 1900,
+                    client_order_index: 6,
+                    reason: "canceled-post-only".to_string(),
+                },
+                AccountEvent::OrderFilled {
+                    market: 0,
+                    order_index: 1700
+                },
+            ]
+        );
+        assert!(state.orders["0"].is_empty());
+    }
+
+    #[test]
+    fn test_numeric_and_string_amounts() {
+        let snapshot = parse(json!({
+            "collateral": 1000.5,
+            "positions": {
+                "0": {"market_id": 0, "sign": 1, "position": 2, "avg_entry_price": null}
+            },
+            "orders": {
+                "0": [
+                    {"order_index": 1700, "market_index": 0, "is_ask": false,
+                     "price": 3000, "remaining_base_amount": "5e-1"}
+                ]
+            }
+        }));
+        assert_eq!(snapshot.collateral, Some(Decimal::new(10005, 1)));
+        assert_eq!(
+            snapshot.collateral_usdc().unwrap(),
+            Some(Usdc::from_units(1_000_500_000))
+        );
+        assert_eq!(snapshot.positions["0"].size, Decimal::from(2));
+        assert_eq!(snapshot.positions["0"].avg_entry_price, None);
+        assert_eq!(
+            snapshot.orders["0"][0].remaining_base_amount,
+            Decimal::new(5, 1)
+        );
+    }
+
+    #[test]
+    fn test_unknown_keys_kept_in_extras() {
+        let snapshot = parse(json!({
+            "collateral": "10",
+            "account_type": 1,
+            "positions": {
+                "0": {"market_id": 0, "sign": 1, "position": "1", "unrealized_pnl": "2.5"}
+            },
+            "orders": {
+                "0": [
+                    {"order_index": 1, "market_index": 0, "is_ask": false, "price": "3000",
+                     "remaining_base_amount": "1", "trigger_price": "0"}
+                ]
+            }
+        }));
+        assert_eq!(snapshot.extras["account_type"], json!(1));
+        assert_eq!(
+            snapshot.positions["0"].extras["unrealized_pnl"],
+            json!("2.5")
+        );
+        assert_eq!(snapshot.orders["0"][0].extras["trigger_price"], json!("0"));
+
+        let round_trip = parse(serde_json::to_value(&snapshot).unwrap());
+        assert_eq!(round_trip, snapshot);
+
+        let err = snapshot.check_strict().unwrap_err().to_string();
+        assert!(
+            err.contains("[\"account_type\"] in AccountSnapshot"),
+            "{}",
+            err
+        );
+        let mut known = snapshot.clone();
+        known.extras.clear();
+        let err = known.check_strict().unwrap_err().to_string();
+        assert!(
+            err.contains("[\"unrealized_pnl\"] in AccountPosition"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_position_wire_forms_parse_alike() {
+        // A long, a short and a flat market, with the sign in its own field
+        let sign_field = json!({"collateral": "500", "positions": {
+            "0": {"market_id": 0, "sign": 1, "position": "1.2500", "avg_entry_price": "3318.42"},
+            "1": {"market_id": 1, "sign": -1, "position": "2.5", "avg_entry_price": "60000"},
+            "2": {"market_id": 2, "sign": 0, "position": "0"}
+        }});
+        // The same account with signed sizes, or a side for the short
+        let signed = json!({"collateral": "500", "positions": {
+            "0": {"market_id": 0, "position": "1.2500", "avg_entry_price": "3318.42"},
+            "1": {"market_id": 1, "side": "short", "position": 2.5, "avg_entry_price": "60000"},
+            "2": {"market_id": 2, "position": "-0"}
+        }});
+        let signed_only = json!({"positions": {
+            "1": {"market_id": 1, "position": "-2.5", "avg_entry_price": "60000"}
+        }});
+
+        let a: AccountSnapshot = serde_json::from_value(sign_field.clone()).unwrap();
+        let b: AccountSnapshot = serde_json::from_value(signed).unwrap();
+        let c: AccountSnapshot = serde_json::from_value(signed_only).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.positions["1"], c.positions["1"]);
+
+        let short = &a.positions["1"];
+        assert_eq!(short.size, Decimal::new(-25, 1));
+        assert_eq!(short.side(), Some(Side::Sell));
+        assert_eq!(short.abs_size(), Decimal::new(25, 1));
+        assert_eq!(a.positions["0"].side(), Some(Side::Buy));
+        assert_eq!(a.positions["2"].side(), None);
+
+        // Signed exposure per market, not a sum of unsigned sizes
+        let marks = HashMap::from([(1, Decimal::from(61_000))]);
+        let exposures = a.exposures(&marks);
+        assert_eq!(exposures[&0], Decimal::new(41480250, 4));
+        assert_eq!(exposures[&1], Decimal::from(-152_500));
+        assert!(!exposures.contains_key(&2));
+
+        // Serialized back in the sign-field form
+        let round_trip = serde_json::to_value(&a).unwrap();
+        assert_eq!(round_trip["positions"]["1"]["sign"], -1);
+        assert_eq!(round_trip["positions"]["1"]["position"], "2.5");
+        assert_eq!(
+            serde_json::from_value::<AccountSnapshot>(round_trip).unwrap(),
+            a
+        );
+    }
+
+    #[test]
+    fn test_contradicting_position_signs_are_rejected() {
+        for position in [
+            json!({"market_id": 1, "sign": 1, "position": "-2.5"}),
+            json!({"market_id": 1, "sign": -1, "side": "long", "position": "2.5"}),
+            json!({"market_id": 1, "side": "sideways", "position": "2.5"}),
+        ] {
+            assert!(serde_json::from_value::<AccountPosition>(position).is_err());
+        }
+    }
+
+    #[test]
+    fn test_retention_trims_markets_orders_and_trades() {
+        let mut state = snapshot_fixture();
+        state.trades.get_mut("0").unwrap().push(parse_trade(2));
+        state.orders.insert("1".to_string(), Vec::new());
+
+        let mut trimmed = state.clone();
+        trimmed.retain(&AccountRetention::default());
+        assert_eq!(trimmed, state);
+
+        trimmed.retain(&AccountRetention {
+            max_trades_per_market: Some(1),
+            markets: Some(vec![0]),
+            ..Default::default()
+        });
+        assert_eq!(trimmed.orders.len(), 1);
+        assert_eq!(trimmed.trades["0"].len(), 1);
+        assert_eq!(trimmed.trades["0"][0].trade_id, 2);
+        assert_eq!(trimmed.positions.len(), 1);
+
+        trimmed.retain(&AccountRetention {
+            max_orders_per_market: Some(0),
+            ..Default::default()
+        });
+        assert!(trimmed.orders.is_empty());
+        assert_eq!(trimmed.collateral, state.collateral);
+    }
+
+    fn parse_trade(trade_id: i64) -> AccountTrade {
+        serde_json::from_value(json!({
+            "trade_id": trade_id, "market_id": 0, "size": "0.1000", "price": "2991.00",
+            "ask_id": 3, "bid_id": 4, "ask_account_id": 9, "bid_account_id": 7
+        }))
+        .unwrap()
+    }
+}
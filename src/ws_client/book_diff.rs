@@ -0,0 +1,247 @@
+//! Comparing a locally maintained order book with a reference snapshot
+//!
+//! The reference is usually a REST snapshot, which may be truncated, so only
+//! local levels within the reference's price range are compared. Levels are
+//! matched by numeric price, so `"3000.0"` and `"3000.00"` are the same level.
+
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use super::{OrderBook, PriceLevel};
+use crate::types::Side;
+
+/// Level whose size differs between the two books by more than the tolerance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub side: Side,
+    pub price: Decimal,
+    pub local: Decimal,
+    pub reference: Decimal,
+}
+
+/// Differences between a local book and a reference snapshot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BookDiff {
+    /// Levels in the reference but not in the local book
+    pub missing_locally: Vec<(Side, Decimal)>,
+    /// Levels in the local book but not in the reference
+    pub extra_locally: Vec<(Side, Decimal)>,
+    pub size_mismatches: Vec<SizeMismatch>,
+    pub local_offset: Option<i64>,
+    pub reference_offset: Option<i64>,
+}
+
+impl BookDiff {
+    /// Compare `local` against `reference`
+    ///
+    /// Sizes within `size_tolerance` of each other count as equal.
+    pub fn between(local: &OrderBook, reference: &OrderBook, size_tolerance: Decimal) -> Self {
+        let mut diff = BookDiff::default();
+        diff.compare_side(Side::Buy, &local.bids, &reference.bids, size_tolerance);
+        diff.compare_side(Side::Sell, &local.asks, &reference.asks, size_tolerance);
+        diff
+    }
+
+    /// Record the offsets both books were built at
+    pub fn with_offsets(mut self, local: Option<i64>, reference: Option<i64>) -> Self {
+        self.local_offset = local;
+        self.reference_offset = reference;
+        self
+    }
+
+    /// `local_offset - reference_offset`, when both are known
+    pub fn offset_delta(&self) -> Option<i64> {
+        Some(self.local_offset? - self.reference_offset?)
+    }
+
+    /// Number of levels that differ
+    pub fn mismatched_levels(&self) -> usize {
+        self.missing_locally.len() + self.extra_locally.len() + self.size_mismatches.len()
+    }
+
+    /// Whether the books differ at all
+    pub fn is_empty(&self) -> bool {
+        self.mismatched_levels() == 0
+    }
+
+    /// Whether the differences point at a broken local book
+    ///
+    /// A local book that is behind the reference may simply not have seen
+    /// the latest updates yet, so it's only flagged when it is at or ahead of
+    /// the reference's offset (or offsets are unknown) and still differs.
+    pub fn is_inconsistent(&self) -> bool {
+        !self.is_empty() && self.offset_delta().is_none_or(|delta| delta >= 0)
+    }
+
+    fn compare_side(
+        &mut self,
+        side: Side,
+        local: &[PriceLevel],
+        reference: &[PriceLevel],
+        size_tolerance: Decimal,
+    ) {
+        let local = levels_by_price(local);
+        let reference = levels_by_price(reference);
+        // Levels beyond the reference's depth can't be checked
+        let in_range = |price: &Decimal| match side {
+            Side::Buy => reference.keys().next().is_some_and(|worst| price >= worst),
+            Side::Sell => reference
+                .keys()
+                .next_back()
+                .is_some_and(|worst| price <= worst),
+        };
+
+        for (price, reference_size) in &reference {
+            match local.get(price) {
+                None => self.missing_locally.push((side, *price)),
+                Some(local_size) if (local_size - reference_size).abs() > size_tolerance => {
+                    self.size_mismatches.push(SizeMismatch {
+                        side,
+                        price: *price,
+                        local: *local_size,
+                        reference: *reference_size,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        self.extra_locally.extend(
+            local
+                .keys()
+                .filter(|price| in_range(price) && !reference.contains_key(price))
+                .map(|price| (side, *price)),
+        );
+    }
+}
+
+/// Non-empty levels keyed by numeric price; unparsable levels are skipped
+fn levels_by_price(levels: &[PriceLevel]) -> BTreeMap<Decimal, Decimal> {
+    levels
+        .iter()
+        .filter_map(|level| {
+            let price = Decimal::from_str(&level.price).ok()?;
+            let size = Decimal::from_str(&level.size).ok()?;
+            (size > Decimal::ZERO).then_some((price, size))
+        })
+        .collect()
+}
+
+/// When [`WsClient::verify_against_rest`](super::WsClient::verify_against_rest)
+/// reports a book as broken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyPolicy {
+    /// Size differences up to this are ignored
+    pub size_tolerance: Decimal,
+    /// Inconsistent books with more mismatched levels than this exceed the policy
+    pub max_mismatched_levels: usize,
+    /// Request a resync when the policy is exceeded
+    pub auto_resync: bool,
+}
+
+impl Default for VerifyPolicy {
+    fn default() -> Self {
+        Self {
+            size_tolerance: Decimal::ZERO,
+            max_mismatched_levels: 0,
+            auto_resync: false,
+        }
+    }
+}
+
+impl VerifyPolicy {
+    /// Whether `diff` exceeds the policy
+    pub fn is_exceeded_by(&self, diff: &BookDiff) -> bool {
+        diff.is_inconsistent() && diff.mismatched_levels() > self.max_mismatched_levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|(price, size)| PriceLevel {
+                    price: price.to_string(),
+                    size: size.to_string(),
+                })
+                .collect()
+        };
+        OrderBook {
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_identical_books_with_formatting_differences() {
+        let local = book(&[("99.0", "1")], &[("101.00", "2.0")]);
+        let reference = book(&[("99", "1.00")], &[("101", "2")]);
+        let diff = BookDiff::between(&local, &reference, Decimal::ZERO);
+        assert!(diff.is_empty());
+        assert!(!diff.is_inconsistent());
+    }
+
+    #[test]
+    fn test_deliberate_discrepancies() {
+        let local = book(
+            &[("100", "1"), ("99", "5"), ("98", "1"), ("90", "1")],
+            &[("101", "2"), ("101.5", "1"), ("103", "1")],
+        );
+        let reference = book(
+            &[("100", "1.05"), ("99", "4"), ("97", "1")],
+            &[("101", "2"), ("102", "1")],
+        );
+
+        let diff = BookDiff::between(&local, &reference, dec("0.1"));
+        assert_eq!(
+            diff.missing_locally,
+            vec![(Side::Buy, dec("97")), (Side::Sell, dec("102"))]
+        );
+        // 90 and 103 are beyond the reference's depth, so they aren't reported
+        assert_eq!(
+            diff.extra_locally,
+            vec![(Side::Buy, dec("98")), (Side::Sell, dec("101.5"))]
+        );
+        assert_eq!(
+            diff.size_mismatches,
+            vec![SizeMismatch {
+                side: Side::Buy,
+                price: dec("99"),
+                local: dec("5"),
+                reference: dec("4"),
+            }]
+        );
+        assert_eq!(diff.mismatched_levels(), 5);
+    }
+
+    #[test]
+    fn test_offsets_decide_whether_a_diff_is_inconsistent() {
+        let local = book(&[("99", "1")], &[]);
+        let reference = book(&[("99", "2")], &[]);
+        let diff = BookDiff::between(&local, &reference, Decimal::ZERO);
+
+        // Local book behind the snapshot: the update may still be in flight
+        let behind = diff.clone().with_offsets(Some(9), Some(10));
+        assert_eq!(behind.offset_delta(), Some(-1));
+        assert!(!behind.is_inconsistent());
+
+        let ahead = diff.clone().with_offsets(Some(11), Some(10));
+        assert!(ahead.is_inconsistent());
+        assert!(diff.is_inconsistent());
+
+        let policy = VerifyPolicy {
+            max_mismatched_levels: 1,
+            ..Default::default()
+        };
+        assert!(!policy.is_exceeded_by(&ahead));
+        assert!(VerifyPolicy::default().is_exceeded_by(&ahead));
+    }
+}
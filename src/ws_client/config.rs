@@ -0,0 +1,82 @@
+//! Connection settings for [`WsClient`](super::WsClient)
+
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::Connector;
+
+use crate::tcp::TcpOptions;
+
+/// WebSocket connection settings
+///
+/// The defaults raise tungstenite's message and frame limits to 16 MiB so
+/// full-depth order book snapshots fit in a single message.
+#[derive(Clone)]
+pub struct WsConfig {
+    /// Largest message accepted once its fragments are reassembled, `None`
+    /// for unlimited
+    pub max_message_size: Option<usize>,
+    /// Largest single frame accepted, `None` for unlimited
+    pub max_frame_size: Option<usize>,
+    /// Bytes buffered before a write is flushed to the socket
+    pub write_buffer_size: usize,
+    /// Interval between client pings, `None` to disable
+    pub ping_interval: Option<Duration>,
+    /// Maximum time for the TCP, TLS and WebSocket handshakes
+    pub connect_timeout: Duration,
+    /// Maximum wait for the server's `connected` message before `run`
+    /// fails, `None` to wait forever; unused with `eager_subscribe`
+    pub handshake_timeout: Option<Duration>,
+    /// Subscribe as soon as the socket is open instead of after the
+    /// `connected` message, for gateways that never send one
+    pub eager_subscribe: bool,
+    /// TLS connector, `None` for the default native-tls configuration
+    pub tls_connector: Option<Connector>,
+    /// Socket options of the connection
+    pub tcp: TcpOptions,
+}
+
+impl WsConfig {
+    /// Default message and frame size limit (16 MiB)
+    pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+    pub(crate) fn websocket_config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: self.max_message_size,
+            max_frame_size: self.max_frame_size,
+            write_buffer_size: self.write_buffer_size,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for WsConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: Some(Self::DEFAULT_MAX_MESSAGE_SIZE),
+            max_frame_size: Some(Self::DEFAULT_MAX_MESSAGE_SIZE),
+            write_buffer_size: 128 * 1024,
+            ping_interval: Some(Duration::from_secs(30)),
+            connect_timeout: Duration::from_secs(10),
+            handshake_timeout: Some(Duration::from_secs(5)),
+            eager_subscribe: false,
+            tls_connector: None,
+            tcp: TcpOptions::default(),
+        }
+    }
+}
+
+impl std::fmt::Debug for WsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsConfig")
+            .field("max_message_size", &self.max_message_size)
+            .field("max_frame_size", &self.max_frame_size)
+            .field("write_buffer_size", &self.write_buffer_size)
+            .field("ping_interval", &self.ping_interval)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("eager_subscribe", &self.eager_subscribe)
+            .field("tls_connector", &self.tls_connector.is_some())
+            .field("tcp", &self.tcp)
+            .finish()
+    }
+}
@@ -0,0 +1,203 @@
+//! Running registered handlers off the read loop
+//!
+//! With [`CallbackDispatch::Spawn`] or [`CallbackDispatch::Pool`], handler
+//! calls are queued as jobs and run by background tasks, so a slow handler
+//! no longer delays message processing. Jobs are queued by key (one market's
+//! book, one account, or stream events) and every key always goes to the
+//! same queue, so the order within a key is the order messages arrived in.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::errors::{LighterError, Result};
+
+/// Where registered handlers and [`run`](super::WsClient::run) callbacks are called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CallbackDispatch {
+    /// On the read loop, before the next message is read
+    #[default]
+    Inline,
+    /// On one background task per market, account or event stream
+    ///
+    /// Queues are unbounded, so a handler that can't keep up grows memory
+    /// instead of slowing the reader.
+    Spawn,
+    /// On a fixed number of background tasks with bounded queues
+    Pool {
+        workers: usize,
+        /// Calls waiting per worker before `overflow` applies
+        queue_capacity: usize,
+        overflow: QueueOverflow,
+    },
+}
+
+/// What a full [`CallbackDispatch::Pool`] queue does with a new job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueOverflow {
+    /// Make the read loop wait for space, applying backpressure to the stream
+    #[default]
+    Block,
+    /// Drop the oldest queued job and count it in
+    /// [`WsClient::dropped_callbacks`](super::WsClient::dropped_callbacks)
+    DropOldest,
+}
+
+/// One queued call of a key's handlers
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+/// Queue of jobs run in order by one background task
+struct Lane {
+    jobs: Mutex<VecDeque<Job>>,
+    capacity: Option<usize>,
+    overflow: QueueOverflow,
+    ready: Notify,
+    space: Notify,
+    started: AtomicBool,
+    closed: AtomicBool,
+}
+
+impl Lane {
+    fn new(capacity: Option<usize>, overflow: QueueOverflow) -> Arc<Self> {
+        Arc::new(Self {
+            jobs: Mutex::new(VecDeque::new()),
+            capacity,
+            overflow,
+            ready: Notify::new(),
+            space: Notify::new(),
+            started: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Queue a job, starting the lane's task on first use
+    async fn push(self: &Arc<Self>, job: Job, dropped: &AtomicU64) {
+        if !self.started.swap(true, Ordering::AcqRel) {
+            tokio::spawn(self.clone().work());
+        }
+        loop {
+            {
+                let mut jobs = self.jobs.lock().unwrap();
+                let full = self.capacity.is_some_and(|capacity| jobs.len() >= capacity);
+                if !full || self.overflow == QueueOverflow::DropOldest {
+                    if full {
+                        jobs.pop_front();
+                        dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    jobs.push_back(job);
+                    break;
+                }
+            }
+            self.space.notified().await;
+        }
+        self.ready.notify_one();
+    }
+
+    /// Run queued jobs until the lane is closed and drained
+    ///
+    /// Jobs run on the blocking pool, so handlers doing blocking I/O don't
+    /// hold up the runtime the read loop is on.
+    async fn work(self: Arc<Self>) {
+        loop {
+            let job = self.jobs.lock().unwrap().pop_front();
+            match job {
+                Some(job) => {
+                    self.space.notify_one();
+                    // Handler panics are caught inside the job
+                    let _ = tokio::task::spawn_blocking(job).await;
+                }
+                None if self.closed.load(Ordering::Acquire) => return,
+                None => self.ready.notified().await,
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.ready.notify_one();
+    }
+}
+
+/// Queues of a [`WsClient`](super::WsClient) for its dispatch mode
+pub(crate) struct Dispatcher {
+    mode: CallbackDispatch,
+    /// Lanes by key for `Spawn`
+    by_key: Mutex<HashMap<String, Arc<Lane>>>,
+    /// Fixed lanes for `Pool`
+    workers: Vec<Arc<Lane>>,
+    dropped: AtomicU64,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(mode: CallbackDispatch) -> Result<Self> {
+        let workers = match mode {
+            CallbackDispatch::Pool {
+                workers,
+                queue_capacity,
+                overflow,
+            } => {
+                if workers == 0 || queue_capacity == 0 {
+                    return Err(LighterError::InvalidConfiguration(
+                        "Callback pool needs at least one worker and a queue capacity of one"
+                            .to_string(),
+                    ));
+                }
+                (0..workers)
+                    .map(|_| Lane::new(Some(queue_capacity), overflow))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            mode,
+            by_key: Mutex::new(HashMap::new()),
+            workers,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn is_inline(&self) -> bool {
+        self.mode == CallbackDispatch::Inline
+    }
+
+    /// Jobs dropped by [`QueueOverflow::DropOldest`]
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queue a job behind earlier jobs of the same key
+    pub(crate) async fn submit(&self, key: &str, job: Job) {
+        let lane = match self.mode {
+            CallbackDispatch::Inline => {
+                job();
+                return;
+            }
+            CallbackDispatch::Spawn => self
+                .by_key
+                .lock()
+                .unwrap()
+                .entry(key.to_string())
+                .or_insert_with(|| Lane::new(None, QueueOverflow::Block))
+                .clone(),
+            CallbackDispatch::Pool { .. } => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                self.workers[hasher.finish() as usize % self.workers.len()].clone()
+            }
+        };
+        lane.push(job, &self.dropped).await;
+    }
+}
+
+impl Drop for Dispatcher {
+    /// Let the lane tasks finish what is queued and exit
+    fn drop(&mut self) {
+        let by_key = self.by_key.get_mut().unwrap();
+        for lane in by_key.values().chain(&self.workers) {
+            lane.close();
+        }
+    }
+}
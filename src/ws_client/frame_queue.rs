@@ -0,0 +1,315 @@
+//! Reading frames apart from applying them
+//!
+//! Each connection has a reader task that only reads and parses frames and
+//! queues them for the run loop, so applying a burst of updates or waiting
+//! on a handler never stops the socket from being read. The queue is
+//! bounded; [`FrameOverflow`] sets what the reader does when it is full.
+
+use futures_util::{Stream, StreamExt};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::error::Error as WsError;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::RawWsMessage;
+
+/// Frames read and not yet applied, unless configured otherwise
+pub const DEFAULT_FRAME_QUEUE_CAPACITY: usize = 4_096;
+
+/// What the reader does when the frame queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameOverflow {
+    /// Wait for space; the socket isn't read meanwhile
+    #[default]
+    Block,
+    /// Keep reading and drop frames until there is space, then resubscribe
+    /// every channel for fresh snapshots
+    Resync,
+}
+
+/// Fill of the frame queue, see [`WsClient::frame_queue_stats`](super::WsClient::frame_queue_stats)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameQueueStats {
+    pub capacity: usize,
+    /// Frames read and not yet taken by the run loop
+    ///
+    /// Can exceed the capacity by two: a frame the reader waits to queue
+    /// and one the run loop is taking.
+    pub depth: usize,
+    /// Largest depth seen since the client was built
+    pub high_water_mark: usize,
+    /// Frames dropped under [`FrameOverflow::Resync`]
+    pub dropped_frames: u64,
+    /// Resubscriptions after dropped frames
+    pub resyncs: u64,
+}
+
+/// Counters shared by the reader, the run loop and the client
+#[derive(Debug, Default)]
+pub(crate) struct FrameQueueCounters {
+    depth: AtomicUsize,
+    high_water_mark: AtomicUsize,
+    dropped_frames: AtomicU64,
+    resyncs: AtomicU64,
+}
+
+impl FrameQueueCounters {
+    pub(crate) fn stats(&self, capacity: usize) -> FrameQueueStats {
+        FrameQueueStats {
+            capacity,
+            depth: self.depth.load(Ordering::Relaxed),
+            high_water_mark: self.high_water_mark.load(Ordering::Relaxed),
+            dropped_frames: self.dropped_frames.load(Ordering::Relaxed),
+            resyncs: self.resyncs.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn count_resync(&self) {
+        self.resyncs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn enter(&self) {
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.high_water_mark.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    fn leave(&self) {
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A frame handed from the reader to the run loop
+pub(crate) enum Frame {
+    Text {
+        text: String,
+        parsed: serde_json::Result<Value>,
+        received_at: Instant,
+    },
+    /// A frame the run loop can't process, described
+    Skipped(String),
+    /// The stream failed; nothing follows
+    Error(WsError),
+    /// `dropped` frames were dropped before the next one
+    Overflowed { dropped: u64 },
+}
+
+/// Forward a raw frame to the tap without ever blocking the reader
+#[derive(Clone)]
+pub(crate) struct RawTap {
+    pub(crate) tx: mpsc::Sender<RawWsMessage>,
+    pub(crate) dropped: Arc<AtomicU64>,
+}
+
+impl RawTap {
+    fn send(&self, text: &str) {
+        let message = RawWsMessage {
+            received_at: chrono::Utc::now(),
+            text: text.to_string(),
+        };
+        if let Err(mpsc::error::TrySendError::Full(_)) = self.tx.try_send(message) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Receiving end of a connection's frame queue
+///
+/// Dropping it stops the reader.
+pub(crate) struct FrameReceiver {
+    rx: mpsc::Receiver<Frame>,
+    counters: Arc<FrameQueueCounters>,
+    reader: JoinHandle<()>,
+}
+
+impl FrameReceiver {
+    /// Next frame, `None` once the stream ended and every frame was taken
+    pub(crate) async fn recv(&mut self) -> Option<Frame> {
+        let frame = self.rx.recv().await?;
+        self.counters.leave();
+        Some(frame)
+    }
+}
+
+impl Drop for FrameReceiver {
+    fn drop(&mut self) {
+        self.reader.abort();
+        // Frames still queued are never applied
+        self.counters.depth.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Start reading `stream` into a queue of `capacity` frames
+pub(crate) fn spawn_reader<S>(
+    stream: S,
+    capacity: usize,
+    overflow: FrameOverflow,
+    counters: Arc<FrameQueueCounters>,
+    tap: Option<RawTap>,
+) -> FrameReceiver
+where
+    S: Stream<Item = std::result::Result<Message, WsError>> + Unpin + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(capacity);
+    counters.depth.store(0, Ordering::Relaxed);
+    let reader = FrameReader {
+        tx,
+        overflow,
+        counters: counters.clone(),
+        tap,
+    };
+    FrameReceiver {
+        rx,
+        counters,
+        reader: tokio::spawn(reader.run(stream)),
+    }
+}
+
+struct FrameReader {
+    tx: mpsc::Sender<Frame>,
+    overflow: FrameOverflow,
+    counters: Arc<FrameQueueCounters>,
+    tap: Option<RawTap>,
+}
+
+impl FrameReader {
+    async fn run<S>(self, mut stream: S)
+    where
+        S: Stream<Item = std::result::Result<Message, WsError>> + Unpin,
+    {
+        let mut dropped = 0;
+        while let Some(message) = stream.next().await {
+            let frame = match message {
+                Ok(Message::Text(text)) => {
+                    let received_at = Instant::now();
+                    if let Some(tap) = &self.tap {
+                        tap.send(&text);
+                    }
+                    let parsed = serde_json::from_str(&text);
+                    Frame::Text {
+                        text,
+                        parsed,
+                        received_at,
+                    }
+                }
+                Ok(Message::Binary(data)) => {
+                    Frame::Skipped(format!("binary message of {} bytes", data.len()))
+                }
+                Ok(Message::Frame(frame)) => {
+                    Frame::Skipped(format!("raw frame of {} bytes", frame.len()))
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    // Never dropped, whatever the policy
+                    let _ = self.send(Frame::Error(e)).await;
+                    return;
+                }
+            };
+
+            let queued = match self.overflow {
+                FrameOverflow::Block => self.send(frame).await,
+                FrameOverflow::Resync => {
+                    // Only resume when the marker and the frame both fit
+                    if dropped > 0 && self.tx.capacity() >= 2 {
+                        self.try_send(Frame::Overflowed { dropped });
+                        dropped = 0;
+                    }
+                    if dropped == 0 && self.try_send(frame) {
+                        true
+                    } else {
+                        dropped += 1;
+                        self.counters.dropped_frames.fetch_add(1, Ordering::Relaxed);
+                        !self.tx.is_closed()
+                    }
+                }
+            };
+            if !queued {
+                return;
+            }
+        }
+    }
+
+    /// Queue a frame, waiting for space; false once the run loop is gone
+    async fn send(&self, frame: Frame) -> bool {
+        self.counters.enter();
+        if self.tx.send(frame).await.is_err() {
+            self.counters.leave();
+            return false;
+        }
+        true
+    }
+
+    /// Queue a frame if there is space
+    fn try_send(&self, frame: Frame) -> bool {
+        // The only sender, so the space can't be taken in between
+        if self.tx.capacity() == 0 {
+            return false;
+        }
+        self.counters.enter();
+        if self.tx.try_send(frame).is_err() {
+            self.counters.leave();
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(n: usize) -> Message {
+        Message::Text(format!(r#"{{"n":{}}}"#, n))
+    }
+
+    #[tokio::test]
+    async fn test_resync_drops_while_full_then_marks_the_gap() {
+        let (stream_tx, stream_rx) = mpsc::unbounded_channel();
+        let stream = stream_of(stream_rx);
+        let counters = Arc::new(FrameQueueCounters::default());
+        let mut frames = spawn_reader(stream, 2, FrameOverflow::Resync, counters.clone(), None);
+
+        for n in 0..5 {
+            stream_tx.send(Ok(text(n))).unwrap();
+        }
+        // Let the reader fill the queue and drop the rest
+        while counters.stats(2).dropped_frames < 3 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(counters.stats(2).depth, 2);
+        let mut seen = Vec::new();
+        for _ in 0..2 {
+            match frames.recv().await.unwrap() {
+                Frame::Text { parsed, .. } => seen.push(parsed.unwrap()["n"].as_u64().unwrap()),
+                _ => panic!("expected text"),
+            }
+        }
+        assert_eq!(seen, [0, 1]);
+
+        stream_tx.send(Ok(text(5))).unwrap();
+        assert!(matches!(
+            frames.recv().await,
+            Some(Frame::Overflowed { dropped: 3 })
+        ));
+        assert!(matches!(frames.recv().await, Some(Frame::Text { .. })));
+        drop(stream_tx);
+        assert!(frames.recv().await.is_none());
+
+        let stats = counters.stats(2);
+        assert_eq!(stats.depth, 0);
+        assert_eq!(stats.high_water_mark, 2);
+        assert_eq!(stats.dropped_frames, 3);
+    }
+
+    /// A stream of what is sent on `rx`
+    fn stream_of<T: Send + 'static>(
+        rx: mpsc::UnboundedReceiver<T>,
+    ) -> impl Stream<Item = T> + Unpin + Send {
+        Box::pin(futures_util::stream::unfold(rx, |mut rx| async move {
+            rx.recv().await.map(|item| (item, rx))
+        }))
+    }
+}
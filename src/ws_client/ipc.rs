@@ -0,0 +1,314 @@
+//! Compact binary order book encoding for inter-process transport
+//!
+//! [`OrderBook::to_ipc_bytes`] writes a four byte header (`LOB` and a
+//! format version) followed by a postcard payload in which every price and
+//! size is a decimal mantissa and scale instead of a string. Decoding
+//! rebuilds the exact strings, so `"101.50"` stays `"101.50"`.
+//!
+//! Levels must hold plain decimals as sent by the server; anything that
+//! wouldn't survive the trip, such as `"1e5"` or `"+1"`, is refused.
+
+use serde::{Deserialize, Serialize};
+
+use super::{OrderBook, PriceLevel};
+use crate::errors::{LighterError, Result};
+
+/// Leading bytes of every encoded book
+const IPC_MAGIC: &[u8; 3] = b"LOB";
+
+/// Format version written by this build; readers reject any other
+pub const IPC_FORMAT_VERSION: u8 = 1;
+
+/// Largest scale a value may have, as for `rust_decimal::Decimal`
+const MAX_SCALE: u32 = 28;
+
+/// Decimal as mantissa and scale, like `rust_decimal::Decimal`'s raw parts
+///
+/// The mantissa is limited to 64 bits, 18 significant digits at least,
+/// which keeps both directions on fast integer arithmetic.
+#[derive(Serialize, Deserialize)]
+struct IpcDecimal {
+    mantissa: i64,
+    scale: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IpcLevel {
+    price: IpcDecimal,
+    size: IpcDecimal,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IpcBook {
+    asks: Vec<IpcLevel>,
+    bids: Vec<IpcLevel>,
+}
+
+impl OrderBook {
+    /// Encode the book in the versioned binary IPC format
+    pub fn to_ipc_bytes(&self) -> Result<Vec<u8>> {
+        let book = IpcBook {
+            asks: encode_levels(&self.asks)?,
+            bids: encode_levels(&self.bids)?,
+        };
+        let mut bytes = IPC_MAGIC.to_vec();
+        bytes.push(IPC_FORMAT_VERSION);
+        postcard::to_extend(&book, bytes)
+            .map_err(|e| LighterError::Other(format!("Failed to encode order book: {}", e)))
+    }
+
+    /// Decode a book written by [`to_ipc_bytes`](Self::to_ipc_bytes)
+    ///
+    /// Fails with [`LighterError::IpcVersionMismatch`] for data written in
+    /// another format version.
+    pub fn from_ipc_bytes(bytes: &[u8]) -> Result<Self> {
+        let payload = match bytes {
+            [m0, m1, m2, version, payload @ ..] if [*m0, *m1, *m2] == *IPC_MAGIC => {
+                if *version != IPC_FORMAT_VERSION {
+                    return Err(LighterError::IpcVersionMismatch {
+                        expected: IPC_FORMAT_VERSION,
+                        actual: *version,
+                    });
+                }
+                payload
+            }
+            _ => {
+                return Err(LighterError::InvalidResponse(
+                    "Not an IPC order book: missing header".to_string(),
+                ))
+            }
+        };
+        let (book, rest): (IpcBook, _) = postcard::take_from_bytes(payload).map_err(|e| {
+            LighterError::InvalidResponse(format!("Failed to decode order book: {}", e))
+        })?;
+        if !rest.is_empty() {
+            return Err(LighterError::InvalidResponse(format!(
+                "{} trailing bytes after IPC order book",
+                rest.len()
+            )));
+        }
+        Ok(OrderBook {
+            asks: decode_levels(book.asks)?,
+            bids: decode_levels(book.bids)?,
+        })
+    }
+}
+
+fn encode_levels(levels: &[PriceLevel]) -> Result<Vec<IpcLevel>> {
+    levels
+        .iter()
+        .map(|level| {
+            Ok(IpcLevel {
+                price: encode_decimal(&level.price)?,
+                size: encode_decimal(&level.size)?,
+            })
+        })
+        .collect()
+}
+
+fn decode_levels(levels: Vec<IpcLevel>) -> Result<Vec<PriceLevel>> {
+    levels
+        .into_iter()
+        .map(|level| {
+            Ok(PriceLevel {
+                price: decode_decimal(level.price)?,
+                size: decode_decimal(level.size)?,
+            })
+        })
+        .collect()
+}
+
+/// Split a decimal string into raw parts, refusing text that wouldn't round-trip
+///
+/// Accepts `-?(0|[1-9][0-9]*)(\.[0-9]+)?` without negative zero.
+fn encode_decimal(text: &str) -> Result<IpcDecimal> {
+    let invalid = || {
+        LighterError::ValidationError(format!(
+            "Order book value {:?} is not a plain decimal",
+            text
+        ))
+    };
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (int_part, frac_part) = match unsigned.split_once('.') {
+        Some((int_part, frac_part)) if !frac_part.is_empty() => (int_part, frac_part),
+        Some(_) => return Err(invalid()),
+        None => (unsigned, ""),
+    };
+    if int_part.is_empty()
+        || (int_part.len() > 1 && int_part.starts_with('0'))
+        || frac_part.len() > MAX_SCALE as usize
+    {
+        return Err(invalid());
+    }
+
+    let mut mantissa: i64 = 0;
+    for digit in int_part.bytes().chain(frac_part.bytes()) {
+        if !digit.is_ascii_digit() {
+            return Err(invalid());
+        }
+        mantissa = mantissa
+            .checked_mul(10)
+            .and_then(|m| m.checked_add((digit - b'0') as i64))
+            .ok_or_else(invalid)?;
+    }
+    if negative {
+        if mantissa == 0 {
+            return Err(invalid());
+        }
+        mantissa = -mantissa;
+    }
+    Ok(IpcDecimal {
+        mantissa,
+        scale: frac_part.len() as u8,
+    })
+}
+
+/// Format raw parts the way the server writes them
+fn decode_decimal(raw: IpcDecimal) -> Result<String> {
+    let scale = raw.scale as usize;
+    if raw.scale as u32 > MAX_SCALE {
+        return Err(LighterError::InvalidResponse(format!(
+            "Invalid decimal in IPC order book: scale {}",
+            raw.scale
+        )));
+    }
+    // At most 20 digits, written without going through `fmt`
+    let mut buf = [0u8; 20];
+    let mut start = buf.len();
+    let mut rest = raw.mantissa.unsigned_abs();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+        if rest == 0 {
+            break;
+        }
+    }
+    // Only ASCII digits were written
+    let digits = std::str::from_utf8(&buf[start..]).unwrap();
+
+    let mut text = String::with_capacity(digits.len() + scale + 3);
+    if raw.mantissa < 0 {
+        text.push('-');
+    }
+    if digits.len() > scale {
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        text.push_str(int_part);
+        if scale > 0 {
+            text.push('.');
+            text.push_str(frac_part);
+        }
+    } else {
+        text.push_str("0.");
+        text.extend(std::iter::repeat_n('0', scale - digits.len()));
+        text.push_str(digits);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(price: &str, size: &str) -> PriceLevel {
+        PriceLevel {
+            price: price.to_string(),
+            size: size.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_preserves_scale() {
+        let books = [
+            OrderBook {
+                asks: vec![level("101.50", "0.0010"), level("102", "3")],
+                bids: vec![
+                    level("99.999999", "12345678.9"),
+                    level("0.00000001", "1.000"),
+                ],
+            },
+            OrderBook {
+                asks: Vec::new(),
+                bids: Vec::new(),
+            },
+            OrderBook {
+                asks: vec![level("101.0", "1.0")],
+                bids: Vec::new(),
+            },
+            OrderBook {
+                asks: Vec::new(),
+                bids: vec![level("-0.5", "0")],
+            },
+        ];
+        for book in books {
+            let bytes = book.to_ipc_bytes().unwrap();
+            assert_eq!(&bytes[..4], b"LOB\x01");
+            assert_eq!(OrderBook::from_ipc_bytes(&bytes).unwrap(), book);
+        }
+    }
+
+    #[test]
+    fn test_binary_is_smaller_than_json() {
+        let book = OrderBook {
+            asks: (0..100)
+                .map(|i| level(&format!("{}.25", 1000 + i), "1.5000"))
+                .collect(),
+            bids: (0..100)
+                .map(|i| level(&format!("{}.75", 999 - i), "0.0200"))
+                .collect(),
+        };
+        let binary = book.to_ipc_bytes().unwrap();
+        let json = serde_json::to_vec(&book).unwrap();
+        assert!(binary.len() * 3 < json.len());
+    }
+
+    #[test]
+    fn test_rejects_other_versions_and_bad_input() {
+        let book = OrderBook {
+            asks: vec![level("1.0", "2.0")],
+            bids: Vec::new(),
+        };
+        let mut bytes = book.to_ipc_bytes().unwrap();
+        bytes[3] = 2;
+        assert!(matches!(
+            OrderBook::from_ipc_bytes(&bytes),
+            Err(LighterError::IpcVersionMismatch {
+                expected: 1,
+                actual: 2
+            })
+        ));
+
+        assert!(matches!(
+            OrderBook::from_ipc_bytes(b"{}"),
+            Err(LighterError::InvalidResponse(_))
+        ));
+        let mut truncated = book.to_ipc_bytes().unwrap();
+        truncated.pop();
+        assert!(OrderBook::from_ipc_bytes(&truncated).is_err());
+
+        for text in [
+            "1e5",
+            "+1",
+            "abc",
+            "",
+            "01.5",
+            ".5",
+            "5.",
+            "-0",
+            "1.2.3",
+            "12345678901234567890.5",
+        ] {
+            let book = OrderBook {
+                asks: vec![level(text, "1")],
+                bids: Vec::new(),
+            };
+            assert!(matches!(
+                book.to_ipc_bytes(),
+                Err(LighterError::ValidationError(_))
+            ));
+        }
+    }
+}
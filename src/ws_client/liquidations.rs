@@ -0,0 +1,244 @@
+//! Public liquidation and deleverage events
+//!
+//! The `liquidations` channel of a market reports positions closed by the
+//! exchange. Each entry becomes a [`LiquidationEvent`], delivered as
+//! [`WsEvent::Liquidation`](super::WsEvent::Liquidation) and kept in a
+//! per-market buffer read with
+//! [`WsClient::recent_liquidations`](super::WsClient::recent_liquidations).
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+
+use crate::constants::{TX_TYPE_INTERNAL_DELEVERAGE, TX_TYPE_INTERNAL_LIQUIDATE_POSITION};
+use crate::errors::{LighterError, Result};
+use crate::serde_util::string_or_number_decimal;
+
+/// Events kept per market unless configured otherwise
+pub const DEFAULT_LIQUIDATION_BUFFER: usize = 1_000;
+
+/// How a position was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidationKind {
+    /// Closed against the book or the insurance fund
+    Liquidation,
+    /// Closed against an opposing position
+    Deleverage,
+}
+
+impl LiquidationKind {
+    /// Kind of an internal transaction type
+    pub fn from_tx_type(tx_type: u8) -> Option<Self> {
+        match tx_type {
+            TX_TYPE_INTERNAL_LIQUIDATE_POSITION => Some(LiquidationKind::Liquidation),
+            TX_TYPE_INTERNAL_DELEVERAGE => Some(LiquidationKind::Deleverage),
+            _ => None,
+        }
+    }
+}
+
+/// A liquidation or deleverage in a market
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LiquidationEvent {
+    pub market_index: u32,
+    /// `None` when the feed doesn't name the account
+    pub liquidated_account: Option<i64>,
+    pub size: Decimal,
+    pub price: Decimal,
+    /// Server time in milliseconds
+    pub timestamp: i64,
+    pub kind: LiquidationKind,
+}
+
+/// Entry of a `liquidations` message as sent
+#[derive(Deserialize)]
+struct RawLiquidation {
+    #[serde(default)]
+    market_id: Option<u32>,
+    #[serde(default)]
+    account_index: Option<i64>,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    size: Decimal,
+    #[serde(deserialize_with = "string_or_number_decimal")]
+    price: Decimal,
+    #[serde(default)]
+    timestamp: Option<i64>,
+    #[serde(default, rename = "type")]
+    kind: Option<LiquidationKind>,
+    #[serde(default)]
+    tx_type: Option<u8>,
+}
+
+/// Events of a `liquidations` message for `market_id`
+///
+/// Entries without a market, timestamp or kind take the channel's market,
+/// the message's timestamp and [`LiquidationKind::Liquidation`].
+pub(crate) fn parse_message(message: &Value, market_id: u32) -> Result<Vec<LiquidationEvent>> {
+    let Some(entries) = message.get("liquidations") else {
+        return Ok(Vec::new());
+    };
+    let envelope_timestamp = message.get("timestamp").and_then(|t| t.as_i64());
+    Vec::<RawLiquidation>::deserialize(entries)?
+        .into_iter()
+        .map(|raw| {
+            let kind = match (raw.kind, raw.tx_type) {
+                (Some(kind), _) => kind,
+                (None, Some(tx_type)) => {
+                    LiquidationKind::from_tx_type(tx_type).ok_or_else(|| {
+                        LighterError::InvalidResponse(format!(
+                            "Unknown liquidation transaction type {}",
+                            tx_type
+                        ))
+                    })?
+                }
+                (None, None) => LiquidationKind::Liquidation,
+            };
+            Ok(LiquidationEvent {
+                market_index: raw.market_id.unwrap_or(market_id),
+                liquidated_account: raw.account_index,
+                size: raw.size,
+                price: raw.price,
+                timestamp: raw.timestamp.or(envelope_timestamp).unwrap_or_default(),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Latest events of each market, oldest first
+#[derive(Debug)]
+pub(crate) struct LiquidationBuffer {
+    capacity: usize,
+    by_market: HashMap<u32, VecDeque<LiquidationEvent>>,
+}
+
+impl LiquidationBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            by_market: HashMap::new(),
+        }
+    }
+
+    /// Keep `event`; returns false for a repeat of a kept event
+    ///
+    /// Snapshots sent on resubscribe repeat events already seen.
+    pub(crate) fn push(&mut self, event: &LiquidationEvent) -> bool {
+        let events = self.by_market.entry(event.market_index).or_default();
+        if events.contains(event) {
+            return false;
+        }
+        events.push_back(event.clone());
+        while events.len() > self.capacity {
+            events.pop_front();
+        }
+        true
+    }
+
+    /// Up to `n` latest events of a market, oldest first
+    pub(crate) fn recent(&self, market_id: u32, n: usize) -> Vec<LiquidationEvent> {
+        self.by_market
+            .get(&market_id)
+            .map_or_else(Vec::new, |events| {
+                events
+                    .iter()
+                    .skip(events.len().saturating_sub(n))
+                    .cloned()
+                    .collect()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(text: &str) -> Decimal {
+        Decimal::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_fixture() {
+        let message: Value = serde_json::from_str(
+            r#"{
+                "type": "update/liquidations",
+                "channel": "liquidations:2",
+                "timestamp": 1700000000500,
+                "liquidations": [
+                    {"market_id": 2, "account_index": 281474976710654, "size": "1.25", "price": "3012.5", "timestamp": 1700000000123, "type": "liquidation"},
+                    {"account_index": null, "size": 0.5, "price": 3010, "tx_type": 23},
+                    {"size": "2", "price": "3000.0"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let events = parse_message(&message, 2).unwrap();
+        assert_eq!(
+            events,
+            [
+                LiquidationEvent {
+                    market_index: 2,
+                    liquidated_account: Some(281474976710654),
+                    size: dec("1.25"),
+                    price: dec("3012.5"),
+                    timestamp: 1700000000123,
+                    kind: LiquidationKind::Liquidation,
+                },
+                LiquidationEvent {
+                    market_index: 2,
+                    liquidated_account: None,
+                    size: dec("0.5"),
+                    price: dec("3010"),
+                    timestamp: 1700000000500,
+                    kind: LiquidationKind::Deleverage,
+                },
+                LiquidationEvent {
+                    market_index: 2,
+                    liquidated_account: None,
+                    size: dec("2"),
+                    price: dec("3000.0"),
+                    timestamp: 1700000000500,
+                    kind: LiquidationKind::Liquidation,
+                },
+            ]
+        );
+
+        let unknown: Value = serde_json::from_str(
+            r#"{"liquidations": [{"size": "1", "price": "1", "tx_type": 14}]}"#,
+        )
+        .unwrap();
+        assert!(parse_message(&unknown, 0).is_err());
+        let no_price: Value = serde_json::from_str(r#"{"liquidations": [{"size": "1"}]}"#).unwrap();
+        assert!(parse_message(&no_price, 0).is_err());
+    }
+
+    #[test]
+    fn test_buffer_keeps_the_latest_without_repeats() {
+        let event = |timestamp| LiquidationEvent {
+            market_index: 1,
+            liquidated_account: None,
+            size: Decimal::ONE,
+            price: Decimal::ONE,
+            timestamp,
+            kind: LiquidationKind::Liquidation,
+        };
+        let mut buffer = LiquidationBuffer::new(3);
+        for timestamp in 0..5 {
+            assert!(buffer.push(&event(timestamp)));
+        }
+        assert!(!buffer.push(&event(4)));
+        let timestamps = |n| {
+            buffer
+                .recent(1, n)
+                .iter()
+                .map(|e| e.timestamp)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(timestamps(2), [3, 4]);
+        assert_eq!(timestamps(10), [2, 3, 4]);
+        assert!(buffer.recent(7, 10).is_empty());
+    }
+}
@@ -0,0 +1,5272 @@
+//! WebSocket client for real-time Lighter Protocol data streams
+//!
+//! This module provides WebSocket connectivity to subscribe to:
+//! - Order book updates
+//! - Account updates
+//! - Liquidation and deleverage events
+//! - Market trading status
+//! - Queue positions of the account's resting orders
+//! - Real-time trading data
+
+pub mod account;
+pub mod book_diff;
+pub mod config;
+pub mod dispatch;
+pub mod frame_queue;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+pub mod liquidations;
+pub mod notify;
+pub mod protocol;
+pub mod queue_position;
+pub mod subscriptions;
+
+pub use account::{
+    AccountEvent, AccountOrder, AccountPosition, AccountRetention, AccountSnapshot, AccountTrade,
+    AccountUpdate, AccountUpdateSource,
+};
+pub use book_diff::{BookDiff, SizeMismatch, VerifyPolicy};
+pub use config::WsConfig;
+pub use dispatch::{CallbackDispatch, QueueOverflow};
+pub use frame_queue::{FrameOverflow, FrameQueueStats, DEFAULT_FRAME_QUEUE_CAPACITY};
+pub use liquidations::{LiquidationEvent, LiquidationKind, DEFAULT_LIQUIDATION_BUFFER};
+pub use notify::{NotifyPolicy, NotifyStats};
+pub use protocol::{Channel, WsRequest};
+pub use queue_position::{QueueAttribution, QueueEstimate, QueuePositionEstimator};
+pub use subscriptions::{SubscriptionState, WsEvent, WsServerError, EARLY_UPDATE_LIMIT};
+pub use tokio_tungstenite::Connector;
+
+use chrono::{DateTime, Utc};
+use futures_util::{SinkExt, StreamExt};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch, RwLock};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::{CapacityError, Error as WsError, ProtocolError};
+use tokio_tungstenite::{client_async_tls_with_config, tungstenite::Message};
+
+use crate::client::{market_index_of, HTTPClient};
+use crate::constants::{MAX_ACCOUNT_INDEX, MAX_MARKET_INDEX, MIN_ACCOUNT_INDEX};
+use crate::errors::{LighterError, Result};
+use crate::market_status::{self, MarketStatus, MarketStatuses};
+use crate::order_flow::FlowTrade;
+use crate::runtime_config::{ClientRuntimeConfig, RuntimeConfig};
+use crate::types::AccountIndex;
+use dispatch::{Dispatcher, Job};
+use frame_queue::{Frame, FrameQueueCounters, RawTap};
+use liquidations::LiquidationBuffer;
+use notify::{BookNotifier, Notify};
+use queue_position::QueuePositions;
+use subscriptions::{normalize_channel, EarlyUpdates, Subscriptions};
+
+/// WebSocket message types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum WsMessageType {
+    #[serde(rename = "connected")]
+    Connected,
+    #[serde(rename = "subscribed/order_book")]
+    SubscribedOrderBook,
+    #[serde(rename = "update/order_book")]
+    UpdateOrderBook,
+    #[serde(rename = "subscribed/account_all")]
+    SubscribedAccount,
+    #[serde(rename = "update/account_all")]
+    UpdateAccount,
+    /// Account snapshot fetched over REST, see [`WsClientBuilder::bootstrap_accounts`]
+    #[serde(rename = "bootstrap/account_all")]
+    BootstrapAccount,
+}
+
+/// Order book data structure
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderBook {
+    pub asks: Vec<PriceLevel>,
+    pub bids: Vec<PriceLevel>,
+}
+
+/// Price level in order book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceLevel {
+    pub price: String,
+    pub size: String,
+}
+
+impl PriceLevel {
+    /// Level without trailing zeros, e.g. `100.50` becomes `100.5`
+    ///
+    /// Values that aren't decimals are kept as they are.
+    pub fn normalized(&self) -> PriceLevel {
+        PriceLevel {
+            price: reformat(&self.price, |d| d.normalize()),
+            size: reformat(&self.size, |d| d.normalize()),
+        }
+    }
+
+    /// Level with a fixed number of decimal places, e.g. `100.5` at 2 becomes `100.50`
+    ///
+    /// Extra places are rounded half to even, like [`Decimal::round_dp`].
+    pub fn with_display_scale(&self, price_dp: u32, size_dp: u32) -> PriceLevel {
+        PriceLevel {
+            price: reformat(&self.price, |d| fixed_scale(d, price_dp)),
+            size: reformat(&self.size, |d| fixed_scale(d, size_dp)),
+        }
+    }
+}
+
+impl OrderBook {
+    /// Book with every level at a fixed number of decimal places
+    ///
+    /// See [`PriceLevel::with_display_scale`].
+    pub fn with_display_scale(&self, price_dp: u32, size_dp: u32) -> OrderBook {
+        let scale = |levels: &[PriceLevel]| {
+            levels
+                .iter()
+                .map(|level| level.with_display_scale(price_dp, size_dp))
+                .collect()
+        };
+        OrderBook {
+            asks: scale(&self.asks),
+            bids: scale(&self.bids),
+        }
+    }
+}
+
+/// Rewrite a decimal string, leaving anything else untouched
+fn reformat(text: &str, f: impl Fn(Decimal) -> Decimal) -> String {
+    match text.parse::<Decimal>() {
+        Ok(value) => f(value).to_string(),
+        Err(_) => text.to_string(),
+    }
+}
+
+fn fixed_scale(value: Decimal, dp: u32) -> Decimal {
+    let mut value = value.round_dp(dp);
+    value.rescale(dp);
+    value
+}
+
+/// Outcome of applying an incremental order book update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyResult {
+    /// The update was merged into the book
+    Applied { levels_changed: usize },
+    /// The update's offset isn't newer than the book's, so it was dropped
+    SkippedStale {
+        update_offset: i64,
+        book_offset: i64,
+    },
+}
+
+/// Whether a local order book follows the server's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BookSyncState {
+    /// Built from a snapshot and its updates
+    #[default]
+    Synced,
+    /// Waiting for a fresh snapshot; updates are dropped meanwhile
+    Resyncing,
+    /// No snapshot yet, or updates stopped lining up with the book
+    Desynced,
+}
+
+/// What the run loop does when a callback fails or panics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CallbackErrorPolicy {
+    /// Log the error and keep processing messages
+    #[default]
+    LogAndContinue,
+    /// Close the connection and return the error from the run loop
+    Stop,
+    /// Close the connection and connect again
+    Reconnect,
+}
+
+/// Server and local timing of a market's book
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BookTiming {
+    /// Highest server timestamp seen, in ms; never goes backwards
+    pub server_timestamp: Option<u64>,
+    /// When the latest snapshot or update was parsed locally
+    pub local_received_at: Option<Instant>,
+    /// Messages whose server timestamp was behind `server_timestamp`
+    pub timestamp_regressions: u64,
+    /// Largest backwards jump of the server clock, in ms
+    pub max_regression_ms: u64,
+}
+
+impl BookTiming {
+    /// Time since the latest snapshot or update was received
+    pub fn age(&self) -> Option<Duration> {
+        self.local_received_at.map(|at| at.elapsed())
+    }
+
+    /// Record a message, keeping the highest server timestamp
+    fn observe(&mut self, server_timestamp: Option<u64>, received_at: Instant) {
+        self.local_received_at = Some(received_at);
+        let Some(timestamp) = server_timestamp else {
+            return;
+        };
+        match self.server_timestamp {
+            Some(latest) if timestamp < latest => {
+                self.timestamp_regressions += 1;
+                self.max_regression_ms = self.max_regression_ms.max(latest - timestamp);
+            }
+            _ => self.server_timestamp = Some(timestamp),
+        }
+    }
+}
+
+/// Snapshots of a market's book beyond the first on each connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotCounts {
+    /// Repeated snapshots that were applied, e.g. after a gateway failover
+    pub resnapshots: u64,
+    /// Repeated snapshots older than the book that were dropped
+    pub stale_snapshots: u64,
+}
+
+/// Book, open orders and position of one market, read together
+///
+/// Taken by [`WsClient::market_view`]; every field reflects the same set of
+/// applied messages.
+#[derive(Debug, Clone)]
+pub struct MarketView {
+    pub market_id: u32,
+    /// `None` before the market's first snapshot; shared with the client,
+    /// which copies on its next update rather than changing this one
+    pub book: Option<Arc<OrderBook>>,
+    /// Offset of the last update applied to `book`, if the server sent one
+    pub offset: Option<i64>,
+    /// See [`WsClient::book_error_code`]
+    pub last_error_code: Option<i64>,
+    /// Open orders of the account in this market
+    pub my_orders: Vec<AccountOrder>,
+    /// `None` if the account has no position entry for this market
+    pub position: Option<AccountPosition>,
+    /// Queue position of each order in `my_orders` that has an estimate, by
+    /// client order index; empty without
+    /// [`queue_positions`](WsClientBuilder::queue_positions)
+    pub queue_positions: HashMap<i64, QueueEstimate>,
+    /// When the view was taken
+    pub as_of: Instant,
+}
+
+/// Orders per side fetched by [`WsClient::verify_against_rest`]
+const REST_BOOK_DEPTH: u32 = 250;
+
+/// Consecutive stale updates after which a restarted subscription is likely
+const STALE_UPDATE_LOG_THRESHOLD: u32 = 20;
+
+/// Last applied offset of a market's book
+#[derive(Debug, Default)]
+struct BookSequence {
+    offset: Option<i64>,
+    consecutive_skipped: u32,
+    state: BookSyncState,
+    timing: BookTiming,
+    /// Code of the last order book message rejected for a non-zero code
+    last_error_code: Option<i64>,
+    /// Whether a snapshot was applied on the current connection
+    snapshot_seen: bool,
+    snapshots: SnapshotCounts,
+}
+
+/// Outcome of a snapshot for a market's sequence
+enum SnapshotCheck {
+    /// Apply the snapshot; carries the sync state the book had before
+    Apply(Option<BookSyncState>),
+    /// A repeated snapshot older than the book; keep the book
+    Stale { book_offset: i64 },
+}
+
+/// Request from a caller to the run loop
+#[derive(Debug)]
+enum WsCommand {
+    Resync(u32),
+}
+
+/// How a single connection of the run loop ended
+enum ConnectionEnd {
+    /// The server closed the stream
+    Closed,
+    /// A callback failed under [`CallbackErrorPolicy::Reconnect`]
+    Reconnect,
+}
+
+/// Marks the client disconnected however a connection ends, including
+/// when its future is dropped
+struct ConnectedGuard<'a>(&'a watch::Sender<bool>);
+
+impl Drop for ConnectedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.send_replace(false);
+    }
+}
+
+/// WebSocket client configuration
+pub struct WsClientBuilder {
+    scheme: String,
+    host: Option<String>,
+    url: Option<String>,
+    path: String,
+    query: Vec<(String, String)>,
+    order_book_ids: Vec<u32>,
+    account_ids: Vec<i64>,
+    liquidation_ids: Vec<u32>,
+    liquidation_buffer: usize,
+    market_stats_ids: Vec<u32>,
+    raw_tap: Option<mpsc::Sender<RawWsMessage>>,
+    config: WsConfig,
+    frame_queue_capacity: usize,
+    frame_overflow: FrameOverflow,
+    callback_error_policy: CallbackErrorPolicy,
+    callback_dispatch: CallbackDispatch,
+    bootstrap_accounts: bool,
+    rest_client: Option<HTTPClient>,
+    retain_raw_accounts: bool,
+    account_retention: Option<AccountRetention>,
+    strict_parsing: bool,
+    notify_policy: Option<NotifyPolicy>,
+    market_notify_policies: HashMap<u32, NotifyPolicy>,
+    runtime_config: Option<RuntimeConfig>,
+    queue_attribution: Option<QueueAttribution>,
+}
+
+impl WsClientBuilder {
+    /// Create a new WebSocket client builder
+    pub fn new() -> Self {
+        Self {
+            scheme: "wss".to_string(),
+            host: None,
+            url: None,
+            path: "/stream".to_string(),
+            query: Vec::new(),
+            order_book_ids: Vec::new(),
+            account_ids: Vec::new(),
+            liquidation_ids: Vec::new(),
+            liquidation_buffer: DEFAULT_LIQUIDATION_BUFFER,
+            market_stats_ids: Vec::new(),
+            raw_tap: None,
+            config: WsConfig::default(),
+            frame_queue_capacity: DEFAULT_FRAME_QUEUE_CAPACITY,
+            frame_overflow: FrameOverflow::default(),
+            callback_error_policy: CallbackErrorPolicy::default(),
+            callback_dispatch: CallbackDispatch::default(),
+            bootstrap_accounts: false,
+            rest_client: None,
+            retain_raw_accounts: false,
+            account_retention: None,
+            strict_parsing: false,
+            notify_policy: None,
+            market_notify_policies: HashMap::new(),
+            runtime_config: None,
+            queue_attribution: None,
+        }
+    }
+
+    /// Set the URL scheme, `ws` or `wss` (defaults to `wss`)
+    ///
+    /// Plain `ws` is meant for local simulators.
+    pub fn scheme(mut self, scheme: impl Into<String>) -> Self {
+        self.scheme = scheme.into();
+        self
+    }
+
+    /// Set the WebSocket host (defaults to testnet)
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.host = Some(host.into());
+        self
+    }
+
+    /// Use a complete WebSocket URL instead of `wss://{host}{path}`
+    pub(crate) fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = Some(url.into());
+        self
+    }
+
+    /// Set the WebSocket path (defaults to "/stream")
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Add a query parameter to the URL, e.g. an auth token
+    ///
+    /// Repeated calls add more parameters, in order. Keys and values are
+    /// percent-encoded.
+    pub fn query_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    /// Subscribe to order book updates for specific markets
+    ///
+    /// Repeated calls add to the markets already given; duplicates are
+    /// subscribed once.
+    pub fn order_books(mut self, ids: Vec<u32>) -> Self {
+        self.order_book_ids.extend(ids);
+        self
+    }
+
+    /// Subscribe to account updates for specific accounts
+    ///
+    /// Repeated calls add to the accounts already given; duplicates are
+    /// subscribed once.
+    pub fn accounts(mut self, ids: Vec<i64>) -> Self {
+        self.account_ids.extend(ids);
+        self
+    }
+
+    /// Subscribe to liquidation and deleverage events for specific markets
+    ///
+    /// Events are delivered as [`WsEvent::Liquidation`] to
+    /// [`on_ws_event`](WsClient::on_ws_event) handlers. Repeated calls add to
+    /// the markets already given; duplicates are subscribed once.
+    pub fn liquidations(mut self, ids: Vec<u32>) -> Self {
+        self.liquidation_ids.extend(ids);
+        self
+    }
+
+    /// Liquidation events kept per market for
+    /// [`recent_liquidations`](WsClient::recent_liquidations) (defaults to
+    /// [`DEFAULT_LIQUIDATION_BUFFER`])
+    pub fn liquidation_buffer(mut self, capacity: usize) -> Self {
+        self.liquidation_buffer = capacity;
+        self
+    }
+
+    /// Subscribe to the statistics of specific markets to follow their
+    /// trading status
+    ///
+    /// Statuses are kept for [`market_status`](WsClient::market_status) and
+    /// changes delivered as [`WsEvent::MarketStatus`]. Repeated calls add to
+    /// the markets already given.
+    pub fn market_stats(mut self, ids: Vec<u32>) -> Self {
+        self.market_stats_ids.extend(ids);
+        self
+    }
+
+    /// Forward every raw text frame to `tx` before it is parsed
+    ///
+    /// Frames are sent with `try_send`, so a slow consumer never stalls the
+    /// read loop; frames that don't fit are dropped and counted in
+    /// [`WsClient::raw_tap_dropped`]. Dropping the receiver is harmless.
+    pub fn raw_message_tap(mut self, tx: mpsc::Sender<RawWsMessage>) -> Self {
+        self.raw_tap = Some(tx);
+        self
+    }
+
+    /// Set connection limits, timeouts and TLS options
+    pub fn config(mut self, config: WsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Bound the frames read ahead of the run loop
+    ///
+    /// A reader task reads and parses frames into a queue of `capacity`
+    /// (defaults to [`DEFAULT_FRAME_QUEUE_CAPACITY`]) while the run loop
+    /// applies them; `overflow` sets what happens when it is full. See
+    /// [`WsClient::frame_queue_stats`].
+    pub fn frame_queue(mut self, capacity: usize, overflow: FrameOverflow) -> Self {
+        self.frame_queue_capacity = capacity;
+        self.frame_overflow = overflow;
+        self
+    }
+
+    /// Choose how failing or panicking callbacks are handled
+    ///
+    /// Defaults to [`CallbackErrorPolicy::LogAndContinue`].
+    pub fn callback_error_policy(mut self, policy: CallbackErrorPolicy) -> Self {
+        self.callback_error_policy = policy;
+        self
+    }
+
+    /// Choose where handlers and `run` callbacks are called
+    ///
+    /// Defaults to [`CallbackDispatch::Inline`]. In the other modes the
+    /// read loop only queues the calls; calls for one market, one account,
+    /// or stream events keep their order. Failures of queued calls are
+    /// logged and panics counted, whatever the [`CallbackErrorPolicy`].
+    pub fn callback_dispatch(mut self, dispatch: CallbackDispatch) -> Self {
+        self.callback_dispatch = dispatch;
+        self
+    }
+
+    /// Fetch each account over REST before its stream snapshot arrives
+    ///
+    /// On connect, accounts without stream data are loaded with
+    /// [`HTTPClient::get_account_snapshot`], plus open orders in the
+    /// subscribed markets when the client has an auth token. The result is
+    /// stored and passed to the account callback as a `bootstrap/account_all`
+    /// message (see [`AccountUpdateSource`]); the stream's own snapshot
+    /// replaces it. Needs a [`rest_client`](Self::rest_client).
+    pub fn bootstrap_accounts(mut self, enabled: bool) -> Self {
+        self.bootstrap_accounts = enabled;
+        self
+    }
+
+    /// REST client used by [`bootstrap_accounts`](Self::bootstrap_accounts)
+    pub fn rest_client(mut self, http: HTTPClient) -> Self {
+        self.rest_client = Some(http);
+        self
+    }
+
+    /// Also keep the latest raw message of each account
+    ///
+    /// Off by default, since full account messages can be large; see
+    /// [`WsClient::get_raw_account`].
+    pub fn retain_raw_accounts(mut self, enabled: bool) -> Self {
+        self.retain_raw_accounts = enabled;
+        self
+    }
+
+    /// Trim the stored account snapshots after every message
+    pub fn account_retention(mut self, retention: AccountRetention) -> Self {
+        self.account_retention = Some(retention);
+        self
+    }
+
+    /// Reject account messages carrying keys this version doesn't know
+    ///
+    /// Off by default: unknown keys are kept in the `extras` of the typed
+    /// snapshot and its entries. On, such a message is reported as a
+    /// [`WsEvent::ParseError`] naming the keys and leaves the snapshot as
+    /// it was.
+    pub fn strict_parsing(mut self, enabled: bool) -> Self {
+        self.strict_parsing = enabled;
+        self
+    }
+
+    /// Choose which order book updates invoke callbacks, for every market
+    ///
+    /// Defaults to [`NotifyPolicy::Always`]. Stored books are kept up to
+    /// date whatever the policy; see [`WsClient::notify_stats`].
+    pub fn notify_policy(mut self, policy: NotifyPolicy) -> Self {
+        self.notify_policy = Some(policy);
+        self
+    }
+
+    /// Override the [`notify_policy`](Self::notify_policy) of one market
+    pub fn market_notify_policy(mut self, market_id: u32, policy: NotifyPolicy) -> Self {
+        self.market_notify_policies.insert(market_id, policy);
+        self
+    }
+
+    /// Read settings that can change while running from `config`, e.g. one
+    /// shared with a `TxClient`
+    ///
+    /// Notify policies set on the builder are written into it by
+    /// [`build`](Self::build). See [`WsClient::update_config`].
+    pub fn runtime_config(mut self, config: RuntimeConfig) -> Self {
+        self.runtime_config = Some(config);
+        self
+    }
+
+    /// Estimate the queue position of every subscribed account's resting
+    /// orders in every subscribed order book
+    ///
+    /// Public trades aren't subscribed; pass them to
+    /// [`WsClient::record_trade`], or only book reductions move orders
+    /// forward. See [`QueuePositionEstimator`].
+    pub fn queue_positions(mut self, attribution: QueueAttribution) -> Self {
+        self.queue_attribution = Some(attribution);
+        self
+    }
+
+    /// Build the WebSocket client
+    pub fn build(mut self) -> Result<WsClient> {
+        if self.order_book_ids.is_empty()
+            && self.account_ids.is_empty()
+            && self.liquidation_ids.is_empty()
+            && self.market_stats_ids.is_empty()
+        {
+            return Err(LighterError::ValidationError(
+                "At least one subscription (order_book, account, liquidations or market_stats) is required"
+                    .to_string(),
+            ));
+        }
+        if self.frame_queue_capacity < 2 {
+            return Err(LighterError::InvalidConfiguration(
+                "Frame queue capacity must be at least 2".to_string(),
+            ));
+        }
+        let bootstrap = match (self.bootstrap_accounts, self.rest_client) {
+            (false, _) => None,
+            (true, Some(http)) => Some(http),
+            (true, None) => {
+                return Err(LighterError::InvalidConfiguration(
+                    "bootstrap_accounts needs a rest_client".to_string(),
+                ))
+            }
+        };
+
+        dedupe(&mut self.order_book_ids);
+        dedupe(&mut self.account_ids);
+        dedupe(&mut self.liquidation_ids);
+        dedupe(&mut self.market_stats_ids);
+        let invalid: Vec<String> = self
+            .order_book_ids
+            .iter()
+            .filter(|id| **id > MAX_MARKET_INDEX as u32)
+            .map(|id| format!("order book {} (max {})", id, MAX_MARKET_INDEX))
+            .chain(
+                self.liquidation_ids
+                    .iter()
+                    .filter(|id| **id > MAX_MARKET_INDEX as u32)
+                    .map(|id| format!("liquidations {} (max {})", id, MAX_MARKET_INDEX)),
+            )
+            .chain(
+                self.market_stats_ids
+                    .iter()
+                    .filter(|id| **id > MAX_MARKET_INDEX as u32)
+                    .map(|id| format!("market_stats {} (max {})", id, MAX_MARKET_INDEX)),
+            )
+            .chain(
+                self.account_ids
+                    .iter()
+                    .filter(|id| !(MIN_ACCOUNT_INDEX..=MAX_ACCOUNT_INDEX).contains(*id))
+                    .map(|id| {
+                        format!(
+                            "account {} (range {}..={})",
+                            id, MIN_ACCOUNT_INDEX, MAX_ACCOUNT_INDEX
+                        )
+                    }),
+            )
+            .collect();
+        if !invalid.is_empty() {
+            return Err(LighterError::ValidationError(format!(
+                "Invalid subscription ids: {}",
+                invalid.join(", ")
+            )));
+        }
+
+        let host = self
+            .host
+            .unwrap_or_else(|| "api-testnet.lighter.xyz".to_string());
+        if self.url.is_none() && host.is_empty() {
+            return Err(LighterError::InvalidConfiguration(
+                "WebSocket host is empty".to_string(),
+            ));
+        }
+        let base_url = self
+            .url
+            .unwrap_or_else(|| format!("{}://{}{}", self.scheme, host, self.path));
+        let base_url = compose_url(&base_url, &self.query)?;
+
+        let subscriptions = Arc::new(Subscriptions::new(channels(
+            &self.order_book_ids,
+            &self.account_ids,
+            &self.liquidation_ids,
+            &self.market_stats_ids,
+        )));
+        let (commands, command_rx) = mpsc::unbounded_channel();
+        let dispatcher = Arc::new(Dispatcher::new(self.callback_dispatch)?);
+        let runtime_config = self.runtime_config.unwrap_or_default();
+        runtime_config.update(|config| {
+            if let Some(policy) = self.notify_policy {
+                config.notify_policy = policy;
+            }
+            config
+                .market_notify_policies
+                .extend(self.market_notify_policies);
+        })?;
+        let notifier = Arc::new(BookNotifier::new(runtime_config.clone()));
+        let queue_positions = self.queue_attribution.map(|attribution| {
+            Arc::new(QueuePositions::new(
+                &self.order_book_ids,
+                &self.account_ids,
+                attribution,
+            ))
+        });
+
+        Ok(WsClient {
+            base_url,
+            config: self.config,
+            order_book_ids: self.order_book_ids,
+            account_ids: self.account_ids,
+            liquidation_ids: self.liquidation_ids,
+            liquidations: Arc::new(Mutex::new(LiquidationBuffer::new(self.liquidation_buffer))),
+            market_stats_ids: self.market_stats_ids,
+            market_statuses: MarketStatuses::new(),
+            order_book_states: Arc::default(),
+            raw_account_states: self
+                .retain_raw_accounts
+                .then(|| Arc::new(RwLock::new(HashMap::new()))),
+            account_snapshots: Arc::new(RwLock::new(HashMap::new())),
+            state_generation: Arc::default(),
+            account_retention: self.account_retention.map(Arc::new),
+            strict_parsing: self.strict_parsing,
+            raw_tap: self.raw_tap,
+            raw_tap_dropped: Arc::new(AtomicU64::new(0)),
+            frame_queue_capacity: self.frame_queue_capacity,
+            frame_overflow: self.frame_overflow,
+            frame_queue: Arc::default(),
+            handlers: Arc::new(Mutex::new(HandlerRegistry::default())),
+            suppressed_panics: Arc::new(AtomicU64::new(0)),
+            callback_error_policy: self.callback_error_policy,
+            dispatcher,
+            notifier,
+            runtime_config,
+            subscriptions,
+            order_book_sequences: Arc::new(Mutex::new(HashMap::new())),
+            skipped_updates: Arc::new(AtomicU64::new(0)),
+            malformed_messages: Arc::new(AtomicU64::new(0)),
+            skipped_messages: Arc::new(AtomicU64::new(0)),
+            early_updates: Arc::default(),
+            display_scales: Arc::new(Mutex::new(HashMap::new())),
+            commands,
+            command_rx: Arc::new(tokio::sync::Mutex::new(command_rx)),
+            bootstrap,
+            queue_positions,
+            connected: watch::channel(false).0,
+        })
+    }
+}
+
+/// Parse a WebSocket URL and append query parameters
+///
+/// Fails on anything but a `ws` or `wss` URL with a host, so a typo
+/// surfaces when building rather than on connect.
+fn compose_url(url: &str, query: &[(String, String)]) -> Result<String> {
+    let invalid = |reason: String| {
+        LighterError::InvalidConfiguration(format!("Invalid WebSocket URL {}: {}", url, reason))
+    };
+    let mut parsed = reqwest::Url::parse(url).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(parsed.scheme(), "ws" | "wss") {
+        return Err(invalid(format!(
+            "scheme must be ws or wss, not {}",
+            parsed.scheme()
+        )));
+    }
+    if parsed.host_str().is_none_or(str::is_empty) {
+        return Err(invalid("missing host".to_string()));
+    }
+    if !query.is_empty() {
+        parsed.query_pairs_mut().extend_pairs(query);
+    }
+    Ok(parsed.into())
+}
+
+/// Run a callback, counting panics and turning them into errors
+fn call_isolated<F: FnOnce() -> Result<()>>(panics: &AtomicU64, f: F) -> Result<()> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        panics.fetch_add(1, Ordering::Relaxed);
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+        Err(LighterError::CallbackPanicked(message))
+    })
+}
+
+/// Run a queued callback; failures can only be logged off the read loop
+fn run_queued<F: FnOnce() -> Result<()>>(panics: &AtomicU64, f: F) {
+    if let Err(e) = call_isolated(panics, f) {
+        eprintln!("WebSocket callback failed: {}; continuing", e);
+    }
+}
+
+/// Remove repeated ids, keeping the first occurrence of each
+fn dedupe<T: PartialEq + Copy>(ids: &mut Vec<T>) {
+    let mut seen = Vec::with_capacity(ids.len());
+    ids.retain(|id| {
+        let first = !seen.contains(id);
+        if first {
+            seen.push(*id);
+        }
+        first
+    });
+}
+
+impl Default for WsClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Order book handler registered on a [`WsClient`]
+type OrderBookHandler = Arc<dyn Fn(String, OrderBook) + Send + Sync>;
+
+/// Account event handler registered on a [`WsClient`]
+type AccountEventHandler = Arc<dyn Fn(String, AccountEvent) + Send + Sync>;
+
+/// Typed account handler registered on a [`WsClient`]
+type AccountHandler = Arc<dyn Fn(i64, AccountUpdate) + Send + Sync>;
+
+/// Stream event handler registered on a [`WsClient`]
+type WsEventHandler = Arc<dyn Fn(WsEvent) + Send + Sync>;
+
+/// Handlers consulted by the run loop on every update
+#[derive(Default)]
+struct HandlerRegistry {
+    by_market: HashMap<String, Vec<OrderBookHandler>>,
+    any: Vec<OrderBookHandler>,
+    account_events: Vec<AccountEventHandler>,
+    by_account: HashMap<i64, Vec<AccountHandler>>,
+    other_accounts: Vec<AccountHandler>,
+    ws_events: Vec<WsEventHandler>,
+}
+
+/// Latest book of each market
+///
+/// Only the run loop writes, changing a book in place unless a reader still
+/// holds it; readers clone the `Arc` and copy the book after releasing the
+/// lock, so a slow reader never holds up the stream.
+type BookStates = Arc<std::sync::RwLock<HashMap<String, Arc<OrderBook>>>>;
+
+/// Counts of messages that started and finished being applied to the
+/// stored state
+///
+/// Books, sequences and accounts sit behind separate locks. A reader
+/// spanning them takes the generation while nothing is being applied and
+/// checks afterwards that no message started since, retrying otherwise.
+#[derive(Debug, Default)]
+struct StateGeneration {
+    started: AtomicU64,
+    finished: AtomicU64,
+}
+
+impl StateGeneration {
+    /// Mark a message as being applied until the guard drops
+    fn applying(&self) -> Applying<'_> {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        Applying(self)
+    }
+
+    /// The current generation, or `None` while a message is being applied
+    fn settled(&self) -> Option<u64> {
+        let finished = self.finished.load(Ordering::SeqCst);
+        (self.started.load(Ordering::SeqCst) == finished).then_some(finished)
+    }
+
+    /// Whether no message started being applied since `settled` returned
+    /// `generation`
+    fn unchanged_since(&self, generation: u64) -> bool {
+        self.started.load(Ordering::SeqCst) == generation
+    }
+}
+
+/// See [`StateGeneration::applying`]
+struct Applying<'a>(&'a StateGeneration);
+
+impl Drop for Applying<'_> {
+    fn drop(&mut self) {
+        self.0.finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// WebSocket client for Lighter Protocol
+pub struct WsClient {
+    base_url: String,
+    config: WsConfig,
+    order_book_ids: Vec<u32>,
+    account_ids: Vec<i64>,
+    liquidation_ids: Vec<u32>,
+    liquidations: Arc<Mutex<LiquidationBuffer>>,
+    market_stats_ids: Vec<u32>,
+    market_statuses: MarketStatuses,
+    order_book_states: BookStates,
+    raw_account_states: Option<Arc<RwLock<HashMap<i64, Value>>>>,
+    account_snapshots: Arc<RwLock<HashMap<i64, Arc<AccountSnapshot>>>>,
+    state_generation: Arc<StateGeneration>,
+    account_retention: Option<Arc<AccountRetention>>,
+    strict_parsing: bool,
+    raw_tap: Option<mpsc::Sender<RawWsMessage>>,
+    raw_tap_dropped: Arc<AtomicU64>,
+    frame_queue_capacity: usize,
+    frame_overflow: FrameOverflow,
+    frame_queue: Arc<FrameQueueCounters>,
+    handlers: Arc<Mutex<HandlerRegistry>>,
+    suppressed_panics: Arc<AtomicU64>,
+    callback_error_policy: CallbackErrorPolicy,
+    dispatcher: Arc<Dispatcher>,
+    notifier: Arc<BookNotifier>,
+    runtime_config: RuntimeConfig,
+    subscriptions: Arc<Subscriptions>,
+    order_book_sequences: Arc<Mutex<HashMap<String, BookSequence>>>,
+    skipped_updates: Arc<AtomicU64>,
+    malformed_messages: Arc<AtomicU64>,
+    skipped_messages: Arc<AtomicU64>,
+    early_updates: Arc<Mutex<EarlyUpdates>>,
+    display_scales: Arc<Mutex<HashMap<String, (u32, u32)>>>,
+    commands: mpsc::UnboundedSender<WsCommand>,
+    command_rx: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<WsCommand>>>,
+    bootstrap: Option<HTTPClient>,
+    queue_positions: Option<Arc<QueuePositions>>,
+    connected: watch::Sender<bool>,
+}
+
+impl std::fmt::Debug for WsClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WsClient")
+            .field("base_url", &self.base_url)
+            .field("order_book_ids", &self.order_book_ids)
+            .field("account_ids", &self.account_ids)
+            .field("liquidation_ids", &self.liquidation_ids)
+            .field("market_stats_ids", &self.market_stats_ids)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl WsClient {
+    /// Create a new WebSocket client builder
+    pub fn builder() -> WsClientBuilder {
+        WsClientBuilder::new()
+    }
+
+    /// URL the client connects to, including query parameters
+    pub fn url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether a connection is open, from the handshake until it ends
+    pub fn is_connected(&self) -> bool {
+        *self.connected.borrow()
+    }
+
+    /// Watch [`is_connected`](Self::is_connected) change
+    pub fn connection_state(&self) -> watch::Receiver<bool> {
+        self.connected.subscribe()
+    }
+
+    /// Number of raw messages dropped because the tap channel was full
+    pub fn raw_tap_dropped(&self) -> u64 {
+        self.raw_tap_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Register a handler for order book updates of a single market
+    ///
+    /// Several handlers can be registered per market. Handlers added while
+    /// [`run`](Self::run) is active receive the next update without reconnecting.
+    pub fn on_order_book<F>(&self, market_id: u32, handler: F)
+    where
+        F: Fn(String, OrderBook) + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .by_market
+            .entry(market_id.to_string())
+            .or_default()
+            .push(Arc::new(handler));
+    }
+
+    /// Register a handler for order book updates of every market
+    pub fn on_any_order_book<F>(&self, handler: F)
+    where
+        F: Fn(String, OrderBook) + Send + Sync + 'static,
+    {
+        self.handlers.lock().unwrap().any.push(Arc::new(handler));
+    }
+
+    /// Register a handler for events derived from `account_all` messages
+    ///
+    /// Events are computed by diffing each message against the stored typed
+    /// snapshot, see [`AccountSnapshot::apply`].
+    pub fn on_account_event<F>(&self, handler: F)
+    where
+        F: Fn(String, AccountEvent) + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .account_events
+            .push(Arc::new(handler));
+    }
+
+    /// Register a typed handler for one account
+    ///
+    /// For every message of the account the handler gets the events derived
+    /// from it, then the merged snapshot. Several handlers can be registered
+    /// per account, also while [`run`](Self::run) is active.
+    pub fn on_account<F>(&self, account_index: i64, handler: F)
+    where
+        F: Fn(i64, AccountUpdate) + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .by_account
+            .entry(account_index)
+            .or_default()
+            .push(Arc::new(handler));
+    }
+
+    /// Remove the handlers of an account; returns whether there were any
+    ///
+    /// Its messages go to the [`on_other_accounts`](Self::on_other_accounts)
+    /// handlers from then on.
+    pub fn off_account(&self, account_index: i64) -> bool {
+        self.handlers
+            .lock()
+            .unwrap()
+            .by_account
+            .remove(&account_index)
+            .is_some()
+    }
+
+    /// Register a typed handler for accounts without their own handlers
+    pub fn on_other_accounts<F>(&self, handler: F)
+    where
+        F: Fn(i64, AccountUpdate) + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .other_accounts
+            .push(Arc::new(handler));
+    }
+
+    /// Register a handler for stream events such as failed subscriptions
+    pub fn on_ws_event<F>(&self, handler: F)
+    where
+        F: Fn(WsEvent) + Send + Sync + 'static,
+    {
+        self.handlers
+            .lock()
+            .unwrap()
+            .ws_events
+            .push(Arc::new(handler));
+    }
+
+    /// Up to `n` latest liquidation events of a market, oldest first
+    ///
+    /// Empty for markets without a [`liquidations`](WsClientBuilder::liquidations)
+    /// subscription.
+    pub fn recent_liquidations(&self, market_id: u32, n: usize) -> Vec<LiquidationEvent> {
+        self.liquidations.lock().unwrap().recent(market_id, n)
+    }
+
+    /// Latest trading status of a market
+    ///
+    /// `None` for markets without a [`market_stats`](WsClientBuilder::market_stats)
+    /// subscription and before their first status arrives.
+    pub fn market_status(&self, market_id: u32) -> Option<MarketStatus> {
+        self.market_statuses.get(market_id)
+    }
+
+    /// Statuses of the subscribed markets, kept up to date by the stream
+    ///
+    /// Hand them to [`TxClient::set_market_statuses`](crate::client::TxClient::set_market_statuses)
+    /// to gate new orders.
+    pub fn market_statuses(&self) -> MarketStatuses {
+        self.market_statuses.clone()
+    }
+
+    /// State of a configured subscription on the current connection
+    ///
+    /// `channel` may be given as `order_book/0` or `order_book:0`. Returns
+    /// `None` for channels this client doesn't subscribe to.
+    pub fn subscription_state(&self, channel: &str) -> Option<SubscriptionState> {
+        self.subscriptions.state(channel)
+    }
+
+    /// Wait until the server acknowledges a subscription
+    ///
+    /// Call this alongside [`run`](Self::run), e.g. to hold off placing
+    /// orders until market data is flowing. Fails with
+    /// [`LighterError::SubscriptionFailed`] if the server rejects the channel
+    /// and [`LighterError::Timeout`] if no answer arrives within `timeout`.
+    /// States reset to pending on every new connection.
+    pub async fn await_subscribed(&self, channel: &str, timeout: Duration) -> Result<()> {
+        self.subscriptions.wait(channel, timeout).await
+    }
+
+    /// Number of callback panics caught by the run loop
+    pub fn suppressed_panics(&self) -> u64 {
+        self.suppressed_panics.load(Ordering::Relaxed)
+    }
+
+    /// Show a market's book with fixed price and size decimal places
+    ///
+    /// Applies to books passed to callbacks and handlers and returned by
+    /// [`get_order_book`](Self::get_order_book), so levels read the same
+    /// whatever precision the server used. Levels are still matched by the
+    /// server's own strings; this only changes the output.
+    pub fn set_display_scale(&self, market_id: u32, price_dp: u32, size_dp: u32) {
+        self.display_scales
+            .lock()
+            .unwrap()
+            .insert(market_id.to_string(), (price_dp, size_dp));
+    }
+
+    /// Apply the market's display scale, if one is set
+    fn displayed(&self, market_id: &str, order_book: OrderBook) -> OrderBook {
+        match self.display_scales.lock().unwrap().get(market_id) {
+            Some(&(price_dp, size_dp)) => order_book.with_display_scale(price_dp, size_dp),
+            None => order_book,
+        }
+    }
+
+    /// [`displayed`](Self::displayed) without copying an unscaled book
+    fn displayed_shared(&self, market_id: &str, order_book: Arc<OrderBook>) -> Arc<OrderBook> {
+        match self.display_scales.lock().unwrap().get(market_id) {
+            Some(&(price_dp, size_dp)) => {
+                Arc::new(OrderBook::clone(&order_book).with_display_scale(price_dp, size_dp))
+            }
+            None => order_book,
+        }
+    }
+
+    /// Number of order book updates dropped as stale
+    pub fn skipped_updates(&self) -> u64 {
+        self.skipped_updates.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages skipped because they couldn't be parsed or applied
+    pub fn malformed_messages(&self) -> u64 {
+        self.malformed_messages.load(Ordering::Relaxed)
+    }
+
+    /// Number of queued callback calls dropped by [`QueueOverflow::DropOldest`]
+    pub fn dropped_callbacks(&self) -> u64 {
+        self.dispatcher.dropped()
+    }
+
+    /// Depth and high-water mark of the queue between the reader task and
+    /// the run loop
+    ///
+    /// A depth staying near the capacity means updates arrive faster than
+    /// they are applied; see [`WsClientBuilder::frame_queue`].
+    pub fn frame_queue_stats(&self) -> FrameQueueStats {
+        self.frame_queue.stats(self.frame_queue_capacity)
+    }
+
+    /// Number of binary messages and raw frames skipped by the run loop
+    ///
+    /// Fragmented text messages are reassembled before they are read, so
+    /// this stays at zero unless the server sends something other than text.
+    pub fn skipped_messages(&self) -> u64 {
+        self.skipped_messages.load(Ordering::Relaxed)
+    }
+
+    /// Number of updates dropped because they arrived before their
+    /// channel's ack and didn't fit the hold buffer, or the ack never came
+    ///
+    /// Updates that arrive before their ack are held and applied after it,
+    /// up to [`EARLY_UPDATE_LIMIT`] per channel.
+    pub fn early_updates_dropped(&self) -> u64 {
+        self.early_updates.lock().unwrap().dropped()
+    }
+
+    /// Order book callback counts of a market under its [`NotifyPolicy`]
+    ///
+    /// `None` before the market's first snapshot.
+    pub fn notify_stats(&self, market_id: u32) -> Option<NotifyStats> {
+        self.notifier.stats(&market_id.to_string())
+    }
+
+    /// Size estimated ahead of an account's resting order
+    ///
+    /// `None` without [`queue_positions`](WsClientBuilder::queue_positions),
+    /// for orders not seen in a subscribed book, and for stale estimates;
+    /// see [`queue_estimate`](Self::queue_estimate).
+    pub fn estimated_ahead(&self, account_index: i64, client_order_index: i64) -> Option<Decimal> {
+        self.queue_estimate(account_index, client_order_index)
+            .filter(|estimate| !estimate.stale)
+            .map(|estimate| estimate.ahead)
+    }
+
+    /// Queue position estimate of an account's resting order, stale or not
+    pub fn queue_estimate(
+        &self,
+        account_index: i64,
+        client_order_index: i64,
+    ) -> Option<QueueEstimate> {
+        self.queue_positions
+            .as_ref()?
+            .estimate(account_index, client_order_index)
+    }
+
+    /// Feed a public trade of a market to the queue position estimates
+    ///
+    /// Pass trades as they arrive, before the book update that shows them.
+    /// Does nothing without [`queue_positions`](WsClientBuilder::queue_positions).
+    pub fn record_trade(&self, market_id: u32, trade: &FlowTrade) {
+        if let Some(queue) = &self.queue_positions {
+            queue.for_market(&market_id.to_string(), |estimator| {
+                estimator.on_trade(trade)
+            });
+        }
+    }
+
+    /// Settings read on every use, see [`RuntimeConfig`]
+    pub fn runtime_config(&self) -> &RuntimeConfig {
+        &self.runtime_config
+    }
+
+    /// Change the runtime settings; the next book update is delivered under
+    /// them
+    ///
+    /// Nothing changes if the result is invalid.
+    pub fn update_config(&self, f: impl FnOnce(&mut ClientRuntimeConfig)) -> Result<()> {
+        self.runtime_config.update(f)
+    }
+
+    /// Sync state of a subscribed market's book
+    ///
+    /// Books are [`Desynced`](BookSyncState::Desynced) until their first
+    /// snapshot. `None` for markets that aren't subscribed.
+    pub fn sync_state(&self, market_id: u32) -> Option<BookSyncState> {
+        if !self.order_book_ids.contains(&market_id) {
+            return None;
+        }
+        let sequences = self.order_book_sequences.lock().unwrap();
+        Some(
+            sequences
+                .get(&market_id.to_string())
+                .map_or(BookSyncState::Desynced, |s| s.state),
+        )
+    }
+
+    /// Code of the last order book message the server sent with a non-zero `code`
+    ///
+    /// Such messages are reported as [`WsEvent::OrderBookError`] and never
+    /// applied, so the book keeps its previous contents. Cleared by the next
+    /// snapshot that is applied.
+    pub fn book_error_code(&self, market_id: u32) -> Option<i64> {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .get(&market_id.to_string())
+            .and_then(|s| s.last_error_code)
+    }
+
+    /// Repeated and stale snapshots of a market's book; `None` before its first snapshot
+    pub fn snapshot_counts(&self, market_id: u32) -> Option<SnapshotCounts> {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .get(&market_id.to_string())
+            .map(|s| s.snapshots)
+    }
+
+    /// Server and local timing of a market's book; `None` before its first snapshot
+    pub fn book_timing(&self, market_id: u32) -> Option<BookTiming> {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .get(&market_id.to_string())
+            .map(|s| s.timing)
+    }
+
+    /// Compare the local book of a market with a REST snapshot
+    ///
+    /// Resyncs the book when `policy` asks for it and the diff exceeds it.
+    pub async fn verify_against_rest(
+        &self,
+        market_id: u32,
+        http: &HTTPClient,
+        policy: &VerifyPolicy,
+    ) -> Result<BookDiff> {
+        let key = market_id.to_string();
+        let rest = http.get_order_book(market_id, REST_BOOK_DEPTH).await?;
+        // Read the book and its offset together so they match
+        let (local, local_offset) = {
+            let states = self.order_book_states.read().unwrap();
+            let local = states.get(&key).cloned().ok_or_else(|| {
+                LighterError::ValidationError(format!(
+                    "No local order book for market {}",
+                    market_id
+                ))
+            })?;
+            let sequences = self.order_book_sequences.lock().unwrap();
+            (local, sequences.get(&key).and_then(|s| s.offset))
+        };
+        let diff = BookDiff::between(&local, &rest.order_book, policy.size_tolerance)
+            .with_offsets(local_offset, rest.offset);
+
+        if policy.auto_resync && policy.is_exceeded_by(&diff) {
+            self.resync_order_book(market_id)?;
+        }
+        Ok(diff)
+    }
+
+    /// Ask the server for a fresh snapshot of a market's book
+    ///
+    /// The running loop unsubscribes and resubscribes `order_book/{id}` on
+    /// the current connection. Updates are dropped until the new snapshot
+    /// replaces the book. Handlers registered with
+    /// [`on_ws_event`](Self::on_ws_event) get
+    /// [`WsEvent::OrderBookSync`] when the resync starts and completes.
+    pub fn resync_order_book(&self, market_id: u32) -> Result<()> {
+        if !self.order_book_ids.contains(&market_id) {
+            return Err(LighterError::ValidationError(format!(
+                "Order book {} is not subscribed",
+                market_id
+            )));
+        }
+        self.mark_resyncing(market_id);
+        self.commands
+            .send(WsCommand::Resync(market_id))
+            .map_err(|_| LighterError::Other("WebSocket command channel closed".to_string()))
+    }
+
+    /// Drop updates of a market's book until its next snapshot
+    fn mark_resyncing(&self, market_id: u32) {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .entry(market_id.to_string())
+            .or_default()
+            .state = BookSyncState::Resyncing;
+    }
+
+    /// Run the WebSocket client with callbacks
+    ///
+    /// # Arguments
+    /// * `on_order_book_update` - Callback for order book updates (market_id, order_book)
+    /// * `on_account_update` - Callback for account updates (account_id, account_data)
+    ///
+    /// Handlers registered with [`on_order_book`](Self::on_order_book) and
+    /// [`on_any_order_book`](Self::on_any_order_book) run before
+    /// `on_order_book_update`. A panicking callback is caught, counted in
+    /// [`suppressed_panics`](Self::suppressed_panics) and handled according
+    /// to the [`CallbackErrorPolicy`]. Where callbacks run is set with
+    /// [`WsClientBuilder::callback_dispatch`].
+    pub async fn run<F1, F2>(&self, on_order_book_update: F1, on_account_update: F2) -> Result<()>
+    where
+        F1: Fn(String, OrderBook) + Send + Sync + 'static,
+        F2: Fn(String, Value) + Send + Sync + 'static,
+    {
+        self.run_with_results(
+            move |market_id, order_book| {
+                on_order_book_update(market_id, order_book);
+                Ok::<(), LighterError>(())
+            },
+            move |account_id, account| {
+                on_account_update(account_id, account);
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Like [`run`](Self::run), with callbacks that can fail
+    ///
+    /// Callback errors and panics are handled according to the
+    /// [`CallbackErrorPolicy`] set on the builder: logged, returned from
+    /// here, or answered with a fresh connection.
+    pub async fn run_with_results<F1, F2, E>(
+        &self,
+        on_order_book_update: F1,
+        on_account_update: F2,
+    ) -> Result<()>
+    where
+        F1: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        F2: Fn(String, Value) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        let on_order_book_update = Arc::new(on_order_book_update);
+        let on_account_update = Arc::new(on_account_update);
+        loop {
+            match self
+                .run_connection(&on_order_book_update, &on_account_update)
+                .await?
+            {
+                ConnectionEnd::Closed => return Ok(()),
+                ConnectionEnd::Reconnect => println!("Reconnecting to {}", self.base_url),
+            }
+        }
+    }
+
+    /// Connect once and process messages until the stream ends
+    async fn run_connection<F1, F2, E>(
+        &self,
+        on_order_book_update: &Arc<F1>,
+        on_account_update: &Arc<F2>,
+    ) -> Result<ConnectionEnd>
+    where
+        F1: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        F2: Fn(String, Value) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        // The TCP connection is opened here so its socket options apply
+        let connect = async {
+            let request = self.base_url.as_str().into_client_request()?;
+            let uri = request.uri();
+            let host = uri.host().unwrap_or_default();
+            let host = host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_string();
+            let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+                Some("wss") => 443,
+                _ => 80,
+            });
+            let stream = self.config.tcp.connect(&host, port).await?;
+            client_async_tls_with_config(
+                request,
+                stream,
+                Some(self.config.websocket_config()),
+                self.config.tls_connector.clone(),
+            )
+            .await
+        };
+        let (ws_stream, _) = tokio::time::timeout(self.config.connect_timeout, connect)
+            .await
+            .map_err(|_| {
+                LighterError::InvalidConfiguration(format!(
+                    "WebSocket connection to {} timed out after {:?}",
+                    self.base_url, self.config.connect_timeout
+                ))
+            })?
+            .map_err(|e| {
+                LighterError::InvalidConfiguration(format!("WebSocket connection failed: {}", e))
+            })?;
+
+        println!("✓ WebSocket connected to {}", self.base_url);
+        self.connected.send_replace(true);
+        let _connected = ConnectedGuard(&self.connected);
+
+        let (mut write, read) = ws_stream.split();
+        let mut frames = frame_queue::spawn_reader(
+            read,
+            self.frame_queue_capacity,
+            self.frame_overflow,
+            self.frame_queue.clone(),
+            self.raw_tap.clone().map(|tx| RawTap {
+                tx,
+                dropped: self.raw_tap_dropped.clone(),
+            }),
+        );
+        let processor = self.processor();
+        // Only one concurrent run takes commands
+        let mut commands = self.command_rx.try_lock().ok();
+
+        let mut ping = self
+            .config
+            .ping_interval
+            .map(|period| tokio::time::interval_at(tokio::time::Instant::now() + period, period));
+
+        processor.early_updates.lock().unwrap().reset();
+
+        // Nothing is subscribed before the server's hello, or before this
+        // point when subscribing eagerly, so the stream's account snapshots
+        // always arrive after these
+        for account in self.bootstrap_accounts(&processor).await {
+            let delivered = self.deliver_account(account, on_account_update).await;
+            if let Err(e) = delivered {
+                let _ = write.close().await;
+                return self.callback_failed(e);
+            }
+        }
+
+        let mut hello_deadline = None;
+        if self.config.eager_subscribe {
+            processor.expect_snapshots();
+            self.subscriptions.reset();
+            self.send_subscriptions(&mut write).await?;
+        } else if let Some(timeout) = self.config.handshake_timeout {
+            hello_deadline = Some(tokio::time::Instant::now() + timeout);
+        }
+
+        // Message handling loop
+        loop {
+            let flush_at = self.notifier.next_flush();
+            let handled = tokio::select! {
+                frame = frames.recv() => match frame {
+                    Some(Frame::Text { text, parsed, received_at }) => {
+                        let dispatch = processor.process_parsed(&text, parsed, received_at).await?;
+                        if matches!(dispatch, Some(Dispatch::Connected)) {
+                            hello_deadline = None;
+                            if self.config.eager_subscribe {
+                                println!("✓ WebSocket connection established (already subscribed)");
+                            } else {
+                                println!("✓ WebSocket connection established");
+                                self.subscriptions.reset();
+                                self.send_subscriptions(&mut write).await?;
+                            }
+                        }
+                        let mut handled = self
+                            .handle_dispatch(dispatch, on_order_book_update, on_account_update)
+                            .await;
+                        // Updates that raced ahead of an ack follow its snapshot
+                        while handled.is_ok() {
+                            let Some((parsed, received_at)) = processor.next_released() else {
+                                break;
+                            };
+                            let text = parsed.to_string();
+                            let dispatch = processor.process_parsed(&text, Ok(parsed), received_at).await?;
+                            handled = self
+                                .handle_dispatch(dispatch, on_order_book_update, on_account_update)
+                                .await;
+                        }
+                        handled
+                    }
+                    Some(Frame::Skipped(what)) => {
+                        self.skip_message(format_args!("{}", what));
+                        continue;
+                    }
+                    Some(Frame::Overflowed { dropped }) => {
+                        self.resync_after_overflow(dropped, &mut write).await
+                    }
+                    Some(Frame::Error(e)) => return Err(self.stream_error(e)),
+                    None => break,
+                },
+                _ = async { ping.as_mut().unwrap().tick().await }, if ping.is_some() => {
+                    // A failed ping means the connection is going away; the
+                    // read side reports the actual error or end of stream
+                    if let Err(e) = write.send(Message::Ping(Vec::new())).await {
+                        eprintln!("WebSocket ping failed: {}", e);
+                    }
+                    continue;
+                }
+                Some(command) = async { commands.as_mut().unwrap().recv().await }, if commands.is_some() => {
+                    let event = self.handle_command(command, &mut write).await?;
+                    self.deliver_ws_event(event).await
+                }
+                _ = async { tokio::time::sleep_until(flush_at.unwrap().into()).await }, if flush_at.is_some() => {
+                    self.flush_held_books(false, on_order_book_update).await
+                }
+                _ = async { tokio::time::sleep_until(hello_deadline.unwrap()).await }, if hello_deadline.is_some() => {
+                    let _ = write.close().await;
+                    return Err(LighterError::InvalidConfiguration(format!(
+                        "No connected message from {} within {:?}; set WsConfig::eager_subscribe \
+                         for servers that don't send one",
+                        self.base_url,
+                        self.config.handshake_timeout.unwrap_or_default()
+                    )));
+                }
+            };
+
+            if let Err(e) = handled {
+                let _ = write.close().await;
+                return self.callback_failed(e);
+            }
+        }
+
+        // Don't leave the latest book held back when the stream ends
+        if let Err(e) = self.flush_held_books(true, on_order_book_update).await {
+            return self.callback_failed(e);
+        }
+        Ok(ConnectionEnd::Closed)
+    }
+
+    /// Deliver one processed message to its callbacks
+    ///
+    /// The server's hello is handled by the run loop.
+    async fn handle_dispatch<F1, F2, E>(
+        &self,
+        dispatch: Option<Dispatch>,
+        on_order_book_update: &Arc<F1>,
+        on_account_update: &Arc<F2>,
+    ) -> Result<()>
+    where
+        F1: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        F2: Fn(String, Value) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        match dispatch {
+            Some(Dispatch::OrderBook(market_id, order_book)) => {
+                let order_book = self.displayed(&market_id, order_book);
+                self.deliver_order_book(market_id, order_book, on_order_book_update)
+                    .await
+            }
+            Some(Dispatch::Resynced(market_id, order_book)) => {
+                let order_book = self.displayed(&market_id, order_book);
+                let event = WsEvent::OrderBookSync {
+                    market_id: market_id.clone(),
+                    state: BookSyncState::Synced,
+                };
+                match self
+                    .deliver_order_book(market_id, order_book, on_order_book_update)
+                    .await
+                {
+                    Ok(()) => self.deliver_ws_event(event).await,
+                    Err(e) => Err(e),
+                }
+            }
+            Some(Dispatch::Account(account)) => {
+                self.deliver_account(account, on_account_update).await
+            }
+            Some(Dispatch::Events(events)) => {
+                let mut delivered = Ok(());
+                for event in events {
+                    if !matches!(event, WsEvent::Liquidation(_)) {
+                        eprintln!("WebSocket stream event: {:?}", event);
+                    }
+                    delivered = self.deliver_ws_event(event).await;
+                    if delivered.is_err() {
+                        break;
+                    }
+                }
+                delivered
+            }
+            Some(Dispatch::Connected) | None => Ok(()),
+        }
+    }
+
+    /// Deliver the books whose coalesced updates are due, or all held books
+    async fn flush_held_books<F, E>(&self, all: bool, callback: &Arc<F>) -> Result<()>
+    where
+        F: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        for market_id in self.notifier.take_due(Instant::now(), all) {
+            if let Some(order_book) = self.book(&market_id) {
+                let order_book = self.displayed(&market_id, OrderBook::clone(&order_book));
+                self.deliver_order_book(market_id, order_book, callback)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count and report a message the run loop can't process
+    fn skip_message(&self, what: std::fmt::Arguments<'_>) {
+        let skipped = self.skipped_messages.fetch_add(1, Ordering::Relaxed) + 1;
+        eprintln!("Skipped WebSocket {} ({} skipped so far)", what, skipped);
+    }
+
+    /// End a connection after a failed callback, per the error policy
+    fn callback_failed(&self, error: LighterError) -> Result<ConnectionEnd> {
+        if self.callback_error_policy == CallbackErrorPolicy::Reconnect {
+            eprintln!("WebSocket callback failed: {}; reconnecting", error);
+            return Ok(ConnectionEnd::Reconnect);
+        }
+        Err(error)
+    }
+
+    /// Load accounts without stream data over REST, if bootstrapping is on
+    ///
+    /// Failures are logged and skipped; the stream snapshot follows anyway.
+    async fn bootstrap_accounts(&self, processor: &MessageProcessor) -> Vec<AccountMessage> {
+        let Some(http) = &self.bootstrap else {
+            return Vec::new();
+        };
+        let with_orders = http.auth_token().is_ok();
+
+        let mut loaded = Vec::new();
+        for account_index in &self.account_ids {
+            let account_id = account_index.to_string();
+            if self
+                .account_snapshots
+                .read()
+                .await
+                .contains_key(account_index)
+            {
+                continue;
+            }
+            let snapshot = match AccountIndex::new(*account_index) {
+                Ok(account_index) => http.get_account_snapshot(account_index).await,
+                Err(e) => Err(e),
+            };
+            let mut snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    eprintln!("Failed to bootstrap account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+            if with_orders {
+                for market_id in &self.order_book_ids {
+                    let orders = match (
+                        AccountIndex::new(*account_index),
+                        market_index_of(*market_id),
+                    ) {
+                        (Ok(account_index), Ok(market_index)) => {
+                            http.get_active_orders(account_index, market_index).await
+                        }
+                        (Err(e), _) | (_, Err(e)) => Err(e),
+                    };
+                    match orders {
+                        Ok(orders) => {
+                            snapshot.orders.insert(market_id.to_string(), orders);
+                        }
+                        Err(e) => eprintln!(
+                            "Failed to bootstrap orders of account {} in market {}: {}",
+                            account_id, market_id, e
+                        ),
+                    }
+                }
+            }
+
+            let mut message = match serde_json::to_value(&snapshot) {
+                Ok(message) => message,
+                Err(e) => {
+                    eprintln!("Failed to bootstrap account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+            message["type"] = Value::from("bootstrap/account_all");
+            message["channel"] = Value::from(Channel::AccountAll(*account_index).key());
+            let loaded_account = match processor
+                .apply_account_message(*account_index, message, true)
+                .await
+            {
+                Ok(account) => account,
+                Err(e) => {
+                    eprintln!("Failed to bootstrap account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+            println!("✓ Bootstrapped account {} over REST", account_id);
+            loaded.push(loaded_account);
+        }
+        loaded
+    }
+
+    /// Describe a read error, naming the configured limit for oversized messages
+    fn stream_error(&self, error: WsError) -> LighterError {
+        match error {
+            WsError::Capacity(CapacityError::MessageTooLong { size, max_size }) => {
+                LighterError::InvalidResponse(format!(
+                    "WebSocket message of {} bytes exceeds the configured limit of {} bytes \
+                     (max_message_size {:?}, max_frame_size {:?})",
+                    size, max_size, self.config.max_message_size, self.config.max_frame_size
+                ))
+            }
+            e => LighterError::InvalidResponse(format!("WebSocket error: {}", e)),
+        }
+    }
+
+    /// Carry out a caller's request on the live connection
+    ///
+    /// Returns the event to report to stream event handlers.
+    async fn handle_command<S>(&self, command: WsCommand, write: &mut S) -> Result<WsEvent>
+    where
+        S: futures_util::Sink<Message, Error = WsError> + Unpin,
+    {
+        match command {
+            WsCommand::Resync(market_id) => {
+                let channel = Channel::OrderBook(market_id);
+                self.subscriptions.mark_pending(&channel.key());
+                for request in [
+                    WsRequest::Unsubscribe { channel },
+                    WsRequest::Subscribe { channel },
+                ] {
+                    send_request(write, &request).await?;
+                }
+                println!("  → Resubscribed to {}", channel);
+                Ok(WsEvent::OrderBookSync {
+                    market_id: market_id.to_string(),
+                    state: BookSyncState::Resyncing,
+                })
+            }
+        }
+    }
+
+    /// Resubscribe every channel after frames were dropped on a full queue
+    ///
+    /// Books drop updates until their new snapshot, and account snapshots
+    /// replace what the dropped frames would have changed.
+    async fn resync_after_overflow<S>(&self, dropped: u64, write: &mut S) -> Result<()>
+    where
+        S: futures_util::Sink<Message, Error = WsError> + Unpin,
+    {
+        eprintln!(
+            "Dropped {} WebSocket frames on a full frame queue; resubscribing",
+            dropped
+        );
+        self.frame_queue.count_resync();
+        self.deliver_ws_event(WsEvent::FramesDropped { dropped })
+            .await?;
+        for &market_id in &self.order_book_ids {
+            self.mark_resyncing(market_id);
+            let event = self
+                .handle_command(WsCommand::Resync(market_id), write)
+                .await?;
+            self.deliver_ws_event(event).await?;
+        }
+        let others = channels(
+            &[],
+            &self.account_ids,
+            &self.liquidation_ids,
+            &self.market_stats_ids,
+        );
+        for channel in others {
+            self.subscriptions.mark_pending(&channel.key());
+            for request in [
+                WsRequest::Unsubscribe { channel },
+                WsRequest::Subscribe { channel },
+            ] {
+                send_request(write, &request).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the configured subscriptions after the server says hello
+    async fn send_subscriptions<S>(&self, write: &mut S) -> Result<()>
+    where
+        S: futures_util::Sink<Message, Error = WsError> + Unpin,
+    {
+        let channels = channels(
+            &self.order_book_ids,
+            &self.account_ids,
+            &self.liquidation_ids,
+            &self.market_stats_ids,
+        );
+        for channel in channels {
+            send_request(write, &WsRequest::Subscribe { channel }).await?;
+            println!("  → Subscribed to {}", channel);
+        }
+
+        Ok(())
+    }
+
+    /// Invoke registered order book handlers for a market
+    fn dispatch_order_book(&self, market_id: &str, order_book: &OrderBook) -> Result<()> {
+        for handler in self.order_book_handlers(market_id) {
+            self.callback_outcome(self.call_isolated(|| {
+                handler(market_id.to_string(), order_book.clone());
+                Ok(())
+            }))?;
+        }
+        Ok(())
+    }
+
+    /// Handlers of a market, cloned so the lock isn't held while they run
+    fn order_book_handlers(&self, market_id: &str) -> Vec<OrderBookHandler> {
+        let registry = self.handlers.lock().unwrap();
+        registry
+            .by_market
+            .get(market_id)
+            .into_iter()
+            .flatten()
+            .chain(registry.any.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Invoke registered account event handlers, then the account's typed handlers
+    fn dispatch_account(&self, account: &AccountMessage) -> Result<()> {
+        let (handlers, typed) = self.account_handlers(account.account_index);
+        let account_id = account.account_index.to_string();
+        for event in &account.events {
+            for handler in &handlers {
+                self.callback_outcome(self.call_isolated(|| {
+                    handler(account_id.clone(), event.clone());
+                    Ok(())
+                }))?;
+            }
+        }
+        for update in account.updates() {
+            for handler in &typed {
+                self.callback_outcome(self.call_isolated(|| {
+                    handler(account.account_index, update.clone());
+                    Ok(())
+                }))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Event handlers and the typed handlers of an account, cloned so the
+    /// lock isn't held while they run
+    fn account_handlers(
+        &self,
+        account_index: i64,
+    ) -> (Vec<AccountEventHandler>, Vec<AccountHandler>) {
+        let registry = self.handlers.lock().unwrap();
+        let typed = match registry.by_account.get(&account_index) {
+            Some(handlers) if !handlers.is_empty() => handlers.clone(),
+            _ => registry.other_accounts.clone(),
+        };
+        (registry.account_events.clone(), typed)
+    }
+
+    /// Invoke registered stream event handlers
+    fn dispatch_ws_event(&self, event: WsEvent) -> Result<()> {
+        let handlers = self.handlers.lock().unwrap().ws_events.clone();
+        for handler in &handlers {
+            self.callback_outcome(self.call_isolated(|| {
+                handler(event.clone());
+                Ok(())
+            }))?;
+        }
+        Ok(())
+    }
+
+    /// Pass a book to its handlers and the `run` callback, per the dispatch mode
+    async fn deliver_order_book<F, E>(
+        &self,
+        market_id: String,
+        order_book: OrderBook,
+        callback: &Arc<F>,
+    ) -> Result<()>
+    where
+        F: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        if self.dispatcher.is_inline() {
+            self.dispatch_order_book(&market_id, &order_book)?;
+            return self.callback_outcome(
+                self.call_isolated(|| callback(market_id, order_book).map_err(Into::into)),
+            );
+        }
+
+        let handlers = self.order_book_handlers(&market_id);
+        let callback = callback.clone();
+        let panics = self.suppressed_panics.clone();
+        let key = format!("order_book/{}", market_id);
+        let job: Job = Box::new(move || {
+            for handler in handlers {
+                run_queued(&panics, || {
+                    handler(market_id.clone(), order_book.clone());
+                    Ok(())
+                });
+            }
+            run_queued(&panics, || {
+                callback(market_id, order_book).map_err(Into::into)
+            });
+        });
+        self.dispatcher.submit(&key, job).await;
+        Ok(())
+    }
+
+    /// Pass account events and the message on, per the dispatch mode
+    async fn deliver_account<F, E>(&self, account: AccountMessage, callback: &Arc<F>) -> Result<()>
+    where
+        F: Fn(String, Value) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        let account_id = account.account_index.to_string();
+        if self.dispatcher.is_inline() {
+            self.dispatch_account(&account)?;
+            return self.callback_outcome(
+                self.call_isolated(|| callback(account_id, account.raw).map_err(Into::into)),
+            );
+        }
+
+        let (handlers, typed) = self.account_handlers(account.account_index);
+        let callback = callback.clone();
+        let panics = self.suppressed_panics.clone();
+        let key = format!("account_all/{}", account_id);
+        let job: Job = Box::new(move || {
+            for event in &account.events {
+                for handler in &handlers {
+                    run_queued(&panics, || {
+                        handler(account_id.clone(), event.clone());
+                        Ok(())
+                    });
+                }
+            }
+            for update in account.updates() {
+                for handler in &typed {
+                    run_queued(&panics, || {
+                        handler(account.account_index, update.clone());
+                        Ok(())
+                    });
+                }
+            }
+            run_queued(&panics, || {
+                callback(account_id, account.raw).map_err(Into::into)
+            });
+        });
+        self.dispatcher.submit(&key, job).await;
+        Ok(())
+    }
+
+    /// Pass a stream event to its handlers, per the dispatch mode
+    async fn deliver_ws_event(&self, event: WsEvent) -> Result<()> {
+        if self.dispatcher.is_inline() {
+            return self.dispatch_ws_event(event);
+        }
+
+        let handlers = self.handlers.lock().unwrap().ws_events.clone();
+        let panics = self.suppressed_panics.clone();
+        let job: Job = Box::new(move || {
+            for handler in &handlers {
+                run_queued(&panics, || {
+                    handler(event.clone());
+                    Ok(())
+                });
+            }
+        });
+        self.dispatcher.submit("events", job).await;
+        Ok(())
+    }
+
+    /// Run a callback, counting panics and turning them into errors
+    fn call_isolated<F: FnOnce() -> Result<()>>(&self, f: F) -> Result<()> {
+        call_isolated(&self.suppressed_panics, f)
+    }
+
+    /// Apply the callback error policy; an error left over ends the connection
+    fn callback_outcome(&self, result: Result<()>) -> Result<()> {
+        match result {
+            Err(e) if self.callback_error_policy == CallbackErrorPolicy::LogAndContinue => {
+                eprintln!("WebSocket callback failed: {}; continuing", e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn processor(&self) -> MessageProcessor {
+        MessageProcessor {
+            order_book_states: self.order_book_states.clone(),
+            raw_account_states: self.raw_account_states.clone(),
+            account_snapshots: self.account_snapshots.clone(),
+            state_generation: self.state_generation.clone(),
+            account_retention: self.account_retention.clone(),
+            strict_parsing: self.strict_parsing,
+            subscriptions: self.subscriptions.clone(),
+            order_book_sequences: self.order_book_sequences.clone(),
+            liquidations: self.liquidations.clone(),
+            market_statuses: self.market_statuses.clone(),
+            skipped_updates: self.skipped_updates.clone(),
+            malformed_messages: self.malformed_messages.clone(),
+            notifier: self.notifier.clone(),
+            queue_positions: self.queue_positions.clone(),
+            early_updates: self.early_updates.clone(),
+        }
+    }
+
+    /// Update order book state with incremental updates
+    ///
+    /// Returns the number of levels that changed. The whole update is
+    /// checked first, so a malformed one leaves the book untouched.
+    fn update_order_book_state(existing: &mut OrderBook, update: &Value) -> Result<usize> {
+        let asks = level_updates(update, "asks")?;
+        let bids = level_updates(update, "bids")?;
+
+        let mut changed = 0;
+        for ask in asks {
+            changed += Self::update_price_levels(&mut existing.asks, ask)? as usize;
+        }
+        for bid in bids {
+            changed += Self::update_price_levels(&mut existing.bids, bid)? as usize;
+        }
+
+        // Remove zero-size levels
+        existing
+            .asks
+            .retain(|level| level.size.parse::<f64>().unwrap_or(0.0) > 0.0);
+        existing
+            .bids
+            .retain(|level| level.size.parse::<f64>().unwrap_or(0.0) > 0.0);
+
+        Ok(changed)
+    }
+
+    /// Update a specific price level; returns whether the book changed
+    fn update_price_levels(levels: &mut Vec<PriceLevel>, update: &Value) -> Result<bool> {
+        let (price, size) = level_fields(update)?;
+
+        // Find existing level
+        for level in levels.iter_mut() {
+            if level.price == price {
+                let changed = level.size != size;
+                level.size = size.to_string();
+                return Ok(changed);
+            }
+        }
+
+        // Add new level if not found and size > 0
+        if size.parse::<f64>().unwrap_or(0.0) > 0.0 {
+            levels.push(PriceLevel {
+                price: price.to_string(),
+                size: size.to_string(),
+            });
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    /// Get current order book state for a market
+    pub async fn get_order_book(&self, market_id: &str) -> Option<OrderBook> {
+        let order_book = self.book(market_id)?;
+        Some(self.displayed(market_id, OrderBook::clone(&order_book)))
+    }
+
+    /// Shared current book of a market, without copying it
+    fn book(&self, market_id: &str) -> Option<Arc<OrderBook>> {
+        self.order_book_states
+            .read()
+            .unwrap()
+            .get(market_id)
+            .cloned()
+    }
+
+    /// Get the typed account snapshot merged from all messages so far
+    ///
+    /// The snapshot is shared, not copied; later messages replace it
+    /// without changing the one returned.
+    pub async fn get_account(&self, account_id: &str) -> Option<Arc<AccountSnapshot>> {
+        let account_index = account_id.parse::<i64>().ok()?;
+        self.account_snapshots
+            .read()
+            .await
+            .get(&account_index)
+            .cloned()
+    }
+
+    /// Owned copy of the account snapshot, see [`get_account`](Self::get_account)
+    pub async fn get_account_snapshot(&self, account_id: &str) -> Option<AccountSnapshot> {
+        self.get_account(account_id)
+            .await
+            .map(|snapshot| AccountSnapshot::clone(&snapshot))
+    }
+
+    /// Read a market's book together with an account's orders and position
+    ///
+    /// The parts sit behind separate locks, so the read is retried until no
+    /// message was applied while it ran; every part then reflects the same
+    /// applied messages. Each lock is released before the next is taken and
+    /// handlers never run while a message is being applied, so calling this
+    /// from a handler is safe. The book is shared with the client rather
+    /// than copied, unless a display scale applies. Returns `None` if the
+    /// market isn't subscribed.
+    pub async fn market_view(&self, market_id: u32, account_id: &str) -> Option<MarketView> {
+        if !self.order_book_ids.contains(&market_id) {
+            return None;
+        }
+        let market = market_id.to_string();
+        let account_index = account_id.parse::<i64>().ok();
+        let mut view = loop {
+            let Some(generation) = self.state_generation.settled() else {
+                tokio::task::yield_now().await;
+                continue;
+            };
+            let book = self.order_book_states.read().unwrap().get(&market).cloned();
+            let (offset, last_error_code) = self
+                .order_book_sequences
+                .lock()
+                .unwrap()
+                .get(&market)
+                .map_or((None, None), |s| (s.offset, s.last_error_code));
+            let account = match account_index {
+                Some(index) => self.account_snapshots.read().await.get(&index).cloned(),
+                None => None,
+            };
+            let queue_positions = match (&self.queue_positions, account_index) {
+                (Some(queue), Some(index)) => queue.estimates(&market, index),
+                _ => HashMap::new(),
+            };
+            if self.state_generation.unchanged_since(generation) {
+                break MarketView {
+                    market_id,
+                    book,
+                    offset,
+                    last_error_code,
+                    my_orders: account
+                        .as_ref()
+                        .and_then(|a| a.orders.get(&market))
+                        .cloned()
+                        .unwrap_or_default(),
+                    position: account.and_then(|a| a.positions.get(&market).cloned()),
+                    queue_positions,
+                    as_of: Instant::now(),
+                };
+            }
+            tokio::task::yield_now().await;
+        };
+        view.book = view.book.map(|book| self.displayed_shared(&market, book));
+        Some(view)
+    }
+
+    /// Get the latest raw message of an account
+    ///
+    /// Only kept with [`WsClientBuilder::retain_raw_accounts`]; `None`
+    /// otherwise.
+    pub async fn get_raw_account(&self, account_id: &str) -> Option<Value> {
+        let account_index = account_id.parse::<i64>().ok()?;
+        self.raw_account_states
+            .as_ref()?
+            .read()
+            .await
+            .get(&account_index)
+            .cloned()
+    }
+}
+
+/// Raw WebSocket frame as received, for recording and replay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawWsMessage {
+    /// Local time the frame was read from the socket
+    pub received_at: DateTime<Utc>,
+    /// Unparsed frame text
+    pub text: String,
+}
+
+/// Receiver of parsed stream updates, used by [`replay_messages`]
+pub trait WsHandler {
+    /// Called with the full order book after every snapshot or update
+    fn on_order_book_update(&mut self, _market_id: String, _order_book: OrderBook) {}
+
+    /// Called with the raw account message after every snapshot or update
+    fn on_account_update(&mut self, _account_id: String, _account: Value) {}
+
+    /// Called for every event derived from an account message
+    fn on_account_event(&mut self, _account_id: String, _event: AccountEvent) {}
+}
+
+/// Outcome of processing one message
+enum Dispatch {
+    Connected,
+    OrderBook(String, OrderBook),
+    /// Snapshot that completed a requested resync
+    Resynced(String, OrderBook),
+    Account(AccountMessage),
+    Events(Vec<WsEvent>),
+}
+
+/// An applied account message
+struct AccountMessage {
+    account_index: i64,
+    /// The message as received
+    raw: Value,
+    /// Typed state after the message; `None` if no message of the account
+    /// has parsed yet
+    snapshot: Option<Arc<AccountSnapshot>>,
+    events: Vec<AccountEvent>,
+}
+
+impl AccountMessage {
+    /// What typed account handlers receive: the events, then the snapshot
+    fn updates(&self) -> impl Iterator<Item = AccountUpdate> + '_ {
+        self.events
+            .iter()
+            .cloned()
+            .map(AccountUpdate::Event)
+            .chain(self.snapshot.clone().map(AccountUpdate::Snapshot))
+    }
+}
+
+/// Message parsing and state application shared by `run` and replay
+struct MessageProcessor {
+    order_book_states: BookStates,
+    raw_account_states: Option<Arc<RwLock<HashMap<i64, Value>>>>,
+    account_snapshots: Arc<RwLock<HashMap<i64, Arc<AccountSnapshot>>>>,
+    state_generation: Arc<StateGeneration>,
+    account_retention: Option<Arc<AccountRetention>>,
+    strict_parsing: bool,
+    subscriptions: Arc<Subscriptions>,
+    order_book_sequences: Arc<Mutex<HashMap<String, BookSequence>>>,
+    liquidations: Arc<Mutex<LiquidationBuffer>>,
+    market_statuses: MarketStatuses,
+    skipped_updates: Arc<AtomicU64>,
+    malformed_messages: Arc<AtomicU64>,
+    notifier: Arc<BookNotifier>,
+    queue_positions: Option<Arc<QueuePositions>>,
+    early_updates: Arc<Mutex<EarlyUpdates>>,
+}
+
+impl MessageProcessor {
+    fn new() -> Self {
+        Self {
+            order_book_states: Arc::default(),
+            raw_account_states: None,
+            account_snapshots: Arc::new(RwLock::new(HashMap::new())),
+            state_generation: Arc::default(),
+            account_retention: None,
+            strict_parsing: false,
+            subscriptions: Arc::new(Subscriptions::new(Vec::new())),
+            order_book_sequences: Arc::new(Mutex::new(HashMap::new())),
+            liquidations: Arc::new(Mutex::new(LiquidationBuffer::new(
+                DEFAULT_LIQUIDATION_BUFFER,
+            ))),
+            market_statuses: MarketStatuses::new(),
+            skipped_updates: Arc::new(AtomicU64::new(0)),
+            malformed_messages: Arc::new(AtomicU64::new(0)),
+            notifier: Arc::new(BookNotifier::default()),
+            queue_positions: None,
+            early_updates: Arc::default(),
+        }
+    }
+
+    /// Apply an update unless its offset isn't newer than the book's
+    ///
+    /// Updates without an offset are always applied.
+    fn apply_update(
+        &self,
+        market_id: &str,
+        existing: &mut OrderBook,
+        update: &Value,
+        offset: Option<i64>,
+    ) -> Result<ApplyResult> {
+        let mut sequences = self.order_book_sequences.lock().unwrap();
+        let sequence = sequences.entry(market_id.to_string()).or_default();
+
+        if let (Some(update_offset), Some(book_offset)) = (offset, sequence.offset) {
+            if update_offset <= book_offset {
+                self.skipped_updates.fetch_add(1, Ordering::Relaxed);
+                sequence.consecutive_skipped += 1;
+                if sequence.consecutive_skipped == STALE_UPDATE_LOG_THRESHOLD {
+                    sequence.state = BookSyncState::Desynced;
+                    eprintln!(
+                        "Skipped {} consecutive stale updates for order book {} (book offset {}); the subscription may have restarted",
+                        sequence.consecutive_skipped, market_id, book_offset
+                    );
+                }
+                return Ok(ApplyResult::SkippedStale {
+                    update_offset,
+                    book_offset,
+                });
+            }
+        }
+
+        let levels_changed = WsClient::update_order_book_state(existing, update)?;
+        sequence.consecutive_skipped = 0;
+        if offset.is_some() {
+            sequence.offset = offset;
+        }
+        Ok(ApplyResult::Applied { levels_changed })
+    }
+
+    /// Reset a market's offset to that of a fresh snapshot
+    ///
+    /// The sequence is updated in place, so timing and counters carry over.
+    /// A snapshot repeated on the same connection without a resync is
+    /// counted, and dropped if its offset is older than the book's.
+    fn reset_sequence(&self, market_id: &str, offset: Option<i64>) -> SnapshotCheck {
+        let mut sequences = self.order_book_sequences.lock().unwrap();
+        let previous = sequences.get(market_id).map(|s| s.state);
+        let sequence = sequences.entry(market_id.to_string()).or_default();
+        if sequence.snapshot_seen && sequence.state != BookSyncState::Resyncing {
+            if let (Some(offset), Some(book_offset)) = (offset, sequence.offset) {
+                if offset < book_offset {
+                    sequence.snapshots.stale_snapshots += 1;
+                    return SnapshotCheck::Stale { book_offset };
+                }
+            }
+            sequence.snapshots.resnapshots += 1;
+        }
+        sequence.offset = offset;
+        sequence.consecutive_skipped = 0;
+        sequence.state = BookSyncState::Synced;
+        sequence.last_error_code = None;
+        sequence.snapshot_seen = true;
+        SnapshotCheck::Apply(previous)
+    }
+
+    /// Expect a first snapshot of every book on a new connection
+    fn expect_snapshots(&self) {
+        for sequence in self.order_book_sequences.lock().unwrap().values_mut() {
+            sequence.snapshot_seen = false;
+        }
+    }
+
+    /// Record when a market's snapshot or update arrived
+    fn observe_timing(&self, market_id: &str, server_timestamp: Option<u64>, received_at: Instant) {
+        if let Some(sequence) = self.order_book_sequences.lock().unwrap().get_mut(market_id) {
+            sequence.timing.observe(server_timestamp, received_at);
+        }
+    }
+
+    /// Record an order book message that was rejected for its `code`
+    ///
+    /// A market without a book stays desynced.
+    fn record_book_error(&self, market_id: &str, code: i64) {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .entry(market_id.to_string())
+            .or_insert_with(|| BookSequence {
+                state: BookSyncState::Desynced,
+                ..Default::default()
+            })
+            .last_error_code = Some(code);
+    }
+
+    fn is_resyncing(&self, market_id: &str) -> bool {
+        self.order_book_sequences
+            .lock()
+            .unwrap()
+            .get(market_id)
+            .is_some_and(|s| s.state == BookSyncState::Resyncing)
+    }
+
+    /// Next held update released by its channel's ack
+    fn next_released(&self) -> Option<(Value, Instant)> {
+        self.early_updates.lock().unwrap().next_released()
+    }
+
+    /// Parse a message and apply it, as the reader and run loop do
+    async fn process(&self, text: &str) -> Result<Option<Dispatch>> {
+        self.process_parsed(text, serde_json::from_str(text), Instant::now())
+            .await
+    }
+
+    /// Apply a message parsed by the reader to the stored state
+    ///
+    /// A message that can't be parsed or applied is counted and reported as
+    /// [`WsEvent::ParseError`] instead of failing, so one bad payload
+    /// doesn't end the stream.
+    async fn process_parsed(
+        &self,
+        text: &str,
+        parsed: serde_json::Result<Value>,
+        received_at: Instant,
+    ) -> Result<Option<Dispatch>> {
+        let processed = match parsed {
+            Ok(parsed) => self.process_message(parsed, received_at).await,
+            Err(e) => Err(e.into()),
+        };
+        match processed {
+            Ok(dispatch) => Ok(dispatch),
+            Err(e) => {
+                self.malformed_messages.fetch_add(1, Ordering::Relaxed);
+                let channel = serde_json::from_str::<Value>(text).ok().and_then(|parsed| {
+                    parsed
+                        .get("channel")
+                        .and_then(|c| c.as_str())
+                        .map(normalize_channel)
+                });
+                Ok(Some(Dispatch::Events(vec![WsEvent::ParseError {
+                    channel,
+                    error: e.to_string(),
+                    raw: truncate_utf8(text, MALFORMED_RAW_PREFIX_BYTES).to_string(),
+                }])))
+            }
+        }
+    }
+
+    async fn process_message(
+        &self,
+        parsed: Value,
+        received_at: Instant,
+    ) -> Result<Option<Dispatch>> {
+        let _applying = self.state_generation.applying();
+        // Updates racing ahead of their channel's first ack wait for it
+        let early_channel = parsed
+            .get("type")
+            .and_then(|t| t.as_str())
+            .filter(|t| t.starts_with("update/"))
+            .and_then(|_| parsed.get("channel").and_then(|c| c.as_str()))
+            .filter(|channel| self.subscriptions.state(channel).is_some())
+            .map(str::to_string);
+        let parsed = match early_channel {
+            Some(channel) => {
+                let mut early = self.early_updates.lock().unwrap();
+                match early.hold(&channel, parsed, received_at) {
+                    Some(parsed) => parsed,
+                    None => return Ok(None),
+                }
+            }
+            None => parsed,
+        };
+        let msg_type = parsed.get("type").and_then(|t| t.as_str());
+
+        // Order book messages with an error code never touch the book
+        let book_error = order_book_error(&parsed).map(|(market_id, code)| {
+            self.record_book_error(&market_id, code);
+            WsEvent::OrderBookError { market_id, code }
+        });
+
+        // Per-channel errors are recorded and reported, never fatal
+        if let Some(mut error) = WsServerError::from_message(&parsed) {
+            eprintln!("WebSocket server error: {}", error);
+            let failed = self.subscriptions.fail_from_error(&mut error);
+            let events = std::iter::once(WsEvent::ServerError(error))
+                .chain(failed)
+                .chain(book_error)
+                .collect();
+            return Ok(Some(Dispatch::Events(events)));
+        }
+
+        if let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) {
+            if msg_type.is_some_and(|t| t.starts_with("subscribed/")) {
+                self.subscriptions.acknowledge(channel);
+                self.early_updates.lock().unwrap().acknowledge(channel);
+            }
+        }
+
+        if let Some(event) = book_error {
+            eprintln!("Order book message rejected: {:?}", event);
+            return Ok(Some(Dispatch::Events(vec![event])));
+        }
+
+        match msg_type {
+            Some("connected") => {
+                self.expect_snapshots();
+                Ok(Some(Dispatch::Connected))
+            }
+            Some("subscribed/order_book") => {
+                if let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) {
+                    let market_id = &channel_market_id(channel);
+                    if let Some(order_book) = parsed.get("order_book") {
+                        // Deserialized in place; snapshots can be many MB
+                        let ob = OrderBook::deserialize(order_book)?;
+                        // Swap the book and its sequence under the state lock
+                        let mut states = self.order_book_states.write().unwrap();
+                        let offset = message_offset(&parsed);
+                        let previous = match self.reset_sequence(market_id, offset) {
+                            SnapshotCheck::Apply(previous) => previous,
+                            SnapshotCheck::Stale { book_offset } => {
+                                eprintln!(
+                                    "Dropped order book {} snapshot at offset {:?}, older than the book at {}",
+                                    market_id, offset, book_offset
+                                );
+                                return Ok(Some(Dispatch::Events(vec![WsEvent::StaleSnapshot {
+                                    market_id: market_id.to_string(),
+                                    offset: offset.unwrap_or_default(),
+                                    book_offset,
+                                }])));
+                            }
+                        };
+                        self.observe_timing(market_id, message_timestamp(&parsed), received_at);
+                        states.insert(market_id.to_string(), Arc::new(ob.clone()));
+                        if let Some(queue) = &self.queue_positions {
+                            queue.for_market(market_id, |estimator| estimator.on_snapshot(&ob));
+                        }
+                        self.notifier.on_snapshot(market_id, received_at);
+                        if previous == Some(BookSyncState::Resyncing) {
+                            return Ok(Some(Dispatch::Resynced(market_id.to_string(), ob)));
+                        }
+                        return Ok(Some(Dispatch::OrderBook(market_id.to_string(), ob)));
+                    }
+                }
+                Ok(None)
+            }
+            Some("update/order_book") => {
+                if let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) {
+                    let market_id = &channel_market_id(channel);
+                    if self.is_resyncing(market_id) {
+                        return Ok(None);
+                    }
+                    if let Some(update) = parsed.get("order_book") {
+                        let mut states = self.order_book_states.write().unwrap();
+                        if let Some(existing) = states.get_mut(market_id) {
+                            // Copied only if a reader still holds the book
+                            let existing = Arc::make_mut(existing);
+                            let offset = message_offset(&parsed);
+                            let before = self.notifier.top_levels(market_id, existing);
+                            let result = self.apply_update(market_id, existing, update, offset)?;
+                            if let ApplyResult::SkippedStale { .. } = result {
+                                return Ok(None);
+                            }
+                            if let Some(queue) = &self.queue_positions {
+                                let delta = update_delta(update)?;
+                                queue.for_market(market_id, |estimator| estimator.on_delta(&delta));
+                            }
+                            let timestamp = message_timestamp(&parsed);
+                            self.observe_timing(market_id, timestamp, received_at);
+                            let top_changed = before.map(|before| {
+                                self.notifier.top_levels(market_id, existing) != Some(before)
+                            });
+                            if self.notifier.on_update(market_id, top_changed, received_at)
+                                == Notify::Hold
+                            {
+                                return Ok(None);
+                            }
+                            return Ok(Some(Dispatch::OrderBook(
+                                market_id.to_string(),
+                                OrderBook::clone(existing),
+                            )));
+                        }
+                    }
+                }
+                Ok(None)
+            }
+            Some("subscribed/account_all")
+            | Some("update/account_all")
+            | Some("bootstrap/account_all") => {
+                if let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) {
+                    let account_index = channel
+                        .parse::<Channel>()
+                        .ok()
+                        .and_then(|c| c.account_index())
+                        .ok_or_else(|| {
+                            LighterError::InvalidResponse(format!(
+                                "Invalid account channel {}",
+                                channel
+                            ))
+                        })?;
+                    let is_snapshot = msg_type != Some("update/account_all");
+                    let account = self
+                        .apply_account_message(account_index, parsed, is_snapshot)
+                        .await?;
+                    return Ok(Some(Dispatch::Account(account)));
+                }
+                Ok(None)
+            }
+            Some("subscribed/liquidations") | Some("update/liquidations") => {
+                let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) else {
+                    return Ok(None);
+                };
+                let market_id = channel
+                    .parse::<Channel>()
+                    .ok()
+                    .and_then(|c| c.market_id())
+                    .ok_or_else(|| {
+                        LighterError::InvalidResponse(format!(
+                            "Invalid liquidations channel {}",
+                            channel
+                        ))
+                    })?;
+                let events = liquidations::parse_message(&parsed, market_id)?;
+                let mut buffer = self.liquidations.lock().unwrap();
+                let events: Vec<WsEvent> = events
+                    .into_iter()
+                    .filter(|event| buffer.push(event))
+                    .map(WsEvent::Liquidation)
+                    .collect();
+                Ok((!events.is_empty()).then_some(Dispatch::Events(events)))
+            }
+            Some("subscribed/market_stats") | Some("update/market_stats") => {
+                let Some(channel) = parsed.get("channel").and_then(|c| c.as_str()) else {
+                    return Ok(None);
+                };
+                let market_id = channel
+                    .parse::<Channel>()
+                    .ok()
+                    .and_then(|c| c.market_id())
+                    .ok_or_else(|| {
+                        LighterError::InvalidResponse(format!(
+                            "Invalid market_stats channel {}",
+                            channel
+                        ))
+                    })?;
+                let Some(status) = market_status::parse_stats(&parsed)? else {
+                    return Ok(None);
+                };
+                Ok(self
+                    .market_statuses
+                    .set(market_id, status)
+                    .map(|change| Dispatch::Events(vec![WsEvent::MarketStatus(change)])))
+            }
+            _ => {
+                eprintln!("Unhandled message type: {:?}", msg_type);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl MessageProcessor {
+    /// Store an account message and derive its events
+    async fn apply_account_message(
+        &self,
+        account_index: i64,
+        message: Value,
+        is_snapshot: bool,
+    ) -> Result<AccountMessage> {
+        let _applying = self.state_generation.applying();
+        let (snapshot, events) = self
+            .apply_account_snapshot(account_index, &message, is_snapshot)
+            .await?;
+        if let Some(raw) = &self.raw_account_states {
+            raw.write().await.insert(account_index, message.clone());
+        }
+        Ok(AccountMessage {
+            account_index,
+            raw: message,
+            snapshot,
+            events,
+        })
+    }
+
+    /// Merge an account message into the typed snapshot and derive events
+    ///
+    /// Messages that don't parse as an [`AccountSnapshot`] leave the
+    /// snapshot unchanged and produce no events. With strict parsing, a
+    /// message with unknown keys fails instead. A snapshot still held by a
+    /// caller is copied before the merge.
+    async fn apply_account_snapshot(
+        &self,
+        account_index: i64,
+        message: &Value,
+        is_snapshot: bool,
+    ) -> Result<(Option<Arc<AccountSnapshot>>, Vec<AccountEvent>)> {
+        let typed = match AccountSnapshot::deserialize(message) {
+            Ok(mut typed) => {
+                // The envelope, not part of the account
+                typed.extras.remove("type");
+                typed.extras.remove("channel");
+                if self.strict_parsing {
+                    typed.check_strict()?;
+                }
+                typed
+            }
+            Err(e) => {
+                eprintln!("Failed to parse account {} message: {}", account_index, e);
+                let current = self
+                    .account_snapshots
+                    .read()
+                    .await
+                    .get(&account_index)
+                    .cloned();
+                return Ok((current, Vec::new()));
+            }
+        };
+
+        let mut snapshots = self.account_snapshots.write().await;
+        let entry = snapshots.entry(account_index).or_default();
+        let snapshot = Arc::make_mut(entry);
+        let events = snapshot.apply(account_index, typed, is_snapshot);
+        if let Some(retention) = &self.account_retention {
+            snapshot.retain(retention);
+        }
+        if let Some(queue) = &self.queue_positions {
+            queue.for_account(account_index, |market, estimator| {
+                for event in &events {
+                    estimator.on_account_event(event);
+                }
+                let orders = snapshot.orders.get(market).map_or(&[][..], Vec::as_slice);
+                estimator.sync_orders(orders);
+            });
+        }
+        Ok((Some(entry.clone()), events))
+    }
+}
+
+/// Channels of the configured subscriptions, in subscription order
+fn channels<'a>(
+    order_book_ids: &'a [u32],
+    account_ids: &'a [i64],
+    liquidation_ids: &'a [u32],
+    market_stats_ids: &'a [u32],
+) -> impl Iterator<Item = Channel> + 'a {
+    order_book_ids
+        .iter()
+        .map(|id| Channel::OrderBook(*id))
+        .chain(account_ids.iter().map(|id| Channel::AccountAll(*id)))
+        .chain(liquidation_ids.iter().map(|id| Channel::Liquidations(*id)))
+        .chain(market_stats_ids.iter().map(|id| Channel::MarketStats(*id)))
+}
+
+/// Market id of a market channel, `unknown` if the channel isn't one
+fn channel_market_id(channel: &str) -> String {
+    channel
+        .parse::<Channel>()
+        .ok()
+        .and_then(|c| c.market_id())
+        .map_or_else(|| "unknown".to_string(), |id| id.to_string())
+}
+
+/// Send a request frame
+///
+/// Once the server has closed the connection nothing is sent; the reader
+/// reports the end of the stream.
+async fn send_request<S>(write: &mut S, request: &WsRequest) -> Result<()>
+where
+    S: futures_util::Sink<Message, Error = WsError> + Unpin,
+{
+    match write.send(Message::Text(request.to_text()?)).await {
+        Ok(())
+        | Err(WsError::AlreadyClosed)
+        | Err(WsError::ConnectionClosed)
+        | Err(WsError::Protocol(ProtocolError::SendAfterClosing)) => Ok(()),
+        Err(e) => Err(LighterError::InvalidResponse(format!("Send error: {}", e))),
+    }
+}
+
+/// Longest start of a malformed message kept in [`WsEvent::ParseError`]
+const MALFORMED_RAW_PREFIX_BYTES: usize = 512;
+
+/// At most `max` bytes of `text`, cut at a character boundary
+fn truncate_utf8(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn malformed_update(what: String) -> LighterError {
+    LighterError::InvalidResponse(format!("Malformed order book update: {}", what))
+}
+
+/// Levels on one side of an order book update, all checked with [`level_fields`]
+///
+/// A missing side is empty; anything other than a list is an error.
+fn level_updates<'a>(update: &'a Value, side: &str) -> Result<&'a [Value]> {
+    if !update.is_object() {
+        return Err(malformed_update(format!(
+            "expected an object, got {}",
+            update
+        )));
+    }
+    let levels = match update.get(side) {
+        None | Some(Value::Null) => return Ok(&[]),
+        Some(Value::Array(levels)) => levels,
+        Some(other) => return Err(malformed_update(format!("{} is {}", side, other))),
+    };
+    for level in levels {
+        level_fields(level)?;
+    }
+    Ok(levels)
+}
+
+/// Levels changed by an update, as a book
+fn update_delta(update: &Value) -> Result<OrderBook> {
+    let side = |name| {
+        level_updates(update, name)?
+            .iter()
+            .map(|level| {
+                let (price, size) = level_fields(level)?;
+                Ok(PriceLevel {
+                    price: price.to_string(),
+                    size: size.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+    Ok(OrderBook {
+        asks: side("asks")?,
+        bids: side("bids")?,
+    })
+}
+
+/// Price and size strings of a level update
+fn level_fields(level: &Value) -> Result<(&str, &str)> {
+    match (
+        level.get("price").and_then(|p| p.as_str()),
+        level.get("size").and_then(|s| s.as_str()),
+    ) {
+        (Some(price), Some(size)) => Ok((price, size)),
+        _ => Err(malformed_update(format!("invalid level {}", level))),
+    }
+}
+
+/// Market and error code of an order book message with a non-zero `code`
+///
+/// The code may be in the book or the envelope; `0` and `200` mean success.
+fn order_book_error(message: &Value) -> Option<(String, i64)> {
+    let msg_type = message.get("type").and_then(|t| t.as_str())?;
+    if msg_type != "subscribed/order_book" && msg_type != "update/order_book" {
+        return None;
+    }
+    let code = [message.get("order_book"), Some(message)]
+        .into_iter()
+        .flatten()
+        .filter_map(|scope| scope.get("code").and_then(|c| c.as_i64()))
+        .find(|code| *code != 0 && *code != 200)?;
+    let channel = message.get("channel").and_then(|c| c.as_str())?;
+    Some((channel_market_id(channel), code))
+}
+
+/// Offset of an order book message, from the book or the envelope
+fn message_offset(message: &Value) -> Option<i64> {
+    message
+        .get("order_book")
+        .and_then(|ob| ob.get("offset"))
+        .or_else(|| message.get("offset"))
+        .and_then(|o| o.as_i64())
+}
+
+/// Server timestamp (ms) of an order book message, from the book or the envelope
+fn message_timestamp(message: &Value) -> Option<u64> {
+    message
+        .get("order_book")
+        .and_then(|ob| ob.get("timestamp"))
+        .or_else(|| message.get("timestamp"))
+        .and_then(|t| t.as_u64())
+}
+
+/// Push recorded messages through the same parsing logic used by [`WsClient::run`]
+///
+/// `reader` yields newline-delimited JSON [`RawWsMessage`] records, as written
+/// by a raw message tap consumer. Blank lines are skipped. Returns the number
+/// of messages replayed.
+pub async fn replay_messages<R, H>(reader: R, handler: &mut H) -> Result<usize>
+where
+    R: BufRead,
+    H: WsHandler,
+{
+    let messages = reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str(&line).map_err(LighterError::from)),
+        Err(e) => Some(Err(LighterError::Other(format!(
+            "Replay read error: {}",
+            e
+        )))),
+    });
+    replay_raw_messages(messages, handler).await
+}
+
+/// Push raw messages through the parsing logic used by [`WsClient::run`]
+pub(crate) async fn replay_raw_messages<I, H>(messages: I, handler: &mut H) -> Result<usize>
+where
+    I: Iterator<Item = Result<RawWsMessage>>,
+    H: WsHandler,
+{
+    let processor = MessageProcessor::new();
+    let mut count = 0;
+
+    for raw in messages {
+        let raw = raw?;
+        count += 1;
+
+        match processor.process(&raw.text).await? {
+            Some(Dispatch::OrderBook(market_id, order_book))
+            | Some(Dispatch::Resynced(market_id, order_book)) => {
+                handler.on_order_book_update(market_id, order_book)
+            }
+            Some(Dispatch::Account(account)) => {
+                let account_id = account.account_index.to_string();
+                for event in account.events {
+                    handler.on_account_event(account_id.clone(), event);
+                }
+                handler.on_account_update(account_id, account.raw)
+            }
+            Some(Dispatch::Connected) | Some(Dispatch::Events(_)) | None => {}
+        }
+    }
+
+    Ok(count)
+}
+
+/// One recorded connection fed through a client as its run loop would
+///
+/// Driven by [`ReplayDriver`](crate::replay::ReplayDriver); messages
+/// update the client's books and accounts and reach its registered
+/// handlers.
+pub(crate) struct ReplaySession<'a> {
+    client: &'a WsClient,
+    processor: MessageProcessor,
+}
+
+impl WsClient {
+    /// Start replaying a recorded connection
+    pub(crate) fn replay_session(&self) -> ReplaySession<'_> {
+        let processor = self.processor();
+        processor.early_updates.lock().unwrap().reset();
+        self.subscriptions.reset();
+        ReplaySession {
+            client: self,
+            processor,
+        }
+    }
+}
+
+impl ReplaySession<'_> {
+    /// Apply a frame, delivering it and any updates its ack releases to the
+    /// callbacks unless `deliver` is false
+    pub(crate) async fn feed<F1, F2, E>(
+        &self,
+        text: &str,
+        deliver: bool,
+        on_order_book_update: &Arc<F1>,
+        on_account_update: &Arc<F2>,
+    ) -> Result<()>
+    where
+        F1: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        F2: Fn(String, Value) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        let mut dispatch = self.processor.process(text).await?;
+        loop {
+            if matches!(dispatch, Some(Dispatch::Connected)) {
+                self.client.subscriptions.reset();
+            }
+            if deliver {
+                self.client
+                    .handle_dispatch(dispatch, on_order_book_update, on_account_update)
+                    .await?;
+            }
+            let Some((parsed, received_at)) = self.processor.next_released() else {
+                break;
+            };
+            let text = parsed.to_string();
+            dispatch = self
+                .processor
+                .process_parsed(&text, Ok(parsed), received_at)
+                .await?;
+        }
+        // Coalesced books that came due, as the run loop's timer delivers them
+        if deliver {
+            self.client
+                .flush_held_books(false, on_order_book_update)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Deliver books still held back by a notify policy, as when a stream ends
+    pub(crate) async fn finish<F, E>(&self, on_order_book_update: &Arc<F>) -> Result<()>
+    where
+        F: Fn(String, OrderBook) -> std::result::Result<(), E> + Send + Sync + 'static,
+        E: Into<LighterError> + 'static,
+    {
+        self.client
+            .flush_held_books(true, on_order_book_update)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::TcpOptions;
+    use crate::test_support::{
+        spawn_mock_ws_server, spawn_mock_ws_server_for, spawn_mock_ws_server_in_bursts,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_ws_client_builder() {
+        let client = WsClient::builder()
+            .order_books(vec![0, 1])
+            .accounts(vec![12345])
+            .build();
+
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn test_ws_client_builder_no_subscriptions() {
+        let client = WsClient::builder().build();
+
+        assert!(client.is_err());
+        assert!(matches!(
+            client.unwrap_err(),
+            LighterError::ValidationError(_)
+        ));
+    }
+
+    #[test]
+    fn test_builder_dedupes_and_accumulates_ids() {
+        let client = WsClient::builder()
+            .order_books(vec![1, 0, 1])
+            .order_books(vec![2, 0])
+            .accounts(vec![7])
+            .accounts(vec![7, 8])
+            .build()
+            .unwrap();
+
+        assert_eq!(client.order_book_ids, vec![1, 0, 2]);
+        assert_eq!(client.account_ids, vec![7, 8]);
+    }
+
+    #[test]
+    fn test_builder_reports_every_invalid_id() {
+        let err = WsClient::builder()
+            .order_books(vec![0, 300, 255])
+            .accounts(vec![-1, 5])
+            .build()
+            .unwrap_err();
+
+        let message = err.to_string();
+        assert!(matches!(err, LighterError::ValidationError(_)));
+        for invalid in ["order book 300", "order book 255", "account -1"] {
+            assert!(message.contains(invalid), "{}", message);
+        }
+        assert!(!message.contains("order book 0"), "{}", message);
+    }
+
+    #[test]
+    fn test_builder_composes_url() {
+        let url = |builder: WsClientBuilder| {
+            builder
+                .order_books(vec![0])
+                .build()
+                .unwrap()
+                .url()
+                .to_string()
+        };
+
+        assert_eq!(
+            url(WsClient::builder()),
+            "wss://api-testnet.lighter.xyz/stream"
+        );
+        assert_eq!(
+            url(WsClient::builder()
+                .scheme("ws")
+                .host("localhost:8080")
+                .path("/sim")),
+            "ws://localhost:8080/sim"
+        );
+        assert_eq!(
+            url(WsClient::builder()
+                .host("gw.example.com")
+                .query_param("auth", "a b&c=d/e")
+                .query_param("compress", "true")),
+            "wss://gw.example.com/stream?auth=a+b%26c%3Dd%2Fe&compress=true"
+        );
+        assert_eq!(
+            url(WsClient::builder()
+                .url("ws://127.0.0.1:9000/stream?v=1")
+                .query_param("token", "x")),
+            "ws://127.0.0.1:9000/stream?v=1&token=x"
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_url() {
+        for builder in [
+            WsClient::builder().host("bad host"),
+            WsClient::builder().host(""),
+            WsClient::builder().scheme("https"),
+            WsClient::builder().url("not a url"),
+        ] {
+            let err = builder.order_books(vec![0]).build().unwrap_err();
+            assert!(
+                matches!(err, LighterError::InvalidConfiguration(_)),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_price_levels() {
+        let mut levels = vec![
+            PriceLevel {
+                price: "100.0".to_string(),
+                size: "10.0".to_string(),
+            },
+            PriceLevel {
+                price: "101.0".to_string(),
+                size: "5.0".to_string(),
+            },
+        ];
+
+        let update = serde_json::json!({
+            "price": "100.0",
+            "size": "15.0"
+        });
+
+        WsClient::update_price_levels(&mut levels, &update).unwrap();
+
+        assert_eq!(levels[0].size, "15.0");
+        assert_eq!(levels.len(), 2);
+    }
+
+    #[test]
+    fn test_update_price_levels_new_level() {
+        let mut levels = vec![PriceLevel {
+            price: "100.0".to_string(),
+            size: "10.0".to_string(),
+        }];
+
+        let update = serde_json::json!({
+            "price": "102.0",
+            "size": "8.0"
+        });
+
+        WsClient::update_price_levels(&mut levels, &update).unwrap();
+
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[1].price, "102.0");
+        assert_eq!(levels[1].size, "8.0");
+    }
+
+    #[tokio::test]
+    async fn test_apply_update_applied_and_stale() {
+        let processor = MessageProcessor::new();
+        let mut book = OrderBook {
+            asks: vec![PriceLevel {
+                price: "101.0".to_string(),
+                size: "1.0".to_string(),
+            }],
+            bids: vec![PriceLevel {
+                price: "99.0".to_string(),
+                size: "2.0".to_string(),
+            }],
+        };
+        processor.reset_sequence("0", Some(10));
+
+        let update = serde_json::json!({
+            "asks": [{"price": "101.0", "size": "3.0"}, {"price": "102.0", "size": "1.0"}],
+            "bids": [{"price": "99.0", "size": "2.0"}]
+        });
+        let result = processor
+            .apply_update("0", &mut book, &update, Some(11))
+            .unwrap();
+        // The bid is unchanged, so only the two asks count
+        assert_eq!(result, ApplyResult::Applied { levels_changed: 2 });
+
+        let result = processor
+            .apply_update("0", &mut book, &update, Some(11))
+            .unwrap();
+        assert_eq!(
+            result,
+            ApplyResult::SkippedStale {
+                update_offset: 11,
+                book_offset: 11
+            }
+        );
+        assert_eq!(processor.skipped_updates.load(Ordering::Relaxed), 1);
+
+        // Without an offset there's nothing to compare against
+        let result = processor
+            .apply_update("0", &mut book, &update, None)
+            .unwrap();
+        assert_eq!(result, ApplyResult::Applied { levels_changed: 0 });
+    }
+
+    #[tokio::test]
+    async fn test_stale_updates_are_counted_and_not_dispatched() {
+        let client = WsClient::builder().order_books(vec![0]).build().unwrap();
+        let processor = client.processor();
+        let frames = [
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","offset":5,"order_book":{"offset":5,"asks":[{"price":"101.0","size":"1.0"}],"bids":[]}}"#,
+            r#"{"type":"update/order_book","channel":"order_book:0","offset":4,"order_book":{"offset":4,"asks":[{"price":"101.0","size":"9.0"}],"bids":[]}}"#,
+            r#"{"type":"update/order_book","channel":"order_book:0","offset":6,"order_book":{"offset":6,"asks":[{"price":"101.0","size":"2.0"}],"bids":[]}}"#,
+        ];
+
+        assert!(processor.process(frames[0]).await.unwrap().is_some());
+        assert!(processor.process(frames[1]).await.unwrap().is_none());
+        assert_eq!(client.skipped_updates(), 1);
+        assert!(matches!(
+            processor.process(frames[2]).await.unwrap(),
+            Some(Dispatch::OrderBook(..))
+        ));
+        assert_eq!(
+            client.get_order_book("0").await.unwrap().asks[0].size,
+            "2.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_timestamp_never_goes_backwards() {
+        let client = WsClient::builder().order_books(vec![0]).build().unwrap();
+        let processor = client.processor();
+        assert_eq!(client.book_timing(0), None);
+
+        let update = |timestamp: u64| {
+            format!(
+                r#"{{"type":"update/order_book","channel":"order_book:0","timestamp":{},"order_book":{{"asks":[],"bids":[]}}}}"#,
+                timestamp
+            )
+        };
+        processor
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:0","timestamp":1000,"order_book":{"asks":[],"bids":[]}}"#)
+            .await
+            .unwrap();
+        let first = client.book_timing(0).unwrap();
+        assert_eq!(first.server_timestamp, Some(1000));
+
+        for timestamp in [1200, 900, 1100, 1300, 1250] {
+            processor.process(&update(timestamp)).await.unwrap();
+        }
+        let timing = client.book_timing(0).unwrap();
+        assert_eq!(timing.server_timestamp, Some(1300));
+        assert_eq!(timing.timestamp_regressions, 3);
+        assert_eq!(timing.max_regression_ms, 300);
+        assert!(timing.local_received_at.unwrap() >= first.local_received_at.unwrap());
+        assert!(timing.age().is_some());
+
+        // A fresh snapshot doesn't forget the server clock
+        processor
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:0","timestamp":1290,"order_book":{"asks":[],"bids":[]}}"#)
+            .await
+            .unwrap();
+        let timing = client.book_timing(0).unwrap();
+        assert_eq!(timing.server_timestamp, Some(1300));
+        assert_eq!(timing.timestamp_regressions, 4);
+    }
+
+    fn sample_frames() -> Vec<String> {
+        vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101.0","size":"1.0"}],"bids":[{"price":"99.0","size":"2.0"}]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101.0","size":"3.0"}],"bids":[]}}"#.to_string(),
+        ]
+    }
+
+    fn mock_client(addr: std::net::SocketAddr, builder: WsClientBuilder) -> WsClient {
+        let mut client = builder.order_books(vec![0]).build().unwrap();
+        client.base_url = format!("ws://{}/stream", addr);
+        client
+    }
+
+    #[tokio::test]
+    async fn test_raw_message_tap_delivers_frames() {
+        let frames = sample_frames();
+        let addr = spawn_mock_ws_server(frames.clone()).await;
+        let (tx, mut rx) = mpsc::channel(16);
+        let client = mock_client(addr, WsClient::builder().raw_message_tap(tx));
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            received.push(message.text);
+        }
+        assert_eq!(received, frames);
+        assert_eq!(client.raw_tap_dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_raw_message_tap_full_or_closed_does_not_block() {
+        // Full channel: frames beyond capacity are dropped and counted
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let (tx, _rx) = mpsc::channel(1);
+        let client = mock_client(addr, WsClient::builder().raw_message_tap(tx));
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+        assert_eq!(client.raw_tap_dropped(), 2);
+
+        // Dropped receiver: the main loop keeps processing
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let (tx, rx) = mpsc::channel(16);
+        drop(rx);
+        let client = mock_client(addr, WsClient::builder().raw_message_tap(tx));
+        let updates = Arc::new(AtomicU64::new(0));
+        let counter = updates.clone();
+        client
+            .run(
+                move |_, _| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(updates.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            client.get_order_book("0").await.unwrap().asks[0].size,
+            "3.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_messages() {
+        #[derive(Default)]
+        struct Collector {
+            books: Vec<(String, OrderBook)>,
+        }
+
+        impl WsHandler for Collector {
+            fn on_order_book_update(&mut self, market_id: String, order_book: OrderBook) {
+                self.books.push((market_id, order_book));
+            }
+        }
+
+        let ndjson: String = sample_frames()
+            .into_iter()
+            .map(|text| {
+                let raw = RawWsMessage {
+                    received_at: Utc::now(),
+                    text,
+                };
+                serde_json::to_string(&raw).unwrap() + "\n"
+            })
+            .collect();
+
+        let mut collector = Collector::default();
+        let count = replay_messages(ndjson.as_bytes(), &mut collector)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(collector.books.len(), 2);
+        assert_eq!(collector.books[1].0, "0");
+        assert_eq!(collector.books[1].1.asks[0].size, "3.0");
+        assert_eq!(collector.books[1].1.bids[0].price, "99.0");
+    }
+
+    fn sample_book() -> OrderBook {
+        OrderBook {
+            asks: vec![],
+            bids: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_market_handlers_routing() {
+        let frames = vec![
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[]}}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:1","order_book":{"asks":[],"bids":[]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:1","order_book":{"asks":[],"bids":[]}}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(addr, WsClient::builder());
+
+        let market_0 = Arc::new(Mutex::new(Vec::new()));
+        let market_1 = Arc::new(Mutex::new(Vec::new()));
+        let any = Arc::new(AtomicU64::new(0));
+
+        let seen = market_0.clone();
+        client.on_order_book(0, move |id, _| seen.lock().unwrap().push(id));
+        let seen = market_1.clone();
+        client.on_order_book(1, move |id, _| seen.lock().unwrap().push(id));
+        let seen = any.clone();
+        client.on_any_order_book(move |_, _| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        assert_eq!(*market_0.lock().unwrap(), vec!["0".to_string()]);
+        assert_eq!(
+            *market_1.lock().unwrap(),
+            vec!["1".to_string(), "1".to_string()]
+        );
+        assert_eq!(any.load(Ordering::Relaxed), 3);
+    }
+
+    /// Snapshot and updates whose single bid size equals the offset, 1..=21
+    fn sized_book_frames() -> Vec<String> {
+        let mut frames = vec![r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":1,"asks":[],"bids":[{"price":"1","size":"1"}]}}"#.to_string()];
+        frames.extend((2..=21).map(|offset| {
+            format!(
+                r#"{{"type":"update/order_book","channel":"order_book:0","order_book":{{"offset":{},"bids":[{{"price":"1","size":"{}"}}]}}}}"#,
+                offset, offset
+            )
+        }));
+        frames
+    }
+
+    /// Handler recording bid sizes that blocks on its first call until the sender is dropped
+    fn gated_handler(client: &WsClient) -> (Arc<Mutex<Vec<u32>>>, std::sync::mpsc::Sender<()>) {
+        let (release, gate) = std::sync::mpsc::channel::<()>();
+        let gate = Mutex::new(Some(gate));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        client.on_order_book(0, move |_, book| {
+            if let Some(gate) = gate.lock().unwrap().take() {
+                let _ = gate.recv();
+            }
+            sink.lock()
+                .unwrap()
+                .push(book.bids[0].size.parse().unwrap());
+        });
+        (seen, release)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_slow_handler_only_stalls_inline_reader() {
+        let modes = [
+            CallbackDispatch::Inline,
+            CallbackDispatch::Spawn,
+            CallbackDispatch::Pool {
+                workers: 2,
+                queue_capacity: 64,
+                overflow: QueueOverflow::Block,
+            },
+        ];
+        for mode in modes {
+            let addr = spawn_mock_ws_server(sized_book_frames()).await;
+            let client = Arc::new(mock_client(
+                addr,
+                WsClient::builder().callback_dispatch(mode),
+            ));
+            let (seen, release) = gated_handler(&client);
+
+            let runner = client.clone();
+            let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+            // The inline handler blocks a worker, which can keep the runtime's
+            // timers from firing, so wait on the test thread instead
+            std::thread::sleep(Duration::from_millis(300));
+
+            let size = client.get_order_book("0").await.unwrap().bids[0]
+                .size
+                .clone();
+            if mode == CallbackDispatch::Inline {
+                assert!(!run.is_finished());
+                assert_eq!(size, "1");
+            } else {
+                assert!(run.is_finished(), "{:?}", mode);
+                assert_eq!(size, "21", "{:?}", mode);
+            }
+
+            drop(release);
+            run.await.unwrap().unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while seen.lock().unwrap().len() < 21 && Instant::now() < deadline {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert_eq!(
+                *seen.lock().unwrap(),
+                (1..=21).collect::<Vec<_>>(),
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pool_drop_oldest_keeps_order() {
+        let addr = spawn_mock_ws_server(sized_book_frames()).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder().callback_dispatch(CallbackDispatch::Pool {
+                workers: 1,
+                queue_capacity: 1,
+                overflow: QueueOverflow::DropOldest,
+            }),
+        );
+        let (seen, release) = gated_handler(&client);
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+        drop(release);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while seen.lock().unwrap().last() != Some(&21) && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.last(), Some(&21));
+        assert!(seen.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", seen);
+        assert!(client.dropped_callbacks() > 0);
+        assert_eq!(seen.len() as u64 + client.dropped_callbacks(), 21);
+    }
+
+    #[test]
+    fn test_pool_needs_workers() {
+        let result = WsClient::builder()
+            .order_books(vec![0])
+            .callback_dispatch(CallbackDispatch::Pool {
+                workers: 0,
+                queue_capacity: 1,
+                overflow: QueueOverflow::Block,
+            })
+            .build();
+        assert!(matches!(result, Err(LighterError::InvalidConfiguration(_))));
+    }
+
+    /// Snapshot with bids 100 down to 91 and asks 101 up to 110, one lot each
+    fn ten_level_snapshot() -> String {
+        let levels = |prices: Vec<u32>| {
+            prices
+                .iter()
+                .map(|p| format!(r#"{{"price":"{}","size":"1"}}"#, p))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!(
+            r#"{{"type":"subscribed/order_book","channel":"order_book:0","order_book":{{"offset":1,"asks":[{}],"bids":[{}]}}}}"#,
+            levels((101..=110).collect()),
+            levels((91..=100).rev().collect())
+        )
+    }
+
+    fn bid_update(offset: i64, price: u32, size: u32) -> String {
+        format!(
+            r#"{{"type":"update/order_book","channel":"order_book:0","order_book":{{"offset":{},"bids":[{{"price":"{}","size":"{}"}}]}}}}"#,
+            offset, price, size
+        )
+    }
+
+    #[tokio::test]
+    async fn test_top_n_policy_skips_deep_updates() {
+        let mut frames = vec![ten_level_snapshot()];
+        // Bids 91..=94 and a new level at 50 are all below the best five
+        frames.extend((0..10).map(|i| bid_update(i + 2, 91 + (i as u32 % 4), i as u32 + 2)));
+        frames.push(bid_update(12, 50, 7));
+        let addr = spawn_mock_ws_server(frames.clone()).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder().notify_policy(NotifyPolicy::TopN(5)),
+        );
+        let calls = Arc::new(AtomicU64::new(0));
+        let seen = calls.clone();
+        client
+            .run(
+                move |_, _| {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        // Only the snapshot was delivered, but the book has every update
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        let stats = client.notify_stats(0).unwrap();
+        assert_eq!((stats.delivered, stats.suppressed), (1, 11));
+        let book = client.get_order_book("0").await.unwrap();
+        let size_at = |price: &str| {
+            book.bids
+                .iter()
+                .find(|level| level.price == price)
+                .map(|level| level.size.clone())
+        };
+        assert_eq!(size_at("91").as_deref(), Some("10"));
+        assert_eq!(size_at("94").as_deref(), Some("9"));
+        assert_eq!(size_at("50").as_deref(), Some("7"));
+
+        // A change at the top gets through, here with the best bid only
+        frames.push(bid_update(13, 100, 3));
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder()
+                .notify_policy(NotifyPolicy::TopN(5))
+                .market_notify_policy(0, NotifyPolicy::BestOnly),
+        );
+        let calls = Arc::new(AtomicU64::new(0));
+        let seen = calls.clone();
+        client
+            .run(
+                move |_, _| {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_min_interval_policy_coalesces_updates() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            ws.send(Message::Text(ten_level_snapshot())).await.unwrap();
+            for i in 0..10 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                ws.send(Message::Text(bid_update(i + 2, 100, i as u32 + 2)))
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let _ = ws.close(None).await;
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let client = Arc::new(mock_client(
+            addr,
+            WsClient::builder().notify_policy(NotifyPolicy::MinIntervalMs(100)),
+        ));
+        let deliveries = Arc::new(Mutex::new(Vec::new()));
+        let (stats_of, seen) = (client.clone(), deliveries.clone());
+        client
+            .run(
+                move |_, book| {
+                    let coalesced = stats_of.notify_stats(0).unwrap().last_coalesced;
+                    seen.lock()
+                        .unwrap()
+                        .push((book.bids[0].size.clone(), coalesced));
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        let deliveries = deliveries.lock().unwrap().clone();
+        assert_eq!(deliveries[0], ("1".to_string(), 1));
+        assert!(deliveries.len() < 11, "{:?}", deliveries);
+        // Every update is accounted for and the last call has the latest book
+        let total: u64 = deliveries.iter().map(|(_, coalesced)| coalesced).sum();
+        assert_eq!(total, 11);
+        assert_eq!(deliveries.last().unwrap().0, "11");
+    }
+
+    #[tokio::test]
+    async fn test_notify_policy_changes_while_running() {
+        let mut frames = vec![ten_level_snapshot()];
+        frames.extend((0..10).map(|i| bid_update(i + 2, 91, i as u32 + 2)));
+        let addr = spawn_mock_ws_server(frames).await;
+        let config = RuntimeConfig::default();
+        let client = Arc::new(mock_client(
+            addr,
+            WsClient::builder()
+                .runtime_config(config.clone())
+                .notify_policy(NotifyPolicy::TopN(5)),
+        ));
+        assert_eq!(config.get().notify_policy, NotifyPolicy::TopN(5));
+
+        let calls = Arc::new(AtomicU64::new(0));
+        let (seen, updater) = (calls.clone(), client.clone());
+        client
+            .run(
+                move |_, _| {
+                    // Deep updates are delivered from the one after the snapshot on
+                    if seen.fetch_add(1, Ordering::Relaxed) == 0 {
+                        updater
+                            .update_config(|c| c.notify_policy = NotifyPolicy::Always)
+                            .unwrap();
+                    }
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 11);
+        assert_eq!(client.notify_stats(0).unwrap().suppressed, 0);
+    }
+
+    #[test]
+    fn test_top_n_needs_a_level() {
+        let result = WsClient::builder()
+            .order_books(vec![0])
+            .market_notify_policy(0, NotifyPolicy::TopN(0))
+            .build();
+        assert!(matches!(result, Err(LighterError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn test_handler_registered_later_receives_updates() {
+        let client = WsClient::builder().order_books(vec![0]).build().unwrap();
+        let calls = Arc::new(AtomicU64::new(0));
+
+        client.dispatch_order_book("0", &sample_book()).unwrap();
+
+        let seen = calls.clone();
+        client.on_order_book(0, move |_, _| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+        client.dispatch_order_book("0", &sample_book()).unwrap();
+        client.dispatch_order_book("1", &sample_book()).unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_handler_panic_is_isolated() {
+        let client = WsClient::builder().order_books(vec![0]).build().unwrap();
+        let calls = Arc::new(AtomicU64::new(0));
+
+        client.on_order_book(0, |_, _| panic!("buggy strategy"));
+        let seen = calls.clone();
+        client.on_order_book(0, move |_, _| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        });
+
+        client.dispatch_order_book("0", &sample_book()).unwrap();
+        client.dispatch_order_book("0", &sample_book()).unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(client.suppressed_panics(), 2);
+    }
+
+    /// Order book callback failing on the `fail_on`-th call, counting all calls
+    fn failing_callback(
+        calls: &Arc<AtomicU64>,
+        fail_on: u64,
+    ) -> impl Fn(String, OrderBook) -> Result<()> + Send + Sync + 'static {
+        let calls = calls.clone();
+        move |_, _| {
+            if calls.fetch_add(1, Ordering::Relaxed) + 1 == fail_on {
+                Err(LighterError::Other("strategy failed".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_callback_error_logged_and_skipped_by_default() {
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let client = mock_client(addr, WsClient::builder());
+        let calls = Arc::new(AtomicU64::new(0));
+
+        client
+            .run_with_results(failing_callback(&calls, 1), |_, _| Ok(()))
+            .await
+            .unwrap();
+
+        // The update after the failing snapshot is still delivered
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_callback_error_stops_run() {
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder().callback_error_policy(CallbackErrorPolicy::Stop),
+        );
+        let calls = Arc::new(AtomicU64::new(0));
+
+        let err = client
+            .run_with_results(failing_callback(&calls, 1), |_, _| Ok(()))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, LighterError::Other(ref msg) if msg == "strategy failed"));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_callback_panic_follows_stop_policy() {
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder().callback_error_policy(CallbackErrorPolicy::Stop),
+        );
+
+        let err = client
+            .run(|_, _| panic!("buggy strategy"), |_, _| {})
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, LighterError::CallbackPanicked(ref msg) if msg == "buggy strategy"));
+        assert_eq!(client.suppressed_panics(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_callback_error_triggers_reconnect() {
+        let addr = spawn_mock_ws_server_for(sample_frames(), 2).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder().callback_error_policy(CallbackErrorPolicy::Reconnect),
+        );
+        let calls = Arc::new(AtomicU64::new(0));
+
+        client
+            .run_with_results(failing_callback(&calls, 1), |_, _| Ok(()))
+            .await
+            .unwrap();
+
+        // One failed snapshot, then snapshot and update on the new connection
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_account_events_dispatched() {
+        let frames = vec![
+            r#"{"type":"subscribed/account_all","channel":"account_all:7","collateral":"100","positions":{"0":{"market_id":0,"sign":1,"position":"0"}}}"#.to_string(),
+            r#"{"type":"update/account_all","channel":"account_all:7","positions":{"0":{"market_id":0,"sign":-1,"position":"2"}},"trades":{"0":[{"trade_id":3,"market_id":0,"size":"2","price":"10","ask_id":11,"bid_id":12,"ask_account_id":7,"bid_account_id":8}]}}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let mut client = WsClient::builder().accounts(vec![7]).build().unwrap();
+        client.base_url = format!("ws://{}/stream", addr);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_account_event(move |id, event| seen.lock().unwrap().push((id, event)));
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|(id, _)| id == "7"));
+        assert!(matches!(
+            events[0].1,
+            AccountEvent::Fill {
+                side: crate::types::Side::Sell,
+                order_index: 11,
+                ..
+            }
+        ));
+        assert!(matches!(events[1].1, AccountEvent::PositionChanged { .. }));
+
+        let snapshot = client.get_account_snapshot("7").await.unwrap();
+        assert_eq!(
+            snapshot.positions["0"].side(),
+            Some(crate::types::Side::Sell)
+        );
+        assert!(client.get_account("7").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_strict_parsing_of_account_messages() {
+        let frames = || {
+            vec![
+                r#"{"type":"subscribed/account_all","channel":"account_all:7","collateral":"100","positions":{"0":{"market_id":0,"sign":1,"position":"1"}}}"#.to_string(),
+                r#"{"type":"update/account_all","channel":"account_all:7","collateral":"90","total_order_count":3}"#.to_string(),
+            ]
+        };
+
+        let addr = spawn_mock_ws_server(frames()).await;
+        let lenient = mock_client(addr, WsClient::builder().accounts(vec![7]));
+        lenient.run(|_, _| {}, |_, _| {}).await.unwrap();
+        let snapshot = lenient.get_account_snapshot("7").await.unwrap();
+        assert_eq!(snapshot.collateral, Some(Decimal::from(90)));
+        assert_eq!(snapshot.extras.len(), 1);
+        assert_eq!(snapshot.extras["total_order_count"], serde_json::json!(3));
+
+        let addr = spawn_mock_ws_server(frames()).await;
+        let strict = mock_client(
+            addr,
+            WsClient::builder().accounts(vec![7]).strict_parsing(true),
+        );
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        strict.on_ws_event(move |event| sink.lock().unwrap().push(event));
+        strict.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let snapshot = strict.get_account_snapshot("7").await.unwrap();
+        assert_eq!(snapshot.collateral, Some(Decimal::from(100)));
+        let errors: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                WsEvent::ParseError { channel, error, .. } => {
+                    Some((channel.clone(), error.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0.as_deref(), Some("account_all:7"));
+        assert!(errors[0].1.contains("total_order_count"), "{}", errors[0].1);
+        assert_eq!(strict.malformed_messages(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_account_handlers_route_by_index() {
+        let frame = |kind: &str, account: i64, collateral: u32| {
+            format!(
+                r#"{{"type":"{}/account_all","channel":"account_all:{}","collateral":"{}"}}"#,
+                kind, account, collateral
+            )
+        };
+        let frames = vec![
+            frame("subscribed", 1, 100),
+            frame("subscribed", 2, 200),
+            frame("subscribed", 3, 300),
+            frame("update", 1, 90),
+            frame("update", 2, 190),
+            frame("update", 3, 290),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = Arc::new(mock_client(
+            addr,
+            WsClient::builder()
+                .accounts(vec![1, 2, 3])
+                .callback_error_policy(CallbackErrorPolicy::LogAndContinue),
+        ));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = |tag: &'static str| {
+            let seen = seen.clone();
+            move |account: i64, update: AccountUpdate| {
+                let what = match update {
+                    AccountUpdate::Event(_) => "event".to_string(),
+                    AccountUpdate::Snapshot(s) => s.collateral.unwrap().to_string(),
+                };
+                seen.lock().unwrap().push((tag, account, what));
+            }
+        };
+        client.on_account(2, record("two"));
+        // A panicking handler doesn't keep the others from running
+        client.on_account(2, |_, _| panic!("handler failure"));
+        client.on_other_accounts(record("other"));
+        // Account 1 registers account 3 and drops account 2 while running
+        let (own, registrar, on_three) = (record("one"), client.clone(), record("three"));
+        let on_three = Arc::new(on_three);
+        client.on_account(1, move |account, update| {
+            let is_update = matches!(&update, AccountUpdate::Snapshot(s) if s.collateral == Some(Decimal::from(90)));
+            own(account, update);
+            if is_update {
+                let on_three = on_three.clone();
+                registrar.on_account(3, move |account, update| on_three(account, update));
+                assert!(registrar.off_account(2));
+            }
+        });
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let entry = |tag, account, what: &str| (tag, account, what.to_string());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                entry("one", 1, "100"),
+                entry("two", 2, "200"),
+                entry("other", 3, "300"),
+                entry("one", 1, "event"),
+                entry("one", 1, "90"),
+                entry("other", 2, "event"),
+                entry("other", 2, "190"),
+                entry("three", 3, "event"),
+                entry("three", 3, "290"),
+            ]
+        );
+        assert_eq!(client.suppressed_panics(), 1);
+        assert!(!client.off_account(2));
+        assert_eq!(
+            client.get_account("2").await.unwrap().collateral,
+            Some(Decimal::from(190))
+        );
+    }
+
+    const ACCOUNT_SNAPSHOT_FRAME: &str = r#"{"type":"subscribed/account_all","channel":"account_all:7","collateral":"100","positions":{"0":{"market_id":0,"sign":1,"position":"1"},"1":{"market_id":1,"sign":1,"position":"3"}},"orders":{"0":[{"order_index":1,"client_order_index":1,"market_index":0,"is_ask":false,"price":"9","remaining_base_amount":"1"},{"order_index":2,"client_order_index":2,"market_index":0,"is_ask":false,"price":"8","remaining_base_amount":"1"}]}}"#;
+    const ACCOUNT_UPDATE_FRAME: &str =
+        r#"{"type":"update/account_all","channel":"account_all:7","collateral":"90"}"#;
+
+    #[tokio::test]
+    async fn test_account_snapshot_shared_until_replaced() {
+        let processor = MessageProcessor::new();
+        processor.process(ACCOUNT_SNAPSHOT_FRAME).await.unwrap();
+        let first = processor.account_snapshots.read().await[&7].clone();
+        let again = processor.account_snapshots.read().await[&7].clone();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        // A held snapshot is copied, not changed, by the next message
+        processor.process(ACCOUNT_UPDATE_FRAME).await.unwrap();
+        let updated = processor.account_snapshots.read().await[&7].clone();
+        assert!(!Arc::ptr_eq(&first, &updated));
+        assert_eq!(first.collateral, Some(Decimal::from(100)));
+        assert_eq!(updated.collateral, Some(Decimal::from(90)));
+        assert_eq!(updated.orders["0"].len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_market_view_consistent_under_updates() {
+        let client = Arc::new(
+            WsClient::builder()
+                .order_books(vec![0])
+                .accounts(vec![7])
+                .build()
+                .unwrap(),
+        );
+        assert!(client.market_view(1, "7").await.is_none());
+        let view = client.market_view(0, "7").await.unwrap();
+        assert!(view.book.is_none() && view.my_orders.is_empty());
+
+        let processor = client.processor();
+        processor
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":0,"asks":[],"bids":[{"price":"1","size":"0"}]}}"#)
+            .await
+            .unwrap();
+        processor.process(ACCOUNT_SNAPSHOT_FRAME).await.unwrap();
+
+        // The single bid's size always equals the offset that set it
+        let writer = tokio::spawn(async move {
+            for offset in 1..=500 {
+                let update = format!(
+                    r#"{{"type":"update/order_book","channel":"order_book:0","order_book":{{"offset":{},"bids":[{{"price":"1","size":"{}"}}]}}}}"#,
+                    offset, offset
+                );
+                processor.process(&update).await.unwrap();
+                if offset % 50 == 0 {
+                    processor.process(ACCOUNT_UPDATE_FRAME).await.unwrap();
+                }
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let mut last_offset = 0;
+        while !writer.is_finished() {
+            let view = client.market_view(0, "7").await.unwrap();
+            let offset = view.offset.unwrap();
+            let book = view.book.unwrap();
+            assert_eq!(book.bids[0].size, offset.to_string());
+            assert!(offset >= last_offset);
+            last_offset = offset;
+            assert_eq!(view.my_orders.len(), 2);
+            assert!(view.position.is_some());
+        }
+        writer.await.unwrap();
+        assert_eq!(client.market_view(0, "7").await.unwrap().offset, Some(500));
+    }
+
+    #[tokio::test]
+    async fn test_market_view_shares_book_and_waits_for_applying() {
+        let client = Arc::new(
+            WsClient::builder()
+                .order_books(vec![0])
+                .accounts(vec![7])
+                .build()
+                .unwrap(),
+        );
+        client
+            .processor()
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":0,"asks":[],"bids":[{"price":"1","size":"2"}]}}"#)
+            .await
+            .unwrap();
+        let first = client.market_view(0, "7").await.unwrap().book.unwrap();
+        let second = client.market_view(0, "7").await.unwrap().book.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Not read while a message is half applied
+        let applying = client.state_generation.applying();
+        let reader = tokio::spawn({
+            let client = client.clone();
+            async move { client.market_view(0, "7").await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!reader.is_finished());
+        drop(applying);
+        let view = reader.await.unwrap().unwrap();
+        assert!(Arc::ptr_eq(&view.book.unwrap(), &first));
+    }
+
+    #[tokio::test]
+    async fn test_queue_positions_follow_the_stream() {
+        let client = WsClient::builder()
+            .order_books(vec![0])
+            .accounts(vec![7])
+            .queue_positions(QueueAttribution::Conservative)
+            .build()
+            .unwrap();
+        let processor = client.processor();
+        let snapshot = r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":1,"asks":[],"bids":[{"price":"9","size":"5"},{"price":"8","size":"2"}]}}"#;
+        processor.process(snapshot).await.unwrap();
+        processor.process(ACCOUNT_SNAPSHOT_FRAME).await.unwrap();
+        assert_eq!(client.estimated_ahead(7, 1), Some(Decimal::from(5)));
+
+        client.record_trade(
+            0,
+            &FlowTrade {
+                price: Decimal::from(9),
+                size: Decimal::from(2),
+                aggressor: crate::types::Side::Sell,
+            },
+        );
+        processor
+            .process(r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"offset":2,"bids":[{"price":"9","size":"3"},{"price":"8","size":"0"}]}}"#)
+            .await
+            .unwrap();
+        let view = client.market_view(0, "7").await.unwrap();
+        assert_eq!(view.queue_positions[&1].ahead, Decimal::from(3));
+        assert_eq!(view.queue_positions[&2].ahead, Decimal::ZERO);
+        assert!(!view.queue_positions[&1].stale);
+
+        // A fresh snapshot may hide missed updates
+        processor
+            .process(&snapshot.replace(r#""offset":1"#, r#""offset":3"#))
+            .await
+            .unwrap();
+        assert_eq!(client.estimated_ahead(7, 1), None);
+        assert!(client.queue_estimate(7, 1).unwrap().stale);
+    }
+
+    #[tokio::test]
+    async fn test_raw_accounts_kept_only_when_enabled() {
+        for retain in [false, true] {
+            let frames = vec![
+                ACCOUNT_SNAPSHOT_FRAME.to_string(),
+                ACCOUNT_UPDATE_FRAME.to_string(),
+            ];
+            let addr = spawn_mock_ws_server(frames).await;
+            let mut client = WsClient::builder()
+                .accounts(vec![7])
+                .retain_raw_accounts(retain)
+                .build()
+                .unwrap();
+            client.base_url = format!("ws://{}/stream", addr);
+            client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+            let account = client.get_account("7").await.unwrap();
+            assert_eq!(account.collateral, Some(Decimal::from(90)));
+            let raw = client.get_raw_account("7").await;
+            if retain {
+                let expected: Value = serde_json::from_str(ACCOUNT_UPDATE_FRAME).unwrap();
+                assert_eq!(raw, Some(expected));
+            } else {
+                assert!(raw.is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_retention_trims_stored_snapshot() {
+        let addr = spawn_mock_ws_server(vec![ACCOUNT_SNAPSHOT_FRAME.to_string()]).await;
+        let mut client = WsClient::builder()
+            .accounts(vec![7])
+            .account_retention(AccountRetention {
+                max_orders_per_market: Some(1),
+                markets: Some(vec![0]),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        client.base_url = format!("ws://{}/stream", addr);
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let account = client.get_account("7").await.unwrap();
+        assert_eq!(account.positions.keys().collect::<Vec<_>>(), vec!["0"]);
+        assert!(account.orders.is_empty());
+        assert_eq!(account.collateral, Some(Decimal::from(100)));
+    }
+
+    #[tokio::test]
+    async fn test_message_over_configured_limit_is_descriptive() {
+        let big = format!(
+            r#"{{"type":"subscribed/order_book","channel":"order_book:0","order_book":{{"asks":[],"bids":[],"pad":"{}"}}}}"#,
+            "x".repeat(4096)
+        );
+        let addr = spawn_mock_ws_server(vec![big]).await;
+        let config = WsConfig {
+            max_message_size: Some(1024),
+            max_frame_size: Some(1024),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+
+        let err = client.run(|_, _| {}, |_, _| {}).await.unwrap_err();
+        let message = err.to_string();
+        assert!(
+            message.contains("configured limit of 1024 bytes"),
+            "{}",
+            message
+        );
+    }
+
+    /// Serve `text` split into continuation frames of `chunk` bytes, then a binary message
+    async fn spawn_fragmenting_server(text: String, chunk: usize) -> std::net::SocketAddr {
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+        use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let pieces: Vec<&[u8]> = text.as_bytes().chunks(chunk).collect();
+            for (i, piece) in pieces.iter().enumerate() {
+                let opcode = if i == 0 { Data::Text } else { Data::Continue };
+                let frame =
+                    Frame::message(piece.to_vec(), OpCode::Data(opcode), i + 1 == pieces.len());
+                if ws.send(Message::Frame(frame)).await.is_err() {
+                    return;
+                }
+            }
+            let _ = ws.send(Message::Binary(vec![1, 2, 3])).await;
+            let _ = ws.close(None).await;
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_snapshot_applied_once() {
+        let bids: Vec<String> = (1..=200)
+            .map(|i| format!(r#"{{"price":"{}","size":"1"}}"#, i))
+            .collect();
+        let snapshot = format!(
+            r#"{{"type":"subscribed/order_book","channel":"order_book:0","order_book":{{"asks":[],"bids":[{}]}}}}"#,
+            bids.join(",")
+        );
+        let addr = spawn_fragmenting_server(snapshot, 512).await;
+        let client = mock_client(addr, WsClient::builder());
+
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        client
+            .run(
+                move |_, book| {
+                    assert_eq!(book.bids.len(), 200);
+                    counted.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(client.get_order_book("0").await.unwrap().bids.len(), 200);
+        assert_eq!(client.malformed_messages(), 0);
+        assert_eq!(client.skipped_messages(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fragments_over_message_limit_are_descriptive() {
+        let big = format!(
+            r#"{{"type":"subscribed/order_book","channel":"order_book:0","order_book":{{"asks":[],"bids":[],"pad":"{}"}}}}"#,
+            "x".repeat(4096)
+        );
+        let addr = spawn_fragmenting_server(big, 512).await;
+        let config = WsConfig {
+            max_message_size: Some(1024),
+            max_frame_size: Some(1024),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+
+        let err = client.run(|_, _| {}, |_, _| {}).await.unwrap_err();
+        assert!(
+            err.to_string().contains("configured limit of 1024 bytes"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // A listener that never completes the WebSocket handshake
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+
+        let config = WsConfig {
+            connect_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+
+        let err = client.run(|_, _| {}, |_, _| {}).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_options_apply_to_the_stream() {
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let config = WsConfig {
+            tcp: TcpOptions::default()
+                .nodelay(true)
+                .keepalive(Duration::from_secs(5))
+                .local_address("127.0.0.1".parse().unwrap()),
+            ..Default::default()
+        };
+        let mut client = WsClient::builder()
+            .order_books(vec![0])
+            .config(config)
+            .build()
+            .unwrap();
+        client.base_url = format!("ws://localhost:{}/stream", addr.port());
+
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+        assert_eq!(client.book("0").unwrap().asks[0].size, "3.0");
+    }
+
+    #[tokio::test]
+    async fn test_missing_connected_message_times_out() {
+        // Accepts the socket but never says hello
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let config = WsConfig {
+            handshake_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+        let err = tokio::time::timeout(Duration::from_secs(5), client.run(|_, _| {}, |_, _| {}))
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.to_string().contains("No connected message"), "{}", err);
+        assert_eq!(
+            client.subscription_state("order_book:0"),
+            Some(SubscriptionState::Pending)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eager_subscribe_without_connected_message() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut subscribes = 0;
+            // Nothing is sent until the client subscribes
+            while let Some(Ok(Message::Text(text))) = ws.next().await {
+                if text.contains(r#""type":"subscribe""#) {
+                    subscribes += 1;
+                    break;
+                }
+            }
+            for frame in [
+                r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101.0","size":"1.0"}],"bids":[]}}"#,
+                // Late hello from a gateway; nothing is resubscribed
+                r#"{"type":"connected"}"#,
+            ] {
+                ws.send(Message::Text(frame.to_string())).await.unwrap();
+            }
+            let _ = ws.close(None).await;
+            while let Some(Ok(message)) = ws.next().await {
+                if message.to_text().unwrap_or_default().contains("subscribe") {
+                    subscribes += 1;
+                }
+            }
+            subscribes
+        });
+
+        let config = WsConfig {
+            eager_subscribe: true,
+            handshake_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        assert_eq!(server.await.unwrap(), 1);
+        assert_eq!(
+            client.subscription_state("order_book:0"),
+            Some(SubscriptionState::Subscribed)
+        );
+        assert_eq!(client.book("0").unwrap().asks[0].size, "1.0");
+    }
+
+    #[tokio::test]
+    async fn test_updates_before_their_ack_follow_the_snapshot() {
+        let addr = spawn_mock_ws_server(vec![
+            r#"{"type":"connected"}"#.to_string(),
+            // Ahead of the ack: one older than the snapshot, one newer
+            r#"{"type":"update/order_book","channel":"order_book:0","offset":4,"order_book":{"offset":4,"asks":[{"price":"101.0","size":"9.0"}],"bids":[]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","offset":6,"order_book":{"offset":6,"asks":[{"price":"101.0","size":"2.0"}],"bids":[]}}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","offset":5,"order_book":{"offset":5,"asks":[{"price":"101.0","size":"1.0"}],"bids":[]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","offset":7,"order_book":{"offset":7,"asks":[{"price":"101.0","size":"3.0"}],"bids":[]}}"#.to_string(),
+        ])
+        .await;
+        let client = mock_client(addr, WsClient::builder());
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let seen = sizes.clone();
+
+        client
+            .run(
+                move |_, book| seen.lock().unwrap().push(book.asks[0].size.clone()),
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(*sizes.lock().unwrap(), ["1.0", "2.0", "3.0"]);
+        assert_eq!(client.skipped_updates(), 1);
+        assert_eq!(client.early_updates_dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_ack() {
+        let addr = spawn_mock_ws_server(sample_frames()).await;
+        let client = Arc::new(mock_client(addr, WsClient::builder()));
+        assert_eq!(
+            client.subscription_state("order_book/0"),
+            Some(SubscriptionState::Pending)
+        );
+
+        let runner = client.clone();
+        let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        client
+            .await_subscribed("order_book/0", Duration::from_secs(5))
+            .await
+            .unwrap();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(
+            client.subscription_state("order_book:0"),
+            Some(SubscriptionState::Subscribed)
+        );
+        assert!(matches!(
+            client
+                .await_subscribed("order_book/9", Duration::from_secs(1))
+                .await,
+            Err(LighterError::ValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_failure() {
+        let frames = vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[]}}"#.to_string(),
+            r#"{"error":{"code":30003,"message":"Invalid Channel: order_book/99"}}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let mut client = WsClient::builder()
+            .order_books(vec![0, 99])
+            .build()
+            .unwrap();
+        client.base_url = format!("ws://{}/stream", addr);
+        let client = Arc::new(client);
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_ws_event(move |event| seen.lock().unwrap().push(event));
+
+        let runner = client.clone();
+        let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        let err = client
+            .await_subscribed("order_book/99", Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        run.await.unwrap().unwrap();
+
+        assert!(
+            matches!(&err, LighterError::SubscriptionFailed { channel, .. } if channel == "order_book:99"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                WsEvent::ServerError(WsServerError {
+                    channel: Some("order_book:99".to_string()),
+                    code: Some(30003),
+                    message: "Invalid Channel: order_book/99".to_string(),
+                    raw: r#"{"error":{"code":30003,"message":"Invalid Channel: order_book/99"}}"#
+                        .to_string(),
+                }),
+                WsEvent::SubscriptionFailed {
+                    channel: "order_book:99".to_string(),
+                    reason: "Invalid Channel: order_book/99".to_string(),
+                }
+            ]
+        );
+        assert_eq!(
+            client.subscription_state("order_book/0"),
+            Some(SubscriptionState::Subscribed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_order_book_error_code_leaves_book_unchanged() {
+        let frames = vec![
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"code":0,"offset":1,"asks":[{"price":"101","size":"1"}],"bids":[{"price":"99","size":"2"}]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"code":21000,"offset":2,"asks":[{"price":"101","size":"0"}]}}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"code":21001,"offset":3,"asks":[],"bids":[]}}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(addr, WsClient::builder());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_ws_event(move |event| seen.lock().unwrap().push(event));
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        client
+            .run(
+                move |_, _| {
+                    counted.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                WsEvent::OrderBookError {
+                    market_id: "0".to_string(),
+                    code: 21000
+                },
+                WsEvent::OrderBookError {
+                    market_id: "0".to_string(),
+                    code: 21001
+                },
+            ]
+        );
+        let book = client.get_order_book("0").await.unwrap();
+        assert_eq!(book.asks[0].size, "1");
+        assert_eq!(book.bids[0].size, "2");
+        assert_eq!(client.book_error_code(0), Some(21001));
+        let view = client.market_view(0, "1").await.unwrap();
+        assert_eq!((view.offset, view.last_error_code), (Some(1), Some(21001)));
+    }
+
+    #[tokio::test]
+    async fn test_order_book_error_before_first_snapshot() {
+        let processor = MessageProcessor::new();
+        let dispatch = processor
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:3","code":503,"order_book":{"asks":[],"bids":[]}}"#)
+            .await
+            .unwrap();
+        let Some(Dispatch::Events(events)) = dispatch else {
+            panic!("expected events");
+        };
+        assert!(matches!(events[0], WsEvent::ServerError(_)));
+        assert_eq!(
+            events.last(),
+            Some(&WsEvent::OrderBookError {
+                market_id: "3".to_string(),
+                code: 503
+            })
+        );
+        assert!(processor.order_book_states.read().unwrap().is_empty());
+        assert!(!processor.is_resyncing("3"));
+        let state = processor.order_book_sequences.lock().unwrap()["3"].state;
+        assert_eq!(state, BookSyncState::Desynced);
+
+        // A good snapshot clears the code
+        processor
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:3","order_book":{"code":0,"asks":[],"bids":[]}}"#)
+            .await
+            .unwrap();
+        let sequences = processor.order_book_sequences.lock().unwrap();
+        assert_eq!(sequences["3"].last_error_code, None);
+        assert_eq!(sequences["3"].state, BookSyncState::Synced);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_snapshots_keep_the_newer_book() {
+        let processor = MessageProcessor::new();
+        let snapshot = |offset: i64, size: &str| {
+            format!(
+                r#"{{"type":"subscribed/order_book","channel":"order_book:0","timestamp":{},"order_book":{{"offset":{},"asks":[{{"price":"101.0","size":"{}"}}],"bids":[]}}}}"#,
+                1_000 + offset,
+                offset,
+                size
+            )
+        };
+        let book_and_sequence = || async {
+            let size = processor.order_book_states.read().unwrap()["0"].asks[0]
+                .size
+                .clone();
+            let sequences = processor.order_book_sequences.lock().unwrap();
+            (size, sequences["0"].offset, sequences["0"].snapshots)
+        };
+
+        processor.process(r#"{"type":"connected"}"#).await.unwrap();
+        processor.process(&snapshot(10, "1.0")).await.unwrap();
+        processor
+            .process(r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"offset":11,"asks":[{"price":"101.0","size":"2.0"}],"bids":[]}}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            book_and_sequence().await,
+            ("2.0".to_string(), Some(11), SnapshotCounts::default())
+        );
+
+        // An older repeated snapshot is dropped and flagged
+        let dispatch = processor.process(&snapshot(5, "9.0")).await.unwrap();
+        let Some(Dispatch::Events(events)) = dispatch else {
+            panic!("expected events");
+        };
+        assert_eq!(
+            events,
+            [WsEvent::StaleSnapshot {
+                market_id: "0".to_string(),
+                offset: 5,
+                book_offset: 11
+            }]
+        );
+        let stale = SnapshotCounts {
+            resnapshots: 0,
+            stale_snapshots: 1,
+        };
+        assert_eq!(
+            book_and_sequence().await,
+            ("2.0".to_string(), Some(11), stale)
+        );
+
+        // A newer one is applied into the same entry; timing carries over
+        let dispatch = processor.process(&snapshot(20, "3.0")).await.unwrap();
+        assert!(matches!(dispatch, Some(Dispatch::OrderBook(..))));
+        let counts = SnapshotCounts {
+            resnapshots: 1,
+            stale_snapshots: 1,
+        };
+        assert_eq!(
+            book_and_sequence().await,
+            ("3.0".to_string(), Some(20), counts)
+        );
+        let timing = processor.order_book_sequences.lock().unwrap()["0"].timing;
+        assert_eq!(timing.server_timestamp, Some(1_020));
+
+        // After a reconnect the first snapshot is taken whatever its offset
+        processor.process(r#"{"type":"connected"}"#).await.unwrap();
+        processor.process(&snapshot(3, "4.0")).await.unwrap();
+        assert_eq!(
+            book_and_sequence().await,
+            ("4.0".to_string(), Some(3), counts)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_liquidations_are_delivered_and_buffered() {
+        let frames = vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/liquidations","channel":"liquidations:1","liquidations":[{"account_index":7,"size":"1","price":"100","timestamp":1,"type":"liquidation"}]}"#.to_string(),
+            // The update repeats the snapshot's event and adds an anonymous deleverage
+            r#"{"type":"update/liquidations","channel":"liquidations:1","liquidations":[{"account_index":7,"size":"1","price":"100","timestamp":1,"type":"liquidation"},{"size":"2","price":"99","timestamp":2,"tx_type":23}]}"#.to_string(),
+            r#"{"type":"update/liquidations","channel":"liquidations:1","liquidations":[{"size":"x","price":"99"}]}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(addr, WsClient::builder().liquidations(vec![1, 1]));
+        assert!(format!("{:?}", client).contains("liquidation_ids: [1]"));
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_ws_event(move |event| seen.lock().unwrap().push(event));
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let events = events.lock().unwrap();
+        let liquidations: Vec<&LiquidationEvent> = events
+            .iter()
+            .filter_map(|event| match event {
+                WsEvent::Liquidation(event) => Some(event),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            liquidations
+                .iter()
+                .map(|e| (e.timestamp, e.liquidated_account, e.kind))
+                .collect::<Vec<_>>(),
+            [
+                (1, Some(7), LiquidationKind::Liquidation),
+                (2, None, LiquidationKind::Deleverage)
+            ]
+        );
+        // The malformed update is reported, not fatal
+        assert!(events.iter().any(
+            |e| matches!(e, WsEvent::ParseError { channel, .. } if channel.as_deref() == Some("liquidations:1"))
+        ));
+
+        let recent = client.recent_liquidations(1, 1);
+        assert_eq!(recent.len(), 1);
+        assert_eq!((recent[0].market_index, recent[0].size), (1, Decimal::TWO));
+        assert_eq!(client.recent_liquidations(1, 10).len(), 2);
+        assert!(client.recent_liquidations(0, 10).is_empty());
+        assert_eq!(
+            client.subscription_state("liquidations/1"),
+            Some(SubscriptionState::Subscribed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_market_status_transitions_are_tracked() {
+        let frames = vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/market_stats","channel":"market_stats:2","market_stats":{"market_id":2,"status":"active"}}"#.to_string(),
+            // Unchanged and missing statuses aren't transitions
+            r#"{"type":"update/market_stats","channel":"market_stats:2","market_stats":{"market_id":2,"status":"active"}}"#.to_string(),
+            r#"{"type":"update/market_stats","channel":"market_stats:2","market_stats":{"market_id":2,"index_price":"1"}}"#.to_string(),
+            r#"{"type":"update/market_stats","channel":"market_stats:2","market_stats":{"market_id":2,"status":"halted"}}"#.to_string(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(addr, WsClient::builder().market_stats(vec![2]));
+        let statuses = client.market_statuses();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_ws_event(move |event| seen.lock().unwrap().push(event));
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        let changes: Vec<(Option<MarketStatus>, MarketStatus)> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|event| match event {
+                WsEvent::MarketStatus(change) => Some((change.previous, change.status)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (None, MarketStatus::Active),
+                (Some(MarketStatus::Active), MarketStatus::Halted)
+            ]
+        );
+        assert_eq!(client.market_status(2), Some(MarketStatus::Halted));
+        assert_eq!(client.market_status(3), None);
+        assert!(matches!(
+            statuses.check(2, false),
+            Err(LighterError::MarketNotActive(MarketStatus::Halted))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_account_subscription_error_does_not_stop_run() {
+        let frames = vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"error":{"code":30003,"message":"Invalid Channel: account_all/999999"}}"#
+                .to_string(),
+            sample_frames()[1].clone(),
+        ];
+        let addr = spawn_mock_ws_server(frames).await;
+        let client = mock_client(addr, WsClient::builder().accounts(vec![999_999]));
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let seen = errors.clone();
+        client.on_ws_event(move |event| {
+            if let WsEvent::ServerError(error) = event {
+                seen.lock().unwrap().push(error);
+            }
+        });
+        let books = Arc::new(AtomicU64::new(0));
+        let seen = books.clone();
+        client
+            .run(
+                move |_, _| {
+                    seen.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].channel.as_deref(), Some("account_all:999999"));
+        assert_eq!(errors[0].code, Some(30003));
+        assert_eq!(books.load(Ordering::Relaxed), 1);
+        assert!(matches!(
+            client.subscription_state("account_all/999999"),
+            Some(SubscriptionState::Failed(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_timeout() {
+        let addr = spawn_mock_ws_server(vec![r#"{"type":"connected"}"#.to_string()]).await;
+        let client = Arc::new(mock_client(addr, WsClient::builder()));
+
+        let runner = client.clone();
+        let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        let result = client
+            .await_subscribed("order_book/0", Duration::from_millis(200))
+            .await;
+        run.await.unwrap().unwrap();
+
+        assert!(matches!(result, Err(LighterError::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_messages_are_skipped() {
+        let long_garbage = format!(
+            "{{\"type\":\"update/order_book\",\"pad\":\"{}",
+            "é".repeat(400)
+        );
+        let addr = spawn_mock_ws_server(vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"101.0","size":"1.0"}],"bids":[]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"102.0","size":"1.0"},{"price":null,"size":"2.0"}],"bids":[]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":"oops"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:1","order_book":{"asks":[{"price":null}],"bids":[]}}"#.to_string(),
+            long_garbage.clone(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[],"bids":[{"price":"99.0","size":"4.0"}]}}"#.to_string(),
+        ])
+        .await;
+        let client = mock_client(addr, WsClient::builder());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        client.on_ws_event(move |event| sink.lock().unwrap().push(event));
+        let books = Arc::new(AtomicU64::new(0));
+        let counter = books.clone();
+
+        client
+            .run(
+                move |_, _| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        // The snapshot and the last update made it; the bad update was
+        // dropped as a whole
+        assert_eq!(books.load(Ordering::Relaxed), 2);
+        let book = client.get_order_book("0").await.unwrap();
+        assert_eq!(book.asks.len(), 1);
+        assert_eq!(book.asks[0].price, "101.0");
+        assert_eq!(book.bids[0].size, "4.0");
+        assert_eq!(client.malformed_messages(), 4);
+
+        let events = events.lock().unwrap().clone();
+        let errors: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WsEvent::ParseError { channel, raw, .. } => Some((channel.clone(), raw.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors.len(), 4);
+        assert_eq!(errors[0].0.as_deref(), Some("order_book:0"));
+        assert_eq!(errors[2].0.as_deref(), Some("order_book:1"));
+        assert_eq!(errors[3].0, None);
+        assert!(errors[3].1.len() <= 512);
+        assert!(long_garbage.starts_with(&errors[3].1));
+    }
+
+    #[test]
+    fn test_price_level_formatting() {
+        let level = PriceLevel {
+            price: "100.50".to_string(),
+            size: "0.12345".to_string(),
+        };
+        assert_eq!(level.normalized().price, "100.5");
+        assert_eq!(level.normalized().size, "0.12345");
+        let scaled = level.with_display_scale(3, 2);
+        assert_eq!(scaled.price, "100.500");
+        assert_eq!(scaled.size, "0.12");
+        assert_eq!(level.with_display_scale(0, 4).price, "100");
+
+        let odd = PriceLevel {
+            price: "n/a".to_string(),
+            size: "100.00".to_string(),
+        };
+        assert_eq!(odd.normalized().price, "n/a");
+        assert_eq!(odd.normalized().size, "100");
+    }
+
+    #[tokio::test]
+    async fn test_display_scale_makes_output_consistent() {
+        let addr = spawn_mock_ws_server(vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"100.0","size":"1.5"}],"bids":[{"price":"99","size":"2.000"}]}}"#.to_string(),
+            r#"{"type":"update/order_book","channel":"order_book:0","order_book":{"asks":[{"price":"100.0","size":"1.50"}],"bids":[{"price":"99","size":"3"}]}}"#.to_string(),
+        ])
+        .await;
+        let client = mock_client(addr, WsClient::builder());
+        client.set_display_scale(0, 2, 3);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        client
+            .run(move |_, book| sink.lock().unwrap().push(book), |_, _| {})
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        for book in &seen {
+            assert_eq!(book.asks[0].price, "100.00");
+            assert_eq!(book.asks[0].size, "1.500");
+            assert_eq!(book.bids[0].price, "99.00");
+        }
+        assert_eq!(seen[0].bids[0].size, "2.000");
+        assert_eq!(seen[1].bids[0].size, "3.000");
+
+        let book = client.get_order_book("0").await.unwrap();
+        assert_eq!(book, seen[1]);
+        // The stored book keeps the server's strings
+        let raw = client.order_book_states.read().unwrap()["0"].clone();
+        assert_eq!(raw.asks[0].size, "1.50");
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_accounts_before_stream_snapshot() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/account")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"code":200,"total":1,"accounts":[{"index":7,"collateral":"90",
+                    "positions":[{"market_id":0,"sign":1,"position":"1.5","avg_entry_price":"100"}]}]}"#,
+            )
+            .create_async()
+            .await;
+        let orders = server
+            .mock("GET", "/api/v1/accountActiveOrders")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"code":200,"orders":[{"order_index":5,"market_index":0,"is_ask":true,"price":"105","remaining_base_amount":"1","status":"open"}]}"#,
+            )
+            .create_async()
+            .await;
+        let mut http = HTTPClient::new(&server.url()).unwrap();
+        http.set_auth_token_source(Duration::from_secs(600), |_| Ok("token".to_string()))
+            .unwrap();
+
+        let addr = spawn_mock_ws_server(vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/account_all","channel":"account_all:7","collateral":"100","positions":{"0":{"market_id":0,"sign":1,"position":"2"}}}"#.to_string(),
+        ])
+        .await;
+        let mut client = WsClient::builder()
+            .order_books(vec![0])
+            .accounts(vec![7])
+            .bootstrap_accounts(true)
+            .rest_client(http)
+            .build()
+            .unwrap();
+        client.base_url = format!("ws://{}/stream", addr);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        client
+            .run(
+                |_, _| {},
+                move |account_id, account| {
+                    sink.lock().unwrap().push((
+                        account_id,
+                        AccountUpdateSource::of(&account),
+                        account["collateral"].clone(),
+                    ))
+                },
+            )
+            .await
+            .unwrap();
+        orders.assert_async().await;
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].0, "7");
+        assert_eq!(seen[0].1, Some(AccountUpdateSource::Bootstrap));
+        assert_eq!(seen[0].2, "90");
+        assert_eq!(seen[1].1, Some(AccountUpdateSource::Snapshot));
+
+        // The stream snapshot replaced the REST one
+        assert!(client.get_raw_account("7").await.is_none());
+        let snapshot = client.get_account_snapshot("7").await.unwrap();
+        assert_eq!(snapshot.collateral, Some(rust_decimal::Decimal::from(100)));
+        assert_eq!(snapshot.positions["0"].size, rust_decimal::Decimal::from(2));
+        assert!(snapshot.orders.is_empty());
+    }
+
+    #[test]
+    fn test_bootstrap_accounts_needs_rest_client() {
+        let result = WsClient::builder()
+            .accounts(vec![7])
+            .bootstrap_accounts(true)
+            .build();
+        assert!(matches!(result, Err(LighterError::InvalidConfiguration(_))));
+    }
+
+    #[tokio::test]
+    async fn test_verify_against_rest_resyncs_on_mismatch() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/orderBookOrders")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"code":200,"offset":5,"asks":[
+                    {"price":"101.00","remaining_base_amount":"1.5"},
+                    {"price":"101.0","remaining_base_amount":"0.5"}],
+                  "bids":[{"price":"99.0","remaining_base_amount":"3"}]}"#,
+            )
+            .create_async()
+            .await;
+        let http = HTTPClient::new(&server.url()).unwrap();
+
+        let client = WsClient::builder().order_books(vec![0]).build().unwrap();
+        client
+            .processor()
+            .process(r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":6,"asks":[{"price":"101.0","size":"2.0"}],"bids":[{"price":"99.0","size":"2.0"}]}}"#)
+            .await
+            .unwrap();
+
+        let policy = VerifyPolicy {
+            auto_resync: true,
+            ..Default::default()
+        };
+        let diff = client.verify_against_rest(0, &http, &policy).await.unwrap();
+
+        // Asks aggregate to the same level; the bid size differs
+        assert_eq!(diff.mismatched_levels(), 1);
+        assert_eq!(diff.offset_delta(), Some(1));
+        assert!(diff.is_inconsistent());
+        assert_eq!(client.sync_state(0), Some(BookSyncState::Resyncing));
+    }
+
+    #[tokio::test]
+    async fn test_resync_order_book_drops_stale_updates() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let book = |kind: &str, size: &str| {
+                Message::Text(format!(
+                    r#"{{"type":"{}/order_book","channel":"order_book:0","order_book":{{"asks":[{{"price":"101.0","size":"{}"}}],"bids":[]}}}}"#,
+                    kind, size
+                ))
+            };
+            ws.send(Message::Text(r#"{"type":"connected"}"#.to_string()))
+                .await
+                .unwrap();
+            ws.send(book("subscribed", "1.0")).await.unwrap();
+
+            async fn next_text(
+                ws: &mut tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+            ) -> String {
+                loop {
+                    if let Some(Ok(Message::Text(text))) = ws.next().await {
+                        return text;
+                    }
+                }
+            }
+            while !next_text(&mut ws).await.contains("unsubscribe") {}
+            // Still in flight when the unsubscribe arrived
+            ws.send(book("update", "9.0")).await.unwrap();
+            assert!(next_text(&mut ws).await.contains(r#""type":"subscribe""#));
+            ws.send(book("subscribed", "5.0")).await.unwrap();
+            ws.send(book("update", "6.0")).await.unwrap();
+            let _ = ws.close(None).await;
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let client = Arc::new(mock_client(addr, WsClient::builder()));
+        assert_eq!(client.sync_state(0), Some(BookSyncState::Desynced));
+        assert_eq!(client.sync_state(7), None);
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let seen = sizes.clone();
+        client.on_order_book(0, move |_, book| {
+            seen.lock().unwrap().push(book.asks[0].size.clone());
+        });
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        client.on_ws_event(move |event| seen.lock().unwrap().push(event));
+
+        let runner = client.clone();
+        let run = tokio::spawn(async move { runner.run(|_, _| {}, |_, _| {}).await });
+        client
+            .await_subscribed("order_book/0", Duration::from_secs(5))
+            .await
+            .unwrap();
+        client.resync_order_book(0).unwrap();
+        assert_eq!(client.sync_state(0), Some(BookSyncState::Resyncing));
+        run.await.unwrap().unwrap();
+
+        assert_eq!(*sizes.lock().unwrap(), vec!["1.0", "5.0", "6.0"]);
+        let sync = |state| WsEvent::OrderBookSync {
+            market_id: "0".to_string(),
+            state,
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![sync(BookSyncState::Resyncing), sync(BookSyncState::Synced)]
+        );
+        assert_eq!(client.sync_state(0), Some(BookSyncState::Synced));
+        assert!(client.resync_order_book(7).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ping_interval_sends_pings() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let got_ping = matches!(ws.next().await, Some(Ok(Message::Ping(_))));
+            // Send the queued pong before closing
+            ws.flush().await.unwrap();
+            let _ = ws.close(None).await;
+            while let Some(Ok(_)) = ws.next().await {}
+            got_ping
+        });
+
+        let config = WsConfig {
+            ping_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let client = mock_client(addr, WsClient::builder().config(config));
+        client.run(|_, _| {}, |_, _| {}).await.unwrap();
+
+        assert!(server.await.unwrap());
+    }
+
+    /// `count` updates of market 0 after a snapshot, cycling over 50 ask
+    /// prices with the offset as size
+    fn burst_of_updates(count: i64) -> Vec<String> {
+        let mut frames = vec![
+            r#"{"type":"connected"}"#.to_string(),
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","order_book":{"offset":0,"asks":[],"bids":[{"price":"99.0","size":"1"}]}}"#.to_string(),
+        ];
+        frames.extend((1..=count).map(|offset| {
+            format!(
+                r#"{{"type":"update/order_book","channel":"order_book:0","order_book":{{"offset":{},"asks":[{{"price":"{}.0","size":"{}"}}],"bids":[]}}}}"#,
+                offset,
+                100 + offset % 50,
+                offset
+            )
+        }));
+        frames
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_slow_snapshot_reader_does_not_stall_the_stream() {
+        const UPDATES: i64 = 5_000;
+        let addr = spawn_mock_ws_server(burst_of_updates(UPDATES)).await;
+        let client = Arc::new(mock_client(
+            addr,
+            WsClient::builder().frame_queue(64, FrameOverflow::Block),
+        ));
+
+        // Holds every book it reads for a while, as a slow consumer would
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let reader = {
+            let (client, done) = (client.clone(), done.clone());
+            tokio::task::spawn_blocking(move || {
+                let mut reads = 0;
+                while !done.load(Ordering::Relaxed) {
+                    if let Some(book) = client.book("0") {
+                        std::thread::sleep(Duration::from_millis(2));
+                        reads += book.bids.len();
+                    }
+                    std::thread::yield_now();
+                }
+                reads
+            })
+        };
+
+        let updates = Arc::new(AtomicU64::new(0));
+        let counter = updates.clone();
+        tokio::time::timeout(
+            Duration::from_secs(20),
+            client.run(
+                move |_, _| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                },
+                |_, _| {},
+            ),
+        )
+        .await
+        .expect("stream stalled")
+        .unwrap();
+        done.store(true, Ordering::Relaxed);
+        assert!(reader.await.unwrap() > 0);
+
+        // Every update applied, in order, to the final book
+        assert_eq!(updates.load(Ordering::Relaxed), UPDATES as u64 + 1);
+        assert_eq!(client.skipped_updates(), 0);
+        let book = client.get_order_book("0").await.unwrap();
+        let mut expected: Vec<(String, String)> = (UPDATES - 49..=UPDATES)
+            .map(|offset| (format!("{}.0", 100 + offset % 50), offset.to_string()))
+            .collect();
+        let mut asks: Vec<(String, String)> = book
+            .asks
+            .iter()
+            .map(|level| (level.price.clone(), level.size.clone()))
+            .collect();
+        expected.sort();
+        asks.sort();
+        assert_eq!(asks, expected);
+        assert_eq!(
+            client.order_book_sequences.lock().unwrap()["0"].offset,
+            Some(UPDATES)
+        );
+
+        let stats = client.frame_queue_stats();
+        assert_eq!(stats.capacity, 64);
+        assert_eq!(stats.depth, 0);
+        assert!(stats.high_water_mark <= 66, "{:?}", stats);
+        assert_eq!((stats.dropped_frames, stats.resyncs), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_full_frame_queue_drops_and_resyncs() {
+        let mut first = burst_of_updates(200);
+        first.push(r#"{"type":"subscribed/account_all","channel":"account_all:7"}"#.to_string());
+        let last = vec![burst_of_updates(201).pop().unwrap()];
+        let addr =
+            spawn_mock_ws_server_in_bursts(vec![first, last], Duration::from_millis(300)).await;
+        let client = mock_client(
+            addr,
+            WsClient::builder()
+                .accounts(vec![7])
+                .frame_queue(4, FrameOverflow::Resync),
+        );
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        client.on_ws_event(move |event| sink.lock().unwrap().push(event));
+
+        // A slow callback lets the reader get ahead of the run loop
+        client
+            .run(
+                |_, _| std::thread::sleep(Duration::from_millis(1)),
+                |_, _| {},
+            )
+            .await
+            .unwrap();
+
+        let stats = client.frame_queue_stats();
+        assert!(stats.dropped_frames > 0, "{:?}", stats);
+        assert!(stats.high_water_mark <= 6, "{:?}", stats);
+        // Every gap is reported, then the book resubscribed
+        let events = seen.lock().unwrap();
+        let gaps: Vec<u64> = events
+            .iter()
+            .filter_map(|event| match event {
+                WsEvent::FramesDropped { dropped } => Some(*dropped),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gaps.len() as u64, stats.resyncs);
+        assert_eq!(gaps.iter().sum::<u64>(), stats.dropped_frames);
+        assert_eq!(
+            events[1],
+            WsEvent::OrderBookSync {
+                market_id: "0".to_string(),
+                state: BookSyncState::Resyncing
+            }
+        );
+        // Updates after the gap wait for the new snapshot
+        assert_eq!(client.sync_state(0), Some(BookSyncState::Resyncing));
+        assert_eq!(
+            client.subscription_state("account_all:7"),
+            Some(SubscriptionState::Pending)
+        );
+    }
+
+    #[test]
+    fn test_frame_queue_needs_room_for_two_frames() {
+        let built = WsClient::builder()
+            .order_books(vec![0])
+            .frame_queue(1, FrameOverflow::Resync)
+            .build();
+        assert!(matches!(built, Err(LighterError::InvalidConfiguration(_))));
+    }
+}
@@ -0,0 +1,244 @@
+//! Filtering order book callbacks by what changed
+//!
+//! A [`NotifyPolicy`] decides which book updates reach handlers and `run`
+//! callbacks. Books are always updated in full; only the calls are skipped
+//! or merged. Snapshots are always delivered.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{OrderBook, PriceLevel};
+use crate::runtime_config::{ClientRuntimeConfig, RuntimeConfig};
+
+/// Which order book updates invoke callbacks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyPolicy {
+    /// Every applied update
+    #[default]
+    Always,
+    /// Updates that change a level within the best `n` of either side
+    TopN(usize),
+    /// Updates that change the best bid or ask; same as `TopN(1)`
+    BestOnly,
+    /// At most one call every this many milliseconds with the latest book
+    ///
+    /// Updates in between are merged into the next call, see
+    /// [`NotifyStats::last_coalesced`].
+    MinIntervalMs(u64),
+}
+
+impl NotifyPolicy {
+    /// Levels per side compared before and after an update
+    fn depth(self) -> Option<usize> {
+        match self {
+            NotifyPolicy::TopN(n) => Some(n),
+            NotifyPolicy::BestOnly => Some(1),
+            _ => None,
+        }
+    }
+}
+
+/// Callback counts of one market under its [`NotifyPolicy`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NotifyStats {
+    /// Books passed to callbacks, snapshots included
+    pub delivered: u64,
+    /// Updates that didn't touch the top levels and weren't delivered
+    pub suppressed: u64,
+    /// Updates merged into the most recent delivery, 1 unless coalescing
+    ///
+    /// Read it from an inline callback to get the count for the book being
+    /// handled.
+    pub last_coalesced: u64,
+}
+
+/// What to do with an applied update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Notify {
+    Deliver,
+    /// Not delivered now; a later update or flush may deliver it
+    Hold,
+}
+
+#[derive(Debug, Default)]
+struct MarketNotify {
+    stats: NotifyStats,
+    last_delivery: Option<Instant>,
+    /// Updates held back since the last delivery
+    pending: u64,
+}
+
+/// Per-market policies and delivery state shared with the message processor
+///
+/// Policies are read from the runtime config on every update.
+#[derive(Debug, Default)]
+pub(crate) struct BookNotifier {
+    config: RuntimeConfig,
+    markets: Mutex<HashMap<String, MarketNotify>>,
+}
+
+impl BookNotifier {
+    pub(crate) fn new(config: RuntimeConfig) -> Self {
+        Self {
+            config,
+            markets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn policy(&self, market_id: &str) -> NotifyPolicy {
+        policy_of(&self.config.get(), market_id)
+    }
+
+    /// Best levels to compare around an update, `None` if the policy doesn't look at them
+    pub(crate) fn top_levels(&self, market_id: &str, book: &OrderBook) -> Option<TopLevels> {
+        let depth = self.policy(market_id).depth()?;
+        Some(TopLevels {
+            asks: best_levels(&book.asks, depth, |a, b| a < b),
+            bids: best_levels(&book.bids, depth, |a, b| a > b),
+        })
+    }
+
+    /// Record a snapshot, which is always delivered along with anything held
+    pub(crate) fn on_snapshot(&self, market_id: &str, now: Instant) {
+        let mut markets = self.markets.lock().unwrap();
+        let market = markets.entry(market_id.to_string()).or_default();
+        market.pending += 1;
+        market.deliver(now);
+    }
+
+    /// Decide on an applied update; `top_changed` is `None` when the policy
+    /// doesn't look at the top levels
+    pub(crate) fn on_update(
+        &self,
+        market_id: &str,
+        top_changed: Option<bool>,
+        now: Instant,
+    ) -> Notify {
+        let policy = self.policy(market_id);
+        let mut markets = self.markets.lock().unwrap();
+        let market = markets.entry(market_id.to_string()).or_default();
+        match policy {
+            NotifyPolicy::Always => {}
+            NotifyPolicy::TopN(_) | NotifyPolicy::BestOnly => {
+                if top_changed == Some(false) {
+                    market.stats.suppressed += 1;
+                    return Notify::Hold;
+                }
+            }
+            NotifyPolicy::MinIntervalMs(ms) => {
+                market.pending += 1;
+                let due = market
+                    .last_delivery
+                    .is_none_or(|at| now >= at + Duration::from_millis(ms));
+                if !due {
+                    return Notify::Hold;
+                }
+                market.deliver(now);
+                return Notify::Deliver;
+            }
+        }
+        market.pending += 1;
+        market.deliver(now);
+        Notify::Deliver
+    }
+
+    /// When the earliest held update is due
+    pub(crate) fn next_flush(&self) -> Option<Instant> {
+        let config = self.config.get();
+        let markets = self.markets.lock().unwrap();
+        markets
+            .iter()
+            .filter(|(_, market)| market.pending > 0)
+            .filter_map(|(market_id, market)| match policy_of(&config, market_id) {
+                NotifyPolicy::MinIntervalMs(ms) => market
+                    .last_delivery
+                    .map(|at| at + Duration::from_millis(ms)),
+                _ => None,
+            })
+            .min()
+    }
+
+    /// Markets whose held updates are due by `now`, or all of them with
+    /// `all`; each is recorded as delivered
+    pub(crate) fn take_due(&self, now: Instant, all: bool) -> Vec<String> {
+        let config = self.config.get();
+        let mut markets = self.markets.lock().unwrap();
+        let mut due = Vec::new();
+        for (market_id, market) in markets.iter_mut() {
+            if market.pending == 0 {
+                continue;
+            }
+            let ready = all
+                || match policy_of(&config, market_id) {
+                    NotifyPolicy::MinIntervalMs(ms) => market
+                        .last_delivery
+                        .is_none_or(|at| now >= at + Duration::from_millis(ms)),
+                    _ => false,
+                };
+            if ready {
+                market.deliver(now);
+                due.push(market_id.clone());
+            }
+        }
+        due
+    }
+
+    pub(crate) fn stats(&self, market_id: &str) -> Option<NotifyStats> {
+        self.markets
+            .lock()
+            .unwrap()
+            .get(market_id)
+            .map(|market| market.stats)
+    }
+}
+
+fn policy_of(config: &ClientRuntimeConfig, market_id: &str) -> NotifyPolicy {
+    market_id
+        .parse()
+        .map_or(config.notify_policy, |id| config.notify_policy_for(id))
+}
+
+impl MarketNotify {
+    fn deliver(&mut self, now: Instant) {
+        self.stats.delivered += 1;
+        self.stats.last_coalesced = self.pending;
+        self.pending = 0;
+        self.last_delivery = Some(now);
+    }
+}
+
+/// Best levels of each side, best first
+#[derive(Debug, PartialEq)]
+pub(crate) struct TopLevels {
+    asks: Vec<PriceLevel>,
+    bids: Vec<PriceLevel>,
+}
+
+/// The `depth` best levels, where `better(a, b)` means price `a` beats `b`
+fn best_levels(
+    levels: &[PriceLevel],
+    depth: usize,
+    better: impl Fn(f64, f64) -> bool,
+) -> Vec<PriceLevel> {
+    let mut priced: Vec<(f64, &PriceLevel)> = levels
+        .iter()
+        .filter_map(|level| Some((level.price.parse::<f64>().ok()?, level)))
+        .collect();
+    priced.sort_by(|(a, _), (b, _)| {
+        if better(*a, *b) {
+            std::cmp::Ordering::Less
+        } else if better(*b, *a) {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    });
+    priced
+        .into_iter()
+        .take(depth)
+        .map(|(_, level)| level.clone())
+        .collect()
+}
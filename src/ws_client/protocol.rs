@@ -0,0 +1,300 @@
+//! Typed frames of the stream protocol
+//!
+//! Channels are requested as `order_book/0` and reported back as
+//! `order_book:0`; [`Channel`] parses both and prints the request form.
+//! Adding a channel kind only touches this module.
+//!
+//! ```
+//! use lighter_rs::ws_client::{Channel, WsRequest};
+//!
+//! let channel: Channel = "order_book:3".parse()?;
+//! assert_eq!(channel, Channel::OrderBook(3));
+//! assert_eq!(channel.to_string(), "order_book/3");
+//!
+//! let frame = serde_json::to_string(&WsRequest::Subscribe { channel })?;
+//! assert_eq!(frame, r#"{"type":"subscribe","channel":"order_book/3"}"#);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::{LighterError, Result};
+
+/// A stream channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Order book of a market
+    OrderBook(u32),
+    /// Everything about an account
+    AccountAll(i64),
+    /// Public trades of a market
+    Trades(u32),
+    /// Price and volume statistics of a market
+    MarketStats(u32),
+    /// Liquidations and deleverages in a market
+    Liquidations(u32),
+}
+
+impl Channel {
+    /// Name of the channel kind, as used on the wire
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Channel::OrderBook(_) => "order_book",
+            Channel::AccountAll(_) => "account_all",
+            Channel::Trades(_) => "trade",
+            Channel::MarketStats(_) => "market_stats",
+            Channel::Liquidations(_) => "liquidations",
+        }
+    }
+
+    /// Market of a market channel
+    pub fn market_id(&self) -> Option<u32> {
+        match *self {
+            Channel::OrderBook(id)
+            | Channel::Trades(id)
+            | Channel::MarketStats(id)
+            | Channel::Liquidations(id) => Some(id),
+            Channel::AccountAll(_) => None,
+        }
+    }
+
+    /// Account of an account channel
+    pub fn account_index(&self) -> Option<i64> {
+        match *self {
+            Channel::AccountAll(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The `kind:id` form the server reports channels in
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.kind(), self.id())
+    }
+
+    fn id(&self) -> i64 {
+        match *self {
+            Channel::OrderBook(id)
+            | Channel::Trades(id)
+            | Channel::MarketStats(id)
+            | Channel::Liquidations(id) => i64::from(id),
+            Channel::AccountAll(id) => id,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    /// The `kind/id` form used in requests
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.kind(), self.id())
+    }
+}
+
+impl FromStr for Channel {
+    type Err = LighterError;
+
+    /// Parse either `kind/id` or `kind:id`
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || LighterError::InvalidResponse(format!("Invalid channel {}", s));
+        let (kind, id) = s.split_once(['/', ':']).ok_or_else(invalid)?;
+        let market = || id.parse::<u32>().map_err(|_| invalid());
+        match kind {
+            "order_book" => market().map(Channel::OrderBook),
+            "trade" => market().map(Channel::Trades),
+            "market_stats" => market().map(Channel::MarketStats),
+            "liquidations" => market().map(Channel::Liquidations),
+            "account_all" => id
+                .parse::<i64>()
+                .map(Channel::AccountAll)
+                .map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Serialize for Channel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A message sent to the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "RequestFrame", from = "RequestFrame")]
+pub enum WsRequest {
+    Subscribe {
+        channel: Channel,
+    },
+    Unsubscribe {
+        channel: Channel,
+    },
+    Ping,
+    /// Authenticate the connection for private channels
+    Auth {
+        token: String,
+    },
+    /// Submit a signed transaction over the stream
+    SendTx {
+        tx_type: u8,
+        /// The signed transaction as a JSON object
+        tx_info: Value,
+    },
+}
+
+impl WsRequest {
+    /// The frame as JSON text
+    pub fn to_text(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Wire layout of [`WsRequest`]
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum RequestFrame {
+    #[serde(rename = "subscribe")]
+    Subscribe { channel: Channel },
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe { channel: Channel },
+    #[serde(rename = "ping")]
+    Ping,
+    #[serde(rename = "auth")]
+    Auth { auth: String },
+    #[serde(rename = "jsonapi/sendtx")]
+    SendTx { data: SendTxData },
+}
+
+#[derive(Serialize, Deserialize)]
+struct SendTxData {
+    tx_type: u8,
+    tx_info: Value,
+}
+
+impl From<WsRequest> for RequestFrame {
+    fn from(request: WsRequest) -> Self {
+        match request {
+            WsRequest::Subscribe { channel } => RequestFrame::Subscribe { channel },
+            WsRequest::Unsubscribe { channel } => RequestFrame::Unsubscribe { channel },
+            WsRequest::Ping => RequestFrame::Ping,
+            WsRequest::Auth { token } => RequestFrame::Auth { auth: token },
+            WsRequest::SendTx { tx_type, tx_info } => RequestFrame::SendTx {
+                data: SendTxData { tx_type, tx_info },
+            },
+        }
+    }
+}
+
+impl From<RequestFrame> for WsRequest {
+    fn from(frame: RequestFrame) -> Self {
+        match frame {
+            RequestFrame::Subscribe { channel } => WsRequest::Subscribe { channel },
+            RequestFrame::Unsubscribe { channel } => WsRequest::Unsubscribe { channel },
+            RequestFrame::Ping => WsRequest::Ping,
+            RequestFrame::Auth { auth } => WsRequest::Auth { token: auth },
+            RequestFrame::SendTx { data } => WsRequest::SendTx {
+                tx_type: data.tx_type,
+                tx_info: data.tx_info,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_channel_forms() {
+        let cases = [
+            (Channel::OrderBook(0), "order_book/0", "order_book:0"),
+            (
+                Channel::AccountAll(281474976710654),
+                "account_all/281474976710654",
+                "account_all:281474976710654",
+            ),
+            (Channel::Trades(12), "trade/12", "trade:12"),
+            (Channel::MarketStats(1), "market_stats/1", "market_stats:1"),
+            (Channel::Liquidations(2), "liquidations/2", "liquidations:2"),
+        ];
+        for (channel, request, reported) in cases {
+            assert_eq!(channel.to_string(), request);
+            assert_eq!(channel.key(), reported);
+            assert_eq!(request.parse::<Channel>().unwrap(), channel);
+            assert_eq!(reported.parse::<Channel>().unwrap(), channel);
+        }
+        for bad in [
+            "order_book",
+            "order_book:x",
+            "order_book:-1",
+            "candles/0",
+            "",
+        ] {
+            assert!(bad.parse::<Channel>().is_err(), "{}", bad);
+        }
+        assert_eq!(Channel::Trades(4).market_id(), Some(4));
+        assert_eq!(Channel::AccountAll(4).market_id(), None);
+        assert_eq!(Channel::AccountAll(4).account_index(), Some(4));
+    }
+
+    #[test]
+    fn test_request_frames_match_the_wire() {
+        let frames = [
+            (
+                WsRequest::Subscribe {
+                    channel: Channel::OrderBook(0),
+                },
+                r#"{"type":"subscribe","channel":"order_book/0"}"#,
+            ),
+            (
+                WsRequest::Unsubscribe {
+                    channel: Channel::AccountAll(7),
+                },
+                r#"{"type":"unsubscribe","channel":"account_all/7"}"#,
+            ),
+            (WsRequest::Ping, r#"{"type":"ping"}"#),
+            (
+                WsRequest::Auth {
+                    token: "1700000000:7:0:abcd".to_string(),
+                },
+                r#"{"type":"auth","auth":"1700000000:7:0:abcd"}"#,
+            ),
+            (
+                WsRequest::SendTx {
+                    tx_type: 14,
+                    tx_info: json!({"AccountIndex": 7, "Nonce": 3}),
+                },
+                r#"{"type":"jsonapi/sendtx","data":{"tx_type":14,"tx_info":{"AccountIndex":7,"Nonce":3}}}"#,
+            ),
+        ];
+        for (request, frame) in frames {
+            assert_eq!(request.to_text().unwrap(), frame);
+            assert_eq!(serde_json::from_str::<WsRequest>(frame).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn test_channels_of_captured_server_frames() {
+        let frames = [
+            r#"{"type":"subscribed/order_book","channel":"order_book:0","offset":5,"order_book":{"asks":[],"bids":[]}}"#,
+            r#"{"type":"update/account_all","channel":"account_all:7","trades":{}}"#,
+        ];
+        let channels: Vec<Channel> = frames
+            .iter()
+            .map(|frame| {
+                let parsed: Value = serde_json::from_str(frame).unwrap();
+                Channel::deserialize(&parsed["channel"]).unwrap()
+            })
+            .collect();
+        assert_eq!(channels, [Channel::OrderBook(0), Channel::AccountAll(7)]);
+    }
+}
@@ -0,0 +1,540 @@
+//! Estimating how much size rests ahead of one's own orders
+//!
+//! [`QueuePositionEstimator`] follows one account's resting orders in one
+//! market. When an order is first seen, the size displayed at its price is
+//! taken as ahead of it. Trades at the price consume the front of the queue
+//! and always reduce it; other reductions of the level are cancellations,
+//! attributed ahead or behind by [`QueueAttribution`]. An estimate never
+//! exceeds the level's displayed size and never goes below zero.
+//!
+//! A snapshot replacing a book that was already held means updates may
+//! have been missed, so every estimate taken before it is marked stale.
+//! [`WsClientBuilder::queue_positions`](super::WsClientBuilder::queue_positions)
+//! keeps estimators for every subscribed market and account.
+
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use super::{AccountEvent, AccountOrder, OrderBook};
+use crate::order_flow::FlowTrade;
+use crate::types::Side;
+
+/// Where cancellations at an order's price are assumed to come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueueAttribution {
+    /// Behind the order; only trades move it forward
+    #[default]
+    Conservative,
+    /// Ahead of the order; every reduction moves it forward
+    Optimistic,
+}
+
+/// Estimated queue position of one order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueEstimate {
+    pub side: Side,
+    pub price: Decimal,
+    /// Size estimated to rest ahead of the order at its price
+    pub ahead: Decimal,
+    /// The book was resynced after the estimate was taken; `ahead` may be
+    /// arbitrarily wrong
+    pub stale: bool,
+}
+
+/// An order being followed
+#[derive(Debug, Clone)]
+struct Tracked {
+    order_index: i64,
+    side: Side,
+    price: Decimal,
+    /// `None` until there is a book to measure against
+    ahead: Option<Decimal>,
+    stale: bool,
+}
+
+/// A displayed level
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    size: Decimal,
+    /// Traded size not yet seen as a reduction of the level
+    traded: Decimal,
+}
+
+/// Queue positions of one account's orders in one market
+#[derive(Debug, Clone, Default)]
+pub struct QueuePositionEstimator {
+    attribution: QueueAttribution,
+    bids: BTreeMap<Decimal, Level>,
+    asks: BTreeMap<Decimal, Level>,
+    has_book: bool,
+    /// By client order index
+    orders: HashMap<i64, Tracked>,
+}
+
+impl QueuePositionEstimator {
+    /// Estimator without a book or orders yet
+    pub fn new(attribution: QueueAttribution) -> Self {
+        Self {
+            attribution,
+            ..Default::default()
+        }
+    }
+
+    /// Replace the book with a snapshot
+    ///
+    /// Estimates taken against an earlier book are marked stale; orders
+    /// seen before the first book are measured against this one.
+    pub fn on_snapshot(&mut self, book: &OrderBook) {
+        if self.has_book {
+            for order in self.orders.values_mut() {
+                order.stale = true;
+            }
+        }
+        self.bids = levels_of(book, Side::Buy);
+        self.asks = levels_of(book, Side::Sell);
+        self.has_book = true;
+        for order in self.orders.values_mut() {
+            if order.ahead.is_none() {
+                let shown = level_of(&self.bids, &self.asks, order.side, order.price).size;
+                order.ahead = Some(shown);
+            }
+        }
+    }
+
+    /// Apply a book delta; levels sent with size zero are removed
+    pub fn on_delta(&mut self, delta: &OrderBook) {
+        for (side, levels) in [(Side::Buy, &delta.bids), (Side::Sell, &delta.asks)] {
+            for level in levels {
+                let (Ok(price), Ok(size)) = (
+                    Decimal::from_str(&level.price),
+                    Decimal::from_str(&level.size),
+                ) else {
+                    continue;
+                };
+                self.set_level(side, price, size.max(Decimal::ZERO));
+            }
+        }
+    }
+
+    /// Record a public trade; it consumes the front of the queue at its price
+    ///
+    /// Feed trades before the book update showing the level they reduced,
+    /// so that reduction isn't also counted as cancellations.
+    pub fn on_trade(&mut self, trade: &FlowTrade) {
+        // Buyers take asks, sellers take bids
+        let side = match trade.aggressor {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        if let Some(level) = self.levels_mut(side).get_mut(&trade.price) {
+            level.traded += trade.size;
+        }
+        self.move_forward(side, trade.price, trade.size);
+    }
+
+    /// Follow an order of the account, or stop once it is terminal
+    ///
+    /// An order seen for the first time, or at a new price, is measured
+    /// against the size displayed at its price now.
+    pub fn on_order(&mut self, order: &AccountOrder) {
+        if order.is_terminal() {
+            self.orders.remove(&order.client_order_index);
+            return;
+        }
+        let side = if order.is_ask { Side::Sell } else { Side::Buy };
+        let known = self
+            .orders
+            .get(&order.client_order_index)
+            .is_some_and(|t| t.side == side && t.price == order.price);
+        if known {
+            return;
+        }
+        let ahead = self
+            .has_book
+            .then(|| level_of(&self.bids, &self.asks, side, order.price).size);
+        self.orders.insert(
+            order.client_order_index,
+            Tracked {
+                order_index: order.order_index,
+                side,
+                price: order.price,
+                ahead,
+                stale: false,
+            },
+        );
+    }
+
+    /// Follow exactly the open orders in `orders`
+    pub fn sync_orders(&mut self, orders: &[AccountOrder]) {
+        self.orders
+            .retain(|cid, _| orders.iter().any(|o| o.client_order_index == *cid));
+        for order in orders {
+            self.on_order(order);
+        }
+    }
+
+    /// Apply an event of the account
+    ///
+    /// A fill of an order means nothing is left ahead of it; finished
+    /// orders are no longer followed.
+    pub fn on_account_event(&mut self, event: &AccountEvent) {
+        match event {
+            AccountEvent::Fill { order_index, .. } => {
+                if let Some(order) = self.by_order_index(*order_index) {
+                    order.ahead = Some(Decimal::ZERO);
+                }
+            }
+            AccountEvent::OrderFilled { order_index, .. }
+            | AccountEvent::OrderCancelled { order_index, .. }
+            | AccountEvent::OrderRejected { order_index, .. } => {
+                self.orders.retain(|_, o| o.order_index != *order_index);
+            }
+            _ => {}
+        }
+    }
+
+    /// Size ahead of an order, `None` if it isn't followed, there is no book
+    /// yet or the estimate is stale
+    pub fn estimated_ahead(&self, client_order_index: i64) -> Option<Decimal> {
+        self.estimate(client_order_index)
+            .filter(|e| !e.stale)
+            .map(|e| e.ahead)
+    }
+
+    /// Estimate of an order, including stale ones
+    pub fn estimate(&self, client_order_index: i64) -> Option<QueueEstimate> {
+        let order = self.orders.get(&client_order_index)?;
+        Some(QueueEstimate {
+            side: order.side,
+            price: order.price,
+            ahead: order.ahead?,
+            stale: order.stale,
+        })
+    }
+
+    /// Estimates of every followed order, by client order index
+    pub fn estimates(&self) -> HashMap<i64, QueueEstimate> {
+        self.orders
+            .keys()
+            .filter_map(|cid| Some((*cid, self.estimate(*cid)?)))
+            .collect()
+    }
+
+    fn set_level(&mut self, side: Side, price: Decimal, size: Decimal) {
+        let attribution = self.attribution;
+        let levels = self.levels_mut(side);
+        if size.is_zero() {
+            // Nothing rests at a removed level, so nothing is ahead
+            levels.remove(&price);
+            for order in self.orders_at(side, price) {
+                order.ahead = Some(Decimal::ZERO);
+            }
+            return;
+        }
+        let level = levels.entry(price).or_default();
+        let reduced = (level.size - size).max(Decimal::ZERO);
+        let by_trades = reduced.min(level.traded);
+        level.traded -= by_trades;
+        level.size = size;
+        let cancelled = reduced - by_trades;
+        if attribution == QueueAttribution::Optimistic {
+            self.move_forward(side, price, cancelled);
+        }
+        for order in self.orders_at(side, price) {
+            order.ahead = order.ahead.map(|ahead| ahead.min(size));
+        }
+    }
+
+    fn move_forward(&mut self, side: Side, price: Decimal, size: Decimal) {
+        for order in self.orders_at(side, price) {
+            order.ahead = order.ahead.map(|ahead| (ahead - size).max(Decimal::ZERO));
+        }
+    }
+
+    fn orders_at(&mut self, side: Side, price: Decimal) -> impl Iterator<Item = &mut Tracked> {
+        self.orders
+            .values_mut()
+            .filter(move |o| o.side == side && o.price == price)
+    }
+
+    fn by_order_index(&mut self, order_index: i64) -> Option<&mut Tracked> {
+        self.orders
+            .values_mut()
+            .find(|o| o.order_index == order_index)
+    }
+
+    fn levels_mut(&mut self, side: Side) -> &mut BTreeMap<Decimal, Level> {
+        match side {
+            Side::Buy => &mut self.bids,
+            Side::Sell => &mut self.asks,
+        }
+    }
+}
+
+fn levels_of(book: &OrderBook, side: Side) -> BTreeMap<Decimal, Level> {
+    let levels = match side {
+        Side::Buy => &book.bids,
+        Side::Sell => &book.asks,
+    };
+    levels
+        .iter()
+        .filter_map(|level| {
+            let price = Decimal::from_str(&level.price).ok()?;
+            let size = Decimal::from_str(&level.size).ok()?;
+            (size > Decimal::ZERO).then_some((
+                price,
+                Level {
+                    size,
+                    traded: Decimal::ZERO,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn level_of(
+    bids: &BTreeMap<Decimal, Level>,
+    asks: &BTreeMap<Decimal, Level>,
+    side: Side,
+    price: Decimal,
+) -> Level {
+    let levels = match side {
+        Side::Buy => bids,
+        Side::Sell => asks,
+    };
+    levels.get(&price).copied().unwrap_or_default()
+}
+
+/// Estimators a [`WsClient`](super::WsClient) keeps, by market and account
+#[derive(Debug, Default)]
+pub(crate) struct QueuePositions {
+    estimators: Mutex<HashMap<(String, i64), QueuePositionEstimator>>,
+}
+
+impl QueuePositions {
+    /// An estimator for every pair of market and account
+    pub(crate) fn new(markets: &[u32], accounts: &[i64], attribution: QueueAttribution) -> Self {
+        let estimators = markets
+            .iter()
+            .flat_map(|market| {
+                accounts.iter().map(move |account| {
+                    (
+                        (market.to_string(), *account),
+                        QueuePositionEstimator::new(attribution),
+                    )
+                })
+            })
+            .collect();
+        Self {
+            estimators: Mutex::new(estimators),
+        }
+    }
+
+    /// Apply `f` to every estimator of a market
+    pub(crate) fn for_market(
+        &self,
+        market_id: &str,
+        mut f: impl FnMut(&mut QueuePositionEstimator),
+    ) {
+        for ((market, _), estimator) in self.estimators.lock().unwrap().iter_mut() {
+            if market == market_id {
+                f(estimator);
+            }
+        }
+    }
+
+    /// Apply `f` to every estimator of an account, with its market
+    pub(crate) fn for_account(
+        &self,
+        account_index: i64,
+        mut f: impl FnMut(&str, &mut QueuePositionEstimator),
+    ) {
+        for ((market, account), estimator) in self.estimators.lock().unwrap().iter_mut() {
+            if *account == account_index {
+                f(market, estimator);
+            }
+        }
+    }
+
+    /// Estimates of an account's orders in a market
+    pub(crate) fn estimates(
+        &self,
+        market_id: &str,
+        account_index: i64,
+    ) -> HashMap<i64, QueueEstimate> {
+        self.estimators
+            .lock()
+            .unwrap()
+            .get(&(market_id.to_string(), account_index))
+            .map(QueuePositionEstimator::estimates)
+            .unwrap_or_default()
+    }
+
+    /// Estimate of an account's order in whichever market it rests
+    pub(crate) fn estimate(
+        &self,
+        account_index: i64,
+        client_order_index: i64,
+    ) -> Option<QueueEstimate> {
+        self.estimators
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((_, account), _)| *account == account_index)
+            .find_map(|(_, estimator)| estimator.estimate(client_order_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ws_client::PriceLevel;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    fn book(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+        let levels = |levels: &[(&str, &str)]| {
+            levels
+                .iter()
+                .map(|(price, size)| PriceLevel {
+                    price: price.to_string(),
+                    size: size.to_string(),
+                })
+                .collect()
+        };
+        OrderBook {
+            bids: levels(bids),
+            asks: levels(asks),
+        }
+    }
+
+    fn bid(cid: i64, price: &str) -> AccountOrder {
+        AccountOrder {
+            order_index: cid + 1000,
+            client_order_index: cid,
+            market_index: 0,
+            is_ask: false,
+            price: dec(price),
+            remaining_base_amount: dec("1"),
+            status: Some("open".to_string()),
+            extras: Default::default(),
+        }
+    }
+
+    fn sell(price: &str, size: &str) -> FlowTrade {
+        FlowTrade {
+            price: dec(price),
+            size: dec(size),
+            aggressor: Side::Sell,
+        }
+    }
+
+    /// Placed behind 10 at 100, then trades, cancellations and joins
+    fn script(attribution: QueueAttribution) -> Vec<Decimal> {
+        let ahead = |e: &QueuePositionEstimator| e.estimated_ahead(1).unwrap();
+        let mut e = QueuePositionEstimator::new(attribution);
+        e.on_snapshot(&book(&[("100", "10"), ("99", "4")], &[("101", "6")]));
+        e.on_order(&bid(1, "100"));
+        let mut seen = vec![ahead(&e)];
+
+        // The order itself shows up behind
+        e.on_delta(&book(&[("100", "11")], &[]));
+        seen.push(ahead(&e));
+        e.on_trade(&sell("100", "3"));
+        seen.push(ahead(&e));
+        // The reduction the trade caused
+        e.on_delta(&book(&[("100", "8")], &[]));
+        seen.push(ahead(&e));
+        // 2 cancelled
+        e.on_delta(&book(&[("100", "6")], &[]));
+        seen.push(ahead(&e));
+        // Joined behind
+        e.on_delta(&book(&[("100", "10")], &[]));
+        seen.push(ahead(&e));
+        // Other levels and sides
+        e.on_trade(&sell("99", "4"));
+        e.on_delta(&book(&[("99", "0")], &[("100", "2")]));
+        seen.push(ahead(&e));
+        e.on_trade(&sell("100", "1"));
+        e.on_delta(&book(&[("100", "9")], &[]));
+        seen.push(ahead(&e));
+        seen
+    }
+
+    #[test]
+    fn test_conservative_attribution() {
+        let expected = ["10", "10", "7", "7", "6", "6", "6", "5"];
+        assert_eq!(script(QueueAttribution::Conservative), expected.map(dec));
+
+        // Cancellations only count once the level can't hold the estimate
+        let mut estimator = QueuePositionEstimator::new(QueueAttribution::Conservative);
+        estimator.on_snapshot(&book(&[("100", "10")], &[]));
+        estimator.on_order(&bid(1, "100"));
+        estimator.on_delta(&book(&[("100", "12")], &[]));
+        estimator.on_delta(&book(&[("100", "8")], &[]));
+        assert_eq!(estimator.estimated_ahead(1), Some(dec("8")));
+    }
+
+    #[test]
+    fn test_optimistic_attribution() {
+        let expected = ["10", "10", "7", "7", "5", "5", "5", "4"];
+        assert_eq!(script(QueueAttribution::Optimistic), expected.map(dec));
+
+        // Every cancellation moves the order forward, never below zero
+        let mut estimator = QueuePositionEstimator::new(QueueAttribution::Optimistic);
+        estimator.on_snapshot(&book(&[("100", "10")], &[]));
+        estimator.on_order(&bid(1, "100"));
+        estimator.on_delta(&book(&[("100", "12")], &[]));
+        estimator.on_delta(&book(&[("100", "8")], &[]));
+        assert_eq!(estimator.estimated_ahead(1), Some(dec("6")));
+        estimator.on_trade(&sell("100", "9"));
+        assert_eq!(estimator.estimated_ahead(1), Some(Decimal::ZERO));
+    }
+
+    #[test]
+    fn test_removal_fill_and_resync() {
+        let mut estimator = QueuePositionEstimator::new(QueueAttribution::Conservative);
+        // Seen before the book: measured once it arrives
+        estimator.on_order(&bid(1, "100"));
+        assert_eq!(estimator.estimate(1), None);
+        estimator.on_snapshot(&book(&[("100", "10"), ("99", "3")], &[]));
+        assert_eq!(estimator.estimated_ahead(1), Some(dec("10")));
+        estimator.on_order(&bid(2, "99"));
+        // A new level has nothing ahead
+        estimator.on_order(&bid(3, "98"));
+        assert_eq!(estimator.estimated_ahead(3), Some(Decimal::ZERO));
+
+        estimator.on_delta(&book(&[("99", "0")], &[]));
+        assert_eq!(estimator.estimated_ahead(2), Some(Decimal::ZERO));
+        estimator.on_account_event(&AccountEvent::Fill {
+            market: 0,
+            price: dec("100"),
+            size: dec("0.5"),
+            side: Side::Buy,
+            order_index: 1001,
+        });
+        assert_eq!(estimator.estimated_ahead(1), Some(Decimal::ZERO));
+
+        estimator.on_snapshot(&book(&[("100", "10")], &[]));
+        let stale = estimator.estimate(1).unwrap();
+        assert!(stale.stale);
+        assert_eq!(estimator.estimated_ahead(1), None);
+        // Orders placed after the resync are estimated afresh
+        estimator.on_order(&bid(4, "100"));
+        assert_eq!(estimator.estimated_ahead(4), Some(dec("10")));
+
+        estimator.on_account_event(&AccountEvent::OrderCancelled {
+            market: 0,
+            order_index: 1004,
+        });
+        estimator.sync_orders(&[bid(1, "100")]);
+        assert_eq!(
+            estimator.estimates().keys().copied().collect::<Vec<_>>(),
+            [1]
+        );
+    }
+}
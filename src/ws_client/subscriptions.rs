@@ -0,0 +1,431 @@
+//! Tracking of subscription acknowledgements
+//!
+//! The stream protocol has no request id, so acks and errors are correlated
+//! with requests by channel. Requests use `order_book/0` while acks report
+//! `order_book:0`; both forms are accepted everywhere.
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use super::{BookSyncState, Channel, LiquidationEvent};
+use crate::errors::{LighterError, Result};
+use crate::market_status::MarketStatusChange;
+
+/// State of one subscription on the current connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionState {
+    /// Requested (or about to be) but not yet acknowledged
+    Pending,
+    /// Acknowledged by the server
+    Subscribed,
+    /// Rejected by the server with a reason
+    Failed(String),
+}
+
+/// Error message sent by the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsServerError {
+    /// Channel the error belongs to, when it could be identified
+    pub channel: Option<String>,
+    pub code: Option<i64>,
+    pub message: String,
+    /// The message as received
+    pub raw: String,
+}
+
+impl WsServerError {
+    /// Parse a server message, returning `None` unless it reports an error
+    ///
+    /// Errors come typed (`"type": "error"`), as a bare `{"error": {...}}`
+    /// object, or as a `subscribed/*` message with a non-zero `code`.
+    pub fn from_message(message: &Value) -> Option<Self> {
+        let msg_type = message.get("type").and_then(|t| t.as_str());
+        let error = message.get("error").unwrap_or(message);
+        let code = error.get("code").and_then(|c| c.as_i64());
+        let is_error = msg_type == Some("error")
+            || message.get("error").is_some()
+            || (msg_type.is_some_and(|t| t.starts_with("subscribed/"))
+                && code.is_some_and(|c| c != 0 && c != 200));
+        if !is_error {
+            return None;
+        }
+
+        let text = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        Some(Self {
+            channel: message
+                .get("channel")
+                .and_then(|c| c.as_str())
+                .map(normalize_channel),
+            code,
+            message: text,
+            raw: message.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for WsServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(code) = self.code {
+            write!(f, "[{}] ", code)?;
+        }
+        f.write_str(&self.message)?;
+        if let Some(channel) = &self.channel {
+            write!(f, " ({})", channel)?;
+        }
+        Ok(())
+    }
+}
+
+/// Event about the stream itself rather than its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WsEvent {
+    /// The server reported an error; the stream keeps running
+    ServerError(WsServerError),
+    /// The server rejected a subscription
+    SubscriptionFailed { channel: String, reason: String },
+    /// A requested order book resync started or completed
+    OrderBookSync {
+        market_id: String,
+        state: BookSyncState,
+    },
+    /// An order book snapshot or update came with a non-zero `code` and
+    /// was not applied
+    ///
+    /// The book keeps its previous contents. Code meanings aren't
+    /// published, so the code is passed on as sent.
+    OrderBookError { market_id: String, code: i64 },
+    /// A position in a subscribed market was liquidated or deleveraged
+    Liquidation(LiquidationEvent),
+    /// A market's trading status changed, or arrived for the first time
+    MarketStatus(MarketStatusChange),
+    /// A repeated order book snapshot was older than the book and dropped
+    StaleSnapshot {
+        market_id: String,
+        offset: i64,
+        book_offset: i64,
+    },
+    /// Frames were dropped on a full frame queue; every channel is being
+    /// resubscribed
+    FramesDropped { dropped: u64 },
+    /// A message couldn't be parsed or applied and was skipped
+    ParseError {
+        channel: Option<String>,
+        error: String,
+        /// Start of the message, at most 512 bytes
+        raw: String,
+    },
+}
+
+/// Canonical `kind:id` form of a channel
+pub(crate) fn normalize_channel(channel: &str) -> String {
+    channel
+        .parse::<Channel>()
+        .map(|c| c.key())
+        .unwrap_or_else(|_| channel.replacen('/', ":", 1))
+}
+
+/// Subscription states shared between the run loop and waiters
+pub(crate) struct Subscriptions {
+    states: watch::Sender<HashMap<String, SubscriptionState>>,
+}
+
+impl Subscriptions {
+    /// Track `channels`, all pending
+    pub(crate) fn new(channels: impl IntoIterator<Item = Channel>) -> Self {
+        let states = channels
+            .into_iter()
+            .map(|c| (c.key(), SubscriptionState::Pending))
+            .collect();
+        Self {
+            states: watch::Sender::new(states),
+        }
+    }
+
+    /// Mark every subscription pending again, e.g. on a new connection
+    pub(crate) fn reset(&self) {
+        self.states.send_modify(|states| {
+            for state in states.values_mut() {
+                *state = SubscriptionState::Pending;
+            }
+        });
+    }
+
+    /// Mark one subscription pending again, e.g. when resubscribing
+    pub(crate) fn mark_pending(&self, channel: &str) {
+        let channel = normalize_channel(channel);
+        self.states
+            .send_if_modified(|states| match states.get_mut(&channel) {
+                Some(state) if *state != SubscriptionState::Pending => {
+                    *state = SubscriptionState::Pending;
+                    true
+                }
+                _ => false,
+            });
+    }
+
+    /// Record an ack; acks for untracked channels are ignored
+    pub(crate) fn acknowledge(&self, channel: &str) {
+        let channel = normalize_channel(channel);
+        self.states
+            .send_if_modified(|states| match states.get_mut(&channel) {
+                Some(state) if *state != SubscriptionState::Subscribed => {
+                    *state = SubscriptionState::Subscribed;
+                    true
+                }
+                _ => false,
+            });
+    }
+
+    /// Match a server error to a subscription and fail it
+    ///
+    /// The channel is taken from the error's `channel` or, failing that, from
+    /// a pending channel named in the error text, and is filled into `error`.
+    /// Returns the failure event, or `None` when no subscription could be
+    /// identified.
+    pub(crate) fn fail_from_error(&self, error: &mut WsServerError) -> Option<WsEvent> {
+        let reason = error.message.clone();
+
+        let mut failed = None;
+        self.states.send_if_modified(|states| {
+            let channel = match &error.channel {
+                Some(channel) => Some(channel.clone()),
+                None => states
+                    .iter()
+                    .filter(|(_, state)| **state == SubscriptionState::Pending)
+                    .map(|(channel, _)| channel)
+                    .find(|channel| {
+                        reason.contains(channel.as_str())
+                            || reason.contains(&channel.replacen(':', "/", 1))
+                    })
+                    .cloned(),
+            };
+            let Some(channel) = channel.filter(|c| states.contains_key(c)) else {
+                return false;
+            };
+            states.insert(channel.clone(), SubscriptionState::Failed(reason.clone()));
+            failed = Some(channel);
+            true
+        });
+
+        if error.channel.is_none() {
+            error.channel = failed.clone();
+        }
+        failed.map(|channel| WsEvent::SubscriptionFailed { channel, reason })
+    }
+
+    /// Current state of a subscription
+    pub(crate) fn state(&self, channel: &str) -> Option<SubscriptionState> {
+        self.states
+            .borrow()
+            .get(&normalize_channel(channel))
+            .cloned()
+    }
+
+    /// Wait until a subscription is acknowledged or rejected
+    pub(crate) async fn wait(&self, channel: &str, timeout: Duration) -> Result<()> {
+        let channel = normalize_channel(channel);
+        if !self.states.borrow().contains_key(&channel) {
+            return Err(LighterError::ValidationError(format!(
+                "{} is not a configured subscription",
+                channel
+            )));
+        }
+
+        let mut rx = self.states.subscribe();
+        let settled =
+            rx.wait_for(|states| states.get(&channel) != Some(&SubscriptionState::Pending));
+        let state = match tokio::time::timeout(timeout, settled).await {
+            Ok(Ok(states)) => states.get(&channel).cloned(),
+            // The sender lives as long as `self`
+            Ok(Err(_)) => None,
+            Err(_) => return Err(LighterError::Timeout),
+        };
+
+        match state {
+            Some(SubscriptionState::Failed(reason)) => {
+                Err(LighterError::SubscriptionFailed { channel, reason })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Updates held per channel before its first ack
+pub const EARLY_UPDATE_LIMIT: usize = 1_000;
+
+/// Updates that arrived before their channel's first ack on a connection
+///
+/// They are held and released in order once the ack, and the snapshot it
+/// carries, has been applied. Updates beyond [`EARLY_UPDATE_LIMIT`] per
+/// channel, and any still held when the connection ends, are dropped and
+/// counted.
+#[derive(Default)]
+pub(crate) struct EarlyUpdates {
+    /// Channels acknowledged on the current connection
+    acked: HashSet<String>,
+    held: HashMap<String, Vec<(Value, Instant)>>,
+    released: VecDeque<(Value, Instant)>,
+    dropped: u64,
+}
+
+impl EarlyUpdates {
+    /// Start over for a new connection
+    pub(crate) fn reset(&mut self) {
+        self.acked.clear();
+        self.released.clear();
+        self.dropped += self
+            .held
+            .drain()
+            .map(|(_, held)| held.len() as u64)
+            .sum::<u64>();
+    }
+
+    /// Hold an update unless its channel was acknowledged on this connection
+    ///
+    /// Returns the update back if it should be applied now.
+    pub(crate) fn hold(
+        &mut self,
+        channel: &str,
+        update: Value,
+        received_at: Instant,
+    ) -> Option<Value> {
+        let channel = normalize_channel(channel);
+        if self.acked.contains(&channel) {
+            return Some(update);
+        }
+        let held = self.held.entry(channel).or_default();
+        if held.len() < EARLY_UPDATE_LIMIT {
+            held.push((update, received_at));
+        } else {
+            self.dropped += 1;
+        }
+        None
+    }
+
+    /// Record a channel's ack, releasing its held updates
+    pub(crate) fn acknowledge(&mut self, channel: &str) {
+        let channel = normalize_channel(channel);
+        if let Some(held) = self.held.remove(&channel) {
+            self.released.extend(held);
+        }
+        self.acked.insert(channel);
+    }
+
+    /// Next released update to apply
+    pub(crate) fn next_released(&mut self) -> Option<(Value, Instant)> {
+        self.released.pop_front()
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_correlated_by_channel_or_text() {
+        let subs = Subscriptions::new([Channel::OrderBook(0), Channel::OrderBook(1)]);
+        subs.acknowledge("order_book:0");
+        assert_eq!(
+            subs.state("order_book/0"),
+            Some(SubscriptionState::Subscribed)
+        );
+
+        let error = |message: Value| WsServerError::from_message(&message).unwrap();
+
+        // Text mentioning an already acknowledged channel isn't a match
+        let mut acked = error(serde_json::json!({
+            "error": {"code": 30003, "message": "Invalid Channel: order_book/0"}
+        }));
+        assert!(subs.fail_from_error(&mut acked).is_none());
+        assert_eq!(acked.channel, None);
+
+        let mut pending = error(serde_json::json!({
+            "error": {"code": 30003, "message": "Invalid Channel: order_book/1"}
+        }));
+        let event = subs.fail_from_error(&mut pending);
+        assert_eq!(pending.channel.as_deref(), Some("order_book:1"));
+        assert_eq!(
+            event,
+            Some(WsEvent::SubscriptionFailed {
+                channel: "order_book:1".to_string(),
+                reason: "Invalid Channel: order_book/1".to_string(),
+            })
+        );
+
+        subs.reset();
+        assert_eq!(subs.state("order_book:1"), Some(SubscriptionState::Pending));
+    }
+
+    #[test]
+    fn test_early_updates_wait_for_their_ack() {
+        let mut early = EarlyUpdates::default();
+        let now = Instant::now();
+        let update = |n: u64| serde_json::json!({"type": "update/order_book", "n": n});
+
+        assert!(early.hold("order_book/0", update(1), now).is_none());
+        assert!(early.hold("order_book:1", update(2), now).is_none());
+        assert!(early.next_released().is_none());
+
+        early.acknowledge("order_book:0");
+        assert_eq!(early.next_released().unwrap().0, update(1));
+        assert!(early.next_released().is_none());
+        assert_eq!(early.hold("order_book:0", update(3), now), Some(update(3)));
+
+        for n in 0..EARLY_UPDATE_LIMIT as u64 + 3 {
+            early.hold("order_book:1", update(n), now);
+        }
+        assert_eq!(early.dropped(), 4);
+        early.reset();
+        assert_eq!(early.dropped(), 4 + EARLY_UPDATE_LIMIT as u64);
+        assert!(early.hold("order_book:0", update(4), now).is_none());
+    }
+
+    #[test]
+    fn test_server_error_shapes() {
+        // Captured from the server after subscribing to an unknown account
+        let captured =
+            r#"{"error":{"code":30003,"message":"Invalid Channel: account_all/999999"}}"#;
+        let error = WsServerError::from_message(&serde_json::from_str(captured).unwrap()).unwrap();
+        assert_eq!(error.code, Some(30003));
+        assert_eq!(error.message, "Invalid Channel: account_all/999999");
+        assert_eq!(error.channel, None);
+        assert_eq!(error.raw, captured);
+        assert!(format!("{:?}", error).contains("30003"));
+
+        let typed = WsServerError::from_message(&serde_json::json!({
+            "type": "error", "channel": "account_all/5", "message": "not found"
+        }))
+        .unwrap();
+        assert_eq!(typed.channel.as_deref(), Some("account_all:5"));
+        assert_eq!(typed.to_string(), "not found (account_all:5)");
+
+        let in_ack = WsServerError::from_message(&serde_json::json!({
+            "type": "subscribed/account_all", "channel": "account_all:5",
+            "code": 21100, "message": "account not found"
+        }))
+        .unwrap();
+        assert_eq!(
+            in_ack.to_string(),
+            "[21100] account not found (account_all:5)"
+        );
+
+        for ok in [
+            serde_json::json!({"type": "subscribed/account_all", "channel": "account_all:5"}),
+            serde_json::json!({"type": "subscribed/order_book", "channel": "order_book:0", "code": 0}),
+            serde_json::json!({"type": "connected"}),
+        ] {
+            assert!(WsServerError::from_message(&ok).is_none(), "{}", ok);
+        }
+    }
+}
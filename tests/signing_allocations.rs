@@ -0,0 +1,121 @@
+//! Allocation budgets of the signing hot path
+//!
+//! Counts heap allocations per signed order with a counting global
+//! allocator, so extra per-order `String`/`Vec` churn fails here instead of
+//! only slowing down `benches/signing.rs`. Counts are per thread, so tests
+//! running in parallel don't disturb each other.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use lighter_rs::client::{TxClient, TxRequest};
+use lighter_rs::signer::{PoseidonKeyManager, Signer};
+use lighter_rs::types::{
+    AccountIndex, ApiKeyIndex, ChainId, CreateOrderTxReq, OrderInfo, TransactOpts, TxInfo,
+};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const KEY_HEX: &str =
+    "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718";
+const CHAIN_ID: u32 = 304;
+const ROUNDS: usize = 100;
+
+/// Mean allocations of one call of `op`, after a warm-up call
+fn allocations_per_op<T>(mut op: impl FnMut() -> T) -> usize {
+    drop(op());
+    let before = ALLOCATIONS.with(Cell::get);
+    for _ in 0..ROUNDS {
+        drop(op());
+    }
+    (ALLOCATIONS.with(Cell::get) - before).div_ceil(ROUNDS)
+}
+
+fn request() -> CreateOrderTxReq {
+    CreateOrderTxReq {
+        market_index: 1,
+        client_order_index: 42,
+        base_amount: 15_000,
+        price: 300_000,
+        ..Default::default()
+    }
+}
+
+fn opts() -> TransactOpts {
+    TransactOpts {
+        from_account_index: Some(12345),
+        api_key_index: Some(0),
+        nonce: Some(1),
+        expired_at: 4_000_000_000_000,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_building_and_validating_an_order_does_not_allocate() {
+    let (req, opts) = (request(), opts());
+    let allocations = allocations_per_op(|| {
+        let info = OrderInfo::from(&req);
+        let tx = req.build_tx(&opts).unwrap();
+        tx.validate().unwrap();
+        (info, tx)
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn test_hash_and_sign_allocation_budget() {
+    let tx = request().build_tx(&opts()).unwrap();
+    assert!(allocations_per_op(|| tx.hash(CHAIN_ID).unwrap()) <= 1);
+
+    let key_manager = PoseidonKeyManager::from_hex(KEY_HEX).unwrap();
+    let hash = tx.hash(CHAIN_ID).unwrap();
+    assert!(allocations_per_op(|| key_manager.sign(&hash).unwrap()) <= 1);
+}
+
+#[test]
+fn test_create_order_allocation_budget() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let client = TxClient::from_ids(
+        "",
+        KEY_HEX,
+        AccountIndex::new(12345).unwrap(),
+        ApiKeyIndex::new(0).unwrap(),
+        ChainId::new(CHAIN_ID).unwrap(),
+    )
+    .unwrap();
+    let (req, opts) = (request(), opts());
+
+    let allocations = allocations_per_op(|| {
+        runtime
+            .block_on(client.create_order(&req, Some(opts.clone())))
+            .unwrap()
+    });
+    // The hash, the signature and the hash in hex
+    assert!(allocations <= 3, "{} allocations per order", allocations);
+}